@@ -0,0 +1,211 @@
+//! Further bars of the same process, e.g. a smaller bar on a second monitor, configured with
+//! `[[bar]]` sections:
+//!
+//! ```toml
+//! [[bar]]
+//! name = "secondary"
+//! blocks = ["time", "battery"]
+//! ```
+//!
+//! The process that runs the configuration prints the bar with all blocks on its stdout and
+//! serves every `[[bar]]` on a Unix socket in `$XDG_RUNTIME_DIR/i3status-rust/bars`. The
+//! `status_command` of the other bar is `i3status-rs --bar secondary`, which relays the status
+//! lines of the socket to its stdout and the clicks on its stdin to the socket. The bars share
+//! the blocks, so also their D-Bus connections and caches.
+
+use std::cell::RefCell;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::DirBuilderExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crossbeam_channel::{Sender, TrySendError};
+
+use crate::config::BarConfig;
+use crate::errors::*;
+use crate::ipc;
+use crate::protocol;
+use crate::protocol::i3bar_event::{read_events, I3BarEvent};
+
+/// Lines that are waiting for a client, e.g. while its bar is paused. Further lines are dropped,
+/// every line replaces the previous one anyway.
+const QUEUED_LINES: usize = 16;
+
+thread_local! {
+    // The bars are drawn on the main thread
+    static BARS: RefCell<Vec<Bar>> = const { RefCell::new(Vec::new()) };
+}
+
+struct Bar {
+    /// The ids of the blocks, in the order of the bar
+    ids: Vec<usize>,
+    clients: Arc<Mutex<Clients>>,
+}
+
+#[derive(Default)]
+struct Clients {
+    senders: Vec<Sender<Arc<str>>>,
+    /// Sent to new clients right away
+    last: Option<Arc<str>>,
+}
+
+fn socket_path(name: &str) -> PathBuf {
+    socket_path_in(&ipc::socket_dir(), name)
+}
+
+fn socket_path_in(dir: &Path, name: &str) -> PathBuf {
+    dir.join("bars").join(format!("{}.sock", name))
+}
+
+/// Starts to serve `bars`. The clicks on them are passed to `clicks`.
+pub fn listen(bars: &[BarConfig], clicks: &Sender<I3BarEvent>, debug: bool) -> Result<()> {
+    if bars.is_empty() {
+        return Ok(());
+    }
+    let dir = ipc::create_socket_dir()?;
+    for bar in bars {
+        let path = socket_path_in(&dir, &bar.name);
+        fs::DirBuilder::new()
+            .mode(0o700)
+            .recursive(true)
+            .create(path.parent().unwrap())
+            .internal_error("bars", "failed to create the socket directory")?;
+        if UnixStream::connect(&path).is_ok() {
+            return Err(ConfigurationError(
+                format!("the bar '{}' is served by another i3status-rs", bar.name),
+                path.display().to_string(),
+            ));
+        }
+        // Left over by a process that didn't exit cleanly, or by this process before it restarted
+        let _ = fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).internal_error("bars", "failed to bind socket")?;
+
+        let clients = Arc::new(Mutex::new(Clients::default()));
+        BARS.with(|bars| {
+            bars.borrow_mut().push(Bar {
+                ids: Vec::new(),
+                clients: clients.clone(),
+            })
+        });
+        let clicks = clicks.clone();
+        thread::Builder::new()
+            .name(format!("bar {}", bar.name))
+            .spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    // A broken client must not take the listener down
+                    let _ = accept(stream, &clients, &clicks, debug);
+                }
+            })
+            .internal_error("bars", "failed to start thread")?;
+    }
+    Ok(())
+}
+
+fn accept(
+    stream: UnixStream,
+    clients: &Mutex<Clients>,
+    clicks: &Sender<I3BarEvent>,
+    debug: bool,
+) -> io::Result<()> {
+    let input = BufReader::new(stream.try_clone()?);
+    let clicks = clicks.clone();
+    thread::Builder::new()
+        .name("bar input".into())
+        .spawn(move || read_events(input, &clicks, debug))?;
+
+    // Written by a thread of the client, so a client that doesn't read can't block the main loop
+    let (sender, lines) = crossbeam_channel::bounded::<Arc<str>>(QUEUED_LINES);
+    let mut clients = clients.lock().unwrap();
+    if let Some(last) = &clients.last {
+        let _ = sender.try_send(last.clone());
+    }
+    clients.senders.push(sender);
+    let mut stream = stream;
+    thread::Builder::new()
+        .name("bar output".into())
+        .spawn(move || {
+            for line in lines {
+                if writeln!(stream, "{}", line).is_err() {
+                    return;
+                }
+            }
+        })?;
+    Ok(())
+}
+
+/// Sets the ids of the blocks of the bars, in the order of the configuration
+pub fn set_blocks(ids: Vec<Vec<usize>>) {
+    BARS.with(|bars| {
+        for (bar, ids) in bars.borrow_mut().iter_mut().zip(ids) {
+            bar.ids = ids;
+        }
+    });
+}
+
+/// Sends the status line that `render` makes of the ids of the blocks to each bar
+pub fn send(mut render: impl FnMut(&[usize]) -> String) {
+    BARS.with(|bars| {
+        for bar in bars.borrow().iter() {
+            let line: Arc<str> = render(&bar.ids).into();
+            let mut clients = bar.clients.lock().unwrap();
+            if clients.last.as_deref() == Some(&*line) {
+                continue;
+            }
+            clients.senders.retain(|sender| {
+                !matches!(
+                    sender.try_send(line.clone()),
+                    Err(TrySendError::Disconnected(_))
+                )
+            });
+            clients.last = Some(line);
+        }
+    });
+}
+
+/// Relays the bar `name` to stdout and the clicks on stdin to the bar, for `--bar`. The bar is
+/// connected to again when the process that serves it restarts.
+pub fn relay(name: &str) -> Result<()> {
+    let path = socket_path(name);
+    let connection: Arc<Mutex<Option<UnixStream>>> = Arc::new(Mutex::new(None));
+
+    let clicks = connection.clone();
+    thread::Builder::new()
+        .name("input".into())
+        .spawn(move || {
+            let stdin = io::stdin();
+            for line in stdin.lock().lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    // i3bar is gone
+                    Err(_) => break,
+                };
+                if let Some(stream) = &mut *clicks.lock().unwrap() {
+                    let _ = writeln!(stream, "{}", line);
+                }
+            }
+            std::process::exit(0);
+        })
+        .internal_error("bars", "failed to start thread")?;
+
+    loop {
+        let stream = match UnixStream::connect(&path) {
+            Ok(stream) => stream,
+            Err(_) => {
+                thread::sleep(Duration::from_secs(1));
+                continue;
+            }
+        };
+        *connection.lock().unwrap() = stream.try_clone().ok();
+        for line in BufReader::new(stream).lines() {
+            match line {
+                Ok(line) => protocol::write_line(&line),
+                Err(_) => break,
+            }
+        }
+        *connection.lock().unwrap() = None;
+    }
+}
@@ -1,27 +1,41 @@
+pub mod aggregate;
+pub mod ambient_light;
 pub mod apt;
 pub mod backlight;
 pub mod base_block;
 pub mod battery;
+pub mod bitwarden;
 pub mod bluetooth;
+pub mod containers;
 pub mod cpu;
 pub mod custom;
 pub mod custom_dbus;
 pub mod disk_space;
 pub mod dnf;
+pub mod dock;
 pub mod docker;
 pub mod external_ip;
 pub mod focused_window;
 pub mod github;
+pub mod gpsd;
+pub mod gpu;
+pub mod guest_agent;
 pub mod hueshift;
+pub mod hugepages;
 pub mod ibus;
+pub mod inbox;
 pub mod kdeconnect;
 pub mod keyboard_layout;
+pub mod kubernetes;
+pub mod libvirt;
+pub mod live_streams;
 pub mod load;
 #[cfg(feature = "maildir")]
 pub mod maildir;
 pub mod memory;
 pub mod music;
 pub mod net;
+pub mod network_mounts;
 pub mod networkmanager;
 pub mod notify;
 #[cfg(feature = "notmuch")]
@@ -29,43 +43,70 @@ pub mod notmuch;
 pub mod nvidia_gpu;
 pub mod pacman;
 pub mod pomodoro;
+pub mod profile;
+pub mod proton;
+pub mod reminders;
+pub mod remote;
+pub mod remote_host;
+pub mod rig;
 pub mod rofication;
+pub mod snapcast;
+pub mod social_inbox;
 pub mod sound;
+pub mod sound_filter;
 pub mod speedtest;
+pub mod ssh_sessions;
+pub mod steam;
 pub mod taskwarrior;
 pub mod temperature;
 pub mod template;
 pub mod time;
 pub mod toggle;
+pub mod touchpad;
 pub mod uptime;
+pub mod wakeups;
 pub mod watson;
 pub mod weather;
 pub mod xrandr;
 
+use self::aggregate::*;
+use self::ambient_light::*;
 use self::apt::*;
 use self::backlight::*;
 use self::base_block::*;
 use self::battery::*;
+use self::bitwarden::*;
 use self::bluetooth::*;
+use self::containers::*;
 use self::cpu::*;
 use self::custom::*;
 use self::custom_dbus::*;
 use self::disk_space::*;
 use self::dnf::*;
+use self::dock::*;
 use self::docker::*;
 use self::external_ip::*;
 use self::focused_window::*;
 use self::github::*;
+use self::gpsd::*;
+use self::gpu::*;
+use self::guest_agent::*;
 use self::hueshift::*;
+use self::hugepages::*;
 use self::ibus::*;
+use self::inbox::*;
 use self::kdeconnect::*;
 use self::keyboard_layout::*;
+use self::kubernetes::*;
+use self::libvirt::*;
+use self::live_streams::*;
 use self::load::*;
 #[cfg(feature = "maildir")]
 use self::maildir::*;
 use self::memory::*;
 use self::music::*;
 use self::net::*;
+use self::network_mounts::*;
 use self::networkmanager::*;
 use self::notify::*;
 #[cfg(feature = "notmuch")]
@@ -73,30 +114,52 @@ use self::notmuch::*;
 use self::nvidia_gpu::*;
 use self::pacman::*;
 use self::pomodoro::*;
+use self::profile::*;
+use self::proton::*;
+use self::reminders::*;
+use self::remote::*;
+use self::remote_host::*;
+use self::rig::*;
 use self::rofication::*;
+use self::snapcast::*;
+use self::social_inbox::*;
 use self::sound::*;
+use self::sound_filter::*;
 use self::speedtest::*;
+use self::ssh_sessions::*;
+use self::steam::*;
 use self::taskwarrior::*;
 use self::temperature::*;
 use self::template::*;
 use self::time::*;
 use self::toggle::*;
+use self::touchpad::*;
 use self::uptime::*;
+use self::wakeups::*;
 use self::watson::*;
 use self::weather::*;
 use self::xrandr::*;
 
-use std::time::Duration;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 use crossbeam_channel::Sender;
 use serde::de::Deserialize;
 use toml::value::Value;
 
+use crate::click::ScrollAcceleration;
 use crate::config::SharedConfig;
 use crate::errors::*;
-use crate::protocol::i3bar_event::I3BarEvent;
+use crate::formatting::FormatTemplate;
+use crate::polling;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::registry;
 use crate::scheduler::Task;
-use crate::widgets::I3BarWidget;
+use crate::schema;
+use crate::services::Service;
+use crate::signals;
+use crate::subprocess;
+use crate::widgets::{text::TextWidget, I3BarWidget, State};
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Update {
@@ -143,6 +206,24 @@ pub trait ConfigBlock: Block {
     fn override_on_click(&mut self) -> Option<&mut Option<String>> {
         None
     }
+
+    /// The services the block needs unless `depends_on` is set, e.g. the network for blocks which
+    /// fetch something from the internet
+    fn default_depends_on() -> &'static [Service]
+    where
+        Self: Sized,
+    {
+        &[]
+    }
+
+    /// Whether the block is updated right after a resume from suspend or a jump of the clock
+    /// unless `update_on_resume` is set, e.g. because it shows the time
+    fn default_update_on_resume() -> bool
+    where
+        Self: Sized,
+    {
+        false
+    }
 }
 
 /// The Block trait is used to interact with a block after it has been instantiated from ConfigBlock
@@ -167,6 +248,13 @@ pub trait Block {
         Ok(None)
     }
 
+    /// Called instead of `update` while a service in `depends_on` is not ready. Returns whether
+    /// the block has something to show anyway, like the last data it fetched, otherwise it's
+    /// hidden.
+    fn update_offline(&mut self) -> Result<bool> {
+        Ok(false)
+    }
+
     /// Sends a signal event with the provided signal, this function is called on every block
     /// for every signal event
     fn signal(&mut self, _signal: i32) -> Result<()> {
@@ -181,12 +269,87 @@ pub trait Block {
     ///
     /// If block uses more that one widget, use the event.instance property to determine which widget was clicked.
     fn click(&mut self, _event: &I3BarEvent) -> Result<()> {
+        // Passed to `on_click_fallthrough`
+        crate::click::set_unhandled();
+        Ok(())
+    }
+
+    /// The name of the widget with the instance id `instance`, so click bindings can be limited to
+    /// it. Blocks with several widgets should name them, e.g. `prev`, `title` and `next`.
+    fn segment(&self, _instance: usize) -> Option<&'static str> {
+        None
+    }
+
+    /// Whether an update that the block requested has to wait because of `min_update_interval`.
+    /// The update is requested again once the interval has passed.
+    fn throttle(&mut self) -> bool {
+        false
+    }
+
+    /// When `timeout` has to be called, e.g. to collapse the block after it was expanded by a click
+    fn deadline(&self) -> Option<Instant> {
+        None
+    }
+
+    /// Called once the deadline has passed
+    fn timeout(&mut self) -> Result<()> {
         Ok(())
     }
+
+    /// Runs a named action, e.g. requested with `i3status-rs action`. Returns `false` if the
+    /// block doesn't have an action called `action`.
+    fn action(&mut self, _action: &str) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// Adds a reminder from `i3status-rs remind`. Returns `false` if the block doesn't keep
+    /// reminders.
+    fn remind(&mut self, _reminder: &Reminder) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// Whether clicks with `button` have to be held back to detect double clicks, because the
+    /// block handles the double click variant of it (e.g. `MouseButton::DoubleLeft`).
+    fn wants_double_click(&self, _button: MouseButton) -> bool {
+        false
+    }
+
+    /// Whether presses of `button` have to be held back until the release, because the block
+    /// handles the hold variant of it (e.g. `MouseButton::HoldLeft`).
+    fn wants_hold(&self, _button: MouseButton) -> bool {
+        false
+    }
+
+    /// The services the block needs, see `services::watch`
+    fn depends_on(&self) -> &[Service] {
+        &[]
+    }
+
+    /// Whether the block is updated after a resume or a jump of the clock, see `resume::watch`
+    fn updates_on_resume(&self) -> bool {
+        false
+    }
+
+    /// Whether the block didn't update for longer than its watchdog allows, e.g. because the
+    /// thread that requests its updates waits for a stream that is gone. Stuck blocks are
+    /// created again.
+    fn stuck(&self, _now: Instant) -> bool {
+        false
+    }
+
+    /// Hides the block, e.g. with `i3status-rs hide`. Hidden blocks are still updated.
+    fn set_hidden(&mut self, _hidden: bool) {}
+
+    fn is_hidden(&self) -> bool {
+        false
+    }
+
+    /// Shows `text` instead of the widgets of the block, or the widgets again if it's `None`
+    fn set_text_override(&mut self, _text: Option<String>) {}
 }
 
 macro_rules! block {
-    ($block_type:ident, $id:expr, $block_config:expr, $shared_config:expr, $update_request:expr) => {{
+    ($block_type:ident, $name:expr, $id:expr, $block_config:expr, $shared_config:expr, $update_request:expr) => {{
         // Extract base(common) config
         let common_config = BaseBlockConfig::extract(&mut $block_config);
         let mut common_config = BaseBlockConfig::deserialize(common_config)
@@ -199,12 +362,91 @@ macro_rules! block {
         if let Some(overrides) = common_config.icons_format {
             $shared_config.icons_format_override(overrides);
         }
+        if let Some(ref name) = common_config.export {
+            registry::set_name($id, name)?;
+        }
+        // The commands and the popup can have the placeholders of the block
+        if common_config.on_click.is_some()
+            || !common_config.click.is_empty()
+            || common_config.popup.is_some()
+        {
+            registry::record_values($id);
+        }
+        if let Some(sensitive) = common_config.sensitive.take() {
+            registry::set_sensitive($id, sensitive);
+        }
+        if !common_config.rules.is_empty() {
+            let mut rules = Vec::new();
+            for rule in std::mem::take(&mut common_config.rules) {
+                let effect = crate::rules::Effect {
+                    state: rule.state,
+                    icon: rule
+                        .icon
+                        .as_deref()
+                        .map(|icon| $shared_config.get_icon(icon))
+                        .transpose()?,
+                    hide: rule.hide,
+                };
+                rules.push((rule, effect));
+            }
+            crate::rules::set($id, rules);
+        }
+        let tx_update_request = $update_request.clone();
+        // Shown by click bindings with `confirm`
+        let confirm_prompt = TextWidget::new($id, 0, $shared_config.clone())
+            .with_text("click again to confirm")
+            .with_state(State::Warning);
+        let text_override = TextWidget::new($id, 0, $shared_config.clone());
+        let error = TextWidget::new($id, 0, $shared_config.clone()).with_state(State::Critical);
+        let error_format = match common_config.error_format.take() {
+            Some(format) => format,
+            None => FormatTemplate::new(base_block::DEFAULT_ERROR_FORMAT, None)?,
+        };
+        let loading = match common_config
+            .loading_text
+            .take()
+            .or_else(|| $shared_config.loading_text.clone())
+        {
+            Some(text) if !text.is_empty() => {
+                Some(TextWidget::new($id, 0, $shared_config.clone()).with_text(&text))
+            }
+            _ => None,
+        };
+        let init_timeout = common_config.init_timeout.or($shared_config.init_timeout);
+        let env = match common_config.env {
+            Some(ref env) => Rc::new(subprocess::resolve_env(env)?),
+            None => Rc::new(Vec::new()),
+        };
 
         // Extract block-specific config
         let block_config = <$block_type as ConfigBlock>::Config::deserialize($block_config)
             .configuration_error("Failed to deserialize block config.")?;
 
-        let mut block = $block_type::new($id, block_config, $shared_config, $update_request)?;
+        let mut block = subprocess::with_env(&env, || {
+            registry::with_block($id, || {
+                $block_type::new($id, block_config, $shared_config, $update_request)
+            })
+        })?;
+        let depends_on = common_config
+            .depends_on
+            .unwrap_or_else(|| $block_type::default_depends_on().to_vec());
+        if let Some(schedule) = &common_config.schedule {
+            if schedule.next(chrono::Local::now().naive_local()).is_none() {
+                return Err(ConfigurationError(
+                    "schedule".to_string(),
+                    "the schedule never matches".to_string(),
+                ));
+            }
+        }
+        if let Some(format) = common_config.format_expanded {
+            registry::set_expanded_format($id, format);
+        }
+        if common_config.interval_hidden.is_some() {
+            polling::watch_bar()?;
+        }
+        if common_config.interval_on_battery.is_some() {
+            polling::watch_battery()?;
+        }
         if let Some(overrided) = block.override_on_click() {
             *overrided = common_config.on_click.take();
         }
@@ -213,79 +455,169 @@ macro_rules! block {
             name: stringify!($block_type).to_string(),
             inner: block,
             on_click: common_config.on_click,
+            click: common_config.click,
+            popup: common_config.popup,
+            scroll: ScrollAcceleration::new(
+                common_config.scroll_step.unwrap_or(1.0),
+                common_config.scroll_accel.unwrap_or(0.0),
+            ),
+            expand_duration: match common_config.expand_duration {
+                Some(d) if d.is_zero() => None,
+                Some(d) => Some(d),
+                None => Some(Duration::from_secs(5)),
+            },
+            expanded_until: None,
+            confirming: None,
+            confirm_prompt,
+            min_update_interval: common_config.min_update_interval,
+            init_timeout,
+            loading,
+            interval_hidden: common_config.interval_hidden,
+            interval_on_battery: common_config.interval_on_battery,
+            last_update: None,
+            deferred_update: None,
+            tx_update_request,
+            offline: !depends_on.is_empty(),
+            shown_offline: false,
+            depends_on,
+            env,
+            hidden: common_config.hidden_by_default,
+            signal: common_config
+                .signal
+                .map(signals::convert_to_valid_signal)
+                .transpose()?,
+            toggle_on_signal: common_config.hidden_by_default,
+            text_override,
+            overridden: false,
+            block_type: $name,
+            error,
+            error_format,
+            error_retry_interval: common_config
+                .error_retry_interval
+                .unwrap_or(Duration::from_secs(5)),
+            max_retries: common_config.max_retries,
+            error_fullscreen: common_config.error_fullscreen,
+            update_on_resume: common_config
+                .update_on_resume
+                .unwrap_or_else($block_type::default_update_on_resume),
+            watchdog: common_config.watchdog,
+            schedule: common_config.schedule,
+            active_hours: common_config.active_hours,
+            inactive: false,
+            failures: 0,
         }) as Box<dyn Block>)
     }};
 }
 
-pub fn create_block(
-    id: usize,
-    name: &str,
-    mut block_config: Value,
-    mut shared_config: SharedConfig,
-    update_request: Sender<Task>,
-) -> Result<Box<dyn Block>> {
-    match name {
-        // Please keep these in alphabetical order.
-        "apt" => block!(Apt, id, block_config, shared_config, update_request),
-        "backlight" => block!(Backlight, id, block_config, shared_config, update_request),
-        "battery" => block!(Battery, id, block_config, shared_config, update_request),
-        "bluetooth" => block!(Bluetooth, id, block_config, shared_config, update_request),
-        "cpu" => block!(Cpu, id, block_config, shared_config, update_request),
-        "custom" => block!(Custom, id, block_config, shared_config, update_request),
-        "custom_dbus" => block!(CustomDBus, id, block_config, shared_config, update_request),
-        "disk_space" => block!(DiskSpace, id, block_config, shared_config, update_request),
-        "dnf" => block!(Dnf, id, block_config, shared_config, update_request),
-        "docker" => block!(Docker, id, block_config, shared_config, update_request), ///////
-        "external_ip" => block!(ExternalIP, id, block_config, shared_config, update_request),
-        "focused_window" => block!(
-            FocusedWindow,
-            id,
-            block_config,
-            shared_config,
-            update_request
-        ),
-        "github" => block!(Github, id, block_config, shared_config, update_request),
-        "hueshift" => block!(Hueshift, id, block_config, shared_config, update_request),
-        "ibus" => block!(IBus, id, block_config, shared_config, update_request),
-        "kdeconnect" => block!(KDEConnect, id, block_config, shared_config, update_request),
-        "keyboard_layout" => block!(
-            KeyboardLayout,
-            id,
-            block_config,
-            shared_config,
-            update_request
-        ),
-        "load" => block!(Load, id, block_config, shared_config, update_request),
-        #[cfg(feature = "maildir")]
-        "maildir" => block!(Maildir, id, block_config, shared_config, update_request),
-        "memory" => block!(Memory, id, block_config, shared_config, update_request),
-        "music" => block!(Music, id, block_config, shared_config, update_request),
-        "net" => block!(Net, id, block_config, shared_config, update_request),
-        "networkmanager" => block!(
-            NetworkManager,
-            id,
-            block_config,
-            shared_config,
-            update_request
-        ),
-        "notify" => block!(Notify, id, block_config, shared_config, update_request),
-        #[cfg(feature = "notmuch")]
-        "notmuch" => block!(Notmuch, id, block_config, shared_config, update_request),
-        "nvidia_gpu" => block!(NvidiaGpu, id, block_config, shared_config, update_request),
-        "pacman" => block!(Pacman, id, block_config, shared_config, update_request),
-        "pomodoro" => block!(Pomodoro, id, block_config, shared_config, update_request),
-        "rofication" => block!(Rofication, id, block_config, shared_config, update_request),
-        "sound" => block!(Sound, id, block_config, shared_config, update_request),
-        "speedtest" => block!(SpeedTest, id, block_config, shared_config, update_request),
-        "taskwarrior" => block!(Taskwarrior, id, block_config, shared_config, update_request),
-        "temperature" => block!(Temperature, id, block_config, shared_config, update_request),
-        "template" => block!(Template, id, block_config, shared_config, update_request),
-        "time" => block!(Time, id, block_config, shared_config, update_request), /////////
-        "toggle" => block!(Toggle, id, block_config, shared_config, update_request),
-        "uptime" => block!(Uptime, id, block_config, shared_config, update_request),
-        "watson" => block!(Watson, id, block_config, shared_config, update_request),
-        "weather" => block!(Weather, id, block_config, shared_config, update_request),
-        "xrandr" => block!(Xrandr, id, block_config, shared_config, update_request),
-        other => Err(BlockError(other.to_string(), "Unknown block!".to_string())),
-    }
+/// The position of the block `id` on the bar. The ids of the blocks stay the same when the
+/// configuration is reloaded, their positions don't.
+pub fn position(blocks: &[Box<dyn Block>], id: usize) -> Option<usize> {
+    blocks.iter().position(|block| block.id() == id)
+}
+
+/// The JSON Schema of the options all blocks have
+pub fn common_schema() -> serde_json::Value {
+    schema::trace::<BaseBlockConfig>()
+}
+
+/// Defines `create_block` and `block_schemas` with the names and types of all blocks
+macro_rules! blocks {
+    ($($(#[$attr:meta])* $name:literal => $block_type:ident,)*) => {
+        pub fn create_block(
+            id: usize,
+            name: &str,
+            mut block_config: Value,
+            mut shared_config: SharedConfig,
+            update_request: Sender<Task>,
+        ) -> Result<Box<dyn Block>> {
+            match name {
+                $(
+                    $(#[$attr])*
+                    $name => block!($block_type, $name, id, block_config, shared_config, update_request),
+                )*
+                other => Err(BlockError(other.to_string(), "Unknown block!".to_string())),
+            }
+        }
+
+        /// The names of the blocks with the JSON Schema of their own options
+        pub fn block_schemas() -> Vec<(&'static str, serde_json::Value)> {
+            vec![$(
+                $(#[$attr])*
+                ($name, schema::trace::<<$block_type as ConfigBlock>::Config>()),
+            )*]
+        }
+    };
+}
+
+blocks! {
+    // Please keep these in alphabetical order.
+    "aggregate" => Aggregate,
+    "ambient_light" => AmbientLight,
+    "apt" => Apt,
+    "backlight" => Backlight,
+    "battery" => Battery,
+    "bitwarden" => Bitwarden,
+    "bluetooth" => Bluetooth,
+    "containers" => Containers,
+    "cpu" => Cpu,
+    "custom" => Custom,
+    "custom_dbus" => CustomDBus,
+    "disk_space" => DiskSpace,
+    "dnf" => Dnf,
+    "dock" => Dock,
+    "docker" => Docker,
+    "external_ip" => ExternalIP,
+    "focused_window" => FocusedWindow,
+    "github" => Github,
+    "gpsd" => Gpsd,
+    "gpu" => Gpu,
+    "guest_agent" => GuestAgent,
+    "hueshift" => Hueshift,
+    "hugepages" => Hugepages,
+    "ibus" => IBus,
+    "inbox" => Inbox,
+    "kdeconnect" => KDEConnect,
+    "keyboard_layout" => KeyboardLayout,
+    "kubernetes" => Kubernetes,
+    "libvirt" => Libvirt,
+    "live_streams" => LiveStreams,
+    "load" => Load,
+    #[cfg(feature = "maildir")]
+    "maildir" => Maildir,
+    "memory" => Memory,
+    "music" => Music,
+    "net" => Net,
+    "network_mounts" => NetworkMounts,
+    "networkmanager" => NetworkManager,
+    "notify" => Notify,
+    #[cfg(feature = "notmuch")]
+    "notmuch" => Notmuch,
+    "nvidia_gpu" => NvidiaGpu,
+    "pacman" => Pacman,
+    "pomodoro" => Pomodoro,
+    "profile" => Profile,
+    "proton" => Proton,
+    "reminders" => Reminders,
+    "remote" => Remote,
+    "remote_host" => RemoteHost,
+    "rig" => Rig,
+    "rofication" => Rofication,
+    "snapcast" => Snapcast,
+    "social_inbox" => SocialInbox,
+    "sound" => Sound,
+    "sound_filter" => SoundFilter,
+    "speedtest" => SpeedTest,
+    "ssh_sessions" => SshSessions,
+    "steam" => Steam,
+    "taskwarrior" => Taskwarrior,
+    "temperature" => Temperature,
+    "template" => Template,
+    "time" => Time,
+    "toggle" => Toggle,
+    "touchpad" => Touchpad,
+    "uptime" => Uptime,
+    "wakeups" => Wakeups,
+    "watson" => Watson,
+    "weather" => Weather,
+    "xrandr" => Xrandr,
 }
@@ -0,0 +1,131 @@
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::registry;
+use crate::scheduler::Task;
+use crate::widgets::{text::TextWidget, I3BarWidget, State};
+
+const BLOCK_NAME: &str = "aggregate";
+
+pub struct Aggregate {
+    id: usize,
+    text: TextWidget,
+    update_interval: Duration,
+    format: FormatTemplate,
+    /// Pairs of the exported block name and the placeholder
+    values: Vec<(String, String)>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AggregateConfig {
+    #[serde(
+        default = "AggregateConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    #[serde(default)]
+    pub format: FormatTemplate,
+
+    /// Values of other blocks in the form `<export>.<placeholder>`
+    pub values: Vec<String>,
+}
+
+impl AggregateConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(5)
+    }
+}
+
+impl ConfigBlock for Aggregate {
+    type Config = AggregateConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let values = block_config
+            .values
+            .iter()
+            .map(|value| match value.split_once('.') {
+                Some((block, placeholder)) => Ok((block.to_string(), placeholder.to_string())),
+                None => Err(ConfigurationError(
+                    BLOCK_NAME.to_string(),
+                    format!(
+                        "invalid value '{}', expected the form '<export>.<placeholder>'",
+                        value
+                    ),
+                )),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if values.is_empty() {
+            return Err(ConfigurationError(
+                BLOCK_NAME.to_string(),
+                "'values' must not be empty".to_string(),
+            ));
+        }
+
+        Ok(Aggregate {
+            id,
+            text: TextWidget::new(id, 0, shared_config),
+            update_interval: block_config.interval,
+            format: block_config.format.with_default("{max}")?,
+            values,
+        })
+    }
+}
+
+impl Block for Aggregate {
+    fn update(&mut self) -> Result<Option<Update>> {
+        // Blocks which didn't export the value yet (e.g. because they were not updated yet) are
+        // left out
+        let values: Vec<Value> = self
+            .values
+            .iter()
+            .filter_map(|(block, placeholder)| registry::get_by_name(block, placeholder))
+            .collect();
+        let numbers: Vec<f64> = values.iter().filter_map(Value::as_f64).collect();
+
+        if numbers.is_empty() {
+            self.text.set_text("-".to_string());
+            self.text.set_state(State::Idle);
+            return Ok(Some(self.update_interval.into()));
+        }
+
+        // The results keep the unit of the first value, so e.g. speeds are still shown in bytes
+        let first = &values[0];
+        let sum: f64 = numbers.iter().sum();
+        let min = numbers.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = numbers.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let values = map!(
+            "min" => first.with_number(min),
+            "max" => first.with_number(max),
+            "avg" => first.with_number(sum / numbers.len() as f64),
+            "sum" => first.with_number(sum),
+            "count" => Value::from_integer(numbers.len() as i64),
+        );
+        self.text.set_format(&self.format, &values)?;
+        self.text.set_state(State::Idle);
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
@@ -0,0 +1,297 @@
+//! A block for the illuminance of an ambient light sensor of the Industrial I/O subsystem, which
+//! can also adjust the backlight to it.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::backlight::BacklitDevice;
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::util::read_file;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+const IIO_DEVICES: &str = "/sys/bus/iio/devices";
+
+/// The channels of light sensors, with the illuminance in lux (`input`) or as raw values
+const CHANNELS: &[&str] = &["in_illuminance", "in_illuminance0"];
+
+/// Differences of the brightness up to this many percent are left alone, so the backlight
+/// doesn't flicker with the noise of the sensor and rounding
+const TOLERANCE: f64 = 2.0;
+
+struct Sensor {
+    /// The directory of the device, e.g. `/sys/bus/iio/devices/iio:device0`
+    path: PathBuf,
+    /// e.g. `in_illuminance`
+    channel: String,
+}
+
+impl Sensor {
+    /// The sensor with the directory or the `name` `device`, or the first light sensor
+    fn find(device: Option<&str>) -> Result<Self> {
+        let entries = Path::new(IIO_DEVICES)
+            .read_dir()
+            .block_error("ambient_light", "failed to read the Industrial I/O devices")?;
+        let mut paths: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+        paths.sort();
+        for path in paths {
+            if let Some(device) = device {
+                let name = read_file("ambient_light", &path.join("name")).unwrap_or_default();
+                if path.file_name().and_then(|x| x.to_str()) != Some(device) && name != device {
+                    continue;
+                }
+            }
+            let channel = CHANNELS.iter().find(|channel| {
+                ["input", "raw"]
+                    .iter()
+                    .any(|kind| path.join(format!("{}_{}", channel, kind)).exists())
+            });
+            if let Some(channel) = channel {
+                return Ok(Sensor {
+                    path,
+                    channel: channel.to_string(),
+                });
+            }
+        }
+        Err(BlockError(
+            "ambient_light".to_string(),
+            match device {
+                Some(device) => format!("'{}' is not an ambient light sensor", device),
+                None => "no ambient light sensor found".to_string(),
+            },
+        ))
+    }
+
+    fn value(&self, kind: &str) -> Option<Result<f64>> {
+        let path = self.path.join(format!("{}_{}", self.channel, kind));
+        if !path.exists() {
+            return None;
+        }
+        Some(read_file("ambient_light", &path).and_then(|value| {
+            value.trim().parse().block_error(
+                "ambient_light",
+                &format!("invalid value in '{}'", path.display()),
+            )
+        }))
+    }
+
+    /// The illuminance in lux
+    fn lux(&self) -> Result<f64> {
+        if let Some(lux) = self.value("input") {
+            return lux;
+        }
+        let raw = self
+            .value("raw")
+            .block_error("ambient_light", "the sensor is gone")??;
+        let offset = self.value("offset").transpose()?.unwrap_or(0.0);
+        let scale = self.value("scale").transpose()?.unwrap_or(1.0);
+        Ok((raw + offset) * scale)
+    }
+}
+
+pub struct AmbientLight {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    update_interval: Duration,
+    sensor: Sensor,
+    /// `None` if there is no backlight, so there is no automatic mode
+    backlight: Option<BacklitDevice>,
+    curve: Vec<(f64, f64)>,
+    automatic: bool,
+    /// The brightness that was set the last time, to notice changes of others
+    last_brightness: Option<u64>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct AmbientLightConfig {
+    /// The sensor, e.g. `iio:device0` or a name like `acpi-als`, the first light sensor by default
+    pub device: Option<String>,
+
+    /// Whether the backlight follows the illuminance when the bar starts
+    pub auto_backlight: bool,
+
+    /// The backlit device of `/sys/class/backlight`, the first one by default
+    pub backlight_device: Option<String>,
+
+    /// Pairs of the illuminance in lux and the brightness in percent, in the order of the lux.
+    /// The brightness between two of them is interpolated.
+    pub curve: Vec<(f64, f64)>,
+
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    pub format: FormatTemplate,
+}
+
+impl Default for AmbientLightConfig {
+    fn default() -> Self {
+        Self {
+            device: None,
+            auto_backlight: false,
+            backlight_device: None,
+            curve: vec![
+                (0.0, 5.0),
+                (10.0, 20.0),
+                (100.0, 40.0),
+                (1000.0, 70.0),
+                (10000.0, 100.0),
+            ],
+            interval: Duration::from_secs(2),
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+/// The brightness for `lux` on the `curve`
+fn curve_brightness(curve: &[(f64, f64)], lux: f64) -> f64 {
+    let after = curve.iter().position(|&(point, _)| point > lux);
+    match after {
+        Some(0) => curve[0].1,
+        Some(after) => {
+            let (lux_0, brightness_0) = curve[after - 1];
+            let (lux_1, brightness_1) = curve[after];
+            brightness_0 + (brightness_1 - brightness_0) * (lux - lux_0) / (lux_1 - lux_0)
+        }
+        None => curve[curve.len() - 1].1,
+    }
+}
+
+impl ConfigBlock for AmbientLight {
+    type Config = AmbientLightConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let curve = block_config.curve;
+        if curve.is_empty() || curve.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+            return Err(BlockError(
+                "ambient_light".to_string(),
+                "'curve' must be pairs of lux and percent, in increasing order of the lux"
+                    .to_string(),
+            ));
+        }
+        let backlight = match &block_config.backlight_device {
+            Some(device) => BacklitDevice::from_device(device.clone(), 1.0),
+            None => BacklitDevice::default(1.0),
+        };
+        // Without the automatic mode, the backlight is only needed for clicks
+        let backlight = match block_config.auto_backlight {
+            true => Some(backlight?),
+            false => backlight.ok(),
+        };
+        Ok(AmbientLight {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("ambient_light")?,
+            format: block_config.format.with_default("{lux}")?,
+            update_interval: block_config.interval,
+            sensor: Sensor::find(block_config.device.as_deref())?,
+            backlight,
+            curve,
+            automatic: block_config.auto_backlight,
+            last_brightness: None,
+        })
+    }
+}
+
+impl AmbientLight {
+    /// Sets the brightness of the curve, and switches to the manual mode if something else
+    /// changed the brightness since the last time
+    fn adjust_backlight(&mut self, lux: f64) -> Result<()> {
+        let backlight = match &self.backlight {
+            Some(backlight) => backlight,
+            None => return Ok(()),
+        };
+        let brightness = backlight.brightness()?;
+        if let Some(last) = self.last_brightness {
+            if (brightness as f64 - last as f64).abs() > TOLERANCE {
+                self.automatic = false;
+                self.last_brightness = None;
+                return Ok(());
+            }
+        }
+        let target = curve_brightness(&self.curve, lux).clamp(0.0, 100.0);
+        if (target - brightness as f64).abs() > TOLERANCE {
+            backlight.set_brightness(target.round() as u64)?;
+            self.last_brightness = Some(target.round() as u64);
+        } else {
+            self.last_brightness = Some(brightness);
+        }
+        Ok(())
+    }
+}
+
+impl Block for AmbientLight {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let lux = self.sensor.lux()?;
+        if self.automatic {
+            self.adjust_backlight(lux)?;
+        }
+        let brightness = match &self.backlight {
+            Some(backlight) => Value::from_integer(backlight.brightness()? as i64).percents(),
+            None => Value::from_string(String::new()),
+        };
+        let values = map!(
+            "lux" => Value::from_float(lux),
+            "brightness" => brightness,
+            "mode" => Value::from_string(if self.automatic { "auto" } else { "manual" }.to_string()),
+        );
+        self.text.set_state(if self.automatic {
+            State::Info
+        } else {
+            State::Idle
+        });
+        self.text.set_format(&self.format, &values)?;
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        match (event.button, &self.backlight) {
+            (MouseButton::Left, Some(_)) => {
+                self.automatic = !self.automatic;
+                self.last_brightness = None;
+                self.update()?;
+            }
+            _ => crate::click::set_unhandled(),
+        }
+        Ok(())
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curve() {
+        let curve = [(0.0, 5.0), (10.0, 20.0), (100.0, 40.0)];
+        assert_eq!(curve_brightness(&curve, 0.0), 5.0);
+        assert_eq!(curve_brightness(&curve, 5.0), 12.5);
+        assert_eq!(curve_brightness(&curve, 55.0), 30.0);
+        assert_eq!(curve_brightness(&curve, 5000.0), 40.0);
+        assert_eq!(curve_brightness(&[(50.0, 30.0)], 10.0), 30.0);
+    }
+}
@@ -1,7 +1,6 @@
 use std::env;
 use std::fs;
 use std::io::Write;
-use std::process::Command;
 use std::time::Duration;
 
 use crossbeam_channel::Sender;
@@ -16,6 +15,7 @@ use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
 use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
+use crate::subprocess::command;
 use crate::widgets::text::TextWidget;
 use crate::widgets::{I3BarWidget, State};
 
@@ -28,9 +28,30 @@ pub struct Apt {
     format_up_to_date: FormatTemplate,
     warning_updates_regex: Option<Regex>,
     critical_updates_regex: Option<Regex>,
+    origin_regex: Option<Regex>,
+    security: Thresholds,
+    phased: Thresholds,
+    dist_upgrade: Thresholds,
     config_path: String,
 }
 
+/// The counts from which the state is set to warning and to critical
+#[derive(Debug, Clone, Copy)]
+struct Thresholds {
+    warning: Option<usize>,
+    critical: Option<usize>,
+}
+
+impl Thresholds {
+    fn state(self, count: usize) -> State {
+        match (self.warning, self.critical) {
+            (_, Some(critical)) if count >= critical => State::Critical,
+            (Some(warning), _) if count >= warning => State::Warning,
+            _ => State::Idle,
+        }
+    }
+}
+
 #[derive(Deserialize, Debug, Clone)]
 #[serde(deny_unknown_fields, default)]
 pub struct AptConfig {
@@ -55,6 +76,29 @@ pub struct AptConfig {
     /// Indicate a `critical` state for the block if any pending update match the following regex.
     /// Default behaviour is that no package updates are deemed critical
     pub critical_updates_regex: Option<String>,
+
+    /// Only count the updates from the suites that match this regex, e.g. `-security$`
+    pub origin_regex: Option<String>,
+
+    /// Number of updates from a security suite, from which state is set to warning
+    pub warning_security: Option<usize>,
+
+    /// Number of updates from a security suite, from which state is set to critical
+    pub critical_security: Option<usize>,
+
+    /// Number of updates that are deferred by phasing, from which state is set to warning
+    pub warning_phased: Option<usize>,
+
+    /// Number of updates that are deferred by phasing, from which state is set to critical
+    pub critical_phased: Option<usize>,
+
+    /// Number of packages that only a dist-upgrade would install or remove, from which state is
+    /// set to warning
+    pub warning_dist_upgrade: Option<usize>,
+
+    /// Number of packages that only a dist-upgrade would install or remove, from which state is
+    /// set to critical
+    pub critical_dist_upgrade: Option<usize>,
 }
 
 impl Default for AptConfig {
@@ -66,6 +110,13 @@ impl Default for AptConfig {
             format_up_to_date: FormatTemplate::default(),
             warning_updates_regex: None,
             critical_updates_regex: None,
+            origin_regex: None,
+            warning_security: None,
+            critical_security: None,
+            warning_phased: None,
+            critical_phased: None,
+            warning_dist_upgrade: None,
+            critical_dist_upgrade: None,
         }
     }
 }
@@ -132,6 +183,23 @@ impl ConfigBlock for Apt {
                     Some(regex)
                 }
             },
+            origin_regex: block_config
+                .origin_regex
+                .map(|regex| Regex::new(&regex))
+                .transpose()
+                .configuration_error("invalid origin regex")?,
+            security: Thresholds {
+                warning: block_config.warning_security,
+                critical: block_config.critical_security,
+            },
+            phased: Thresholds {
+                warning: block_config.warning_phased,
+                critical: block_config.critical_phased,
+            },
+            dist_upgrade: Thresholds {
+                warning: block_config.warning_dist_upgrade,
+                critical: block_config.critical_dist_upgrade,
+            },
             config_path: cache_dir.into_os_string().into_string().unwrap(),
         })
     }
@@ -147,14 +215,14 @@ fn has_critical_update(updates: &str, regex: &Regex) -> bool {
 
 fn get_updates_list(config_path: &str) -> Result<String> {
     // Update database
-    Command::new("sh")
+    command("sh")
         .env("APT_CONFIG", config_path)
         .args(&["-c", "apt update"])
         .output()
         .block_error("apt", "Failed to run `apt update` command")?;
 
     String::from_utf8(
-        Command::new("sh")
+        command("sh")
             .env("APT_CONFIG", config_path)
             .args(&["-c", "apt list --upgradable"])
             .output()
@@ -164,11 +232,62 @@ fn get_updates_list(config_path: &str) -> Result<String> {
     .block_error("apt", "Problem capturing apt command output")
 }
 
-fn get_update_count(updates: &str) -> usize {
-    updates
-        .lines()
-        .filter(|line| line.contains("[upgradable"))
-        .count()
+/// What `apt-get dist-upgrade` would do beyond the upgrades
+#[derive(Debug, Default, PartialEq)]
+struct Simulation {
+    /// The packages that are deferred by phased updates
+    phased: Vec<String>,
+    /// The packages that would be installed or removed
+    new: Vec<String>,
+    removals: Vec<String>,
+}
+
+fn simulate_dist_upgrade(config_path: &str) -> Result<String> {
+    // A simulation needs neither root nor the lock
+    let output = command("apt-get")
+        .env("APT_CONFIG", config_path)
+        .args(["--simulate", "-o", "Debug::NoLocking=1", "dist-upgrade"])
+        .output()
+        .block_error("apt", "Failed to run `apt-get --simulate dist-upgrade`")?;
+    String::from_utf8(output.stdout).block_error("apt", "Problem capturing apt-get output")
+}
+
+/// Parses the lists of the output of `apt-get --simulate dist-upgrade`, like
+/// `The following NEW packages will be installed:` followed by indented package names
+fn parse_simulation(output: &str) -> Simulation {
+    let mut simulation = Simulation::default();
+    let mut list = None;
+    for line in output.lines() {
+        if !line.starts_with(' ') {
+            list = if line.contains("deferred due to phasing") {
+                Some(&mut simulation.phased)
+            } else if line.contains("NEW packages") {
+                Some(&mut simulation.new)
+            } else if line.contains("REMOVED") {
+                Some(&mut simulation.removals)
+            } else {
+                None
+            };
+        } else if let Some(list) = list.as_mut() {
+            list.extend(line.split_whitespace().map(str::to_string));
+        }
+    }
+    simulation
+}
+
+/// The name and the suites of a line of `apt list --upgradable`, like
+/// `openssl/jammy-updates,jammy-security 3.0.2 amd64 [upgradable from: 3.0.1]`
+fn parse_upgradable(line: &str) -> Option<(&str, &str)> {
+    if !line.contains("[upgradable") {
+        return None;
+    }
+    line.split_whitespace().next()?.split_once('/')
+}
+
+fn is_security(suites: &str) -> bool {
+    suites
+        .split(',')
+        .any(|suite| suite == "security" || suite.ends_with("-security"))
 }
 
 impl Block for Apt {
@@ -181,12 +300,48 @@ impl Block for Apt {
     }
 
     fn update(&mut self) -> Result<Option<Update>> {
-        let (formatting_map, warning, critical, cum_count) = {
-            let updates_list = get_updates_list(&self.config_path)?;
-            let count = get_update_count(&updates_list);
+        let (formatting_map, warning, critical, cum_count, counts_state) = {
+            let all_updates = get_updates_list(&self.config_path)?;
+            let updates: Vec<(&str, &str, &str)> = all_updates
+                .lines()
+                .filter_map(|line| {
+                    let (name, suites) = parse_upgradable(line)?;
+                    Some((line, name, suites))
+                })
+                .filter(|(_, _, suites)| match &self.origin_regex {
+                    Some(regex) => regex.is_match(suites),
+                    None => true,
+                })
+                .collect();
+            let updates_list = updates
+                .iter()
+                .map(|(line, _, _)| *line)
+                .collect::<Vec<_>>()
+                .join("\n");
+            let count = updates.len();
+            let security = updates
+                .iter()
+                .filter(|(_, _, suites)| is_security(suites))
+                .count();
+            let simulation = parse_simulation(&simulate_dist_upgrade(&self.config_path)?);
+            let phased = simulation
+                .phased
+                .iter()
+                .filter(|package| updates.iter().any(|(_, name, _)| name == package))
+                .count();
+            let dist_upgrade = simulation.new.len() + simulation.removals.len();
             let formatting_map = map!(
-                "count" => Value::from_integer(count as i64)
+                "count" => Value::from_integer(count as i64),
+                "security" => Value::from_integer(security as i64),
+                "phased" => Value::from_integer(phased as i64),
+                "new" => Value::from_integer(simulation.new.len() as i64),
+                "removals" => Value::from_integer(simulation.removals.len() as i64),
             );
+            let counts_state = [
+                self.security.state(security),
+                self.phased.state(phased),
+                self.dist_upgrade.state(dist_upgrade),
+            ];
 
             let warning = self
                 .warning_updates_regex
@@ -197,7 +352,7 @@ impl Block for Apt {
                 .as_ref()
                 .map_or(false, |regex| has_critical_update(&updates_list, regex));
 
-            (formatting_map, warning, critical, count)
+            (formatting_map, warning, critical, count, counts_state)
         };
         self.output.set_texts(match cum_count {
             0 => self.format_up_to_date.render(&formatting_map)?,
@@ -207,9 +362,9 @@ impl Block for Apt {
         self.output.set_state(match cum_count {
             0 => State::Idle,
             _ => {
-                if critical {
+                if critical || counts_state.contains(&State::Critical) {
                     State::Critical
-                } else if warning {
+                } else if warning || counts_state.contains(&State::Warning) {
                     State::Warning
                 } else {
                     State::Info
@@ -226,3 +381,44 @@ impl Block for Apt {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apt_output() {
+        let line = "openssl/jammy-updates,jammy-security 3.0.2-0ubuntu1.10 amd64 [upgradable from: 3.0.2-0ubuntu1.9]";
+        assert_eq!(
+            parse_upgradable(line),
+            Some(("openssl", "jammy-updates,jammy-security"))
+        );
+        assert!(is_security("jammy-updates,jammy-security"));
+        assert!(!is_security("jammy-updates"));
+        assert_eq!(parse_upgradable("Listing... Done"), None);
+
+        let output = "NOTE: This is only a simulation!\n\
+            Reading package lists...\n\
+            The following packages will be REMOVED:\n  \
+              linux-image-5.15.0-70-generic\n\
+            The following NEW packages will be installed:\n  \
+              linux-image-5.15.0-76-generic linux-modules-5.15.0-76-generic\n\
+            The following upgrades have been deferred due to phasing:\n  \
+              gnome-shell\n\
+            The following packages will be upgraded:\n  \
+              openssl\n\
+            1 upgraded, 2 newly installed, 1 to remove and 1 not upgraded.\n\
+            Inst openssl [3.0.2-0ubuntu1.9] (3.0.2-0ubuntu1.10 Ubuntu:22.04/jammy-updates [amd64])\n";
+        assert_eq!(
+            parse_simulation(output),
+            Simulation {
+                phased: vec!["gnome-shell".to_string()],
+                new: vec![
+                    "linux-image-5.15.0-76-generic".to_string(),
+                    "linux-modules-5.15.0-76-generic".to_string()
+                ],
+                removals: vec!["linux-image-5.15.0-70-generic".to_string()],
+            }
+        );
+    }
+}
@@ -20,12 +20,15 @@ use serde_derive::Deserialize;
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::SharedConfig;
 use crate::config::{LogicalDirection, Scrolling};
+use crate::dbus_connections;
 use crate::errors::*;
 use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_block::I3BarBlockMinWidth;
 use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
-use crate::subprocess::spawn_child_async;
+use crate::subprocess::{command, spawn_child_async};
+use crate::util::read_inotify_events;
 use crate::widgets::text::TextWidget;
 use crate::widgets::I3BarWidget;
 
@@ -103,6 +106,24 @@ impl BacklitDevice {
         })
     }
 
+    /// All backlit devices in the `/sys/class/backlight` directory, e.g. the panel of a laptop
+    /// and external monitors with the `ddcci` driver
+    pub fn all(root_scaling: f64) -> Result<Vec<Self>> {
+        let mut devices = Vec::new();
+        for entry in Path::new("/sys/class/backlight")
+            .read_dir()
+            .block_error("backlight", "Failed to read backlight device directory")?
+            .flatten()
+        {
+            devices.push(BacklitDevice {
+                max_brightness: read_brightness(&entry.path().join("max_brightness"))?,
+                device_path: entry.path(),
+                root_scaling: clamp_root_scaling(root_scaling),
+            });
+        }
+        Ok(devices)
+    }
+
     /// Query the brightness value for this backlit device, as a percent.
     pub fn brightness(&self) -> Result<u64> {
         let raw = read_brightness(&self.brightness_file())?;
@@ -148,8 +169,7 @@ impl BacklitDevice {
             .and_then(|x| x.to_str())
             .block_error("backlight", "Malformed device path")?;
 
-        let con = dbus::ffidisp::Connection::get_private(dbus::ffidisp::BusType::System)
-            .block_error("backlight", "Failed to establish D-Bus connection.")?;
+        let con = dbus_connections::system()?;
         let msg = dbus::Message::new_method_call(
             "org.freedesktop.login1",
             "/org/freedesktop/login1/session/auto",
@@ -177,11 +197,123 @@ impl BacklitDevice {
     }
 }
 
+/// The numbers of the displays that `ddcutil detect --brief` found
+fn parse_ddc_displays(detect: &str) -> Vec<String> {
+    detect
+        .lines()
+        .filter_map(|line| line.strip_prefix("Display "))
+        .map(|number| number.trim().to_string())
+        .collect()
+}
+
+/// The current and the maximum value of `ddcutil getvcp 10 --brief`, e.g. `VCP 10 C 50 100`
+fn parse_ddc_brightness(getvcp: &str) -> Option<(u64, u64)> {
+    match getvcp.split_whitespace().collect::<Vec<_>>()[..] {
+        ["VCP", _, "C", current, max] => Some((current.parse().ok()?, max.parse().ok()?)),
+        _ => None,
+    }
+}
+
+/// A monitor whose brightness is set over DDC/CI with `ddcutil`
+pub struct DdcDisplay {
+    display: String,
+}
+
+/// The VCP feature of the brightness
+const DDC_BRIGHTNESS: &str = "10";
+
+impl DdcDisplay {
+    fn ddcutil(args: &[&str]) -> Result<String> {
+        let output = command("ddcutil")
+            .args(args)
+            .output()
+            .block_error("backlight", "Failed to run ddcutil")?;
+        if !output.status.success() {
+            return Err(BlockError(
+                "backlight".to_string(),
+                format!(
+                    "ddcutil failed: {}",
+                    String::from_utf8_lossy(&output.stdout).trim()
+                ),
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    /// The monitors that support DDC/CI, none if `ddcutil` is not installed
+    pub fn detect() -> Vec<Self> {
+        Self::ddcutil(&["detect", "--brief"])
+            .map(|detect| parse_ddc_displays(&detect))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|display| DdcDisplay { display })
+            .collect()
+    }
+
+    fn get(&self) -> Result<(u64, u64)> {
+        let getvcp = Self::ddcutil(&[
+            "--display",
+            &self.display,
+            "getvcp",
+            DDC_BRIGHTNESS,
+            "--brief",
+        ])?;
+        parse_ddc_brightness(&getvcp).block_error(
+            "backlight",
+            &format!("Failed to read the brightness of display {}", self.display),
+        )
+    }
+
+    /// The brightness as a percent
+    pub fn brightness(&self) -> Result<u64> {
+        let (current, max) = self.get()?;
+        Ok((current * 100).checked_div(max).unwrap_or(0).min(100))
+    }
+
+    /// Set the brightness as a percent
+    pub fn set_brightness(&self, value: u64) -> Result<()> {
+        let (_, max) = self.get()?;
+        let raw = (value.min(100) * max + 50) / 100;
+        Self::ddcutil(&[
+            "--display",
+            &self.display,
+            "setvcp",
+            DDC_BRIGHTNESS,
+            &raw.to_string(),
+        ])
+        .map(|_| ())
+    }
+}
+
+/// A device whose brightness follows the one of the block with `sync`
+pub enum SyncedDevice {
+    Backlight(BacklitDevice),
+    Ddc(DdcDisplay),
+}
+
+impl SyncedDevice {
+    fn brightness(&self) -> Result<u64> {
+        match self {
+            SyncedDevice::Backlight(device) => device.brightness(),
+            SyncedDevice::Ddc(display) => display.brightness(),
+        }
+    }
+
+    fn set_brightness(&self, value: u64) -> Result<()> {
+        match self {
+            SyncedDevice::Backlight(device) => device.set_brightness(value),
+            SyncedDevice::Ddc(display) => display.set_brightness(value),
+        }
+    }
+}
+
 /// A block for displaying the brightness of a backlit device.
 pub struct Backlight {
     id: usize,
     output: TextWidget,
     device: BacklitDevice,
+    /// The other devices, which are set along with `device`
+    synced: Vec<SyncedDevice>,
     step_width: u64,
     minimum: u64,
     maximum: u64,
@@ -190,6 +322,7 @@ pub struct Backlight {
     scrolling: Scrolling,
     invert_icons: bool,
     on_click: Option<String>,
+    click_to_set: bool,
     format: FormatTemplate,
 }
 
@@ -226,6 +359,15 @@ pub struct BacklightConfig {
     pub invert_icons: bool,
 
     pub on_click: Option<String>,
+
+    /// Left clicks set the brightness according to their position on the widget
+    pub click_to_set: bool,
+
+    /// Width of the widget in pixels, so a brightness is always at the same position
+    pub click_to_set_width: Option<usize>,
+
+    /// Changes of the brightness apply to all backlit devices and DDC/CI monitors
+    pub sync: bool,
 }
 
 impl Default for BacklightConfig {
@@ -240,11 +382,36 @@ impl Default for BacklightConfig {
             minimum: 5,
             maximum: 100,
             cycle: None,
+            click_to_set: false,
+            click_to_set_width: None,
+            sync: false,
         }
     }
 }
 
 impl Backlight {
+    /// Changes the brightness of every device by `step` percent of its range, so devices that
+    /// were set to the same brightness stay matched
+    fn change_brightness(&mut self, step: i64) -> Result<()> {
+        let clamp = |brightness: u64| {
+            (brightness as i64 + step).clamp(self.minimum as i64, self.maximum as i64) as u64
+        };
+        self.device
+            .set_brightness(clamp(self.device.brightness()?))?;
+        for device in &self.synced {
+            device.set_brightness(clamp(device.brightness()?))?;
+        }
+        Ok(())
+    }
+
+    fn set_brightness(&self, value: u64) -> Result<()> {
+        self.device.set_brightness(value)?;
+        for device in &self.synced {
+            device.set_brightness(value)?;
+        }
+        Ok(())
+    }
+
     fn advance_cycle(&mut self) -> Result<()> {
         if self.cycle.is_empty() {
             return Ok(());
@@ -275,7 +442,7 @@ impl Backlight {
                 .0
         };
         self.cycle_index = (nearest + 1) % self.cycle.len();
-        self.device.set_brightness(self.cycle[self.cycle_index])
+        self.set_brightness(self.cycle[self.cycle_index])
     }
 }
 
@@ -293,6 +460,15 @@ impl ConfigBlock for Backlight {
             None => BacklitDevice::default(block_config.root_scaling),
         }?;
         let brightness_file = device.brightness_file();
+        let mut synced = Vec::new();
+        if block_config.sync {
+            for other in BacklitDevice::all(block_config.root_scaling)? {
+                if other.device_path != device.device_path {
+                    synced.push(SyncedDevice::Backlight(other));
+                }
+            }
+            synced.extend(DdcDisplay::detect().into_iter().map(SyncedDevice::Ddc));
+        }
 
         let (minimum, maximum) = if block_config.minimum <= block_config.maximum {
             (block_config.minimum, block_config.maximum)
@@ -303,6 +479,7 @@ impl ConfigBlock for Backlight {
         let backlight = Self {
             id,
             device,
+            synced,
             step_width: block_config.step_width,
             minimum,
             maximum,
@@ -310,7 +487,12 @@ impl ConfigBlock for Backlight {
             cycle_index: 0,
             on_click: block_config.on_click,
             scrolling: shared_config.scrolling,
-            output: TextWidget::new(id, 0, shared_config),
+            click_to_set: block_config.click_to_set,
+            output: TextWidget::new(id, 0, shared_config).with_min_width(
+                block_config
+                    .click_to_set_width
+                    .map(I3BarBlockMinWidth::Pixels),
+            ),
             invert_icons: block_config.invert_icons,
             format: block_config.format.with_default("{brightness}")?,
         };
@@ -327,8 +509,7 @@ impl ConfigBlock for Backlight {
 
                 let mut buffer = [0; 1024];
                 loop {
-                    let mut events = notify
-                        .read_events_blocking(&mut buffer)
+                    let mut events = read_inotify_events(id, &mut notify, &mut buffer)
                         .expect("Error while reading inotify events");
 
                     if events.any(|event| event.mask.contains(EventMask::MODIFY)) {
@@ -393,6 +574,13 @@ impl Block for Backlight {
     fn click(&mut self, event: &I3BarEvent) -> Result<()> {
         match event.button {
             MouseButton::Right => self.advance_cycle()?,
+            MouseButton::Left if self.click_to_set && event.position.is_some() => {
+                if let Some(brightness) =
+                    event.slider_value(self.minimum as f64, self.maximum as f64)
+                {
+                    self.set_brightness(brightness as u64)?
+                }
+            }
             MouseButton::Left => {
                 if let Some(ref cmd) = self.on_click {
                     spawn_child_async("sh", &["-c", cmd])
@@ -402,26 +590,46 @@ impl Block for Backlight {
                 }
             }
             _ => {
-                let brightness = self.device.brightness()? as i64;
-                let step_width = self.step_width as i64;
+                let step_width = event.scale_step(self.step_width as f64) as i64;
                 if let Some(direction) = self.scrolling.to_logical_direction(event.button) {
                     use LogicalDirection::*;
                     let sign = match direction {
                         Up => 1,
                         Down => -1,
                     };
-                    self.device.set_brightness(
-                        (brightness + sign * step_width)
-                            .clamp(self.minimum as i64, self.maximum as i64)
-                            as u64,
-                    )?
+                    self.change_brightness(sign * step_width)?
                 }
             }
         }
         Ok(())
     }
 
+    fn action(&mut self, action: &str) -> Result<bool> {
+        match action {
+            "up" => self.change_brightness(self.step_width as i64)?,
+            "down" => self.change_brightness(-(self.step_width as i64))?,
+            "cycle" => self.advance_cycle()?,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
     fn id(&self) -> usize {
         self.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ddcutil_output() {
+        let detect = "Display 1\n   I2C bus:  /dev/i2c-4\n   Monitor:  DEL:DELL U2415:7MT0\n\n\
+            Invalid display\n   I2C bus:  /dev/i2c-5\n\n\
+            Display 2\n   I2C bus:  /dev/i2c-6\n";
+        assert_eq!(parse_ddc_displays(detect), vec!["1", "2"]);
+        assert_eq!(parse_ddc_brightness("VCP 10 C 50 100\n"), Some((50, 100)));
+        assert_eq!(parse_ddc_brightness("VCP 10 ERR\n"), None);
+    }
+}
@@ -1,18 +1,329 @@
 //! A Base block for common behavior for all blocks
 
 use std::collections::HashMap;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
+use crate::blocks::reminders::Reminder;
+use crate::bus;
+use crate::click::{self, ClickHandler, Popup, ScrollAcceleration, CONFIRM_TIMEOUT};
+use crate::cron::{ActiveHours, Schedule};
+use crate::de::deserialize_opt_duration;
+use crate::deadline;
 use crate::errors::*;
+use crate::formatting::value::Value as FormatValue;
+use crate::formatting::FormatTemplate;
+use crate::logging;
+use crate::metrics;
+use crate::polling;
 use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
-use crate::{blocks::Update, subprocess::spawn_child_async, widgets::I3BarWidget, Block};
+use crate::registry::{self, Sensitive};
+use crate::rules::Rule;
+use crate::scheduler::Task;
+use crate::services::Service;
+use crate::subprocess::{self, spawn_child_async, spawn_child_async_with_input, EnvValue};
+use crate::widgets::{text::TextWidget, I3BarWidget};
+use crate::{blocks::Update, Block};
 
+use chrono::{Local, NaiveDateTime};
+use crossbeam_channel::Sender;
 use serde_derive::Deserialize;
 use toml::{value::Table, Value};
 
+/// Updates that take longer are logged at the info level, since they delay the other blocks
+const SLOW_UPDATE: Duration = Duration::from_secs(1);
+
+/// The longest wait before an update that failed is retried
+const MAX_RETRY_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+fn since(now: NaiveDateTime, time: NaiveDateTime) -> Duration {
+    (time - now).to_std().unwrap_or_default()
+}
+
+/// Without a `watchdog`, a block whose thread sends heartbeats is stuck after this long without
+/// one. The threads send one every second while they wait for events.
+const DEFAULT_WATCHDOG: Duration = Duration::from_secs(60);
+
+pub(super) const DEFAULT_ERROR_FORMAT: &str = "{block}: {message^24}";
+
+/// The message of an error without its kind, context and cause
+fn error_message(error: &Error) -> &str {
+    match error {
+        BlockError(_, message) | ConfigurationError(message, _) | InternalError(_, message, _) => {
+            message
+        }
+    }
+}
+
 pub(super) struct BaseBlock<T: Block> {
     pub name: String,
     pub inner: T,
     pub on_click: Option<String>,
+    pub click: Vec<ClickHandler>,
+    pub popup: Option<Popup>,
+    pub scroll: ScrollAcceleration,
+    /// How long the block stays expanded after a click, or forever if `None`
+    pub expand_duration: Option<Duration>,
+    pub expanded_until: Option<Instant>,
+    /// The click binding with `confirm` that was clicked once, and until when it waits for the
+    /// second click
+    pub confirming: Option<(usize, Instant)>,
+    pub confirm_prompt: TextWidget,
+    pub min_update_interval: Option<Duration>,
+    /// How long the first update may take, see `deadline`
+    pub init_timeout: Option<Duration>,
+    /// Shown until the first update has finished
+    pub loading: Option<TextWidget>,
+    /// The update interval of the block is at least this long while the bar is hidden
+    pub interval_hidden: Option<Duration>,
+    /// The update interval of the block is at least this long while on battery
+    pub interval_on_battery: Option<Duration>,
+    pub last_update: Option<Instant>,
+    /// When a throttled update is requested again
+    pub deferred_update: Option<Instant>,
+    pub tx_update_request: Sender<Task>,
+    /// Services the block needs. The block is not updated while one of them is not ready, and
+    /// hidden unless `update_offline` has something to show.
+    pub depends_on: Vec<Service>,
+    pub offline: bool,
+    /// Whether the block has something to show while it's offline
+    pub shown_offline: bool,
+    /// Environment variables of the commands spawned by the block
+    pub env: Rc<Vec<(String, String)>>,
+    /// Hidden with `i3status-rs hide` or by `signal`
+    pub hidden: bool,
+    /// The real time signal that updates the block, or toggles it if `hidden_by_default` is set
+    pub signal: Option<i32>,
+    pub toggle_on_signal: bool,
+    /// Shown instead of the block while it has a text, set with `i3status-rs text`
+    pub text_override: TextWidget,
+    pub overridden: bool,
+    /// The name of the block in the configuration, e.g. `disk_space`
+    pub block_type: &'static str,
+    /// Shown instead of the block while its updates fail
+    pub error: TextWidget,
+    pub error_format: FormatTemplate,
+    /// The first wait before an update that failed is retried, which doubles with every further
+    /// failure
+    pub error_retry_interval: Duration,
+    /// Failed updates in a row after which the block is no longer retried
+    pub max_retries: Option<u32>,
+    /// Errors are shown over the whole bar instead, which stops all blocks
+    pub error_fullscreen: bool,
+    /// Updated right after a resume from suspend or a jump of the clock
+    pub update_on_resume: bool,
+    /// How long the block may go without an update or a heartbeat before it's restarted. By
+    /// default only blocks with heartbeats are watched, zero turns it off.
+    pub watchdog: Option<Duration>,
+    /// The times of the updates, which replace the intervals of the block
+    pub schedule: Option<Schedule>,
+    /// The block is hidden and not updated outside of these hours and the hours of `schedule`
+    pub active_hours: Option<ActiveHours>,
+    pub inactive: bool,
+    /// Updates that failed in a row
+    pub failures: u32,
+}
+
+impl<T: Block> BaseBlock<T> {
+    /// The span of the messages of an update or a click of the block
+    fn span(&self) -> tracing::Span {
+        tracing::info_span!("block", id = self.id(), name = %self.block_type)
+    }
+
+    /// Shows the error of a failed update in the block and schedules the retry, unless it
+    /// failed too often
+    fn failed(&mut self, error: &Error) -> Result<Option<Update>> {
+        self.failures += 1;
+        let values = map!(
+            "block" => FormatValue::from_string(self.block_type.to_string()),
+            "message" => FormatValue::from_string(error_message(error).to_string()),
+            "failures" => FormatValue::from_integer(self.failures as i64),
+        );
+        self.error.set_format(&self.error_format, &values)?;
+        if self.max_retries.is_some_and(|max| self.failures > max) {
+            return Ok(None);
+        }
+        let retry = self
+            .error_retry_interval
+            .saturating_mul(1 << (self.failures - 1).min(16))
+            .min(MAX_RETRY_INTERVAL.max(self.error_retry_interval));
+        Ok(Some(Update::Every(retry)))
+    }
+
+    /// How long the block is inactive from `now` on, if it's outside of its `active_hours` or the
+    /// hours of its `schedule`
+    fn inactive_for(&self, now: NaiveDateTime) -> Option<Duration> {
+        if let Some(hours) = &self.active_hours {
+            if !hours.contains(now.time()) {
+                return Some(hours.until_start(now));
+            }
+        }
+        let schedule = self.schedule.as_ref().filter(|s| !s.is_active(now))?;
+        // Schedules that never match are refused when the block is created
+        Some(
+            schedule
+                .next(now)
+                .map_or(Duration::from_secs(24 * 60 * 60), |next| since(now, next)),
+        )
+    }
+
+    /// The next update at the time of the `schedule`, and at the latest when the block becomes
+    /// inactive, so it's hidden
+    fn next_scheduled(&self, now: NaiveDateTime, update: Option<Update>) -> Option<Update> {
+        let mut next = match (&self.schedule, &update) {
+            (Some(schedule), _) => schedule.next(now).map(|next| since(now, next)),
+            (None, Some(Update::Every(interval))) => Some(*interval),
+            (None, _) => None,
+        };
+        let ends = [
+            self.active_hours.and_then(|hours| hours.until_end(now)),
+            self.schedule
+                .as_ref()
+                .and_then(|schedule| schedule.inactive_from(now))
+                .map(|end| since(now, end)),
+        ];
+        for &end in ends.iter().flatten() {
+            next = Some(next.map_or(end, |next| next.min(end)));
+        }
+        match next {
+            Some(next) => Some(Update::Every(next)),
+            None => update,
+        }
+    }
+
+    /// Runs a command bound to a click, or only prints it when debugging clicks
+    fn run(&self, cmd: &str) -> Result<()> {
+        let id = self.id();
+        let (cmd, values) = click::substitute(cmd, |name| {
+            match name.split_once('.') {
+                Some((block, placeholder)) => registry::get_by_name(block, placeholder),
+                None => registry::get(id, name),
+            }
+            .map(|value| value.plain())
+        });
+        if click::debug() {
+            eprintln!("click: would run `{}` with {:?}", cmd, values);
+            return Ok(());
+        }
+        // The values are passed to the shell in the environment of the block
+        let env = Rc::new(self.env.iter().cloned().chain(values).collect());
+        subprocess::with_env(&env, || spawn_child_async("sh", &["-c", &cmd]))
+            .block_error(&self.name, "could not spawn child")
+    }
+
+    fn toggle_expanded(&mut self) -> Result<()> {
+        if registry::is_expanded(self.id()) {
+            return self.collapse();
+        }
+        registry::set_expanded(self.id(), true);
+        self.expanded_until = self.expand_duration.map(|d| Instant::now() + d);
+        self.run_update()?;
+        Ok(())
+    }
+
+    fn collapse(&mut self) -> Result<()> {
+        registry::set_expanded(self.id(), false);
+        self.expanded_until = None;
+        self.run_update()?;
+        Ok(())
+    }
+
+    /// `update` within the span of the block
+    fn run_update(&mut self) -> Result<Option<Update>> {
+        let now = Local::now().naive_local();
+        let inactive_for = self.inactive_for(now);
+        self.inactive = inactive_for.is_some();
+        if let Some(inactive_for) = inactive_for {
+            tracing::debug!("inactive for {:?}", inactive_for);
+            return Ok(Some(Update::Every(inactive_for)));
+        }
+        // Updated again by the watcher of the services once they are ready
+        self.offline = !self.depends_on.iter().all(|service| service.is_ready());
+        let id = self.id();
+        let inner = &mut self.inner;
+        self.loading = None;
+        if self.offline {
+            self.shown_offline = subprocess::with_env(&self.env, || {
+                registry::with_block(id, || {
+                    tracing::debug!("not updated while its services are offline");
+                    inner.update_offline()
+                })
+            })?;
+            return Ok(None);
+        }
+        let deadline = match self.last_update {
+            None => self.init_timeout.map(|timeout| Instant::now() + timeout),
+            Some(_) => None,
+        };
+        let started = Instant::now();
+        self.last_update = Some(started);
+        registry::update_started(id);
+        let mut result = subprocess::with_env(&self.env, || {
+            registry::with_block(id, || match deadline {
+                Some(deadline) => deadline::with_deadline(deadline, || inner.update()),
+                None => inner.update(),
+            })
+        });
+        if let Ok(Some(Update::Every(interval))) = &mut result {
+            let slowest = [
+                self.interval_hidden.filter(|_| polling::bar_hidden()),
+                self.interval_on_battery.filter(|_| polling::on_battery()),
+            ];
+            if let Some(&slowest) = slowest.iter().flatten().max() {
+                if slowest > *interval {
+                    *interval = slowest;
+                    polling::slowed(id, &self.tx_update_request);
+                }
+            }
+        }
+        if let Ok(update) = &mut result {
+            *update = self.next_scheduled(now, update.take());
+        }
+        let next = match &result {
+            Ok(Some(Update::Every(interval))) => Some(*interval),
+            _ => None,
+        };
+        match &result {
+            Ok(_) => registry::update_succeeded(id, next),
+            Err(_) => registry::update_failed(id),
+        }
+        let duration = started.elapsed();
+        match (&result, next) {
+            (Err(error), _) => tracing::warn!("update failed after {:?}: {:?}", duration, error),
+            (Ok(_), Some(next)) => {
+                tracing::debug!(
+                    "updated in {:?}, the next update is in {:?}",
+                    duration,
+                    next
+                )
+            }
+            (Ok(_), None) => tracing::debug!("updated in {:?}", duration),
+        }
+        if duration > SLOW_UPDATE {
+            tracing::info!("the update was slow, it took {:?}", duration);
+        }
+        logging::dump_update(
+            id,
+            self.block_type,
+            duration,
+            result.as_ref().err(),
+            next,
+            &self.inner.view(),
+        );
+        if result.is_ok() {
+            bus::block_updated(id);
+            metrics::block_updated(id, &self.name);
+        } else {
+            metrics::block_failed(id, &self.name);
+        }
+        match result {
+            Err(error) if !self.error_fullscreen => self.failed(&error),
+            result => {
+                self.failures = 0;
+                result
+            }
+        }
+    }
 }
 
 impl<T: Block> Block for BaseBlock<T> {
@@ -21,28 +332,298 @@ impl<T: Block> Block for BaseBlock<T> {
     }
 
     fn view(&self) -> Vec<&dyn I3BarWidget> {
-        self.inner.view()
+        if self.hidden {
+            return Vec::new();
+        }
+        if self.inactive {
+            return Vec::new();
+        }
+        if let Some(loading) = &self.loading {
+            return vec![loading];
+        }
+        if self.offline && !self.shown_offline {
+            return Vec::new();
+        }
+        if self.overridden {
+            return vec![&self.text_override];
+        }
+        if self.failures > 0 {
+            return vec![&self.error];
+        }
+        match self.confirming {
+            Some(_) => vec![&self.confirm_prompt],
+            None => {
+                let mut view = self.inner.view();
+                view.retain(|widget| !widget.is_hidden());
+                view
+            }
+        }
     }
 
     fn update(&mut self) -> Result<Option<Update>> {
-        self.inner.update()
+        let _span = self.span().entered();
+        self.run_update()
     }
 
     fn signal(&mut self, signal: i32) -> Result<()> {
-        self.inner.signal(signal)
+        if self.signal == Some(signal) {
+            if self.toggle_on_signal {
+                // The block keeps updating while it's hidden, so it's up to date when it's shown
+                self.hidden = !self.hidden;
+            } else {
+                self.tx_update_request.send(Task {
+                    id: self.id(),
+                    update_time: Instant::now(),
+                })?;
+            }
+        }
+        let id = self.id();
+        let inner = &mut self.inner;
+        subprocess::with_env(&self.env, || {
+            registry::with_block(id, || inner.signal(signal))
+        })
     }
 
     fn click(&mut self, e: &I3BarEvent) -> Result<()> {
+        let _span = self.span().entered();
+        let mut e = e.clone();
+        self.scroll.apply(&mut e);
+        let e = &e;
+        let debug = click::debug();
+        let segment = e.instance.and_then(|instance| self.inner.segment(instance));
+        if debug {
+            eprintln!(
+                "click: {:?} on block {} ({}), instance {:?}, segment {:?}, position {:?}, scroll factor {}",
+                e.button,
+                self.id(),
+                self.name,
+                e.instance,
+                segment,
+                e.position,
+                e.scroll_factor
+            );
+        }
+
+        // Failed updates are retried right away
+        if self.failures > 0 && matches!(e.button, MouseButton::Left) {
+            self.tx_update_request
+                .send(Task {
+                    id: self.id(),
+                    update_time: Instant::now(),
+                })
+                .internal_error(&self.name, "failed to request an update")?;
+            return Ok(());
+        }
+
+        // Any click either confirms or cancels
+        let confirming = self
+            .confirming
+            .take()
+            .filter(|(_, until)| *until > Instant::now());
+        if let Some(index) = self.click.iter().position(|h| h.matches(e, segment)) {
+            let handler = &self.click[index];
+            if handler.confirm && confirming.map(|(i, _)| i) != Some(index) {
+                if debug {
+                    eprintln!("click: waiting for a second click to confirm");
+                }
+                self.confirming = Some((index, Instant::now() + CONFIRM_TIMEOUT));
+                return Ok(());
+            }
+            if let Some(ref cmd) = handler.cmd {
+                self.run(cmd)?;
+            }
+            if handler.update {
+                if debug {
+                    eprintln!("click: would update the block");
+                } else {
+                    self.run_update()?;
+                }
+            }
+            return Ok(());
+        }
+
+        if let Some(popup) = self.popup.as_ref().filter(|popup| popup.button == e.button) {
+            let (text, _) = popup.format.render(&registry::values(self.id()))?;
+            if debug {
+                eprintln!("click: would pipe {:?} to `{}`", text, popup.cmd);
+                return Ok(());
+            }
+            return subprocess::with_env(&self.env, || {
+                spawn_child_async_with_input("sh", &["-c", &popup.cmd], text)
+            })
+            .block_error(&self.name, "could not spawn child");
+        }
+
+        if e.button == MouseButton::Left && registry::has_expanded_format(self.id()) {
+            if debug {
+                eprintln!("click: would toggle the expanded format");
+                return Ok(());
+            }
+            return self.toggle_expanded();
+        }
+
         match &self.on_click {
             Some(cmd) => {
                 if let MouseButton::Left = e.button {
-                    spawn_child_async("sh", &["-c", cmd])
-                        .block_error(&self.name, "could not spawn child")?;
+                    self.run(cmd)?;
+                } else if debug {
+                    eprintln!("click: ignored, on_click only handles left clicks");
                 }
                 Ok(())
             }
-            None => self.inner.click(e),
+            None if debug => {
+                eprintln!("click: would pass the click to the block");
+                Ok(())
+            }
+            None => {
+                let id = self.id();
+                let inner = &mut self.inner;
+                subprocess::with_env(&self.env, || registry::with_block(id, || inner.click(e)))
+            }
+        }
+    }
+
+    fn segment(&self, instance: usize) -> Option<&'static str> {
+        self.inner.segment(instance)
+    }
+
+    fn throttle(&mut self) -> bool {
+        if self.deferred_update.is_some() {
+            return true;
         }
+        let next = match (self.min_update_interval, self.last_update) {
+            (Some(interval), Some(last)) => last + interval,
+            _ => return false,
+        };
+        if next <= Instant::now() {
+            return false;
+        }
+        self.deferred_update = Some(next);
+        true
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        let confirming = self.confirming.map(|(_, until)| until);
+        [self.expanded_until, confirming, self.deferred_update]
+            .iter()
+            .flatten()
+            .min()
+            .copied()
+    }
+
+    fn timeout(&mut self) -> Result<()> {
+        let now = Instant::now();
+        if matches!(self.deferred_update, Some(until) if until <= now) {
+            self.deferred_update = None;
+            // Requested again, so blocks that don't run on the scheduler stay off it
+            self.tx_update_request
+                .send(Task {
+                    id: self.id(),
+                    update_time: now,
+                })
+                .internal_error(&self.name, "failed to request an update")?;
+        }
+        if matches!(self.confirming, Some((_, until)) if until <= now) {
+            self.confirming = None;
+        }
+        if matches!(self.expanded_until, Some(until) if until <= now) {
+            let _span = self.span().entered();
+            self.collapse()?;
+        }
+        Ok(())
+    }
+
+    fn action(&mut self, action: &str) -> Result<bool> {
+        let id = self.id();
+        let inner = &mut self.inner;
+        if subprocess::with_env(&self.env, || {
+            registry::with_block(id, || inner.action(action))
+        })? {
+            return Ok(true);
+        }
+
+        // Actions available for every block
+        let button = match action {
+            "update" => {
+                self.update()?;
+                return Ok(true);
+            }
+            "toggle_format" if registry::has_expanded_format(id) => {
+                let _span = self.span().entered();
+                self.toggle_expanded()?;
+                return Ok(true);
+            }
+            "left_click" => MouseButton::Left,
+            "middle_click" => MouseButton::Middle,
+            "right_click" => MouseButton::Right,
+            _ => return Ok(false),
+        };
+        self.click(&I3BarEvent {
+            id: Some(id),
+            instance: None,
+            button,
+            position: None,
+            scroll_factor: 1.0,
+            raw: None,
+        })?;
+        Ok(true)
+    }
+
+    fn remind(&mut self, reminder: &Reminder) -> Result<bool> {
+        let id = self.id();
+        let inner = &mut self.inner;
+        subprocess::with_env(&self.env, || {
+            registry::with_block(id, || inner.remind(reminder))
+        })
+    }
+
+    fn depends_on(&self) -> &[Service] {
+        &self.depends_on
+    }
+
+    fn updates_on_resume(&self) -> bool {
+        self.update_on_resume
+    }
+
+    fn stuck(&self, now: Instant) -> bool {
+        let heartbeat = registry::last_heartbeat(self.id());
+        let limit = match (self.watchdog, heartbeat) {
+            (Some(watchdog), _) if watchdog.is_zero() => return false,
+            (Some(watchdog), _) => watchdog,
+            (None, Some(_)) => DEFAULT_WATCHDOG,
+            // Without a thread that waits for events, only an update that hangs could keep the
+            // block from updating, and that holds up the whole bar
+            (None, None) => return false,
+        };
+        !self.offline
+            && !self.inactive
+            && self
+                .last_update
+                .max(heartbeat)
+                .is_some_and(|last| now.saturating_duration_since(last) > limit)
+    }
+
+    fn set_hidden(&mut self, hidden: bool) {
+        self.hidden = hidden;
+    }
+
+    fn is_hidden(&self) -> bool {
+        self.hidden
+    }
+
+    fn set_text_override(&mut self, text: Option<String>) {
+        self.overridden = text.is_some();
+        self.text_override.set_text(text.unwrap_or_default());
+    }
+
+    fn wants_double_click(&self, button: MouseButton) -> bool {
+        let double = button.double();
+        self.click.iter().any(|h| h.button == double) || self.inner.wants_double_click(button)
+    }
+
+    fn wants_hold(&self, button: MouseButton) -> bool {
+        let hold = button.hold();
+        self.click.iter().any(|h| h.button == hold) || self.inner.wants_hold(button)
     }
 }
 
@@ -51,12 +632,130 @@ pub(super) struct BaseBlockConfig {
     /// Command to execute when the button is clicked
     pub on_click: Option<String>,
 
+    /// Bindings for specific buttons, which take precedence over `on_click`
+    #[serde(default)]
+    pub click: Vec<ClickHandler>,
+
     pub theme_overrides: Option<HashMap<String, String>>,
     pub icons_format: Option<String>,
+
+    /// Name under which the values of the block are available to other blocks
+    pub export: Option<String>,
+
+    /// Placeholders that are masked in the presentation mode
+    pub sensitive: Option<Sensitive>,
+
+    /// Passes details of the block to a command when the block is clicked
+    pub popup: Option<Popup>,
+
+    /// Format shown for `expand_duration` after a left click
+    pub format_expanded: Option<FormatTemplate>,
+    /// Zero keeps the block expanded until it is clicked again
+    #[serde(default, deserialize_with = "deserialize_opt_duration")]
+    pub expand_duration: Option<Duration>,
+
+    /// Services the block needs, replaces the default of the block
+    #[serde(default)]
+    pub depends_on: Option<Vec<Service>>,
+
+    /// Updates requested by the block, e.g. because of an event, are at least this far apart
+    #[serde(default, deserialize_with = "deserialize_opt_duration")]
+    pub min_update_interval: Option<Duration>,
+
+    /// The longest time the first update may take, so a block whose server or command doesn't
+    /// answer shows its error soon. Overrides the `init_timeout` of the bar.
+    #[serde(default, deserialize_with = "deserialize_opt_duration")]
+    pub init_timeout: Option<Duration>,
+    /// Shown until the first update has finished, overrides the `loading_text` of the bar. An
+    /// empty text turns it off.
+    pub loading_text: Option<String>,
+
+    /// The update interval while all bars of i3status-rs are hidden, if it's longer than the one of
+    /// the block
+    #[serde(default, deserialize_with = "deserialize_opt_duration")]
+    pub interval_hidden: Option<Duration>,
+    /// The update interval while no power supply is online, if it's longer than the one of the
+    /// block
+    #[serde(default, deserialize_with = "deserialize_opt_duration")]
+    pub interval_on_battery: Option<Duration>,
+
+    /// Factor for the steps of scroll actions, e.g. the volume step of the sound block
+    pub scroll_step: Option<f64>,
+    /// Increase of the factor with every further rapid wheel event
+    pub scroll_accel: Option<f64>,
+
+    /// Environment variables of the commands spawned by the block
+    pub env: Option<HashMap<String, EnvValue>>,
+
+    /// Real time signal relative to `SIGRTMIN` that updates the block
+    pub signal: Option<i32>,
+    /// Hides the block until `signal` is received, which toggles the block from then on
+    #[serde(default)]
+    pub hidden_by_default: bool,
+
+    /// The first wait before an update that failed is retried, 5 seconds by default
+    #[serde(default, deserialize_with = "deserialize_opt_duration")]
+    pub error_retry_interval: Option<Duration>,
+    /// Failed updates in a row after which the block is no longer retried, until it's clicked
+    pub max_retries: Option<u32>,
+    /// Shown instead of the block while its updates fail
+    pub error_format: Option<FormatTemplate>,
+    /// Shows errors over the whole bar instead, like before blocks could recover from them
+    #[serde(default)]
+    pub error_fullscreen: bool,
+
+    /// Updates the block right after a resume from suspend or a jump of the clock, instead of at
+    /// its next update. Set by default for blocks that show something about the time.
+    pub update_on_resume: Option<bool>,
+
+    /// Times of the updates like `*/5 8-18 * * mon-fri`, which replace the intervals of the block.
+    /// The block is hidden outside of its hours and days.
+    pub schedule: Option<Schedule>,
+    /// Hours of the day like `08:00-20:00` outside of which the block is hidden and not updated
+    pub active_hours: Option<ActiveHours>,
+
+    /// Restarts the block if it didn't update or send a heartbeat for this long, zero turns it off
+    #[serde(default, deserialize_with = "deserialize_opt_duration")]
+    pub watchdog: Option<Duration>,
+
+    /// Change the state, the icon, the format or the visibility of the block depending on its
+    /// placeholders. The first rule whose condition holds applies.
+    #[serde(default)]
+    pub rules: Vec<Rule>,
 }
 
 impl BaseBlockConfig {
-    const FIELDS: &'static [&'static str] = &["on_click", "theme_overrides", "icons_format"];
+    const FIELDS: &'static [&'static str] = &[
+        "on_click",
+        "click",
+        "theme_overrides",
+        "icons_format",
+        "export",
+        "env",
+        "scroll_step",
+        "scroll_accel",
+        "format_expanded",
+        "expand_duration",
+        "popup",
+        "sensitive",
+        "min_update_interval",
+        "init_timeout",
+        "loading_text",
+        "interval_hidden",
+        "interval_on_battery",
+        "depends_on",
+        "signal",
+        "hidden_by_default",
+        "error_retry_interval",
+        "max_retries",
+        "error_format",
+        "error_fullscreen",
+        "update_on_resume",
+        "watchdog",
+        "schedule",
+        "active_hours",
+        "rules",
+    ];
 
     // FIXME: this function is to paper over https://github.com/serde-rs/serde/issues/1957
     pub(super) fn extract(config: &mut Value) -> Value {
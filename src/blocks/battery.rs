@@ -12,15 +12,19 @@ use std::time::{Duration, Instant};
 use crossbeam_channel::Sender;
 use dbus::arg::Array;
 use dbus::ffidisp::stdintf::org_freedesktop_dbus::Properties;
+use dbus::ffidisp::BusType;
 use serde_derive::Deserialize;
 
 use crate::apcaccess::ApcAccess;
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::SharedConfig;
+use crate::dbus_connections::{self, Event};
 use crate::de::deserialize_duration;
+use crate::deadline;
 use crate::errors::*;
 use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
+use crate::netlink::{self, Source};
 use crate::scheduler::Task;
 use crate::util::{battery_level_to_icon, read_file};
 use crate::widgets::text::TextWidget;
@@ -452,7 +456,6 @@ impl BatteryDevice for ApcUpsDevice {
 
 pub struct UpowerDevice {
     device_path: String,
-    con: dbus::ffidisp::Connection,
     allow_missing: bool,
 }
 
@@ -465,77 +468,65 @@ impl UpowerDevice {
     /// battery.
     pub fn from_device(device: &str, allow_missing: bool) -> Result<Self> {
         let device_path = format!("/org/freedesktop/UPower/devices/{}", device);
-        let con = dbus::ffidisp::Connection::get_private(dbus::ffidisp::BusType::System)
-            .block_error("battery", "Failed to establish D-Bus connection.")?;
+        dbus_connections::system()?;
 
         Ok(UpowerDevice {
             device_path,
-            con,
             allow_missing,
         })
     }
 
     /// Monitor UPower property changes in a separate thread and send updates
     /// via the `update_request` channel.
-    pub fn monitor(&self, id: usize, update_request: Sender<Task>) {
+    pub fn monitor(&self, id: usize, update_request: Sender<Task>) -> Result<()> {
         let path = self.device_path.clone();
-        thread::Builder::new()
-            .name("battery".into())
-            .spawn(move || {
-                let con = dbus::ffidisp::Connection::get_private(dbus::ffidisp::BusType::System)
-                    .expect("Failed to establish D-Bus connection.");
-                let properties_changed_rule = format!(
-                    "type='signal',\
-                 path='{}',\
-                 interface='org.freedesktop.DBus.Properties',\
-                 member='PropertiesChanged'",
-                    path
-                );
-                let device_removed_rule = "type='signal',\
-                 interface='org.freedesktop.UPower',\
-                 member='DeviceRemoved'";
-                let device_added_rule = "type='signal',\
-                 interface='org.freedesktop.UPower',\
-                 member='DeviceAdded'";
-
-                // First we're going to get an (irrelevant) NameAcquired event.
-                con.incoming(10_000).next();
-
-                con.add_match(&properties_changed_rule)
-                    .expect("Failed to add D-Bus match rule.");
-                con.add_match(device_removed_rule)
-                    .expect("Failed to add D-Bus match rule.");
-                con.add_match(device_added_rule)
-                    .expect("Failed to add D-Bus match rule.");
-
-                let device_path = dbus::Path::from(&path);
-                loop {
-                    if let Some(msg) = con.incoming(10_000).next() {
-                        if let Some(interface) =
-                            msg.interface().map(|interface| interface.to_string())
-                        {
-                            if (interface == "org.freedesktop.UPower"
-                                && msg
-                                    .get1::<dbus::Path>()
-                                    .expect("Unable to get objectpath argument")
-                                    == device_path)
-                                || interface == "org.freedesktop.DBus.Properties"
-                            {
-                                update_request
-                                    .send(Task {
-                                        id,
-                                        update_time: Instant::now(),
-                                    })
-                                    .unwrap();
-                            }
+        let properties_changed_rule = format!(
+            "type='signal',\
+             path='{}',\
+             interface='org.freedesktop.DBus.Properties',\
+             member='PropertiesChanged'",
+            path
+        );
+        let device_removed_rule = "type='signal',\
+             interface='org.freedesktop.UPower',\
+             member='DeviceRemoved'";
+        let device_added_rule = "type='signal',\
+             interface='org.freedesktop.UPower',\
+             member='DeviceAdded'";
+
+        let device_path = dbus::Path::from(path);
+        dbus_connections::watch(
+            "battery",
+            BusType::System,
+            vec![
+                properties_changed_rule,
+                device_removed_rule.to_string(),
+                device_added_rule.to_string(),
+            ],
+            move |event| {
+                let relevant = match event {
+                    Event::Connected => true,
+                    Event::Signal(msg) => match msg.interface().as_deref() {
+                        Some("org.freedesktop.UPower") => {
+                            msg.get1::<dbus::Path>().as_ref() == Some(&device_path)
                         }
-                        // Avoid update spam.
-                        // TODO: Is this necessary?
-                        thread::sleep(Duration::from_millis(1000))
-                    }
+                        Some("org.freedesktop.DBus.Properties") => true,
+                        _ => false,
+                    },
+                };
+                if relevant {
+                    update_request
+                        .send(Task {
+                            id,
+                            update_time: Instant::now(),
+                        })
+                        .unwrap();
+                    // Avoid update spam.
+                    // TODO: Is this necessary?
+                    thread::sleep(Duration::from_millis(1000))
                 }
-            })
-            .unwrap();
+            },
+        )
     }
 
     fn get_upower_value<T: for<'b> dbus::arg::Get<'b>>(
@@ -543,8 +534,12 @@ impl UpowerDevice {
         key: &str,
         fallback_value: T,
     ) -> Result<T> {
-        self.con
-            .with_path("org.freedesktop.UPower", &self.device_path, 1000)
+        dbus_connections::system()?
+            .with_path(
+                "org.freedesktop.UPower",
+                &self.device_path,
+                deadline::limit_millis(1000),
+            )
             .get::<T>("org.freedesktop.UPower.Device", key)
             .or_else(|_| {
                 if self.allow_missing {
@@ -566,7 +561,11 @@ impl BatteryDevice for UpowerDevice {
             "org.freedesktop.UPower",
             "EnumerateDevices",
         ) {
-            if let Ok(dbus_reply) = self.con.send_with_reply_and_block(msg, 2000) {
+            let reply = dbus_connections::system().ok().and_then(|con| {
+                con.send_with_reply_and_block(msg, deadline::limit_millis(2000))
+                    .ok()
+            });
+            if let Some(dbus_reply) = reply {
                 // EnumerateDevices returns one argument, which is an array of ObjectPaths (not dbus::tree:ObjectPath).
                 if let Some(mut paths) = dbus_reply.get1::<Array<dbus::Path, _>>() {
                     // Target device path
@@ -768,19 +767,23 @@ impl ConfigBlock for Battery {
             BatteryDriver::Upower => {
                 let out =
                     UpowerDevice::from_device(&block_config.device, block_config.allow_missing)?;
-                out.monitor(id, update_request);
+                out.monitor(id, update_request)?;
                 Box::new(out)
             }
-            BatteryDriver::Sysfs => Box::new(PowerSupplyDevice::from_device(
-                &block_config.device,
-                block_config.allow_missing,
-            )?),
+            BatteryDriver::Sysfs => {
+                // Plugging in the charger or a battery updates the block right away
+                netlink::subscribe(Source::PowerSupply, id, update_request);
+                Box::new(PowerSupplyDevice::from_device(
+                    &block_config.device,
+                    block_config.allow_missing,
+                )?)
+            }
         };
 
         let fallback = match shared_config.get_icon("bat_10") {
             Ok(_) => false,
             Err(_) => {
-                eprintln!("Icon bat_10 not found in your icons file. Please check NEWS.md");
+                tracing::warn!("Icon bat_10 not found in your icons file. Please check NEWS.md");
                 true
             }
         };
@@ -805,6 +808,10 @@ impl ConfigBlock for Battery {
             fallback_icons: fallback,
         })
     }
+
+    fn default_update_on_resume() -> bool {
+        true
+    }
 }
 
 impl Block for Battery {
@@ -823,7 +830,7 @@ impl Block for Battery {
             );
 
             self.output.set_icon("bat_not_available")?;
-            self.output.set_texts(self.missing_format.render(&values)?);
+            self.output.set_format(&self.missing_format, &values)?;
             self.output.set_state(State::Warning);
         } else {
             // The device may have gone missing
@@ -856,10 +863,10 @@ impl Block for Battery {
 
             if status == "Full" || status == "Not charging" || capacity_is_above_full_threshold {
                 self.output.set_icon("bat_full")?;
-                self.output.set_texts(self.full_format.render(&values)?);
+                self.output.set_format(&self.full_format, &values)?;
                 self.output.set_state(State::Good);
             } else {
-                self.output.set_texts(self.format.render(&values)?);
+                self.output.set_format(&self.format, &values)?;
 
                 // Check if the battery is in charging mode and change the state to Good.
                 // Otherwise, adjust the state depeding the power percentance.
@@ -0,0 +1,199 @@
+use std::process::Output;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::subprocess::command;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// The official CLI, which is unlocked for the session in `BW_SESSION`
+    Bw,
+    /// The unofficial CLI, whose agent keeps the vault unlocked
+    Rbw,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Locked,
+    Unlocked,
+    /// Not logged in to any server
+    Unauthenticated,
+}
+
+impl Status {
+    fn name(self) -> &'static str {
+        match self {
+            Status::Locked => "locked",
+            Status::Unlocked => "unlocked",
+            Status::Unauthenticated => "unauthenticated",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct BwStatus {
+    status: String,
+}
+
+pub struct Bitwarden {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    update_interval: Duration,
+    backend: Backend,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct BitwardenConfig {
+    /// Update interval in seconds
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    pub format: FormatTemplate,
+
+    /// `bw` or `rbw`
+    pub backend: Backend,
+}
+
+impl Default for BitwardenConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(30),
+            format: FormatTemplate::default(),
+            backend: Backend::Bw,
+        }
+    }
+}
+
+impl ConfigBlock for Bitwarden {
+    type Config = BitwardenConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        Ok(Bitwarden {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("vault_locked")?,
+            format: block_config.format.with_default("")?,
+            update_interval: block_config.interval,
+            backend: block_config.backend,
+        })
+    }
+}
+
+fn run(program: &str, args: &[&str]) -> Result<Output> {
+    command(program)
+        .args(args)
+        .output()
+        .block_error("bitwarden", &format!("failed to run '{}'", program))
+}
+
+impl Bitwarden {
+    fn status(&self) -> Result<Status> {
+        match self.backend {
+            Backend::Bw => {
+                let output = run("bw", &["status"])?;
+                let status: BwStatus = serde_json::from_slice(&output.stdout)
+                    .block_error("bitwarden", "failed to parse the output of 'bw status'")?;
+                match status.status.as_str() {
+                    "unlocked" => Ok(Status::Unlocked),
+                    "locked" => Ok(Status::Locked),
+                    "unauthenticated" => Ok(Status::Unauthenticated),
+                    other => Err(BlockError(
+                        "bitwarden".to_string(),
+                        format!("unknown status '{}'", other),
+                    )),
+                }
+            }
+            // Fails if the vault is locked or the agent isn't running
+            Backend::Rbw => match run("rbw", &["unlocked"])?.status.success() {
+                true => Ok(Status::Unlocked),
+                false => Ok(Status::Locked),
+            },
+        }
+    }
+
+    fn lock(&mut self) -> Result<()> {
+        let program = match self.backend {
+            Backend::Bw => "bw",
+            Backend::Rbw => "rbw",
+        };
+        let output = run(program, &["lock"])?;
+        if !output.status.success() {
+            return Err(BlockError(
+                "bitwarden".to_string(),
+                format!(
+                    "'{} lock' failed: {}",
+                    program,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+            ));
+        }
+        self.update()?;
+        Ok(())
+    }
+}
+
+impl Block for Bitwarden {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let status = self.status()?;
+        let values = map!(
+            "status" => Value::from_string(status.name().to_string()),
+        );
+        self.text.set_icon(match status {
+            Status::Unlocked => "vault_unlocked",
+            _ => "vault_locked",
+        })?;
+        // A vault that is left unlocked is what the block is there for
+        self.text.set_state(match status {
+            Status::Unlocked => State::Warning,
+            _ => State::Idle,
+        });
+        self.text.set_format(&self.format, &values)?;
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        match event.button {
+            MouseButton::Left => self.lock(),
+            _ => {
+                crate::click::set_unhandled();
+                Ok(())
+            }
+        }
+    }
+
+    fn action(&mut self, action: &str) -> Result<bool> {
+        match action {
+            "lock" => self.lock()?,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
@@ -1,18 +1,20 @@
 use serde_derive::Deserialize;
 
 use std::sync::{Arc, Mutex};
-use std::thread;
-use std::time::{Duration, Instant};
+use std::time::Instant;
 
 use crossbeam_channel::Sender;
-use dbus::{
-    arg::RefArg,
-    ffidisp::stdintf::org_freedesktop_dbus::{ObjectManager, Properties},
-    message::SignalArgs,
+use dbus::ffidisp::stdintf::org_freedesktop_dbus::{
+    ObjectManager, ObjectManagerInterfacesAdded as IA, ObjectManagerInterfacesRemoved as IR,
+    Properties, PropertiesPropertiesChanged as PPC,
 };
+use dbus::ffidisp::BusType;
+use dbus::{arg::RefArg, message::SignalArgs};
 
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::SharedConfig;
+use crate::dbus_connections::{self, Event};
+use crate::deadline;
 use crate::errors::*;
 use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
@@ -25,19 +27,17 @@ pub struct BluetoothDevice {
     pub path: String,
     pub icon: Option<String>,
     pub label: String,
-    con: dbus::ffidisp::Connection,
     available: Arc<Mutex<bool>>,
 }
 
 impl BluetoothDevice {
     pub fn new(mac: String, controller_id: String, label: Option<String>) -> Result<Self> {
-        let con = dbus::ffidisp::Connection::get_private(dbus::ffidisp::BusType::System)
-            .block_error("bluetooth", "Failed to establish D-Bus connection.")?;
+        let con = dbus_connections::system()?;
 
         // Bluez does not provide a convenient way to list devices, so we
         // have to employ a rather verbose workaround.
         let objects = con
-            .with_path("org.bluez", "/", 1000)
+            .with_path("org.bluez", "/", deadline::limit_millis(1000))
             .get_managed_objects()
             .block_error("bluetooth", "Failed to get managed objects from org.bluez.")?;
 
@@ -89,7 +89,7 @@ impl BluetoothDevice {
 
         // Swallow errors, since this is optional.
         let icon: Option<String> = con
-            .with_path("org.bluez", &path, 1000)
+            .with_path("org.bluez", &path, deadline::limit_millis(1000))
             .get("org.bluez.Device1", "Icon")
             .ok();
 
@@ -101,22 +101,23 @@ impl BluetoothDevice {
             path,
             icon,
             label: label.unwrap_or_else(|| "".to_string()),
-            con,
             available,
         })
     }
 
     pub fn battery(&self) -> Option<u8> {
         // Swallow errors here; not all devices implement this API.
-        self.con
-            .with_path("org.bluez", &self.path, 1000)
+        dbus_connections::system()
+            .ok()?
+            .with_path("org.bluez", &self.path, deadline::limit_millis(1000))
             .get("org.bluez.Battery1", "Percentage")
             .ok()
     }
 
     pub fn icon(&self) -> Option<String> {
-        self.con
-            .with_path("org.bluez", &self.path, 1000)
+        dbus_connections::system()
+            .ok()?
+            .with_path("org.bluez", &self.path, deadline::limit_millis(1000))
             .get("org.bluez.Device1", "Icon")
             .ok()
     }
@@ -129,9 +130,13 @@ impl BluetoothDevice {
     }
 
     pub fn connected(&self) -> bool {
-        self.con
-            .with_path("org.bluez", &self.path, 1000)
-            .get("org.bluez.Device1", "Connected")
+        dbus_connections::system()
+            .ok()
+            .and_then(|con| {
+                con.with_path("org.bluez", &self.path, deadline::limit_millis(1000))
+                    .get("org.bluez.Device1", "Connected")
+                    .ok()
+            })
             // In the case that the D-Bus interface missing or responds
             // incorrectly, it seems reasonable to treat the device as "down"
             // instead of nuking the bar. This matches the behaviour elsewhere.
@@ -150,75 +155,61 @@ impl BluetoothDevice {
             dbus::Message::new_method_call("org.bluez", &self.path, "org.bluez.Device1", method)
                 .block_error("bluetooth", "Failed to build D-Bus method.")?;
 
-        let _ = self.con.send(msg);
+        let _ = dbus_connections::system()?.send(msg);
         Ok(())
     }
 
     /// Monitor Bluetooth property changes in a separate thread and send updates
     /// via the `update_request` channel.
-    pub fn monitor(&self, id: usize, update_request: Sender<Task>) {
-        let path_copy1 = self.path.clone();
-        let path_copy2 = self.path.clone();
-        let avail_copy1 = self.available.clone();
-        let avail_copy2 = self.available.clone();
-        let update_request_copy1 = update_request.clone();
-        let update_request_copy2 = update_request.clone();
-        let update_request_copy3 = update_request;
-
-        thread::Builder::new().name("bluetooth".into()).spawn(move || {
-            let c = dbus::blocking::Connection::new_system().unwrap();
-            use dbus::ffidisp::stdintf::org_freedesktop_dbus::ObjectManagerInterfacesAdded as IA;
-            let ma = IA::match_rule(Some(&"org.bluez".into()), None).static_clone();
-            c.add_match(ma, move |ia: IA, _, _| {
-                if ia.object == path_copy1.clone().into() {
-                    let mut avail = avail_copy1.lock().unwrap();
-                    *avail = true;
-                    update_request_copy1
-                        .send(Task {
-                            id,
-                            update_time: Instant::now(),
-                        })
-                        .unwrap();
+    pub fn monitor(&self, id: usize, update_request: Sender<Task>) -> Result<()> {
+        let path = self.path.clone();
+        let available = self.available.clone();
+        let bluez = "org.bluez".into();
+        let rules = vec![
+            IA::match_str(Some(&bluez), None),
+            IR::match_str(Some(&bluez), None),
+            PPC::match_str(Some(&bluez), None),
+        ];
+        dbus_connections::watch("bluetooth", BusType::System, rules, move |event| {
+            match event {
+                // The device may have come or gone while the bus was away
+                Event::Connected => {
+                    let objects = dbus_connections::system().and_then(|con| {
+                        con.with_path("org.bluez", "/", deadline::limit_millis(1000))
+                            .get_managed_objects()
+                            .block_error(
+                                "bluetooth",
+                                "Failed to get managed objects from org.bluez.",
+                            )
+                    });
+                    if let Ok(objects) = objects {
+                        *available.lock().unwrap() = objects.keys().any(|object| **object == *path);
+                    }
                 }
-                true
-            })
-            .unwrap();
-
-            use dbus::ffidisp::stdintf::org_freedesktop_dbus::ObjectManagerInterfacesRemoved as IR;
-            let mr = IR::match_rule(Some(&"org.bluez".into()), None).static_clone();
-            c.add_match(mr, move |ir: IR, _, _| {
-                if ir.object == path_copy2.clone().into() {
-                    let mut avail = avail_copy2.lock().unwrap();
-                    *avail = false;
-                    update_request_copy2
-                        .send(Task {
-                            id,
-                            update_time: Instant::now(),
-                        })
-                        .unwrap();
+                Event::Signal(msg) => {
+                    if let Some(ia) = IA::from_message(msg) {
+                        if *ia.object != *path {
+                            return;
+                        }
+                        *available.lock().unwrap() = true;
+                    } else if let Some(ir) = IR::from_message(msg) {
+                        if *ir.object != *path {
+                            return;
+                        }
+                        *available.lock().unwrap() = false;
+                    } else if PPC::from_message(msg).is_none() {
+                        // TODO: get updated values from the signal message
+                        return;
+                    }
                 }
-                true
-            })
-            .unwrap();
-
-            use dbus::ffidisp::stdintf::org_freedesktop_dbus::PropertiesPropertiesChanged as PPC;
-            let mr = PPC::match_rule(Some(&"org.bluez".into()), None).static_clone();
-            // TODO: get updated values from the signal message
-            c.add_match(mr, move |_ppc: PPC, _, _| {
-                update_request_copy3
-                    .send(Task {
-                        id,
-                        update_time: Instant::now(),
-                    })
-                    .unwrap();
-                true
-            })
-            .unwrap();
-
-            loop {
-                c.process(Duration::from_millis(1000)).unwrap();
             }
-        }).unwrap();
+            update_request
+                .send(Task {
+                    id,
+                    update_time: Instant::now(),
+                })
+                .unwrap();
+        })
     }
 }
 
@@ -273,7 +264,7 @@ impl ConfigBlock for Bluetooth {
             block_config.controller_id,
             block_config.label,
         )?;
-        device.monitor(id, send);
+        device.monitor(id, send)?;
 
         Ok(Bluetooth {
             id,
@@ -329,10 +320,9 @@ impl Block for Bluetooth {
                 });
             }
             if connected {
-                self.output.set_texts(self.format.render(&values)?);
+                self.output.set_format(&self.format, &values)?;
             } else {
-                self.output
-                    .set_texts(self.format_disconnected.render(&values)?);
+                self.output.set_format(&self.format_disconnected, &values)?;
             }
         } else {
             let values = map!(
@@ -340,8 +330,7 @@ impl Block for Bluetooth {
                 "percentage" => Value::from_string("".into()),
             );
             self.output.set_state(State::Idle);
-            self.output
-                .set_texts(self.format_unavailable.render(&values)?);
+            self.output.set_format(&self.format_unavailable, &values)?;
         }
 
         Ok(None)
@@ -0,0 +1,384 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use regex::Regex;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::http;
+use crate::scheduler::Task;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+const BLOCK_NAME: &str = "containers";
+
+/// The containers of the Docker API, which Podman has as well
+const CONTAINERS_URL: &str = "http://localhost/containers/json?all=true";
+
+pub struct Containers {
+    id: usize,
+    /// The counts, or one widget for each container with `per_container`
+    widgets: Vec<TextWidget>,
+    shared_config: SharedConfig,
+    format: FormatTemplate,
+    format_container: FormatTemplate,
+    update_interval: Duration,
+    socket_path: PathBuf,
+    name_regex: Option<Regex>,
+    labels: Vec<String>,
+    per_container: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct ContainersConfig {
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    pub format: FormatTemplate,
+
+    /// The format of a container with `per_container`
+    pub format_container: FormatTemplate,
+
+    /// The socket of Docker or Podman. By default the first that exists of the socket of Docker,
+    /// the one of rootless Podman, and the one of Podman.
+    pub socket_path: Option<String>,
+
+    /// Only watch the containers whose name matches this regex
+    pub name_regex: Option<String>,
+
+    /// Only watch the containers with all of these labels, like `com.example.watch` or
+    /// `com.docker.compose.project=web`
+    pub labels: Vec<String>,
+
+    /// Show a widget for each watched container instead of the counts
+    pub per_container: bool,
+}
+
+impl Default for ContainersConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(5),
+            format: FormatTemplate::default(),
+            format_container: FormatTemplate::default(),
+            socket_path: None,
+            name_regex: None,
+            labels: Vec::new(),
+            per_container: false,
+        }
+    }
+}
+
+/// A container of `/containers/json`
+#[derive(Deserialize, Debug, Clone)]
+struct Container {
+    #[serde(rename = "Names")]
+    names: Vec<String>,
+
+    #[serde(rename = "Image")]
+    image: String,
+
+    /// Like `running`, `paused` or `exited`
+    #[serde(rename = "State")]
+    state: String,
+
+    /// Like `Up 2 hours (healthy)`
+    #[serde(rename = "Status")]
+    status: String,
+
+    #[serde(rename = "Labels", default)]
+    labels: Option<HashMap<String, String>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Health {
+    /// The container has no healthcheck
+    None,
+    Starting,
+    Healthy,
+    Unhealthy,
+}
+
+impl Health {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Health::None => "none",
+            Health::Starting => "starting",
+            Health::Healthy => "healthy",
+            Health::Unhealthy => "unhealthy",
+        }
+    }
+}
+
+impl Container {
+    /// Without the `/` of the API
+    fn name(&self) -> &str {
+        self.names
+            .first()
+            .map_or("", |name| name.trim_start_matches('/'))
+    }
+
+    /// The health from the status, which the list of containers has instead of the healthcheck
+    fn health(&self) -> Health {
+        if self.status.contains("(unhealthy)") {
+            Health::Unhealthy
+        } else if self.status.contains("(healthy)") {
+            Health::Healthy
+        } else if self.status.contains("(health: starting)") {
+            Health::Starting
+        } else {
+            Health::None
+        }
+    }
+
+    /// Whether the container has the label `label`, which is a key or a `key=value`
+    fn has_label(&self, label: &str) -> bool {
+        let labels = match &self.labels {
+            Some(labels) => labels,
+            None => return false,
+        };
+        match label.split_once('=') {
+            Some((key, value)) => labels.get(key).map(String::as_str) == Some(value),
+            None => labels.contains_key(label),
+        }
+    }
+}
+
+/// The counts of the containers, like the placeholders of `format`
+#[derive(Debug, Default, PartialEq)]
+struct Counts {
+    total: usize,
+    running: usize,
+    paused: usize,
+    stopped: usize,
+    healthy: usize,
+    unhealthy: usize,
+}
+
+fn count(containers: &[&Container]) -> Counts {
+    let mut counts = Counts {
+        total: containers.len(),
+        ..Counts::default()
+    };
+    for container in containers {
+        match container.state.as_str() {
+            "running" => counts.running += 1,
+            "paused" => counts.paused += 1,
+            _ => counts.stopped += 1,
+        }
+        match container.health() {
+            Health::Healthy => counts.healthy += 1,
+            Health::Unhealthy => counts.unhealthy += 1,
+            _ => (),
+        }
+    }
+    counts
+}
+
+fn default_socket_path() -> PathBuf {
+    let mut paths = vec![PathBuf::from("/var/run/docker.sock")];
+    if let Some(runtime_dir) = std::env::var_os("XDG_RUNTIME_DIR") {
+        paths.push(Path::new(&runtime_dir).join("podman/podman.sock"));
+    }
+    paths.push(PathBuf::from("/run/podman/podman.sock"));
+    let first = paths[0].clone();
+    paths
+        .into_iter()
+        .find(|path| path.exists())
+        .unwrap_or(first)
+}
+
+impl Containers {
+    /// The containers that match `name_regex` and `labels`, sorted by name
+    fn watched<'a>(&self, containers: &'a [Container]) -> Vec<&'a Container> {
+        let mut watched: Vec<&Container> = containers
+            .iter()
+            .filter(|container| match &self.name_regex {
+                Some(regex) => regex.is_match(container.name()),
+                None => true,
+            })
+            .filter(|container| self.labels.iter().all(|label| container.has_label(label)))
+            .collect();
+        watched.sort_by(|a, b| a.name().cmp(b.name()));
+        watched
+    }
+
+    fn widget(&mut self, instance: usize) -> Result<&mut TextWidget> {
+        while self.widgets.len() <= instance {
+            let widget = TextWidget::new(self.id, self.widgets.len(), self.shared_config.clone())
+                .with_icon("docker")?;
+            self.widgets.push(widget);
+        }
+        Ok(&mut self.widgets[instance])
+    }
+}
+
+impl ConfigBlock for Containers {
+    type Config = ContainersConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let socket_path = match &block_config.socket_path {
+            Some(path) => PathBuf::from(
+                shellexpand::full(path)
+                    .configuration_error("failed to expand the path of 'socket_path'")?
+                    .to_string(),
+            ),
+            None => default_socket_path(),
+        };
+        Ok(Containers {
+            id,
+            widgets: Vec::new(),
+            shared_config,
+            format: block_config.format.with_default("{running}")?,
+            format_container: block_config.format_container.with_default("{name}")?,
+            update_interval: block_config.interval,
+            socket_path,
+            name_regex: block_config
+                .name_regex
+                .map(|regex| Regex::new(&regex))
+                .transpose()
+                .configuration_error("invalid name regex")?,
+            labels: block_config.labels,
+            per_container: block_config.per_container,
+        })
+    }
+}
+
+impl Block for Containers {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let response = http::http_get_socket_json(self.socket_path.clone(), CONTAINERS_URL)
+            .block_error(
+                BLOCK_NAME,
+                &format!("failed to talk to {}", self.socket_path.display()),
+            )?;
+        let containers: Vec<Container> = serde_json::from_value(response.content)
+            .block_error(BLOCK_NAME, "unexpected list of containers")?;
+        let watched = self.watched(&containers);
+
+        if !self.per_container {
+            let counts = count(&watched);
+            let values = map!(
+                "total" => Value::from_integer(counts.total as i64),
+                "running" => Value::from_integer(counts.running as i64),
+                "paused" => Value::from_integer(counts.paused as i64),
+                "stopped" => Value::from_integer(counts.stopped as i64),
+                "healthy" => Value::from_integer(counts.healthy as i64),
+                "unhealthy" => Value::from_integer(counts.unhealthy as i64),
+            );
+            let format = self.format.clone();
+            let widget = self.widget(0)?;
+            widget.set_format(&format, &values)?;
+            widget.set_state(match counts.unhealthy {
+                0 => State::Idle,
+                _ => State::Warning,
+            });
+            return Ok(Some(self.update_interval.into()));
+        }
+
+        let format = self.format_container.clone();
+        self.widgets.truncate(watched.len());
+        for (instance, container) in watched.iter().enumerate() {
+            let health = container.health();
+            let values = map!(
+                "name" => Value::from_string(container.name().to_string()),
+                "image" => Value::from_string(container.image.clone()),
+                "state" => Value::from_string(container.state.clone()),
+                "health" => Value::from_string(health.as_str().to_string()),
+                "status" => Value::from_string(container.status.clone()),
+            );
+            let widget = self.widget(instance)?;
+            widget.set_format(&format, &values)?;
+            widget.set_state(match (health, container.state.as_str()) {
+                (Health::Unhealthy, _) => State::Warning,
+                (_, "running") => State::Idle,
+                (_, "paused") => State::Info,
+                _ => State::Critical,
+            });
+        }
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        self.widgets
+            .iter()
+            .map(|widget| widget as &dyn I3BarWidget)
+            .collect()
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn containers_json() {
+        let containers: Vec<Container> = serde_json::from_value(serde_json::json!([
+            {
+                "Id": "8dfafdbc3a40",
+                "Names": ["/web"],
+                "Image": "nginx:latest",
+                "State": "running",
+                "Status": "Up 2 hours (unhealthy)",
+                "Labels": { "com.docker.compose.project": "site" },
+            },
+            {
+                "Names": ["/db"],
+                "Image": "postgres:16",
+                "State": "running",
+                "Status": "Up 2 hours (healthy)",
+                "Labels": { "com.docker.compose.project": "site", "backup": "" },
+            },
+            {
+                "Names": ["/cache"],
+                "Image": "redis",
+                "State": "paused",
+                "Status": "Up 5 minutes (Paused)",
+                "Labels": null,
+            },
+            {
+                "Names": ["/build"],
+                "Image": "rust",
+                "State": "exited",
+                "Status": "Exited (0) 3 days ago",
+            },
+        ]))
+        .unwrap();
+        let all: Vec<&Container> = containers.iter().collect();
+        assert_eq!(
+            count(&all),
+            Counts {
+                total: 4,
+                running: 2,
+                paused: 1,
+                stopped: 1,
+                healthy: 1,
+                unhealthy: 1,
+            }
+        );
+        assert_eq!(containers[0].name(), "web");
+        assert_eq!(containers[0].health(), Health::Unhealthy);
+        assert_eq!(containers[2].health(), Health::None);
+        assert!(containers[0].has_label("com.docker.compose.project=site"));
+        assert!(!containers[0].has_label("com.docker.compose.project=shop"));
+        assert!(containers[1].has_label("backup"));
+        assert!(!containers[2].has_label("backup"));
+        assert!(!containers[3].has_label("backup"));
+    }
+}
@@ -198,7 +198,7 @@ impl Block for Cpu {
             );
         }
 
-        self.output.set_texts(self.format.render(&values)?);
+        self.output.set_format(&self.format, &values)?;
 
         Ok(Some(self.update_interval.into()))
     }
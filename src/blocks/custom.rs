@@ -1,6 +1,5 @@
 use std::env;
 use std::iter::{Cycle, Peekable};
-use std::process::Command;
 use std::thread;
 use std::time::{Duration, Instant};
 use std::vec;
@@ -11,8 +10,8 @@ use crate::de::deserialize_update;
 use crate::errors::*;
 use crate::protocol::i3bar_event::I3BarEvent;
 use crate::scheduler::Task;
-use crate::signals::convert_to_valid_signal;
-use crate::subprocess::spawn_child_async;
+use crate::subprocess::{command, spawn_child_async};
+use crate::util::read_inotify_events;
 use crate::widgets::text::TextWidget;
 use crate::widgets::{I3BarWidget, State};
 use crossbeam_channel::Sender;
@@ -26,7 +25,6 @@ pub struct Custom {
     command: Option<String>,
     on_click: Option<String>,
     cycle: Option<Peekable<Cycle<vec::IntoIter<String>>>>,
-    signal: Option<i32>,
     tx_update_request: Sender<Task>,
     pub json: bool,
     hide_when_empty: bool,
@@ -47,9 +45,6 @@ pub struct CustomConfig {
     /// Commands to execute and change when the button is clicked
     pub cycle: Option<Vec<String>>,
 
-    /// Signal to update upon reception
-    pub signal: Option<i32>,
-
     /// Files to watch for modifications and trigger update
     pub watch_files: Option<Vec<String>>,
 
@@ -68,7 +63,6 @@ impl Default for CustomConfig {
             interval: Update::Every(Duration::from_secs(10)),
             command: None,
             cycle: None,
-            signal: None,
             watch_files: None,
             json: false,
             hide_when_empty: false,
@@ -93,7 +87,6 @@ impl ConfigBlock for Custom {
             command: None,
             on_click: None,
             cycle: None,
-            signal: None,
             tx_update_request: tx,
             json: block_config.json,
             hide_when_empty: block_config.hide_when_empty,
@@ -101,11 +94,6 @@ impl ConfigBlock for Custom {
             shell: block_config.shell,
         };
 
-        if let Some(signal) = block_config.signal {
-            // If the signal is not in the valid range we return an error
-            custom.signal = Some(convert_to_valid_signal(signal)?);
-        };
-
         if let Some(paths) = block_config.watch_files {
             let tx_inotify = custom.tx_update_request.clone();
             let mut notify = Inotify::init().expect("Failed to start inotify");
@@ -130,8 +118,7 @@ impl ConfigBlock for Custom {
                 .spawn(move || {
                     let mut buffer = [0; 1024];
                     loop {
-                        let mut events = notify
-                            .read_events_blocking(&mut buffer)
+                        let mut events = read_inotify_events(id, &mut notify, &mut buffer)
                             .expect("Error while reading inotify events");
 
                         if events.any(|event| event.mask.contains(EventMask::MODIFY)) {
@@ -200,7 +187,7 @@ impl Block for Custom {
             .or_else(|| self.command.clone())
             .unwrap_or_else(|| "".to_owned());
 
-        let raw_output = match Command::new(&self.shell)
+        let raw_output = match command(&self.shell)
             .args(&["-c", &command_str])
             .output()
             .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_owned())
@@ -237,18 +224,6 @@ impl Block for Custom {
         }
     }
 
-    fn signal(&mut self, signal: i32) -> Result<()> {
-        if let Some(sig) = self.signal {
-            if sig == signal {
-                self.tx_update_request.send(Task {
-                    id: self.id,
-                    update_time: Instant::now(),
-                })?;
-            }
-        }
-        Ok(())
-    }
-
     fn click(&mut self, _e: &I3BarEvent) -> Result<()> {
         let mut update = false;
 
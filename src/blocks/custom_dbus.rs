@@ -11,8 +11,10 @@ use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::SharedConfig;
+use crate::dbus_connections;
 use crate::de::deserialize_opt_duration;
 use crate::errors::*;
+use crate::registry;
 use crate::scheduler::Task;
 use crate::widgets::text::TextWidget;
 use crate::widgets::{I3BarWidget, State};
@@ -73,69 +75,13 @@ impl ConfigBlock for CustomDBus {
         let name = block_config.name;
         thread::Builder::new()
             .name("custom_dbus".into())
-            .spawn(move || {
-                let c = LocalConnection::new_session()
-                    .expect("Failed to establish DBus connection in thread");
-                c.request_name("i3.status.rs", false, true, false)
-                    .expect("Failed to request bus name");
-
-                // TODO: better to rewrite this to use a property?
-                let f = Factory::new_fn::<()>();
-                let tree = f
-                    .tree(())
-                    .add(
-                        f.object_path(format!("/{}", name), ())
-                            .introspectable()
-                            .add(
-                                f.interface("i3.status.rs", ()).add_m(
-                                    f.method("SetStatus", (), move |m| {
-                                        // This is the callback that will be called when another peer on the bus calls our method.
-                                        // the callback receives "MethodInfo" struct and can return either an error, or a list of
-                                        // messages to send back.
-
-                                        let args = m.msg.get3::<&str, &str, &str>();
-                                        let mut status = status_original.lock().unwrap();
-
-                                        if let Some(new_content) = args.0 {
-                                            status.content = String::from(new_content);
-                                        }
-
-                                        if let Some(new_icon) = args.1 {
-                                            status.icon = String::from(new_icon);
-                                        }
-
-                                        if let Some(new_state) = args.2 {
-                                            status.state =
-                                                State::from_str(new_state).unwrap_or(status.state);
-                                        }
-
-                                        // Tell block to update now.
-                                        send.send(Task {
-                                            id,
-                                            update_time: Instant::now(),
-                                        })
-                                        .unwrap();
-
-                                        Ok(vec![m.msg.method_return()])
-                                    })
-                                    // We also add the signal to the interface. This is mainly for introspection.
-                                    .in_args(vec![
-                                        ("name", Signature::make::<&str>()),
-                                        ("icon", Signature::make::<&str>()),
-                                        ("state", Signature::make::<&str>()),
-                                    ]),
-                                ),
-                            ),
-                    )
-                    .add(f.object_path("/", ()).introspectable());
-
-                // We add the tree to the connection so that incoming method calls will be handled.
-                tree.start_receive(&c);
-
-                // Serve clients forever.
-                loop {
-                    c.process(Duration::from_millis(1000)).unwrap();
+            .spawn(move || loop {
+                // The bus may have been restarted, which takes the name and the objects with it
+                if let Err(e) = serve(id, &name, &status_original, &send) {
+                    tracing::warn!("custom_dbus: {}", e);
                 }
+                registry::heartbeat(id);
+                thread::sleep(dbus_connections::RECONNECT_INTERVAL);
             })
             .unwrap();
 
@@ -193,3 +139,78 @@ impl Block for CustomDBus {
         vec![&self.text]
     }
 }
+
+/// Serves `SetStatus` on the session bus until the connection to the bus is lost. The block owns a
+/// name on the bus, so it has a connection of its own instead of the shared one.
+fn serve(
+    id: usize,
+    name: &str,
+    status_original: &Arc<Mutex<CustomDBusStatus>>,
+    send: &Sender<Task>,
+) -> Result<()> {
+    let c = LocalConnection::new_session()
+        .block_error("custom_dbus", "Failed to establish DBus connection")?;
+    c.request_name("i3.status.rs", false, true, false)
+        .block_error("custom_dbus", "Failed to request bus name")?;
+
+    let status_original = status_original.clone();
+    let send = send.clone();
+    // TODO: better to rewrite this to use a property?
+    let f = Factory::new_fn::<()>();
+    let tree = f
+        .tree(())
+        .add(
+            f.object_path(format!("/{}", name), ())
+                .introspectable()
+                .add(
+                    f.interface("i3.status.rs", ()).add_m(
+                        f.method("SetStatus", (), move |m| {
+                            // This is the callback that will be called when another peer on the bus calls our method.
+                            // the callback receives "MethodInfo" struct and can return either an error, or a list of
+                            // messages to send back.
+
+                            let args = m.msg.get3::<&str, &str, &str>();
+                            let mut status = status_original.lock().unwrap();
+
+                            if let Some(new_content) = args.0 {
+                                status.content = String::from(new_content);
+                            }
+
+                            if let Some(new_icon) = args.1 {
+                                status.icon = String::from(new_icon);
+                            }
+
+                            if let Some(new_state) = args.2 {
+                                status.state = State::from_str(new_state).unwrap_or(status.state);
+                            }
+
+                            // Tell block to update now.
+                            send.send(Task {
+                                id,
+                                update_time: Instant::now(),
+                            })
+                            .unwrap();
+
+                            Ok(vec![m.msg.method_return()])
+                        })
+                        // We also add the signal to the interface. This is mainly for introspection.
+                        .in_args(vec![
+                            ("name", Signature::make::<&str>()),
+                            ("icon", Signature::make::<&str>()),
+                            ("state", Signature::make::<&str>()),
+                        ]),
+                    ),
+                ),
+        )
+        .add(f.object_path("/", ()).introspectable());
+
+    // We add the tree to the connection so that incoming method calls will be handled.
+    tree.start_receive(&c);
+
+    // Serve clients until the bus goes away.
+    loop {
+        registry::heartbeat(id);
+        c.process(registry::HEARTBEAT_INTERVAL)
+            .block_error("custom_dbus", "lost the connection to the bus")?;
+    }
+}
@@ -11,6 +11,7 @@ use crate::de::deserialize_duration;
 use crate::errors::*;
 use crate::formatting::FormatTemplate;
 use crate::formatting::{prefix::Prefix, value::Value};
+use crate::netlink::{self, Source};
 use crate::scheduler::Task;
 use crate::widgets::text::TextWidget;
 use crate::widgets::{I3BarWidget, State};
@@ -130,8 +131,10 @@ impl ConfigBlock for DiskSpace {
         id: usize,
         block_config: Self::Config,
         shared_config: SharedConfig,
-        _tx_update_request: Sender<Task>,
+        tx_update_request: Sender<Task>,
     ) -> Result<Self> {
+        // Mounting and unmounting updates the block right away
+        netlink::subscribe(Source::Storage, id, tx_update_request);
         let icon = shared_config.get_icon("disk_drive")?;
 
         Ok(DiskSpace {
@@ -203,7 +206,7 @@ impl Block for DiskSpace {
             //TODO remove
             "alias" => Value::from_string(self.alias.clone()),
         );
-        self.disk_space.set_texts(self.format.render(&values)?);
+        self.disk_space.set_format(&self.format, &values)?;
 
         // Send percentage to alert check if we don't want absolute alerts
         let alert_val = if self.alert_absolute {
@@ -1,4 +1,3 @@
-use std::process::Command;
 use std::time::Duration;
 
 use crossbeam_channel::Sender;
@@ -12,6 +11,7 @@ use crate::errors::*;
 use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
 use crate::scheduler::Task;
+use crate::subprocess::command;
 use crate::widgets::text::TextWidget;
 use crate::widgets::{I3BarWidget, State};
 
@@ -109,7 +109,7 @@ impl ConfigBlock for Dnf {
 
 fn get_updates_list() -> Result<String> {
     String::from_utf8(
-        Command::new("sh")
+        command("sh")
             .env("LC_LANG", "C")
             .args(&["-c", "dnf check-update -q --skip-broken"])
             .output()
@@ -0,0 +1,113 @@
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use dbus::ffidisp::stdintf::org_freedesktop_dbus::Properties;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::dbus_connections;
+use crate::de::deserialize_duration;
+use crate::deadline;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::scheduler::Task;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+const LOGIND: &str = "org.freedesktop.login1";
+const MANAGER: &str = "org.freedesktop.login1.Manager";
+
+pub struct Dock {
+    id: usize,
+    text: TextWidget,
+    format_docked: FormatTemplate,
+    format_undocked: FormatTemplate,
+    update_interval: Duration,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct DockConfig {
+    /// Format while logind considers the machine docked
+    pub format_docked: FormatTemplate,
+
+    pub format_undocked: FormatTemplate,
+
+    /// logind doesn't signal changes of the dock or the lid, so they are polled
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+}
+
+impl Default for DockConfig {
+    fn default() -> Self {
+        Self {
+            format_docked: FormatTemplate::default(),
+            format_undocked: FormatTemplate::default(),
+            interval: Duration::from_secs(5),
+        }
+    }
+}
+
+impl ConfigBlock for Dock {
+    type Config = DockConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        Ok(Dock {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("dock")?,
+            format_docked: block_config.format_docked.with_default("docked")?,
+            format_undocked: block_config.format_undocked.with_default("undocked")?,
+            update_interval: block_config.interval,
+        })
+    }
+}
+
+impl Block for Dock {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let con = dbus_connections::system()?;
+        let manager = con.with_path(
+            LOGIND,
+            "/org/freedesktop/login1",
+            deadline::limit_millis(1000),
+        );
+        // Docked with a docking station or with more than one display
+        let docked: bool = manager
+            .get(MANAGER, "Docked")
+            .block_error("dock", "failed to get the dock state from logind")?;
+        // Machines without a lid switch don't have the property
+        let lid = match manager.get::<bool>(MANAGER, "LidClosed") {
+            Ok(true) => "closed",
+            Ok(false) => "open",
+            Err(_) => "",
+        };
+        let values = map!(
+            "lid" => Value::from_string(lid.to_string()),
+        );
+        let format = match docked {
+            true => &self.format_docked,
+            false => &self.format_undocked,
+        };
+        self.text.set_state(match docked {
+            true => State::Good,
+            false => State::Idle,
+        });
+        self.text.set_format(format, &values)?;
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
@@ -125,7 +125,7 @@ impl Block for Docker {
             "images" =>  Value::from_integer(status.images),
         );
 
-        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_format(&self.format, &values)?;
         self.text.set_state(State::Idle);
 
         Ok(Some(self.update_interval.into()))
@@ -1,18 +1,20 @@
-use std::thread;
 use std::time::Instant;
 
 use crossbeam_channel::Sender;
-use dbus::ffidisp::{BusType, Connection, ConnectionItem};
+use dbus::ffidisp::BusType;
 use serde::{Deserialize as des, Serialize as ser};
 use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
+use crate::cache::{self, Cached};
 use crate::config::SharedConfig;
+use crate::dbus_connections;
 use crate::errors::*;
 use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
 use crate::http;
 use crate::scheduler::Task;
+use crate::services::Service;
 use crate::util::country_flag_from_iso_code;
 use crate::widgets::text::TextWidget;
 use crate::widgets::{I3BarWidget, State};
@@ -60,6 +62,8 @@ pub struct ExternalIP {
     format: FormatTemplate,
     refresh_interval_success: u64,
     refresh_interval_failure: u64,
+    /// The address of the last successful update, also shown while the network is down
+    last: Option<Cached<IPAddressInfo>>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -83,6 +87,58 @@ impl Default for ExternalIPConfig {
     }
 }
 
+impl ExternalIP {
+    /// Shows the last address that was fetched, if there is one. If it's `stale`, its age is
+    /// shown in `{stale}`.
+    fn show_last(&mut self, stale: bool) -> Result<bool> {
+        let last = match &self.last {
+            Some(last) => last,
+            None => return Ok(false),
+        };
+        let ip_info = &last.data;
+        let flag = country_flag_from_iso_code(ip_info.country_code.as_str());
+        let values = map!(
+            "ip" => Value::from_string (ip_info.ip.clone()),
+            "version" => Value::from_string (ip_info.version.clone()),
+            "city" => Value::from_string (ip_info.city.clone()),
+            "region" => Value::from_string (ip_info.region.clone()),
+            "region_code" => Value::from_string (ip_info.region_code.clone()),
+            "country" => Value::from_string (ip_info.country.clone()),
+            "country_name" => Value::from_string (ip_info.country_name.clone()),
+            "country_code" => Value::from_string (ip_info.country_code.clone()),
+            "country_code_iso3" => Value::from_string (ip_info.country_code_iso3.clone()),
+            "country_capital" => Value::from_string (ip_info.country_capital.clone()),
+            "country_tld" => Value::from_string (ip_info.country_tld.clone()),
+            "continent_code" => Value::from_string (ip_info.continent_code.clone()),
+            "in_eu" => Value::from_boolean (ip_info.in_eu),
+            "postal" => Value::from_string (ip_info.postal.clone().unwrap_or_else(|| "No postal code".to_string())),
+            "latitude" => Value::from_float (ip_info.latitude),
+            "longitude" => Value::from_float (ip_info.longitude),
+            "timezone" => Value::from_string (ip_info.timezone.clone()),
+            "utc_offset" => Value::from_string (ip_info.utc_offset.clone()),
+            "country_calling_code" => Value::from_string (ip_info.country_calling_code.clone()),
+            "currency" => Value::from_string (ip_info.currency.clone()),
+            "currency_name" => Value::from_string (ip_info.currency_name.clone()),
+            "languages" => Value::from_string (ip_info.languages.clone()),
+            "country_area" => Value::from_float (ip_info.country_area),
+            "country_population" => Value::from_float (ip_info.country_population),
+            "asn" => Value::from_string (ip_info.asn.clone()),
+            "org" => Value::from_string (ip_info.org.clone()),
+            "country_flag" => Value::from_string(flag),
+            "stale" => Value::from_string(match stale {
+                true => last.age(),
+                false => String::new(),
+            }),
+        );
+        self.output.set_format(&self.format, &values)?;
+        self.output.set_state(match stale {
+            true => State::Warning,
+            false => State::Idle,
+        });
+        Ok(true)
+    }
+}
+
 impl ConfigBlock for ExternalIP {
     type Config = ExternalIPConfig;
 
@@ -93,50 +149,36 @@ impl ConfigBlock for ExternalIP {
         send: Sender<Task>,
     ) -> Result<Self> {
         if block_config.with_network_manager {
-            thread::Builder::new()
-                .name("externalip".into())
-                .spawn(move || {
-                    let c = Connection::get_private(BusType::System).unwrap();
-                    c.add_match(
-                        "type='signal',\
+            dbus_connections::watch(
+                "externalip",
+                BusType::System,
+                vec![
+                    "type='signal',\
                     path='/org/freedesktop/NetworkManager',\
                     interface='org.freedesktop.DBus.Properties',\
-                    member='PropertiesChanged'",
-                    )
-                    .unwrap();
-                    c.add_match(
-                        "type='signal',\
+                    member='PropertiesChanged'"
+                        .to_string(),
+                    "type='signal',\
                     path_namespace='/org/freedesktop/NetworkManager/ActiveConnection',\
                     interface='org.freedesktop.DBus.Properties',\
-                    member='PropertiesChanged'",
-                    )
-                    .unwrap();
-                    c.add_match(
-                        "type='signal',\
+                    member='PropertiesChanged'"
+                        .to_string(),
+                    "type='signal',\
                     path_namespace='/org/freedesktop/NetworkManager/IP4Config',\
                     interface='org.freedesktop.DBus',\
-                    member='PropertiesChanged'",
-                    )
-                    .unwrap();
-
-                    loop {
-                        let timeout = 300_000;
-
-                        for event in c.iter(timeout) {
-                            match event {
-                                ConnectionItem::Nothing => (),
-                                _ => {
-                                    send.send(Task {
-                                        id,
-                                        update_time: Instant::now(),
-                                    })
-                                    .unwrap();
-                                }
-                            }
-                        }
-                    }
-                })
-                .unwrap();
+                    member='PropertiesChanged'"
+                        .to_string(),
+                ],
+                // The network may have changed while the bus was away, so reconnecting updates
+                // the block as well
+                move |_| {
+                    send.send(Task {
+                        id,
+                        update_time: Instant::now(),
+                    })
+                    .unwrap()
+                },
+            )?;
         }
         Ok(ExternalIP {
             id,
@@ -144,8 +186,13 @@ impl ConfigBlock for ExternalIP {
             format: block_config.format.with_default("{ip} {country_flag}")?,
             refresh_interval_success: block_config.interval,
             refresh_interval_failure: block_config.error_interval,
+            last: cache::load(BLOCK_NAME),
         })
     }
+
+    fn default_depends_on() -> &'static [Service] {
+        &[Service::Network]
+    }
 }
 
 impl Block for ExternalIP {
@@ -154,66 +201,36 @@ impl Block for ExternalIP {
     }
 
     fn update(&mut self) -> Result<Option<Update>> {
-        let (external_ip, success) = {
-            let ip_info: Result<IPAddressInfo> =
-                match http::http_get_json(API_ENDPOINT, Some(Duration::from_secs(3)), vec![]) {
-                    Ok(ip_info_json) => serde_json::from_value(ip_info_json.content)
-                        .block_error(BLOCK_NAME, "Failed to decode JSON"),
-                    _ => Err(BlockError(
-                        BLOCK_NAME.to_string(),
-                        "Failed to contact API".to_string(),
-                    )),
-                };
-            match ip_info {
-                Ok(ip_info) => match ip_info.error {
-                    false => {
-                        self.output.set_state(State::Idle);
-                        let flag = country_flag_from_iso_code(ip_info.country_code.as_str());
-                        let values = map!(
-                            "ip" => Value::from_string (ip_info.ip),
-                            "version" => Value::from_string (ip_info.version),
-                            "city" => Value::from_string (ip_info.city),
-                            "region" => Value::from_string (ip_info.region),
-                            "region_code" => Value::from_string (ip_info.region_code),
-                            "country" => Value::from_string (ip_info.country),
-                            "country_name" => Value::from_string (ip_info.country_name),
-                            "country_code" => Value::from_string (ip_info.country_code),
-                            "country_code_iso3" => Value::from_string (ip_info.country_code_iso3),
-                            "country_capital" => Value::from_string (ip_info.country_capital),
-                            "country_tld" => Value::from_string (ip_info.country_tld),
-                            "continent_code" => Value::from_string (ip_info.continent_code),
-                            "in_eu" => Value::from_boolean (ip_info.in_eu),
-                            "postal" => Value::from_string (ip_info.postal.unwrap_or_else(|| "No postal code".to_string())),
-                            "latitude" => Value::from_float (ip_info.latitude),
-                            "longitude" => Value::from_float (ip_info.longitude),
-                            "timezone" => Value::from_string (ip_info.timezone),
-                            "utc_offset" => Value::from_string (ip_info.utc_offset),
-                            "country_calling_code" => Value::from_string (ip_info.country_calling_code),
-                            "currency" => Value::from_string (ip_info.currency),
-                            "currency_name" => Value::from_string (ip_info.currency_name),
-                            "languages" => Value::from_string (ip_info.languages),
-                            "country_area" => Value::from_float (ip_info.country_area),
-                            "country_population" => Value::from_float (ip_info.country_population),
-                            "asn" => Value::from_string (ip_info.asn),
-                            "org" => Value::from_string (ip_info.org),
-                            "country_flag" => Value::from_string(flag),
-                        );
-                        let s = self.format.render(&values)?;
-                        (s.0, true)
-                    }
-                    true => {
-                        self.output.set_state(State::Critical);
-                        (format!("Error: {}", ip_info.reason), false)
-                    }
-                },
-                Err(err) => {
-                    self.output.set_state(State::Critical);
-                    (err.to_string(), false)
-                }
+        let ip_info: Result<IPAddressInfo> =
+            match http::http_get_json(API_ENDPOINT, Some(Duration::from_secs(3)), vec![]) {
+                Ok(ip_info_json) => serde_json::from_value(ip_info_json.content)
+                    .block_error(BLOCK_NAME, "Failed to decode JSON"),
+                _ => Err(BlockError(
+                    BLOCK_NAME.to_string(),
+                    "Failed to contact API".to_string(),
+                )),
+            };
+        let success = match ip_info {
+            Ok(ip_info) if !ip_info.error => {
+                let last = Cached::new(ip_info);
+                cache::store(BLOCK_NAME, &last);
+                self.last = Some(last);
+                self.show_last(false)?;
+                true
+            }
+            _ if self.show_last(true)? => false,
+            Ok(ip_info) => {
+                self.output.set_state(State::Critical);
+                self.output.set_text(format!("Error: {}", ip_info.reason));
+                false
+            }
+            Err(err) => {
+                self.output.set_state(State::Critical);
+                self.output.set_text(err.to_string());
+                false
             }
         };
 
-        self.output.set_text(external_ip);
         match success {
             /* The external IP address can change without triggering a
              * notification (for example a refresh between the router and
@@ -227,6 +244,10 @@ impl Block for ExternalIP {
         }
     }
 
+    fn update_offline(&mut self) -> Result<bool> {
+        self.show_last(true)
+    }
+
     fn view(&self) -> Vec<&dyn I3BarWidget> {
         vec![&self.output]
     }
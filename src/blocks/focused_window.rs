@@ -10,6 +10,7 @@ use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::SharedConfig;
 use crate::errors::*;
 use crate::formatting::value::Value;
+use crate::formatting::width;
 use crate::formatting::FormatTemplate;
 use crate::scheduler::Task;
 use crate::util::escape_pango_text;
@@ -203,13 +204,13 @@ impl Block for FocusedWindow {
             .lock()
             .block_error("focused_window", "failed to acquire lock")?)
         .clone();
-        marks_string = marks_string.chars().take(self.max_width).collect();
+        marks_string.truncate(width::truncate(&marks_string, self.max_width).len());
         let mut title_string = (*self
             .title
             .lock()
             .block_error("focused_window", "failed to acquire lock")?)
         .clone();
-        title_string = title_string.chars().take(self.max_width).collect();
+        title_string.truncate(width::truncate(&title_string, self.max_width).len());
         let out_str = match self.show_marks {
             MarksType::None => &title_string,
             _ => {
@@ -226,7 +227,7 @@ impl Block for FocusedWindow {
             "title" => Value::from_string(escape_pango_text(&title_string))
         );
 
-        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_format(&self.format, &values)?;
 
         Ok(None)
     }
@@ -14,6 +14,7 @@ use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
 use crate::http;
 use crate::scheduler::Task;
+use crate::services::Service;
 use crate::widgets::{text::TextWidget, I3BarWidget, State};
 
 const GITHUB_TOKEN_ENV: &str = "I3RS_GITHUB_TOKEN";
@@ -105,6 +106,10 @@ impl ConfigBlock for Github {
             critical: block_config.critical,
         })
     }
+
+    fn default_depends_on() -> &'static [Service] {
+        &[Service::Network]
+    }
 }
 
 impl Block for Github {
@@ -147,7 +152,7 @@ impl Block for Github {
             "team_mention" =>     Value::from_integer(*aggregations.get("team_mention").unwrap_or(&default) as i64),
         );
 
-        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_format(&self.format, &values)?;
 
         self.text.set_state(get_state(
             &self.critical,
@@ -0,0 +1,345 @@
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::scheduler::Task;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long to wait before connecting again after gpsd went away
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum GpsdUnits {
+    Metric,
+    Imperial,
+}
+
+/// The latest reports of gpsd, kept up to date by a thread that watches them
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Fix {
+    /// 0 or 1 without a fix, 2 for a 2D fix and 3 for a 3D fix
+    mode: u64,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    /// In meters
+    altitude: Option<f64>,
+    /// In meters per second
+    speed: Option<f64>,
+    /// In degrees from true north
+    track: Option<f64>,
+    satellites: u64,
+    satellites_used: u64,
+    device: String,
+    /// Why there is no connection to gpsd
+    error: Option<String>,
+}
+
+impl Fix {
+    /// Applies a `TPV` or `SKY` report. Reports of other devices than `device` are ignored.
+    fn apply(&mut self, report: &serde_json::Value, device: Option<&str>) {
+        let report_device = report["device"].as_str().unwrap_or_default();
+        if matches!(device, Some(device) if device != report_device) {
+            return;
+        }
+        match report["class"].as_str() {
+            Some("TPV") => {
+                self.mode = report["mode"].as_u64().unwrap_or(0);
+                self.latitude = report["lat"].as_f64();
+                self.longitude = report["lon"].as_f64();
+                // Newer versions of gpsd report the altitude above the mean sea level separately
+                self.altitude = report["altMSL"].as_f64().or_else(|| report["alt"].as_f64());
+                self.speed = report["speed"].as_f64();
+                self.track = report["track"].as_f64();
+                self.device = report_device.to_string();
+            }
+            Some("SKY") => {
+                if let Some(satellites) = report["satellites"].as_array() {
+                    self.satellites = satellites.len() as u64;
+                    self.satellites_used = satellites
+                        .iter()
+                        .filter(|satellite| satellite["used"].as_bool() == Some(true))
+                        .count() as u64;
+                } else if let Some(satellites) = report["nSat"].as_u64() {
+                    // Reports without the list of satellites have only the numbers
+                    self.satellites = satellites;
+                    self.satellites_used = report["uSat"].as_u64().unwrap_or(0);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn has_fix(&self) -> bool {
+        self.mode >= 2 && self.latitude.is_some() && self.longitude.is_some()
+    }
+}
+
+pub struct Gpsd {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    format_no_fix: FormatTemplate,
+    update_interval: Duration,
+    units: GpsdUnits,
+    fix: Arc<Mutex<Fix>>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct GpsdConfig {
+    /// Host of gpsd
+    pub host: String,
+
+    pub port: u16,
+
+    /// The path of the receiver, e.g. `/dev/ttyACM0`, for gpsd with several. All receivers by
+    /// default.
+    pub device: Option<String>,
+
+    /// Units of the speed and the altitude, km/h and m for "metric", mph and ft for "imperial"
+    pub units: GpsdUnits,
+
+    /// Update interval. Losing or getting a fix updates the block as well.
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    pub format: FormatTemplate,
+
+    /// Format while there is no fix
+    pub format_no_fix: FormatTemplate,
+}
+
+impl Default for GpsdConfig {
+    fn default() -> Self {
+        Self {
+            host: "localhost".to_string(),
+            port: 2947,
+            device: None,
+            units: GpsdUnits::Metric,
+            interval: Duration::from_secs(5),
+            format: FormatTemplate::default(),
+            format_no_fix: FormatTemplate::default(),
+        }
+    }
+}
+
+/// Reads the reports of gpsd on one connection, until it fails or the block is gone
+fn read_reports(
+    host: &str,
+    port: u16,
+    device: Option<&str>,
+    fix: &Arc<Mutex<Fix>>,
+    notify: &dyn Fn(),
+) -> std::result::Result<(), String> {
+    let address = (host, port)
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addresses| addresses.next())
+        .ok_or_else(|| format!("failed to resolve '{}'", host))?;
+    let stream = TcpStream::connect_timeout(&address, TIMEOUT)
+        .map_err(|_| format!("failed to connect to {}:{}", host, port))?;
+    // gpsd is silent without a receiver. The timeout lets the thread notice that the block is
+    // gone.
+    stream
+        .set_read_timeout(Some(Duration::from_secs(30)))
+        .map_err(|e| e.to_string())?;
+    (&stream)
+        .write_all(b"?WATCH={\"enable\":true,\"json\":true};\n")
+        .map_err(|_| "failed to watch the reports".to_string())?;
+
+    let mut reader = BufReader::new(&stream);
+    let mut line = String::new();
+    loop {
+        if Arc::strong_count(fix) == 1 {
+            return Ok(());
+        }
+        match reader.read_line(&mut line) {
+            Ok(0) => return Err("gpsd closed the connection".to_string()),
+            Ok(_) => (),
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => continue,
+            Err(e) => return Err(e.to_string()),
+        }
+        if let Ok(report) = serde_json::from_str::<serde_json::Value>(&line) {
+            let mut fix = fix.lock().unwrap();
+            let had_fix = fix.has_fix();
+            let was_connected = fix.error.is_none();
+            fix.error = None;
+            fix.apply(&report, device);
+            if fix.has_fix() != had_fix || !was_connected {
+                notify();
+            }
+        }
+        line.clear();
+    }
+}
+
+impl ConfigBlock for Gpsd {
+    type Config = GpsdConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let fix = Arc::new(Mutex::new(Fix {
+            error: Some("connecting to gpsd".to_string()),
+            ..Fix::default()
+        }));
+        let watched = fix.clone();
+        let host = block_config.host;
+        let port = block_config.port;
+        let device = block_config.device;
+        thread::Builder::new()
+            .name("gpsd".into())
+            .spawn(move || {
+                let notify = || {
+                    let _ = tx_update_request.send(Task {
+                        id,
+                        update_time: Instant::now(),
+                    });
+                };
+                // Stops once the block was removed by a reload
+                while Arc::strong_count(&watched) > 1 {
+                    if let Err(error) =
+                        read_reports(&host, port, device.as_deref(), &watched, &notify)
+                    {
+                        let mut fix = watched.lock().unwrap();
+                        if fix.error.as_ref() != Some(&error) {
+                            *fix = Fix {
+                                error: Some(error),
+                                ..Fix::default()
+                            };
+                            notify();
+                        }
+                    }
+                    thread::sleep(RECONNECT_INTERVAL);
+                }
+            })
+            .block_error("gpsd", "failed to start thread")?;
+
+        Ok(Gpsd {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("gps")?,
+            format: block_config.format.with_default("{latitude} {longitude}")?,
+            format_no_fix: block_config
+                .format_no_fix
+                .with_default("no fix {satellites_used:1}/{satellites:1}")?,
+            update_interval: block_config.interval,
+            units: block_config.units,
+            fix,
+        })
+    }
+}
+
+impl Block for Gpsd {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let fix = self.fix.lock().unwrap().clone();
+        if let Some(error) = fix.error {
+            return Err(BlockError("gpsd".to_string(), error));
+        }
+        let (speed, altitude) = match self.units {
+            // m/s to km/h
+            GpsdUnits::Metric => (fix.speed.map(|s| s * 3.6), fix.altitude),
+            GpsdUnits::Imperial => (
+                fix.speed.map(|s| s * 2.236_936),
+                fix.altitude.map(|a| a * 3.280_84),
+            ),
+        };
+        let number = |value: Option<f64>| match value {
+            Some(value) => Value::from_integer(value.round() as i64),
+            None => Value::from_string(String::new()),
+        };
+        let coordinate = |value: Option<f64>| {
+            Value::from_string(value.map_or_else(String::new, |value| format!("{:.5}", value)))
+        };
+        let values = map!(
+            "fix" => Value::from_string(match fix.mode {
+                2 => "2D",
+                3 => "3D",
+                _ => "none",
+            }.to_string()),
+            "latitude" => coordinate(fix.latitude),
+            "longitude" => coordinate(fix.longitude),
+            "altitude" => number(altitude),
+            "speed" => number(speed),
+            "course" => match fix.track {
+                Some(track) => Value::from_integer(track.round() as i64).degrees(),
+                None => Value::from_string(String::new()),
+            },
+            "satellites" => Value::from_integer(fix.satellites as i64),
+            "satellites_used" => Value::from_integer(fix.satellites_used as i64),
+            "device" => Value::from_string(fix.device.clone()),
+        );
+        if fix.has_fix() {
+            self.text.set_state(State::Idle);
+            self.text.set_format(&self.format, &values)?;
+        } else {
+            self.text.set_state(State::Warning);
+            self.text.set_format(&self.format_no_fix, &values)?;
+        }
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn reports() {
+        let mut fix = Fix::default();
+        fix.apply(
+            &json!({ "class": "SKY", "device": "/dev/ttyACM0", "satellites": [
+                { "PRN": 5, "used": true }, { "PRN": 7, "used": false }, { "PRN": 9, "used": true },
+            ]}),
+            None,
+        );
+        assert_eq!((fix.satellites, fix.satellites_used), (3, 2));
+        assert!(!fix.has_fix());
+        fix.apply(
+            &json!({ "class": "TPV", "device": "/dev/ttyACM0", "mode": 3,
+                     "lat": 52.370216, "lon": 4.895168, "alt": 12.5, "altMSL": 10.0, "speed": 2.0 }),
+            None,
+        );
+        assert!(fix.has_fix());
+        assert_eq!(fix.altitude, Some(10.0));
+        assert_eq!(fix.speed, Some(2.0));
+        // Other receivers are ignored
+        fix.apply(
+            &json!({ "class": "TPV", "device": "/dev/ttyUSB0", "mode": 1 }),
+            Some("/dev/ttyACM0"),
+        );
+        assert!(fix.has_fix());
+        fix.apply(
+            &json!({ "class": "SKY", "device": "/dev/ttyACM0", "nSat": 10, "uSat": 6 }),
+            Some("/dev/ttyACM0"),
+        );
+        assert_eq!((fix.satellites, fix.satellites_used), (10, 6));
+    }
+}
@@ -0,0 +1,327 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::scheduler::Task;
+use crate::util::read_file;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+const BLOCK_NAME: &str = "gpu";
+
+const DRM: &str = "/sys/class/drm";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Driver {
+    Amdgpu,
+    I915,
+}
+
+/// A card of `/sys/class/drm`
+#[derive(Debug)]
+struct Card {
+    /// e.g. `card0`
+    name: String,
+    driver: Driver,
+    path: PathBuf,
+    /// The `hwmon` directory of the device, with the temperature and the power
+    hwmon: Option<PathBuf>,
+}
+
+/// The counters of the last update, of which the utilization and the power are the differences
+struct Counters {
+    time: Instant,
+    /// The time the GPU of i915 was idle, in milliseconds
+    rc6_residency: Option<f64>,
+    /// In microjoules
+    energy: Option<f64>,
+}
+
+pub struct Gpu {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    update_interval: Duration,
+    card: Card,
+    previous: Option<Counters>,
+    minimum_info: f64,
+    minimum_warning: f64,
+    minimum_critical: f64,
+    temperature_warning: f64,
+    temperature_critical: f64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct GpuConfig {
+    /// The card in `/sys/class/drm` like `card1`. The first card of amdgpu or i915 by default.
+    pub card: Option<String>,
+
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    pub format: FormatTemplate,
+
+    /// Minimum utilization, where state is set to info
+    pub info: f64,
+
+    /// Minimum utilization, where state is set to warning
+    pub warning: f64,
+
+    /// Minimum utilization, where state is set to critical
+    pub critical: f64,
+
+    /// Minimum temperature, where state is set to warning
+    pub temperature_warning: f64,
+
+    /// Minimum temperature, where state is set to critical
+    pub temperature_critical: f64,
+}
+
+impl Default for GpuConfig {
+    fn default() -> Self {
+        Self {
+            card: None,
+            interval: Duration::from_secs(2),
+            format: FormatTemplate::default(),
+            info: 30.,
+            warning: 60.,
+            critical: 90.,
+            temperature_warning: 80.,
+            temperature_critical: 95.,
+        }
+    }
+}
+
+fn read_number(path: &Path) -> Option<f64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// The clock of the active level of `pp_dpm_sclk` of amdgpu, like `1: 1200Mhz *`, in Hz
+fn active_clock(levels: &str) -> Option<f64> {
+    let level = levels.lines().find(|line| line.trim_end().ends_with('*'))?;
+    let clock = level.split_whitespace().nth(1)?.to_lowercase();
+    Some(clock.strip_suffix("mhz")?.parse::<f64>().ok()? * 1e6)
+}
+
+/// The utilization in percent from the time the GPU was idle in the last `elapsed` milliseconds
+fn busy_percents(idle: f64, elapsed: f64) -> f64 {
+    match elapsed > 0. {
+        true => (100. - idle / elapsed * 100.).clamp(0., 100.),
+        false => 0.,
+    }
+}
+
+impl Card {
+    fn open(name: &str) -> Option<Card> {
+        let path = Path::new(DRM).join(name);
+        let driver = fs::read_link(path.join("device/driver")).ok()?;
+        let driver = match driver.file_name()?.to_str()? {
+            "amdgpu" => Driver::Amdgpu,
+            "i915" => Driver::I915,
+            _ => return None,
+        };
+        let hwmon = fs::read_dir(path.join("device/hwmon"))
+            .ok()
+            .and_then(|mut entries| entries.next())
+            .and_then(|entry| entry.ok())
+            .map(|entry| entry.path());
+        Some(Card {
+            name: name.to_string(),
+            driver,
+            path,
+            hwmon,
+        })
+    }
+
+    /// The card `name`, or else the first card of a supported driver
+    fn find(name: Option<&str>) -> Result<Card> {
+        if let Some(name) = name {
+            return Card::open(name).block_error(
+                BLOCK_NAME,
+                &format!("'{}' is not a card of amdgpu or i915 in {}", name, DRM),
+            );
+        }
+        let mut names: Vec<String> = fs::read_dir(DRM)
+            .block_error(BLOCK_NAME, &format!("failed to read {}", DRM))?
+            .flatten()
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            // Not the connectors like `card0-DP-1`
+            .filter(|name| name.starts_with("card") && !name.contains('-'))
+            .collect();
+        names.sort();
+        names
+            .iter()
+            .find_map(|name| Card::open(name))
+            .block_error(BLOCK_NAME, "no card of amdgpu or i915 found")
+    }
+
+    fn read(&self, file: &str) -> Option<f64> {
+        read_number(&self.path.join(file))
+    }
+
+    fn read_hwmon(&self, file: &str) -> Option<f64> {
+        read_number(&self.hwmon.as_ref()?.join(file))
+    }
+
+    fn counters(&self) -> Counters {
+        Counters {
+            time: Instant::now(),
+            rc6_residency: match self.driver {
+                Driver::I915 => self
+                    .read("gt/gt0/rc6_residency_ms")
+                    .or_else(|| self.read("power/rc6_residency_ms")),
+                Driver::Amdgpu => None,
+            },
+            energy: self.read_hwmon("energy1_input"),
+        }
+    }
+}
+
+impl ConfigBlock for Gpu {
+    type Config = GpuConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let card = Card::find(block_config.card.as_deref())?;
+        // i915 doesn't report the temperature of integrated GPUs
+        let default_format = match card.driver {
+            Driver::Amdgpu => "{utilization} {temperature}",
+            Driver::I915 => "{utilization}",
+        };
+        Ok(Gpu {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("gpu")?,
+            format: block_config.format.with_default(default_format)?,
+            update_interval: block_config.interval,
+            card,
+            previous: None,
+            minimum_info: block_config.info,
+            minimum_warning: block_config.warning,
+            minimum_critical: block_config.critical,
+            temperature_warning: block_config.temperature_warning,
+            temperature_critical: block_config.temperature_critical,
+        })
+    }
+}
+
+impl Block for Gpu {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let card = &self.card;
+        let counters = card.counters();
+        let previous = self.previous.as_ref();
+        let elapsed_ms = previous.map(|p| (counters.time - p.time).as_secs_f64() * 1000.);
+        let difference =
+            |counter: fn(&Counters) -> Option<f64>| Some(counter(&counters)? - counter(previous?)?);
+
+        let utilization = match card.driver {
+            Driver::Amdgpu => card.read("device/gpu_busy_percent"),
+            // Busy while not in RC6, 0 until there are two readings
+            Driver::I915 => match (difference(|c| c.rc6_residency), elapsed_ms) {
+                (Some(idle), Some(elapsed)) => Some(busy_percents(idle, elapsed)),
+                _ => counters.rc6_residency.map(|_| 0.),
+            },
+        }
+        .block_error(BLOCK_NAME, "the driver doesn't report the utilization")?;
+
+        let mut values = map!(
+            "name" => Value::from_string(card.name.clone()),
+            "utilization" => Value::from_float(utilization).percents(),
+        );
+        let vram_used = card.read("device/mem_info_vram_used");
+        let vram_total = card.read("device/mem_info_vram_total");
+        if let (Some(used), Some(total)) = (vram_used, vram_total) {
+            values.insert("vram_used", Value::from_float(used).bytes());
+            values.insert("vram_total", Value::from_float(total).bytes());
+            if total > 0. {
+                values.insert(
+                    "vram_used_percents",
+                    Value::from_float(used / total * 100.).percents(),
+                );
+            }
+        }
+        let temperature = card.read_hwmon("temp1_input").map(|t| t / 1000.);
+        if let Some(temperature) = temperature {
+            values.insert("temperature", Value::from_float(temperature).degrees());
+        }
+        let clock = match card.driver {
+            Driver::Amdgpu => card.read_hwmon("freq1_input").or_else(|| {
+                read_file(BLOCK_NAME, &card.path.join("device/pp_dpm_sclk"))
+                    .ok()
+                    .and_then(|levels| active_clock(&levels))
+            }),
+            Driver::I915 => card.read("gt_act_freq_mhz").map(|mhz| mhz * 1e6),
+        };
+        if let Some(clock) = clock {
+            values.insert("clock", Value::from_float(clock).hertz());
+        }
+        // In microwatts, or else from the energy since the last update
+        let power = card
+            .read_hwmon("power1_average")
+            .or_else(|| card.read_hwmon("power1_input"))
+            .map(|power| power / 1e6)
+            .or_else(|| Some(difference(|c| c.energy)? / 1e3 / elapsed_ms?));
+        if let Some(power) = power {
+            values.insert("power", Value::from_float(power).watts());
+        }
+
+        let utilization_state = match utilization {
+            u if u >= self.minimum_critical => State::Critical,
+            u if u >= self.minimum_warning => State::Warning,
+            u if u >= self.minimum_info => State::Info,
+            _ => State::Idle,
+        };
+        let temperature_state = match temperature {
+            Some(t) if t >= self.temperature_critical => State::Critical,
+            Some(t) if t >= self.temperature_warning => State::Warning,
+            _ => State::Idle,
+        };
+        self.text
+            .set_state(match (utilization_state, temperature_state) {
+                (_, State::Critical) | (State::Critical, _) => State::Critical,
+                (_, State::Warning) | (State::Warning, _) => State::Warning,
+                (state, _) => state,
+            });
+        self.text.set_format(&self.format, &values)?;
+        self.previous = Some(counters);
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sysfs() {
+        let levels = "0: 500Mhz \n1: 1200Mhz *\n2: 1800Mhz \n";
+        assert_eq!(active_clock(levels), Some(1.2e9));
+        assert_eq!(active_clock("0: 500Mhz \n"), None);
+        assert_eq!(busy_percents(1500., 2000.), 25.);
+        // The counter and the clock are read at slightly different times
+        assert_eq!(busy_percents(2010., 2000.), 0.);
+        assert_eq!(busy_percents(0., 0.), 0.);
+    }
+}
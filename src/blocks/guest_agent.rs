@@ -0,0 +1,241 @@
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+use serde_json::json;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::scheduler::Task;
+use crate::subprocess::command;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, Spacing, State};
+
+pub struct GuestAgent {
+    id: usize,
+    /// One widget for each domain
+    output: Vec<TextWidget>,
+    format: FormatTemplate,
+    update_interval: Duration,
+    uri: String,
+    domains: Vec<String>,
+    agent_timeout: Duration,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct GuestAgentConfig {
+    /// The names of the virtual machines
+    pub domains: Vec<String>,
+
+    /// The libvirt connection
+    #[serde(default = "GuestAgentConfig::default_uri")]
+    pub uri: String,
+
+    /// Time the guest agent has to answer
+    #[serde(
+        default = "GuestAgentConfig::default_agent_timeout",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub agent_timeout: Duration,
+
+    #[serde(
+        default = "GuestAgentConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    #[serde(default)]
+    pub format: FormatTemplate,
+}
+
+impl GuestAgentConfig {
+    fn default_uri() -> String {
+        "qemu:///system".to_string()
+    }
+
+    fn default_agent_timeout() -> Duration {
+        Duration::from_secs(2)
+    }
+
+    fn default_interval() -> Duration {
+        Duration::from_secs(10)
+    }
+}
+
+impl ConfigBlock for GuestAgent {
+    type Config = GuestAgentConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let output = (0..block_config.domains.len())
+            .map(|i| {
+                TextWidget::new(id, i, shared_config.clone())
+                    .with_icon("vm")
+                    .map(|widget| widget.with_spacing(Spacing::Inline))
+            })
+            .collect::<Result<_>>()?;
+        Ok(GuestAgent {
+            id,
+            output,
+            format: block_config.format.with_default("{name} {ip} {load}")?,
+            update_interval: block_config.interval,
+            uri: block_config.uri,
+            domains: block_config.domains,
+            agent_timeout: block_config.agent_timeout,
+        })
+    }
+}
+
+/// What the guest agent of a running domain answered
+struct Guest {
+    ip: Option<String>,
+    /// The load average of the last minute, `None` for agents without `guest-get-load`
+    load: Option<f64>,
+}
+
+/// The first address of the guest that isn't a loopback or link-local address, IPv4 first
+fn guest_ip(interfaces: &serde_json::Value) -> Option<String> {
+    let addresses: Vec<(&str, &str)> = interfaces["return"]
+        .as_array()?
+        .iter()
+        .filter(|interface| interface["name"] != "lo")
+        .flat_map(|interface| interface["ip-addresses"].as_array().into_iter().flatten())
+        .filter_map(|address| {
+            Some((
+                address["ip-address-type"].as_str()?,
+                address["ip-address"].as_str()?,
+            ))
+        })
+        .filter(|(_, address)| !address.starts_with("127.") && !address.starts_with("fe80:"))
+        .collect();
+    ["ipv4", "ipv6"].iter().find_map(|&kind| {
+        addresses
+            .iter()
+            .find(|(address_kind, _)| *address_kind == kind)
+            .map(|(_, address)| address.to_string())
+    })
+}
+
+impl GuestAgent {
+    fn virsh(&self, args: &[&str]) -> Result<String> {
+        let output = command("virsh")
+            .args(["--quiet", "--connect", &self.uri])
+            .args(args)
+            .output()
+            .block_error("guest_agent", "failed to run 'virsh'")?;
+        if !output.status.success() {
+            return Err(BlockError(
+                "guest_agent".to_string(),
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    fn agent_command(&self, domain: &str, agent_command: &str) -> Result<serde_json::Value> {
+        let timeout = self.agent_timeout.as_secs().max(1).to_string();
+        let request = json!({ "execute": agent_command }).to_string();
+        let answer = self.virsh(&[
+            "qemu-agent-command",
+            "--timeout",
+            &timeout,
+            domain,
+            &request,
+        ])?;
+        serde_json::from_str(&answer).block_error("guest_agent", "invalid answer of the agent")
+    }
+
+    /// `None` if the agent doesn't answer, e.g. while the guest boots
+    fn guest(&self, domain: &str) -> Option<Guest> {
+        let interfaces = self
+            .agent_command(domain, "guest-network-get-interfaces")
+            .ok()?;
+        let load = self
+            .agent_command(domain, "guest-get-load")
+            .ok()
+            .and_then(|load| load["return"]["load1"].as_f64());
+        Some(Guest {
+            ip: guest_ip(&interfaces),
+            load,
+        })
+    }
+}
+
+impl Block for GuestAgent {
+    fn update(&mut self) -> Result<Option<Update>> {
+        for (i, domain) in self.domains.iter().enumerate() {
+            let domain_state = self.virsh(&["domstate", domain])?;
+            let guest = match domain_state.as_str() {
+                "running" => self.guest(domain),
+                _ => None,
+            };
+            let (ip, load) = match &guest {
+                Some(guest) => (guest.ip.clone(), guest.load),
+                None => (None, None),
+            };
+            let values = map!(
+                "name" => Value::from_string(domain.clone()),
+                "state" => Value::from_string(domain_state.clone()),
+                "ip" => Value::from_string(ip.unwrap_or_default()),
+                "load" => match load {
+                    Some(load) => Value::from_float(load),
+                    None => Value::from_string(String::new()),
+                },
+            );
+            let widget = &mut self.output[i];
+            widget.set_state(match (domain_state.as_str(), &guest) {
+                (_, Some(_)) => State::Good,
+                // The guest is booting or has no agent
+                ("running", None) => State::Warning,
+                _ => State::Idle,
+            });
+            widget.set_format(&self.format, &values)?;
+        }
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        self.output.iter().map(|x| x as &dyn I3BarWidget).collect()
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addresses() {
+        let interfaces = json!({ "return": [
+            { "name": "lo", "ip-addresses": [
+                { "ip-address-type": "ipv4", "ip-address": "127.0.0.1", "prefix": 8 },
+            ]},
+            { "name": "enp1s0", "ip-addresses": [
+                { "ip-address-type": "ipv6", "ip-address": "fe80::5054:ff:fe12:3456", "prefix": 64 },
+                { "ip-address-type": "ipv6", "ip-address": "fd00::10", "prefix": 64 },
+                { "ip-address-type": "ipv4", "ip-address": "192.168.122.10", "prefix": 24 },
+            ]},
+        ]});
+        assert_eq!(guest_ip(&interfaces), Some("192.168.122.10".to_string()));
+        let ipv6_only = json!({ "return": [
+            { "name": "enp1s0", "ip-addresses": [
+                { "ip-address-type": "ipv6", "ip-address": "fd00::10", "prefix": 64 },
+            ]},
+        ]});
+        assert_eq!(guest_ip(&ipv6_only), Some("fd00::10".to_string()));
+        assert_eq!(guest_ip(&json!({ "return": [] })), None);
+    }
+}
@@ -1,4 +1,3 @@
-use std::process::Command;
 use std::time::Duration;
 
 use crossbeam_channel::Sender;
@@ -11,6 +10,7 @@ use crate::de::deserialize_duration;
 use crate::errors::*;
 use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
+use crate::subprocess::command;
 use crate::util::has_command;
 use crate::widgets::text::TextWidget;
 use crate::widgets::I3BarWidget;
@@ -35,7 +35,7 @@ trait HueShiftDriver {
 struct Redshift();
 impl HueShiftDriver for Redshift {
     fn update(&self, temp: u16) -> Result<()> {
-        Command::new("sh")
+        command("sh")
             .args(&[
                 "-c",
                 format!("redshift -O {} -P >/dev/null 2>&1", temp).as_str(),
@@ -48,7 +48,7 @@ impl HueShiftDriver for Redshift {
         Ok(())
     }
     fn reset(&self) -> Result<()> {
-        Command::new("sh")
+        command("sh")
             .args(&["-c", "redshift -x >/dev/null 2>&1"])
             .spawn()
             .block_error(
@@ -61,14 +61,14 @@ impl HueShiftDriver for Redshift {
 struct Sct();
 impl HueShiftDriver for Sct {
     fn update(&self, temp: u16) -> Result<()> {
-        Command::new("sh")
+        command("sh")
             .args(&["-c", format!("sct {} >/dev/null 2>&1", temp).as_str()])
             .spawn()
             .block_error("hueshift", "Failed to set new color temperature using sct.")?;
         Ok(())
     }
     fn reset(&self) -> Result<()> {
-        Command::new("sh")
+        command("sh")
             .args(&["-c", "sct >/dev/null 2>&1"])
             .spawn()
             .block_error("hueshift", "Failed to set new color temperature using sct.")?;
@@ -78,7 +78,7 @@ impl HueShiftDriver for Sct {
 struct Gammastep();
 impl HueShiftDriver for Gammastep {
     fn update(&self, temp: u16) -> Result<()> {
-        Command::new("sh")
+        command("sh")
             .args(&[
                 "-c",
                 &format!("pkill gammastep; gammastep -O {} -P &", temp),
@@ -91,7 +91,7 @@ impl HueShiftDriver for Gammastep {
         Ok(())
     }
     fn reset(&self) -> Result<()> {
-        Command::new("sh")
+        command("sh")
             .args(&["-c", "gammastep -x >/dev/null 2>&1"])
             .spawn()
             .block_error(
@@ -104,7 +104,7 @@ impl HueShiftDriver for Gammastep {
 struct Wlsunset();
 impl HueShiftDriver for Wlsunset {
     fn update(&self, temp: u16) -> Result<()> {
-        Command::new("sh")
+        command("sh")
             // wlsunset does not have a oneshot option, so set both day and
             // night temperature. wlsunset dose not allow for day and night
             // temperatures to be the same, so increment the day temperature.
@@ -120,7 +120,7 @@ impl HueShiftDriver for Wlsunset {
         Ok(())
     }
     fn reset(&self) -> Result<()> {
-        Command::new("sh")
+        command("sh")
             // wlsunset does not have a reset option, so just kill the process.
             // Trying to call wlsunset without any arguments uses the defaults:
             // day temp: 6500K
@@ -273,16 +273,17 @@ impl Block for Hueshift {
             mb => {
                 use LogicalDirection::*;
                 let new_temp: u16;
+                let step = event.scale_step(self.step as f64) as u16;
                 match self.scrolling.to_logical_direction(mb) {
                     Some(Up) => {
-                        new_temp = self.current_temp + self.step;
+                        new_temp = self.current_temp + step;
                         if new_temp <= self.max_temp {
                             self.hue_shift_driver.update(new_temp)?;
                             self.current_temp = new_temp;
                         }
                     }
                     Some(Down) => {
-                        new_temp = self.current_temp - self.step;
+                        new_temp = self.current_temp - step;
                         if new_temp >= self.min_temp {
                             self.hue_shift_driver.update(new_temp)?;
                             self.current_temp = new_temp;
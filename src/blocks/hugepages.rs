@@ -0,0 +1,193 @@
+use std::path::Path;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::scheduler::Task;
+use crate::util::read_file;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+pub struct Hugepages {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    update_interval: Duration,
+    warning: f64,
+    critical: f64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct HugepagesConfig {
+    pub format: FormatTemplate,
+
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Fragmentation in percent, where state is set to warning
+    pub warning: f64,
+
+    /// Fragmentation in percent, where state is set to critical
+    pub critical: f64,
+}
+
+impl Default for HugepagesConfig {
+    fn default() -> Self {
+        Self {
+            format: FormatTemplate::default(),
+            interval: Duration::from_secs(10),
+            warning: 75.0,
+            critical: 90.0,
+        }
+    }
+}
+
+impl ConfigBlock for Hugepages {
+    type Config = HugepagesConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        Ok(Hugepages {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("memory_mem")?,
+            format: block_config
+                .format
+                .with_default("{hugepages_used}/{hugepages_total} {fragmentation}")?,
+            update_interval: block_config.interval,
+            warning: block_config.warning,
+            critical: block_config.critical,
+        })
+    }
+}
+
+/// A value of `/proc/meminfo`, in kB for the sizes
+fn meminfo(contents: &str, key: &str) -> Option<u64> {
+    contents.lines().find_map(|line| {
+        let value = line.strip_prefix(key)?.strip_prefix(':')?;
+        value.split_whitespace().next()?.parse().ok()
+    })
+}
+
+/// The mode in brackets, e.g. `madvise` of `always [madvise] never`
+fn thp_mode(enabled: &str) -> Option<&str> {
+    let start = enabled.find('[')? + 1;
+    let end = start + enabled[start..].find(']')?;
+    Some(&enabled[start..end])
+}
+
+/// The share of the free memory of all zones that is in blocks smaller than `order`, so it can't
+/// be used for allocations of that order. This is the unusable free space index of the kernel.
+fn fragmentation(buddyinfo: &str, order: usize) -> Option<f64> {
+    let mut free = 0;
+    let mut usable = 0;
+    for line in buddyinfo.lines() {
+        // `Node 0, zone Normal` and the free blocks of each order
+        let counts = line
+            .split_whitespace()
+            .skip(4)
+            .filter_map(|count| count.parse::<u64>().ok());
+        for (block_order, count) in counts.enumerate() {
+            let pages = count << block_order;
+            free += pages;
+            if block_order >= order {
+                usable += pages;
+            }
+        }
+    }
+    match free {
+        0 => None,
+        _ => Some((free - usable) as f64 / free as f64),
+    }
+}
+
+/// The order of the blocks of `size` kB
+fn order(size: u64) -> usize {
+    let page_size = nix::unistd::sysconf(nix::unistd::SysconfVar::PAGE_SIZE)
+        .ok()
+        .flatten()
+        .map_or(4096, |page_size| page_size as u64);
+    let pages = (size * 1024 / page_size).max(1);
+    (u64::BITS - 1 - pages.leading_zeros()) as usize
+}
+
+impl Block for Hugepages {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let meminfo_contents = read_file("hugepages", Path::new("/proc/meminfo"))?;
+        let value = |key| meminfo(&meminfo_contents, key).unwrap_or(0);
+        let total = value("HugePages_Total");
+        let free = value("HugePages_Free");
+        let size = value("Hugepagesize");
+        // Kernels without transparent hugepages don't have the directory
+        let thp = read_file(
+            "hugepages",
+            Path::new("/sys/kernel/mm/transparent_hugepage/enabled"),
+        )
+        .ok()
+        .and_then(|enabled| thp_mode(&enabled).map(String::from))
+        .unwrap_or_else(|| "never".to_string());
+        let buddyinfo = read_file("hugepages", Path::new("/proc/buddyinfo"))?;
+        let fragmentation = fragmentation(&buddyinfo, order(size.max(1))).unwrap_or(0.0) * 100.0;
+
+        let values = map!(
+            "hugepages_total" => Value::from_integer(total as i64),
+            "hugepages_free" => Value::from_integer(free as i64),
+            "hugepages_used" => Value::from_integer(total.saturating_sub(free) as i64),
+            "hugepages_reserved" => Value::from_integer(value("HugePages_Rsvd") as i64),
+            "hugepage_size" => Value::from_float(size as f64 * 1024.0).bytes(),
+            "thp" => Value::from_string(thp),
+            "thp_used" => Value::from_float(value("AnonHugePages") as f64 * 1024.0).bytes(),
+            "fragmentation" => Value::from_float(fragmentation).percents(),
+        );
+        self.text.set_state(match fragmentation {
+            x if x > self.critical => State::Critical,
+            x if x > self.warning => State::Warning,
+            _ => State::Idle,
+        });
+        self.text.set_format(&self.format, &values)?;
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_state() {
+        let meminfo_contents =
+            "AnonHugePages:     16384 kB\nHugePages_Total:      64\nHugepagesize:       2048 kB\n";
+        assert_eq!(meminfo(meminfo_contents, "AnonHugePages"), Some(16384));
+        assert_eq!(meminfo(meminfo_contents, "HugePages_Total"), Some(64));
+        assert_eq!(meminfo(meminfo_contents, "HugePages"), None);
+        assert_eq!(thp_mode("always [madvise] never"), Some("madvise"));
+        assert_eq!(thp_mode("always madvise never"), None);
+
+        let buddyinfo = "Node 0, zone      DMA      0      0      0      0      0      0      0      0      1      1      3
+Node 0, zone   Normal   4      2      0      0      0      0      0      0      0      0      0";
+        // 8 free pages in small blocks, 256 + 512 + 3072 in blocks of order 8 and up
+        assert_eq!(fragmentation(buddyinfo, 0), Some(0.0));
+        assert_eq!(fragmentation(buddyinfo, 9), Some((8.0 + 256.0) / 3848.0));
+        assert_eq!(fragmentation("", 9), None);
+    }
+}
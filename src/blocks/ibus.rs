@@ -2,7 +2,6 @@ use std::collections::BTreeMap;
 use std::env;
 use std::fs::{read_dir, File};
 use std::io::prelude::*;
-use std::process::Command;
 use std::sync::{Arc, Condvar, Mutex};
 use std::thread;
 use std::time::Instant;
@@ -20,11 +19,14 @@ use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::SharedConfig;
+use crate::dbus_connections::{self, Event};
+use crate::deadline;
 use crate::errors::*;
 use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
 use crate::protocol::i3bar_event::I3BarEvent;
 use crate::scheduler::Task;
+use crate::subprocess::command;
 use crate::util::xdg_config_home;
 use crate::widgets::text::TextWidget;
 use crate::widgets::I3BarWidget;
@@ -69,72 +71,65 @@ impl ConfigBlock for IBus {
         let init_text = block_config.initial_text;
         let engine_original = Arc::new(Mutex::new(init_text.clone()));
 
-        let c = Connection::get_private(BusType::Session).block_error(
-            "ibus",
-            "failed to establish D-Bus connection to session bus",
-        )?;
-        let m = Message::new_method_call(
-            "org.freedesktop.DBus",
-            "/",
-            "org.freedesktop.DBus",
-            "ListNames",
-        )
-        .unwrap();
-        let r = c.send_with_reply_and_block(m, 2000).unwrap();
-        let arr: Array<&str, _> = r.get1().unwrap();
-        // On my system after starting `ibus-daemon` I get `org.freedesktop.IBus`,
-        // `org.freedesktop.IBus.Panel.Extension.Gtk3` and `org.freedesktop.portal.IBus`.
-        // The last one comes up a while after the other two, and until then any calls to
-        // `GlobalEngine` result in a "No global engine" response.
-        // Hence the check below to see if there are 3 or more names on the bus with "IBus" in them.
-        // TODO: Possibly we only need to check for `org.freedesktop.portal.IBus`? Not sure atm.
-        // TODO: Is the Gtk3 one always there? Is it ever a Qt one?
-        let running = arr.filter(|entry| entry.contains("IBus")).count() > 2;
+        let running = ibus_running()?;
         // TODO: revisit this lint
         #[allow(clippy::mutex_atomic)]
         let available = Arc::new((Mutex::new(running), Condvar::new()));
         let available_copy = available.clone();
         let engine_copy = engine_original.clone();
         let send2 = send.clone();
-        thread::Builder::new().name("ibus-daemon-monitor".into()).spawn(move || {
-            let c = Connection::get_private(BusType::Session).unwrap();
-            c.add_match("interface='org.freedesktop.DBus',member='NameOwnerChanged',path='/org/freedesktop/DBus',arg0namespace='org.freedesktop.IBus'")
-                .unwrap();
-            // Skip the NameAcquired event.
-            c.incoming(10_000).next();
-            loop {
-                for ci in c.iter(100_000) {
-                    if let ConnectionItem::Signal(x) = ci {
-                    	let (name, old_owner, new_owner): (&str, &str, &str) = x.read3().unwrap();
-						if name.contains("IBus") && !old_owner.is_empty() && new_owner.is_empty() {
-							let (lock, cvar) = &*available_copy;
-							let mut available = lock.lock().unwrap();
-							*available = false;
-							cvar.notify_one();
-                            let mut engine = engine_copy.lock().unwrap();
-                            // see comment in the ibus-engine-monitor thread
-                            // TODO: way to restart the other thread with the new IBus address
-                            eprintln!("ibus block: ibus-daemon was restarted, so the block will no longer update");
-                            *engine = "ibus restarted so i3status-rs must be restarted!".to_string();
-							send2.send(Task {
-								id,
-								update_time: Instant::now(),
-							}).unwrap();
-						} else if name.contains("IBus") && old_owner.is_empty() && !new_owner.is_empty() {
-							let (lock, cvar) = &*available_copy;
-							let mut available = lock.lock().unwrap();
-							*available = true;
-							cvar.notify_one();
-                            eprintln!("ibus block: ibus-daemon has started!");
-							send2.send(Task {
-						   		id,
-						   		update_time: Instant::now(),
-							}).unwrap();
-						}
+        dbus_connections::watch(
+            "ibus-daemon-monitor",
+            BusType::Session,
+            vec!["interface='org.freedesktop.DBus',member='NameOwnerChanged',path='/org/freedesktop/DBus',arg0namespace='org.freedesktop.IBus'".to_string()],
+            move |event| {
+                let x = match event {
+                    // ibus-daemon may have come or gone while the bus was away
+                    Event::Connected => {
+                        if let Ok(running) = ibus_running() {
+                            let (lock, cvar) = &*available_copy;
+                            *lock.lock().unwrap() = running;
+                            cvar.notify_one();
+                        }
+                        return;
                     }
+                    Event::Signal(x) => x,
+                };
+                let (name, old_owner, new_owner): (&str, &str, &str) = match x.read3() {
+                    Ok(args) => args,
+                    Err(_) => return,
+                };
+                if name.contains("IBus") && !old_owner.is_empty() && new_owner.is_empty() {
+                    let (lock, cvar) = &*available_copy;
+                    let mut available = lock.lock().unwrap();
+                    *available = false;
+                    cvar.notify_one();
+                    let mut engine = engine_copy.lock().unwrap();
+                    // see comment in the ibus-engine-monitor thread
+                    // TODO: way to restart the other thread with the new IBus address
+                    tracing::warn!("ibus-daemon was restarted, so the block will no longer update");
+                    *engine = "ibus restarted so i3status-rs must be restarted!".to_string();
+                    send2
+                        .send(Task {
+                            id,
+                            update_time: Instant::now(),
+                        })
+                        .unwrap();
+                } else if name.contains("IBus") && old_owner.is_empty() && !new_owner.is_empty() {
+                    let (lock, cvar) = &*available_copy;
+                    let mut available = lock.lock().unwrap();
+                    *available = true;
+                    cvar.notify_one();
+                    tracing::info!("ibus-daemon has started");
+                    send2
+                        .send(Task {
+                            id,
+                            update_time: Instant::now(),
+                        })
+                        .unwrap();
                 }
-            }
-        }).unwrap();
+            },
+        )?;
 
         let current_engine: String = if running {
             let ibus_address = get_ibus_address()?;
@@ -154,7 +149,7 @@ impl ConfigBlock for IBus {
             let value = &info.0;
             match value.arg_type() {
                 arg::ArgType::String => {
-                    eprintln!("ibus block: global engine not set");
+                    tracing::info!("global engine not set");
                     init_text
                 }
                 arg::ArgType::Struct => {
@@ -258,7 +253,7 @@ impl Block for IBus {
             "engine" => Value::from_string(display_engine)
         );
 
-        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_format(&self.format, &values)?;
         Ok(None)
     }
 
@@ -274,6 +269,32 @@ impl Block for IBus {
     }
 }
 
+/// Whether ibus-daemon is running and ready to be asked for the engine
+fn ibus_running() -> Result<bool> {
+    let c = dbus_connections::session()?;
+    let m = Message::new_method_call(
+        "org.freedesktop.DBus",
+        "/",
+        "org.freedesktop.DBus",
+        "ListNames",
+    )
+    .unwrap();
+    let r = c
+        .send_with_reply_and_block(m, deadline::limit_millis(2000))
+        .block_error("ibus", "failed to list the names on the session bus")?;
+    let arr: Array<&str, _> = r
+        .get1()
+        .block_error("ibus", "failed to list the names on the session bus")?;
+    // On my system after starting `ibus-daemon` I get `org.freedesktop.IBus`,
+    // `org.freedesktop.IBus.Panel.Extension.Gtk3` and `org.freedesktop.portal.IBus`.
+    // The last one comes up a while after the other two, and until then any calls to
+    // `GlobalEngine` result in a "No global engine" response.
+    // Hence the check below to see if there are 3 or more names on the bus with "IBus" in them.
+    // TODO: Possibly we only need to check for `org.freedesktop.portal.IBus`? Not sure atm.
+    // TODO: Is the Gtk3 one always there? Is it ever a Qt one?
+    Ok(arr.filter(|entry| entry.contains("IBus")).count() > 2)
+}
+
 fn parse_msg(ci: &ConnectionItem) -> Option<&str> {
     let m = if let ConnectionItem::Signal(ref s) = *ci {
         s
@@ -305,20 +326,17 @@ fn parse_msg(ci: &ConnectionItem) -> Option<&str> {
 // ```
 fn get_ibus_address() -> Result<String> {
     if let Ok(address) = env::var("IBUS_ADDRESS") {
-        eprintln!("ibus block: using address from $IBUS_ADDRESS ({})", address);
+        tracing::debug!("using address from $IBUS_ADDRESS ({})", address);
         return Ok(address);
     }
 
     // This is the surefire way to get the current IBus address
-    if let Ok(address) = Command::new("ibus")
+    if let Ok(address) = command("ibus")
         .args(&["address"])
         .output()
         .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_owned())
     {
-        eprintln!(
-            "ibus block: using address from `ibus address` ({})",
-            address
-        );
+        tracing::debug!("using address from `ibus address` ({})", address);
         return Ok(address);
     }
 
@@ -388,8 +406,8 @@ fn get_ibus_address() -> Result<String> {
     )?;
 
     let address = cap[1].to_string();
-    eprintln!(
-        "ibus block: using address from {} ({})",
+    tracing::debug!(
+        "using address from {} ({})",
         socket_path.into_os_string().into_string().unwrap(),
         address
     );
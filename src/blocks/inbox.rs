@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+use crossbeam_channel::{Receiver, Sender};
+use serde_derive::Deserialize;
+use toml::value::Value as TomlValue;
+
+use crate::blocks::{create_block, Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::registry;
+use crate::scheduler::Task;
+use crate::util::pseudo_uuid;
+use crate::widgets::{text::TextWidget, I3BarWidget, State};
+
+const BLOCK_NAME: &str = "inbox";
+
+struct Source {
+    label: String,
+    /// The placeholder of the source block that holds the count
+    count: String,
+    block: Box<dyn Block>,
+    next_update: Option<Instant>,
+}
+
+pub struct Inbox {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    hide_if_zero: bool,
+    sources: Vec<Source>,
+    /// Ids of the sources that requested an update
+    requested: Arc<Mutex<Vec<usize>>>,
+    total: f64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct InboxConfig {
+    #[serde(default)]
+    pub format: FormatTemplate,
+
+    /// Hide the block if all counts are zero
+    #[serde(default)]
+    pub hide_if_zero: bool,
+
+    /// Configurations of the source blocks, with an additional `block` key like top-level blocks
+    pub sources: Vec<TomlValue>,
+}
+
+/// The placeholder holding the count for blocks that are commonly used as sources
+fn default_count(block: &str) -> Option<&'static str> {
+    match block {
+        "github" => Some("total"),
+        "maildir" => Some("count"),
+        "notmuch" => Some("count"),
+        "rofication" => Some("num"),
+        "social_inbox" => Some("unread"),
+        _ => None,
+    }
+}
+
+fn take_string(table: &mut toml::value::Table, key: &str) -> Result<Option<String>> {
+    match table.remove(key) {
+        None => Ok(None),
+        Some(TomlValue::String(s)) => Ok(Some(s)),
+        Some(_) => Err(ConfigurationError(
+            BLOCK_NAME.to_string(),
+            format!("'{}' of a source must be a string", key),
+        )),
+    }
+}
+
+impl Inbox {
+    /// Forwards the update requests of the sources, which are unknown to the scheduler, as
+    /// requests for this block.
+    fn forward_update_requests(
+        id: usize,
+        rx_sources: Receiver<Task>,
+        tx_update_request: Sender<Task>,
+        requested: Arc<Mutex<Vec<usize>>>,
+    ) -> Result<()> {
+        thread::Builder::new()
+            .name("inbox".into())
+            .spawn(move || {
+                for task in rx_sources {
+                    requested.lock().unwrap().push(task.id);
+                    tx_update_request
+                        .send(Task {
+                            id,
+                            update_time: task.update_time,
+                        })
+                        .unwrap();
+                }
+            })
+            .map(|_| ())
+            .internal_error(BLOCK_NAME, "failed to start thread")
+    }
+
+    fn render(&mut self) -> Result<()> {
+        let mut values = HashMap::new();
+        self.total = 0.0;
+        for source in &self.sources {
+            let count = registry::get(source.block.id(), &source.count)
+                .and_then(|value| value.as_f64())
+                .unwrap_or(0.0);
+            self.total += count;
+            values.insert(source.label.clone(), Value::from_integer(count as i64));
+        }
+        values.insert("total".to_string(), Value::from_integer(self.total as i64));
+
+        self.text.set_format(&self.format, &values)?;
+        self.text.set_state(if self.total > 0.0 {
+            State::Info
+        } else {
+            State::Idle
+        });
+        Ok(())
+    }
+}
+
+impl ConfigBlock for Inbox {
+    type Config = InboxConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let (tx_sources, rx_sources) = crossbeam_channel::unbounded();
+        let requested = Arc::new(Mutex::new(Vec::new()));
+        Self::forward_update_requests(id, rx_sources, tx_update_request, requested.clone())?;
+
+        let mut sources: Vec<Source> = Vec::new();
+        for mut config in block_config.sources {
+            let table = config.as_table_mut().ok_or_else(|| {
+                ConfigurationError(BLOCK_NAME.to_string(), "invalid source".to_string())
+            })?;
+            let name = take_string(table, "block")?.ok_or_else(|| {
+                ConfigurationError(
+                    BLOCK_NAME.to_string(),
+                    "every source must have a 'block'".to_string(),
+                )
+            })?;
+            let label = take_string(table, "label")?.unwrap_or_else(|| name.clone());
+            let count = match take_string(table, "count")? {
+                Some(count) => count,
+                None => default_count(&name)
+                    .ok_or_else(|| {
+                        ConfigurationError(
+                            BLOCK_NAME.to_string(),
+                            format!("the source '{}' needs a 'count' placeholder", name),
+                        )
+                    })?
+                    .to_string(),
+            };
+            if label == "total" || sources.iter().any(|s| s.label == label) {
+                return Err(ConfigurationError(
+                    BLOCK_NAME.to_string(),
+                    format!(
+                        "duplicate label '{}', give the sources a unique 'label'",
+                        label
+                    ),
+                ));
+            }
+
+            // The sources are not on the bar, so any id not used by the top-level blocks works
+            let block = create_block(
+                pseudo_uuid(),
+                &name,
+                config,
+                shared_config.clone(),
+                tx_sources.clone(),
+            )?;
+            registry::record_values(block.id());
+            sources.push(Source {
+                label,
+                count,
+                block,
+                next_update: Some(Instant::now()),
+            });
+        }
+
+        // Shown after a click, unless the `format_expanded` option is set
+        let expanded = sources
+            .iter()
+            .map(|s| format!("{}:{{{}}}", s.label, s.label))
+            .collect::<Vec<_>>()
+            .join(" ");
+        registry::set_expanded_format(id, FormatTemplate::new(&expanded, None)?);
+
+        Ok(Inbox {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("bell")?,
+            format: block_config.format.with_default("{total}")?,
+            hide_if_zero: block_config.hide_if_zero,
+            sources,
+            requested,
+            total: 0.0,
+        })
+    }
+}
+
+impl Block for Inbox {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let now = Instant::now();
+        let requested: Vec<usize> = self.requested.lock().unwrap().drain(..).collect();
+
+        for source in &mut self.sources {
+            let due = source.next_update.map(|t| t <= now).unwrap_or(false);
+            if due || requested.contains(&source.block.id()) {
+                source.next_update = match source.block.update()? {
+                    Some(Update::Every(d)) => Some(now + d),
+                    Some(Update::Once) | None => None,
+                };
+            }
+        }
+        self.render()?;
+
+        Ok(self
+            .sources
+            .iter()
+            .filter_map(|s| s.next_update)
+            .min()
+            .map(|next| next.saturating_duration_since(Instant::now()).into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        if self.hide_if_zero && self.total == 0.0 {
+            vec![]
+        } else {
+            vec![&self.text]
+        }
+    }
+
+    fn signal(&mut self, signal: i32) -> Result<()> {
+        for source in &mut self.sources {
+            source.block.signal(signal)?;
+        }
+        self.render()
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
@@ -1,20 +1,22 @@
-use std::process::Command;
 use std::sync::{Arc, Mutex};
-use std::thread;
-use std::time::{Duration, Instant};
+use std::time::Instant;
 
 use crossbeam_channel::Sender;
 use dbus::arg;
-use dbus::blocking::{stdintf::org_freedesktop_dbus::Properties, Connection};
-use dbus::Message;
+use dbus::ffidisp::stdintf::org_freedesktop_dbus::Properties;
+use dbus::ffidisp::BusType;
+use dbus::message::SignalArgs;
 use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::SharedConfig;
+use crate::dbus_connections::{self, Event};
+use crate::deadline;
 use crate::errors::*;
 use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
 use crate::scheduler::Task;
+use crate::subprocess::command;
 use crate::util::battery_level_to_icon;
 use crate::widgets::text::TextWidget;
 use crate::widgets::{I3BarWidget, State};
@@ -22,11 +24,7 @@ use crate::widgets::{I3BarWidget, State};
 pub struct KDEConnect {
     id: usize,
     device_id: String,
-    device_name: Arc<Mutex<String>>,
-    battery_charge: Arc<Mutex<i32>>,
-    battery_state: Arc<Mutex<bool>>,
-    notif_count: Arc<Mutex<i32>>,
-    phone_reachable: Arc<Mutex<bool>>,
+    state: Arc<Mutex<DeviceState>>,
     // TODO
     //notif_text: Arc<Mutex<String>>,
     bat_good: i32,
@@ -77,6 +75,81 @@ impl Default for KDEConnectConfig {
     }
 }
 
+/// What the block shows about the device, kept up to date by the signals of KDE Connect
+struct DeviceState {
+    name: String,
+    reachable: bool,
+    charge: i32,
+    charging: bool,
+    notif_count: i32,
+}
+
+impl DeviceState {
+    /// Asks KDE Connect for the whole state, when the block starts and when the bus is back
+    fn read(device_id: &str, old_kdeconnect: bool) -> Result<Self> {
+        let c = dbus_connections::session()?;
+        let p2 = c.with_path(
+            "org.kde.kdeconnect",
+            format!("/modules/kdeconnect/devices/{}", device_id),
+            deadline::limit_millis(5000),
+        );
+        let name: String = p2
+            .get("org.kde.kdeconnect.device", "name")
+            .unwrap_or_else(|_| String::from(""));
+        let reachable: bool = p2
+            .get("org.kde.kdeconnect.device", "isReachable")
+            .unwrap_or(false);
+
+        let (charge, charging) = if old_kdeconnect {
+            let (charge,): (i32,) = p2
+                .method_call("org.kde.kdeconnect.device.battery", "charge", ())
+                .unwrap_or((0,));
+            let (charging,): (bool,) = p2
+                .method_call("org.kde.kdeconnect.device.battery", "isCharging", ())
+                .unwrap_or((false,));
+            (charge, charging)
+        } else {
+            let p3 = c.with_path(
+                "org.kde.kdeconnect",
+                format!("/modules/kdeconnect/devices/{}/battery", device_id),
+                deadline::limit_millis(5000),
+            );
+            let charge: i32 = p3
+                .get("org.kde.kdeconnect.device.battery", "charge")
+                .unwrap_or(0);
+            let charging: bool = p3
+                .get("org.kde.kdeconnect.device.battery", "isCharging")
+                .unwrap_or(false);
+            (charge, charging)
+        };
+
+        let notifications = if old_kdeconnect {
+            p2.clone()
+        } else {
+            c.with_path(
+                "org.kde.kdeconnect",
+                format!("/modules/kdeconnect/devices/{}/notifications", device_id),
+                deadline::limit_millis(5000),
+            )
+        };
+        let (notifications,): (Vec<String>,) = notifications
+            .method_call(
+                "org.kde.kdeconnect.device.notifications",
+                "activeNotifications",
+                (),
+            )
+            .unwrap_or((vec![String::from("")],));
+
+        Ok(DeviceState {
+            name,
+            reachable,
+            charge,
+            charging,
+            notif_count: notifications.len() as i32,
+        })
+    }
+}
+
 impl ConfigBlock for KDEConnect {
     type Config = KDEConnectConfig;
 
@@ -86,24 +159,14 @@ impl ConfigBlock for KDEConnect {
         shared_config: SharedConfig,
         send: Sender<Task>,
     ) -> Result<Self> {
-        let send2 = send.clone();
-        let send3 = send.clone();
-        let send4 = send.clone();
-        let send5 = send.clone();
-        let send6 = send.clone();
-        let send7 = send.clone();
-
-        let c = Connection::new_session().block_error(
-            "kdeconnect",
-            &"Failed to establish D-Bus connection".to_string(),
-        )?;
+        let c = dbus_connections::session()?;
 
         let device_id = if block_config.device_id.is_none() {
             // If none specified in block config, just grab the first device found.
-            let p1 = c.with_proxy(
+            let p1 = c.with_path(
                 "org.kde.kdeconnect",
                 "/modules/kdeconnect",
-                Duration::from_millis(5000),
+                deadline::limit_millis(5000),
             );
             // method call opts: only_reachable=false, only_paired=true
             let (devices,): (Vec<String>,) = p1
@@ -123,25 +186,13 @@ impl ConfigBlock for KDEConnect {
             block_config.device_id.unwrap()
         };
 
-        let p2 = c.with_proxy(
-            "org.kde.kdeconnect",
-            format!("/modules/kdeconnect/devices/{}", device_id),
-            Duration::from_millis(5000),
-        );
-        let initial_name: String = p2
-            .get("org.kde.kdeconnect.device", "name")
-            .unwrap_or_else(|_| String::from(""));
-        let initial_reachable: bool = p2
-            .get("org.kde.kdeconnect.device", "isReachable")
-            .unwrap_or(false);
-
         // Test whether we are dealing with kdeconnect v20.08.03 or older,
         // or kdeconnect v20.11.80 or newer, so we can adapt to the differences.
         //
         // Starting with kdeconnect v20.11.80, the version output by the cli
         // matches the versioning scheme used by Ubuntu, where as before that it
         // was  1.3.x or 1.4.x.
-        let old_kdeconnect = Command::new("kdeconnect-cli")
+        let old_kdeconnect = command("kdeconnect-cli")
             .args(&["--version"])
             .output()
             .block_error(
@@ -155,83 +206,7 @@ impl ConfigBlock for KDEConnect {
             .unwrap()
             .contains("kdeconnect-cli 1.");
 
-        let initial_charge = if old_kdeconnect {
-            let (charge,): (i32,) = p2
-                .method_call("org.kde.kdeconnect.device.battery", "charge", ())
-                .unwrap_or((0,));
-            charge
-        } else {
-            let p3 = c.with_proxy(
-                "org.kde.kdeconnect",
-                format!("/modules/kdeconnect/devices/{}/battery", device_id),
-                Duration::from_millis(5000),
-            );
-            let charge: i32 = p3
-                .get("org.kde.kdeconnect.device.battery", "charge")
-                .unwrap_or(0);
-            charge
-        };
-
-        let initial_charging = if old_kdeconnect {
-            let (charging,): (bool,) = p2
-                .method_call("org.kde.kdeconnect.device.battery", "isCharging", ())
-                .unwrap_or((false,));
-            charging
-        } else {
-            let p3 = c.with_proxy(
-                "org.kde.kdeconnect",
-                format!("/modules/kdeconnect/devices/{}/battery", device_id),
-                Duration::from_millis(5000),
-            );
-            let charging: bool = p3
-                .get("org.kde.kdeconnect.device.battery", "isCharging")
-                .unwrap_or(false);
-            charging
-        };
-
-        let initial_notifications = if old_kdeconnect {
-            let (notifications,): (Vec<String>,) = p2
-                .method_call(
-                    "org.kde.kdeconnect.device.notifications",
-                    "activeNotifications",
-                    (),
-                )
-                .unwrap_or((vec![String::from("")],));
-            notifications
-        } else {
-            let p4 = c.with_proxy(
-                "org.kde.kdeconnect",
-                format!("/modules/kdeconnect/devices/{}/notifications", device_id),
-                Duration::from_millis(5000),
-            );
-            let (notifications,): (Vec<String>,) = p4
-                .method_call(
-                    "org.kde.kdeconnect.device.notifications",
-                    "activeNotifications",
-                    (),
-                )
-                .unwrap_or((vec![String::from("")],));
-            notifications
-        };
-
-        let device_id_copy = device_id.clone();
-        let device_name = Arc::new(Mutex::new(initial_name));
-        let device_name_copy = device_name.clone();
-        let charge = Arc::new(Mutex::new(initial_charge));
-        let charge_copy = charge.clone();
-        // TODO: revisit this lint
-        #[allow(clippy::mutex_atomic)]
-        let charging = Arc::new(Mutex::new(initial_charging));
-        let charging_copy = charging.clone();
-        let notif_count = Arc::new(Mutex::new(initial_notifications.len() as i32));
-        let notif_count_copy1 = notif_count.clone();
-        let notif_count_copy2 = notif_count.clone();
-        let notif_count_copy3 = notif_count.clone();
-        // TODO: revisit this lint
-        #[allow(clippy::mutex_atomic)]
-        let reachable = Arc::new(Mutex::new(initial_reachable));
-        let reachable_copy1 = reachable.clone();
-        let reachable_copy2 = reachable.clone();
+        let state = Arc::new(Mutex::new(DeviceState::read(&device_id, old_kdeconnect)?));
 
         // TODO: See if can reliably get the text and/or app of the most recent notification.
         // Will need to see if the order of notifications is guaranteed or not.
@@ -243,291 +218,130 @@ impl ConfigBlock for KDEConnect {
         //    Arc::new(Mutex::new(initial_notifications.get(0).unwrap().to_string()))
         //};
 
-        thread::Builder::new()
-            .name("kdeconnect".into())
-            .spawn(move || {
-                let c = Connection::new_session()
-                    .expect("Failed to establish D-Bus connection in thread");
-
-                let p1 = c.with_proxy(
-                    "org.kde.kdeconnect",
-                    format!("/modules/kdeconnect/devices/{}", device_id_copy),
-                    Duration::from_millis(5000),
-                );
-
-                let _device_name_handler = p1.match_signal(
-                    move |s: OrgKdeKdeconnectDeviceNameChanged, _: &Connection, _: &Message| {
-                        let mut name = device_name_copy.lock().unwrap();
-                        *name = s.name;
-
-                        // Tell block to update now.
-                        send2
-                            .send(Task {
-                                id,
-                                update_time: Instant::now(),
-                            })
-                            .unwrap();
-
-                        true
-                    },
-                );
-
-                let _phone_reachable_handler = p1.match_signal(
-                    move |s: OrgKdeKdeconnectDeviceReachableChanged,
-                          _: &Connection,
-                          _: &Message| {
-                        let mut reachable = reachable_copy1.lock().unwrap();
-                        *reachable = s.reachable;
-
-                        send6
-                            .send(Task {
-                                id,
-                                update_time: Instant::now(),
-                            })
-                            .unwrap();
-
-                        true
-                    },
-                );
-
-                if old_kdeconnect {
-                    let _battery_state_handler = p1.match_signal(
-                        move |s: OrgKdeKdeconnectDeviceBatteryStateChanged,
-                              _: &Connection,
-                              _: &Message| {
-                            let mut charging = charging_copy.lock().unwrap();
-                            *charging = s.charging;
-
-                            // Tell block to update now.
-                            // The older KDEConnect emits both stateChanged and chargeChanged
-                            // whenever there is an update regardless of whether or
-                            // not they both changed. So we only need to send updates
-                            // in one of the two battery signal handlers.
-                            send.send(Task {
-                                id,
-                                update_time: Instant::now(),
-                            })
-                            .unwrap();
-
-                            true
-                        },
-                    );
-
-                    let _battery_charge_handler = p1.match_signal(
-                        move |s: OrgKdeKdeconnectDeviceBatteryChargeChanged,
-                              _: &Connection,
-                              _: &Message| {
-                            let mut charge = charge_copy.lock().unwrap();
-                            *charge = s.charge;
-
-                            true
-                        },
-                    );
-                } else {
-                    let p2 = c.with_proxy(
-                        "org.kde.kdeconnect",
-                        format!("/modules/kdeconnect/devices/{}/battery", device_id_copy),
-                        Duration::from_millis(5000),
-                    );
-                    let _battery_state_handler = p2.match_signal(
-                        move |s: OrgKdeKdeconnectDeviceBatteryRefreshed,
-                              _: &Connection,
-                              _: &Message| {
-                            let mut charging = charging_copy.lock().unwrap();
-                            *charging = s.is_charging;
-
-                            let mut charge = charge_copy.lock().unwrap();
-                            *charge = s.charge;
-
-                            send.send(Task {
-                                id,
-                                update_time: Instant::now(),
-                            })
-                            .unwrap();
-
-                            true
-                        },
-                    );
-                };
-
-                if old_kdeconnect {
-                    let _notification_added_handler = p1.match_signal(
-                        move |_s: OrgKdeKdeconnectDeviceNotificationsNotificationPosted,
-                              _: &Connection,
-                              _: &Message| {
-                            let mut notif_count = notif_count_copy1.lock().unwrap();
-                            *notif_count += 1;
-
-                            // Tell block to update now.
-                            send3
-                                .send(Task {
-                                    id,
-                                    update_time: Instant::now(),
-                                })
-                                .unwrap();
-
-                            true
-                        },
-                    );
-
-                    let _notification_removed_handler = p1.match_signal(
-                        move |_s: OrgKdeKdeconnectDeviceNotificationsNotificationRemoved,
-                              _: &Connection,
-                              _: &Message| {
-                            let mut notif_count = notif_count_copy2.lock().unwrap();
-                            *notif_count = if *notif_count - 1 < 0 {
-                                0
-                            } else {
-                                *notif_count - 1
-                            };
-
-                            // Tell block to update now.
-                            send4
-                                .send(Task {
-                                    id,
-                                    update_time: Instant::now(),
-                                })
-                                .unwrap();
-
-                            true
-                        },
-                    );
-
-                    let _notification_all_removed_handler = p1.match_signal(
-                        move |_s: OrgKdeKdeconnectDeviceNotificationsAllNotificationsRemoved,
-                              _: &Connection,
-                              _: &Message| {
-                            let mut notif_count = notif_count_copy3.lock().unwrap();
-                            *notif_count = 0;
-
-                            // Tell block to update now.
-                            send5
-                                .send(Task {
-                                    id,
-                                    update_time: Instant::now(),
-                                })
-                                .unwrap();
-
-                            true
-                        },
-                    );
-                } else {
-                    let p3 = c.with_proxy(
-                        "org.kde.kdeconnect",
-                        format!(
-                            "/modules/kdeconnect/devices/{}/notifications",
-                            device_id_copy
-                        ),
-                        Duration::from_millis(5000),
-                    );
-
-                    let _notification_added_handler = p3.match_signal(
-                        move |_s: OrgKdeKdeconnectDeviceNotificationsNotificationPosted,
-                              _: &Connection,
-                              _: &Message| {
-                            let mut notif_count = notif_count_copy1.lock().unwrap();
-                            *notif_count += 1;
-
-                            // Tell block to update now.
-                            send3
-                                .send(Task {
-                                    id,
-                                    update_time: Instant::now(),
-                                })
-                                .unwrap();
-
-                            true
-                        },
-                    );
-
-                    let _notification_removed_handler = p3.match_signal(
-                        move |_s: OrgKdeKdeconnectDeviceNotificationsNotificationRemoved,
-                              _: &Connection,
-                              _: &Message| {
-                            let mut notif_count = notif_count_copy2.lock().unwrap();
-                            *notif_count = if *notif_count - 1 < 0 {
-                                0
-                            } else {
-                                *notif_count - 1
-                            };
-
-                            // Tell block to update now.
-                            send4
-                                .send(Task {
-                                    id,
-                                    update_time: Instant::now(),
-                                })
-                                .unwrap();
-
-                            true
-                        },
-                    );
-
-                    let _notification_all_removed_handler = p3.match_signal(
-                        move |_s: OrgKdeKdeconnectDeviceNotificationsAllNotificationsRemoved,
-                              _: &Connection,
-                              _: &Message| {
-                            let mut notif_count = notif_count_copy3.lock().unwrap();
-                            *notif_count = 0;
-
-                            // Tell block to update now.
-                            send5
-                                .send(Task {
-                                    id,
-                                    update_time: Instant::now(),
-                                })
-                                .unwrap();
+        let sender = "org.kde.kdeconnect".into();
+        let device: dbus::Path = format!("/modules/kdeconnect/devices/{}", device_id).into();
+        let (battery, notifications) = if old_kdeconnect {
+            (device.clone(), device.clone())
+        } else {
+            (
+                format!("/modules/kdeconnect/devices/{}/battery", device_id).into(),
+                format!("/modules/kdeconnect/devices/{}/notifications", device_id).into(),
+            )
+        };
+        let mut rules = vec![
+            OrgKdeKdeconnectDeviceNameChanged::match_str(Some(&sender), Some(&device)),
+            OrgKdeKdeconnectDeviceReachableChanged::match_str(Some(&sender), Some(&device)),
+            OrgKdeKdeconnectDeviceNotificationsNotificationPosted::match_str(
+                Some(&sender),
+                Some(&notifications),
+            ),
+            OrgKdeKdeconnectDeviceNotificationsNotificationRemoved::match_str(
+                Some(&sender),
+                Some(&notifications),
+            ),
+            OrgKdeKdeconnectDeviceNotificationsAllNotificationsRemoved::match_str(
+                Some(&sender),
+                Some(&notifications),
+            ),
+            OrgKdeKdeconnectDaemonDeviceVisibilityChanged::match_str(
+                Some(&sender),
+                Some(&"/modules/kdeconnect".into()),
+            ),
+        ];
+        if old_kdeconnect {
+            rules.push(OrgKdeKdeconnectDeviceBatteryStateChanged::match_str(
+                Some(&sender),
+                Some(&battery),
+            ));
+            rules.push(OrgKdeKdeconnectDeviceBatteryChargeChanged::match_str(
+                Some(&sender),
+                Some(&battery),
+            ));
+        } else {
+            rules.push(OrgKdeKdeconnectDeviceBatteryRefreshed::match_str(
+                Some(&sender),
+                Some(&battery),
+            ));
+        }
 
-                            true
-                        },
-                    );
-
-                    //if notif_text is ever implemented this may be handy
-                    //OrgKdeKdeconnectDeviceNotificationsNotificationUpdated
-                };
-
-                let p4 = c.with_proxy(
-                    "org.kde.kdeconnect",
-                    "/modules/kdeconnect",
-                    Duration::from_millis(5000),
-                );
-
-                let _phone_visible_handler = p4.match_signal(
-                    move |s: OrgKdeKdeconnectDaemonDeviceVisibilityChanged,
-                          _: &Connection,
-                          _: &Message| {
+        let device_id_copy = device_id.clone();
+        let state_copy = state.clone();
+        dbus_connections::watch("kdeconnect", BusType::Session, rules, move |event| {
+            match event {
+                // KDE Connect may have been restarted together with the bus
+                Event::Connected => match DeviceState::read(&device_id_copy, old_kdeconnect) {
+                    Ok(read) => *state_copy.lock().unwrap() = read,
+                    Err(_) => return,
+                },
+                Event::Signal(msg) => {
+                    let mut state = state_copy.lock().unwrap();
+                    if let Some(s) = OrgKdeKdeconnectDeviceNameChanged::from_message(msg) {
+                        state.name = s.name;
+                    } else if let Some(s) = OrgKdeKdeconnectDeviceReachableChanged::from_message(msg)
+                    {
+                        state.reachable = s.reachable;
+                    } else if let Some(s) =
+                        OrgKdeKdeconnectDeviceBatteryStateChanged::from_message(msg)
+                    {
+                        state.charging = s.charging;
+                    } else if let Some(s) =
+                        OrgKdeKdeconnectDeviceBatteryChargeChanged::from_message(msg)
+                    {
+                        // The older KDEConnect emits both stateChanged and chargeChanged
+                        // whenever there is an update regardless of whether or
+                        // not they both changed. So we only need to send updates
+                        // in one of the two battery signal handlers.
+                        state.charge = s.charge;
+                        return;
+                    } else if let Some(s) = OrgKdeKdeconnectDeviceBatteryRefreshed::from_message(msg)
+                    {
+                        state.charging = s.is_charging;
+                        state.charge = s.charge;
+                    } else if OrgKdeKdeconnectDeviceNotificationsNotificationPosted::from_message(
+                        msg,
+                    )
+                    .is_some()
+                    {
+                        state.notif_count += 1;
+                    } else if OrgKdeKdeconnectDeviceNotificationsNotificationRemoved::from_message(
+                        msg,
+                    )
+                    .is_some()
+                    {
+                        state.notif_count = (state.notif_count - 1).max(0);
+                    } else if OrgKdeKdeconnectDeviceNotificationsAllNotificationsRemoved::from_message(
+                        msg,
+                    )
+                    .is_some()
+                    {
+                        state.notif_count = 0;
+                    } else if let Some(s) =
+                        OrgKdeKdeconnectDaemonDeviceVisibilityChanged::from_message(msg)
+                    {
                         // TODO: check if s.id matches our device? Is visible same as reachable?
-                        let mut reachable = reachable_copy2.lock().unwrap();
-                        *reachable = s.is_visible;
-
-                        // Tell block to update now.
-                        send7
-                            .send(Task {
-                                id,
-                                update_time: Instant::now(),
-                            })
-                            .unwrap();
-
-                        true
-                    },
-                );
-
-                loop {
-                    c.process(Duration::from_millis(1000)).unwrap();
+                        state.reachable = s.is_visible;
+                    } else {
+                        //if notif_text is ever implemented this may be handy
+                        //OrgKdeKdeconnectDeviceNotificationsNotificationUpdated
+                        return;
+                    }
                 }
+            }
+
+            // Tell block to update now.
+            send.send(Task {
+                id,
+                update_time: Instant::now(),
             })
             .unwrap();
+        })?;
 
         Ok(KDEConnect {
             id,
             device_id,
-            device_name,
-            battery_charge: charge,
-            battery_state: charging,
-            notif_count,
+            state,
             // TODO
             //notif_text,
-            phone_reachable: reachable,
             bat_good: block_config.bat_good,
             bat_info: block_config.bat_info,
             bat_warning: block_config.bat_warning,
@@ -548,21 +362,16 @@ impl Block for KDEConnect {
     }
 
     fn update(&mut self) -> Result<Option<Update>> {
-        let charge = (*self
-            .battery_charge
-            .lock()
-            .block_error("kdeconnect", "failed to acquire lock for `charge`")?)
-            as i32;
-
-        let charging = *self
-            .battery_state
-            .lock()
-            .block_error("kdeconnect", "failed to acquire lock for `battery_state`")?;
-
-        let notif_count = *self
-            .notif_count
+        let state = self
+            .state
             .lock()
-            .block_error("kdeconnect", "failed to acquire lock for `notif_count`")?;
+            .block_error("kdeconnect", "failed to acquire lock for `state`")?;
+        let charge = state.charge;
+        let charging = state.charging;
+        let notif_count = state.notif_count;
+        let phone_reachable = state.reachable;
+        let name = state.name.clone();
+        drop(state);
 
         // TODO
         //let notif_text = (*self
@@ -571,17 +380,6 @@ impl Block for KDEConnect {
         //   .block_error("kdeconnect", "failed to acquire lock for `notif_text`")?)
         //   .clone();
 
-        let phone_reachable = *self
-            .phone_reachable
-            .lock()
-            .block_error("kdeconnect", "failed to acquire lock for `phone_reachable`")?;
-
-        let name = (*self
-            .device_name
-            .lock()
-            .block_error("kdeconnect", "failed to acquire lock for `name`")?)
-        .clone();
-
         let bat_icon = self
             .shared_config
             .get_icon(if charging {
@@ -596,7 +394,7 @@ impl Block for KDEConnect {
                     match self.shared_config.get_icon("bat_10") {
                         Ok(_) => false,
                         Err(_) => {
-                            eprintln!(
+                            tracing::warn!(
                                 "Icon bat_10 not found in your icons file. Please check NEWS.md"
                             );
                             true
@@ -648,11 +446,10 @@ impl Block for KDEConnect {
         if !phone_reachable {
             self.output.set_state(State::Critical);
             self.output.set_icon("phone_disconnected")?;
-            self.output
-                .set_texts(self.format_disconnected.render(&values)?);
+            self.output.set_format(&self.format_disconnected, &values)?;
         } else {
             self.output.set_icon("phone")?;
-            self.output.set_texts(self.format.render(&values)?);
+            self.output.set_format(&self.format, &values)?;
         }
 
         Ok(None)
@@ -1,25 +1,25 @@
 use std::collections::HashMap;
-use std::process::Command;
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
 
 use crossbeam_channel::Sender;
 use dbus::ffidisp::stdintf::org_freedesktop_dbus::Properties;
-use dbus::{
-    ffidisp::{MsgHandlerResult, MsgHandlerType},
-    Message,
-};
+use dbus::ffidisp::BusType;
+use dbus::Message;
 use serde_derive::Deserialize;
 use swayipc::{Connection, Event, EventType, InputChange};
 
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::SharedConfig;
+use crate::dbus_connections;
 use crate::de::deserialize_duration;
+use crate::deadline;
 use crate::errors::*;
 use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
 use crate::scheduler::Task;
+use crate::subprocess::command;
 use crate::widgets::text::TextWidget;
 use crate::widgets::I3BarWidget;
 
@@ -46,7 +46,9 @@ pub trait KeyboardLayoutMonitor {
 
     /// Monitor layout changes and send updates via the `update_request`
     /// channel. By default, this method does nothing.
-    fn monitor(&self, _id: usize, _update_request: Sender<Task>) {}
+    fn monitor(&self, _id: usize, _update_request: Sender<Task>) -> Result<()> {
+        Ok(())
+    }
 }
 
 pub struct SetXkbMap;
@@ -59,7 +61,7 @@ impl SetXkbMap {
 }
 
 fn setxkbmap_layouts() -> Result<String> {
-    let output = Command::new("setxkbmap")
+    let output = command("setxkbmap")
         .args(&["-query"])
         .output()
         .block_error("keyboard_layout", "Failed to execute setxkbmap.")
@@ -99,30 +101,35 @@ impl KeyboardLayoutMonitor for SetXkbMap {
     }
 }
 
-pub struct LocaleBus {
-    con: dbus::ffidisp::Connection,
-}
+pub struct LocaleBus;
 
 impl LocaleBus {
     pub fn new() -> Result<Self> {
-        let con = dbus::ffidisp::Connection::get_private(dbus::ffidisp::BusType::System)
-            .block_error("locale", "Failed to establish D-Bus connection.")?;
+        dbus_connections::system()?;
 
-        Ok(LocaleBus { con })
+        Ok(LocaleBus)
     }
 }
 
 impl KeyboardLayoutMonitor for LocaleBus {
     fn keyboard_layout(&self) -> Result<String> {
-        self.con
-            .with_path("org.freedesktop.locale1", "/org/freedesktop/locale1", 1000)
+        dbus_connections::system()?
+            .with_path(
+                "org.freedesktop.locale1",
+                "/org/freedesktop/locale1",
+                deadline::limit_millis(1000),
+            )
             .get("org.freedesktop.locale1", "X11Layout")
             .block_error("locale", "Failed to get X11Layout property.")
     }
 
     fn keyboard_variant(&self) -> Result<String> {
-        self.con
-            .with_path("org.freedesktop.locale1", "/org/freedesktop/locale1", 1000)
+        dbus_connections::system()?
+            .with_path(
+                "org.freedesktop.locale1",
+                "/org/freedesktop/locale1",
+                deadline::limit_millis(1000),
+            )
             .get("org.freedesktop.locale1", "X11Variant")
             .block_error("locale", "Failed to get X11Variant property.")
     }
@@ -134,37 +141,28 @@ impl KeyboardLayoutMonitor for LocaleBus {
     /// Monitor Locale property changes in a separate thread and send updates
     /// via the `update_request` channel.
     // TODO: pull the new value from the PropertiesChanged message instead of making another method call
-    fn monitor(&self, id: usize, update_request: Sender<Task>) {
-        thread::Builder::new()
-            .name("keyboard_layout".into())
-            .spawn(move || {
-                let con = dbus::ffidisp::Connection::get_private(dbus::ffidisp::BusType::System)
-                    .expect("Failed to establish D-Bus connection.");
-                let rule = "type='signal',\
-                        path='/org/freedesktop/locale1',\
-                        interface='org.freedesktop.DBus.Properties',\
-                        member='PropertiesChanged'";
-
-                // Skip the NameAcquired event.
-                con.incoming(10_000).next();
-
-                con.add_match(rule)
-                    .expect("Failed to add D-Bus match rule.");
-
-                loop {
-                    // TODO: This actually seems to trigger twice for each localectl
-                    // change.
-                    if con.incoming(10_000).next().is_some() {
-                        update_request
-                            .send(Task {
-                                id,
-                                update_time: Instant::now(),
-                            })
-                            .unwrap();
-                    }
-                }
-            })
-            .unwrap();
+    fn monitor(&self, id: usize, update_request: Sender<Task>) -> Result<()> {
+        let rule = "type='signal',\
+                path='/org/freedesktop/locale1',\
+                interface='org.freedesktop.DBus.Properties',\
+                member='PropertiesChanged'";
+        // The layout may have changed while the bus was away, so reconnecting updates the block
+        // as well
+        dbus_connections::watch(
+            "keyboard_layout",
+            BusType::System,
+            vec![rule.to_string()],
+            move |_| {
+                // TODO: This actually seems to trigger twice for each localectl
+                // change.
+                update_request
+                    .send(Task {
+                        id,
+                        update_time: Instant::now(),
+                    })
+                    .unwrap()
+            },
+        )
     }
 }
 
@@ -178,7 +176,7 @@ pub struct KbdDaemonBus {
 
 impl KbdDaemonBus {
     pub fn new() -> Result<Self> {
-        Command::new("setxkbmap")
+        command("setxkbmap")
             .arg("-version")
             .output()
             .block_error("kbddaemonbus", "setxkbmap not found")?;
@@ -192,8 +190,7 @@ impl KbdDaemonBus {
     }
 
     fn get_initial_layout_id() -> Result<u32> {
-        let c = dbus::ffidisp::Connection::get_private(dbus::ffidisp::BusType::Session)
-            .block_error("kbddaemonbus", "can't connect to dbus")?;
+        let c = dbus_connections::session()?;
 
         let send_msg = Message::new_method_call(
             "ru.gentoo.KbddService",
@@ -204,7 +201,7 @@ impl KbdDaemonBus {
         .block_error("kbddaemonbus", "Create get-layout-id message failure")?;
 
         let repl_msg = c
-            .send_with_reply_and_block(send_msg, 5000)
+            .send_with_reply_and_block(send_msg, deadline::limit_millis(5000))
             .block_error("kbddaemonbus", "Is kbdd running?")?;
 
         let current_layout_id: u32 = repl_msg
@@ -250,60 +247,35 @@ impl KeyboardLayoutMonitor for KbdDaemonBus {
 
     // Monitor KbdDaemon 'layoutChanged' property in a separate thread and send updates
     // via the `update_request` channel.
-    fn monitor(&self, id: usize, update_request: Sender<Task>) {
+    fn monitor(&self, id: usize, update_request: Sender<Task>) -> Result<()> {
         let arc = Arc::clone(&self.kbdd_layout_id);
-        thread::Builder::new()
-            .name("keyboard_layout".into())
-            .spawn(move || {
-                let c = dbus::ffidisp::Connection::get_private(dbus::ffidisp::BusType::Session)
-                    .unwrap();
-                c.add_match(
-                    "interface='ru.gentoo.kbdd',\
+        dbus_connections::watch(
+            "keyboard_layout",
+            BusType::Session,
+            vec!["interface='ru.gentoo.kbdd',\
                  member='layoutChanged',\
-                 path='/ru/gentoo/KbddService'",
-                )
-                .expect("Failed to add D-Bus match rule, is kbdd started?");
-
-                // skip NameAcquired
-                c.incoming(10_000).next();
-
-                c.add_handler(KbddMessageHandler(arc));
-                loop {
-                    for ci in c.iter(100_000) {
-                        if let dbus::ffidisp::ConnectionItem::Signal(_) = ci {
-                            update_request
-                                .send(Task {
-                                    id,
-                                    update_time: Instant::now(),
-                                })
-                                .unwrap();
-                        }
+                 path='/ru/gentoo/KbddService'"
+                .to_string()],
+            move |event| {
+                let layout = match event {
+                    // kbdd may have been restarted together with the bus
+                    dbus_connections::Event::Connected => {
+                        KbdDaemonBus::get_initial_layout_id().ok()
                     }
+                    dbus_connections::Event::Signal(msg) => msg.get1(),
+                };
+                if let Some(idx) = layout {
+                    let mut val = arc.lock().unwrap();
+                    *val = idx;
                 }
-            })
-            .unwrap();
-    }
-}
-
-struct KbddMessageHandler(Arc<Mutex<u32>>);
-
-impl dbus::ffidisp::MsgHandler for KbddMessageHandler {
-    fn handler_type(&self) -> MsgHandlerType {
-        dbus::ffidisp::MsgHandlerType::MsgType(dbus::MessageType::Signal)
-    }
-
-    fn handle_msg(&mut self, msg: &Message) -> Option<MsgHandlerResult> {
-        let layout: Option<u32> = msg.get1();
-        if let Some(idx) = layout {
-            let mut val = self.0.lock().unwrap();
-            *val = idx;
-        }
-        //handled=false - because we still need to call update_request.send in monitor
-        Some(MsgHandlerResult {
-            handled: false,
-            done: false,
-            reply: vec![],
-        })
+                update_request
+                    .send(Task {
+                        id,
+                        update_time: Instant::now(),
+                    })
+                    .unwrap();
+            },
+        )
     }
 }
 
@@ -366,7 +338,7 @@ impl KeyboardLayoutMonitor for Sway {
 
     /// Monitor layout changes in a separate thread and send updates
     /// via the `update_request` channel.
-    fn monitor(&self, id: usize, update_request: Sender<Task>) {
+    fn monitor(&self, id: usize, update_request: Sender<Task>) -> Result<()> {
         let arc = Arc::clone(&self.sway_kb_layout);
         thread::Builder::new()
             .name("keyboard_layout".into())
@@ -409,6 +381,7 @@ impl KeyboardLayoutMonitor for Sway {
                 }
             })
             .unwrap();
+        Ok(())
     }
 }
 
@@ -416,7 +389,7 @@ pub struct XkbSwitch;
 
 impl XkbSwitch {
     pub fn new() -> Result<XkbSwitch> {
-        Command::new("xkb-switch")
+        command("xkb-switch")
             .output()
             .block_error("keyboard_layout", "Failed to find xkb-switch in PATH")
             .map(|_| XkbSwitch)
@@ -425,7 +398,7 @@ impl XkbSwitch {
 
 fn xkb_switch_show_layout_and_variant() -> Result<(String, Option<String>)> {
     // This command should return a string like "layout(variant)" or "layout"
-    Command::new("xkb-switch")
+    command("xkb-switch")
         .args(&["-p"])
         .output()
         .block_error("keyboard_layout", "Failed to execute `xkb-switch -p`.")
@@ -514,18 +487,18 @@ impl ConfigBlock for KeyboardLayout {
             KeyboardLayoutDriver::SetXkbMap => Box::new(SetXkbMap::new()?),
             KeyboardLayoutDriver::LocaleBus => {
                 let monitor = LocaleBus::new()?;
-                monitor.monitor(id, send);
+                monitor.monitor(id, send)?;
                 Box::new(monitor)
             }
             KeyboardLayoutDriver::KbddBus => {
                 let monitor = KbdDaemonBus::new()?;
-                monitor.monitor(id, send);
+                monitor.monitor(id, send)?;
                 Box::new(monitor)
             }
             KeyboardLayoutDriver::XkbSwitch => Box::new(XkbSwitch::new()?),
             KeyboardLayoutDriver::Sway => {
                 let monitor = Sway::new(block_config.sway_kb_identifier)?;
-                monitor.monitor(id, send);
+                monitor.monitor(id, send)?;
                 Box::new(monitor)
             }
         };
@@ -564,7 +537,7 @@ impl Block for KeyboardLayout {
             "variant" => Value::from_string(variant)
         );
 
-        self.output.set_texts(self.format.render(&values)?);
+        self.output.set_format(&self.format, &values)?;
         Ok(self.update_interval.map(|d| d.into()))
     }
 
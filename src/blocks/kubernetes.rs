@@ -0,0 +1,438 @@
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::subprocess::command;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+const BLOCK_NAME: &str = "kubernetes";
+
+/// How long the requests to the API server may take
+const REQUEST_TIMEOUT: &str = "--request-timeout=5s";
+
+pub struct Kubernetes {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    update_interval: Duration,
+    health_interval: Duration,
+    namespace: Option<String>,
+    check_nodes: bool,
+    check_pods: bool,
+    /// The last health check, which is reused until `health_interval` has passed
+    health: Option<(Instant, Health)>,
+    context: Option<Context>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct KubernetesConfig {
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// The interval of the requests to the API server for the nodes and the pods
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub health_interval: Duration,
+
+    /// The namespace of the pods, the one of the context by default
+    pub namespace: Option<String>,
+
+    pub format: FormatTemplate,
+}
+
+impl Default for KubernetesConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(5),
+            health_interval: Duration::from_secs(60),
+            namespace: None,
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+/// The parts of `kubectl config view --minify -o json` that are shown
+#[derive(Deserialize, Debug)]
+struct Kubeconfig {
+    #[serde(rename = "current-context", default)]
+    current_context: String,
+    #[serde(default)]
+    contexts: Vec<NamedContext>,
+}
+
+#[derive(Deserialize, Debug)]
+struct NamedContext {
+    name: String,
+    context: ContextDetails,
+}
+
+#[derive(Deserialize, Debug)]
+struct ContextDetails {
+    #[serde(default)]
+    cluster: String,
+    #[serde(default)]
+    user: String,
+    namespace: Option<String>,
+}
+
+/// The current context
+#[derive(Debug, Clone, PartialEq)]
+struct Context {
+    name: String,
+    cluster: String,
+    user: String,
+    namespace: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct List<T> {
+    items: Vec<T>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Node {
+    #[serde(default)]
+    status: NodeStatus,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct NodeStatus {
+    #[serde(default)]
+    conditions: Vec<NodeCondition>,
+}
+
+#[derive(Deserialize, Debug)]
+struct NodeCondition {
+    #[serde(rename = "type")]
+    kind: String,
+    status: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Pod {
+    #[serde(default)]
+    status: PodStatus,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct PodStatus {
+    /// Like `Running`, `Pending`, `Succeeded`, `Failed` or `Unknown`
+    #[serde(default)]
+    phase: String,
+    #[serde(rename = "containerStatuses", default)]
+    container_statuses: Vec<ContainerStatus>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ContainerStatus {
+    #[serde(default)]
+    state: ContainerState,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct ContainerState {
+    waiting: Option<Waiting>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Waiting {
+    /// Like `ContainerCreating` or `CrashLoopBackOff`
+    #[serde(default)]
+    reason: String,
+}
+
+impl Node {
+    fn is_ready(&self) -> bool {
+        self.status
+            .conditions
+            .iter()
+            .any(|condition| condition.kind == "Ready" && condition.status == "True")
+    }
+}
+
+impl Pod {
+    /// Whether the pod failed or keeps failing to start, like in `CrashLoopBackOff` or
+    /// `ImagePullBackOff`. Pods that are starting and finished pods don't count.
+    fn is_failing(&self) -> bool {
+        match self.status.phase.as_str() {
+            "Failed" | "Unknown" => true,
+            "Succeeded" => false,
+            _ => self
+                .status
+                .container_statuses
+                .iter()
+                .any(|status| match &status.state.waiting {
+                    Some(waiting) => !matches!(
+                        waiting.reason.as_str(),
+                        "ContainerCreating" | "PodInitializing"
+                    ),
+                    None => false,
+                }),
+        }
+    }
+}
+
+/// The counts of the nodes and the pods that were queried
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Health {
+    nodes: Option<(usize, usize)>,
+    pods: Option<(usize, usize)>,
+}
+
+fn parse_context(kubeconfig: &str) -> Result<Context> {
+    let kubeconfig: Kubeconfig = serde_json::from_str(kubeconfig).block_error(
+        BLOCK_NAME,
+        "failed to parse the output of 'kubectl config view'",
+    )?;
+    let Kubeconfig {
+        current_context,
+        contexts,
+    } = kubeconfig;
+    let current = contexts
+        .into_iter()
+        .find(|context| context.name == current_context)
+        .block_error(BLOCK_NAME, "no current context")?;
+    Ok(Context {
+        name: current.name,
+        cluster: current.context.cluster,
+        user: current.context.user,
+        namespace: current
+            .context
+            .namespace
+            .unwrap_or_else(|| "default".to_string()),
+    })
+}
+
+/// The context after `current` in `contexts`, or before it if `backwards`
+fn next_context<'a>(contexts: &'a [&str], current: &str, backwards: bool) -> Option<&'a str> {
+    if contexts.is_empty() {
+        return None;
+    }
+    let position = contexts.iter().position(|context| *context == current);
+    let count = contexts.len();
+    let next = match (position, backwards) {
+        (Some(position), false) => (position + 1) % count,
+        (Some(position), true) => (position + count - 1) % count,
+        (None, _) => 0,
+    };
+    Some(contexts[next])
+}
+
+/// The number of nodes, and of those that aren't ready
+fn count_nodes(nodes: &str) -> Result<(usize, usize)> {
+    let nodes: List<Node> =
+        serde_json::from_str(nodes).block_error(BLOCK_NAME, "unexpected list of nodes")?;
+    let not_ready = nodes.items.iter().filter(|node| !node.is_ready()).count();
+    Ok((nodes.items.len(), not_ready))
+}
+
+/// The number of pods, and of those that are failing
+fn count_pods(pods: &str) -> Result<(usize, usize)> {
+    let pods: List<Pod> =
+        serde_json::from_str(pods).block_error(BLOCK_NAME, "unexpected list of pods")?;
+    let failing = pods.items.iter().filter(|pod| pod.is_failing()).count();
+    Ok((pods.items.len(), failing))
+}
+
+fn kubectl(args: &[&str]) -> Result<String> {
+    let output = command("kubectl")
+        .args(args)
+        .output()
+        .block_error(BLOCK_NAME, "failed to run 'kubectl'")?;
+    if !output.status.success() {
+        return Err(BlockError(
+            BLOCK_NAME.to_string(),
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+impl Kubernetes {
+    fn check_health(&self, context: &Context) -> Result<Health> {
+        let mut health = Health::default();
+        if self.check_nodes {
+            let nodes = kubectl(&[
+                "--context",
+                &context.name,
+                REQUEST_TIMEOUT,
+                "get",
+                "nodes",
+                "-o",
+                "json",
+            ])?;
+            health.nodes = Some(count_nodes(&nodes)?);
+        }
+        if self.check_pods {
+            let namespace = self.namespace.as_deref().unwrap_or(&context.namespace);
+            let pods = kubectl(&[
+                "--context",
+                &context.name,
+                REQUEST_TIMEOUT,
+                "get",
+                "pods",
+                "--namespace",
+                namespace,
+                "-o",
+                "json",
+            ])?;
+            health.pods = Some(count_pods(&pods)?);
+        }
+        Ok(health)
+    }
+
+    fn cycle_context(&mut self, backwards: bool) -> Result<()> {
+        let contexts = kubectl(&["config", "get-contexts", "-o", "name"])?;
+        let contexts: Vec<&str> = contexts.lines().map(str::trim).collect();
+        let current = self
+            .context
+            .as_ref()
+            .map_or("", |context| context.name.as_str());
+        if let Some(next) = next_context(&contexts, current, backwards) {
+            kubectl(&["config", "use-context", next])?;
+            // The health of the other cluster is checked right away
+            self.health = None;
+        }
+        Ok(())
+    }
+}
+
+impl ConfigBlock for Kubernetes {
+    type Config = KubernetesConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let format = block_config.format.with_default("{context}")?;
+        Ok(Kubernetes {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("kubernetes")?,
+            check_nodes: format.contains("nodes") || format.contains("not_ready"),
+            check_pods: format.contains("pods") || format.contains("failing"),
+            format,
+            update_interval: block_config.interval,
+            health_interval: block_config.health_interval,
+            namespace: block_config.namespace,
+            health: None,
+            context: None,
+        })
+    }
+}
+
+impl Block for Kubernetes {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let context = parse_context(&kubectl(&["config", "view", "--minify", "-o", "json"])?)?;
+        if self.context.as_ref() != Some(&context) {
+            self.health = None;
+        }
+        let health = match &self.health {
+            Some((checked, health)) if checked.elapsed() < self.health_interval => health.clone(),
+            _ => {
+                let health = self.check_health(&context)?;
+                self.health = Some((Instant::now(), health.clone()));
+                health
+            }
+        };
+
+        let mut values = map!(
+            "context" => Value::from_string(context.name.clone()),
+            "cluster" => Value::from_string(context.cluster.clone()),
+            "user" => Value::from_string(context.user.clone()),
+            "namespace" => Value::from_string(
+                self.namespace.clone().unwrap_or_else(|| context.namespace.clone())
+            ),
+        );
+        if let Some((nodes, not_ready)) = health.nodes {
+            values.insert("nodes", Value::from_integer(nodes as i64));
+            values.insert("not_ready", Value::from_integer(not_ready as i64));
+        }
+        if let Some((pods, failing)) = health.pods {
+            values.insert("pods", Value::from_integer(pods as i64));
+            values.insert("failing", Value::from_integer(failing as i64));
+        }
+        self.text.set_state(match (health.nodes, health.pods) {
+            (Some((_, not_ready)), _) if not_ready > 0 => State::Critical,
+            (_, Some((_, failing))) if failing > 0 => State::Warning,
+            _ => State::Idle,
+        });
+        self.text.set_format(&self.format, &values)?;
+        self.context = Some(context);
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        match event.button {
+            MouseButton::Left => self.cycle_context(false)?,
+            MouseButton::Right => self.cycle_context(true)?,
+            _ => return Ok(()),
+        }
+        self.update()?;
+        Ok(())
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kubectl_output() {
+        let context = parse_context(
+            r#"{"kind":"Config","apiVersion":"v1","preferences":{},
+                "clusters":[{"name":"kind-dev","cluster":{"server":"https://127.0.0.1:6443"}}],
+                "users":[{"name":"kind-dev","user":{"client-certificate-data":"REDACTED"}}],
+                "contexts":[{"name":"kind-dev","context":{"cluster":"kind-dev","user":"kind-dev"}}],
+                "current-context":"kind-dev"}"#,
+        )
+        .unwrap();
+        assert_eq!(context.name, "kind-dev");
+        assert_eq!(context.namespace, "default");
+        assert!(parse_context(r#"{"contexts":[],"current-context":""}"#).is_err());
+
+        let contexts = ["dev", "staging", "prod"];
+        assert_eq!(next_context(&contexts, "dev", false), Some("staging"));
+        assert_eq!(next_context(&contexts, "prod", false), Some("dev"));
+        assert_eq!(next_context(&contexts, "dev", true), Some("prod"));
+        assert_eq!(next_context(&contexts, "gone", false), Some("dev"));
+        assert_eq!(next_context(&[], "dev", false), None);
+
+        let nodes = r#"{"items":[
+            {"status":{"conditions":[{"type":"MemoryPressure","status":"False"},
+                                     {"type":"Ready","status":"True"}]}},
+            {"status":{"conditions":[{"type":"Ready","status":"Unknown"}]}}]}"#;
+        assert_eq!(count_nodes(nodes).unwrap(), (2, 1));
+
+        let pods = r#"{"items":[
+            {"status":{"phase":"Running","containerStatuses":[{"state":{"running":{}}}]}},
+            {"status":{"phase":"Running","containerStatuses":[
+                {"state":{"waiting":{"reason":"CrashLoopBackOff"}}}]}},
+            {"status":{"phase":"Pending","containerStatuses":[
+                {"state":{"waiting":{"reason":"ContainerCreating"}}}]}},
+            {"status":{"phase":"Pending"}},
+            {"status":{"phase":"Succeeded"}},
+            {"status":{"phase":"Failed"}}]}"#;
+        assert_eq!(count_pods(pods).unwrap(), (6, 2));
+    }
+}
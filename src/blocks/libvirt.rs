@@ -0,0 +1,283 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::subprocess::command;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+const BLOCK_NAME: &str = "libvirt";
+
+pub struct Libvirt {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    update_interval: Duration,
+    uri: String,
+    domain: Option<String>,
+    /// The CPU time of the domain in nanoseconds at the last update, for `{cpu}`
+    previous_cpu_time: Option<(Instant, u64)>,
+    /// The state of the domain at the last update, which the clicks depend on
+    domain_state: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct LibvirtConfig {
+    /// The libvirt connection
+    #[serde(default = "LibvirtConfig::default_uri")]
+    pub uri: String,
+
+    /// The name of a virtual machine that is shown instead of the counts
+    #[serde(default)]
+    pub domain: Option<String>,
+
+    #[serde(
+        default = "LibvirtConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    #[serde(default)]
+    pub format: FormatTemplate,
+}
+
+impl LibvirtConfig {
+    fn default_uri() -> String {
+        "qemu:///system".to_string()
+    }
+
+    fn default_interval() -> Duration {
+        Duration::from_secs(5)
+    }
+}
+
+/// The domains of `virsh --quiet list --all` by state, like ` 1    debian   running` or
+/// ` -    win10    shut off`
+fn parse_list(list: &str) -> HashMap<String, usize> {
+    let mut states = HashMap::new();
+    for line in list.lines() {
+        let state = line
+            .split_whitespace()
+            .skip(2)
+            .collect::<Vec<_>>()
+            .join(" ");
+        if !state.is_empty() {
+            *states.entry(state).or_insert(0) += 1;
+        }
+    }
+    states
+}
+
+/// The values of `virsh domstats`, like `  cpu.time=32466312968`
+fn parse_domstats(stats: &str) -> HashMap<&str, u64> {
+    stats
+        .lines()
+        .filter_map(|line| line.trim().split_once('='))
+        .filter_map(|(key, value)| Some((key, value.parse().ok()?)))
+        .collect()
+}
+
+/// The name of `state.state` of `virsh domstats`, like the ones of `virsh domstate`
+fn state_name(state: u64) -> &'static str {
+    match state {
+        1 => "running",
+        2 => "idle",
+        3 => "paused",
+        4 => "in shutdown",
+        5 => "shut off",
+        6 => "crashed",
+        7 => "pmsuspended",
+        _ => "no state",
+    }
+}
+
+impl Libvirt {
+    fn virsh(&self, args: &[&str]) -> Result<String> {
+        let output = command("virsh")
+            .args(["--quiet", "--connect", &self.uri])
+            .args(args)
+            .output()
+            .block_error(BLOCK_NAME, "failed to run 'virsh'")?;
+        if !output.status.success() {
+            return Err(BlockError(
+                BLOCK_NAME.to_string(),
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    fn update_counts(&mut self) -> Result<()> {
+        let states = parse_list(&self.virsh(&["list", "--all"])?);
+        let count = |state: &str| states.get(state).copied().unwrap_or(0);
+        let total: usize = states.values().sum();
+        let (running, paused) = (count("running"), count("paused"));
+        let values = map!(
+            "running" => Value::from_integer(running as i64),
+            "paused" => Value::from_integer(paused as i64),
+            "stopped" => Value::from_integer((total - running - paused) as i64),
+            "total" => Value::from_integer(total as i64),
+        );
+        self.text.set_state(match count("crashed") {
+            0 => State::Idle,
+            _ => State::Critical,
+        });
+        self.text.set_format(&self.format, &values)
+    }
+
+    fn update_domain(&mut self, domain: &str) -> Result<()> {
+        let output = self.virsh(&[
+            "domstats",
+            "--state",
+            "--cpu-total",
+            "--balloon",
+            "--vcpu",
+            domain,
+        ])?;
+        let stats = parse_domstats(&output);
+        let now = Instant::now();
+        let state = state_name(stats.get("state.state").copied().unwrap_or(0));
+
+        // In percents of the virtual CPUs of the domain, from the second update on
+        let cpu_time = stats.get("cpu.time").copied();
+        let vcpus = stats.get("vcpu.current").copied().unwrap_or(1).max(1);
+        let cpu = match (self.previous_cpu_time, cpu_time) {
+            (Some((then, previous)), Some(cpu_time)) if state == "running" => {
+                let elapsed = (now - then).as_nanos() as f64;
+                Some(cpu_time.saturating_sub(previous) as f64 / elapsed / vcpus as f64 * 100.)
+            }
+            _ => None,
+        };
+        self.previous_cpu_time = cpu_time.map(|cpu_time| (now, cpu_time));
+
+        // In KiB. What the guest uses if its balloon driver reports it, else what QEMU uses.
+        let memory = match (stats.get("balloon.available"), stats.get("balloon.unused")) {
+            (Some(available), Some(unused)) => Some(available.saturating_sub(*unused)),
+            _ => stats.get("balloon.rss").copied(),
+        };
+        let memory_total = stats.get("balloon.current").copied();
+        let running = state == "running";
+        let kib = |value: Option<u64>| match value.filter(|_| running) {
+            Some(value) => Value::from_float(value as f64 * 1024.).bytes(),
+            None => Value::from_string(String::new()),
+        };
+
+        let values = map!(
+            "name" => Value::from_string(domain.to_string()),
+            "state" => Value::from_string(state.to_string()),
+            "cpu" => match cpu {
+                Some(cpu) => Value::from_float(cpu.clamp(0., 100.)).percents(),
+                None => Value::from_string(String::new()),
+            },
+            "vcpus" => Value::from_integer(vcpus as i64),
+            "memory" => kib(memory),
+            "memory_total" => kib(memory_total),
+        );
+        self.text.set_state(match state {
+            "running" => State::Good,
+            "paused" | "in shutdown" | "pmsuspended" => State::Warning,
+            "crashed" => State::Critical,
+            _ => State::Idle,
+        });
+        self.domain_state = Some(state.to_string());
+        self.text.set_format(&self.format, &values)
+    }
+}
+
+impl ConfigBlock for Libvirt {
+    type Config = LibvirtConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let default_format = match block_config.domain {
+            Some(_) => "{name} {cpu} {memory}",
+            None => "{running}",
+        };
+        Ok(Libvirt {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("vm")?,
+            format: block_config.format.with_default(default_format)?,
+            update_interval: block_config.interval,
+            uri: block_config.uri,
+            domain: block_config.domain,
+            previous_cpu_time: None,
+            domain_state: None,
+        })
+    }
+}
+
+impl Block for Libvirt {
+    fn update(&mut self) -> Result<Option<Update>> {
+        match self.domain.clone() {
+            Some(domain) => self.update_domain(&domain)?,
+            None => self.update_counts()?,
+        }
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        let domain = match &self.domain {
+            Some(domain) => domain.clone(),
+            None => return Ok(()),
+        };
+        // Right clicks shut down, so a domain isn't shut down by a stray left click
+        let action = match (event.button, self.domain_state.as_deref()) {
+            (MouseButton::Left, Some("shut off")) | (MouseButton::Left, Some("crashed")) => "start",
+            (MouseButton::Left, Some("paused")) => "resume",
+            (MouseButton::Right, Some("running")) => "shutdown",
+            _ => return Ok(()),
+        };
+        self.virsh(&[action, &domain])?;
+        self.update()?;
+        Ok(())
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn virsh_output() {
+        let states = parse_list(
+            " 1    debian      running\n 2    pihole      running\n -    win10       shut off\n",
+        );
+        assert_eq!(states.get("running"), Some(&2));
+        assert_eq!(states.get("shut off"), Some(&1));
+        assert_eq!(parse_list("").len(), 0);
+
+        let stats = parse_domstats(
+            "Domain: 'debian'\n  state.state=1\n  state.reason=1\n  cpu.time=32466312968\n  \
+             balloon.current=2097152\n  balloon.rss=1338624\n  vcpu.current=2\n  \
+             vcpu.0.state=1\n",
+        );
+        assert_eq!(stats.get("cpu.time"), Some(&32466312968));
+        assert_eq!(stats.get("balloon.rss"), Some(&1338624));
+        assert_eq!(stats.get("Domain: 'debian'"), None);
+        assert_eq!(state_name(stats["state.state"]), "running");
+    }
+}
@@ -0,0 +1,370 @@
+use std::env;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::{LogicalDirection, Scrolling, SharedConfig};
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::http;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::services::Service;
+use crate::subprocess::spawn_child_async;
+use crate::util::escape_pango_text;
+use crate::widgets::{text::TextWidget, I3BarWidget, State};
+
+const TWITCH_CLIENT_ID_ENV: &str = "I3RS_TWITCH_CLIENT_ID";
+const TWITCH_TOKEN_ENV: &str = "I3RS_TWITCH_TOKEN";
+const YOUTUBE_API_KEY_ENV: &str = "I3RS_YOUTUBE_API_KEY";
+const BLOCK_NAME: &str = "live_streams";
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "name", rename_all = "lowercase")]
+pub enum StreamService {
+    Twitch {
+        #[serde(default = "StreamService::getenv_twitch_client_id")]
+        client_id: Option<String>,
+        #[serde(default = "StreamService::getenv_twitch_token")]
+        token: Option<String>,
+        /// Login names of the streamers
+        channels: Vec<String>,
+    },
+    #[serde(rename = "youtube")]
+    YouTube {
+        #[serde(default = "StreamService::getenv_youtube_api_key")]
+        api_key: Option<String>,
+        /// Channel IDs (the `UC...` part of the channel URL)
+        channels: Vec<String>,
+    },
+}
+
+impl StreamService {
+    fn getenv_twitch_client_id() -> Option<String> {
+        env::var(TWITCH_CLIENT_ID_ENV).ok()
+    }
+    fn getenv_twitch_token() -> Option<String> {
+        env::var(TWITCH_TOKEN_ENV).ok()
+    }
+    fn getenv_youtube_api_key() -> Option<String> {
+        env::var(YOUTUBE_API_KEY_ENV).ok()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct LiveStream {
+    channel: String,
+    title: String,
+    viewers: i64,
+    url: String,
+}
+
+pub struct LiveStreams {
+    id: usize,
+    text: TextWidget,
+    update_interval: Duration,
+    format: FormatTemplate,
+    service: StreamService,
+    hide_when_offline: bool,
+    open_command: String,
+    scrolling: Scrolling,
+    streams: Vec<LiveStream>,
+    selected: usize,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct LiveStreamsConfig {
+    #[serde(
+        default = "LiveStreamsConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    #[serde(default)]
+    pub format: FormatTemplate,
+
+    pub service: StreamService,
+
+    /// Hide the block when none of the channels is live
+    #[serde(default)]
+    pub hide_when_offline: bool,
+
+    /// Command used to open the selected stream
+    #[serde(default = "LiveStreamsConfig::default_open_command")]
+    pub open_command: String,
+}
+
+impl LiveStreamsConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(300)
+    }
+
+    fn default_open_command() -> String {
+        "xdg-open".to_string()
+    }
+}
+
+fn missing_option(option: &str, env: &str) -> Error {
+    ConfigurationError(
+        BLOCK_NAME.to_string(),
+        format!(
+            "Missing member 'service.{}'. Add the member or configure with the environment variable {}",
+            option, env
+        ),
+    )
+}
+
+impl LiveStreams {
+    fn fetch_twitch(client_id: &str, token: &str, channels: &[String]) -> Result<Vec<LiveStream>> {
+        if channels.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // Refer to https://dev.twitch.tv/docs/api/reference#get-streams
+        let query = channels
+            .iter()
+            .map(|c| format!("user_login={}", http::encode(c)))
+            .collect::<Vec<String>>()
+            .join("&");
+        let url = format!("https://api.twitch.tv/helix/streams?{}", query);
+        let auth = format!("Bearer {}", token);
+        let headers = vec![("Client-Id", client_id), ("Authorization", auth.as_str())];
+        let output = http::http_get_json(&url, Some(Duration::from_secs(5)), headers)?;
+
+        if output.code != 200 {
+            return Err(BlockError(
+                BLOCK_NAME.to_string(),
+                format!("Twitch API returned code {}", output.code),
+            ));
+        }
+
+        let data = output
+            .content
+            .get("data")
+            .and_then(|d| d.as_array())
+            .block_error(BLOCK_NAME, "malformed Twitch API response")?;
+
+        let mut streams: Vec<(usize, LiveStream)> = data
+            .iter()
+            .filter(|s| s.get("type").and_then(|t| t.as_str()) == Some("live"))
+            .filter_map(|s| {
+                let login = s.get("user_login")?.as_str()?;
+                // Keep the order of the configuration, so that the "first" channel is predictable
+                let position = channels
+                    .iter()
+                    .position(|c| c.eq_ignore_ascii_case(login))
+                    .unwrap_or(usize::MAX);
+                let stream = LiveStream {
+                    channel: s
+                        .get("user_name")
+                        .and_then(|n| n.as_str())
+                        .unwrap_or(login)
+                        .to_string(),
+                    title: s
+                        .get("title")
+                        .and_then(|t| t.as_str())
+                        .unwrap_or_default()
+                        .to_string(),
+                    viewers: s
+                        .get("viewer_count")
+                        .and_then(|v| v.as_i64())
+                        .unwrap_or_default(),
+                    url: format!("https://www.twitch.tv/{}", login),
+                };
+                Some((position, stream))
+            })
+            .collect();
+        streams.sort_by_key(|(position, _)| *position);
+
+        Ok(streams.into_iter().map(|(_, stream)| stream).collect())
+    }
+
+    fn fetch_youtube(api_key: &str, channels: &[String]) -> Result<Vec<LiveStream>> {
+        let mut streams = Vec::new();
+
+        // Refer to https://developers.google.com/youtube/v3/docs/search/list
+        // Note that every search costs 100 quota units, so keep the interval reasonably high.
+        for channel in channels {
+            let url = format!(
+                "https://www.googleapis.com/youtube/v3/search?part=snippet&channelId={}&eventType=live&type=video&key={}",
+                http::encode(channel),
+                http::encode(api_key)
+            );
+            let output = http::http_get_json(&url, Some(Duration::from_secs(5)), vec![])?;
+
+            if let Some(message) = output.content.pointer("/error/message") {
+                return Err(BlockError(
+                    BLOCK_NAME.to_string(),
+                    format!(
+                        "YouTube API error: {}",
+                        message.as_str().unwrap_or_default()
+                    ),
+                ));
+            }
+
+            if let Some(item) = output.content.pointer("/items/0") {
+                let video_id = item
+                    .pointer("/id/videoId")
+                    .and_then(|v| v.as_str())
+                    .block_error(BLOCK_NAME, "malformed YouTube API response")?;
+                streams.push(LiveStream {
+                    channel: item
+                        .pointer("/snippet/channelTitle")
+                        .and_then(|t| t.as_str())
+                        .unwrap_or(channel)
+                        .to_string(),
+                    title: item
+                        .pointer("/snippet/title")
+                        .and_then(|t| t.as_str())
+                        .unwrap_or_default()
+                        .to_string(),
+                    // The search endpoint doesn't report the number of viewers
+                    viewers: 0,
+                    url: format!("https://www.youtube.com/watch?v={}", video_id),
+                });
+            }
+        }
+
+        Ok(streams)
+    }
+
+    fn fetch(&self) -> Result<Vec<LiveStream>> {
+        match &self.service {
+            StreamService::Twitch {
+                client_id,
+                token,
+                channels,
+            } => {
+                let client_id = client_id
+                    .as_ref()
+                    .ok_or_else(|| missing_option("client_id", TWITCH_CLIENT_ID_ENV))?;
+                let token = token
+                    .as_ref()
+                    .ok_or_else(|| missing_option("token", TWITCH_TOKEN_ENV))?;
+                Self::fetch_twitch(client_id, token, channels)
+            }
+            StreamService::YouTube { api_key, channels } => {
+                let api_key = api_key
+                    .as_ref()
+                    .ok_or_else(|| missing_option("api_key", YOUTUBE_API_KEY_ENV))?;
+                Self::fetch_youtube(api_key, channels)
+            }
+        }
+    }
+
+    fn render(&mut self) -> Result<()> {
+        let selected = self.streams.get(self.selected);
+        let values = map!(
+            "count" => Value::from_integer(self.streams.len() as i64),
+            "first" => Value::from_string(selected.map(|s| escape_pango_text(&s.channel)).unwrap_or_default()),
+            "title" => Value::from_string(selected.map(|s| escape_pango_text(&s.title)).unwrap_or_default()),
+            "viewers" => Value::from_integer(selected.map(|s| s.viewers).unwrap_or_default()),
+        );
+        self.text.set_format(&self.format, &values)?;
+        self.text.set_state(if self.streams.is_empty() {
+            State::Idle
+        } else {
+            State::Info
+        });
+        Ok(())
+    }
+}
+
+impl ConfigBlock for LiveStreams {
+    type Config = LiveStreamsConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let scrolling = shared_config.scrolling;
+        Ok(LiveStreams {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("live")?,
+            update_interval: block_config.interval,
+            format: block_config.format.with_default("{count} {first}")?,
+            service: block_config.service,
+            hide_when_offline: block_config.hide_when_offline,
+            open_command: block_config.open_command,
+            scrolling,
+            streams: Vec::new(),
+            selected: 0,
+        })
+    }
+
+    fn default_depends_on() -> &'static [Service] {
+        &[Service::Network]
+    }
+}
+
+impl Block for LiveStreams {
+    fn update(&mut self) -> Result<Option<Update>> {
+        match self.fetch() {
+            Ok(streams) => {
+                self.streams = streams;
+                if self.selected >= self.streams.len() {
+                    self.selected = 0;
+                }
+                self.render()?;
+            }
+            Err(ConfigurationError(_, msg)) => {
+                return Err(BlockError(BLOCK_NAME.to_string(), msg));
+            }
+            Err(_) => {
+                // Network errors are usually temporary
+                self.text.set_text("×".to_string());
+                self.text.set_state(State::Warning);
+            }
+        }
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        if self.hide_when_offline && self.streams.is_empty() {
+            vec![]
+        } else {
+            vec![&self.text]
+        }
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        match event.button {
+            MouseButton::Left => {
+                if let Some(stream) = self.streams.get(self.selected) {
+                    spawn_child_async(&self.open_command, &[&stream.url])
+                        .block_error(BLOCK_NAME, "could not spawn open command")?;
+                }
+            }
+            MouseButton::Right => {
+                self.update()?;
+            }
+            _ => {
+                if self.streams.is_empty() {
+                    return Ok(());
+                }
+                let count = self.streams.len();
+                match self.scrolling.to_logical_direction(event.button) {
+                    Some(LogicalDirection::Up) => self.selected = (self.selected + 1) % count,
+                    Some(LogicalDirection::Down) => {
+                        self.selected = (self.selected + count - 1) % count
+                    }
+                    None => return Ok(()),
+                }
+                self.render()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
@@ -123,7 +123,7 @@ impl Block for Load {
             _ => State::Idle,
         });
 
-        self.text.set_texts(self.format.render(&values)?);
+        self.text.set_format(&self.format, &values)?;
 
         Ok(Some(self.update_interval.into()))
     }
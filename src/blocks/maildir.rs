@@ -8,6 +8,8 @@ use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::SharedConfig;
 use crate::de::deserialize_duration;
 use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::registry;
 use crate::scheduler::Task;
 use crate::widgets::text::TextWidget;
 use crate::widgets::{I3BarWidget, State};
@@ -110,6 +112,8 @@ impl Block for Maildir {
         }
         self.text.set_state(state);
         self.text.set_text(format!("{}", newmails));
+        // This block has no format, export the count for other blocks anyway
+        registry::record(&map!("count" => Value::from_integer(newmails as i64)));
         Ok(Some(self.update_interval.into()))
     }
 
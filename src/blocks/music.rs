@@ -1,7 +1,6 @@
 use std::boxed::Box;
 use std::result;
 use std::sync::{Arc, Mutex};
-use std::thread;
 use std::time::{Duration, Instant};
 
 use crossbeam_channel::Sender;
@@ -17,9 +16,12 @@ use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::{LogicalDirection, Scrolling, SharedConfig};
+use crate::dbus_connections::{self, Event};
 use crate::de::deserialize_duration;
+use crate::deadline;
 use crate::errors::*;
 use crate::formatting::value::Value;
+use crate::formatting::width;
 use crate::formatting::FormatTemplate;
 use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
@@ -42,7 +44,8 @@ struct Player {
 
 impl Player {
     pub fn new(dbus_conn: &Connection, name: &str, bus_name: &str) -> Self {
-        let path = dbus_conn.with_path(name, "/org/mpris/MediaPlayer2", 500);
+        let path =
+            dbus_conn.with_path(name, "/org/mpris/MediaPlayer2", deadline::limit_millis(500));
         let data = path
             .get("org.mpris.MediaPlayer2.Player", "Metadata")
             .map(|d: Box<dyn RefArg>| extract_from_metadata(d.as_ref()));
@@ -95,7 +98,6 @@ pub struct Music {
     on_collapsed_click_widget: TextWidget,
     on_collapsed_click: Option<String>,
     on_click: Option<String>,
-    dbus_conn: Connection,
     marquee: bool,
     marquee_interval: Duration,
     smart_trim: bool,
@@ -114,13 +116,14 @@ impl Music {
         // Below code is by https://github.com/jgbyrne
         let mut artist: String = artist;
         let mut title: String = title;
-        let textlen =
-            title.chars().count() + self.separator.chars().count() + artist.chars().count();
+        let textlen = width::str_width(&title)
+            + width::str_width(&self.separator)
+            + width::str_width(&artist);
 
         if title.is_empty() {
-            artist.truncate(self.max_width);
+            artist.truncate(width::truncate(&artist, self.max_width).len());
         } else if artist.is_empty() {
-            title.truncate(self.max_width);
+            title.truncate(width::truncate(&title, self.max_width).len());
         } else {
             // overshoot: # of chars we need to trim
             // substance: # of chars available for trimming
@@ -267,159 +270,66 @@ impl ConfigBlock for Music {
         let next_id = pseudo_uuid();
         let collapsed_id = pseudo_uuid();
 
-        let dbus_conn = Connection::get_private(BusType::Session)
-            .block_error("music", "failed to establish D-Bus connection")?;
+        let dbus_conn = dbus_connections::session()?;
 
         let interface_name_exclude_regexps =
             compile_regexps(block_config.clone().interface_name_exclude)
                 .block_error("music", "failed to parse exclude patterns")?;
 
-        // ListNames returns one argument, which is an array of strings.
-        let list_names = dbus_conn
-            .send_with_reply_and_block(
-                Message::new_method_call(
-                    "org.freedesktop.DBus",
-                    "/",
-                    "org.freedesktop.DBus",
-                    "ListNames",
-                )
-                .unwrap(),
-                500,
-            )
-            .unwrap();
-        let names = list_names.get1::<Array<&str, _>>().unwrap().filter(|name| {
-            // If an interface matches an exclude pattern, ignore it
-            !ignored_player(
-                name,
-                &interface_name_exclude_regexps,
-                block_config.player.clone(),
-            )
-        });
-
-        let mut players = Vec::<Player>::new();
-        for name in names {
-            // Get bus connection name
-            let get_name_owner = dbus_conn
-                .send_with_reply_and_block(
-                    Message::new_method_call(
-                        "org.freedesktop.DBus",
-                        "/",
-                        "org.freedesktop.DBus",
-                        "GetNameOwner",
-                    )
-                    .unwrap()
-                    .append1(name),
-                    500,
-                )
-                .unwrap();
-            let bus_name: &str = get_name_owner.read1().unwrap();
-
-            // Skip if already added
-            if players.iter().any(|p| p.bus_name == bus_name) {
-                continue;
-            }
-
-            // Add player
-            players.push(Player::new(&dbus_conn, name, bus_name));
-        }
+        let players = list_players(
+            &dbus_conn,
+            &interface_name_exclude_regexps,
+            block_config.player.clone(),
+        )?;
 
         let players = Arc::new(Mutex::new(players));
         let players_clone = players.clone();
         let send_clone = send.clone();
         let preferred_player = block_config.player.clone();
 
-        thread::Builder::new()
-            .name("music".into())
-            .spawn(move || {
-                let dbus_conn = Connection::get_private(BusType::Session).unwrap();
-
-                // Listen to changes of players
-                dbus_conn.add_match("interface='org.freedesktop.DBus.Properties',member='PropertiesChanged',path='/org/mpris/MediaPlayer2'").unwrap();
-                // Add/remove players
-                dbus_conn.add_match("interface='org.freedesktop.DBus',member='NameOwnerChanged',path='/org/freedesktop/DBus',arg0namespace='org.mpris.MediaPlayer2'").unwrap();
-
-                // Skip the NameAcquired event.
-                dbus_conn.incoming(10_000).next();
-
-                loop {
-                    for ref signal in dbus_conn.incoming(60_000) {
-                        let mut players = players_clone
-                            .lock()
-                            .expect("failed to acquire lock for `players`");
-                        let mut updated = false;
-
-                        // Some property changed
-                        if let Some(prop_changed) = PropertiesPropertiesChanged::from_message(signal) {
-                            if let Some(sender) = signal.sender() {
-                                let sender = sender.to_string();
-                                if let Some(player) = players.iter_mut().find(|p| p.bus_name == sender) {
-                                    if let Some(data) = prop_changed.changed_properties.get("Metadata") {
-                                        let (title, artist) = extract_from_metadata(&data.0).unwrap_or((None,None));
-                                        if player.title != title || player.artist != artist {
-                                            player.title = title;
-                                            player.artist = artist;
-                                            updated = true;
-                                        }
-                                    }
-                                    if let Some(data) = prop_changed.changed_properties.get("PlaybackStatus") {
-                                        let new_playback = extract_playback_status(&data.0);
-                                        if player.playback_status != new_playback {
-                                            player.playback_status = new_playback;
-                                            updated = true;
-                                        }
-                                    }
-                                    // workaround for `playerctld`
-                                    // This block keeps track of players currently active on the MPRIS bus,
-                                    // and only clears the metadata when a player has disappeared from the bus.
-                                    // However `playerctl` is essentially doing the same thing as this block by
-                                    // keeping track of players by itself, and when the last player is closed
-                                    // the playerctld bus still remains which means the block never clears the
-                                    // metadata for the last player that disappeared. We can get around this by
-                                    // listening to the PlayerNames signal sent by playerctld and then only clear
-                                    // the metadata when there are no more players left.
-                                    if let Some(data) = prop_changed.changed_properties.get("PlayerNames") {
-                                        if data.0.as_iter().unwrap().peekable().peek().is_none() {
-                                            player.artist = None;
-                                            player.title = None;
-                                            updated = true;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        // Add/remove player
-                        else if signal.member().as_deref() == Some("NameOwnerChanged") {
-                            if let Ok((name, old_owner, new_owner)) = signal.read3::<&str, &str, &str>() {
-                                match (old_owner, new_owner) {
-                                    ("", new_owner) => { // Add a new player
-                                        // Skip if already presented (or ignored)
-                                        if !players.iter().any(|p| p.bus_name == new_owner) && !ignored_player(name, &interface_name_exclude_regexps, preferred_player.clone()) {
-                                            players.push(Player::new(&dbus_conn,name,new_owner));
-                                            updated = true;
-                                        }
-                                    }
-                                    (old_owner, "") => { // Remove an old player
-                                        if let Some(pos) = players.iter().position(|p| p.bus_name == old_owner) {
-                                            players.remove(pos);
-                                            updated = true;
-                                        }
-                                    }
-                                    _ => ()
-                                }
-                            }
-                        }
-
-                        // Request to update the block
-                        if updated {
-                            send_clone.send(Task {
-                                id,
-                                update_time: Instant::now(),
-                            }).unwrap();
-                        }
+        let rules = vec![
+            // Listen to changes of players
+            "interface='org.freedesktop.DBus.Properties',member='PropertiesChanged',path='/org/mpris/MediaPlayer2'".to_string(),
+            // Add/remove players
+            "interface='org.freedesktop.DBus',member='NameOwnerChanged',path='/org/freedesktop/DBus',arg0namespace='org.mpris.MediaPlayer2'".to_string(),
+        ];
+        dbus_connections::watch("music", BusType::Session, rules, move |event| {
+            let mut players = players_clone
+                .lock()
+                .expect("failed to acquire lock for `players`");
+            let updated = match event {
+                // The players were restarted together with the bus, or they changed in between
+                Event::Connected => match dbus_connections::session().and_then(|dbus_conn| {
+                    list_players(
+                        &dbus_conn,
+                        &interface_name_exclude_regexps,
+                        preferred_player.clone(),
+                    )
+                }) {
+                    Ok(listed) => {
+                        *players = listed;
+                        true
                     }
-                }
-            })
-            .unwrap();
+                    Err(_) => false,
+                },
+                Event::Signal(signal) => apply_signal(
+                    &mut players,
+                    signal,
+                    &interface_name_exclude_regexps,
+                    &preferred_player,
+                ),
+            };
+
+            // Request to update the block
+            if updated {
+                send_clone
+                    .send(Task {
+                        id,
+                        update_time: Instant::now(),
+                    })
+                    .unwrap();
+            }
+        })?;
 
         let mut play: Option<TextWidget> = None;
         let mut prev: Option<TextWidget> = None;
@@ -490,8 +400,6 @@ impl ConfigBlock for Music {
                 .with_state(State::Info)
                 .with_spacing(Spacing::Hidden),
             on_collapsed_click: block_config.on_collapsed_click,
-            dbus_conn: Connection::get_private(BusType::Session)
-                .block_error("music", "failed to establish D-Bus connection")?,
             marquee: block_config.marquee,
             marquee_interval: block_config.marquee_interval,
             smart_trim: block_config.smart_trim,
@@ -540,15 +448,16 @@ impl Block for Music {
         let player_name = split[3].to_string();
         let artist = metadata.clone().artist.unwrap_or_else(|| String::from(""));
         let title = metadata.clone().title.unwrap_or_else(|| String::from(""));
-        let combo =
-            if (title.chars().count() + self.separator.chars().count() + artist.chars().count())
-                < self.max_width
-                || !self.smart_trim
-            {
-                format!("{}{}{}", title, self.separator, artist)
-            } else {
-                self.smart_trim(artist.clone(), title.clone())
-            };
+        let combo = if (width::str_width(&title)
+            + width::str_width(&self.separator)
+            + width::str_width(&artist))
+            < self.max_width
+            || !self.smart_trim
+        {
+            format!("{}{}{}", title, self.separator, artist)
+        } else {
+            self.smart_trim(artist.clone(), title.clone())
+        };
 
         let values = map!(
             "artist" => Value::from_string(artist.clone()),
@@ -604,6 +513,17 @@ impl Block for Music {
         }
     }
 
+    fn segment(&self, instance: usize) -> Option<&'static str> {
+        match instance {
+            id if id == self.play_id => Some("play"),
+            id if id == self.next_id => Some("next"),
+            id if id == self.prev_id => Some("prev"),
+            id if id == self.id => Some("title"),
+            id if id == self.collapsed_id => Some("collapsed"),
+            _ => None,
+        }
+    }
+
     fn click(&mut self, event: &I3BarEvent) -> Result<()> {
         if let Some(event_id) = event.instance {
             let action = match event_id {
@@ -631,7 +551,7 @@ impl Block for Music {
                             action,
                         )
                         .block_error("music", "failed to create D-Bus method call")?;
-                        self.dbus_conn
+                        dbus_connections::session()?
                             .send(m)
                             .block_error("music", "failed to call method via D-Bus")?;
                     } else if event_id == self.collapsed_id && self.on_collapsed_click.is_some() {
@@ -674,15 +594,16 @@ impl Block for Music {
                         .block_error("music", "failed to create D-Bus method call")?;
 
                         use LogicalDirection::*;
+                        let seek_step = event.scale_step(self.seek_step as f64) as i64;
                         match self.scrolling.to_logical_direction(event.button) {
                             Some(Up) => {
-                                self.dbus_conn
-                                    .send(m.append1(self.seek_step * 1000))
+                                dbus_connections::session()?
+                                    .send(m.append1(seek_step * 1000))
                                     .block_error("music", "failed to call method via D-Bus")?;
                             }
                             Some(Down) => {
-                                self.dbus_conn
-                                    .send(m.append1(self.seek_step * -1000))
+                                dbus_connections::session()?
+                                    .send(m.append1(seek_step * -1000))
                                     .block_error("music", "failed to call method via D-Bus")?;
                             }
                             None => {}
@@ -778,6 +699,152 @@ fn extract_from_metadata(metadata: &dyn RefArg) -> Result<(Option<String>, Optio
     Ok((title, artist))
 }
 
+/// Applies a signal about a change of a player, or about a player that was added or removed.
+/// Returns whether anything changed.
+fn apply_signal(
+    players: &mut Vec<Player>,
+    signal: &Message,
+    interface_name_exclude_regexps: &[Regex],
+    preferred_player: &Option<String>,
+) -> bool {
+    let mut updated = false;
+
+    // Some property changed
+    if let Some(prop_changed) = PropertiesPropertiesChanged::from_message(signal) {
+        if let Some(sender) = signal.sender() {
+            let sender = sender.to_string();
+            if let Some(player) = players.iter_mut().find(|p| p.bus_name == sender) {
+                if let Some(data) = prop_changed.changed_properties.get("Metadata") {
+                    let (title, artist) = extract_from_metadata(&data.0).unwrap_or((None, None));
+                    if player.title != title || player.artist != artist {
+                        player.title = title;
+                        player.artist = artist;
+                        updated = true;
+                    }
+                }
+                if let Some(data) = prop_changed.changed_properties.get("PlaybackStatus") {
+                    let new_playback = extract_playback_status(&data.0);
+                    if player.playback_status != new_playback {
+                        player.playback_status = new_playback;
+                        updated = true;
+                    }
+                }
+                // workaround for `playerctld`
+                // This block keeps track of players currently active on the MPRIS bus,
+                // and only clears the metadata when a player has disappeared from the bus.
+                // However `playerctl` is essentially doing the same thing as this block by
+                // keeping track of players by itself, and when the last player is closed
+                // the playerctld bus still remains which means the block never clears the
+                // metadata for the last player that disappeared. We can get around this by
+                // listening to the PlayerNames signal sent by playerctld and then only clear
+                // the metadata when there are no more players left.
+                if let Some(data) = prop_changed.changed_properties.get("PlayerNames") {
+                    if data.0.as_iter().unwrap().peekable().peek().is_none() {
+                        player.artist = None;
+                        player.title = None;
+                        updated = true;
+                    }
+                }
+            }
+        }
+    }
+    // Add/remove player
+    else if signal.member().as_deref() == Some("NameOwnerChanged") {
+        if let Ok((name, old_owner, new_owner)) = signal.read3::<&str, &str, &str>() {
+            match (old_owner, new_owner) {
+                ("", new_owner) => {
+                    // Add a new player
+                    // Skip if already presented (or ignored)
+                    if !players.iter().any(|p| p.bus_name == new_owner)
+                        && !ignored_player(
+                            name,
+                            interface_name_exclude_regexps,
+                            preferred_player.clone(),
+                        )
+                    {
+                        if let Ok(dbus_conn) = dbus_connections::session() {
+                            players.push(Player::new(&dbus_conn, name, new_owner));
+                            updated = true;
+                        }
+                    }
+                }
+                (old_owner, "") => {
+                    // Remove an old player
+                    if let Some(pos) = players.iter().position(|p| p.bus_name == old_owner) {
+                        players.remove(pos);
+                        updated = true;
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    updated
+}
+
+/// The players on the bus, without the ignored ones
+fn list_players(
+    dbus_conn: &Connection,
+    interface_name_exclude_regexps: &[Regex],
+    preferred_player: Option<String>,
+) -> Result<Vec<Player>> {
+    // ListNames returns one argument, which is an array of strings.
+    let list_names = dbus_conn
+        .send_with_reply_and_block(
+            Message::new_method_call(
+                "org.freedesktop.DBus",
+                "/",
+                "org.freedesktop.DBus",
+                "ListNames",
+            )
+            .unwrap(),
+            500,
+        )
+        .block_error("music", "failed to list the names on the bus")?;
+    let names = list_names
+        .get1::<Array<&str, _>>()
+        .block_error("music", "failed to list the names on the bus")?
+        .filter(|name| {
+            // If an interface matches an exclude pattern, ignore it
+            !ignored_player(
+                name,
+                interface_name_exclude_regexps,
+                preferred_player.clone(),
+            )
+        });
+
+    let mut players = Vec::<Player>::new();
+    for name in names {
+        // Get bus connection name
+        let get_name_owner = dbus_conn.send_with_reply_and_block(
+            Message::new_method_call(
+                "org.freedesktop.DBus",
+                "/",
+                "org.freedesktop.DBus",
+                "GetNameOwner",
+            )
+            .unwrap()
+            .append1(name),
+            500,
+        );
+        // The player may have quit in the meantime
+        let bus_name: &str = match get_name_owner.as_ref().map(|reply| reply.read1()) {
+            Ok(Ok(bus_name)) => bus_name,
+            _ => continue,
+        };
+
+        // Skip if already added
+        if players.iter().any(|p| p.bus_name == bus_name) {
+            continue;
+        }
+
+        // Add player
+        players.push(Player::new(dbus_conn, name, bus_name));
+    }
+    Ok(players)
+}
+
 fn ignored_player(
     name: &str,
     interface_name_exclude_regexps: &[Regex],
@@ -2,7 +2,6 @@ use std::fmt;
 use std::fs::{read_to_string, OpenOptions};
 use std::io::prelude::*;
 use std::path::{Path, PathBuf};
-use std::process::Command;
 use std::time::{Duration, Instant};
 
 use crossbeam_channel::Sender;
@@ -16,8 +15,10 @@ use crate::de::deserialize_duration;
 use crate::errors::*;
 use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
+use crate::netlink::{self, Source};
 use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
+use crate::subprocess::command;
 use crate::util::{escape_pango_text, format_vec_to_bar_graph};
 use crate::widgets::{text::TextWidget, I3BarWidget, Spacing};
 
@@ -82,7 +83,7 @@ impl NetworkDevice {
     /// and will change when the status of devices change.
     pub fn default_device() -> Option<String> {
         String::from_utf8(
-            Command::new("ip")
+            command("ip")
                 .args(&["route", "show", "default"])
                 .output()
                 .ok()
@@ -194,7 +195,7 @@ impl NetworkDevice {
         if !self.is_up()? {
             return Ok(None);
         }
-        let output = Command::new("ip")
+        let output = command("ip")
             .args(&["-json", "-family", "inet", "address", "show", &self.device])
             .output()
             .block_error("net", "Failed to execute IP address query.")
@@ -229,7 +230,7 @@ impl NetworkDevice {
         if !self.is_up()? {
             return Ok(None);
         }
-        let output = Command::new("ip")
+        let output = command("ip")
             .args(&["-json", "-family", "inet6", "address", "show", &self.device])
             .output()
             .block_error("net", "Failed to execute IP address query.")
@@ -267,7 +268,7 @@ impl NetworkDevice {
             return Ok(None);
         }
         if self.wireless {
-            let bitrate_output = Command::new("iw")
+            let bitrate_output = command("iw")
                 .args(&["dev", &self.device, "link"])
                 .output()
                 .block_error("net", "Failed to execute bitrate query with iw.")?
@@ -285,7 +286,7 @@ impl NetworkDevice {
                 Ok(None)
             }
         } else {
-            let output = Command::new("ethtool")
+            let output = command("ethtool")
                 .arg(&self.device)
                 .output()
                 .block_error("net", "Failed to execute bitrate query with ethtool")?
@@ -328,6 +329,13 @@ pub struct Net {
     hide_inactive: bool,
     hide_missing: bool,
     last_update: Instant,
+    /// When the byte counters were read
+    last_counters: Instant,
+    /// Whether changes of the network come from rtnetlink, so the default device is only looked up
+    /// again after a change
+    events: bool,
+    /// The generation of the network events of the last update
+    generation: u64,
     shared_config: SharedConfig,
 }
 
@@ -393,8 +401,10 @@ impl ConfigBlock for Net {
         id: usize,
         block_config: Self::Config,
         shared_config: SharedConfig,
-        _tx_update_request: Sender<Task>,
+        tx_update_request: Sender<Task>,
     ) -> Result<Self> {
+        let events = netlink::subscribe(Source::Network, id, tx_update_request);
+        let generation = netlink::generation(Source::Network);
         let default_device = match NetworkDevice::default_device() {
             Some(ref s) if !s.is_empty() => s.to_string(),
             _ => "lo".to_string(),
@@ -462,6 +472,9 @@ impl ConfigBlock for Net {
             hide_inactive: block_config.hide_inactive,
             hide_missing: block_config.hide_missing,
             last_update: Instant::now() - Duration::from_secs(30),
+            last_counters: Instant::now(),
+            events,
+            generation,
             shared_config,
             format,
             format_alt,
@@ -510,9 +523,14 @@ impl Net {
     }
 
     fn update_tx_rx(&mut self) -> Result<()> {
-        // TODO: consider using `as_nanos`
-        let update_interval = (self.update_interval.as_secs() as f64)
-            + (self.update_interval.subsec_nanos() as f64 / 1_000_000_000.0);
+        // Updates because of network events keep the speeds of the last interval, as the counters
+        // changed too little since then
+        let elapsed = self.last_counters.elapsed();
+        if elapsed < self.update_interval / 2 {
+            return Ok(());
+        }
+        self.last_counters = Instant::now();
+        let update_interval = elapsed.as_secs_f64();
 
         // Update the throughput/graph widgets if they are enabled
         let current_tx = self.device.tx_bytes()?;
@@ -543,8 +561,12 @@ impl Net {
 
 impl Block for Net {
     fn update(&mut self) -> Result<Option<Update>> {
+        let generation = netlink::generation(Source::Network);
+        let changed = generation != self.generation;
+        self.generation = generation;
+
         // Update device
-        if self.auto_device {
+        if self.auto_device && (changed || !self.events) {
             let dev = match NetworkDevice::default_device() {
                 Some(ref s) if !s.is_empty() => s.to_string(),
                 _ => "lo".to_string(),
@@ -591,6 +613,7 @@ impl Block for Net {
         };
 
         if (now.duration_since(self.last_update).as_secs() > 30)
+            || changed
             || waiting_for_ip
             || waiting_for_ipv6
         {
@@ -618,7 +641,7 @@ impl Block for Net {
             "graph_down" => Value::from_string(self.graph_rx.clone()),
         );
 
-        self.output.set_texts(self.format.render(&values)?);
+        self.output.set_format(&self.format, &values)?;
 
         Ok(Some(self.update_interval.into()))
     }
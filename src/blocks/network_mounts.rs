@@ -0,0 +1,328 @@
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::{Receiver, Sender, TryRecvError};
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::scheduler::Task;
+use crate::util::read_file;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+/// The file systems that are checked if no mounts are configured
+const NETWORK_FILESYSTEMS: &[&str] = &[
+    "9p",
+    "ceph",
+    "cifs",
+    "davfs",
+    "fuse.glusterfs",
+    "fuse.rclone",
+    "fuse.sshfs",
+    "nfs",
+    "nfs4",
+    "smb3",
+    "smbfs",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Status {
+    /// Not checked yet
+    Unknown,
+    Ok,
+    /// The mount point isn't mounted
+    Missing,
+    /// The check failed, e.g. with a stale file handle
+    Unreachable(String),
+    /// The check didn't return within the timeout
+    Hung,
+}
+
+/// A check that may be blocked in the kernel for a long time, so it runs in its own thread
+struct Probe {
+    started: Instant,
+    result: Receiver<std::result::Result<(), String>>,
+}
+
+struct Mount {
+    path: String,
+    status: Status,
+    probe: Option<Probe>,
+    /// When the mount is checked next
+    next_check: Instant,
+}
+
+pub struct NetworkMounts {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    update_interval: Duration,
+    timeout: Duration,
+    /// The configured mount points, or `None` for all network file systems
+    configured: Option<Vec<String>>,
+    mounts: Vec<Mount>,
+    tx_update_request: Sender<Task>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct NetworkMountsConfig {
+    /// Mount points to check, all mounted network file systems by default
+    pub mounts: Option<Vec<String>>,
+
+    /// Time after which a mount that doesn't answer is reported as hung
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub timeout: Duration,
+
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    pub format: FormatTemplate,
+}
+
+impl Default for NetworkMountsConfig {
+    fn default() -> Self {
+        Self {
+            mounts: None,
+            timeout: Duration::from_secs(2),
+            interval: Duration::from_secs(30),
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+impl ConfigBlock for NetworkMounts {
+    type Config = NetworkMountsConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let configured = block_config.mounts.map(|mounts| {
+            mounts
+                .iter()
+                .map(|mount| shellexpand::tilde(mount.trim_end_matches('/')).to_string())
+                .collect()
+        });
+        Ok(NetworkMounts {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("disk_drive")?,
+            format: block_config.format.with_default("{ok}/{total}")?,
+            update_interval: block_config.interval,
+            timeout: block_config.timeout,
+            configured,
+            mounts: Vec::new(),
+            tx_update_request,
+        })
+    }
+}
+
+/// Undoes the octal escapes of the spaces and other characters in `/proc/mounts`
+fn unescape(field: &str) -> String {
+    let mut unescaped = String::with_capacity(field.len());
+    let mut rest = field;
+    while let Some(start) = rest.find('\\') {
+        unescaped.push_str(&rest[..start]);
+        let code = rest
+            .get(start + 1..start + 4)
+            .and_then(|code| u8::from_str_radix(code, 8).ok());
+        match code {
+            Some(code) => {
+                unescaped.push(code as char);
+                rest = &rest[start + 4..];
+            }
+            None => {
+                unescaped.push('\\');
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+    unescaped
+}
+
+/// The mount points and file system types of `/proc/mounts`
+fn parse_mounts(contents: &str) -> Vec<(String, String)> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace().skip(1);
+            Some((unescape(fields.next()?), fields.next()?.to_string()))
+        })
+        .collect()
+}
+
+impl NetworkMounts {
+    /// Adds the mounts that appeared and removes the ones that are gone, if no mounts are
+    /// configured
+    fn sync_mounts(&mut self, mounted: &[(String, String)]) {
+        let paths: Vec<String> = match &self.configured {
+            Some(paths) => paths.clone(),
+            None => mounted
+                .iter()
+                .filter(|(_, fs_type)| NETWORK_FILESYSTEMS.contains(&fs_type.as_str()))
+                .map(|(path, _)| path.clone())
+                .collect(),
+        };
+        self.mounts.retain(|mount| paths.contains(&mount.path));
+        for path in paths {
+            if !self.mounts.iter().any(|mount| mount.path == path) {
+                self.mounts.push(Mount {
+                    path,
+                    status: Status::Unknown,
+                    probe: None,
+                    next_check: Instant::now(),
+                });
+            }
+        }
+    }
+}
+
+/// Checks the mount at `path` in a thread, which requests an update of the block `id` when it's
+/// done
+fn probe(id: usize, tx_update_request: &Sender<Task>, path: &str) -> Result<Probe> {
+    let (tx, rx) = crossbeam_channel::bounded(1);
+    let path = path.to_string();
+    let tx_update_request = tx_update_request.clone();
+    thread::Builder::new()
+        .name("network_mounts".into())
+        .spawn(move || {
+            let result = nix::sys::statvfs::statvfs(Path::new(&path))
+                .map(|_| ())
+                .map_err(|e| e.desc().to_string());
+            // The block may have been reloaded while the mount was hung
+            if tx.send(result).is_ok() {
+                let _ = tx_update_request.send(Task {
+                    id,
+                    update_time: Instant::now(),
+                });
+            }
+        })
+        .block_error("network_mounts", "failed to start a check")?;
+    Ok(Probe {
+        started: Instant::now(),
+        result: rx,
+    })
+}
+
+impl Block for NetworkMounts {
+    fn update(&mut self) -> Result<Option<Update>> {
+        // Reading the mount table doesn't touch the mounts, so it doesn't hang
+        let mounted = parse_mounts(&read_file("network_mounts", Path::new("/proc/mounts"))?);
+        self.sync_mounts(&mounted);
+
+        let now = Instant::now();
+        let mut next_update = now + self.update_interval;
+        for mount in &mut self.mounts {
+            if let Some(probe) = &mount.probe {
+                match probe.result.try_recv() {
+                    Ok(result) => {
+                        mount.status = match result {
+                            Ok(()) => Status::Ok,
+                            Err(e) => Status::Unreachable(e),
+                        };
+                        mount.probe = None;
+                    }
+                    Err(TryRecvError::Empty) if now >= probe.started + self.timeout => {
+                        mount.status = Status::Hung;
+                    }
+                    // Still waiting for the answer
+                    Err(TryRecvError::Empty) => {
+                        next_update = next_update.min(probe.started + self.timeout);
+                    }
+                    Err(TryRecvError::Disconnected) => mount.probe = None,
+                }
+            }
+            // A hung check blocks the next ones as well, so there is at most one per mount. It
+            // requests an update when it's done.
+            if mount.probe.is_some() {
+                continue;
+            }
+            if now < mount.next_check {
+                next_update = next_update.min(mount.next_check);
+                continue;
+            }
+            mount.next_check = now + self.update_interval;
+            if !mounted.iter().any(|(path, _)| *path == mount.path) {
+                mount.status = Status::Missing;
+                continue;
+            }
+            let probe = probe(self.id, &self.tx_update_request, &mount.path)?;
+            next_update = next_update.min(probe.started + self.timeout);
+            mount.probe = Some(probe);
+        }
+
+        let failed: Vec<String> = self
+            .mounts
+            .iter()
+            .filter_map(|mount| match &mount.status {
+                Status::Unknown | Status::Ok => None,
+                Status::Missing => Some(format!("{} (not mounted)", mount.path)),
+                Status::Unreachable(e) => Some(format!("{} ({})", mount.path, e)),
+                Status::Hung => Some(format!("{} (hung)", mount.path)),
+            })
+            .collect();
+        let ok = self
+            .mounts
+            .iter()
+            .filter(|mount| mount.status == Status::Ok)
+            .count();
+        let values = map!(
+            "total" => Value::from_integer(self.mounts.len() as i64),
+            "ok" => Value::from_integer(ok as i64),
+            "failed" => Value::from_integer(failed.len() as i64),
+            "failed_mounts" => Value::from_string(failed.join(", ")),
+        );
+        self.text.set_state(if failed.is_empty() {
+            State::Idle
+        } else {
+            State::Critical
+        });
+        self.text.set_format(&self.format, &values)?;
+
+        Ok(Some(
+            next_update
+                .saturating_duration_since(now)
+                .max(Duration::from_millis(100))
+                .into(),
+        ))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mount_table() {
+        let contents = "proc /proc proc rw,nosuid 0 0
+nas:/export /mnt/nas nfs4 rw,relatime,vers=4.2 0 0
+//server/share /mnt/my\\040share cifs rw 0 0
+";
+        assert_eq!(
+            parse_mounts(contents),
+            [
+                ("/proc".to_string(), "proc".to_string()),
+                ("/mnt/nas".to_string(), "nfs4".to_string()),
+                ("/mnt/my share".to_string(), "cifs".to_string()),
+            ]
+        );
+        assert_eq!(unescape("a\\134b\\"), "a\\b\\");
+    }
+}
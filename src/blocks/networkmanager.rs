@@ -1,14 +1,13 @@
 use std::fmt;
 use std::net::Ipv4Addr;
 use std::result;
-use std::thread;
 use std::time::Instant;
 
 use crossbeam_channel::Sender;
 use dbus::arg::{Array, Iter, Variant};
 use dbus::{
     arg::messageitem::MessageItem,
-    ffidisp::{BusType, Connection, ConnectionItem},
+    ffidisp::{BusType, Connection},
     Message, Path,
 };
 use regex::Regex;
@@ -16,6 +15,8 @@ use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::SharedConfig;
+use crate::dbus_connections;
+use crate::deadline;
 use crate::errors::*;
 use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
@@ -182,7 +183,7 @@ impl ConnectionManager {
             MessageItem::Str(property.to_string()),
         );
 
-        let r = c.send_with_reply_and_block(m, 1000);
+        let r = c.send_with_reply_and_block(m, deadline::limit_millis(1000));
 
         r.block_error("networkmanager", "Failed to retrieve property")
     }
@@ -460,7 +461,6 @@ pub struct NetworkManager {
     id: usize,
     indicator: TextWidget,
     output: Vec<TextWidget>,
-    dbus_conn: Connection,
     manager: ConnectionManager,
     primary_only: bool,
     ap_format: FormatTemplate,
@@ -516,47 +516,33 @@ impl ConfigBlock for NetworkManager {
         shared_config: SharedConfig,
         send: Sender<Task>,
     ) -> Result<Self> {
-        let dbus_conn = Connection::get_private(BusType::System)
-            .block_error("networkmanager", "failed to establish D-Bus connection")?;
         let manager = ConnectionManager::new();
 
-        thread::Builder::new()
-            .name("networkmanager".into())
-            .spawn(move || {
-                let c = Connection::get_private(BusType::System).unwrap();
-
-                c.add_match(
-                    "type='signal',\
-                    path='/org/freedesktop/NetworkManager',\
-                    interface='org.freedesktop.DBus.Properties',\
-                    member='PropertiesChanged'",
-                )
-                .unwrap();
-                c.add_match(
-                    "type='signal',\
-                    path_namespace='/org/freedesktop/NetworkManager/ActiveConnection',\
-                    interface='org.freedesktop.DBus.Properties',\
-                    member='PropertiesChanged'",
-                )
-                .unwrap();
-
-                loop {
-                    let timeout = 300_000;
-
-                    for event in c.iter(timeout) {
-                        match event {
-                            ConnectionItem::Nothing => (),
-                            _ => send
-                                .send(Task {
-                                    id,
-                                    update_time: Instant::now(),
-                                })
-                                .unwrap(),
-                        }
-                    }
-                }
-            })
-            .unwrap();
+        dbus_connections::watch(
+            "networkmanager",
+            BusType::System,
+            vec![
+                "type='signal',\
+                path='/org/freedesktop/NetworkManager',\
+                interface='org.freedesktop.DBus.Properties',\
+                member='PropertiesChanged'"
+                    .to_string(),
+                "type='signal',\
+                path_namespace='/org/freedesktop/NetworkManager/ActiveConnection',\
+                interface='org.freedesktop.DBus.Properties',\
+                member='PropertiesChanged'"
+                    .to_string(),
+            ],
+            // The connections may have changed while the bus was away, so reconnecting updates
+            // the block as well
+            move |_| {
+                send.send(Task {
+                    id,
+                    update_time: Instant::now(),
+                })
+                .unwrap()
+            },
+        )?;
 
         fn compile_regexps(patterns: Vec<String>) -> result::Result<Vec<Regex>, regex::Error> {
             patterns.iter().map(|p| Regex::new(p)).collect()
@@ -566,7 +552,6 @@ impl ConfigBlock for NetworkManager {
             id,
             indicator: TextWidget::new(id, 0, shared_config.clone()),
             output: Vec::new(),
-            dbus_conn,
             manager,
             primary_only: block_config.primary_only,
             ap_format: block_config.ap_format.with_default("{ssid}")?,
@@ -589,7 +574,8 @@ impl Block for NetworkManager {
     }
 
     fn update(&mut self) -> Result<Option<Update>> {
-        let state = self.manager.state(&self.dbus_conn);
+        let c = dbus_connections::system()?;
+        let state = self.manager.state(&c);
 
         self.indicator.set_state(match state {
             Ok(NetworkState::ConnectedGlobal) => State::Good,
@@ -620,7 +606,7 @@ impl Block for NetworkManager {
                 };
 
                 let connections = if self.primary_only {
-                    match self.manager.primary_connection(&self.dbus_conn) {
+                    match self.manager.primary_connection(&c) {
                         Ok(conn) => vec![conn],
                         Err(_) => vec![],
                     }
@@ -628,9 +614,9 @@ impl Block for NetworkManager {
                     // We sort things so that the primary connection comes first
                     let active = self
                         .manager
-                        .active_connections(&self.dbus_conn)
+                        .active_connections(&c)
                         .unwrap_or_else(|_| Vec::new());
-                    match self.manager.primary_connection(&self.dbus_conn) {
+                    match self.manager.primary_connection(&c) {
                         Ok(conn) => vec![conn.clone()]
                             .into_iter()
                             .chain(active.into_iter().filter(|x| x.path != conn.path))
@@ -643,7 +629,7 @@ impl Block for NetworkManager {
                     .into_iter()
                     .filter_map(|conn| {
                         // Hide vpn connection(s) since its devices are the devices of its parent connection
-                        if let Ok(true) = conn.vpn(&self.dbus_conn) {
+                        if let Ok(true) = conn.vpn(&c) {
                             return None;
                         };
 
@@ -652,7 +638,7 @@ impl Block for NetworkManager {
                             .with_spacing(Spacing::Inline);
 
                         // Set the state for this connection
-                        widget.set_state(if let Ok(conn_state) = conn.state(&self.dbus_conn) {
+                        widget.set_state(if let Ok(conn_state) = conn.state(&c) {
                             conn_state.to_state(good_state)
                         } else {
                             ActiveConnectionState::Unknown.to_state(good_state)
@@ -660,9 +646,9 @@ impl Block for NetworkManager {
 
                         // Get all devices for this connection
                         let mut devicevec: Vec<String> = Vec::new();
-                        if let Ok(devices) = conn.devices(&self.dbus_conn) {
+                        if let Ok(devices) = conn.devices(&c) {
                             'devices: for device in devices {
-                                let name = match device.interface_name(&self.dbus_conn) {
+                                let name = match device.interface_name(&c) {
                                     Ok(v) => v,
                                     Err(_) => "".to_string(),
                                 };
@@ -688,7 +674,7 @@ impl Block for NetworkManager {
                                 }
 
                                 let (icon, type_name) =
-                                    if let Ok(dev_type) = device.device_type(&self.dbus_conn) {
+                                    if let Ok(dev_type) = device.device_type(&c) {
                                         match dev_type.to_icon_name() {
                                             Some(icon_name) => {
                                                 let i = self
@@ -709,11 +695,11 @@ impl Block for NetworkManager {
                                         ("".to_string(), "".to_string())
                                     };
 
-                                let ap = if let Ok(ap) = device.active_access_point(&self.dbus_conn)
+                                let ap = if let Ok(ap) = device.active_access_point(&c)
                                 {
-                                    let ssid = ap.ssid(&self.dbus_conn).unwrap_or_else(|_| "".to_string());
-                                    let strength = ap.strength(&self.dbus_conn).unwrap_or(0);
-                                    let freq = match ap.frequency(&self.dbus_conn) {
+                                    let ssid = ap.ssid(&c).unwrap_or_else(|_| "".to_string());
+                                    let strength = ap.strength(&c).unwrap_or(0);
+                                    let freq = match ap.frequency(&c) {
                                         Ok(v) => v.to_string(),
                                         Err(_) => "0".to_string(),
                                     };
@@ -733,8 +719,8 @@ impl Block for NetworkManager {
                                 };
 
                                 let mut ips = "×".to_string();
-                                if let Ok(ip4config) = device.ip4config(&self.dbus_conn) {
-                                    if let Ok(addresses) = ip4config.addresses(&self.dbus_conn) {
+                                if let Ok(ip4config) = device.ip4config(&c) {
+                                    if let Ok(addresses) = ip4config.addresses(&c) {
                                         if !addresses.is_empty() {
                                             ips = addresses
                                                 .into_iter()
@@ -761,7 +747,7 @@ impl Block for NetworkManager {
                             }
                         };
 
-                        let id = match conn.id(&self.dbus_conn) {
+                        let id = match conn.id(&c) {
                             Ok(id) => id,
                             Err(v) => format!("{:?}", v),
                         };
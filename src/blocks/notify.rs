@@ -1,15 +1,15 @@
 use std::sync::{Arc, Mutex};
-use std::thread;
 use std::time::Instant;
 
 use crossbeam_channel::Sender;
 use dbus::ffidisp::stdintf::org_freedesktop_dbus::{Properties, PropertiesPropertiesChanged};
-use dbus::ffidisp::{BusType, Connection};
+use dbus::ffidisp::BusType;
 use dbus::message::SignalArgs;
 use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::SharedConfig;
+use crate::dbus_connections::{self, Event};
 use crate::errors::*;
 use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
@@ -44,20 +44,7 @@ impl ConfigBlock for Notify {
         shared_config: SharedConfig,
         send: Sender<Task>,
     ) -> Result<Self> {
-        let c = Connection::get_private(BusType::Session).block_error(
-            "notify",
-            &"Failed to establish D-Bus connection".to_string(),
-        )?;
-
-        let p = c.with_path(
-            "org.freedesktop.Notifications",
-            "/org/freedesktop/Notifications",
-            5000,
-        );
-        let initial_state: bool = p.get("org.dunstproject.cmd0", "paused").block_error(
-            "notify",
-            &"Failed to get dunst state. Is it running?".to_string(),
-        )?;
+        let initial_state = paused()?;
 
         let icon = if initial_state { "bell-slash" } else { "bell" };
 
@@ -66,36 +53,38 @@ impl ConfigBlock for Notify {
         let state = Arc::new(Mutex::new(initial_state as i64));
         let state_copy = state.clone();
 
-        thread::Builder::new()
-            .name("notify".into())
-            .spawn(move || {
-                let c = Connection::get_private(BusType::Session)
-                    .expect("Failed to establish D-Bus connection in thread");
-
-                let matched_signal = PropertiesPropertiesChanged::match_str(
-                    Some(&"org.freedesktop.Notifications".into()),
-                    None,
-                );
-                c.add_match(&matched_signal).unwrap();
-                loop {
-                    for msg in c.incoming(1000) {
-                        if let Some(signal) = PropertiesPropertiesChanged::from_message(&msg) {
-                            let value = signal.changed_properties.get("paused").unwrap();
-                            let status = &value.0.as_i64().unwrap();
-                            let mut paused = state_copy.lock().unwrap();
-                            *paused = *status;
-
-                            // Tell block to update now.
-                            send.send(Task {
-                                id,
-                                update_time: Instant::now(),
-                            })
-                            .unwrap();
-                        }
-                    }
-                }
-            })
-            .unwrap();
+        let matched_signal = PropertiesPropertiesChanged::match_str(
+            Some(&"org.freedesktop.Notifications".into()),
+            None,
+        );
+        dbus_connections::watch(
+            "notify",
+            BusType::Session,
+            vec![matched_signal],
+            move |event| {
+                let status = match event {
+                    // Dunst may have been restarted together with the bus
+                    Event::Connected => match paused() {
+                        Ok(paused) => paused as i64,
+                        Err(_) => return,
+                    },
+                    Event::Signal(msg) => match PropertiesPropertiesChanged::from_message(msg)
+                        .and_then(|signal| signal.changed_properties.get("paused")?.0.as_i64())
+                    {
+                        Some(status) => status,
+                        None => return,
+                    },
+                };
+                *state_copy.lock().unwrap() = status;
+
+                // Tell block to update now.
+                send.send(Task {
+                    id,
+                    update_time: Instant::now(),
+                })
+                .unwrap();
+            },
+        )?;
 
         Ok(Notify {
             id,
@@ -106,6 +95,20 @@ impl ConfigBlock for Notify {
     }
 }
 
+/// Whether dunst is paused
+fn paused() -> Result<bool> {
+    let c = dbus_connections::session()?;
+    let p = c.with_path(
+        "org.freedesktop.Notifications",
+        "/org/freedesktop/Notifications",
+        5000,
+    );
+    p.get("org.dunstproject.cmd0", "paused").block_error(
+        "notify",
+        &"Failed to get dunst state. Is it running?".to_string(),
+    )
+}
+
 impl Block for Notify {
     fn id(&self) -> usize {
         self.id
@@ -121,7 +124,7 @@ impl Block for Notify {
             "state" => Value::from_string(paused.to_string())
         );
 
-        self.output.set_texts(self.format.render(&values)?);
+        self.output.set_format(&self.format, &values)?;
 
         let icon = if paused == 1 { "bell-slash" } else { "bell" };
         self.output.set_icon(icon)?;
@@ -136,11 +139,7 @@ impl Block for Notify {
 
     fn click(&mut self, e: &I3BarEvent) -> Result<()> {
         if let MouseButton::Left = e.button {
-            let c = Connection::get_private(BusType::Session).block_error(
-                "notify",
-                &"Failed to establish D-Bus connection".to_string(),
-            )?;
-
+            let c = dbus_connections::session()?;
             let p = c.with_path(
                 "org.freedesktop.Notifications",
                 "/org/freedesktop/Notifications",
@@ -1,44 +1,70 @@
+use std::collections::HashMap;
 use std::env;
-use std::time::Duration;
+use std::ffi::OsStr;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use crossbeam_channel::Sender;
+use inotify::{EventMask, Inotify, WatchMask};
 use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::SharedConfig;
-use crate::de::deserialize_duration;
+use crate::de::deserialize_opt_duration;
 use crate::errors::*;
+use crate::formatting::value::Value;
 use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::registry;
 use crate::scheduler::Task;
+use crate::subprocess::spawn_child_async;
+use crate::util::read_inotify_events;
 use crate::widgets::text::TextWidget;
 use crate::widgets::{I3BarWidget, State};
 
+/// The interval without `interval` if the database can't be watched
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(10);
+
 pub struct Notmuch {
     id: usize,
-    text: TextWidget,
-    update_interval: Duration,
-    query: String,
+    /// One widget for each search
+    texts: Vec<TextWidget>,
+    /// `None` while the database is watched
+    update_interval: Option<Duration>,
+    searches: Vec<SavedSearch>,
     db: String,
     threshold_info: u32,
     threshold_good: u32,
     threshold_warning: u32,
     threshold_critical: u32,
-    name: Option<String>,
+    new_on_click: bool,
+}
+
+/// A query with the label that is shown before its count
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SavedSearch {
+    pub name: Option<String>,
+    pub query: String,
 }
 
 #[derive(Deserialize, Debug, Clone)]
 #[serde(deny_unknown_fields, default)]
 pub struct NotmuchConfig {
-    /// Update interval in seconds
-    #[serde(deserialize_with = "deserialize_duration")]
-    pub interval: Duration,
+    /// Update interval in seconds. By default the block updates when the database changes.
+    #[serde(deserialize_with = "deserialize_opt_duration")]
+    pub interval: Option<Duration>,
     pub maildir: String,
     pub query: String,
+    /// Queries that are counted instead of `query`, each in its own widget
+    pub searches: Vec<SavedSearch>,
     pub threshold_warning: u32,
     pub threshold_critical: u32,
     pub threshold_info: u32,
     pub threshold_good: u32,
     pub name: Option<String>,
+    /// Run `notmuch new` on a left click
+    pub new_on_click: bool,
     // DEPRECATED
     pub no_icon: bool,
 }
@@ -53,23 +79,62 @@ impl Default for NotmuchConfig {
         let maildir = format!("{}/.mail", home_dir);
 
         Self {
-            interval: Duration::from_secs(10),
+            interval: None,
             maildir,
             query: "".to_string(),
+            searches: Vec::new(),
             threshold_warning: std::u32::MAX,
             threshold_critical: std::u32::MAX,
             threshold_info: std::u32::MAX,
             threshold_good: std::u32::MAX,
             name: None,
+            new_on_click: false,
             no_icon: false,
         }
     }
 }
 
-fn run_query(db_path: &str, query_string: &str) -> std::result::Result<u32, notmuch::Error> {
+fn run_queries(
+    db_path: &str,
+    searches: &[SavedSearch],
+) -> std::result::Result<Vec<u32>, notmuch::Error> {
     let db = notmuch::Database::open(&db_path, notmuch::DatabaseMode::ReadOnly)?;
-    let query = db.create_query(query_string)?;
-    query.count_messages()
+    searches
+        .iter()
+        .map(|search| db.create_query(&search.query)?.count_messages())
+        .collect()
+}
+
+/// Requests an update once a writer like `notmuch new` or a tagging script releases the lock of
+/// the Xapian database
+fn watch_database(db_path: &str, id: usize, tx_update_request: Sender<Task>) -> Result<()> {
+    let xapian = Path::new(db_path).join(".notmuch/xapian");
+    let mut notify = Inotify::init().block_error("notmuch", "failed to start inotify")?;
+    notify
+        .add_watch(&xapian, WatchMask::CLOSE_WRITE)
+        .block_error("notmuch", &format!("failed to watch {}", xapian.display()))?;
+    thread::Builder::new()
+        .name("notmuch".into())
+        .spawn(move || {
+            let mut buffer = [0; 1024];
+            loop {
+                let mut events = read_inotify_events(id, &mut notify, &mut buffer)
+                    .expect("Error while reading inotify events");
+                if events.any(|event| {
+                    event.mask.contains(EventMask::CLOSE_WRITE)
+                        && event.name == Some(OsStr::new("flintlock"))
+                }) {
+                    let _ = tx_update_request.send(Task {
+                        id,
+                        update_time: Instant::now(),
+                    });
+                }
+                // A `notmuch new` commits several times
+                thread::sleep(Duration::from_millis(250))
+            }
+        })
+        .map(|_| ())
+        .internal_error("notmuch", "failed to start thread")
 }
 
 impl ConfigBlock for Notmuch {
@@ -79,37 +144,68 @@ impl ConfigBlock for Notmuch {
         id: usize,
         block_config: Self::Config,
         shared_config: SharedConfig,
-        _tx_update_request: Sender<Task>,
+        tx_update_request: Sender<Task>,
     ) -> Result<Self> {
-        let mut widget = TextWidget::new(id, 0, shared_config);
-        if !block_config.no_icon {
-            widget.set_icon("mail")?;
+        let searches = match block_config.searches.is_empty() {
+            true => vec![SavedSearch {
+                name: block_config.name,
+                query: block_config.query,
+            }],
+            false => block_config.searches,
+        };
+        let mut texts = Vec::new();
+        for instance in 0..searches.len() {
+            let mut widget = TextWidget::new(id, instance, shared_config.clone());
+            // Once for all searches
+            if !block_config.no_icon && instance == 0 {
+                widget.set_icon("mail")?;
+            }
+            texts.push(widget);
         }
+        let update_interval = match block_config.interval {
+            Some(interval) => Some(interval),
+            None => match watch_database(&block_config.maildir, id, tx_update_request) {
+                Ok(()) => None,
+                Err(e) => {
+                    tracing::warn!("{}, updating every {:?}", e, DEFAULT_INTERVAL);
+                    Some(DEFAULT_INTERVAL)
+                }
+            },
+        };
         Ok(Notmuch {
             id,
-            update_interval: block_config.interval,
+            update_interval,
             db: block_config.maildir,
-            query: block_config.query,
+            searches,
             threshold_info: block_config.threshold_info,
             threshold_good: block_config.threshold_good,
             threshold_warning: block_config.threshold_warning,
             threshold_critical: block_config.threshold_critical,
-            name: block_config.name,
-            text: widget,
+            new_on_click: block_config.new_on_click,
+            texts,
         })
     }
 }
 
 impl Notmuch {
-    fn update_text(&mut self, count: u32) {
-        let text = match self.name {
-            Some(ref s) => format!("{}:{}", s, count),
-            _ => format!("{}", count),
-        };
-        self.text.set_text(text);
+    fn update_texts(&mut self, counts: &[u32]) {
+        let mut values: HashMap<String, Value> = HashMap::new();
+        for ((text, search), &count) in self.texts.iter_mut().zip(&self.searches).zip(counts) {
+            text.set_text(match search.name {
+                Some(ref s) => format!("{}:{}", s, count),
+                _ => format!("{}", count),
+            });
+            if let Some(name) = &search.name {
+                values.insert(name.clone(), Value::from_integer(count as i64));
+            }
+        }
+        let total: u32 = counts.iter().sum();
+        values.insert("count".to_string(), Value::from_integer(total as i64));
+        // This block has no format, export the counts for other blocks anyway
+        registry::record(&values);
     }
 
-    fn update_state(&mut self, count: u32) {
+    fn state(&self, count: u32) -> State {
         let mut state = State::Idle;
         if count >= self.threshold_critical {
             state = State::Critical;
@@ -120,29 +216,41 @@ impl Notmuch {
         } else if count >= self.threshold_info {
             state = State::Info;
         }
-        self.text.set_state(state);
+        state
     }
 }
 
 impl Block for Notmuch {
     fn update(&mut self) -> Result<Option<Update>> {
-        match run_query(&self.db, &self.query) {
-            Ok(count) => {
-                self.update_text(count);
-                self.update_state(count);
-                Ok(Some(self.update_interval.into()))
+        match run_queries(&self.db, &self.searches) {
+            Ok(counts) => {
+                self.update_texts(&counts);
+                for (i, &count) in counts.iter().enumerate() {
+                    let state = self.state(count);
+                    self.texts[i].set_state(state);
+                }
+                Ok(self.update_interval.map(Update::from))
             }
             Err(e) => Err(BlockError("notmuch".to_string(), e.to_string())),
         }
     }
 
     fn view(&self) -> Vec<&dyn I3BarWidget> {
-        vec![&self.text]
+        self.texts
+            .iter()
+            .map(|text| text as &dyn I3BarWidget)
+            .collect()
     }
 
     fn click(&mut self, event: &I3BarEvent) -> Result<()> {
         if event.button == MouseButton::Left {
-            self.update()?;
+            if self.new_on_click {
+                // The block updates once the database changes, or with the next interval
+                spawn_child_async("notmuch", &["new"])
+                    .block_error("notmuch", "could not run `notmuch new`")?;
+            } else {
+                self.update()?;
+            }
         }
 
         Ok(())
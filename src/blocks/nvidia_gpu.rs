@@ -1,6 +1,6 @@
 use std::io::BufRead;
 use std::io::BufReader;
-use std::process::{Child, ChildStdout, Command, Stdio};
+use std::process::{Child, ChildStdout, Stdio};
 use std::time::Duration;
 
 use crossbeam_channel::Sender;
@@ -11,8 +11,10 @@ use crate::config::SharedConfig;
 use crate::config::{LogicalDirection, Scrolling};
 use crate::de::deserialize_duration;
 use crate::errors::*;
+use crate::nvml::{self, Nvml, Reading};
 use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
+use crate::subprocess::command;
 use crate::util::pseudo_uuid;
 use crate::widgets::text::TextWidget;
 use crate::widgets::{I3BarWidget, Spacing, State};
@@ -50,8 +52,28 @@ pub struct NvidiaGpu {
     maximum_info: u64,
     maximum_warning: u64,
 
-    handle: Child,
-    reader: BufReader<ChildStdout>,
+    source: Source,
+    /// Whether the NVIDIA driver is loaded, the block is hidden while it isn't
+    driver_loaded: bool,
+}
+
+/// Where the values of the GPU come from
+enum Source {
+    /// `nvidia-smi` printing them every `interval`
+    Smi {
+        handle: Child,
+        reader: BufReader<ChildStdout>,
+    },
+    Nvml(Nvml),
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum NvidiaGpuDriver {
+    /// NVML if the library is installed, otherwise `nvidia-smi`
+    Auto,
+    Nvml,
+    NvidiaSmi,
 }
 
 enum MemoryWidgetMode {
@@ -72,6 +94,9 @@ pub struct NvidiaGpuConfig {
     #[serde(deserialize_with = "deserialize_duration")]
     pub interval: Duration,
 
+    /// How the values of the GPU are read
+    pub driver: NvidiaGpuDriver,
+
     /// Label to show instead of the default GPU name from `nvidia-smi`
     pub label: Option<String>,
 
@@ -113,6 +138,7 @@ impl Default for NvidiaGpuConfig {
     fn default() -> Self {
         Self {
             interval: Duration::from_secs(3),
+            driver: NvidiaGpuDriver::Auto,
             label: None,
             gpu_id: 0,
             show_utilization: true,
@@ -141,17 +167,13 @@ impl ConfigBlock for NvidiaGpu {
         let id_memory = pseudo_uuid();
         let id_fans = pseudo_uuid();
 
-        let mut params = String::from("name,memory.total,");
-
         let show_utilization = if block_config.show_utilization {
-            params += "utilization.gpu,";
             Some(TextWidget::new(id, id, shared_config.clone()).with_spacing(Spacing::Inline))
         } else {
             None
         };
 
         let show_memory = if block_config.show_memory {
-            params += "memory.used,";
             Some(
                 TextWidget::new(id, id_memory, shared_config.clone()).with_spacing(Spacing::Inline),
             )
@@ -160,52 +182,61 @@ impl ConfigBlock for NvidiaGpu {
         };
 
         let show_temperature = if block_config.show_temperature {
-            params += "temperature.gpu,";
             Some(TextWidget::new(id, id, shared_config.clone()).with_spacing(Spacing::Inline))
         } else {
             None
         };
 
         let show_fan = if block_config.show_fan_speed {
-            params += "fan.speed,";
             Some(TextWidget::new(id, id_fans, shared_config.clone()).with_spacing(Spacing::Inline))
         } else {
             None
         };
 
         let show_clocks = if block_config.show_clocks {
-            params += "clocks.current.graphics,";
             Some(TextWidget::new(id, id, shared_config.clone()).with_spacing(Spacing::Inline))
         } else {
             None
         };
 
         let show_power_draw = if block_config.show_power_draw {
-            params += "power.draw,";
             Some(TextWidget::new(id, id, shared_config.clone()).with_spacing(Spacing::Inline))
         } else {
             None
         };
 
-        let mut handle = Command::new("nvidia-smi")
-            .args(&[
-                "-l",
-                &block_config.interval.as_secs().to_string(),
-                "-i",
-                &block_config.gpu_id.to_string(),
-                &format!("--query-gpu={}", params),
-                "--format=csv,noheader,nounits",
-            ])
-            .stdout(Stdio::piped())
-            .spawn()
-            .block_error("gpu", "Failed to execute nvidia-smi.")?;
-
-        let reader = BufReader::new(
-            handle
-                .stdout
-                .take()
-                .block_error("gpu", "Failed to create bufreader for nvidia-smi.")?,
-        );
+        let nvml = match block_config.driver {
+            NvidiaGpuDriver::NvidiaSmi => None,
+            NvidiaGpuDriver::Auto => Nvml::load().ok(),
+            NvidiaGpuDriver::Nvml => {
+                Some(Nvml::load().map_err(|e| BlockError("nvidia_gpu".to_string(), e))?)
+            }
+        };
+        let source = match nvml {
+            Some(nvml) => Source::Nvml(nvml),
+            None => {
+                let mut handle = command("nvidia-smi")
+                    .args(&[
+                        "-l",
+                        &block_config.interval.as_secs().to_string(),
+                        "-i",
+                        &block_config.gpu_id.to_string(),
+                        &format!("--query-gpu={}", nvml::SMI_FIELDS),
+                        "--format=csv,noheader,nounits",
+                    ])
+                    .stdout(Stdio::piped())
+                    .spawn()
+                    .block_error("gpu", "Failed to execute nvidia-smi.")?;
+
+                let reader = BufReader::new(
+                    handle
+                        .stdout
+                        .take()
+                        .block_error("gpu", "Failed to create bufreader for nvidia-smi.")?,
+                );
+                Source::Smi { handle, reader }
+            }
+        };
 
         Ok(NvidiaGpu {
             id,
@@ -245,8 +276,8 @@ impl ConfigBlock for NvidiaGpu {
             maximum_info: block_config.info,
             maximum_warning: block_config.warning,
 
-            handle,
-            reader,
+            source,
+            driver_loaded: true,
         })
     }
 }
@@ -254,15 +285,18 @@ impl ConfigBlock for NvidiaGpu {
 impl Drop for NvidiaGpu {
     //Prevent zombies by killing and waiting on nvidia-smi command
     fn drop(&mut self) {
-        let _ = self.handle.kill();
-        let _ = self.handle.wait();
+        if let Source::Smi { handle, .. } = &mut self.source {
+            let _ = handle.kill();
+            let _ = handle.wait();
+        }
     }
 }
 
-impl Block for NvidiaGpu {
-    fn update(&mut self) -> Result<Option<Update>> {
-        self.gpu_enabled = match self.handle.try_wait() {
-            Ok(None) => true,
+impl NvidiaGpu {
+    /// The last values that `nvidia-smi` printed
+    fn read_smi(handle: &mut Child, reader: &mut BufReader<ChildStdout>) -> Result<Reading> {
+        match handle.try_wait() {
+            Ok(None) => (),
             Ok(Some(code)) => {
                 return Err(BlockError(
                     "nvidia_gpu".to_string(),
@@ -278,8 +312,7 @@ impl Block for NvidiaGpu {
         };
 
         let mut result_str = String::new();
-        let buf = self
-            .reader
+        let buf = reader
             .fill_buf()
             .block_error("gpu", "Nvidia-smi fill_buf error")?;
         let buf_str =
@@ -288,15 +321,34 @@ impl Block for NvidiaGpu {
         /* Catch up on any existing lines */
         for _ in buf_str.lines() {
             result_str.clear();
-            self.reader
+            reader
                 .read_line(&mut result_str)
                 .block_error("gpu", "Nvidia-smi read_line error")?;
         }
 
-        let result: Vec<&str> = result_str.trim().split(", ").collect();
+        nvml::parse_smi(&result_str).block_error("gpu", "unexpected output of nvidia-smi")
+    }
+}
+
+impl Block for NvidiaGpu {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let reading = match &mut self.source {
+            Source::Smi { handle, reader } => Self::read_smi(handle, reader)?,
+            Source::Nvml(nvml) => match nvml.read(self.gpu_id as u32) {
+                Ok(reading) => reading,
+                // Tried again at the next update
+                Err(e) if e.is_driver_not_loaded() => {
+                    self.driver_loaded = false;
+                    return Ok(Some(self.update_interval.into()));
+                }
+                Err(e) => return Err(BlockError("nvidia_gpu".to_string(), e.message)),
+            },
+        };
+        self.gpu_enabled = true;
+        self.driver_loaded = true;
 
-        let gpu_name = result[0].to_string();
-        let memory_total = result[1].to_string();
+        let gpu_name = reading.name;
+        let unknown = || "-".to_string();
 
         match self.name_widget_mode {
             NameWidgetMode::ShowDefaultName => {
@@ -308,24 +360,22 @@ impl Block for NvidiaGpu {
             }
         }
 
-        let mut count: usize = 2;
         if let Some(ref mut utilization_widget) = self.show_utilization {
-            utilization_widget.set_text(format!("{:02}%", result[count]));
-            count += 1;
+            utilization_widget.set_text(
+                reading
+                    .utilization
+                    .map_or_else(unknown, |utilization| format!("{:02}%", utilization)),
+            );
         }
         if let Some(ref mut memory_widget) = self.show_memory {
-            match self.memory_widget_mode {
-                MemoryWidgetMode::ShowUsedMemory => {
-                    memory_widget.set_text(format!("{}MB", result[count]));
-                }
-                MemoryWidgetMode::ShowTotalMemory => {
-                    memory_widget.set_text(format!("{}MB", memory_total));
-                }
-            }
-            count += 1;
+            let memory = match self.memory_widget_mode {
+                MemoryWidgetMode::ShowUsedMemory => reading.memory_used,
+                MemoryWidgetMode::ShowTotalMemory => reading.memory_total,
+            };
+            memory_widget.set_text(memory.map_or_else(unknown, |memory| format!("{}MB", memory)));
         }
         if let Some(ref mut temperature_widget) = self.show_temperature {
-            let temp = result[count].parse::<u64>().unwrap_or(0);
+            let temp = reading.temperature.unwrap_or(0);
             temperature_widget.set_state(match temp {
                 t if t <= self.maximum_idle => State::Idle,
                 t if t <= self.maximum_good => State::Good,
@@ -334,25 +384,33 @@ impl Block for NvidiaGpu {
                 _ => State::Critical,
             });
             temperature_widget.set_text(format!("{:02}°C", temp));
-            count += 1;
         }
         if let Some(ref mut fan_widget) = self.show_fan {
-            self.fan_speed = result[count].parse::<u64>().unwrap_or(0);
+            self.fan_speed = reading.fan_speed.unwrap_or(0);
             fan_widget.set_text(format!("{:02}%", self.fan_speed));
-            count += 1;
         }
         if let Some(ref mut clocks_widget) = self.show_clocks {
-            clocks_widget.set_text(format!("{}MHz", result[count]));
-            count += 1;
+            clocks_widget.set_text(
+                reading
+                    .clocks
+                    .map_or_else(unknown, |clocks| format!("{}MHz", clocks)),
+            );
         }
         if let Some(ref mut power_draw_widget) = self.show_power_draw {
-            power_draw_widget.set_text(format!("{} W", result[count]));
+            power_draw_widget.set_text(
+                reading
+                    .power_draw
+                    .map_or_else(unknown, |power| format!("{:.2} W", power)),
+            );
         }
 
         Ok(Some(self.update_interval.into()))
     }
 
     fn view(&self) -> Vec<&dyn I3BarWidget> {
+        if !self.driver_loaded {
+            return Vec::new();
+        }
         let mut widgets: Vec<&dyn I3BarWidget> = vec![&self.name_widget];
 
         if self.gpu_enabled {
@@ -437,7 +495,7 @@ impl Block for NvidiaGpu {
                 if let Some(ref mut fan_widget) = self.show_fan {
                     if controlled_changed {
                         if self.fan_speed_controlled {
-                            Command::new("nvidia-settings")
+                            command("nvidia-settings")
                                 .args(&[
                                     "-a",
                                     &format!("[gpu:{}]/GPUFanControlState=1", self.gpu_id),
@@ -452,7 +510,7 @@ impl Block for NvidiaGpu {
                             fan_widget.set_text(format!("{:02}%", self.fan_speed));
                             fan_widget.set_state(State::Warning);
                         } else {
-                            Command::new("nvidia-settings")
+                            command("nvidia-settings")
                                 .args(&[
                                     "-a",
                                     &format!("[gpu:{}]/GPUFanControlState=0", self.gpu_id),
@@ -462,7 +520,7 @@ impl Block for NvidiaGpu {
                             fan_widget.set_state(State::Idle);
                         }
                     } else if self.fan_speed_controlled {
-                        Command::new("nvidia-settings")
+                        command("nvidia-settings")
                             .args(&[
                                 "-a",
                                 &format!(
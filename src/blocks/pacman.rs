@@ -1,9 +1,10 @@
+use std::collections::HashSet;
 use std::env;
 use std::ffi::OsString;
 use std::fs;
 use std::os::unix::fs::symlink;
 use std::path::Path;
-use std::process::{Command, Stdio};
+use std::process::Stdio;
 use std::time::Duration;
 
 use crossbeam_channel::Sender;
@@ -18,6 +19,7 @@ use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
 use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
+use crate::subprocess::command;
 use crate::util::has_command;
 use crate::widgets::text::TextWidget;
 use crate::widgets::{I3BarWidget, State};
@@ -34,8 +36,13 @@ pub struct Pacman {
     watched: Watched,
     uptodate: bool,
     hide_when_uptodate: bool,
+    /// Whether the updates of running packages are looked for
+    check_running: bool,
+    warning_running: bool,
 }
 
+const PACMAN_CONF: &str = "/etc/pacman.conf";
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum Watched {
     None,
@@ -75,6 +82,10 @@ pub struct PacmanConfig {
     pub aur_command: Option<String>,
 
     pub hide_when_uptodate: bool,
+
+    /// Indicate a `warning` state for the block if an update is for a package that is running,
+    /// like the kernel or the compositor
+    pub warning_running: bool,
 }
 
 impl Default for PacmanConfig {
@@ -88,6 +99,7 @@ impl Default for PacmanConfig {
             critical_updates_regex: None,
             aur_command: None,
             hide_when_uptodate: false,
+            warning_running: false,
         }
     }
 }
@@ -180,6 +192,11 @@ impl ConfigBlock for Pacman {
             )?,
             uptodate: false,
             hide_when_uptodate: block_config.hide_when_uptodate,
+            check_running: block_config.warning_running
+                || [&fmt_normal, &fmt_singular, &fmt_up_to_date]
+                    .iter()
+                    .any(|format| format.contains("running")),
+            warning_running: block_config.warning_running,
             format: fmt_normal,
             format_singular: fmt_singular,
             format_up_to_date: fmt_up_to_date,
@@ -239,7 +256,7 @@ fn get_pacman_available_updates() -> Result<String> {
     }
 
     // Update database
-    Command::new("sh")
+    command("sh")
         .env("LC_ALL", "C")
         .args(&[
             "-c",
@@ -254,7 +271,7 @@ fn get_pacman_available_updates() -> Result<String> {
 
     // Get updates list
     String::from_utf8(
-        Command::new("sh")
+        command("sh")
             .env("LC_ALL", "C")
             .args(&[
                 "-c",
@@ -272,7 +289,7 @@ fn get_pacman_available_updates() -> Result<String> {
 
 fn get_aur_available_updates(aur_command: &str) -> Result<String> {
     String::from_utf8(
-        Command::new("sh")
+        command("sh")
             .args(&["-c", aur_command])
             .output()
             .block_error("pacman", &format!("aur command: {} failed", aur_command))?
@@ -284,6 +301,104 @@ fn get_aur_available_updates(aur_command: &str) -> Result<String> {
     )
 }
 
+/// The patterns of `IgnorePkg` in the `[options]` of pacman.conf, like `linux*`
+fn ignored_packages(conf: &str) -> Vec<String> {
+    let mut options = false;
+    let mut patterns = Vec::new();
+    for line in conf.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            options = section == "options";
+        } else if let (true, Some((key, value))) = (options, line.split_once('=')) {
+            if key.trim() == "IgnorePkg" {
+                patterns.extend(value.split_whitespace().map(str::to_string));
+            }
+        }
+    }
+    patterns
+}
+
+/// Whether `name` matches the glob `pattern` of pacman.conf, with `*` and `?`
+fn glob_matches(pattern: &[u8], name: &[u8]) -> bool {
+    match (pattern.first(), name.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => {
+            glob_matches(&pattern[1..], name)
+                || (!name.is_empty() && glob_matches(pattern, &name[1..]))
+        }
+        (Some(b'?'), Some(_)) => glob_matches(&pattern[1..], &name[1..]),
+        (Some(p), Some(n)) if p == n => glob_matches(&pattern[1..], &name[1..]),
+        _ => false,
+    }
+}
+
+/// The name of the package of a line like `systemd 245.4-2 -> 245.5-1`
+fn package_name(line: &str) -> &str {
+    line.split_whitespace().next().unwrap_or_default()
+}
+
+/// The lines of `updates` without the packages of `IgnorePkg`, which AUR helpers may list
+fn without_ignored(updates: &str, ignored: &[String]) -> String {
+    updates
+        .lines()
+        .filter(|line| {
+            let name = package_name(line).as_bytes();
+            !ignored
+                .iter()
+                .any(|pattern| glob_matches(pattern.as_bytes(), name))
+        })
+        .map(|line| format!("{}\n", line))
+        .collect()
+}
+
+/// The packages of `updates` that are running: the kernel of `/usr/lib/modules/<release>/pkgbase`
+/// and the packages that have the program of a running process, like the compositor
+fn running_packages(updates: &str) -> Result<Vec<String>> {
+    let packages: Vec<&str> = updates
+        .lines()
+        .filter(|line| !line.contains("[ignored]"))
+        .map(package_name)
+        .filter(|name| !name.is_empty())
+        .collect();
+    if packages.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut running = HashSet::new();
+
+    let release = nix::sys::utsname::uname().release().to_string();
+    if let Ok(kernel) = fs::read_to_string(format!("/usr/lib/modules/{}/pkgbase", release)) {
+        let kernel = kernel.trim();
+        if packages.contains(&kernel) {
+            running.insert(kernel.to_string());
+        }
+    }
+
+    let programs: HashSet<_> = fs::read_dir("/proc")
+        .block_error("pacman", "failed to read /proc")?
+        .flatten()
+        .filter_map(|entry| fs::read_link(entry.path().join("exe")).ok())
+        .collect();
+    // Lines like `sway /usr/bin/sway`, and errors for the packages that aren't installed
+    let files = command("pacman")
+        .env("LC_ALL", "C")
+        .arg("-Ql")
+        .args(&packages)
+        .stderr(Stdio::null())
+        .output()
+        .block_error("pacman", "failed to run `pacman -Ql`")?;
+    for line in String::from_utf8_lossy(&files.stdout).lines() {
+        if let Some((package, file)) = line.split_once(' ') {
+            if programs.contains(Path::new(file)) {
+                running.insert(package.to_string());
+            }
+        }
+    }
+
+    let mut running: Vec<String> = running.into_iter().collect();
+    running.sort();
+    Ok(running)
+}
+
 fn get_update_count(updates: &str) -> usize {
     updates
         .lines()
@@ -313,10 +428,15 @@ impl Block for Pacman {
     }
 
     fn update(&mut self) -> Result<Option<Update>> {
-        let (formatting_map, warning, critical, cum_count) = match &self.watched {
+        // Read every time, so changes to pacman.conf apply right away
+        let ignored = fs::read_to_string(PACMAN_CONF)
+            .map(|conf| ignored_packages(&conf))
+            .unwrap_or_default();
+        let (mut formatting_map, warning, critical, cum_count, updates) = match &self.watched {
             Watched::Pacman => {
                 check_fakeroot_command_exists()?;
-                let pacman_available_updates = get_pacman_available_updates()?;
+                let pacman_available_updates =
+                    without_ignored(&get_pacman_available_updates()?, &ignored);
                 let pacman_count = get_update_count(&pacman_available_updates);
                 let formatting_map = map!(
                     "count" => Value::from_integer(pacman_count as i64),
@@ -330,10 +450,17 @@ impl Block for Pacman {
                     has_critical_update(&pacman_available_updates, regex)
                 });
 
-                (formatting_map, warning, critical, pacman_count)
+                (
+                    formatting_map,
+                    warning,
+                    critical,
+                    pacman_count,
+                    pacman_available_updates,
+                )
             }
             Watched::AUR(aur_command) => {
-                let aur_available_updates = get_aur_available_updates(aur_command)?;
+                let aur_available_updates =
+                    without_ignored(&get_aur_available_updates(aur_command)?, &ignored);
                 let aur_count = get_update_count(&aur_available_updates);
                 let formatting_map = map!(
                     "aur" => Value::from_integer(aur_count as i64)
@@ -346,13 +473,21 @@ impl Block for Pacman {
                     has_critical_update(&aur_available_updates, regex)
                 });
 
-                (formatting_map, warning, critical, aur_count)
+                (
+                    formatting_map,
+                    warning,
+                    critical,
+                    aur_count,
+                    aur_available_updates,
+                )
             }
             Watched::Both(aur_command) => {
                 check_fakeroot_command_exists()?;
-                let pacman_available_updates = get_pacman_available_updates()?;
+                let pacman_available_updates =
+                    without_ignored(&get_pacman_available_updates()?, &ignored);
                 let pacman_count = get_update_count(&pacman_available_updates);
-                let aur_available_updates = get_aur_available_updates(aur_command)?;
+                let aur_available_updates =
+                    without_ignored(&get_aur_available_updates(aur_command)?, &ignored);
                 let aur_count = get_update_count(&aur_available_updates);
                 let formatting_map = map!(
                     "count" =>  Value::from_integer(pacman_count as i64),
@@ -370,10 +505,30 @@ impl Block for Pacman {
                         || has_critical_update(&pacman_available_updates, regex)
                 });
 
-                (formatting_map, warning, critical, pacman_count + aur_count)
+                (
+                    formatting_map,
+                    warning,
+                    critical,
+                    pacman_count + aur_count,
+                    pacman_available_updates + &aur_available_updates,
+                )
             }
-            Watched::None => (std::collections::HashMap::new(), false, false, 0),
+            Watched::None => (
+                std::collections::HashMap::new(),
+                false,
+                false,
+                0,
+                String::new(),
+            ),
         };
+        let running = match self.check_running {
+            true => running_packages(&updates)?.len(),
+            false => 0,
+        };
+        if self.check_running {
+            formatting_map.insert("running", Value::from_integer(running as i64));
+        }
+        let warning = warning || (self.warning_running && running > 0);
         self.output.set_texts(match cum_count {
             0 => self.format_up_to_date.render(&formatting_map)?,
             1 => self.format_singular.render(&formatting_map)?,
@@ -407,7 +562,8 @@ impl Block for Pacman {
 #[cfg(test)]
 mod tests {
     use crate::blocks::pacman::{
-        get_aur_available_updates, get_update_count, PacmanConfig, Watched,
+        get_aur_available_updates, get_update_count, ignored_packages, without_ignored,
+        PacmanConfig, Watched,
     };
     use crate::formatting::FormatTemplate;
 
@@ -422,6 +578,26 @@ mod tests {
         assert_eq!(get_update_count(two_updates_available), 2);
     }
 
+    #[test]
+    fn test_ignored_packages() {
+        let conf = "[options]\n\
+            HoldPkg = pacman glibc\n\
+            IgnorePkg = linux* nvidia # until the driver is fixed\n\
+            IgnorePkg = sway\n\
+            [core]\n\
+            IgnorePkg = systemd\n";
+        let ignored = ignored_packages(conf);
+        assert_eq!(ignored, ["linux*", "nvidia", "sway"]);
+        let updates = "linux-zen 6.1.1-1 -> 6.1.2-1\n\
+            nvidia-utils 525-1 -> 525-2\n\
+            swayidle 1.7-1 -> 1.8-1\n";
+        assert_eq!(
+            without_ignored(updates, &ignored),
+            "nvidia-utils 525-1 -> 525-2\nswayidle 1.7-1 -> 1.8-1\n"
+        );
+        assert_eq!(get_update_count(&without_ignored("", &ignored)), 0);
+    }
+
     #[test]
     fn test_watched() {
         let fmt_count = FormatTemplate::new("foo {count} bar", None).unwrap();
@@ -0,0 +1,95 @@
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::{LogicalDirection, Scrolling, SharedConfig};
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::profiles;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::widgets::text::TextWidget;
+use crate::widgets::I3BarWidget;
+
+/// Shows the active profile and switches to the next one when it's clicked. The main loop
+/// updates it after every switch.
+pub struct Profile {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    scrolling: Scrolling,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct ProfileConfig {
+    pub format: FormatTemplate,
+}
+
+impl ConfigBlock for Profile {
+    type Config = ProfileConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        Ok(Profile {
+            id,
+            scrolling: shared_config.scrolling,
+            text: TextWidget::new(id, 0, shared_config),
+            format: block_config.format.with_default("{profile}")?,
+        })
+    }
+}
+
+impl Profile {
+    fn switch(&self, backwards: bool) {
+        match profiles::next(backwards) {
+            Some(name) => profiles::request(name),
+            None => crate::click::set_unhandled(),
+        }
+    }
+}
+
+impl Block for Profile {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let values = map!(
+            "profile" => Value::from_string(profiles::active().unwrap_or_else(|| "all".to_string())),
+        );
+        self.text.set_format(&self.format, &values)?;
+        Ok(None)
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        match event.button {
+            MouseButton::Left => self.switch(false),
+            MouseButton::Right => self.switch(true),
+            button => match self.scrolling.to_logical_direction(button) {
+                Some(LogicalDirection::Up) => self.switch(false),
+                Some(LogicalDirection::Down) => self.switch(true),
+                None => crate::click::set_unhandled(),
+            },
+        }
+        Ok(())
+    }
+
+    fn action(&mut self, action: &str) -> Result<bool> {
+        match action {
+            "next" => self.switch(false),
+            "previous" => self.switch(true),
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
@@ -0,0 +1,225 @@
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::http;
+use crate::scheduler::Task;
+use crate::services::Service;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+const GITHUB_TOKEN_ENV: &str = "I3RS_GITHUB_TOKEN";
+
+/// A compatibility tool whose releases are published on GitHub, like in ProtonUp-Qt
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Runner {
+    pub name: String,
+    /// The GitHub repository of the releases, e.g. `GloriousEggroll/proton-ge-custom`
+    pub repository: String,
+    /// The directory of the installed versions, which have the tag of their release in their name
+    pub directory: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum RunnerConfig {
+    /// One of the runners of `PRESETS`
+    Preset(String),
+    Custom(Runner),
+}
+
+/// The name, the repository and the directory of the runners that are known by name
+const PRESETS: &[(&str, &str, &str)] = &[
+    (
+        "GE-Proton",
+        "GloriousEggroll/proton-ge-custom",
+        "~/.steam/root/compatibilitytools.d",
+    ),
+    (
+        "Wine-GE",
+        "GloriousEggroll/wine-ge-custom",
+        "~/.local/share/lutris/runners/wine",
+    ),
+    (
+        "Kron4ek",
+        "Kron4ek/Wine-Builds",
+        "~/.local/share/lutris/runners/wine",
+    ),
+];
+
+pub struct Proton {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    format_up_to_date: FormatTemplate,
+    hide_when_up_to_date: bool,
+    update_interval: Duration,
+    api_server: String,
+    runners: Vec<Runner>,
+    up_to_date: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct ProtonConfig {
+    /// Names of the presets or tables with `name`, `repository` and `directory`
+    pub runners: Vec<RunnerConfig>,
+
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    pub api_server: String,
+
+    /// Format while a runner has an update
+    pub format: FormatTemplate,
+
+    pub format_up_to_date: FormatTemplate,
+
+    pub hide_when_up_to_date: bool,
+}
+
+impl Default for ProtonConfig {
+    fn default() -> Self {
+        Self {
+            runners: vec![RunnerConfig::Preset("GE-Proton".to_string())],
+            interval: Duration::from_secs(6 * 60 * 60),
+            api_server: "https://api.github.com".to_string(),
+            format: FormatTemplate::default(),
+            format_up_to_date: FormatTemplate::default(),
+            hide_when_up_to_date: true,
+        }
+    }
+}
+
+/// Whether one of the `installed` versions is the release `tag`. The directories of the versions
+/// are named like the tag, sometimes with a prefix or a suffix like `lutris-GE-Proton8-26-x86_64`.
+fn installed(installed: &[String], tag: &str) -> bool {
+    installed.iter().any(|name| name.contains(tag))
+}
+
+impl ConfigBlock for Proton {
+    type Config = ProtonConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let runners = block_config
+            .runners
+            .into_iter()
+            .map(|runner| match runner {
+                RunnerConfig::Custom(runner) => Ok(runner),
+                RunnerConfig::Preset(name) => PRESETS
+                    .iter()
+                    .find(|(preset, _, _)| *preset == name)
+                    .map(|(name, repository, directory)| Runner {
+                        name: name.to_string(),
+                        repository: repository.to_string(),
+                        directory: directory.to_string(),
+                    })
+                    .block_error("proton", &format!("unknown runner '{}'", name)),
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Proton {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("proton")?,
+            format: block_config.format.with_default("{latest}")?,
+            format_up_to_date: block_config.format_up_to_date.with_default("up to date")?,
+            hide_when_up_to_date: block_config.hide_when_up_to_date,
+            update_interval: block_config.interval,
+            api_server: block_config.api_server,
+            runners,
+            up_to_date: true,
+        })
+    }
+
+    fn default_depends_on() -> &'static [Service] {
+        &[Service::Network]
+    }
+}
+
+impl Block for Proton {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let token = std::env::var(GITHUB_TOKEN_ENV).ok();
+        let authorization = token.map(|token| format!("Bearer {}", token));
+        let mut outdated = Vec::new();
+        for runner in &self.runners {
+            let directory = shellexpand::tilde(&runner.directory).to_string();
+            // Runners that aren't installed can't be outdated
+            if !Path::new(&directory).is_dir() {
+                continue;
+            }
+            let versions: Vec<String> = fs::read_dir(&directory)
+                .block_error("proton", &format!("failed to read '{}'", directory))?
+                .flatten()
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .collect();
+
+            let mut headers = vec![("Accept", "application/vnd.github+json")];
+            if let Some(authorization) = &authorization {
+                headers.push(("Authorization", authorization));
+            }
+            let url = format!(
+                "{}/repos/{}/releases/latest",
+                self.api_server, runner.repository
+            );
+            let release = http::http_get_json(&url, Some(Duration::from_secs(10)), headers)?;
+            let tag = release.content["tag_name"].as_str().block_error(
+                "proton",
+                &format!("no release of {} was found", runner.repository),
+            )?;
+            if !installed(&versions, tag) {
+                outdated.push((runner.name.clone(), tag.to_string()));
+            }
+        }
+
+        self.up_to_date = outdated.is_empty();
+        let join = |part: fn(&(String, String)) -> &String| {
+            outdated
+                .iter()
+                .map(part)
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        let values = map!(
+            "count" => Value::from_integer(outdated.len() as i64),
+            "runners" => Value::from_string(join(|(name, _)| name)),
+            "latest" => Value::from_string(join(|(_, tag)| tag)),
+        );
+        if self.up_to_date {
+            self.text.set_state(State::Idle);
+            self.text.set_format(&self.format_up_to_date, &values)?;
+        } else {
+            self.text.set_state(State::Info);
+            self.text.set_format(&self.format, &values)?;
+        }
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        if self.hide_when_up_to_date && self.up_to_date {
+            vec![]
+        } else {
+            vec![&self.text]
+        }
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
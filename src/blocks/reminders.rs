@@ -0,0 +1,453 @@
+//! Reminders that are added with `i3status-rs remind "standup in 20m"` and kept in a file, one
+//! per line with the due time and the text separated by a tab, e.g.
+//! `2021-10-14T10:20:00+02:00\tstandup`. The file is read on every update, so it can also be
+//! edited by hand.
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use chrono::{DateTime, Duration as ChronoDuration, Local, NaiveTime, TimeZone};
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::scheduler::Task;
+use crate::subprocess::spawn_child_async;
+use crate::widgets::text::TextWidget;
+use crate::widgets::I3BarWidget;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reminder {
+    pub due: DateTime<Local>,
+    pub text: String,
+}
+
+impl Reminder {
+    /// Parses a reminder like `standup in 20m`, `call mum in 1 hour 30 minutes`, `lunch at 12:30`
+    /// or `dentist tomorrow at 9am`. Times of the day that have passed are on the next day, and
+    /// just `tomorrow` is at 9.
+    pub fn parse(input: &str, now: DateTime<Local>) -> StdResult<Self, String> {
+        let words: Vec<&str> = input.split_whitespace().collect();
+        let invalid = || {
+            format!(
+                "can't tell when '{}' is due, give a time like 'in 20m' or 'at 14:00'",
+                input.trim()
+            )
+        };
+        // The time is at the end, e.g. `talk about it in the meeting in 5m` is due in 5 minutes
+        let (position, due) = words
+            .iter()
+            .enumerate()
+            .find_map(|(i, &word)| {
+                let rest = &words[i + 1..];
+                let due = match word {
+                    "in" => Some(now + parse_duration(rest)?),
+                    "at" => match rest {
+                        [time @ .., "tomorrow"] => at(now, time, true),
+                        time => at(now, time, false),
+                    },
+                    "tomorrow" => match rest {
+                        ["at", time @ ..] => at(now, time, true),
+                        [] => at(now, &["9"], true),
+                        _ => None,
+                    },
+                    _ => None,
+                }?;
+                Some((i, due))
+            })
+            .ok_or_else(invalid)?;
+        let text = &words[..position];
+        if text.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Reminder {
+            // Whole seconds, so the same reminder sent to several bars is only added once
+            due: Local.timestamp(due.timestamp(), 0),
+            text: text.join(" "),
+        })
+    }
+
+    fn to_line(&self) -> String {
+        format!(
+            "{}\t{}\n",
+            self.due.to_rfc3339(),
+            self.text.replace('\n', " ")
+        )
+    }
+}
+
+/// `20m`, `1h30m`, `1 hour 30 minutes` or `an hour`
+fn parse_duration(words: &[&str]) -> Option<ChronoDuration> {
+    if words.is_empty() {
+        return None;
+    }
+    let mut total = ChronoDuration::zero();
+    let mut amount: Option<i64> = None;
+    for word in words {
+        let mut rest = *word;
+        while !rest.is_empty() {
+            let digits = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let (number, unit) = rest.split_at(digits);
+            if !number.is_empty() {
+                if amount.is_some() {
+                    return None;
+                }
+                amount = Some(number.parse().ok()?);
+            }
+            let end = unit
+                .find(|c: char| c.is_ascii_digit())
+                .unwrap_or(unit.len());
+            let (unit, tail) = unit.split_at(end);
+            rest = tail;
+            if unit.is_empty() {
+                continue;
+            }
+            if unit == "a" || unit == "an" {
+                if amount.is_some() {
+                    return None;
+                }
+                amount = Some(1);
+                continue;
+            }
+            let seconds = match unit {
+                "s" | "sec" | "secs" | "second" | "seconds" => 1,
+                "m" | "min" | "mins" | "minute" | "minutes" => 60,
+                "h" | "hr" | "hrs" | "hour" | "hours" => 3600,
+                "d" | "day" | "days" => 86400,
+                _ => return None,
+            };
+            total = total + ChronoDuration::seconds(amount.take()? * seconds);
+        }
+    }
+    match amount {
+        // A number without a unit
+        Some(_) => None,
+        None if total > ChronoDuration::zero() => Some(total),
+        None => None,
+    }
+}
+
+/// `14:00`, `9`, `9am`, `9:30 pm`, today or `tomorrow`
+fn at(now: DateTime<Local>, words: &[&str], tomorrow: bool) -> Option<DateTime<Local>> {
+    let (time, suffix) = match words {
+        [time] => {
+            let split = time
+                .find(|c: char| c.is_ascii_alphabetic())
+                .unwrap_or(time.len());
+            time.split_at(split)
+        }
+        [time, suffix] => (*time, *suffix),
+        _ => return None,
+    };
+    let (hour, minute) = match time.split_once(':') {
+        Some((hour, minute)) if minute.len() == 2 => (hour.parse().ok()?, minute.parse().ok()?),
+        Some(_) => return None,
+        None => (time.parse::<u32>().ok()?, 0),
+    };
+    let hour = match suffix.to_ascii_lowercase().as_str() {
+        "" => hour,
+        "am" if (1..=12).contains(&hour) => hour % 12,
+        "pm" if (1..=12).contains(&hour) => hour % 12 + 12,
+        _ => return None,
+    };
+    let time = NaiveTime::from_hms_opt(hour, minute, 0)?;
+    let mut date = now.date();
+    if tomorrow || now.time() >= time {
+        date = date.succ();
+    }
+    date.and_time(time)
+}
+
+pub struct Reminders {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    update_interval: Duration,
+    file: PathBuf,
+    /// Whether there is a reminder to show
+    pending: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct RemindersConfig {
+    pub format: FormatTemplate,
+
+    /// The file with the reminders
+    pub file: String,
+
+    /// Update interval in seconds while there are no reminders, to see reminders that were
+    /// added to the file by hand
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+}
+
+impl Default for RemindersConfig {
+    fn default() -> Self {
+        Self {
+            format: FormatTemplate::default(),
+            file: "~/.local/share/i3status-rust/reminders.txt".to_string(),
+            interval: Duration::from_secs(60),
+        }
+    }
+}
+
+impl ConfigBlock for Reminders {
+    type Config = RemindersConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let file = shellexpand::full(&block_config.file).map_err(|e| {
+            ConfigurationError(
+                "reminders".to_string(),
+                format!("Failed to expand file path {}: {}", block_config.file, e),
+            )
+        })?;
+        Ok(Reminders {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("bell")?,
+            format: block_config.format.with_default("{text} {countdown}")?,
+            update_interval: block_config.interval,
+            file: PathBuf::from(file.to_string()),
+            pending: false,
+        })
+    }
+}
+
+impl Reminders {
+    /// The reminders of the file, sorted by their due time
+    fn read(&self) -> Result<Vec<Reminder>> {
+        let content = match fs::read_to_string(&self.file) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(_) => {
+                return Err(BlockError(
+                    "reminders".to_string(),
+                    format!("failed to read {}", self.file.display()),
+                ))
+            }
+        };
+        let mut reminders = content
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(number, line)| {
+                line.split_once('\t')
+                    .and_then(|(due, text)| {
+                        Some(Reminder {
+                            due: DateTime::parse_from_rfc3339(due)
+                                .ok()?
+                                .with_timezone(&Local),
+                            text: text.to_string(),
+                        })
+                    })
+                    .block_error(
+                        "reminders",
+                        &format!("invalid line {} in {}", number + 1, self.file.display()),
+                    )
+            })
+            .collect::<Result<Vec<Reminder>>>()?;
+        reminders.sort_by_key(|reminder| reminder.due);
+        Ok(reminders)
+    }
+
+    fn write(&self, reminders: &[Reminder]) -> Result<()> {
+        if let Some(dir) = self.file.parent() {
+            fs::create_dir_all(dir).block_error("reminders", "failed to create the directory")?;
+        }
+        fs::write(
+            &self.file,
+            reminders.iter().map(Reminder::to_line).collect::<String>(),
+        )
+        .block_error(
+            "reminders",
+            &format!("failed to write {}", self.file.display()),
+        )
+    }
+}
+
+fn seconds_left(left: ChronoDuration) -> u64 {
+    (left.num_milliseconds().max(0) as u64).div_ceil(1000)
+}
+
+/// `45s`, `20m`, `2h 05m` or `3d 4h`, rounded up, since the reminder isn't due before
+fn countdown(left: ChronoDuration) -> String {
+    let seconds = seconds_left(left);
+    if seconds < 60 {
+        return format!("{}s", seconds);
+    }
+    let minutes = seconds.div_ceil(60);
+    match minutes {
+        _ if minutes < 60 => format!("{}m", minutes),
+        _ if minutes < 24 * 60 => format!("{}h {:02}m", minutes / 60, minutes % 60),
+        _ => format!("{}d {}h", minutes / (24 * 60), minutes % (24 * 60) / 60),
+    }
+}
+
+impl Block for Reminders {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let mut reminders = self.read()?;
+        let now = Local::now();
+        let due = reminders
+            .iter()
+            .take_while(|reminder| reminder.due <= now)
+            .count();
+        if due > 0 {
+            // Reminders that were due while the bar wasn't running are shown late
+            for reminder in reminders.drain(..due) {
+                spawn_child_async(
+                    "notify-send",
+                    &["--urgency", "critical", "Reminder", &reminder.text],
+                )
+                .block_error("reminders", "failed to run notify-send")?;
+            }
+            self.write(&reminders)?;
+        }
+
+        let next = match reminders.first() {
+            Some(next) => next,
+            None => {
+                self.pending = false;
+                return Ok(Some(self.update_interval.into()));
+            }
+        };
+        let left = next.due - now;
+        let values = map!(
+            "text" => Value::from_string(next.text.clone()),
+            "countdown" => Value::from_string(countdown(left)),
+            "time" => Value::from_string(next.due.format("%R").to_string()),
+            "count" => Value::from_integer(reminders.len() as i64),
+        );
+        self.text.set_format(&self.format, &values)?;
+        self.pending = true;
+
+        // Until the countdown changes, which is when the next reminder is due at the latest
+        let changes = match seconds_left(left) {
+            seconds @ 0..=60 => seconds.saturating_sub(1),
+            seconds => (seconds.div_ceil(60) - 1) * 60,
+        };
+        let wait = (left - ChronoDuration::seconds(changes as i64))
+            .to_std()
+            .unwrap_or_default();
+        Ok(Some(wait.max(Duration::from_millis(10)).into()))
+    }
+
+    fn remind(&mut self, reminder: &Reminder) -> Result<bool> {
+        let mut reminders = self.read()?;
+        if !reminders.contains(reminder) {
+            reminders.push(reminder.clone());
+            reminders.sort_by_key(|reminder| reminder.due);
+            self.write(&reminders)?;
+        }
+        Ok(true)
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        match self.pending {
+            true => vec![&self.text],
+            false => Vec::new(),
+        }
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> DateTime<Local> {
+        Local.ymd(2021, 10, 14).and_hms(10, 0, 0)
+    }
+
+    fn parse(input: &str) -> StdResult<Reminder, String> {
+        Reminder::parse(input, now())
+    }
+
+    fn due(input: &str) -> DateTime<Local> {
+        parse(input).unwrap().due
+    }
+
+    #[test]
+    fn quick_add() {
+        assert_eq!(
+            parse("standup in 20m"),
+            Ok(Reminder {
+                due: Local.ymd(2021, 10, 14).and_hms(10, 20, 0),
+                text: "standup".to_string(),
+            })
+        );
+        assert_eq!(
+            due("x in 1h30m"),
+            Local.ymd(2021, 10, 14).and_hms(11, 30, 0)
+        );
+        assert_eq!(
+            due("x in 1 hour 30 minutes"),
+            Local.ymd(2021, 10, 14).and_hms(11, 30, 0)
+        );
+        assert_eq!(
+            due("x in an hour"),
+            Local.ymd(2021, 10, 14).and_hms(11, 0, 0)
+        );
+        assert_eq!(
+            due("x in 2 days"),
+            Local.ymd(2021, 10, 16).and_hms(10, 0, 0)
+        );
+        assert_eq!(
+            due("lunch at 12:30"),
+            Local.ymd(2021, 10, 14).and_hms(12, 30, 0)
+        );
+        assert_eq!(due("x at 9am"), Local.ymd(2021, 10, 15).and_hms(9, 0, 0));
+        assert_eq!(
+            due("x at 3:15 pm"),
+            Local.ymd(2021, 10, 14).and_hms(15, 15, 0)
+        );
+        assert_eq!(due("x at 12am"), Local.ymd(2021, 10, 15).and_hms(0, 0, 0));
+        assert_eq!(
+            due("x tomorrow at 10"),
+            Local.ymd(2021, 10, 15).and_hms(10, 0, 0)
+        );
+        assert_eq!(
+            due("x at 11 tomorrow"),
+            Local.ymd(2021, 10, 15).and_hms(11, 0, 0)
+        );
+        assert_eq!(parse("x at 11 tomorrow").unwrap().text, "x");
+        assert_eq!(
+            parse("talk about it in the meeting in 5m").unwrap().text,
+            "talk about it in the meeting"
+        );
+        assert!(parse("standup").is_err());
+        assert!(parse("in 20m").is_err());
+        assert!(parse("x in 20").is_err());
+        assert!(parse("x in 20 parsecs").is_err());
+        assert!(parse("x at 25:00").is_err());
+        assert!(parse("x at 13pm").is_err());
+    }
+
+    #[test]
+    fn countdowns() {
+        assert_eq!(countdown(ChronoDuration::seconds(45)), "45s");
+        assert_eq!(countdown(ChronoDuration::milliseconds(44_100)), "45s");
+        assert_eq!(countdown(ChronoDuration::seconds(60)), "1m");
+        assert_eq!(countdown(ChronoDuration::seconds(1170)), "20m");
+        assert_eq!(countdown(ChronoDuration::seconds(3900)), "1h 05m");
+        assert_eq!(
+            countdown(ChronoDuration::seconds(3 * 86400 + 4 * 3600)),
+            "3d 4h"
+        );
+    }
+}
@@ -0,0 +1,283 @@
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::http;
+use crate::scheduler::Task;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+const BLOCK_NAME: &str = "remote";
+
+/// How long the other bar has to answer
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct Remote {
+    id: usize,
+    /// One widget for each widget of the remote blocks
+    widgets: Vec<TextWidget>,
+    shared_config: SharedConfig,
+    update_interval: Duration,
+    address: Address,
+    token: Option<String>,
+    blocks: Vec<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RemoteConfig {
+    /// The `metrics` address of the other bar like `http://server:9123`, or the path of its socket
+    pub address: String,
+
+    /// The `metrics_token` of the other bar
+    #[serde(default)]
+    pub token: Option<String>,
+
+    /// The blocks that are shown, by their `export` name, type or position. All by default.
+    #[serde(default)]
+    pub blocks: Vec<String>,
+
+    #[serde(
+        default = "RemoteConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+}
+
+impl RemoteConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(10)
+    }
+}
+
+enum Address {
+    /// The URL of `/state`
+    Http(String),
+    Socket(String),
+}
+
+/// The parts of the state of `i3status-rs get-state` that are shown
+#[derive(Deserialize, Debug)]
+struct RemoteState {
+    blocks: Vec<RemoteBlock>,
+}
+
+#[derive(Deserialize, Debug)]
+struct RemoteBlock {
+    position: usize,
+    block: Option<String>,
+    name: Option<String>,
+    hidden: bool,
+    widgets: Vec<RemoteWidget>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+struct RemoteWidget {
+    full_text: String,
+    short_text: Option<String>,
+    /// Themed like the widgets of this bar
+    state: State,
+}
+
+impl RemoteBlock {
+    fn matches(&self, block: &str) -> bool {
+        self.name.as_deref() == Some(block)
+            || self.block.as_deref() == Some(block)
+            || self.position.to_string() == block
+    }
+}
+
+/// The widgets of the shown blocks, in the order of `blocks`, or of all blocks if it's empty
+fn select<'a>(state: &'a RemoteState, blocks: &[String]) -> Vec<&'a RemoteWidget> {
+    let shown = state.blocks.iter().filter(|remote| !remote.hidden);
+    match blocks.is_empty() {
+        true => shown.flat_map(|remote| &remote.widgets).collect(),
+        false => blocks
+            .iter()
+            .flat_map(|block| shown.clone().filter(move |remote| remote.matches(block)))
+            .flat_map(|remote| &remote.widgets)
+            .collect(),
+    }
+}
+
+impl Remote {
+    fn fetch(&self) -> Result<RemoteState> {
+        let state = match &self.address {
+            Address::Http(url) => {
+                let authorization = self.token.as_ref().map(|token| format!("Bearer {}", token));
+                let headers = authorization
+                    .iter()
+                    .map(|authorization| ("Authorization", authorization.as_str()))
+                    .collect();
+                let response = http::http_get_json(url, Some(TIMEOUT), headers)?;
+                if response.code != 200 {
+                    return Err(BlockError(
+                        BLOCK_NAME.to_string(),
+                        format!(
+                            "{}: {}",
+                            url,
+                            response.content["error"]
+                                .as_str()
+                                .map_or_else(|| format!("HTTP {}", response.code), str::to_string)
+                        ),
+                    ));
+                }
+                response.content
+            }
+            Address::Socket(path) => {
+                let stream = UnixStream::connect(path)
+                    .block_error(BLOCK_NAME, &format!("failed to connect to '{}'", path))?;
+                let mut reply = String::new();
+                stream
+                    .set_read_timeout(Some(TIMEOUT))
+                    .and_then(|_| (&stream).write_all(b"get-state\n"))
+                    .and_then(|_| BufReader::new(&stream).read_line(&mut reply))
+                    .block_error(BLOCK_NAME, &format!("failed to talk to '{}'", path))?;
+                if let Some(e) = reply.trim_end().strip_prefix("error ") {
+                    return Err(BlockError(
+                        BLOCK_NAME.to_string(),
+                        format!("{}: {}", path, e),
+                    ));
+                }
+                serde_json::from_str(&reply)
+                    .block_error(BLOCK_NAME, "failed to parse the state of the other bar")?
+            }
+        };
+        serde_json::from_value(state).block_error(BLOCK_NAME, "unexpected state of the other bar")
+    }
+}
+
+impl ConfigBlock for Remote {
+    type Config = RemoteConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let address = block_config.address.trim_end_matches('/');
+        let address = if address.starts_with("http://") || address.starts_with("https://") {
+            Address::Http(format!("{}/state", address))
+        } else {
+            let path = shellexpand::full(address)
+                .configuration_error("failed to expand the path of 'address'")?;
+            match path.starts_with('/') {
+                true => Address::Socket(path.to_string()),
+                false => {
+                    return Err(ConfigurationError(
+                        BLOCK_NAME.to_string(),
+                        format!(
+                            "invalid address '{}', expected an URL like 'http://server:9123' or \
+                             the path of a socket",
+                            address
+                        ),
+                    ))
+                }
+            }
+        };
+
+        Ok(Remote {
+            id,
+            widgets: Vec::new(),
+            shared_config,
+            update_interval: block_config.interval,
+            address,
+            token: block_config.token,
+            blocks: block_config.blocks,
+        })
+    }
+}
+
+impl Block for Remote {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let state = self.fetch()?;
+        let remote_widgets = select(&state, &self.blocks);
+
+        let (id, shared_config) = (self.id, &self.shared_config);
+        self.widgets.truncate(remote_widgets.len());
+        while self.widgets.len() < remote_widgets.len() {
+            let instance = self.widgets.len();
+            self.widgets
+                .push(TextWidget::new(id, instance, shared_config.clone()));
+        }
+        for (widget, remote) in self.widgets.iter_mut().zip(remote_widgets) {
+            // The spacing of this bar instead of the one of the other
+            widget.set_texts((
+                remote.full_text.trim().to_string(),
+                remote
+                    .short_text
+                    .as_deref()
+                    .map(|short| short.trim().to_string()),
+            ));
+            widget.set_state(remote.state);
+        }
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        self.widgets
+            .iter()
+            .map(|widget| widget as &dyn I3BarWidget)
+            .collect()
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selection() {
+        let state: RemoteState = serde_json::from_value(serde_json::json!({
+            "time": "2026-10-14T12:00:00.000+02:00",
+            "blocks": [
+                {
+                    "position": 0,
+                    "block": "cpu",
+                    "name": null,
+                    "hidden": false,
+                    "widgets": [{ "full_text": " 12% ", "short_text": null, "state": "Idle" }],
+                },
+                {
+                    "position": 1,
+                    "block": "disk_space",
+                    "name": "root",
+                    "hidden": false,
+                    "widgets": [{ "full_text": " 91% ", "short_text": null, "state": "Warning" }],
+                },
+                {
+                    "position": 2,
+                    "block": "net",
+                    "name": null,
+                    "hidden": true,
+                    "widgets": [{ "full_text": " eth0 ", "short_text": null, "state": "Idle" }],
+                },
+            ],
+        }))
+        .unwrap();
+        let texts = |blocks: &[&str]| {
+            let blocks: Vec<String> = blocks.iter().map(|b| b.to_string()).collect();
+            select(&state, &blocks)
+                .iter()
+                .map(|widget| widget.full_text.trim().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(texts(&[]), ["12%", "91%"]);
+        assert_eq!(texts(&["root", "0"]), ["91%", "12%"]);
+        assert_eq!(texts(&["disk_space"]), ["91%"]);
+        assert!(texts(&["net", "memory"]).is_empty());
+    }
+}
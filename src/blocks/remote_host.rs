@@ -0,0 +1,252 @@
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::scheduler::Task;
+use crate::subprocess::command;
+use crate::util::shell_quote;
+use crate::widgets::text::TextWidget;
+use crate::widgets::I3BarWidget;
+
+/// Separates the files the remote host prints
+const SEPARATOR: &str = "--i3status-rs--";
+
+pub struct RemoteHost {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    update_interval: Duration,
+    host: String,
+    disk: String,
+    ssh_options: Vec<String>,
+    control_path: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RemoteHostConfig {
+    /// The host for `ssh`, e.g. `user@server` or a host of `~/.ssh/config`
+    pub host: String,
+
+    /// The file system of which the use is shown
+    #[serde(default = "RemoteHostConfig::default_disk")]
+    pub disk: String,
+
+    /// Further options of `ssh`, e.g. `["-p", "2222"]`
+    #[serde(default)]
+    pub ssh_options: Vec<String>,
+
+    #[serde(
+        default = "RemoteHostConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    #[serde(default)]
+    pub format: FormatTemplate,
+}
+
+impl RemoteHostConfig {
+    fn default_disk() -> String {
+        "/".to_string()
+    }
+
+    fn default_interval() -> Duration {
+        Duration::from_secs(10)
+    }
+}
+
+impl ConfigBlock for RemoteHost {
+    type Config = RemoteHostConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let runtime_dir = std::env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| "/tmp".to_string());
+        Ok(RemoteHost {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("server")?,
+            format: block_config
+                .format
+                .with_default("{host} {load1} {mem_used_percents} {disk_used_percents}")?,
+            update_interval: block_config.interval,
+            host: block_config.host,
+            disk: block_config.disk,
+            ssh_options: block_config.ssh_options,
+            // `%C` is a hash of the host, the port and the user
+            control_path: format!("{}/i3status-rs-ssh-%C", runtime_dir),
+        })
+    }
+}
+
+/// What the remote host reported
+#[derive(Debug, PartialEq)]
+struct Report {
+    load: [f64; 3],
+    /// In bytes
+    mem_total: f64,
+    mem_used: f64,
+    disk_total: f64,
+    disk_used: f64,
+}
+
+/// Parses the output of the command of `RemoteHost::query`
+fn parse_report(output: &str) -> Option<Report> {
+    let mut parts = output.split(SEPARATOR);
+    let (loadavg, meminfo, df) = (parts.next()?, parts.next()?, parts.next()?);
+
+    let mut load = [0.0; 3];
+    let mut fields = loadavg.split_whitespace();
+    for average in &mut load {
+        *average = fields.next()?.parse().ok()?;
+    }
+
+    let meminfo_kb = |key: &str| {
+        meminfo.lines().find_map(|line| {
+            let value = line.strip_prefix(key)?.strip_prefix(':')?;
+            value.split_whitespace().next()?.parse::<f64>().ok()
+        })
+    };
+    let mem_total = meminfo_kb("MemTotal")?;
+    let mem_available = meminfo_kb("MemAvailable")?;
+
+    // After `Filesystem 1024-blocks Used Available Capacity Mounted on`, the name of the file
+    // system can be on a line of its own
+    let mut df_fields = df
+        .trim()
+        .lines()
+        .skip(1)
+        .flat_map(str::split_whitespace)
+        .skip(1);
+    let disk_total: f64 = df_fields.next()?.parse().ok()?;
+    let disk_used: f64 = df_fields.next()?.parse().ok()?;
+
+    Some(Report {
+        load,
+        mem_total: mem_total * 1024.0,
+        mem_used: (mem_total - mem_available) * 1024.0,
+        disk_total: disk_total * 1024.0,
+        disk_used: disk_used * 1024.0,
+    })
+}
+
+impl RemoteHost {
+    fn query(&self) -> Result<Report> {
+        let script = format!(
+            "cat /proc/loadavg; echo {separator}; cat /proc/meminfo; echo {separator}; df -Pk {disk}",
+            separator = SEPARATOR,
+            disk = shell_quote(&self.disk),
+        );
+        // The connection is kept open between the updates, so they don't log in every time
+        let output = command("ssh")
+            .args([
+                "-o",
+                "BatchMode=yes",
+                "-o",
+                "ConnectTimeout=5",
+                "-o",
+                "ServerAliveInterval=15",
+                "-o",
+                "ControlMaster=auto",
+                "-o",
+                "ControlPersist=600",
+                "-o",
+            ])
+            .arg(format!("ControlPath={}", self.control_path))
+            .args(&self.ssh_options)
+            .arg(&self.host)
+            .arg(script)
+            .output()
+            .block_error("remote_host", "failed to run 'ssh'")?;
+        if !output.status.success() {
+            return Err(BlockError(
+                "remote_host".to_string(),
+                format!(
+                    "{}: {}",
+                    self.host,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+            ));
+        }
+        parse_report(&String::from_utf8_lossy(&output.stdout)).block_error(
+            "remote_host",
+            &format!("unexpected output of '{}'", self.host),
+        )
+    }
+}
+
+impl Block for RemoteHost {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let report = self.query()?;
+        let percents = |used: f64, total: f64| match total {
+            total if total > 0.0 => used / total * 100.0,
+            _ => 0.0,
+        };
+        let values = map!(
+            "host" => Value::from_string(self.host.clone()),
+            "load1" => Value::from_float(report.load[0]),
+            "load5" => Value::from_float(report.load[1]),
+            "load15" => Value::from_float(report.load[2]),
+            "mem_total" => Value::from_float(report.mem_total).bytes(),
+            "mem_used" => Value::from_float(report.mem_used).bytes(),
+            "mem_used_percents" => Value::from_float(percents(report.mem_used, report.mem_total)).percents(),
+            "disk_total" => Value::from_float(report.disk_total).bytes(),
+            "disk_used" => Value::from_float(report.disk_used).bytes(),
+            "disk_used_percents" => Value::from_float(percents(report.disk_used, report.disk_total)).percents(),
+        );
+        self.text.set_format(&self.format, &values)?;
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report() {
+        let output = format!(
+            "0.52 0.58 0.59 1/467 12345
+{separator}
+MemTotal:        8000000 kB
+MemFree:         1000000 kB
+MemAvailable:    6000000 kB
+{separator}
+Filesystem     1024-blocks     Used Available Capacity Mounted on
+/dev/mapper/volume-with-a-long-name
+                 100000000 25000000  75000000      25% /
+",
+            separator = SEPARATOR
+        );
+        assert_eq!(
+            parse_report(&output),
+            Some(Report {
+                load: [0.52, 0.58, 0.59],
+                mem_total: 8000000.0 * 1024.0,
+                mem_used: 2000000.0 * 1024.0,
+                disk_total: 100000000.0 * 1024.0,
+                disk_used: 25000000.0 * 1024.0,
+            })
+        );
+        assert_eq!(parse_report("ssh: banner"), None);
+    }
+}
@@ -0,0 +1,260 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::config::{LogicalDirection, Scrolling};
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+pub struct Rig {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    update_interval: Duration,
+    host: String,
+    port: u16,
+    steps: Vec<u64>,
+    /// The index of the current tuning step in `steps`
+    step: usize,
+    scrolling: Scrolling,
+    /// The frequency of the last update, in Hz
+    frequency: Option<u64>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct RigConfig {
+    /// Host of rigctld
+    pub host: String,
+
+    pub port: u16,
+
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    pub format: FormatTemplate,
+
+    /// Tuning steps in Hz. Scrolling tunes by the current step, a right click switches to the next
+    /// one.
+    pub steps: Vec<u64>,
+}
+
+impl Default for RigConfig {
+    fn default() -> Self {
+        Self {
+            host: "localhost".to_string(),
+            port: 4532,
+            interval: Duration::from_secs(1),
+            format: FormatTemplate::default(),
+            steps: vec![1000],
+        }
+    }
+}
+
+/// The answer of rigctld to a command, without the `RPRT 0` of commands that set something.
+/// Commands that fail are answered with `RPRT` and a negative error code of Hamlib.
+fn answer(lines: Vec<String>) -> std::result::Result<Vec<String>, String> {
+    match lines.last().and_then(|line| line.strip_prefix("RPRT ")) {
+        Some("0") => Ok(lines[..lines.len() - 1].to_vec()),
+        Some(code) => Err(format!("rigctld failed with error {}", code)),
+        None => Ok(lines),
+    }
+}
+
+/// The frequency in Hz and the mode and passband of rigctld's answers to `f` and `m`
+fn parse_status(frequency: &[String], mode: &[String]) -> Option<(u64, String, Option<u64>)> {
+    // Some versions of Hamlib answer with decimals
+    let frequency = frequency.first()?.trim().parse::<f64>().ok()? as u64;
+    let passband = mode
+        .get(1)
+        .and_then(|passband| passband.trim().parse().ok());
+    Some((frequency, mode.first()?.trim().to_string(), passband))
+}
+
+impl Rig {
+    /// Sends `command` to rigctld and reads the `lines` lines of the answer
+    fn command(&self, command: &str, lines: usize) -> Result<Vec<String>> {
+        let address = (self.host.as_str(), self.port)
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addresses| addresses.next())
+            .block_error("rig", &format!("failed to resolve '{}'", self.host))?;
+        let stream = TcpStream::connect_timeout(&address, TIMEOUT).block_error(
+            "rig",
+            &format!("failed to connect to {}:{}", self.host, self.port),
+        )?;
+        stream
+            .set_read_timeout(Some(TIMEOUT))
+            .block_error("rig", "failed to set a timeout")?;
+        (&stream)
+            .write_all(format!("{}\n", command).as_bytes())
+            .block_error("rig", "failed to send a command to rigctld")?;
+        let mut answer_lines = Vec::new();
+        for line in BufReader::new(&stream).lines().take(lines) {
+            let line = line.block_error("rig", "failed to read the answer of rigctld")?;
+            // A failed command is answered with only the error code
+            let failed = line.starts_with("RPRT ");
+            answer_lines.push(line);
+            if failed {
+                break;
+            }
+        }
+        answer(answer_lines).map_err(|e| BlockError("rig".to_string(), e))
+    }
+
+    fn tune(&mut self, steps: i64) -> Result<()> {
+        let frequency = match self.frequency {
+            Some(frequency) => frequency,
+            None => return Ok(()),
+        };
+        let step = self.steps[self.step] as i64;
+        let frequency = (frequency as i64 + steps * step).max(0);
+        self.command(&format!("F {}", frequency), 1)?;
+        self.update()?;
+        Ok(())
+    }
+
+    fn next_step(&mut self) -> Result<()> {
+        self.step = (self.step + 1) % self.steps.len();
+        self.update()?;
+        Ok(())
+    }
+}
+
+impl ConfigBlock for Rig {
+    type Config = RigConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        if block_config.steps.is_empty() {
+            return Err(BlockError(
+                "rig".to_string(),
+                "'steps' needs at least one step".to_string(),
+            ));
+        }
+        Ok(Rig {
+            id,
+            scrolling: shared_config.scrolling,
+            text: TextWidget::new(id, 0, shared_config).with_icon("rig")?,
+            format: block_config.format.with_default("{frequency_mhz} {mode}")?,
+            update_interval: block_config.interval,
+            host: block_config.host,
+            port: block_config.port,
+            steps: block_config.steps,
+            step: 0,
+            frequency: None,
+        })
+    }
+}
+
+impl Block for Rig {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let status = self
+            .command("f", 1)
+            .and_then(|frequency| Ok((frequency, self.command("m", 2)?)));
+        let (frequency, mode) = match status {
+            Ok(status) => status,
+            Err(e) => {
+                self.frequency = None;
+                return Err(e);
+            }
+        };
+        let (frequency, mode, passband) = parse_status(&frequency, &mode)
+            .block_error("rig", "failed to parse the answer of rigctld")?;
+        self.frequency = Some(frequency);
+
+        let step = self.steps[self.step];
+        let values = map!(
+            "frequency" => Value::from_float(frequency as f64).hertz(),
+            "frequency_mhz" => Value::from_string(format!("{:.6}", frequency as f64 / 1e6)),
+            "mode" => Value::from_string(mode),
+            "passband" => match passband {
+                Some(passband) => Value::from_integer(passband as i64).hertz(),
+                None => Value::from_string(String::new()),
+            },
+            "step" => Value::from_integer(step as i64).hertz(),
+        );
+        self.text.set_state(State::Idle);
+        self.text.set_format(&self.format, &values)?;
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        match event.button {
+            MouseButton::Right => self.next_step()?,
+            _ => {
+                if let Some(direction) = self.scrolling.to_logical_direction(event.button) {
+                    let steps = event.scale_step(1.0) as i64;
+                    match direction {
+                        LogicalDirection::Up => self.tune(steps)?,
+                        LogicalDirection::Down => self.tune(-steps)?,
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn action(&mut self, action: &str) -> Result<bool> {
+        match action {
+            "up" => self.tune(1)?,
+            "down" => self.tune(-1)?,
+            "next_step" => self.next_step()?,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|line| line.to_string()).collect()
+    }
+
+    #[test]
+    fn answers() {
+        assert_eq!(answer(lines(&["RPRT 0"])), Ok(vec![]));
+        assert!(answer(lines(&["RPRT -11"])).is_err());
+        assert_eq!(
+            parse_status(
+                &answer(lines(&["14074000"])).unwrap(),
+                &answer(lines(&["USB", "3000"])).unwrap()
+            ),
+            Some((14_074_000, "USB".to_string(), Some(3000)))
+        );
+        assert_eq!(
+            parse_status(&lines(&["7074000.000000"]), &lines(&["PKTUSB"])),
+            Some((7_074_000, "PKTUSB".to_string(), None))
+        );
+        assert_eq!(parse_status(&lines(&["-"]), &lines(&["USB"])), None);
+    }
+}
@@ -94,7 +94,7 @@ impl Block for Rofication {
                     "num" => Value::from_string(status.num.to_string()),
                 );
 
-                self.text.set_texts(self.format.render(&values)?);
+                self.text.set_format(&self.format, &values)?;
                 if status.crit > 0 {
                     self.text.set_state(State::Critical)
                 } else if status.num > 0 {
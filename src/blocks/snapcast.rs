@@ -0,0 +1,331 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+use serde_json::json;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::{LogicalDirection, Scrolling, SharedConfig};
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+pub struct Snapcast {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    update_interval: Duration,
+    host: String,
+    port: u16,
+    /// The name or id of the client of this room
+    client: String,
+    step_width: u32,
+    scrolling: Scrolling,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct SnapcastConfig {
+    /// Host of the Snapcast server
+    pub host: String,
+
+    /// Port of the JSON-RPC interface
+    pub port: u16,
+
+    /// The name, host name or id of the client, the host name of this machine by default
+    pub client: Option<String>,
+
+    /// Volume in percent that a wheel event changes
+    pub step_width: u32,
+
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    pub format: FormatTemplate,
+}
+
+impl Default for SnapcastConfig {
+    fn default() -> Self {
+        Self {
+            host: "localhost".to_string(),
+            port: 1705,
+            client: None,
+            step_width: 5,
+            interval: Duration::from_secs(5),
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+impl ConfigBlock for Snapcast {
+    type Config = SnapcastConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let client = match block_config.client {
+            Some(client) => client,
+            None => {
+                let mut buffer = [0; 256];
+                nix::unistd::gethostname(&mut buffer)
+                    .block_error("snapcast", "failed to get the host name")?
+                    .to_string_lossy()
+                    .into_owned()
+            }
+        };
+        Ok(Snapcast {
+            id,
+            scrolling: shared_config.scrolling,
+            text: TextWidget::new(id, 0, shared_config).with_icon("volume_full")?,
+            format: block_config.format.with_default("{stream} {volume}")?,
+            update_interval: block_config.interval,
+            host: block_config.host,
+            port: block_config.port,
+            client,
+            step_width: block_config.step_width,
+        })
+    }
+}
+
+/// The client of this room and the group and stream it plays
+struct Room {
+    client_id: String,
+    client: String,
+    connected: bool,
+    volume: u64,
+    muted: bool,
+    group: String,
+    stream: String,
+    /// `playing` or `idle`
+    status: String,
+}
+
+/// Finds the client `client` in the answer of `Server.GetStatus`
+fn find_room(status: &serde_json::Value, client: &str) -> Option<Room> {
+    let server = &status["server"];
+    let (group, client) = server["groups"].as_array()?.iter().find_map(|group| {
+        let found = group["clients"].as_array()?.iter().find(|candidate| {
+            candidate["id"] == client
+                || candidate["config"]["name"] == client
+                || candidate["host"]["name"] == client
+        })?;
+        Some((group, found))
+    })?;
+    let stream_id = group["stream_id"].as_str()?;
+    let stream = server["streams"]
+        .as_array()?
+        .iter()
+        .find(|stream| stream["id"] == stream_id);
+    // Clients without a name of their own are shown with their host name
+    let name = match client["config"]["name"].as_str() {
+        Some(name) if !name.is_empty() => name,
+        _ => client["host"]["name"].as_str().unwrap_or_default(),
+    };
+    let volume = &client["config"]["volume"];
+    Some(Room {
+        client_id: client["id"].as_str()?.to_string(),
+        client: name.to_string(),
+        connected: client["connected"].as_bool().unwrap_or(true),
+        volume: volume["percent"].as_u64().unwrap_or(0),
+        muted: volume["muted"].as_bool().unwrap_or(false),
+        group: group["name"].as_str().unwrap_or_default().to_string(),
+        stream: stream_id.to_string(),
+        status: stream
+            .and_then(|stream| stream["status"].as_str())
+            .unwrap_or("unknown")
+            .to_string(),
+    })
+}
+
+impl Snapcast {
+    /// Calls a method of the JSON-RPC interface, on a connection of its own, and returns the
+    /// result
+    fn call(&self, method: &str, params: serde_json::Value) -> Result<serde_json::Value> {
+        let address = (self.host.as_str(), self.port)
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addresses| addresses.next())
+            .block_error("snapcast", &format!("failed to resolve '{}'", self.host))?;
+        let stream = TcpStream::connect_timeout(&address, TIMEOUT).block_error(
+            "snapcast",
+            &format!("failed to connect to {}:{}", self.host, self.port),
+        )?;
+        stream
+            .set_read_timeout(Some(TIMEOUT))
+            .block_error("snapcast", "failed to set a timeout")?;
+        let request = json!({ "id": 1, "jsonrpc": "2.0", "method": method, "params": params });
+        (&stream)
+            .write_all(format!("{}\r\n", request).as_bytes())
+            .block_error("snapcast", "failed to send a request")?;
+
+        // The server sends notifications about other changes in between
+        for line in BufReader::new(&stream).lines() {
+            let line = line.block_error("snapcast", "failed to read the answer")?;
+            let answer: serde_json::Value = match serde_json::from_str(&line) {
+                Ok(answer) => answer,
+                Err(_) => continue,
+            };
+            if answer["id"] != 1 {
+                continue;
+            }
+            if let Some(message) = answer["error"]["message"].as_str() {
+                return Err(BlockError(
+                    "snapcast".to_string(),
+                    format!("{}: {}", method, message),
+                ));
+            }
+            return Ok(answer["result"].clone());
+        }
+        Err(BlockError(
+            "snapcast".to_string(),
+            "the server closed the connection".to_string(),
+        ))
+    }
+
+    fn room(&self) -> Result<Room> {
+        let status = self.call("Server.GetStatus", json!({}))?;
+        find_room(&status, &self.client).block_error(
+            "snapcast",
+            &format!("the server has no client '{}'", self.client),
+        )
+    }
+
+    fn set_volume(&self, room: &Room, volume: u64, muted: bool) -> Result<()> {
+        self.call(
+            "Client.SetVolume",
+            json!({
+                "id": room.client_id,
+                "volume": { "percent": volume.min(100), "muted": muted },
+            }),
+        )?;
+        Ok(())
+    }
+
+    fn toggle_mute(&mut self) -> Result<()> {
+        let room = self.room()?;
+        self.set_volume(&room, room.volume, !room.muted)?;
+        self.update()?;
+        Ok(())
+    }
+
+    fn change_volume(&mut self, step: i64) -> Result<()> {
+        let room = self.room()?;
+        let volume = (room.volume as i64 + step).max(0) as u64;
+        self.set_volume(&room, volume, room.muted)?;
+        self.update()?;
+        Ok(())
+    }
+}
+
+impl Block for Snapcast {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let room = self.room()?;
+        let values = map!(
+            "client" => Value::from_string(room.client.clone()),
+            "group" => Value::from_string(room.group.clone()),
+            "stream" => Value::from_string(room.stream.clone()),
+            "status" => Value::from_string(room.status.clone()),
+            "volume" => Value::from_integer(room.volume as i64).percents(),
+        );
+        self.text.set_icon(match (room.muted, room.volume) {
+            (true, _) => "volume_muted",
+            (false, 0..=20) => "volume_empty",
+            (false, 21..=70) => "volume_half",
+            _ => "volume_full",
+        })?;
+        self.text.set_state(if room.connected {
+            State::Idle
+        } else {
+            State::Critical
+        });
+        self.text.set_format(&self.format, &values)?;
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        match event.button {
+            MouseButton::Right => self.toggle_mute(),
+            button => {
+                let step = event.scale_step(self.step_width as f64) as i64;
+                match self.scrolling.to_logical_direction(button) {
+                    Some(LogicalDirection::Up) => self.change_volume(step),
+                    Some(LogicalDirection::Down) => self.change_volume(-step),
+                    None => {
+                        crate::click::set_unhandled();
+                        Ok(())
+                    }
+                }
+            }
+        }
+    }
+
+    fn action(&mut self, action: &str) -> Result<bool> {
+        match action {
+            "toggle_mute" => self.toggle_mute()?,
+            "up" => self.change_volume(self.step_width as i64)?,
+            "down" => self.change_volume(-(self.step_width as i64))?,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rooms() {
+        let status = json!({ "server": {
+            "groups": [
+                { "id": "g1", "name": "", "stream_id": "Spotify", "clients": [
+                    { "id": "00:11:22:33:44:55", "connected": true,
+                      "config": { "name": "", "volume": { "muted": false, "percent": 48 } },
+                      "host": { "name": "kitchen" } },
+                ]},
+                { "id": "g2", "name": "Upstairs", "stream_id": "Mopidy", "clients": [
+                    { "id": "66:77:88:99:aa:bb", "connected": false,
+                      "config": { "name": "Bedroom", "volume": { "muted": true, "percent": 30 } },
+                      "host": { "name": "pi" } },
+                ]},
+            ],
+            "streams": [
+                { "id": "Mopidy", "status": "playing" },
+                { "id": "Spotify", "status": "idle" },
+            ],
+        }});
+        let kitchen = find_room(&status, "kitchen").unwrap();
+        assert_eq!(kitchen.client, "kitchen");
+        assert_eq!(kitchen.stream, "Spotify");
+        assert_eq!(kitchen.status, "idle");
+        assert_eq!(kitchen.volume, 48);
+        let bedroom = find_room(&status, "Bedroom").unwrap();
+        assert_eq!(bedroom.client_id, "66:77:88:99:aa:bb");
+        assert_eq!(bedroom.group, "Upstairs");
+        assert_eq!(bedroom.status, "playing");
+        assert!(bedroom.muted && !bedroom.connected);
+        assert!(find_room(&status, "pi").is_some());
+        assert!(find_room(&status, "garage").is_none());
+    }
+}
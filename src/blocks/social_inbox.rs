@@ -0,0 +1,507 @@
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::cache::{self, Cached};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::http;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::services::Service;
+use crate::subprocess::spawn_child_async;
+use crate::widgets::{text::TextWidget, I3BarWidget, State};
+
+const REDDIT_CLIENT_ID_ENV: &str = "I3RS_REDDIT_CLIENT_ID";
+const REDDIT_CLIENT_SECRET_ENV: &str = "I3RS_REDDIT_CLIENT_SECRET";
+const REDDIT_REFRESH_TOKEN_ENV: &str = "I3RS_REDDIT_REFRESH_TOKEN";
+const BLOCK_NAME: &str = "social_inbox";
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "name", rename_all = "lowercase")]
+pub enum SocialService {
+    /// Hacker News doesn't have an inbox, so replies to the most recent submissions and
+    /// comments of the user are counted instead.
+    HackerNews {
+        user: String,
+        /// How many of the most recent items of the user are checked for replies
+        #[serde(default = "SocialService::default_check_items")]
+        check_items: usize,
+    },
+    Reddit {
+        #[serde(default = "SocialService::getenv_reddit_client_id")]
+        client_id: Option<String>,
+        #[serde(default = "SocialService::getenv_reddit_client_secret")]
+        client_secret: Option<String>,
+        #[serde(default = "SocialService::getenv_reddit_refresh_token")]
+        refresh_token: Option<String>,
+    },
+}
+
+impl SocialService {
+    fn default_check_items() -> usize {
+        10
+    }
+    fn getenv_reddit_client_id() -> Option<String> {
+        env::var(REDDIT_CLIENT_ID_ENV).ok()
+    }
+    fn getenv_reddit_client_secret() -> Option<String> {
+        env::var(REDDIT_CLIENT_SECRET_ENV).ok()
+    }
+    fn getenv_reddit_refresh_token() -> Option<String> {
+        env::var(REDDIT_REFRESH_TOKEN_ENV).ok()
+    }
+}
+
+pub struct SocialInbox {
+    id: usize,
+    text: TextWidget,
+    update_interval: Duration,
+    format: FormatTemplate,
+    service: SocialService,
+    hide_if_zero: bool,
+    open_command: String,
+    karma: i64,
+    unread: i64,
+    /// Unix timestamp of the last time the replies were marked as read (Hacker News only), which
+    /// is kept in the cache across restarts
+    last_read: i64,
+    /// When the replies to the recent items of the user were written (Hacker News only)
+    replies: Vec<i64>,
+    hackernews: Option<HackerNewsWorker>,
+    /// A cached Reddit access token and its expiration time
+    access_token: Option<(String, Instant)>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SocialInboxConfig {
+    #[serde(
+        default = "SocialInboxConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    #[serde(default)]
+    pub format: FormatTemplate,
+
+    pub service: SocialService,
+
+    /// Hide the block if there are no unread replies
+    #[serde(default)]
+    pub hide_if_zero: bool,
+
+    /// Command used to open the inbox
+    #[serde(default = "SocialInboxConfig::default_open_command")]
+    pub open_command: String,
+}
+
+impl SocialInboxConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(300)
+    }
+
+    fn default_open_command() -> String {
+        "xdg-open".to_string()
+    }
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+fn missing_option(option: &str, env: &str) -> Error {
+    ConfigurationError(
+        BLOCK_NAME.to_string(),
+        format!(
+            "Missing member 'service.{}'. Add the member or configure with the environment variable {}",
+            option, env
+        ),
+    )
+}
+
+fn malformed_json_error() -> Error {
+    BlockError(BLOCK_NAME.to_string(), "Malformed JSON.".to_string())
+}
+
+/// Fetches the karma and the replies from Hacker News on its own thread, as that takes a request
+/// for each of the recent items of the user. The thread requests an update of the block once it's
+/// done.
+struct HackerNewsWorker {
+    requests: Sender<()>,
+    fetched: Arc<Mutex<Option<Result<HackerNewsReplies>>>>,
+}
+
+/// The karma of the user and when the replies to their recent items were written
+type HackerNewsReplies = (i64, Vec<i64>);
+
+impl HackerNewsWorker {
+    fn spawn(id: usize, user: String, check_items: usize, done: Sender<Task>) -> Result<Self> {
+        let (requests, received) = crossbeam_channel::unbounded::<()>();
+        let fetched = Arc::new(Mutex::new(None));
+        let result = fetched.clone();
+        thread::Builder::new()
+            .name("social_inbox".into())
+            .spawn(move || {
+                // Ends once the block is gone, e.g. after a reload
+                for () in received {
+                    let replies = fetch_hackernews(&user, check_items);
+                    *result.lock().unwrap() = Some(replies);
+                    if done
+                        .send(Task {
+                            id,
+                            update_time: Instant::now(),
+                        })
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            })
+            .internal_error(BLOCK_NAME, "failed to start the thread")?;
+        Ok(Self { requests, fetched })
+    }
+}
+
+/// Fetches the replies to the `check_items` most recent items of `user`
+fn fetch_hackernews(user: &str, check_items: usize) -> Result<HackerNewsReplies> {
+    // Refer to https://github.com/HackerNews/API
+    let profile = http::http_get_json(
+        &format!(
+            "https://hacker-news.firebaseio.com/v0/user/{}.json",
+            http::encode(user)
+        ),
+        Some(Duration::from_secs(5)),
+        vec![],
+    )?
+    .content;
+    if profile.is_null() {
+        return Err(ConfigurationError(
+            BLOCK_NAME.to_string(),
+            format!("Hacker News user '{}' not found", user),
+        ));
+    }
+
+    let karma = profile
+        .get("karma")
+        .and_then(|k| k.as_i64())
+        .ok_or_else(malformed_json_error)?;
+
+    let submitted = profile
+        .get("submitted")
+        .and_then(|s| s.as_array())
+        .map(|s| s.iter().filter_map(|id| id.as_u64()).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    // The Algolia API returns the whole comment tree of an item in one request
+    let mut replies = Vec::new();
+    for item in submitted.iter().take(check_items) {
+        let tree = http::http_get_json(
+            &format!("https://hn.algolia.com/api/v1/items/{}", item),
+            Some(Duration::from_secs(5)),
+            vec![],
+        )?
+        .content;
+        if let Some(children) = tree.get("children").and_then(|c| c.as_array()) {
+            replies.extend(
+                children
+                    .iter()
+                    .map(|c| c.get("created_at_i").and_then(|t| t.as_i64()).unwrap_or(0)),
+            );
+        }
+    }
+
+    Ok((karma, replies))
+}
+
+fn last_read_key(user: &str) -> String {
+    format!("{}-hackernews-{}", BLOCK_NAME, user)
+}
+
+/// When the replies to `user` were last marked as read, or now if they never were
+fn load_last_read(user: &str) -> i64 {
+    let key = last_read_key(user);
+    match cache::load(&key) {
+        Some(last_read) => last_read.data,
+        None => {
+            let last_read = Cached::new(unix_now());
+            cache::store(&key, &last_read);
+            last_read.data
+        }
+    }
+}
+
+impl SocialInbox {
+    fn reddit_access_token(
+        &mut self,
+        client_id: &str,
+        client_secret: &str,
+        refresh_token: &str,
+    ) -> Result<String> {
+        if let Some((token, expires)) = &self.access_token {
+            if *expires > Instant::now() {
+                return Ok(token.clone());
+            }
+        }
+
+        // Refer to https://github.com/reddit-archive/reddit/wiki/OAuth2
+        let output = http::http_post_form_json(
+            "https://www.reddit.com/api/v1/access_token",
+            Some(Duration::from_secs(5)),
+            Some((client_id, client_secret)),
+            &format!(
+                "grant_type=refresh_token&refresh_token={}",
+                http::encode(refresh_token)
+            ),
+        )?;
+        if let Some(error) = output.content.get("error") {
+            return Err(BlockError(
+                BLOCK_NAME.to_string(),
+                format!("Reddit authentication failed: {}", error),
+            ));
+        }
+
+        let token = output
+            .content
+            .get("access_token")
+            .and_then(|t| t.as_str())
+            .ok_or_else(malformed_json_error)?
+            .to_string();
+        let expires_in = output
+            .content
+            .get("expires_in")
+            .and_then(|e| e.as_u64())
+            .unwrap_or(3600);
+        // Renew the token a bit early to avoid using an expired one
+        self.access_token = Some((
+            token.clone(),
+            Instant::now() + Duration::from_secs(expires_in.saturating_sub(60)),
+        ));
+        Ok(token)
+    }
+
+    fn update_reddit(
+        &mut self,
+        client_id: &str,
+        client_secret: &str,
+        refresh_token: &str,
+    ) -> Result<()> {
+        let token = self.reddit_access_token(client_id, client_secret, refresh_token)?;
+        let auth = format!("bearer {}", token);
+        let output = http::http_get_json(
+            "https://oauth.reddit.com/api/v1/me",
+            Some(Duration::from_secs(5)),
+            vec![("Authorization", &auth)],
+        )?;
+        if output.code == 401 {
+            // The token was revoked, get a new one at the next update
+            self.access_token = None;
+        }
+        if output.code != 200 {
+            return Err(BlockError(
+                BLOCK_NAME.to_string(),
+                format!("Reddit API returned code {}", output.code),
+            ));
+        }
+
+        let me = output.content;
+        self.karma = me
+            .get("total_karma")
+            .and_then(|k| k.as_i64())
+            .or_else(|| Some(me.get("link_karma")?.as_i64()? + me.get("comment_karma")?.as_i64()?))
+            .ok_or_else(malformed_json_error)?;
+        self.unread = me
+            .get("inbox_count")
+            .and_then(|c| c.as_i64())
+            .ok_or_else(malformed_json_error)?;
+
+        Ok(())
+    }
+
+    fn update_service(&mut self) -> Result<()> {
+        match self.service.clone() {
+            // Fetched by the thread of the worker, see `update`
+            SocialService::HackerNews { .. } => Ok(()),
+            SocialService::Reddit {
+                client_id,
+                client_secret,
+                refresh_token,
+            } => {
+                let client_id =
+                    client_id.ok_or_else(|| missing_option("client_id", REDDIT_CLIENT_ID_ENV))?;
+                let client_secret = client_secret
+                    .ok_or_else(|| missing_option("client_secret", REDDIT_CLIENT_SECRET_ENV))?;
+                let refresh_token = refresh_token
+                    .ok_or_else(|| missing_option("refresh_token", REDDIT_REFRESH_TOKEN_ENV))?;
+                self.update_reddit(&client_id, &client_secret, &refresh_token)
+            }
+        }
+    }
+
+    fn count_unread(&self) -> i64 {
+        self.replies
+            .iter()
+            .filter(|&&written| written > self.last_read)
+            .count() as i64
+    }
+
+    fn render(&mut self) -> Result<()> {
+        let values = map!(
+            "karma" => Value::from_integer(self.karma),
+            "unread" => Value::from_integer(self.unread),
+        );
+        self.text.set_format(&self.format, &values)?;
+        self.text.set_state(if self.unread > 0 {
+            State::Info
+        } else {
+            State::Idle
+        });
+        Ok(())
+    }
+
+    fn inbox_url(&self) -> String {
+        match &self.service {
+            SocialService::HackerNews { user, .. } => {
+                format!(
+                    "https://news.ycombinator.com/threads?id={}",
+                    http::encode(user)
+                )
+            }
+            SocialService::Reddit { .. } => "https://www.reddit.com/message/unread".to_string(),
+        }
+    }
+}
+
+impl ConfigBlock for SocialInbox {
+    type Config = SocialInboxConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let icon = match block_config.service {
+            SocialService::HackerNews { .. } => "hackernews",
+            SocialService::Reddit { .. } => "reddit",
+        };
+        let (last_read, hackernews) = match &block_config.service {
+            SocialService::HackerNews { user, check_items } => (
+                load_last_read(user),
+                Some(HackerNewsWorker::spawn(
+                    id,
+                    user.clone(),
+                    *check_items,
+                    tx_update_request,
+                )?),
+            ),
+            SocialService::Reddit { .. } => (unix_now(), None),
+        };
+        Ok(SocialInbox {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon(icon)?,
+            update_interval: block_config.interval,
+            format: block_config.format.with_default("{unread}")?,
+            service: block_config.service,
+            hide_if_zero: block_config.hide_if_zero,
+            open_command: block_config.open_command,
+            karma: 0,
+            unread: 0,
+            last_read,
+            replies: Vec::new(),
+            hackernews,
+            access_token: None,
+        })
+    }
+
+    fn default_depends_on() -> &'static [Service] {
+        &[Service::Network]
+    }
+}
+
+impl Block for SocialInbox {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let result = match &self.hackernews {
+            Some(worker) => {
+                let fetched = worker
+                    .fetched
+                    .lock()
+                    .block_error(BLOCK_NAME, "mutex poisoned")?
+                    .take();
+                match fetched {
+                    Some(Ok((karma, replies))) => {
+                        self.karma = karma;
+                        self.replies = replies;
+                        self.unread = self.count_unread();
+                        Ok(())
+                    }
+                    Some(Err(e)) => Err(e),
+                    None => {
+                        // The worker requests another update once it's done
+                        worker
+                            .requests
+                            .send(())
+                            .block_error(BLOCK_NAME, "the thread of the worker is gone")?;
+                        return Ok(Some(self.update_interval.into()));
+                    }
+                }
+            }
+            None => self.update_service(),
+        };
+        match result {
+            Ok(()) => self.render()?,
+            Err(ConfigurationError(_, msg)) => return Err(BlockError(BLOCK_NAME.to_string(), msg)),
+            Err(_) => {
+                // Network and API errors are usually temporary
+                self.text.set_text("×".to_string());
+                self.text.set_state(State::Warning);
+            }
+        }
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        if self.hide_if_zero && self.unread == 0 {
+            vec![]
+        } else {
+            vec![&self.text]
+        }
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        match event.button {
+            MouseButton::Left => {
+                spawn_child_async(&self.open_command, &[&self.inbox_url()])
+                    .block_error(BLOCK_NAME, "could not spawn open command")?;
+                // Reddit keeps track of read messages itself
+                if let SocialService::HackerNews { user, .. } = &self.service {
+                    self.last_read = unix_now();
+                    cache::store(&last_read_key(user), &Cached::new(self.last_read));
+                    self.unread = 0;
+                    self.render()?;
+                }
+            }
+            MouseButton::Right => {
+                self.update()?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
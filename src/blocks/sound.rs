@@ -23,7 +23,7 @@ use {
 use std::cmp::{max, min};
 use std::collections::BTreeMap;
 use std::io::Read;
-use std::process::{Command, Stdio};
+use std::process::Stdio;
 use std::thread;
 use std::time::{Duration, Instant};
 
@@ -36,9 +36,10 @@ use crate::config::{LogicalDirection, Scrolling};
 use crate::errors::*;
 use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_block::I3BarBlockMinWidth;
 use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
-use crate::subprocess::spawn_child_async;
+use crate::subprocess::{command, spawn_child_async};
 use crate::widgets::text::TextWidget;
 use crate::widgets::{I3BarWidget, State};
 
@@ -51,6 +52,8 @@ trait SoundDevice {
 
     fn get_info(&mut self) -> Result<()>;
     fn set_volume(&mut self, step: i32, max_vol: Option<u32>) -> Result<()>;
+    /// Sets the volume of all channels to `volume` percent
+    fn set_volume_to(&mut self, volume: u32, max_vol: Option<u32>) -> Result<()>;
     fn toggle(&mut self) -> Result<()>;
     fn monitor(&mut self, id: usize, tx_update_request: Sender<Task>) -> Result<()>;
 }
@@ -103,7 +106,7 @@ impl SoundDevice for AlsaSoundDevice {
         };
         args.extend(&["-D", &self.device, "get", &self.name]);
 
-        let output = Command::new("amixer")
+        let output = command("amixer")
             .args(&args)
             .output()
             .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_owned())
@@ -133,10 +136,14 @@ impl SoundDevice for AlsaSoundDevice {
 
     fn set_volume(&mut self, step: i32, max_vol: Option<u32>) -> Result<()> {
         let new_vol = max(0, self.volume as i32 + step) as u32;
+        self.set_volume_to(new_vol, max_vol)
+    }
+
+    fn set_volume_to(&mut self, volume: u32, max_vol: Option<u32>) -> Result<()> {
         let capped_volume = if let Some(vol_cap) = max_vol {
-            min(new_vol, vol_cap)
+            min(volume, vol_cap)
         } else {
-            new_vol
+            volume
         };
         let mut args = Vec::new();
         if self.natural_mapping {
@@ -145,7 +152,7 @@ impl SoundDevice for AlsaSoundDevice {
         let vol_str = &format!("{}%", capped_volume);
         args.extend(&["-D", &self.device, "set", &self.name, vol_str]);
 
-        Command::new("amixer")
+        command("amixer")
             .args(&args)
             .output()
             .block_error("sound", "failed to set volume")?;
@@ -162,7 +169,7 @@ impl SoundDevice for AlsaSoundDevice {
         };
         args.extend(&["-D", &self.device, "set", &self.name, "toggle"]);
 
-        Command::new("amixer")
+        command("amixer")
             .args(&args)
             .output()
             .block_error("sound", "failed to toggle mute")?;
@@ -173,14 +180,17 @@ impl SoundDevice for AlsaSoundDevice {
     }
 
     fn monitor(&mut self, id: usize, tx_update_request: Sender<Task>) -> Result<()> {
+        // Line-buffer to reduce noise. The command is created here to get the block's environment.
+        let mut monitor = command("stdbuf");
+        monitor
+            .args(&["-oL", "alsactl", "monitor"])
+            .stdout(Stdio::piped());
+
         // Monitor volume changes in a separate thread.
         thread::Builder::new()
             .name("sound_alsa".into())
             .spawn(move || {
-                // Line-buffer to reduce noise.
-                let mut monitor = Command::new("stdbuf")
-                    .args(&["-oL", "alsactl", "monitor"])
-                    .stdout(Stdio::piped())
+                let mut monitor = monitor
                     .spawn()
                     .expect("Failed to start alsactl monitor")
                     .stdout
@@ -695,6 +705,25 @@ impl SoundDevice for PulseAudioSoundDevice {
         Ok(())
     }
 
+    fn set_volume_to(&mut self, volume: u32, max_vol: Option<u32>) -> Result<()> {
+        let mut volumes = self.volume.block_error("sound", "volume unknown")?;
+
+        let capped = max_vol.map_or(volume, |vol_cap| min(volume, vol_cap));
+        let target = (capped as f32 * Volume::NORMAL.0 as f32 / 100.0).round() as u32;
+        for vol in volumes.get_mut().iter_mut() {
+            vol.0 = min(target, Volume::MAX.0);
+        }
+
+        self.volume(volumes);
+        PulseAudioClient::send(PulseAudioClientRequest::SetVolumeByName(
+            self.device_kind,
+            self.name(),
+            volumes,
+        ))?;
+
+        Ok(())
+    }
+
     fn toggle(&mut self) -> Result<()> {
         self.muted = !self.muted;
 
@@ -729,6 +758,7 @@ pub struct Sound {
     show_volume_when_muted: bool,
     mappings: Option<BTreeMap<String, String>>,
     max_vol: Option<u32>,
+    click_to_set: bool,
     scrolling: Scrolling,
 }
 
@@ -804,6 +834,12 @@ pub struct SoundConfig {
     pub mappings: Option<BTreeMap<String, String>>,
 
     pub max_vol: Option<u32>,
+
+    /// Left clicks set the volume according to their position on the widget
+    pub click_to_set: bool,
+
+    /// Width of the widget in pixels, so a volume is always at the same position
+    pub click_to_set_width: Option<usize>,
 }
 
 impl Default for SoundConfig {
@@ -820,6 +856,8 @@ impl Default for SoundConfig {
             show_volume_when_muted: false,
             mappings: None,
             max_vol: None,
+            click_to_set: false,
+            click_to_set_width: None,
         }
     }
 }
@@ -896,8 +934,15 @@ impl ConfigBlock for Sound {
             show_volume_when_muted: block_config.show_volume_when_muted,
             mappings: block_config.mappings,
             max_vol: block_config.max_vol,
+            click_to_set: block_config.click_to_set,
             scrolling: shared_config.scrolling,
-            text: TextWidget::new(id, 0, shared_config).with_icon("volume_empty")?,
+            text: TextWidget::new(id, 0, shared_config)
+                .with_icon("volume_empty")?
+                .with_min_width(
+                    block_config
+                        .click_to_set_width
+                        .map(I3BarBlockMinWidth::Pixels),
+                ),
         };
 
         sound.device.monitor(id, tx_update_request)?;
@@ -978,6 +1023,12 @@ impl Block for Sound {
     fn click(&mut self, e: &I3BarEvent) -> Result<()> {
         match e.button {
             MouseButton::Right => self.device.toggle()?,
+            MouseButton::Left if self.click_to_set && e.position.is_some() => {
+                let max = self.max_vol.unwrap_or(100) as f64;
+                if let Some(volume) = e.slider_value(0.0, max) {
+                    self.device.set_volume_to(volume as u32, self.max_vol)?;
+                }
+            }
             MouseButton::Left => {
                 if let Some(ref cmd) = self.on_click {
                     spawn_child_async("sh", &["-c", cmd])
@@ -986,13 +1037,10 @@ impl Block for Sound {
             }
             _ => {
                 use LogicalDirection::*;
+                let step_width = e.scale_step(self.step_width as f64) as i32;
                 match self.scrolling.to_logical_direction(e.button) {
-                    Some(Up) => self
-                        .device
-                        .set_volume(self.step_width as i32, self.max_vol)?,
-                    Some(Down) => self
-                        .device
-                        .set_volume(-(self.step_width as i32), self.max_vol)?,
+                    Some(Up) => self.device.set_volume(step_width, self.max_vol)?,
+                    Some(Down) => self.device.set_volume(-step_width, self.max_vol)?,
                     None => (),
                 }
             }
@@ -1001,6 +1049,21 @@ impl Block for Sound {
         Ok(())
     }
 
+    fn action(&mut self, action: &str) -> Result<bool> {
+        match action {
+            "toggle_mute" => self.device.toggle()?,
+            "up" => self
+                .device
+                .set_volume(self.step_width as i32, self.max_vol)?,
+            "down" => self
+                .device
+                .set_volume(-(self.step_width as i32), self.max_vol)?,
+            _ => return Ok(false),
+        }
+        self.update()?;
+        Ok(true)
+    }
+
     fn id(&self) -> usize {
         self.id
     }
@@ -0,0 +1,204 @@
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::subprocess::command;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+struct Sink {
+    name: String,
+    description: String,
+}
+
+pub struct SoundFilter {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    update_interval: Duration,
+    sink: String,
+    fallback: Option<String>,
+    /// The default sink before the filter was switched on
+    previous: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SoundFilterConfig {
+    /// The name of the sink of the filter
+    pub sink: String,
+
+    /// The sink that is used while the filter is off
+    #[serde(default)]
+    pub fallback: Option<String>,
+
+    /// Update interval in seconds
+    #[serde(
+        default = "SoundFilterConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    #[serde(default)]
+    pub format: FormatTemplate,
+}
+
+impl SoundFilterConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(5)
+    }
+}
+
+impl ConfigBlock for SoundFilter {
+    type Config = SoundFilterConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        Ok(SoundFilter {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("equalizer")?,
+            format: block_config.format.with_default("")?,
+            update_interval: block_config.interval,
+            sink: block_config.sink,
+            fallback: block_config.fallback,
+            previous: None,
+        })
+    }
+}
+
+fn pactl(args: &[&str]) -> Result<String> {
+    let output = command("pactl")
+        // The output is parsed
+        .env("LC_ALL", "C")
+        .args(args)
+        .output()
+        .block_error("sound_filter", "failed to run 'pactl'")?;
+    if !output.status.success() {
+        return Err(BlockError(
+            "sound_filter".to_string(),
+            format!(
+                "'pactl {}' failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn sinks() -> Result<Vec<Sink>> {
+    let mut sinks: Vec<Sink> = Vec::new();
+    for line in pactl(&["list", "sinks"])?.lines().map(str::trim) {
+        if let Some(name) = line.strip_prefix("Name: ") {
+            sinks.push(Sink {
+                name: name.to_string(),
+                description: name.to_string(),
+            });
+        } else if let (Some(description), Some(sink)) =
+            (line.strip_prefix("Description: "), sinks.last_mut())
+        {
+            sink.description = description.to_string();
+        }
+    }
+    Ok(sinks)
+}
+
+fn default_sink() -> Result<String> {
+    pactl(&["info"])?
+        .lines()
+        .find_map(|line| line.strip_prefix("Default Sink: "))
+        .map(String::from)
+        .block_error("sound_filter", "the sound server has no default sink")
+}
+
+impl SoundFilter {
+    /// Makes the sink of the filter the default sink, or switches back to the previous one.
+    /// Streams that were not moved to a sink by hand follow the default sink.
+    fn toggle(&mut self) -> Result<()> {
+        let sinks = sinks()?;
+        let default = default_sink()?;
+        let next = if default == self.sink {
+            let previous = self.fallback.as_ref().or(self.previous.as_ref());
+            match previous.filter(|&previous| sinks.iter().any(|sink| sink.name == *previous)) {
+                Some(previous) => previous.clone(),
+                None => sinks
+                    .iter()
+                    .map(|sink| &sink.name)
+                    .find(|&name| *name != self.sink)
+                    .block_error("sound_filter", "there is no other sink")?
+                    .clone(),
+            }
+        } else {
+            if !sinks.iter().any(|sink| sink.name == self.sink) {
+                return Err(BlockError(
+                    "sound_filter".to_string(),
+                    format!("there is no sink '{}'", self.sink),
+                ));
+            }
+            self.previous = Some(default);
+            self.sink.clone()
+        };
+        pactl(&["set-default-sink", &next])?;
+        self.update()?;
+        Ok(())
+    }
+}
+
+impl Block for SoundFilter {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let default = default_sink()?;
+        let on = default == self.sink;
+        let description = sinks()?
+            .into_iter()
+            .find(|sink| sink.name == default)
+            .map_or(default, |sink| sink.description);
+        let values = map!(
+            "state" => Value::from_string(if on { "on" } else { "off" }.to_string()),
+            "sink" => Value::from_string(description),
+        );
+        self.text
+            .set_state(if on { State::Info } else { State::Idle });
+        self.text.set_format(&self.format, &values)?;
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        match event.button {
+            MouseButton::Left => self.toggle(),
+            _ => {
+                crate::click::set_unhandled();
+                Ok(())
+            }
+        }
+    }
+
+    fn action(&mut self, action: &str) -> Result<bool> {
+        match action {
+            "toggle" => self.toggle()?,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
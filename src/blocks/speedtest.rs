@@ -14,6 +14,8 @@ use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
 use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
+use crate::services::Service;
+use crate::subprocess::command;
 use crate::widgets::text::TextWidget;
 use crate::widgets::I3BarWidget;
 
@@ -49,9 +51,7 @@ impl Default for SpeedTestConfig {
     }
 }
 
-fn get_values() -> Result<String> {
-    let mut cmd = Command::new("speedtest-cli");
-    cmd.arg("--simple");
+fn get_values(cmd: &mut Command) -> Result<String> {
     String::from_utf8(
         cmd.output()
             .block_error("speedtest", "could not get speedtest-cli output")?
@@ -83,11 +83,14 @@ fn make_thread(
     values: Arc<Mutex<(bool, Vec<f32>)>>,
     id: usize,
 ) {
+    // The command is created here to get the block's environment, and run again for each test
+    let mut cmd = command("speedtest-cli");
+    cmd.arg("--simple");
     thread::Builder::new()
         .name("speedtest".into())
         .spawn(move || loop {
             if recv.recv().is_ok() {
-                if let Ok(output) = get_values() {
+                if let Ok(output) = get_values(&mut cmd) {
                     if let Ok(vals) = parse_values(&output) {
                         if vals.len() == 3 {
                             let (ref mut update, ref mut values) = *values
@@ -140,6 +143,10 @@ impl ConfigBlock for SpeedTest {
             send,
         })
     }
+
+    fn default_depends_on() -> &'static [Service] {
+        &[Service::Network]
+    }
 }
 
 impl Block for SpeedTest {
@@ -164,7 +171,7 @@ impl Block for SpeedTest {
                     "speed_up" => Value::from_float(up).bits().icon(self.up_icon.clone()),
                 );
 
-                self.output.set_texts(self.format.render(&values)?);
+                self.output.set_format(&self.format, &values)?;
             }
 
             Ok(None)
@@ -0,0 +1,300 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use inotify::{EventMask, Inotify, WatchMask};
+use regex::Regex;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::scheduler::Task;
+use crate::util::read_inotify_events;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+const BLOCK_NAME: &str = "ssh_sessions";
+
+/// The size of a `struct utmp` of glibc, which is the same on 32 and 64 bit machines
+const RECORD_SIZE: usize = 384;
+/// `ut_type` of a login
+const USER_PROCESS: i16 = 7;
+
+pub struct SshSessions {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    update_interval: Duration,
+    utmp_path: PathBuf,
+    allowed_users: Vec<String>,
+    allowed_hosts: Option<Regex>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct SshSessionsConfig {
+    /// Updates in between the changes of `utmp_path`, which drop the sessions whose process is
+    /// gone without a logout
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    pub format: FormatTemplate,
+
+    pub utmp_path: String,
+
+    /// The users that are expected to log in. Any user if it's empty.
+    pub allowed_users: Vec<String>,
+
+    /// A regex of the hosts that are expected to log in from. Any host if it's not set.
+    pub allowed_hosts: Option<String>,
+}
+
+impl Default for SshSessionsConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(60),
+            format: FormatTemplate::default(),
+            utmp_path: "/run/utmp".to_string(),
+            allowed_users: Vec::new(),
+            allowed_hosts: None,
+        }
+    }
+}
+
+/// A login of utmp
+#[derive(Debug, Clone, PartialEq)]
+struct Login {
+    pid: i32,
+    user: String,
+    /// Like `203.0.113.5`, or `203.0.113.5 via mosh [4242]` for mosh
+    host: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Ssh,
+    Mosh,
+}
+
+/// A field of utmp, which is padded with zeros
+fn text(field: &[u8]) -> String {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+/// The logins of the records of `/run/utmp`
+fn parse_utmp(data: &[u8]) -> Vec<Login> {
+    data.chunks_exact(RECORD_SIZE)
+        .filter(|record| i16::from_ne_bytes([record[0], record[1]]) == USER_PROCESS)
+        .map(|record| Login {
+            pid: i32::from_ne_bytes([record[4], record[5], record[6], record[7]]),
+            user: text(&record[44..76]),
+            host: text(&record[76..332]),
+        })
+        .collect()
+}
+
+/// Whether the login with the process `comm` is a session of SSH or mosh. Local logins like
+/// terminals of the desktop aren't.
+fn session_kind(comm: &str, host: &str) -> Option<Kind> {
+    if comm == "mosh-server" || host.contains("via mosh") || host.starts_with("mosh [") {
+        Some(Kind::Mosh)
+    } else if comm == "sshd" || comm == "sshd-session" {
+        Some(Kind::Ssh)
+    } else {
+        None
+    }
+}
+
+/// The host a session comes from, without what mosh adds to it. Empty for a mosh session that no
+/// client is attached to.
+fn remote_host(host: &str) -> &str {
+    match host.find("via mosh") {
+        Some(position) => host[..position].trim(),
+        None if host.starts_with("mosh [") => "",
+        None => host,
+    }
+}
+
+/// Requests an update whenever a login or a logout is written to `utmp_path`
+fn watch_utmp(utmp_path: &Path, id: usize, tx_update_request: Sender<Task>) -> Result<()> {
+    let mut notify = Inotify::init().block_error(BLOCK_NAME, "failed to start inotify")?;
+    notify.add_watch(utmp_path, WatchMask::MODIFY).block_error(
+        BLOCK_NAME,
+        &format!("failed to watch {}", utmp_path.display()),
+    )?;
+    thread::Builder::new()
+        .name(BLOCK_NAME.into())
+        .spawn(move || {
+            let mut buffer = [0; 1024];
+            loop {
+                let mut events = read_inotify_events(id, &mut notify, &mut buffer)
+                    .expect("Error while reading inotify events");
+                if events.any(|event| event.mask.contains(EventMask::MODIFY)) {
+                    let _ = tx_update_request.send(Task {
+                        id,
+                        update_time: Instant::now(),
+                    });
+                }
+                // A login writes several records
+                thread::sleep(Duration::from_millis(250))
+            }
+        })
+        .map(|_| ())
+        .internal_error(BLOCK_NAME, "failed to start thread")
+}
+
+impl SshSessions {
+    fn is_expected(&self, login: &Login) -> bool {
+        let user = self.allowed_users.is_empty() || self.allowed_users.contains(&login.user);
+        let host = match &self.allowed_hosts {
+            Some(regex) => regex.is_match(remote_host(&login.host)),
+            None => true,
+        };
+        user && host
+    }
+}
+
+impl ConfigBlock for SshSessions {
+    type Config = SshSessionsConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let utmp_path = PathBuf::from(block_config.utmp_path);
+        watch_utmp(&utmp_path, id, tx_update_request)?;
+        Ok(SshSessions {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("ssh")?,
+            format: block_config.format.with_default("{sessions}")?,
+            update_interval: block_config.interval,
+            utmp_path,
+            allowed_users: block_config.allowed_users,
+            allowed_hosts: block_config
+                .allowed_hosts
+                .map(|regex| Regex::new(&regex))
+                .transpose()
+                .configuration_error("invalid regex of 'allowed_hosts'")?,
+        })
+    }
+}
+
+impl Block for SshSessions {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let data = fs::read(&self.utmp_path).block_error(
+            BLOCK_NAME,
+            &format!("failed to read {}", self.utmp_path.display()),
+        )?;
+        let (mut ssh, mut mosh, mut unexpected) = (0, 0, 0);
+        let mut users = BTreeSet::new();
+        let mut connections = BTreeSet::new();
+        for login in parse_utmp(&data) {
+            // Sessions whose process is gone, e.g. after a crash, are still in utmp
+            let comm = match fs::read_to_string(format!("/proc/{}/comm", login.pid)) {
+                Ok(comm) => comm,
+                Err(_) => continue,
+            };
+            match session_kind(comm.trim(), &login.host) {
+                Some(Kind::Ssh) => ssh += 1,
+                Some(Kind::Mosh) => mosh += 1,
+                None => continue,
+            }
+            if !self.is_expected(&login) {
+                unexpected += 1;
+            }
+            let host = remote_host(&login.host);
+            connections.insert(match host.is_empty() {
+                true => login.user.clone(),
+                false => format!("{}@{}", login.user, host),
+            });
+            users.insert(login.user);
+        }
+
+        let join = |names: BTreeSet<String>| names.into_iter().collect::<Vec<_>>().join(", ");
+        let values = map!(
+            "sessions" => Value::from_integer(ssh + mosh),
+            "ssh" => Value::from_integer(ssh),
+            "mosh" => Value::from_integer(mosh),
+            "unexpected" => Value::from_integer(unexpected),
+            "users" => Value::from_string(join(users)),
+            "connections" => Value::from_string(join(connections)),
+        );
+        self.text.set_state(match (unexpected, ssh + mosh) {
+            (0, 0) => State::Idle,
+            (0, _) => State::Info,
+            _ => State::Critical,
+        });
+        self.text.set_format(&self.format, &values)?;
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(kind: i16, pid: i32, line: &str, user: &str, host: &str) -> Vec<u8> {
+        let mut record = vec![0; RECORD_SIZE];
+        record[0..2].copy_from_slice(&kind.to_ne_bytes());
+        record[4..8].copy_from_slice(&pid.to_ne_bytes());
+        record[8..8 + line.len()].copy_from_slice(line.as_bytes());
+        record[44..44 + user.len()].copy_from_slice(user.as_bytes());
+        record[76..76 + host.len()].copy_from_slice(host.as_bytes());
+        record
+    }
+
+    #[test]
+    fn utmp() {
+        let mut data = record(2, 0, "~", "reboot", "6.1.0");
+        data.extend(record(7, 812, "pts/0", "alice", "203.0.113.5"));
+        data.extend(record(8, 0, "pts/1", "", ""));
+        data.extend(record(
+            7,
+            915,
+            "pts/2",
+            "bob",
+            "198.51.100.7 via mosh [915]",
+        ));
+        let logins = parse_utmp(&data);
+        assert_eq!(logins.len(), 2);
+        assert_eq!(
+            logins[0],
+            Login {
+                pid: 812,
+                user: "alice".to_string(),
+                host: "203.0.113.5".to_string(),
+            }
+        );
+
+        assert_eq!(session_kind("sshd", "203.0.113.5"), Some(Kind::Ssh));
+        assert_eq!(session_kind("sshd-session", "::1"), Some(Kind::Ssh));
+        assert_eq!(
+            session_kind("mosh-server", &logins[1].host),
+            Some(Kind::Mosh)
+        );
+        assert_eq!(session_kind("login", ""), None);
+        assert_eq!(session_kind("tmux: server", "tmux(1234).%0"), None);
+        assert_eq!(remote_host(&logins[1].host), "198.51.100.7");
+        assert_eq!(remote_host("mosh [915]"), "");
+        assert_eq!(remote_host("203.0.113.5"), "203.0.113.5");
+    }
+}
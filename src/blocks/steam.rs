@@ -0,0 +1,364 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::scheduler::Task;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+/// Where Steam is installed natively, by older installers and as a Flatpak
+const STEAM_DIRS: &[&str] = &[
+    "~/.local/share/Steam",
+    "~/.steam/steam",
+    "~/.var/app/com.valvesoftware.Steam/.local/share/Steam",
+];
+
+// Flags of `StateFlags` in the app manifests
+const UPDATE_RUNNING: u64 = 0x100;
+const UPDATE_PAUSED: u64 = 0x200;
+const UPDATE_STARTED: u64 = 0x400;
+const DOWNLOADING: u64 = 0x10_0000;
+const STAGING: u64 = 0x20_0000;
+const COMMITTING: u64 = 0x40_0000;
+
+/// The string pairs of a file in Valve's KeyValues format, like the app manifests, in order.
+/// Keys of nested sections are skipped.
+fn key_values(text: &str) -> Vec<(String, String)> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                let mut token = String::new();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => token.extend(chars.next()),
+                        c => token.push(c),
+                    }
+                }
+                tokens.push(Some(token));
+            }
+            '{' | '}' => tokens.push(None),
+            '/' if chars.peek() == Some(&'/') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+    let mut pairs = Vec::new();
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        if let Some(key) = token {
+            if let Some(Some(_)) = tokens.peek() {
+                pairs.push((key, tokens.next().flatten().unwrap()));
+            }
+        }
+    }
+    pairs
+}
+
+/// An app that is updated or installed, from its `appmanifest_<appid>.acf`
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Download {
+    appid: String,
+    name: String,
+    flags: u64,
+    to_download: u64,
+    downloaded: u64,
+    to_stage: u64,
+    staged: u64,
+}
+
+impl Download {
+    /// The download in a manifest, if the app is being updated or has an update queued
+    fn parse(manifest: &str) -> Option<Download> {
+        let mut download = Download::default();
+        for (key, value) in key_values(manifest) {
+            let number = || value.parse().unwrap_or(0);
+            match key.as_str() {
+                "appid" => download.appid = value.clone(),
+                "name" => download.name = value.clone(),
+                "StateFlags" => download.flags = number(),
+                "BytesToDownload" => download.to_download = number(),
+                "BytesDownloaded" => download.downloaded = number(),
+                "BytesToStage" => download.to_stage = number(),
+                "BytesStaged" => download.staged = number(),
+                _ => (),
+            }
+        }
+        let updating = download.flags & (UPDATE_RUNNING | UPDATE_PAUSED | UPDATE_STARTED) != 0;
+        let unfinished = download.downloaded < download.to_download;
+        match updating || unfinished {
+            true => Some(download),
+            false => None,
+        }
+    }
+
+    fn state(&self) -> &'static str {
+        if self.flags & UPDATE_PAUSED != 0 {
+            "paused"
+        } else if self.flags & DOWNLOADING != 0 {
+            "downloading"
+        } else if self.flags & (STAGING | COMMITTING) != 0 {
+            "installing"
+        } else {
+            "queued"
+        }
+    }
+
+    /// The bytes that are done and the bytes to do, of the download or else of the installation
+    fn progress(&self) -> (u64, u64) {
+        if self.downloaded < self.to_download || self.to_stage == 0 {
+            (self.downloaded, self.to_download)
+        } else {
+            (self.staged, self.to_stage)
+        }
+    }
+}
+
+pub struct Steam {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    format_idle: FormatTemplate,
+    hide_when_idle: bool,
+    update_interval: Duration,
+    steam_dir: Option<PathBuf>,
+    /// The active download, if there is one
+    download: Option<Download>,
+    /// The download speed in bytes per second of the apps, from the bytes done at the last update
+    speeds: HashMap<String, (Instant, u64, f64)>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct SteamConfig {
+    /// The directory of Steam, with `steamapps`. Found in the usual places by default.
+    pub steam_dir: Option<String>,
+
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    pub format: FormatTemplate,
+
+    /// Format while nothing is downloaded
+    pub format_idle: FormatTemplate,
+
+    /// Hides the block while nothing is downloaded
+    pub hide_when_idle: bool,
+}
+
+impl Default for SteamConfig {
+    fn default() -> Self {
+        Self {
+            steam_dir: None,
+            interval: Duration::from_secs(5),
+            format: FormatTemplate::default(),
+            format_idle: FormatTemplate::default(),
+            hide_when_idle: true,
+        }
+    }
+}
+
+/// The `steamapps` directories of all Steam libraries
+fn libraries(steam_dir: &Path) -> Vec<PathBuf> {
+    let steamapps = steam_dir.join("steamapps");
+    let mut libraries = vec![steamapps.clone()];
+    if let Ok(folders) = fs::read_to_string(steamapps.join("libraryfolders.vdf")) {
+        for (key, path) in key_values(&folders) {
+            let library = Path::new(&path).join("steamapps");
+            if key == "path" && !libraries.contains(&library) {
+                libraries.push(library);
+            }
+        }
+    }
+    libraries
+}
+
+impl ConfigBlock for Steam {
+    type Config = SteamConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let steam_dir = match block_config.steam_dir {
+            Some(dir) => Some(PathBuf::from(shellexpand::tilde(&dir).to_string())),
+            None => STEAM_DIRS
+                .iter()
+                .map(|dir| PathBuf::from(shellexpand::tilde(dir).to_string()))
+                .find(|dir| dir.join("steamapps").is_dir()),
+        };
+        Ok(Steam {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("steam")?,
+            format: block_config
+                .format
+                .with_default("{name} {progress} {eta}")?,
+            format_idle: block_config.format_idle.with_default("idle")?,
+            hide_when_idle: block_config.hide_when_idle,
+            update_interval: block_config.interval,
+            steam_dir,
+            download: None,
+            speeds: HashMap::new(),
+        })
+    }
+}
+
+impl Block for Steam {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let steam_dir = self
+            .steam_dir
+            .as_ref()
+            .block_error("steam", "Steam was not found, set 'steam_dir'")?;
+        let mut downloads = Vec::new();
+        for library in libraries(steam_dir) {
+            for entry in fs::read_dir(&library).into_iter().flatten().flatten() {
+                let file_name = entry.file_name().to_string_lossy().to_string();
+                if !(file_name.starts_with("appmanifest_") && file_name.ends_with(".acf")) {
+                    continue;
+                }
+                if let Some(download) = fs::read_to_string(entry.path())
+                    .ok()
+                    .and_then(|manifest| Download::parse(&manifest))
+                {
+                    downloads.push(download);
+                }
+            }
+        }
+        // Steam downloads one app at a time, the others are queued
+        downloads.sort_by_key(|download| download.state() != "downloading");
+        let queued = downloads.len().saturating_sub(1);
+
+        let now = Instant::now();
+        let mut speeds = HashMap::new();
+        for download in &downloads {
+            let (done, _) = download.progress();
+            let speed = match self.speeds.get(&download.appid) {
+                Some(&(then, bytes, speed)) if done >= bytes => {
+                    let sample = (done - bytes) as f64 / (now - then).as_secs_f64();
+                    // Smoothed, since Steam writes the manifests every few seconds
+                    match speed {
+                        speed if speed > 0.0 => 0.3 * sample + 0.7 * speed,
+                        _ => sample,
+                    }
+                }
+                _ => 0.0,
+            };
+            speeds.insert(download.appid.clone(), (now, done, speed));
+        }
+        self.speeds = speeds;
+        self.download = downloads.into_iter().next();
+
+        let download = match &self.download {
+            Some(download) => download,
+            None => {
+                self.text.set_state(State::Idle);
+                self.text
+                    .set_format(&self.format_idle, &HashMap::<&str, _>::new())?;
+                return Ok(Some(self.update_interval.into()));
+            }
+        };
+        let (done, total) = download.progress();
+        let speed = self.speeds[&download.appid].2;
+        let eta = match (download.state(), total.saturating_sub(done)) {
+            ("downloading" | "installing", remaining) if speed > 0.0 => {
+                let minutes = (remaining as f64 / speed / 60.0).ceil() as u64;
+                format!("{}:{:02}", minutes / 60, minutes % 60)
+            }
+            _ => String::new(),
+        };
+        let values = map!(
+            "name" => Value::from_string(download.name.clone()),
+            "appid" => Value::from_string(download.appid.clone()),
+            "state" => Value::from_string(download.state().to_string()),
+            "progress" => Value::from_integer(match total {
+                0 => 0,
+                total => (done as f64 / total as f64 * 100.0) as i64,
+            }).percents(),
+            "done" => Value::from_integer(done as i64).bytes(),
+            "total" => Value::from_integer(total as i64).bytes(),
+            "speed" => Value::from_float(speed).bytes(),
+            "eta" => Value::from_string(eta),
+            "queued" => Value::from_integer(queued as i64),
+        );
+        self.text.set_state(match download.state() {
+            "downloading" | "installing" => State::Info,
+            _ => State::Idle,
+        });
+        self.text.set_format(&self.format, &values)?;
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        if self.hide_when_idle && self.download.is_none() {
+            vec![]
+        } else {
+            vec![&self.text]
+        }
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifests() {
+        let manifest = r#"
+"AppState"
+{
+	"appid"		"570"
+	"name"		"Dota \"2\""
+	"StateFlags"		"1026"
+	"BytesToDownload"		"2000"
+	"BytesDownloaded"		"500"
+	"BytesToStage"		"4000"
+	"BytesStaged"		"0"
+	"InstalledDepots"
+	{
+		"373301"
+		{
+			"manifest"		"123"
+		}
+	}
+}
+"#;
+        let download = Download::parse(manifest).unwrap();
+        assert_eq!(download.name, "Dota \"2\"");
+        assert_eq!(download.state(), "queued");
+        assert_eq!(download.progress(), (500, 2000));
+        let installed = manifest
+            .replace("\"1026\"", "\"4\"")
+            .replace("\"500\"", "\"2000\"");
+        assert_eq!(Download::parse(&installed), None);
+
+        let folders = "\"libraryfolders\"\n{\n\t\"0\"\n\t{\n\t\t\"path\"\t\t\"/games\"\n\t}\n}";
+        assert_eq!(
+            key_values(folders),
+            vec![("path".to_string(), "/games".to_string())]
+        );
+    }
+}
@@ -1,4 +1,6 @@
-use std::process::Command;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
 use std::thread;
 use std::time::{Duration, Instant};
 
@@ -13,6 +15,8 @@ use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
 use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
+use crate::subprocess::command;
+use crate::util::{read_inotify_events, shell_quote};
 use crate::widgets::text::TextWidget;
 use crate::widgets::{I3BarWidget, State};
 use inotify::{EventMask, Inotify, WatchMask};
@@ -152,8 +156,7 @@ impl ConfigBlock for Taskwarrior {
 
                 let mut buffer = [0; 1024];
                 loop {
-                    let mut events = notify
-                        .read_events_blocking(&mut buffer)
+                    let mut events = read_inotify_events(id, &mut notify, &mut buffer)
                         .expect("Error while reading inotify events");
 
                     if events.any(|event| event.mask.contains(EventMask::MODIFY)) {
@@ -188,9 +191,56 @@ impl ConfigBlock for Taskwarrior {
     }
 }
 
+/// The name of the hook in the hooks directory of taskwarrior
+const HOOK_NAME: &str = "on-exit.i3status-rs";
+
+/// How the hook tells the running bars that tasks changed
+#[derive(Debug, Clone, PartialEq)]
+pub enum HookTrigger {
+    /// `i3status-rs refresh <block>` over the socket of the bars
+    Refresh(String),
+    /// The real time signal of the `signal` of the block, relative to `SIGRTMIN`
+    Signal(i32),
+}
+
+/// An `on-exit` hook that runs `trigger` after the commands that added or modified tasks.
+/// `program` is the path of `i3status-rs`.
+fn hook_script(program: &str, trigger: &HookTrigger) -> String {
+    let command = match trigger {
+        HookTrigger::Refresh(block) => {
+            format!("{} refresh {}", shell_quote(program), shell_quote(block))
+        }
+        HookTrigger::Signal(signal) => format!("pkill -RTMIN+{} -x i3status-rs", signal),
+    };
+    format!(
+        "#!/bin/sh\n\
+         # Updates the taskwarrior block of i3status-rs, installed by\n\
+         # `i3status-rs install-taskwarrior-hook`. Taskwarrior passes the added and\n\
+         # modified tasks on stdin, there are none after reports like `task list`.\n\
+         if [ -n \"$(cat)\" ]; then\n    \
+             {} >/dev/null 2>&1 &\n\
+         fi\n\
+         exit 0\n",
+        command
+    )
+}
+
+/// Installs the hook into `hooks_dir`, like `~/.task/hooks`, replacing an earlier one
+pub fn install_hook(hooks_dir: &Path, trigger: &HookTrigger) -> Result<PathBuf> {
+    let program = std::env::current_exe()
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| "i3status-rs".to_string());
+    let path = hooks_dir.join(HOOK_NAME);
+    fs::create_dir_all(hooks_dir)
+        .and_then(|()| fs::write(&path, hook_script(&program, trigger)))
+        .and_then(|()| fs::set_permissions(&path, fs::Permissions::from_mode(0o755)))
+        .configuration_error(&format!("failed to write '{}'", path.display()))?;
+    Ok(path)
+}
+
 fn has_taskwarrior() -> Result<bool> {
     Ok(String::from_utf8(
-        Command::new("sh")
+        command("sh")
             .args(&["-c", "type -P task"])
             .output()
             .block_error(
@@ -206,7 +256,7 @@ fn has_taskwarrior() -> Result<bool> {
 
 fn get_number_of_tasks(filter: &str) -> Result<u32> {
     String::from_utf8(
-        Command::new("sh")
+        command("sh")
             .args(&["-c", &format!("task rc.gc=off {} count", filter)])
             .output()
             .block_error(
@@ -280,3 +330,21 @@ impl Block for Taskwarrior {
         self.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hooks() {
+        let refresh = hook_script(
+            "/usr/bin/i3status-rs",
+            &HookTrigger::Refresh("it's tasks".to_string()),
+        );
+        assert!(refresh.starts_with("#!/bin/sh\n"));
+        assert!(refresh
+            .contains("    '/usr/bin/i3status-rs' refresh 'it'\\''s tasks' >/dev/null 2>&1 &\n"));
+        let signal = hook_script("/usr/bin/i3status-rs", &HookTrigger::Signal(3));
+        assert!(signal.contains("    pkill -RTMIN+3 -x i3status-rs >/dev/null 2>&1 &\n"));
+    }
+}
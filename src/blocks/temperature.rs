@@ -1,6 +1,5 @@
 use std::collections::HashMap;
 use std::fs;
-use std::process::Command;
 use std::time::Duration;
 
 use crossbeam_channel::Sender;
@@ -14,6 +13,7 @@ use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
 use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
+use crate::subprocess::command;
 use crate::widgets::{text::TextWidget, I3BarWidget, Spacing, State};
 
 #[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq)]
@@ -175,7 +175,7 @@ impl ConfigBlock for Temperature {
             inputs: block_config.inputs,
             fallback_required: match block_config.driver {
                 TemperatureDriver::Sysfs => false,
-                TemperatureDriver::Sensors => !Command::new("sensors")
+                TemperatureDriver::Sensors => !command("sensors")
                     .args(&["--help"])
                     .output()
                     .block_error(
@@ -213,7 +213,7 @@ impl Block for Temperature {
                 if let Some(ref chip) = &self.chip {
                     args.push(chip);
                 }
-                let output = Command::new("sensors")
+                let output = command("sensors")
                     .args(&args)
                     .output()
                     .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_owned())
@@ -237,7 +237,7 @@ impl Block for Temperature {
                                     }
                                     Ok(t) => {
                                         // This error is recoverable and therefore should not stop the program
-                                        eprintln!(
+                                        tracing::warn!(
                                             "Temperature ({}) outside of range ([-100, 150])",
                                             t
                                         );
@@ -278,7 +278,7 @@ impl Block for Temperature {
                                     temperatures.push(value);
                                 } else {
                                     // This error is recoverable and therefore should not stop the program
-                                    eprintln!(
+                                    tracing::warn!(
                                         "Temperature ({}) outside of range ([-100, 150])",
                                         value
                                     );
@@ -325,7 +325,7 @@ impl Block for Temperature {
                                     temperatures.push(value);
                                 } else {
                                     // This error is recoverable and therefore should not stop the program
-                                    eprintln!(
+                                    tracing::warn!(
                                         "Temperature ({}) outside of range ([-100, 150])",
                                         value
                                     );
@@ -4,18 +4,21 @@ use std::time::Duration;
 
 use chrono::{
     offset::{Local, Utc},
-    Locale,
+    Datelike, Locale, NaiveDate,
 };
 use chrono_tz::Tz;
 use crossbeam_channel::Sender;
 use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
+use crate::calendars::{self, Chinese};
 use crate::config::SharedConfig;
 use crate::de::deserialize_duration;
 use crate::errors::*;
+use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
 use crate::scheduler::Task;
+use crate::topics;
 use crate::widgets::text::TextWidget;
 use crate::widgets::I3BarWidget;
 
@@ -23,9 +26,13 @@ pub struct Time {
     id: usize,
     time: TextWidget,
     update_interval: Duration,
-    formats: (String, Option<String>),
+    format: FormatTemplate,
+    /// The placeholders of the last date, which change once a day
+    dates: Option<(NaiveDate, HashMap<&'static str, Value>)>,
     timezone: Option<Tz>,
     locale: Option<String>,
+    weather_tint: bool,
+    shared_config: SharedConfig,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -43,6 +50,9 @@ pub struct TimeConfig {
     pub timezone: Option<Tz>,
 
     pub locale: Option<String>,
+
+    /// Tints the block with the color of the weather in the theme, from a `weather` block
+    pub weather_tint: bool,
 }
 
 impl Default for TimeConfig {
@@ -52,6 +62,7 @@ impl Default for TimeConfig {
             interval: Duration::from_secs(5),
             timezone: None,
             locale: None,
+            weather_tint: false,
         }
     }
 }
@@ -63,25 +74,68 @@ impl ConfigBlock for Time {
         id: usize,
         block_config: Self::Config,
         shared_config: SharedConfig,
-        _tx_update_request: Sender<Task>,
+        tx_update_request: Sender<Task>,
     ) -> Result<Self> {
+        if block_config.weather_tint {
+            topics::subscribe(topics::WEATHER, id, tx_update_request);
+        }
         Ok(Time {
             id,
-            time: TextWidget::new(id, 0, shared_config)
+            time: TextWidget::new(id, 0, shared_config.clone())
                 .with_text("")
                 .with_icon("time")?,
             update_interval: block_config.interval,
-            formats: block_config
-                .format
-                .with_default("%a %d/%m %R")?
-                .render(&HashMap::<&str, _>::new())?,
+            format: block_config.format.with_default("%a %d/%m %R")?,
+            dates: None,
             timezone: block_config.timezone,
             locale: block_config.locale,
+            weather_tint: block_config.weather_tint,
+            shared_config,
         })
     }
+
+    fn default_update_on_resume() -> bool {
+        true
+    }
 }
 
 impl Time {
+    fn today(&self) -> NaiveDate {
+        match self.timezone {
+            Some(tz) => Utc::now().with_timezone(&tz).naive_local().date(),
+            None => Local::now().naive_local().date(),
+        }
+    }
+
+    /// The other calendars are only computed if they are used
+    fn date_values(&self, date: NaiveDate) -> HashMap<&'static str, Value> {
+        let week = date.iso_week();
+        let mut values = map!(
+            "iso_week" => Value::from_integer(week.week() as i64),
+            "iso_year" => Value::from_integer(week.year() as i64),
+            "day_of_year" => Value::from_integer(date.ordinal() as i64),
+        );
+        if self.format.contains("hebrew") {
+            values.insert("hebrew", Value::from_string(calendars::hebrew(date)));
+        }
+        if self.format.contains("hijri") {
+            values.insert("hijri", Value::from_string(calendars::hijri(date)));
+        }
+        if ["chinese", "chinese_year", "chinese_zodiac"]
+            .iter()
+            .any(|var| self.format.contains(var))
+        {
+            let chinese = Chinese::new(date);
+            values.insert("chinese", Value::from_string(chinese.date()));
+            values.insert("chinese_year", Value::from_string(chinese.year_name()));
+            values.insert(
+                "chinese_zodiac",
+                Value::from_string(chinese.zodiac().to_string()),
+            );
+        }
+        values
+    }
+
     fn get_formatted_time(&self, format: &str) -> Result<String> {
         let time = match &self.locale {
             Some(l) => {
@@ -107,12 +161,26 @@ impl Time {
 
 impl Block for Time {
     fn update(&mut self) -> Result<Option<Update>> {
-        let full = self.get_formatted_time(&self.formats.0)?;
-        let short = match &self.formats.1 {
+        let today = self.today();
+        if !matches!(&self.dates, Some((date, _)) if *date == today) {
+            self.dates = Some((today, self.date_values(today)));
+        }
+        let formats = self.format.render(&self.dates.as_ref().unwrap().1)?;
+        let full = self.get_formatted_time(&formats.0)?;
+        let short = match &formats.1 {
             Some(short_fmt) => Some(self.get_formatted_time(short_fmt)?),
             None => None,
         };
         self.time.set_texts((full, short));
+        if self.weather_tint {
+            let theme = self.shared_config.theme();
+            let weather = &theme.weather;
+            self.time.set_tint(
+                topics::with(topics::WEATHER, |condition| weather.color(condition))
+                    .flatten()
+                    .map(|color| (color, weather.tint)),
+            );
+        }
         Ok(Some(self.update_interval.into()))
     }
 
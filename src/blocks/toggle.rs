@@ -1,5 +1,4 @@
 use std::env;
-use std::process::Command;
 use std::time::Duration;
 
 use crossbeam_channel::Sender;
@@ -11,6 +10,7 @@ use crate::de::deserialize_opt_duration;
 use crate::errors::*;
 use crate::protocol::i3bar_event::I3BarEvent;
 use crate::scheduler::Task;
+use crate::subprocess::command;
 use crate::widgets::text::TextWidget;
 use crate::widgets::{I3BarWidget, State};
 
@@ -90,7 +90,7 @@ impl ConfigBlock for Toggle {
 
 impl Block for Toggle {
     fn update(&mut self) -> Result<Option<Update>> {
-        let output = Command::new(env::var("SHELL").unwrap_or_else(|_| "sh".to_owned()))
+        let output = command(&env::var("SHELL").unwrap_or_else(|_| "sh".to_owned()))
             .args(&["-c", &self.command_state])
             .output()
             .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_owned())
@@ -123,7 +123,7 @@ impl Block for Toggle {
             &self.command_on
         };
 
-        let output = Command::new(env::var("SHELL").unwrap_or_else(|_| "sh".to_owned()))
+        let output = command(&env::var("SHELL").unwrap_or_else(|_| "sh".to_owned()))
             .args(&["-c", cmd])
             .output()
             .block_error("toggle", "failed to run toggle command")?;
@@ -0,0 +1,356 @@
+use std::env;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+use swayipc::{Connection, Event, EventType, InputChange, SendEvents};
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::subprocess::command;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TouchpadDriver {
+    /// Sway while it's running, otherwise xinput
+    Auto,
+    Sway,
+    Xinput,
+}
+
+/// Whether the touchpad sends events, like the `events` of an input in sway
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Events {
+    Enabled,
+    Disabled,
+    DisabledOnExternalMouse,
+}
+
+/// The touchpad of the last update
+#[derive(Debug, Clone, PartialEq)]
+struct Device {
+    /// The identifier of the input in sway, or the name of the device in xinput
+    identifier: String,
+    name: String,
+    events: Events,
+}
+
+pub struct Touchpad {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    driver: TouchpadDriver,
+    device: Option<String>,
+    update_interval: Option<Duration>,
+    touchpad: Option<Device>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct TouchpadConfig {
+    pub driver: TouchpadDriver,
+
+    /// The identifier of the input in sway or the name of the device in xinput. The first
+    /// touchpad by default.
+    pub device: Option<String>,
+
+    /// Update interval with xinput, which doesn't report changes
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    pub format: FormatTemplate,
+}
+
+impl Default for TouchpadConfig {
+    fn default() -> Self {
+        Self {
+            driver: TouchpadDriver::Auto,
+            device: None,
+            interval: Duration::from_secs(5),
+            format: FormatTemplate::default(),
+        }
+    }
+}
+
+/// The device called `device` or else the first one with "touchpad" in its name, from the output
+/// of `xinput list --name-only`
+fn find_xinput_device(names: &str, device: Option<&str>) -> Option<String> {
+    names
+        .lines()
+        .map(str::trim)
+        .find(|name| match device {
+            Some(device) => *name == device,
+            None => name.to_lowercase().contains("touchpad"),
+        })
+        .map(str::to_string)
+}
+
+/// Whether the device is enabled, from the output of `xinput list-props`
+fn xinput_enabled(props: &str) -> Option<bool> {
+    props.lines().find_map(|line| {
+        let (property, value) = line.trim().split_once(':')?;
+        match (property.starts_with("Device Enabled"), value.trim()) {
+            (true, "1") => Some(true),
+            (true, "0") => Some(false),
+            _ => None,
+        }
+    })
+}
+
+fn xinput(args: &[&str]) -> Result<String> {
+    let output = command("xinput")
+        .args(args)
+        .output()
+        .block_error("touchpad", "failed to run xinput")?;
+    if !output.status.success() {
+        return Err(BlockError(
+            "touchpad".to_string(),
+            format!(
+                "xinput failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn sway() -> Result<Connection> {
+    Connection::new().block_error("touchpad", "failed to connect to sway")
+}
+
+/// Requests an update when a touchpad is added, removed or configured in sway
+fn watch_sway(id: usize, tx_update_request: Sender<Task>) -> Result<()> {
+    let events = sway()?
+        .subscribe([EventType::Input])
+        .block_error("touchpad", "failed to subscribe to the inputs of sway")?;
+    thread::Builder::new()
+        .name("touchpad".into())
+        .spawn(move || {
+            for event in events {
+                match event {
+                    Ok(Event::Input(event)) if event.input.input_type == "touchpad" => {
+                        match event.change {
+                            InputChange::Added
+                            | InputChange::Removed
+                            | InputChange::LibinputConfig => {
+                                let _ = tx_update_request.send(Task {
+                                    id,
+                                    update_time: Instant::now(),
+                                });
+                            }
+                            _ => (),
+                        }
+                    }
+                    Ok(_) => (),
+                    Err(_) => return,
+                }
+            }
+        })
+        .map(|_| ())
+        .internal_error("touchpad", "failed to start thread")
+}
+
+impl Touchpad {
+    fn find_touchpad(&self) -> Result<Option<Device>> {
+        let device = self.device.as_deref();
+        match self.driver {
+            TouchpadDriver::Sway => Ok(sway()?
+                .get_inputs()
+                .block_error("touchpad", "failed to get the inputs of sway")?
+                .into_iter()
+                .find(|input| match device {
+                    Some(device) => input.identifier == device,
+                    None => input.input_type == "touchpad",
+                })
+                .map(|input| Device {
+                    events: match input.libinput.and_then(|libinput| libinput.send_events) {
+                        Some(SendEvents::Disabled) => Events::Disabled,
+                        Some(SendEvents::DisabledOnExternalMouse) => {
+                            Events::DisabledOnExternalMouse
+                        }
+                        _ => Events::Enabled,
+                    },
+                    identifier: input.identifier,
+                    name: input.name,
+                })),
+            _ => {
+                let name = match find_xinput_device(&xinput(&["list", "--name-only"])?, device) {
+                    Some(name) => name,
+                    None => return Ok(None),
+                };
+                let enabled = xinput_enabled(&xinput(&["list-props", &name])?)
+                    .block_error("touchpad", "failed to read 'Device Enabled' of xinput")?;
+                Ok(Some(Device {
+                    identifier: name.clone(),
+                    name,
+                    events: match enabled {
+                        true => Events::Enabled,
+                        false => Events::Disabled,
+                    },
+                }))
+            }
+        }
+    }
+
+    fn set_enabled(&mut self, enabled: bool) -> Result<()> {
+        let identifier = match &self.touchpad {
+            Some(touchpad) => touchpad.identifier.clone(),
+            None => return Ok(()),
+        };
+        match self.driver {
+            TouchpadDriver::Sway => {
+                let events = match enabled {
+                    true => "enabled",
+                    false => "disabled",
+                };
+                let outcomes = sway()?
+                    .run_command(format!("input \"{}\" events {}", identifier, events))
+                    .block_error("touchpad", "failed to run a command of sway")?;
+                for outcome in outcomes {
+                    outcome.block_error("touchpad", "sway failed to set the touchpad")?;
+                }
+            }
+            _ => {
+                let action = match enabled {
+                    true => "enable",
+                    false => "disable",
+                };
+                xinput(&[action, &identifier])?;
+            }
+        }
+        self.update()?;
+        Ok(())
+    }
+
+    fn toggle(&mut self) -> Result<()> {
+        let enabled = matches!(
+            self.touchpad.as_ref().map(|touchpad| touchpad.events),
+            Some(Events::Enabled)
+        );
+        self.set_enabled(!enabled)
+    }
+}
+
+impl ConfigBlock for Touchpad {
+    type Config = TouchpadConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let driver = match block_config.driver {
+            TouchpadDriver::Auto if env::var("SWAYSOCK").is_ok() => TouchpadDriver::Sway,
+            TouchpadDriver::Auto => TouchpadDriver::Xinput,
+            driver => driver,
+        };
+        // Sway reports the changes, xinput has to be polled
+        let update_interval = match driver {
+            TouchpadDriver::Sway => {
+                watch_sway(id, tx_update_request)?;
+                None
+            }
+            _ => Some(block_config.interval),
+        };
+        Ok(Touchpad {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("touchpad")?,
+            format: block_config.format.with_default("{state}")?,
+            driver,
+            device: block_config.device,
+            update_interval,
+            touchpad: None,
+        })
+    }
+}
+
+impl Block for Touchpad {
+    fn update(&mut self) -> Result<Option<Update>> {
+        self.touchpad = self.find_touchpad()?;
+        if let Some(touchpad) = &self.touchpad {
+            let values = map!(
+                "state" => Value::from_string(match touchpad.events {
+                    Events::Enabled => "on",
+                    Events::Disabled => "off",
+                    Events::DisabledOnExternalMouse => "auto",
+                }.to_string()),
+                "name" => Value::from_string(touchpad.name.clone()),
+            );
+            self.text.set_state(match touchpad.events {
+                Events::Disabled => State::Warning,
+                _ => State::Idle,
+            });
+            self.text.set_format(&self.format, &values)?;
+        }
+
+        Ok(self.update_interval.map(|d| d.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        match self.touchpad {
+            Some(_) => vec![&self.text],
+            None => vec![],
+        }
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if let MouseButton::Left = event.button {
+            self.toggle()?;
+        }
+        Ok(())
+    }
+
+    fn action(&mut self, action: &str) -> Result<bool> {
+        match action {
+            "toggle" => self.toggle()?,
+            "enable" => self.set_enabled(true)?,
+            "disable" => self.set_enabled(false)?,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xinput_output() {
+        let names = "Virtual core pointer\n\
+            Virtual core XTEST pointer\n\
+            SynPS/2 Synaptics TouchPad\n\
+            TPPS/2 IBM TrackPoint\n";
+        assert_eq!(
+            find_xinput_device(names, None),
+            Some("SynPS/2 Synaptics TouchPad".to_string())
+        );
+        assert_eq!(
+            find_xinput_device(names, Some("TPPS/2 IBM TrackPoint")),
+            Some("TPPS/2 IBM TrackPoint".to_string())
+        );
+        assert_eq!(find_xinput_device("Virtual core pointer\n", None), None);
+
+        let props = "Device 'SynPS/2 Synaptics TouchPad':\n\
+            \tDevice Enabled (168):\t0\n\
+            \tCoordinate Transformation Matrix (170):\t1.000000, 0.000000\n\
+            \tlibinput Tapping Enabled (305):\t1\n";
+        assert_eq!(xinput_enabled(props), Some(false));
+        assert_eq!(xinput_enabled(&props.replace(":\t0", ":\t1")), Some(true));
+        assert_eq!(xinput_enabled("Device 'x':\n"), None);
+    }
+}
@@ -50,6 +50,10 @@ impl ConfigBlock for Uptime {
             text: TextWidget::new(id, 0, shared_config).with_icon("uptime")?,
         })
     }
+
+    fn default_update_on_resume() -> bool {
+        true
+    }
 }
 
 impl Block for Uptime {
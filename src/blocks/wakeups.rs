@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::SharedConfig;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::FormatTemplate;
+use crate::scheduler::Task;
+use crate::util::read_file;
+use crate::widgets::text::TextWidget;
+use crate::widgets::{I3BarWidget, State};
+
+/// A line of `/proc/interrupts`
+#[derive(Debug)]
+struct Interrupt {
+    /// The number of the interrupt or a name like `LOC`
+    irq: String,
+    /// The device, e.g. `i915`, or what the interrupt is, e.g. `Local timer interrupts`
+    name: String,
+    /// The interrupts on all CPUs since boot
+    count: u64,
+}
+
+pub struct Wakeups {
+    id: usize,
+    text: TextWidget,
+    format: FormatTemplate,
+    update_interval: Duration,
+    warning: f64,
+    critical: f64,
+    /// The counts of the previous update, by `irq`
+    previous: Option<(Instant, HashMap<String, u64>)>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct WakeupsConfig {
+    pub format: FormatTemplate,
+
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub interval: Duration,
+
+    /// Wakeups per second, where state is set to warning
+    pub warning: f64,
+
+    /// Wakeups per second, where state is set to critical
+    pub critical: f64,
+}
+
+impl Default for WakeupsConfig {
+    fn default() -> Self {
+        Self {
+            format: FormatTemplate::default(),
+            interval: Duration::from_secs(5),
+            warning: 1000.0,
+            critical: 3000.0,
+        }
+    }
+}
+
+impl ConfigBlock for Wakeups {
+    type Config = WakeupsConfig;
+
+    fn new(
+        id: usize,
+        block_config: Self::Config,
+        shared_config: SharedConfig,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        Ok(Wakeups {
+            id,
+            text: TextWidget::new(id, 0, shared_config).with_icon("wakeups")?,
+            format: block_config.format.with_default("{wakeups}/s")?,
+            update_interval: block_config.interval,
+            warning: block_config.warning,
+            critical: block_config.critical,
+            previous: None,
+        })
+    }
+}
+
+fn parse_interrupts(contents: &str) -> Vec<Interrupt> {
+    let mut lines = contents.lines();
+    let cpus = lines
+        .next()
+        .map_or(0, |header| header.split_whitespace().count());
+    lines
+        .filter_map(|line| {
+            let (irq, rest) = line.trim_start().split_once(':')?;
+            let mut fields = rest.split_whitespace().peekable();
+            let mut count = 0;
+            // Lines like `ERR` have only one count
+            for _ in 0..cpus {
+                match fields.peek().and_then(|field| field.parse::<u64>().ok()) {
+                    Some(n) => count += n,
+                    None => break,
+                }
+                fields.next();
+            }
+            let rest: Vec<&str> = fields.collect();
+            let name = match irq.parse::<u32>() {
+                // After the interrupt controller and the type, e.g. `IO-APIC 1-edge i8042`
+                Ok(_) => rest.last().copied().unwrap_or_default().to_string(),
+                Err(_) => rest.join(" "),
+            };
+            Some(Interrupt {
+                irq: irq.to_string(),
+                name: if name.is_empty() {
+                    irq.to_string()
+                } else {
+                    name
+                },
+                count,
+            })
+        })
+        .collect()
+}
+
+/// Seconds since boot
+fn uptime() -> Result<f64> {
+    read_file("wakeups", Path::new("/proc/uptime"))?
+        .split_whitespace()
+        .next()
+        .and_then(|uptime| uptime.parse().ok())
+        .block_error("wakeups", "failed to parse /proc/uptime")
+}
+
+impl Block for Wakeups {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let interrupts = parse_interrupts(&read_file("wakeups", Path::new("/proc/interrupts"))?);
+        let now = Instant::now();
+        // The first update shows the average since boot
+        let (seconds, previous) = match self.previous.take() {
+            Some((time, counts)) => (now.duration_since(time).as_secs_f64(), counts),
+            None => (uptime()?, HashMap::new()),
+        };
+        let seconds = seconds.max(0.001);
+
+        let rate = |interrupt: &Interrupt| {
+            let before = previous.get(&interrupt.irq).copied().unwrap_or(0);
+            interrupt.count.saturating_sub(before) as f64 / seconds
+        };
+        let wakeups: f64 = interrupts.iter().map(rate).sum();
+        let top = interrupts
+            .iter()
+            .map(|interrupt| (interrupt, rate(interrupt)))
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+
+        let (top, top_wakeups) = top.map_or((String::new(), 0.0), |(interrupt, rate)| {
+            (interrupt.name.clone(), rate)
+        });
+
+        let values = map!(
+            "wakeups" => Value::from_integer(wakeups.round() as i64),
+            "top" => Value::from_string(top),
+            "top_wakeups" => Value::from_integer(top_wakeups.round() as i64),
+        );
+        self.text.set_state(match wakeups {
+            x if x > self.critical => State::Critical,
+            x if x > self.warning => State::Warning,
+            _ => State::Idle,
+        });
+        self.text.set_format(&self.format, &values)?;
+
+        self.previous = Some((
+            now,
+            interrupts
+                .into_iter()
+                .map(|interrupt| (interrupt.irq, interrupt.count))
+                .collect(),
+        ));
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> usize {
+        self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interrupts() {
+        let contents = "           CPU0       CPU1
+  0:         20          0   IO-APIC   2-edge      timer
+  1:          9         11   IO-APIC   1-edge      i8042
+130:       1200        300   PCI-MSI 327680-edge      xhci_hcd
+NMI:          1          2   Non-maskable interrupts
+LOC:     123456      65432   Local timer interrupts
+ERR:          0
+";
+        let interrupts = parse_interrupts(contents);
+        let find = |irq: &str| interrupts.iter().find(|i| i.irq == irq).unwrap();
+        assert_eq!(interrupts.len(), 6);
+        assert_eq!(find("1").name, "i8042");
+        assert_eq!(find("1").count, 20);
+        assert_eq!(find("130").name, "xhci_hcd");
+        assert_eq!(find("LOC").name, "Local timer interrupts");
+        assert_eq!(find("LOC").count, 188888);
+        assert_eq!(find("ERR").name, "ERR");
+        assert_eq!(find("ERR").count, 0);
+    }
+}
@@ -11,7 +11,7 @@ use crate::de::deserialize_local_timestamp;
 use crate::errors::*;
 use crate::protocol::i3bar_event::I3BarEvent;
 use crate::scheduler::Task;
-use crate::util::xdg_config_home;
+use crate::util::{read_inotify_events, xdg_config_home};
 use crate::widgets::text::TextWidget;
 use crate::widgets::{I3BarWidget, State};
 use chrono::offset::Local;
@@ -100,8 +100,7 @@ impl ConfigBlock for Watson {
 
             let mut buffer = [0; 1024];
             loop {
-                let events = notify
-                    .read_events_blocking(&mut buffer)
+                let events = read_inotify_events(id, &mut notify, &mut buffer)
                     .expect("error while reading inotify events");
 
                 for event in events {
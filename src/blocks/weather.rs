@@ -2,10 +2,13 @@ use std::collections::HashMap;
 use std::env;
 use std::time::Duration;
 
+use serde_json::Value as JsonValue;
+
 use crossbeam_channel::Sender;
 use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
+use crate::cache::{self, Cached};
 use crate::config::SharedConfig;
 use crate::de::deserialize_duration;
 use crate::errors::*;
@@ -14,6 +17,8 @@ use crate::formatting::FormatTemplate;
 use crate::http;
 use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
+use crate::services::Service;
+use crate::topics;
 use crate::widgets::{text::TextWidget, I3BarWidget, State};
 
 const OPENWEATHERMAP_API_KEY_ENV: &str = "OPENWEATHERMAP_API_KEY";
@@ -67,6 +72,9 @@ pub struct Weather {
     service: WeatherService,
     update_interval: Duration,
     autolocate: bool,
+    /// The answer of the last successful update, also shown while the network is down
+    last: Option<Cached<JsonValue>>,
+    cache_key: String,
 }
 
 fn malformed_json_error() -> Error {
@@ -146,12 +154,12 @@ fn convert_wind_direction(direction_opt: Option<f64>) -> String {
     }
 }
 
-fn configuration_error(msg: &str) -> Result<()> {
+fn configuration_error<T>(msg: &str) -> Result<T> {
     Err(ConfigurationError("weather".to_owned(), msg.to_owned()))
 }
 
 impl Weather {
-    fn update_weather(&mut self) -> Result<()> {
+    fn fetch_weather(&self) -> Result<JsonValue> {
         match &self.service {
             WeatherService::OpenWeatherMap {
                 api_key: api_key_opt,
@@ -229,6 +237,15 @@ impl Weather {
                     ));
                 };
 
+                Ok(json)
+            }
+        }
+    }
+
+    /// Sets the placeholders from `json`, an answer of the API
+    fn set_weather(&mut self, json: &JsonValue) -> Result<()> {
+        match &self.service {
+            WeatherService::OpenWeatherMap { units, .. } => {
                 let raw_weather = json
                     .pointer("/weather/0/main")
                     .and_then(|v| v.as_str())
@@ -267,14 +284,23 @@ impl Weather {
                     .map(|s| s.to_string())
                     .ok_or_else(malformed_json_error)?;
 
-                self.weather.set_icon(match raw_weather.as_str() {
-                    "Clear" => "weather_sun",
-                    "Rain" | "Drizzle" => "weather_rain",
-                    "Clouds" | "Fog" | "Mist" => "weather_clouds",
-                    "Thunderstorm" => "weather_thunder",
-                    "Snow" => "weather_snow",
-                    _ => "weather_default",
+                let condition = match raw_weather.as_str() {
+                    "Clear" => "sun",
+                    "Rain" | "Drizzle" => "rain",
+                    "Clouds" | "Fog" | "Mist" => "clouds",
+                    "Thunderstorm" => "thunder",
+                    "Snow" => "snow",
+                    _ => "other",
+                };
+                self.weather.set_icon(match condition {
+                    "other" => "weather_default",
+                    "sun" => "weather_sun",
+                    "rain" => "weather_rain",
+                    "clouds" => "weather_clouds",
+                    "thunder" => "weather_thunder",
+                    _ => "weather_snow",
                 })?;
+                topics::publish(topics::WEATHER, condition);
 
                 let kmh_wind_speed = if *units == OpenWeatherMapUnits::Metric {
                     raw_wind_speed * 3600.0 / 1000.0
@@ -296,11 +322,58 @@ impl Weather {
                     "wind_kmh" => Value::from_float(kmh_wind_speed),
                     "direction" => Value::from_string(convert_wind_direction(raw_wind_direction)),
                     "location" => Value::from_string(raw_location),
+                    "stale" => Value::from_string(String::new()),
                 );
                 Ok(())
             }
         }
     }
+
+    /// Shows the answer of the last successful update, with its age in `{stale}`
+    fn show_last(&mut self) -> Result<bool> {
+        let last = match self.last.clone() {
+            Some(last) => last,
+            None => return Ok(false),
+        };
+        // E.g. the cache of an older version
+        if self.set_weather(&last.data).is_err() {
+            return Ok(false);
+        }
+        self.weather_keys
+            .insert("stale", Value::from_string(last.age()));
+        self.weather.set_format(&self.format, &self.weather_keys)?;
+        self.weather.set_state(State::Warning);
+        Ok(true)
+    }
+}
+
+/// The name of the cached weather, which depends on the location and the units but not on the API
+/// key
+fn cache_key(service: &WeatherService, autolocate: bool) -> String {
+    match service {
+        WeatherService::OpenWeatherMap {
+            city_id,
+            place,
+            coordinates,
+            units,
+            lang,
+            ..
+        } => {
+            let location = match (city_id, place, coordinates) {
+                _ if autolocate => "autolocate".to_string(),
+                (Some(city_id), _, _) => city_id.clone(),
+                (_, Some(place), _) => place.clone(),
+                (_, _, Some((lat, lon))) => format!("{},{}", lat, lon),
+                _ => String::new(),
+            };
+            format!(
+                "weather-{}-{:?}-{}",
+                location,
+                units,
+                lang.as_deref().unwrap_or_default()
+            )
+        }
+    }
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -333,6 +406,7 @@ impl ConfigBlock for Weather {
         shared_config: SharedConfig,
         _tx_update_request: Sender<Task>,
     ) -> Result<Self> {
+        let cache_key = cache_key(&block_config.service, block_config.autolocate);
         Ok(Weather {
             id,
             weather: TextWidget::new(id, 0, shared_config),
@@ -341,23 +415,41 @@ impl ConfigBlock for Weather {
             service: block_config.service,
             update_interval: block_config.interval,
             autolocate: block_config.autolocate,
+            last: cache::load(&cache_key),
+            cache_key,
         })
     }
+
+    fn default_depends_on() -> &'static [Service] {
+        &[Service::Network]
+    }
+
+    fn default_update_on_resume() -> bool {
+        true
+    }
 }
 
 impl Block for Weather {
     fn update(&mut self) -> Result<Option<Update>> {
-        match self.update_weather() {
-            Ok(_) => {
-                self.weather
-                    .set_texts(self.format.render(&self.weather_keys)?);
+        let weather = self.fetch_weather().and_then(|json| {
+            self.set_weather(&json)?;
+            Ok(json)
+        });
+        match weather {
+            Ok(json) => {
+                let last = Cached::new(json);
+                cache::store(&self.cache_key, &last);
+                self.last = Some(last);
+                self.weather.set_format(&self.format, &self.weather_keys)?;
                 self.weather.set_state(State::Idle)
             }
             Err(BlockError(block, _)) | Err(InternalError(block, _, _)) if block == "curl" => {
-                // Ignore curl/api errors
-                self.weather.set_icon("weather_default")?;
-                self.weather.set_text("×".to_string());
-                self.weather.set_state(State::Warning)
+                // Ignore curl/api errors, and show the last weather if there is one
+                if !self.show_last()? {
+                    self.weather.set_icon("weather_default")?;
+                    self.weather.set_text("×".to_string());
+                    self.weather.set_state(State::Warning)
+                }
             }
             Err(err) => {
                 self.weather.set_text(format!("weather error {}:", err));
@@ -368,6 +460,10 @@ impl Block for Weather {
         Ok(Some(self.update_interval.into()))
     }
 
+    fn update_offline(&mut self) -> Result<bool> {
+        self.show_last()
+    }
+
     fn view(&self) -> Vec<&dyn I3BarWidget> {
         vec![&self.weather]
     }
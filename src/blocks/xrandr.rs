@@ -1,4 +1,3 @@
-use std::process::Command;
 use std::str::FromStr;
 use std::time::Duration;
 
@@ -14,7 +13,7 @@ use crate::formatting::value::Value;
 use crate::formatting::FormatTemplate;
 use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
-use crate::subprocess::spawn_child_async;
+use crate::subprocess::{command, spawn_child_async};
 use crate::widgets::text::TextWidget;
 use crate::widgets::I3BarWidget;
 
@@ -103,7 +102,7 @@ macro_rules! unwrap_or_continue {
 impl Xrandr {
     fn get_active_monitors() -> Result<Option<Vec<String>>> {
         let active_monitors_cli = String::from_utf8(
-            Command::new("xrandr")
+            command("xrandr")
                 .args(&["--listactivemonitors"])
                 .output()
                 .block_error("xrandr", "couldn't collect active xrandr monitors")?
@@ -132,7 +131,7 @@ impl Xrandr {
     fn get_monitor_metrics(monitor_names: &[String]) -> Result<Option<Vec<Monitor>>> {
         let mut monitor_metrics: Vec<Monitor> = Vec::new();
         let monitor_info_cli = String::from_utf8(
-            Command::new("xrandr")
+            command("xrandr")
                 .args(&["--verbose"])
                 .output()
                 .block_error("xrandr", "couldn't collect xrandr monitor info")?
@@ -193,7 +192,7 @@ impl Xrandr {
                 "resolution" => Value::from_string(m.resolution.clone()),
                 "res_icon" => Value::from_string(self.shared_config.get_icon("resolution")?),
             );
-            self.text.set_texts(self.format.render(&values)?);
+            self.text.set_format(&self.format, &values)?;
         }
 
         Ok(())
@@ -266,18 +265,19 @@ impl Block for Xrandr {
             }
             mb => {
                 use LogicalDirection::*;
+                let step_width = e.scale_step(self.step_width as f64) as u32;
                 match self.shared_config.scrolling.to_logical_direction(mb) {
                     Some(Up) => {
                         if let Some(monitor) = self.monitors.get_mut(self.current_idx) {
-                            if monitor.brightness <= (100 - self.step_width) {
-                                monitor.set_brightness(self.step_width as i32);
+                            if monitor.brightness <= (100 - step_width) {
+                                monitor.set_brightness(step_width as i32);
                             }
                         }
                     }
                     Some(Down) => {
                         if let Some(monitor) = self.monitors.get_mut(self.current_idx) {
-                            if monitor.brightness >= self.step_width {
-                                monitor.set_brightness(-(self.step_width as i32));
+                            if monitor.brightness >= step_width {
+                                monitor.set_brightness(-(step_width as i32));
                             }
                         }
                     }
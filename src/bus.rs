@@ -0,0 +1,183 @@
+//! The D-Bus interface of the bar, `rs.i3status.StatusBar` on the session bus.
+//!
+//! It's only published with the top-level option `dbus_interface`. The methods are passed to the
+//! main loop like the requests on the socket of `ipc`. Blocks are referenced the same way, by
+//! their `export` name, their type or their position on the bar. The signal `BlockUpdated` is sent
+//! after every update of a block with the id of the block, the `id` of `get-state`, and its
+//! placeholders.
+//!
+//! The thread of the interface sleeps until a message arrives or a block was updated, which the
+//! main thread tells it through a pipe.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::os::unix::io::RawFd;
+use std::thread;
+use std::time::Duration;
+
+use crossbeam_channel::{Receiver, Sender};
+use dbus::blocking::LocalConnection;
+use dbus::channel::{BusType, Channel, Sender as _};
+use dbus::Message;
+use dbus_tree::{Factory, MethodErr};
+use nix::errno::Errno;
+use nix::fcntl::OFlag;
+use nix::poll::{poll, PollFd, PollFlags};
+use nix::unistd;
+
+use crate::errors::*;
+use crate::ipc::{self, Command, Request};
+use crate::registry;
+
+const NAME: &str = "rs.i3status.StatusBar";
+const PATH: &str = "/rs/i3status/StatusBar";
+
+type Update = (u32, HashMap<String, String>);
+
+/// The updates for the thread of the interface, and the pipe that wakes it up
+struct Updates {
+    sender: Sender<Update>,
+    wake: RawFd,
+}
+
+thread_local! {
+    // Set on the main thread, where the blocks are updated
+    static UPDATES: RefCell<Option<Updates>> = const { RefCell::new(None) };
+}
+
+/// Sends `BlockUpdated` for the block `id` with the values it recorded the last time. Sensitive
+/// values are masked in the presentation mode, like on the bar.
+pub fn block_updated(id: usize) {
+    UPDATES.with(|updates| {
+        if let Some(updates) = &*updates.borrow() {
+            let values = registry::plain_values(id);
+            // The bus thread is gone if it failed to connect
+            if updates.sender.send((id as u32, values)).is_ok() {
+                // The pipe doesn't block, and a full pipe wakes the thread up anyway
+                let _ = unistd::write(updates.wake, &[0]);
+            }
+        }
+    });
+}
+
+/// Starts a thread that serves the interface. The name is taken by the first bar, the others use
+/// `rs.i3status.StatusBar.pid<pid>`.
+pub fn serve(requests: Sender<Request>) -> Result<()> {
+    let (tx_updates, rx_updates) = crossbeam_channel::unbounded();
+    let (wakeup, wake) = unistd::pipe2(OFlag::O_NONBLOCK | OFlag::O_CLOEXEC)
+        .internal_error("bus", "failed to create a pipe")?;
+    UPDATES.with(|updates| {
+        *updates.borrow_mut() = Some(Updates {
+            sender: tx_updates,
+            wake,
+        })
+    });
+
+    thread::Builder::new()
+        .name("bus".into())
+        .spawn(move || {
+            // There might be no session bus, e.g. on a bare tty
+            if let Err(e) = run(requests, rx_updates, wakeup) {
+                tracing::warn!("{:?}", e);
+            }
+        })
+        .map(|_| ())
+        .internal_error("bus", "failed to start thread")
+}
+
+fn run(requests: Sender<Request>, rx_updates: Receiver<Update>, wakeup: RawFd) -> Result<()> {
+    let mut channel = Channel::get_private(BusType::Session)
+        .internal_error("bus", "failed to establish D-Bus connection")?;
+    // For the file descriptor of the connection
+    channel.set_watch_enabled(true);
+    let c = LocalConnection::from(channel);
+    if c.request_name(NAME, false, false, true).is_err() {
+        c.request_name(
+            format!("{}.pid{}", NAME, std::process::id()),
+            false,
+            false,
+            true,
+        )
+        .internal_error("bus", "failed to request bus name")?;
+    }
+
+    let f = Factory::new_fn::<()>();
+    let method = |name: &'static str, command: fn(&Message) -> Option<Command>| {
+        let requests = requests.clone();
+        f.method(name, (), move |m| {
+            let command = command(m.msg).ok_or_else(MethodErr::no_arg)?;
+            match ipc::execute(&requests, command) {
+                Ok(_) => Ok(vec![m.msg.method_return()]),
+                Err(e) => Err(MethodErr::failed(&e)),
+            }
+        })
+    };
+    let interface = f
+        .interface(NAME, ())
+        .add_m(
+            method("RefreshBlock", |msg| {
+                Some(Command::Refresh {
+                    block: msg.get1::<String>()?,
+                })
+            })
+            .inarg::<&str, _>("block"),
+        )
+        .add_m(
+            method("SetBlockHidden", |msg| {
+                let (block, hidden) = msg.get2::<String, bool>();
+                Some(match hidden? {
+                    true => Command::Hide { block: block? },
+                    false => Command::Show { block: block? },
+                })
+            })
+            .inarg::<&str, _>("block")
+            .inarg::<bool, _>("hidden"),
+        )
+        // Switches between `format` and `format_expanded`
+        .add_m(
+            method("CycleFormat", |msg| {
+                Some(Command::Action {
+                    block: msg.get1::<String>()?,
+                    action: "toggle_format".to_string(),
+                })
+            })
+            .inarg::<&str, _>("block"),
+        )
+        .add_s(
+            f.signal("BlockUpdated", ())
+                .sarg::<u32, _>("block")
+                .sarg::<HashMap<&str, &str>, _>("values"),
+        );
+    let tree = f
+        .tree(())
+        .add(f.object_path(PATH, ()).introspectable().add(interface))
+        .add(f.object_path("/", ()).introspectable());
+    tree.start_receive(&c);
+
+    let bus = c.channel().watch().fd;
+    let mut buffer = [0; 64];
+    loop {
+        // Handles the messages that arrived, including the ones libdbus read already
+        while c
+            .process(Duration::ZERO)
+            .internal_error("bus", "failed to process D-Bus messages")?
+        {}
+        for (block, values) in rx_updates.try_iter() {
+            let signal = Message::signal(&PATH.into(), &NAME.into(), &"BlockUpdated".into())
+                .append2(block, values);
+            // Fails only if the connection is gone, which `process` reports
+            let _ = c.send(signal);
+        }
+        c.channel().flush();
+
+        let mut fds = [
+            PollFd::new(bus, PollFlags::POLLIN),
+            PollFd::new(wakeup, PollFlags::POLLIN),
+        ];
+        match poll(&mut fds, -1) {
+            Ok(_) | Err(Errno::EINTR) => {}
+            Err(e) => return Err(e).internal_error("bus", "failed to wait for D-Bus messages"),
+        }
+        while let Ok(1..) = unistd::read(wakeup, &mut buffer) {}
+    }
+}
@@ -0,0 +1,98 @@
+//! The last data that blocks fetched from the network, kept in `$XDG_CACHE_HOME/i3status-rs`.
+//!
+//! Blocks show the cached data right away at startup and while the network is down, instead of
+//! nothing, and mark it with their `stale` placeholder. Blocks also keep small bits of state in
+//! it that should outlive a restart, like when `social_inbox` was last read. The cache is only a convenience, so files
+//! that are missing, can't be read or written, or belong to an older version are ignored.
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Cached<T> {
+    pub data: T,
+    /// When the data was fetched
+    pub stored: SystemTime,
+}
+
+impl<T> Cached<T> {
+    pub fn new(data: T) -> Self {
+        Self {
+            data,
+            stored: SystemTime::now(),
+        }
+    }
+
+    /// How old the data is, like `40s`, `5m`, `2h` or `3d`
+    pub fn age(&self) -> String {
+        age(SystemTime::now()
+            .duration_since(self.stored)
+            .unwrap_or_default())
+    }
+}
+
+fn age(age: Duration) -> String {
+    match age.as_secs() {
+        secs if secs < 60 => format!("{}s", secs),
+        secs if secs < 60 * 60 => format!("{}m", secs / 60),
+        secs if secs < 24 * 60 * 60 => format!("{}h", secs / (60 * 60)),
+        secs => format!("{}d", secs / (24 * 60 * 60)),
+    }
+}
+
+fn cache_dir() -> PathBuf {
+    match std::env::var("XDG_CACHE_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var("HOME").unwrap_or_default()).join(".cache"),
+    }
+    .join("i3status-rs")
+}
+
+/// The file of `key`, which is made of the name of the block and what it fetched, e.g. the
+/// location of the weather. Keys must not contain secrets like API keys.
+fn path(key: &str) -> PathBuf {
+    let name: String = key
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+            _ => '_',
+        })
+        .collect();
+    cache_dir().join(format!("{}.json", name))
+}
+
+pub fn load<T: DeserializeOwned>(key: &str) -> Option<Cached<T>> {
+    let file = fs::read_to_string(path(key)).ok()?;
+    serde_json::from_str(&file).ok()
+}
+
+pub fn store<T: Serialize>(key: &str, cached: &Cached<T>) {
+    let path = path(key);
+    let json = match serde_json::to_string(cached) {
+        Ok(json) => json,
+        Err(_) => return,
+    };
+    // Written to another file first, so other bars never read half of it
+    let temporary = path.with_extension(format!("{}.tmp", std::process::id()));
+    let _ = fs::create_dir_all(cache_dir())
+        .and_then(|_| fs::write(&temporary, json))
+        .and_then(|_| fs::rename(&temporary, &path));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ages() {
+        assert_eq!(age(Duration::from_secs(59)), "59s");
+        assert_eq!(age(Duration::from_secs(5 * 60 + 30)), "5m");
+        assert_eq!(age(Duration::from_secs(2 * 60 * 60)), "2h");
+        assert_eq!(age(Duration::from_secs(3 * 24 * 60 * 60 + 1)), "3d");
+        assert!(path("weather-q=New York, US").ends_with("i3status-rs/weather-q_New_York__US.json"));
+    }
+}
@@ -0,0 +1,429 @@
+//! Dates of other calendars for the `time` block, computed locally after "Calendrical
+//! Calculations" by Reingold and Dershowitz.
+//!
+//! Days are counted as in the book, from January 1st of the year 1 (day 1), which is what
+//! `Datelike::num_days_from_ce` returns. The Hijri date is the one of the arithmetical (tabular)
+//! calendar, which can differ by a day from the dates based on sightings of the moon. The Chinese
+//! date is based on the new moons and solar terms in the time zone of China, computed with the
+//! low-accuracy algorithms of Meeus' "Astronomical Algorithms", so it can be wrong for days where a
+//! new moon is within minutes of midnight.
+
+use std::f64::consts::PI;
+
+use chrono::{Datelike, NaiveDate};
+
+const HEBREW_EPOCH: i64 = -1_373_427;
+const ISLAMIC_EPOCH: i64 = 227_015;
+
+const HEBREW_MONTHS: [&str; 13] = [
+    "Nisan",
+    "Iyyar",
+    "Sivan",
+    "Tammuz",
+    "Av",
+    "Elul",
+    "Tishri",
+    "Marheshvan",
+    "Kislev",
+    "Tevet",
+    "Shevat",
+    "Adar",
+    "Adar II",
+];
+
+const ISLAMIC_MONTHS: [&str; 12] = [
+    "Muharram",
+    "Safar",
+    "Rabi' al-awwal",
+    "Rabi' al-thani",
+    "Jumada al-awwal",
+    "Jumada al-thani",
+    "Rajab",
+    "Sha'ban",
+    "Ramadan",
+    "Shawwal",
+    "Dhu al-Qi'dah",
+    "Dhu al-Hijjah",
+];
+
+const STEMS: [&str; 10] = ["甲", "乙", "丙", "丁", "戊", "己", "庚", "辛", "壬", "癸"];
+const BRANCHES: [&str; 12] = [
+    "子", "丑", "寅", "卯", "辰", "巳", "午", "未", "申", "酉", "戌", "亥",
+];
+const ZODIAC: [&str; 12] = [
+    "Rat", "Ox", "Tiger", "Rabbit", "Dragon", "Snake", "Horse", "Goat", "Monkey", "Rooster", "Dog",
+    "Pig",
+];
+const CHINESE_MONTHS: [&str; 12] = [
+    "正月", "二月", "三月", "四月", "五月", "六月", "七月", "八月", "九月", "十月", "冬月", "腊月",
+];
+const CHINESE_DIGITS: [&str; 10] = ["", "一", "二", "三", "四", "五", "六", "七", "八", "九"];
+
+fn fixed(date: NaiveDate) -> i64 {
+    date.num_days_from_ce() as i64
+}
+
+/// `x` modulo `y` in `1..=y`
+fn amod(x: i64, y: i64) -> i64 {
+    (x - 1).rem_euclid(y) + 1
+}
+
+// Hebrew
+
+fn hebrew_leap_year(year: i64) -> bool {
+    (7 * year + 1).rem_euclid(19) < 7
+}
+
+fn last_hebrew_month(year: i64) -> i64 {
+    if hebrew_leap_year(year) {
+        13
+    } else {
+        12
+    }
+}
+
+fn hebrew_elapsed_days(year: i64) -> i64 {
+    let months = (235 * year - 234).div_euclid(19);
+    let parts = 12084 + 13753 * months;
+    let days = 29 * months + parts.div_euclid(25920);
+    if (3 * (days + 1)).rem_euclid(7) < 3 {
+        days + 1
+    } else {
+        days
+    }
+}
+
+fn hebrew_new_year(year: i64) -> i64 {
+    let correction = if hebrew_elapsed_days(year + 1) - hebrew_elapsed_days(year) == 356 {
+        2
+    } else if hebrew_elapsed_days(year) - hebrew_elapsed_days(year - 1) == 382 {
+        1
+    } else {
+        0
+    };
+    HEBREW_EPOCH + hebrew_elapsed_days(year) + correction
+}
+
+fn hebrew_month_length(year: i64, month: i64) -> i64 {
+    let year_length = hebrew_new_year(year + 1) - hebrew_new_year(year);
+    let short = match month {
+        2 | 4 | 6 | 10 | 13 => true,
+        12 => !hebrew_leap_year(year),
+        8 => year_length != 355 && year_length != 385,
+        9 => year_length == 353 || year_length == 383,
+        _ => false,
+    };
+    if short {
+        29
+    } else {
+        30
+    }
+}
+
+fn fixed_from_hebrew(year: i64, month: i64, day: i64) -> i64 {
+    // The year starts with Tishri, the 7th month
+    let months: i64 = if month < 7 {
+        (7..=last_hebrew_month(year))
+            .chain(1..month)
+            .map(|m| hebrew_month_length(year, m))
+            .sum()
+    } else {
+        (7..month).map(|m| hebrew_month_length(year, m)).sum()
+    };
+    hebrew_new_year(year) + months + day - 1
+}
+
+/// The Hebrew date, e.g. `1 Tishri 5785`
+pub fn hebrew(date: NaiveDate) -> String {
+    let date = fixed(date);
+    let approx = ((date - HEBREW_EPOCH) as f64 / (35_975_351. / 98_496.)).floor() as i64 + 1;
+    let year = (approx - 1..)
+        .take_while(|&year| hebrew_new_year(year) <= date)
+        .last()
+        .unwrap();
+    let start = if date < fixed_from_hebrew(year, 1, 1) {
+        7
+    } else {
+        1
+    };
+    let month = (start..)
+        .find(|&m| date <= fixed_from_hebrew(year, m, hebrew_month_length(year, m)))
+        .unwrap();
+    let day = date - fixed_from_hebrew(year, month, 1) + 1;
+    let name = match month {
+        12 if hebrew_leap_year(year) => "Adar I",
+        _ => HEBREW_MONTHS[month as usize - 1],
+    };
+    format!("{} {} {}", day, name, year)
+}
+
+// Islamic
+
+fn fixed_from_islamic(year: i64, month: i64, day: i64) -> i64 {
+    day + 29 * (month - 1)
+        + (6 * month - 1).div_euclid(11)
+        + (year - 1) * 354
+        + (3 + 11 * year).div_euclid(30)
+        + ISLAMIC_EPOCH
+        - 1
+}
+
+/// The date of the tabular Hijri calendar, e.g. `1 Muharram 1446`
+pub fn hijri(date: NaiveDate) -> String {
+    let date = fixed(date);
+    let year = (30 * (date - ISLAMIC_EPOCH) + 10646).div_euclid(10631);
+    let prior_days = date - fixed_from_islamic(year, 1, 1);
+    let month = (11 * prior_days + 330).div_euclid(325);
+    let day = date - fixed_from_islamic(year, month, 1) + 1;
+    format!("{} {} {}", day, ISLAMIC_MONTHS[month as usize - 1], year)
+}
+
+// Chinese
+
+/// Julian day of the beginning of day 0
+const FIXED_EPOCH_JD: f64 = 1_721_424.5;
+const MEAN_SYNODIC_MONTH: f64 = 29.530_588_861;
+/// China Standard Time, UTC+8
+const CHINA_OFFSET: f64 = 8. / 24.;
+/// TT - UT in days, about its value in this century
+const DELTA_T: f64 = 69. / 86_400.;
+
+fn sin_deg(degrees: f64) -> f64 {
+    (degrees * PI / 180.).sin()
+}
+
+/// The moment (Julian day in UT) of the new moon number `k`, counting from January 2000
+fn new_moon(k: f64) -> f64 {
+    let t = k / 1236.85;
+    let jde = 2_451_550.097_66 + MEAN_SYNODIC_MONTH * k + 0.000_154_37 * t * t
+        - 0.000_000_150 * t.powi(3)
+        + 0.000_000_000_73 * t.powi(4);
+    let e = 1. - 0.002_516 * t - 0.000_007_4 * t * t;
+    let m = 2.5534 + 29.105_356_70 * k - 0.000_001_4 * t * t;
+    let mp = 201.5643 + 385.816_935_28 * k + 0.010_758_2 * t * t + 0.000_012_38 * t.powi(3);
+    let f = 160.7108 + 390.670_502_84 * k - 0.001_611_8 * t * t - 0.000_002_27 * t.powi(3);
+    let omega = 124.7746 - 1.563_755_88 * k + 0.002_067_2 * t * t;
+    let correction = -0.40720 * sin_deg(mp)
+        + 0.17241 * e * sin_deg(m)
+        + 0.01608 * sin_deg(2. * mp)
+        + 0.01039 * sin_deg(2. * f)
+        + 0.00739 * e * sin_deg(mp - m)
+        - 0.00514 * e * sin_deg(mp + m)
+        + 0.00208 * e * e * sin_deg(2. * m)
+        - 0.00111 * sin_deg(mp - 2. * f)
+        - 0.00057 * sin_deg(mp + 2. * f)
+        + 0.00056 * e * sin_deg(2. * mp + m)
+        - 0.00042 * sin_deg(3. * mp)
+        + 0.00042 * e * sin_deg(m + 2. * f)
+        + 0.00038 * e * sin_deg(m - 2. * f)
+        - 0.00024 * e * sin_deg(2. * mp - m)
+        - 0.00017 * sin_deg(omega)
+        - 0.00007 * sin_deg(mp + 2. * m)
+        + 0.00004 * sin_deg(2. * mp - 2. * f)
+        + 0.00004 * sin_deg(3. * m)
+        + 0.00003 * sin_deg(mp + m - 2. * f)
+        + 0.00003 * sin_deg(2. * mp + 2. * f)
+        - 0.00003 * sin_deg(mp + m + 2. * f)
+        + 0.00003 * sin_deg(mp - m + 2. * f)
+        - 0.00002 * sin_deg(mp - m - 2. * f)
+        - 0.00002 * sin_deg(3. * mp + m)
+        + 0.00002 * sin_deg(4. * mp);
+    jde + correction - DELTA_T
+}
+
+/// The apparent longitude of the sun in degrees at the moment `jd`
+fn solar_longitude(jd: f64) -> f64 {
+    let t = (jd + DELTA_T - 2_451_545.) / 36525.;
+    let l0 = 280.466_46 + 36_000.769_83 * t + 0.000_303_2 * t * t;
+    let m = 357.529_11 + 35_999.050_29 * t - 0.000_153_7 * t * t;
+    let c = (1.914_602 - 0.004_817 * t - 0.000_014 * t * t) * sin_deg(m)
+        + (0.019_993 - 0.000_101 * t) * sin_deg(2. * m)
+        + 0.000_289 * sin_deg(3. * m);
+    let omega = 125.04 - 1934.136 * t;
+    (l0 + c - 0.005_69 - 0.004_78 * sin_deg(omega)).rem_euclid(360.)
+}
+
+/// The moment of the beginning of the day `date` in China
+fn china_midnight(date: i64) -> f64 {
+    date as f64 + FIXED_EPOCH_JD - CHINA_OFFSET
+}
+
+/// The day in China of the moment `jd`
+fn china_day(jd: f64) -> i64 {
+    (jd + CHINA_OFFSET - FIXED_EPOCH_JD).floor() as i64
+}
+
+/// The first day of the month that contains the day `date`
+fn new_moon_on_or_before(date: i64) -> i64 {
+    let end = china_midnight(date + 1);
+    let mut k = ((end - 2_451_550.1) / MEAN_SYNODIC_MONTH).floor() + 1.;
+    while new_moon(k) >= end {
+        k -= 1.;
+    }
+    china_day(new_moon(k))
+}
+
+/// The first day of the next month after the day `date`
+fn new_moon_after(date: i64) -> i64 {
+    new_moon_on_or_before(date + 31).min(new_moon_on_or_before(new_moon_on_or_before(date) + 45))
+}
+
+/// The day of the winter solstice in China on or before the day `date`
+fn winter_solstice_on_or_before(date: i64) -> i64 {
+    let year = NaiveDate::from_num_days_from_ce_opt(date as i32)
+        .map(|d| d.year())
+        .unwrap_or(2000);
+    let solstice = |year: i32| {
+        // Between December 15 and 28, where the longitude is increasing through 270°
+        let mut low = china_midnight(fixed(NaiveDate::from_ymd_opt(year, 12, 15).unwrap()));
+        let mut high = low + 13.;
+        while high - low > 1. / 1440. {
+            let mid = (low + high) / 2.;
+            if solar_longitude(mid) < 270. {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        china_day(high)
+    };
+    match solstice(year) {
+        day if day <= date => day,
+        _ => solstice(year - 1),
+    }
+}
+
+/// The index of the major solar term at the beginning of the day `date`
+fn major_solar_term(date: i64) -> i64 {
+    (solar_longitude(china_midnight(date)) / 30.).floor() as i64
+}
+
+/// Whether the month that starts on `month` contains no major solar term
+fn no_major_solar_term(month: i64) -> bool {
+    major_solar_term(month) == major_solar_term(new_moon_after(month))
+}
+
+/// Whether there is a leap month from the month that starts on `first` up to the one that starts
+/// on `month`
+fn prior_leap_month(first: i64, month: i64) -> bool {
+    month >= first
+        && (no_major_solar_term(month) || prior_leap_month(first, new_moon_on_or_before(month - 1)))
+}
+
+/// A date of the Chinese calendar
+#[derive(Debug, PartialEq, Eq)]
+pub struct Chinese {
+    pub year: i32,
+    pub month: u32,
+    pub leap_month: bool,
+    pub day: u32,
+}
+
+impl Chinese {
+    pub fn new(date: NaiveDate) -> Self {
+        let gregorian_year = date.year();
+        let date = fixed(date);
+        let s1 = winter_solstice_on_or_before(date);
+        let s2 = winter_solstice_on_or_before(s1 + 370);
+        // The month after the one with the winter solstice, which is always the 11th month
+        let m12 = new_moon_after(s1);
+        let next_m11 = new_moon_on_or_before(s2);
+        let month_start = new_moon_on_or_before(date);
+        let leap_year = ((next_m11 - m12) as f64 / MEAN_SYNODIC_MONTH).round() as i64 == 12;
+        let months = ((month_start - m12) as f64 / MEAN_SYNODIC_MONTH).round() as i64;
+        let leap_before = leap_year && prior_leap_month(m12, month_start);
+        let month = amod(months - leap_before as i64, 12);
+        let leap_month = leap_year
+            && no_major_solar_term(month_start)
+            && !prior_leap_month(m12, new_moon_on_or_before(month_start - 1));
+        let year = match month {
+            // From the winter solstice to the new year
+            11 | 12 if date < fixed(NaiveDate::from_ymd_opt(gregorian_year, 3, 1).unwrap()) => {
+                gregorian_year - 1
+            }
+            _ => gregorian_year,
+        };
+        Chinese {
+            year,
+            month: month as u32,
+            leap_month,
+            day: (date - month_start + 1) as u32,
+        }
+    }
+
+    /// The month and the day, e.g. `正月初一`
+    pub fn date(&self) -> String {
+        let day = match self.day {
+            10 => "初十".to_string(),
+            20 => "二十".to_string(),
+            30 => "三十".to_string(),
+            day => {
+                let tens = ["初", "十", "廿"][day as usize / 10];
+                format!("{}{}", tens, CHINESE_DIGITS[day as usize % 10])
+            }
+        };
+        let leap = if self.leap_month { "闰" } else { "" };
+        format!("{}{}{}", leap, CHINESE_MONTHS[self.month as usize - 1], day)
+    }
+
+    /// The year in the sexagenary cycle, e.g. `甲辰`
+    pub fn year_name(&self) -> String {
+        let cycle = (self.year - 4).rem_euclid(60) as usize;
+        format!("{}{}", STEMS[cycle % 10], BRANCHES[cycle % 12])
+    }
+
+    /// The animal of the year in English, e.g. `Dragon`
+    pub fn zodiac(&self) -> &'static str {
+        ZODIAC[(self.year - 4).rem_euclid(12) as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn hebrew_dates() {
+        assert_eq!(hebrew(date(2024, 10, 3)), "1 Tishri 5785");
+        assert_eq!(hebrew(date(2025, 9, 23)), "1 Tishri 5786");
+        assert_eq!(hebrew(date(2024, 4, 23)), "15 Nisan 5784");
+        assert_eq!(hebrew(date(2024, 3, 24)), "14 Adar II 5784");
+        assert_eq!(hebrew(date(2024, 2, 23)), "14 Adar I 5784");
+    }
+
+    #[test]
+    fn hijri_dates() {
+        assert_eq!(hijri(date(622, 7, 19)), "1 Muharram 1");
+        assert_eq!(hijri(date(2024, 7, 7)), "30 Dhu al-Hijjah 1445");
+        assert_eq!(hijri(date(2024, 7, 8)), "1 Muharram 1446");
+        assert_eq!(hijri(date(2025, 3, 1)), "1 Ramadan 1446");
+    }
+
+    #[test]
+    fn chinese_dates() {
+        let new_year = Chinese::new(date(2024, 2, 10));
+        assert_eq!(
+            new_year,
+            Chinese {
+                year: 2024,
+                month: 1,
+                leap_month: false,
+                day: 1
+            }
+        );
+        assert_eq!(new_year.date(), "正月初一");
+        assert_eq!(new_year.year_name(), "甲辰");
+        assert_eq!(new_year.zodiac(), "Dragon");
+        assert_eq!(Chinese::new(date(2024, 2, 9)).date(), "腊月三十");
+        assert_eq!(Chinese::new(date(2024, 2, 9)).year, 2023);
+        assert_eq!(Chinese::new(date(2025, 1, 28)).date(), "腊月廿九");
+        assert_eq!(Chinese::new(date(2023, 3, 22)).date(), "闰二月初一");
+        assert_eq!(Chinese::new(date(2023, 4, 20)).date(), "三月初一");
+        assert_eq!(Chinese::new(date(2020, 5, 23)).date(), "闰四月初一");
+        assert_eq!(Chinese::new(date(2023, 12, 22)).date(), "冬月初十");
+    }
+}
@@ -0,0 +1,112 @@
+//! `i3status-rs check`, which finds the mistakes in a configuration before the bar shows them as
+//! errors: options the blocks don't have, invalid formats, icons missing from the icon set, and
+//! with `--update` also the errors of the first update of every block.
+
+use std::path::Path;
+
+use crossbeam_channel::{Receiver, Sender};
+
+use crate::blocks::{create_block, Block};
+use crate::config::{Config, SharedConfig};
+use crate::errors::*;
+use crate::icons::Icons;
+use crate::includes;
+use crate::scheduler::Task;
+
+/// Checks the configuration file at `path` and prints the problems. Returns the number of errors.
+pub fn check(path: &Path, update: bool) -> usize {
+    let config: Result<Config> = includes::read(path).and_then(|included| {
+        included
+            .value
+            .try_into()
+            .configuration_error("failed to parse the configuration")
+    });
+    let mut config = match config {
+        Ok(config) => config,
+        Err(e) => {
+            println!("{}: {:?}", path.display(), e);
+            return 1;
+        }
+    };
+    // All blocks are checked, whether they would be created on this machine or not
+    config.ignore_conditions();
+    crate::formatting::width::set_private_use_width(config.private_use_width);
+    crate::http::configure(config.http.clone());
+
+    // Icons that are missing only show up once a block is in the state that needs them
+    let mut missing: Vec<String> = Icons::default()
+        .0
+        .into_keys()
+        .filter(|icon| !config.icons.0.contains_key(icon))
+        .collect();
+    missing.sort();
+    if !missing.is_empty() {
+        println!(
+            "warning: the icon set has no {}, blocks that use them show an error",
+            missing.join(", ")
+        );
+    }
+
+    let (tx_update_requests, _rx_update_requests): (Sender<Task>, Receiver<Task>) =
+        crossbeam_channel::unbounded();
+    let shared_config = SharedConfig::new(&config);
+    let mut errors = 0;
+    let mut blocks: Vec<Box<dyn Block>> = Vec::new();
+    for (position, (name, block_config)) in config.blocks.iter().enumerate() {
+        let description = match block_config.get("export").and_then(|name| name.as_str()) {
+            Some(export) => format!("block {} ({}, exported as '{}')", position, name, export),
+            None => format!("block {} ({})", position, name),
+        };
+        // The errors are reported instead of being shown in the blocks
+        let mut block_config = block_config.clone();
+        if let Some(table) = block_config.as_table_mut() {
+            table.insert("error_fullscreen".to_string(), toml::Value::Boolean(true));
+        }
+        let result = create_block(
+            position,
+            name,
+            block_config,
+            shared_config.clone(),
+            tx_update_requests.clone(),
+        )
+        .and_then(|mut block| match update {
+            true => block.update().map(|_| block),
+            false => Ok(block),
+        });
+        match result {
+            Ok(block) => blocks.push(block),
+            Err(e) => {
+                println!("{}: {:?}", description, e);
+                errors += 1;
+            }
+        }
+    }
+
+    // The positions are only right if all blocks were created
+    if errors == 0 {
+        if let Err(e) = crate::bar_blocks(&blocks, &config) {
+            println!("invalid [[bar]]: {}", e);
+            errors += 1;
+        }
+        for profile in &config.profiles {
+            if let Err(e) = crate::profile_blocks(&blocks, &config, &profile.name) {
+                println!("invalid [[profile]]: {}", e);
+                errors += 1;
+            }
+        }
+        if let Some(name) = &config.default_profile {
+            if !config.profiles.iter().any(|profile| profile.name == *name) {
+                println!("invalid default_profile: there is no profile '{}'", name);
+                errors += 1;
+            }
+        }
+    }
+
+    match errors {
+        0 if blocks.len() == 1 => println!("{}: 1 block, no errors", path.display()),
+        0 => println!("{}: {} blocks, no errors", path.display(), blocks.len()),
+        1 => println!("{}: 1 error", path.display()),
+        _ => println!("{}: {} errors", path.display(), errors),
+    }
+    errors
+}
@@ -0,0 +1,588 @@
+//! Click handlers of the blocks and the detection of double clicks.
+
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use serde_derive::Deserialize;
+
+use crate::formatting::FormatTemplate;
+use crate::protocol::i3bar_event::{I3BarEvent, MouseButton};
+
+/// Set by `--debug-clicks`, clicks are logged and their actions are printed instead of executed
+static DEBUG: AtomicBool = AtomicBool::new(false);
+
+pub fn debug() -> bool {
+    DEBUG.load(Ordering::Relaxed)
+}
+
+pub fn set_debug(debug: bool) {
+    DEBUG.store(debug, Ordering::Relaxed);
+}
+
+thread_local! {
+    static UNHANDLED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Marks the click that is being dispatched as not handled by the block
+pub fn set_unhandled() {
+    UNHANDLED.with(|unhandled| unhandled.set(true));
+}
+
+/// Whether the click that was dispatched last wasn't handled, which resets it
+pub fn take_unhandled() -> bool {
+    UNHANDLED.with(|unhandled| unhandled.replace(false))
+}
+
+/// The JSON passed to `on_click_fallthrough`: the event sent by the bar, with the type of the
+/// block in `block`
+pub fn fallthrough_payload(event: &I3BarEvent, block: Option<&str>) -> String {
+    let mut payload: serde_json::Map<String, serde_json::Value> = event
+        .raw
+        .as_deref()
+        .and_then(|raw| serde_json::from_str(raw).ok())
+        .unwrap_or_default();
+    if let Some(block) = block {
+        payload.insert("block".to_string(), block.into());
+    }
+    serde_json::Value::Object(payload).to_string()
+}
+
+/// A binding from the `click` option of a block
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ClickHandler {
+    pub button: MouseButton,
+    /// Only clicks on this part of the widget
+    pub region: Option<Region>,
+    /// Only clicks on the widget with this name, for blocks with several widgets
+    pub segment: Option<String>,
+    /// Command to run with `sh -c`
+    pub cmd: Option<String>,
+    /// Update the block after the click
+    #[serde(default)]
+    pub update: bool,
+    /// Only run the command if the block is clicked again within `CONFIRM_TIMEOUT`
+    #[serde(default)]
+    pub confirm: bool,
+}
+
+/// The `popup` option of a block, which passes details of the block to a command like `rofi` or
+/// `dunstify`
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Popup {
+    /// Rendered with the placeholders of the block and written to the stdin of `cmd`
+    pub format: FormatTemplate,
+    pub cmd: String,
+    #[serde(default = "Popup::default_button")]
+    pub button: MouseButton,
+}
+
+impl Popup {
+    fn default_button() -> MouseButton {
+        MouseButton::Left
+    }
+}
+
+/// How long a click binding with `confirm` waits for the second click
+pub const CONFIRM_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Part of a widget, either a third of it or a range from 0 (left edge) to 1 (right edge)
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Region {
+    Third(Third),
+    Range([f64; 2]),
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Third {
+    Left,
+    Middle,
+    Right,
+}
+
+impl Region {
+    fn contains(&self, position: f64) -> bool {
+        let [from, to] = match self {
+            Region::Third(Third::Left) => [0.0, 1.0 / 3.0],
+            Region::Third(Third::Middle) => [1.0 / 3.0, 2.0 / 3.0],
+            Region::Third(Third::Right) => [2.0 / 3.0, 1.0],
+            Region::Range(range) => *range,
+        };
+        from <= position && (position < to || to >= 1.0)
+    }
+}
+
+impl ClickHandler {
+    /// Whether the handler is responsible for `event` on the widget named `segment`. Handlers with
+    /// a region never match clicks without a position, which are sent by old versions of the bars.
+    pub fn matches(&self, event: &I3BarEvent, segment: Option<&str>) -> bool {
+        self.button == event.button
+            && (self.segment.is_none() || self.segment.as_deref() == segment)
+            && match (&self.region, event.position) {
+                (None, _) => true,
+                (Some(region), Some(position)) => region.contains(position),
+                (Some(_), None) => false,
+            }
+    }
+}
+
+/// Replaces `$name` and `${name}` in a command with a reference to the environment variable
+/// `I3RS_name`, and returns the variables with the results of `lookup` to be passed to the child.
+/// The values never become part of the command, so the shell doesn't run a song title like
+/// `$(reboot)`. The reference fits the quotes around it, so it works in single quotes, in double
+/// quotes and without quotes. Unknown names are left alone, so variables of the shell still work,
+/// and `$I3RS_name` can be written directly.
+pub fn substitute(
+    cmd: &str,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> (String, Vec<(String, String)>) {
+    let is_name = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut result = String::with_capacity(cmd.len());
+    let mut vars: Vec<(String, String)> = Vec::new();
+    // The quote the shell is within at this point of the command
+    let mut quote = None;
+    let mut rest = cmd;
+    while let Some(c) = rest.chars().next() {
+        let after = &rest[c.len_utf8()..];
+        match (c, quote) {
+            // Backslashes escape the next character, except in single quotes
+            ('\\', None | Some('"')) => {
+                let escaped = after.chars().next().map_or(0, char::len_utf8);
+                result.push_str(&rest[..1 + escaped]);
+                rest = &after[escaped..];
+                continue;
+            }
+            ('\'' | '"', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            ('$', _) => {
+                // `${block.placeholder}` refers to an exported block, so it can contain a dot
+                let (name, len) = match after.strip_prefix('{') {
+                    Some(braced) => match braced.find('}') {
+                        Some(end) => (&braced[..end], end + 2),
+                        None => ("", 0),
+                    },
+                    None => {
+                        let end = after.find(|c| !is_name(c)).unwrap_or(after.len());
+                        (&after[..end], end)
+                    }
+                };
+                if let Some(value) = lookup(name).filter(|_| !name.is_empty()) {
+                    let var = format!("I3RS_{}", name.replace('.', "_"));
+                    match quote {
+                        None => result.push_str(&format!("\"${{{}}}\"", var)),
+                        Some('\'') => result.push_str(&format!("'\"${{{}}}\"'", var)),
+                        _ => result.push_str(&format!("${{{}}}", var)),
+                    }
+                    if !vars.iter().any(|(known, _)| *known == var) {
+                        vars.push((var, value));
+                    }
+                    rest = &after[len..];
+                    continue;
+                }
+                // `$I3RS_name` is used as it is, but the variable has to be set
+                if let Some(value) = name.strip_prefix("I3RS_").and_then(&lookup) {
+                    if !vars.iter().any(|(known, _)| known == name) {
+                        vars.push((name.to_string(), value));
+                    }
+                }
+            }
+            _ => (),
+        }
+        result.push(c);
+        rest = after;
+    }
+    (result, vars)
+}
+
+/// Wheel events that follow each other within this time are counted as rapid scrolling
+const SCROLL_STREAK_TIMEOUT: Duration = Duration::from_millis(150);
+/// Limit for the acceleration, so a long scroll doesn't jump to the end of the range
+const MAX_SCROLL_STREAK: u32 = 10;
+
+/// Computes the factor for the steps of scroll actions from the `scroll_step` and `scroll_accel`
+/// options of a block
+#[derive(Debug)]
+pub struct ScrollAcceleration {
+    step: f64,
+    accel: f64,
+    last: Option<(MouseButton, Instant)>,
+    streak: u32,
+}
+
+impl ScrollAcceleration {
+    pub fn new(step: f64, accel: f64) -> Self {
+        Self {
+            step,
+            accel,
+            last: None,
+            streak: 0,
+        }
+    }
+
+    /// Sets the `scroll_factor` of wheel events
+    pub fn apply(&mut self, event: &mut I3BarEvent) {
+        if !matches!(event.button, MouseButton::WheelUp | MouseButton::WheelDown) {
+            return;
+        }
+        let now = Instant::now();
+        self.streak = match self.last {
+            Some((button, time))
+                if button == event.button && now.duration_since(time) < SCROLL_STREAK_TIMEOUT =>
+            {
+                (self.streak + 1).min(MAX_SCROLL_STREAK)
+            }
+            _ => 0,
+        };
+        self.last = Some((event.button, now));
+        event.scroll_factor = self.step * (1.0 + self.accel * self.streak as f64);
+    }
+}
+
+/// Clicks on buttons that have a double click binding are held back, until either the second
+/// click arrives or the interval has passed. Only then it is known whether it was a single click.
+///
+/// With a `hold_interval`, presses of buttons that have a hold binding are held back as well,
+/// until the button is released (see `release`). The press is a click if the release arrives
+/// within the interval, and a hold when the interval passes without one.
+pub struct ClickDispatcher {
+    interval: Duration,
+    hold_interval: Option<Duration>,
+    pending: Option<(I3BarEvent, Instant)>,
+    /// The press, whether the block wants double clicks, and when it becomes a hold
+    held: Option<(I3BarEvent, bool, Instant)>,
+}
+
+impl ClickDispatcher {
+    pub fn new(interval: Duration, hold_interval: Option<Duration>) -> Self {
+        Self {
+            interval,
+            hold_interval,
+            pending: None,
+            held: None,
+        }
+    }
+
+    /// Returns the events that can be delivered now. `wants_double_click` and `wants_hold` tell
+    /// whether the clicked block has a double click or a hold binding for the button.
+    pub fn push(
+        &mut self,
+        event: I3BarEvent,
+        wants_double_click: bool,
+        wants_hold: bool,
+    ) -> Vec<I3BarEvent> {
+        // The release of the held press was missed, so it was a click
+        let mut ready = match self.held.take() {
+            Some((held, wants_double_click, _)) => self.click(held, wants_double_click),
+            None => Vec::new(),
+        };
+        match self.hold_interval {
+            Some(hold_interval) if wants_hold && event.button.hold() != event.button => {
+                self.held = Some((event, wants_double_click, Instant::now() + hold_interval));
+            }
+            _ => ready.extend(self.click(event, wants_double_click)),
+        }
+        ready
+    }
+
+    /// Releases the held press of `button`, or of any button. Returns the events that can be
+    /// delivered now.
+    pub fn release(&mut self, button: Option<MouseButton>) -> Vec<I3BarEvent> {
+        match self.held.take() {
+            Some((held, wants_double_click, deadline))
+                if button.is_none_or(|button| button == held.button)
+                    && Instant::now() < deadline =>
+            {
+                self.click(held, wants_double_click)
+            }
+            held => {
+                self.held = held;
+                Vec::new()
+            }
+        }
+    }
+
+    fn click(&mut self, event: I3BarEvent, wants_double_click: bool) -> Vec<I3BarEvent> {
+        let mut ready = Vec::new();
+
+        if let Some((pending, deadline)) = self.pending.take() {
+            let same_target = pending.id == event.id
+                && pending.instance == event.instance
+                && pending.button == event.button;
+            if same_target && Instant::now() < deadline {
+                ready.push(I3BarEvent {
+                    button: pending.button.double(),
+                    ..pending
+                });
+                return ready;
+            }
+            // Another click in the meantime, so the pending one was a single click
+            ready.push(pending);
+        }
+
+        if wants_double_click && event.button.double() != event.button {
+            self.pending = Some((event, Instant::now() + self.interval));
+        } else {
+            ready.push(event);
+        }
+        ready
+    }
+
+    /// Time until the pending click has to be delivered as a single click, or the held press as
+    /// a hold
+    pub fn timeout(&self) -> Option<Duration> {
+        let pending = self.pending.as_ref().map(|(_, deadline)| *deadline);
+        let held = self.held.as_ref().map(|(_, _, deadline)| *deadline);
+        pending
+            .into_iter()
+            .chain(held)
+            .min()
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /// The pending click, if it is not going to be a double click anymore, and the held press, if
+    /// it was held long enough
+    pub fn expire(&mut self) -> Vec<I3BarEvent> {
+        let now = Instant::now();
+        let mut ready = Vec::new();
+        if let Some((_, deadline)) = self.pending {
+            if deadline <= now {
+                ready.extend(self.pending.take().map(|(event, _)| event));
+            }
+        }
+        if let Some((_, _, deadline)) = self.held {
+            if deadline <= now {
+                ready.extend(self.held.take().map(|(event, _, _)| I3BarEvent {
+                    button: event.button.hold(),
+                    ..event
+                }));
+            }
+        }
+        ready
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn click(id: usize, button: MouseButton) -> I3BarEvent {
+        I3BarEvent {
+            id: Some(id),
+            instance: None,
+            button,
+            position: None,
+            scroll_factor: 1.0,
+            raw: None,
+        }
+    }
+
+    #[test]
+    fn substitution() {
+        let lookup = |name: &str| match name {
+            "volume" => Some("42".to_string()),
+            "cpu.utilization" => Some("7".to_string()),
+            "title" => Some("it's \"$(echo INJECTED)\" `echo INJECTED`".to_string()),
+            _ => None,
+        };
+        let (cmd, vars) = substitute("notify-send 'Volume is $volume%'", lookup);
+        assert_eq!(cmd, r#"notify-send 'Volume is '"${I3RS_volume}"'%'"#);
+        assert_eq!(vars, [("I3RS_volume".to_string(), "42".to_string())]);
+        let (cmd, vars) = substitute("echo ${volume}0 ${cpu.utilization} $volume", lookup);
+        assert_eq!(
+            cmd,
+            r#"echo "${I3RS_volume}"0 "${I3RS_cpu_utilization}" "${I3RS_volume}""#
+        );
+        assert_eq!(vars.len(), 2);
+        assert_eq!(
+            substitute(r#"echo $HOME $volumes ${nope} \$volume $ ${"#, lookup).0,
+            r#"echo $HOME $volumes ${nope} \$volume $ ${"#
+        );
+
+        // The shell passes the value on as it is, wherever it is in the command
+        let run = |cmd: &str| {
+            let (cmd, vars) = substitute(cmd, lookup);
+            let output = std::process::Command::new("sh")
+                .args(["-c", &cmd])
+                .envs(vars)
+                .output()
+                .unwrap();
+            String::from_utf8(output.stdout).unwrap()
+        };
+        let title = lookup("title").unwrap();
+        assert_eq!(run("printf %s $title"), title);
+        assert_eq!(
+            run("printf %s 'Playing $title'"),
+            format!("Playing {}", title)
+        );
+        assert_eq!(
+            run(r#"printf %s "Playing \"$title\" at $volume%""#),
+            format!("Playing \"{}\" at 42%", title)
+        );
+        assert_eq!(run(r#"printf %s "$I3RS_title""#), title);
+    }
+
+    #[test]
+    fn regions() {
+        let handler: ClickHandler =
+            toml::from_str("button = \"left\"\nregion = \"middle\"").unwrap();
+        let at = |position| I3BarEvent {
+            position,
+            ..click(0, MouseButton::Left)
+        };
+        assert!(handler.matches(&at(Some(0.5)), None));
+        assert!(!handler.matches(&at(Some(0.2)), None));
+        assert!(!handler.matches(&at(None), None));
+
+        let handler: ClickHandler =
+            toml::from_str("button = \"left\"\nregion = [0.75, 1.0]").unwrap();
+        assert!(handler.matches(&at(Some(1.0)), None));
+        assert!(!handler.matches(&at(Some(0.5)), None));
+
+        let handler: ClickHandler =
+            toml::from_str("button = \"left\"\nsegment = \"next\"").unwrap();
+        assert!(handler.matches(&at(None), Some("next")));
+        assert!(!handler.matches(&at(None), Some("prev")));
+        assert!(!handler.matches(&at(None), None));
+    }
+
+    #[test]
+    fn scroll_acceleration() {
+        let mut scroll = ScrollAcceleration::new(0.5, 1.0);
+        let mut factors = Vec::new();
+        for button in [
+            MouseButton::WheelUp,
+            MouseButton::WheelUp,
+            MouseButton::WheelDown,
+        ] {
+            let mut event = click(0, button);
+            scroll.apply(&mut event);
+            factors.push(event.scroll_factor);
+        }
+        // Changing the direction resets the acceleration
+        assert_eq!(factors, [0.5, 1.0, 0.5]);
+        assert_eq!(click(0, MouseButton::WheelUp).scale_step(5.0), 5.0);
+        let event = I3BarEvent {
+            scroll_factor: 0.1,
+            ..click(0, MouseButton::WheelUp)
+        };
+        assert_eq!(event.scale_step(5.0), 1.0);
+    }
+
+    #[test]
+    fn slider() {
+        let at = |position| I3BarEvent {
+            position,
+            ..click(0, MouseButton::Left)
+        };
+        assert_eq!(at(Some(0.5)).slider_value(0.0, 100.0), Some(50.0));
+        assert_eq!(at(Some(0.0)).slider_value(5.0, 100.0), Some(5.0));
+        assert_eq!(at(Some(1.2)).slider_value(0.0, 150.0), Some(150.0));
+        assert_eq!(at(None).slider_value(0.0, 100.0), None);
+    }
+
+    #[test]
+    fn fallthrough() {
+        let event = I3BarEvent {
+            raw: Some(r#"{"name":"2","button":2,"modifiers":["Shift"]}"#.to_string()),
+            ..click(2, MouseButton::Middle)
+        };
+        let payload: serde_json::Value =
+            serde_json::from_str(&fallthrough_payload(&event, Some("sound"))).unwrap();
+        assert_eq!(payload["block"], "sound");
+        assert_eq!(payload["modifiers"][0], "Shift");
+        assert_eq!(
+            fallthrough_payload(&click(0, MouseButton::Left), None),
+            "{}"
+        );
+    }
+
+    #[test]
+    fn double_click() {
+        let mut dispatcher = ClickDispatcher::new(Duration::from_secs(60), None);
+        assert!(dispatcher
+            .push(click(0, MouseButton::Left), true, false)
+            .is_empty());
+        assert!(dispatcher.timeout().is_some());
+        let events = dispatcher.push(click(0, MouseButton::Left), true, false);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].button, MouseButton::DoubleLeft);
+        assert!(dispatcher.timeout().is_none());
+    }
+
+    #[test]
+    fn single_clicks() {
+        let mut dispatcher = ClickDispatcher::new(Duration::from_secs(60), None);
+        // Blocks without double click bindings get their clicks immediately
+        assert_eq!(
+            dispatcher
+                .push(click(0, MouseButton::Left), false, false)
+                .len(),
+            1
+        );
+
+        // A click elsewhere turns the pending click into a single click
+        assert!(dispatcher
+            .push(click(0, MouseButton::Left), true, false)
+            .is_empty());
+        let events = dispatcher.push(click(1, MouseButton::Right), false, false);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].button, MouseButton::Left);
+        assert_eq!(events[1].id, Some(1));
+
+        let mut dispatcher = ClickDispatcher::new(Duration::from_secs(0), None);
+        assert!(dispatcher
+            .push(click(0, MouseButton::Left), true, false)
+            .is_empty());
+        let events = dispatcher.expire();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].button, MouseButton::Left);
+    }
+
+    #[test]
+    fn hold() {
+        let mut dispatcher = ClickDispatcher::new(Duration::from_secs(60), Some(Duration::ZERO));
+        assert!(dispatcher
+            .push(click(0, MouseButton::Left), false, true)
+            .is_empty());
+        let events = dispatcher.expire();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].button, MouseButton::HoldLeft);
+        // The release after the hold is ignored
+        assert!(dispatcher.release(Some(MouseButton::Left)).is_empty());
+
+        // Released in time, so it's a click
+        let mut dispatcher =
+            ClickDispatcher::new(Duration::from_secs(60), Some(Duration::from_secs(60)));
+        assert!(dispatcher
+            .push(click(0, MouseButton::Left), false, true)
+            .is_empty());
+        assert!(dispatcher.release(Some(MouseButton::Right)).is_empty());
+        let events = dispatcher.release(None);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].button, MouseButton::Left);
+
+        // Without releases, the next click turns the held press into a click
+        assert!(dispatcher
+            .push(click(0, MouseButton::Left), false, true)
+            .is_empty());
+        assert_eq!(
+            dispatcher
+                .push(click(1, MouseButton::Right), false, false)
+                .len(),
+            2
+        );
+
+        // Holds need a hold_interval
+        let mut dispatcher = ClickDispatcher::new(Duration::from_secs(60), None);
+        assert_eq!(
+            dispatcher
+                .push(click(0, MouseButton::Left), false, true)
+                .len(),
+            1
+        );
+    }
+}
@@ -1,63 +1,150 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::path::Path;
+use std::process::Stdio;
 use std::rc::Rc;
+use std::time::Duration;
 
-use serde::de::{Deserialize, Deserializer};
+use serde::de::{Deserialize, Deserializer, Error};
 use serde_derive::Deserialize;
 use toml::value;
 
+use crate::de::{deserialize_duration, deserialize_opt_duration};
 use crate::errors;
+use crate::http::HttpConfig;
 use crate::icons::Icons;
 use crate::protocol::i3bar_event::MouseButton;
+use crate::subprocess::command;
 use crate::themes::Theme;
 
+/// The theme and the icons of the bar, which a reload replaces for all blocks at once
 #[derive(Debug)]
-pub struct SharedConfig {
-    pub theme: Rc<Theme>,
+struct Style {
+    theme: Rc<Theme>,
     icons: Rc<Icons>,
     icons_format: String,
+    /// Counts the replacements, so widgets know when to look up their icons again
+    generation: usize,
+}
+
+/// The `theme_overrides` of a block, and the theme of the bar they were last applied to
+#[derive(Debug)]
+struct ThemeOverrides {
+    overrides: HashMap<String, String>,
+    applied: RefCell<(Rc<Theme>, Rc<Theme>)>,
+}
+
+#[derive(Debug)]
+pub struct SharedConfig {
+    style: Rc<RefCell<Style>>,
+    theme_overrides: Option<Rc<ThemeOverrides>>,
+    /// The `icons_format` of the block, instead of the one of the bar
+    icons_format: Option<String>,
     pub scrolling: Scrolling,
+    /// Shown in place of the blocks until their first update, unless they set their own
+    pub loading_text: Option<String>,
+    /// How long the first updates of the blocks may take, unless they set their own
+    pub init_timeout: Option<Duration>,
 }
 
 impl SharedConfig {
     pub fn new(config: &Config) -> Self {
         Self {
-            theme: Rc::new(config.theme.clone()),
-            icons: Rc::new(config.icons.clone()),
-            icons_format: config.icons_format.clone(),
+            style: Rc::new(RefCell::new(Style {
+                theme: Rc::new(config.theme.clone()),
+                icons: Rc::new(config.icons.clone()),
+                icons_format: config.icons_format.clone(),
+                generation: 0,
+            })),
+            theme_overrides: None,
+            icons_format: None,
             scrolling: config.scrolling,
+            loading_text: config.loading_text.clone(),
+            init_timeout: config.init_timeout,
         }
     }
 
+    /// Replaces the theme and the icons of all blocks with the ones of `config`
+    pub fn replace_style(&self, config: &Config) {
+        let mut style = self.style.borrow_mut();
+        style.theme = Rc::new(config.theme.clone());
+        style.icons = Rc::new(config.icons.clone());
+        style.icons_format = config.icons_format.clone();
+        style.generation += 1;
+    }
+
+    /// Changes with every `replace_style`
+    pub fn style_generation(&self) -> usize {
+        self.style.borrow().generation
+    }
+
     pub fn icons_format_override(&mut self, icons_format: String) {
-        self.icons_format = icons_format;
+        self.icons_format = Some(icons_format);
     }
 
     pub fn theme_override(&mut self, overrides: &HashMap<String, String>) -> errors::Result<()> {
-        let mut theme = self.theme.as_ref().clone();
+        let base = self.style.borrow().theme.clone();
+        let mut theme = base.as_ref().clone();
         theme.apply_overrides(overrides)?;
-        self.theme = Rc::new(theme);
+        self.theme_overrides = Some(Rc::new(ThemeOverrides {
+            overrides: overrides.clone(),
+            applied: RefCell::new((base, Rc::new(theme))),
+        }));
         Ok(())
     }
 
+    /// The theme of the bar with the overrides of the block
+    pub fn theme(&self) -> Rc<Theme> {
+        let base = self.style.borrow().theme.clone();
+        let overrides = match &self.theme_overrides {
+            Some(overrides) => overrides,
+            None => return base,
+        };
+        let mut applied = overrides.applied.borrow_mut();
+        if !Rc::ptr_eq(&applied.0, &base) {
+            let mut theme = base.as_ref().clone();
+            // The overrides were valid with the previous theme, so they are just left out if
+            // they aren't with this one
+            if theme.apply_overrides(&overrides.overrides).is_err() {
+                theme = base.as_ref().clone();
+            }
+            *applied = (base, Rc::new(theme));
+        }
+        applied.1.clone()
+    }
+
     pub fn get_icon(&self, icon: &str) -> crate::errors::Result<String> {
         use crate::errors::OptionExt;
-        Ok(self.icons_format.clone().replace(
-            "{icon}",
-            self.icons
-                .0
-                .get(icon)
-                .internal_error("get_icon()", &format!("icon '{}' not found in your icons file. If you recently upgraded to v0.2 please check NEWS.md.", icon))?,
-        ))
+        let style = self.style.borrow();
+        Ok(self
+            .icons_format
+            .as_ref()
+            .unwrap_or(&style.icons_format)
+            .replace(
+                "{icon}",
+                style
+                    .icons
+                    .0
+                    .get(icon)
+                    .internal_error("get_icon()", &format!("icon '{}' not found in your icons file. If you recently upgraded to v0.2 please check NEWS.md.", icon))?,
+            ))
     }
 }
 
 impl Default for SharedConfig {
     fn default() -> Self {
         Self {
-            theme: Rc::new(Theme::default()),
-            icons: Rc::new(Icons::default()),
-            icons_format: " {icon} ".to_string(),
+            style: Rc::new(RefCell::new(Style {
+                theme: Rc::new(Theme::default()),
+                icons: Rc::new(Icons::default()),
+                icons_format: " {icon} ".to_string(),
+                generation: 0,
+            })),
+            theme_overrides: None,
+            icons_format: None,
             scrolling: Scrolling::default(),
+            loading_text: None,
+            init_timeout: None,
         }
     }
 }
@@ -65,10 +152,12 @@ impl Default for SharedConfig {
 impl Clone for SharedConfig {
     fn clone(&self) -> Self {
         Self {
-            theme: Rc::clone(&self.theme),
-            icons: Rc::clone(&self.icons),
+            style: Rc::clone(&self.style),
+            theme_overrides: self.theme_overrides.clone(),
             icons_format: self.icons_format.clone(),
             scrolling: self.scrolling,
+            loading_text: self.loading_text.clone(),
+            init_timeout: self.init_timeout,
         }
     }
 }
@@ -92,14 +181,125 @@ pub struct Config {
     #[serde(default)]
     pub scrolling: Scrolling,
 
+    /// Maximum time between the clicks of a double click
+    #[serde(
+        default = "Config::default_double_click_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub double_click_interval: Duration,
+
+    /// How long a button has to be held for its hold binding, which needs the releases of the
+    /// buttons from `i3status-rs release`
+    #[serde(default, deserialize_with = "deserialize_opt_duration")]
+    pub hold_interval: Option<Duration>,
+
+    /// Width of the glyphs of icon fonts in columns, used to measure text
+    #[serde(default = "Config::default_private_use_width")]
+    pub private_use_width: usize,
+
+    /// Most redraws of the bar per second. Updates in between are drawn together.
+    pub max_frame_rate: Option<f64>,
+
+    /// Text of the blocks until their first update has finished, e.g. `"…"`
+    pub loading_text: Option<String>,
+
+    /// How long the first update of a block may take, unless it sets its own `init_timeout`
+    #[serde(default, deserialize_with = "deserialize_opt_duration")]
+    pub init_timeout: Option<Duration>,
+
+    /// Command that gets the clicks no block handles as JSON on stdin
+    pub on_click_fallthrough: Option<String>,
+
+    /// Address on which the values of the blocks are served for Prometheus
+    pub metrics: Option<String>,
+
+    /// Token that the clients of `metrics` have to send as `Authorization: Bearer <token>`
+    pub metrics_token: Option<String>,
+
+    /// FIFO from which requests like `refresh weather` are read
+    pub command_fifo: Option<String>,
+
+    /// Publishes the bar on the session bus, see `bus`
+    #[serde(default)]
+    pub dbus_interface: bool,
+
+    /// Proxy, request budget and backoff of the HTTP requests of all blocks
+    #[serde(default)]
+    pub http: HttpConfig,
+
+    /// Further bars with some of the blocks, e.g. for a second monitor
+    #[serde(default, rename = "bar")]
+    pub bars: Vec<BarConfig>,
+
+    /// Named sets of the blocks that are shown, which can be switched at runtime
+    #[serde(default, rename = "profile")]
+    pub profiles: Vec<ProfileConfig>,
+
+    /// The profile that is active when the bar starts, all blocks are shown without one
+    pub default_profile: Option<String>,
+
     #[serde(rename = "block", deserialize_with = "deserialize_blocks")]
     pub blocks: Vec<(String, value::Value)>,
 }
 
 impl Config {
+    /// The shortest time between two redraws of the bar, from `max_frame_rate`
+    pub fn min_frame_interval(&self) -> Option<Duration> {
+        self.max_frame_rate
+            .filter(|rate| *rate > 0.0)
+            .map(|rate| Duration::from_secs_f64(1.0 / rate))
+    }
+
     fn default_icons_format() -> String {
         " {icon} ".to_string()
     }
+
+    fn default_double_click_interval() -> Duration {
+        Duration::from_millis(300)
+    }
+
+    fn default_private_use_width() -> usize {
+        1
+    }
+
+    /// Keeps only the blocks with the `export` name, type or position `block`, for `--block`
+    pub fn retain_block(&mut self, block: &str) {
+        let position = block.parse::<usize>().ok();
+        let mut i = 0;
+        self.blocks.retain(|(name, block_config)| {
+            i += 1;
+            position == Some(i - 1)
+                || name == block
+                || block_config.get("export").and_then(value::Value::as_str) == Some(block)
+        });
+    }
+
+    /// Drops the blocks whose conditions don't hold on this machine and removes the conditions
+    /// from the others. This runs the commands of `cmd:` conditions, so it's done once before the
+    /// blocks are created, and again when the configuration is reloaded.
+    pub fn apply_conditions(&mut self) -> Result<(), String> {
+        let mut holds = Vec::with_capacity(self.blocks.len());
+        for (_, block) in &mut self.blocks {
+            holds.push(match block {
+                value::Value::Table(table) => Conditions::take(table)?.hold(),
+                _ => true,
+            });
+        }
+        let mut holds = holds.into_iter();
+        self.blocks.retain(|_| holds.next().unwrap_or(true));
+        Ok(())
+    }
+
+    /// Removes the conditions from the blocks without evaluating them, so all blocks are kept
+    pub fn ignore_conditions(&mut self) {
+        for (_, block) in &mut self.blocks {
+            if let value::Value::Table(table) = block {
+                for key in Conditions::KEYS {
+                    table.remove(key);
+                }
+            }
+        }
+    }
 }
 
 impl Default for Config {
@@ -109,11 +309,44 @@ impl Default for Config {
             theme: Theme::default(),
             icons_format: Config::default_icons_format(),
             scrolling: Scrolling::default(),
+            double_click_interval: Config::default_double_click_interval(),
+            hold_interval: None,
+            private_use_width: Config::default_private_use_width(),
+            max_frame_rate: None,
+            loading_text: None,
+            init_timeout: None,
+            on_click_fallthrough: None,
+            metrics: None,
+            metrics_token: None,
+            command_fifo: None,
+            dbus_interface: false,
+            http: HttpConfig::default(),
+            bars: Vec::new(),
+            profiles: Vec::new(),
+            default_profile: None,
             blocks: Vec::new(),
         }
     }
 }
 
+/// A bar that is shown by `i3status-rs --bar <name>`
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct BarConfig {
+    pub name: String,
+    /// The `export` names, types or positions of the blocks, in the order of the bar
+    pub blocks: Vec<String>,
+}
+
+/// A set of blocks that is shown by `i3status-rs profile <name>`
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileConfig {
+    pub name: String,
+    /// The `export` names, types or positions of the blocks that are shown
+    pub blocks: Vec<String>,
+}
+
 #[derive(Deserialize, Copy, Clone, Debug)]
 #[serde(rename_all = "lowercase")]
 pub enum Scrolling {
@@ -153,6 +386,8 @@ where
     let mut blocks: Vec<(String, value::Value)> = Vec::new();
     let raw_blocks: Vec<value::Table> = Deserialize::deserialize(deserializer)?;
     for mut entry in raw_blocks {
+        // Only checked here, they are evaluated by `Config::apply_conditions`
+        Conditions::parse(&entry).map_err(D::Error::custom)?;
         if let Some(name) = entry.remove("block") {
             if let Some(name) = name.as_str() {
                 blocks.push((name.to_owned(), value::Value::Table(entry)))
@@ -162,3 +397,221 @@ where
 
     Ok(blocks)
 }
+
+/// Blocks with conditions are only created on the machines where all of them hold, e.g. a
+/// battery block on laptops. The conditions are checked again when the configuration is reloaded.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Conditions {
+    /// `path_exists:<path>`, or `cmd:<command>`, which holds if the command succeeds
+    #[serde(rename = "if")]
+    probe: Option<String>,
+    /// `NAME`, which holds if the variable is set and not empty, or `NAME=value`
+    if_env: Option<String>,
+    /// `wayland`, `x11` or `tty`
+    if_session_type: Option<String>,
+}
+
+/// The `if` condition of a block
+#[derive(Debug, PartialEq)]
+enum Probe<'a> {
+    PathExists(&'a str),
+    Cmd(&'a str),
+}
+
+impl Conditions {
+    const KEYS: [&'static str; 3] = ["if", "if_env", "if_session_type"];
+
+    /// The conditions in the options of a block, without evaluating them
+    fn parse(block: &value::Table) -> Result<Conditions, String> {
+        let mut conditions = value::Table::new();
+        for key in Conditions::KEYS {
+            if let Some(condition) = block.get(key) {
+                conditions.insert(key.to_string(), condition.clone());
+            }
+        }
+        let conditions: Conditions = value::Value::Table(conditions)
+            .try_into()
+            .map_err(|e| e.to_string())?;
+        conditions.probe()?;
+        Ok(conditions)
+    }
+
+    /// Like `parse`, but removes the conditions from the options of the block
+    fn take(block: &mut value::Table) -> Result<Conditions, String> {
+        let conditions = Conditions::parse(block)?;
+        for key in Conditions::KEYS {
+            block.remove(key);
+        }
+        Ok(conditions)
+    }
+
+    fn probe(&self) -> Result<Option<Probe<'_>>, String> {
+        let probe = match &self.probe {
+            Some(probe) => probe,
+            None => return Ok(None),
+        };
+        match probe.split_once(':') {
+            Some(("path_exists", path)) => Ok(Some(Probe::PathExists(path.trim()))),
+            Some(("cmd", cmd)) => Ok(Some(Probe::Cmd(cmd))),
+            _ => Err(format!(
+                "invalid condition '{}', expected 'path_exists:<path>' or 'cmd:<command>'",
+                probe
+            )),
+        }
+    }
+
+    /// Whether all conditions hold. A path that can't be expanded doesn't exist.
+    fn hold(&self) -> bool {
+        let holds = match self.probe() {
+            Ok(Some(Probe::PathExists(path))) => {
+                shellexpand::full(path).is_ok_and(|path| Path::new(path.as_ref()).exists())
+            }
+            Ok(Some(Probe::Cmd(cmd))) => command("sh")
+                .args(["-c", cmd])
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok_and(|status| status.success()),
+            // Invalid conditions are refused by `parse`
+            Ok(None) | Err(_) => true,
+        };
+        if !holds {
+            return false;
+        }
+        if let Some(env) = &self.if_env {
+            let holds = match env.split_once('=') {
+                Some((name, value)) => std::env::var(name).ok().as_deref() == Some(value),
+                None => std::env::var(env).is_ok_and(|value| !value.is_empty()),
+            };
+            if !holds {
+                return false;
+            }
+        }
+        if let Some(session_type) = &self.if_session_type {
+            if !session_type.eq_ignore_ascii_case(&Conditions::session_type()) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// The type of the session from logind, or from the displays that are set
+    fn session_type() -> String {
+        match std::env::var("XDG_SESSION_TYPE") {
+            Ok(session_type) if !session_type.is_empty() => session_type,
+            _ if std::env::var_os("WAYLAND_DISPLAY").is_some() => "wayland".to_string(),
+            _ if std::env::var_os("DISPLAY").is_some() => "x11".to_string(),
+            _ => "tty".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blocks(toml: &str) -> Config {
+        toml::from_str(toml).unwrap()
+    }
+
+    fn names(config: &Config) -> Vec<&str> {
+        config
+            .blocks
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn conditions() {
+        std::env::set_var("I3RS_TEST_CONDITION", "yes");
+        let mut config = blocks(
+            r#"
+            [[block]]
+            block = "cpu"
+            if = "cmd:exit 0"
+            [[block]]
+            block = "battery"
+            if = "cmd:exit 1"
+            [[block]]
+            block = "load"
+            if = "path_exists:/"
+            [[block]]
+            block = "memory"
+            if = "path_exists:/no/such/path"
+            [[block]]
+            block = "time"
+            if_env = "I3RS_TEST_CONDITION=yes"
+            [[block]]
+            block = "uptime"
+            if_env = "I3RS_TEST_CONDITION=no"
+            [[block]]
+            block = "net"
+            if_env = "I3RS_TEST_UNSET_CONDITION"
+            "#,
+        );
+        // Nothing is evaluated while parsing
+        assert_eq!(config.blocks.len(), 7);
+        assert!(config.blocks[0].1.get("if").is_some());
+
+        config.apply_conditions().unwrap();
+        assert_eq!(names(&config), ["cpu", "load", "time"]);
+        assert!(config.blocks[0].1.get("if").is_none());
+    }
+
+    #[test]
+    fn cmd_conditions_run_once() {
+        let marker = std::env::temp_dir().join(format!("i3rs-condition-{}", std::process::id()));
+        let _ = std::fs::remove_file(&marker);
+        let toml = format!(
+            "[[block]]\nblock = \"cpu\"\nif = \"cmd:echo >> {}\"",
+            marker.display()
+        );
+        let mut config = blocks(&toml);
+        assert!(!marker.exists());
+        config.apply_conditions().unwrap();
+        // The conditions are gone, so they aren't run again
+        config.apply_conditions().unwrap();
+        assert_eq!(std::fs::read_to_string(&marker).unwrap(), "\n");
+        std::fs::remove_file(&marker).unwrap();
+
+        let mut config = blocks(&toml);
+        config.ignore_conditions();
+        assert_eq!(names(&config), ["cpu"]);
+        assert!(!marker.exists());
+    }
+
+    #[test]
+    fn invalid_conditions() {
+        let config = toml::from_str::<Config>("[[block]]\nblock = \"cpu\"\nif = \"exists:/\"");
+        assert!(config.is_err());
+        let config = toml::from_str::<Config>("[[block]]\nblock = \"cpu\"\nif_host = \"a\"");
+        assert!(config.is_ok());
+    }
+
+    #[test]
+    fn replace_style() {
+        let shared_config = SharedConfig::new(&blocks("block = []"));
+        let mut block_config = shared_config.clone();
+        let overrides = HashMap::from([("idle_fg".to_string(), "#123456".to_string())]);
+        block_config.theme_override(&overrides).unwrap();
+        let generation = block_config.style_generation();
+
+        let mut config = blocks("icons_format = \"<{icon}>\"\nblock = []");
+        let bg = HashMap::from([("idle_bg".to_string(), "#654321".to_string())]);
+        config.theme.apply_overrides(&bg).unwrap();
+        shared_config.replace_style(&config);
+        // The clones of the blocks see the new style, with their own overrides on top
+        assert_ne!(block_config.style_generation(), generation);
+        let theme = block_config.theme();
+        assert_eq!(theme.idle_bg, "#654321".parse().unwrap());
+        assert_eq!(theme.idle_fg, "#123456".parse().unwrap());
+        assert_eq!(shared_config.theme().idle_fg, Theme::default().idle_fg);
+        assert_eq!(
+            block_config.get_icon("time").unwrap(),
+            format!("<{}>", Icons::default().0["time"])
+        );
+    }
+}
@@ -0,0 +1,330 @@
+//! The times at which blocks update with `schedule`, like `*/5 8-18 * * mon-fri`, and the hours
+//! in which they are shown with `active_hours`, like `08:00-20:00`.
+//!
+//! Schedules have the five fields of cron: the minute, the hour, the day of the month, the month
+//! and the day of the week. Fields are `*`, values like `5` or `mon`, ranges like `8-18`, steps
+//! like `*/15` or `0-30/10`, or lists of them like `mon,wed,fri`. The times are the local time.
+//!
+//! Like in cron, a day matches if either its day of the month or its day of the week matches, as
+//! long as neither of the two fields starts with `*`. Otherwise both fields have to match.
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+use serde::de::{self, Deserialize, Deserializer};
+
+use crate::errors::*;
+
+const MONTHS: &[&str] = &[
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+const WEEKDAYS: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// A cron schedule, with a bit for every value of a field that matches
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Whether the day of the month and the day of the week start with `*`, see `matches_day`
+    any_day: bool,
+    any_weekday: bool,
+}
+
+/// The values of a field from `min` to `max`. `names` are the names of the values from `first`
+/// on, e.g. of the months.
+fn field(field: &str, min: u32, max: u32, names: &[&str], first: u32) -> StdResult<u64, String> {
+    let value = |value: &str| {
+        let name = value.to_lowercase();
+        if let Some(index) = names.iter().position(|n| *n == name) {
+            return Ok(first + index as u32);
+        }
+        match value.parse::<u32>() {
+            Ok(value) if (min..=max).contains(&value) => Ok(value),
+            _ => Err(format!(
+                "invalid value '{}', expected {} to {}",
+                value, min, max
+            )),
+        }
+    };
+    let mut values = 0;
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => match step.parse::<usize>() {
+                Ok(step) if step > 0 => (range, step),
+                _ => return Err(format!("invalid step '{}'", step)),
+            },
+            None => (item, 1),
+        };
+        let (from, to) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((from, to)) => (value(from)?, value(to)?),
+            // `5/10` is every 10 from 5 on
+            None if step > 1 => (value(range)?, max),
+            None => (value(range)?, value(range)?),
+        };
+        if from > to {
+            return Err(format!("invalid range '{}'", range));
+        }
+        for value in (from..=to).step_by(step) {
+            values |= 1 << value;
+        }
+    }
+    Ok(values)
+}
+
+fn has(values: u64, value: u32) -> bool {
+    values & (1 << value) != 0
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(schedule: &str) -> StdResult<Self, String> {
+        let fields: Vec<&str> = schedule.split_whitespace().collect();
+        let (minutes, hours, days, months, weekdays) = match fields[..] {
+            [minutes, hours, days, months, weekdays] => (minutes, hours, days, months, weekdays),
+            _ => {
+                return Err(format!(
+                "'{}' doesn't have the five fields minute, hour, day, month and day of the week",
+                schedule
+            ))
+            }
+        };
+        let invalid = |e: String| format!("{} in the schedule '{}'", e, schedule);
+        // Sunday is 0 or 7
+        let mut weekday_values = field(weekdays, 0, 7, WEEKDAYS, 0).map_err(invalid)?;
+        if has(weekday_values, 7) {
+            weekday_values = (weekday_values | 1) & !(1 << 7);
+        }
+        Ok(Schedule {
+            minutes: field(minutes, 0, 59, &[], 0).map_err(invalid)?,
+            hours: field(hours, 0, 23, &[], 0).map_err(invalid)?,
+            days: field(days, 1, 31, &[], 0).map_err(invalid)?,
+            months: field(months, 1, 12, MONTHS, 1).map_err(invalid)?,
+            weekdays: weekday_values,
+            // Like in cron, steps like `*/2` are not restrictions of the day either
+            any_day: days.starts_with('*'),
+            any_weekday: weekdays.starts_with('*'),
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Schedule {
+    fn deserialize<D>(deserializer: D) -> StdResult<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+impl Schedule {
+    /// Whether the month and the day of `date` are in the schedule, see the module documentation
+    fn matches_day(&self, date: NaiveDate) -> bool {
+        let day = has(self.days, date.day());
+        let weekday = has(self.weekdays, date.weekday().num_days_from_sunday());
+        has(self.months, date.month())
+            && match (self.any_day, self.any_weekday) {
+                (false, false) => day || weekday,
+                _ => day && weekday,
+            }
+    }
+
+    /// Whether the hour and the day of `time` are in the schedule, no matter the minute
+    pub fn is_active(&self, time: NaiveDateTime) -> bool {
+        has(self.hours, time.hour()) && self.matches_day(time.date())
+    }
+
+    /// The first time of the schedule after `after`, if there is one in the next years
+    pub fn next(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut time =
+            after.date().and_hms(after.hour(), after.minute(), 0) + chrono::Duration::minutes(1);
+        // Enough for every schedule that can match, e.g. on the 29th of February
+        for _ in 0..100_000 {
+            if !self.matches_day(time.date()) {
+                time = time.date().succ().and_hms(0, 0, 0);
+            } else if !has(self.hours, time.hour()) {
+                time = time.date().and_hms(time.hour(), 0, 0) + chrono::Duration::hours(1);
+            } else if !has(self.minutes, time.minute()) {
+                time += chrono::Duration::minutes(1);
+            } else {
+                return Some(time);
+            }
+        }
+        None
+    }
+
+    /// The first full hour after `now` whose hour or day is not in the schedule, if there is one
+    /// in the next year
+    pub fn inactive_from(&self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut time = now.date().and_hms(now.hour(), 0, 0);
+        for _ in 0..366 * 24 {
+            time += chrono::Duration::hours(1);
+            if !self.is_active(time) {
+                return Some(time);
+            }
+        }
+        None
+    }
+}
+
+/// A time window of every day, which can span midnight like `22:00-06:00`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ActiveHours {
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl FromStr for ActiveHours {
+    type Err = String;
+
+    fn from_str(hours: &str) -> StdResult<Self, String> {
+        let invalid = || {
+            format!(
+                "invalid active hours '{}', expected e.g. '08:00-20:00'",
+                hours
+            )
+        };
+        let time = |time: &str| match time.trim() {
+            "24:00" => Ok(NaiveTime::from_hms(0, 0, 0)),
+            time => NaiveTime::parse_from_str(time, "%H:%M").map_err(|_| invalid()),
+        };
+        let (start, end) = hours.split_once('-').ok_or_else(invalid)?;
+        Ok(ActiveHours {
+            start: time(start)?,
+            end: time(end)?,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for ActiveHours {
+    fn deserialize<D>(deserializer: D) -> StdResult<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+impl ActiveHours {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        match self.start < self.end {
+            true => self.start <= time && time < self.end,
+            // Spans midnight, or the whole day if the start is the end
+            false => time >= self.start || time < self.end,
+        }
+    }
+
+    fn next(now: NaiveDateTime, time: NaiveTime) -> Duration {
+        let today = now.date().and_time(time);
+        let next = match today > now {
+            true => today,
+            false => today + chrono::Duration::days(1),
+        };
+        (next - now).to_std().unwrap_or_default()
+    }
+
+    /// The time until the window starts again
+    pub fn until_start(&self, now: NaiveDateTime) -> Duration {
+        Self::next(now, self.start)
+    }
+
+    /// The time until the window ends, `None` if it never does
+    pub fn until_end(&self, now: NaiveDateTime) -> Option<Duration> {
+        match self.start == self.end {
+            true => None,
+            false => Some(Self::next(now, self.end)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        // The 5th of October 2026 is a Monday
+        NaiveDate::from_ymd(2026, 10, day).and_hms(hour, minute, 0)
+    }
+
+    #[test]
+    fn schedules() {
+        let schedule: Schedule = "*/5 8-18 * * mon-fri".parse().unwrap();
+        assert_eq!(schedule.next(at(5, 8, 0)), Some(at(5, 8, 5)));
+        assert_eq!(schedule.next(at(5, 8, 3)), Some(at(5, 8, 5)));
+        assert_eq!(schedule.next(at(5, 18, 55)), Some(at(6, 8, 0)));
+        // From Friday evening to Monday
+        assert_eq!(schedule.next(at(9, 19, 0)), Some(at(12, 8, 0)));
+        assert!(schedule.is_active(at(5, 18, 30)));
+        assert!(!schedule.is_active(at(5, 19, 0)));
+        assert!(!schedule.is_active(at(10, 12, 0)));
+        assert_eq!(schedule.inactive_from(at(5, 17, 30)), Some(at(5, 19, 0)));
+
+        let schedule: Schedule = "30 0 1,15 * sun".parse().unwrap();
+        // Sundays as well as the 1st and the 15th
+        assert_eq!(schedule.next(at(5, 0, 0)), Some(at(11, 0, 30)));
+        assert_eq!(schedule.next(at(11, 1, 0)), Some(at(15, 0, 30)));
+        // A step of all days doesn't restrict the day, so only the Mondays that are odd days
+        let odd_mondays: Schedule = "0 0 */2 * mon".parse().unwrap();
+        assert_eq!(odd_mondays.next(at(5, 0, 0)), Some(at(19, 0, 0)));
+        assert!(!odd_mondays.is_active(at(7, 0, 0)));
+        assert!(!odd_mondays.is_active(at(12, 0, 0)));
+        let leap: Schedule = "0 0 29 feb *".parse().unwrap();
+        assert_eq!(
+            leap.next(at(5, 0, 0)),
+            Some(NaiveDate::from_ymd(2028, 2, 29).and_hms(0, 0, 0))
+        );
+        assert_eq!("0 0 * * 7".parse::<Schedule>(), "0 0 * * 0".parse());
+
+        assert!("* * * *".parse::<Schedule>().is_err());
+        assert!("60 * * * *".parse::<Schedule>().is_err());
+        assert!("*/0 * * * *".parse::<Schedule>().is_err());
+        assert!("* 18-8 * * *".parse::<Schedule>().is_err());
+        assert!("* * * * someday".parse::<Schedule>().is_err());
+        assert_eq!(
+            "0 0 31 feb *"
+                .parse::<Schedule>()
+                .unwrap()
+                .next(at(5, 0, 0)),
+            None
+        );
+    }
+
+    #[test]
+    fn active_hours() {
+        let hours: ActiveHours = "08:00-20:00".parse().unwrap();
+        assert!(hours.contains(NaiveTime::from_hms(8, 0, 0)));
+        assert!(!hours.contains(NaiveTime::from_hms(20, 0, 0)));
+        assert_eq!(
+            hours.until_start(at(5, 21, 0)),
+            Duration::from_secs(11 * 60 * 60)
+        );
+        assert_eq!(
+            hours.until_end(at(5, 19, 30)),
+            Some(Duration::from_secs(30 * 60))
+        );
+
+        let night: ActiveHours = "22:00-06:00".parse().unwrap();
+        assert!(night.contains(NaiveTime::from_hms(23, 0, 0)));
+        assert!(night.contains(NaiveTime::from_hms(5, 59, 0)));
+        assert!(!night.contains(NaiveTime::from_hms(12, 0, 0)));
+        let evening: ActiveHours = "18:00-24:00".parse().unwrap();
+        assert!(evening.contains(NaiveTime::from_hms(23, 59, 0)));
+        assert!(!evening.contains(NaiveTime::from_hms(0, 30, 0)));
+        let always: ActiveHours = "00:00-00:00".parse().unwrap();
+        assert!(always.contains(NaiveTime::from_hms(12, 0, 0)));
+        assert_eq!(always.until_end(at(5, 12, 0)), None);
+
+        assert!("8-20".parse::<ActiveHours>().is_err());
+        assert!("08:00".parse::<ActiveHours>().is_err());
+    }
+}
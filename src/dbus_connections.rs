@@ -0,0 +1,114 @@
+//! Connections to the session and the system bus, shared by the blocks.
+//!
+//! The blocks of a thread share one connection to each bus instead of opening their own. When the
+//! bus was restarted, e.g. by an upgrade of dbus or a new login session, the next call connects
+//! again. The threads started with `watch` connect again as well and add their match rules again,
+//! so the blocks keep working without a restart of the bar.
+//!
+//! A few connections are left out: `custom_dbus` and the control interface in `bus.rs` own a
+//! name on the session bus, which a shared connection would hand to every block, so they open
+//! their own (`custom_dbus` requests its name again after a restart of the bus). `ibus` talks to
+//! the private bus of the IBus daemon, whose address changes with every start of IBus.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::thread::{self, LocalKey};
+use std::time::Duration;
+
+use dbus::ffidisp::{BusType, Connection};
+use dbus::Message;
+
+use crate::errors::*;
+use crate::registry;
+
+/// How long to wait before connecting again after the bus went away
+pub const RECONNECT_INTERVAL: Duration = Duration::from_secs(2);
+
+type Shared = RefCell<Option<Rc<Connection>>>;
+
+thread_local! {
+    static SESSION: Shared = const { RefCell::new(None) };
+    static SYSTEM: Shared = const { RefCell::new(None) };
+}
+
+fn shared(key: &'static LocalKey<Shared>, bus: BusType) -> Result<Rc<Connection>> {
+    key.with(|shared| {
+        let mut shared = shared.borrow_mut();
+        if let Some(connection) = shared.as_ref().filter(|c| c.is_connected()) {
+            return Ok(connection.clone());
+        }
+        let connection = Rc::new(Connection::get_private(bus).internal_error(
+            "dbus",
+            match bus {
+                BusType::System => "failed to connect to the system bus",
+                _ => "failed to connect to the session bus",
+            },
+        )?);
+        *shared = Some(connection.clone());
+        Ok(connection)
+    })
+}
+
+/// The connection of this thread to the session bus
+pub fn session() -> Result<Rc<Connection>> {
+    shared(&SESSION, BusType::Session)
+}
+
+/// The connection of this thread to the system bus
+pub fn system() -> Result<Rc<Connection>> {
+    shared(&SYSTEM, BusType::System)
+}
+
+/// What a thread started with `watch` gets
+pub enum Event<'a> {
+    /// The thread connected to the bus and added the match rules, the first time or after the
+    /// bus went away. As the signals in between are lost, the state may have changed.
+    Connected,
+    Signal(&'a Message),
+}
+
+/// Starts a thread that passes the messages with the match `rules` on `bus` to `handler`. The
+/// connection is also the one of `session()` or `system()` in `handler`. Started by a block, the
+/// thread sends heartbeats of the block for the watchdog.
+pub fn watch<F>(name: &str, bus: BusType, rules: Vec<String>, mut handler: F) -> Result<()>
+where
+    F: FnMut(Event) + Send + 'static,
+{
+    let block = registry::current_block();
+    let heartbeat = move || {
+        if let Some(id) = block {
+            registry::heartbeat(id);
+        }
+    };
+    thread::Builder::new()
+        .name(name.into())
+        .spawn(move || loop {
+            heartbeat();
+            let connection = match bus {
+                BusType::System => system(),
+                _ => session(),
+            };
+            let subscribed = connection.and_then(|connection| {
+                for rule in &rules {
+                    connection
+                        .add_match(rule)
+                        .internal_error("dbus", "failed to add a match rule")?;
+                }
+                Ok(connection)
+            });
+            if let Ok(connection) = subscribed {
+                handler(Event::Connected);
+                while connection.is_connected() {
+                    let timeout = registry::HEARTBEAT_INTERVAL.as_millis() as u32;
+                    for message in connection.incoming(timeout) {
+                        handler(Event::Signal(&message));
+                        heartbeat();
+                    }
+                    heartbeat();
+                }
+            }
+            thread::sleep(RECONNECT_INTERVAL);
+        })
+        .map(|_| ())
+        .internal_error(name, "failed to start thread")
+}
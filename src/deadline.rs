@@ -0,0 +1,59 @@
+//! The time by which the update that is running has to be done, e.g. because of the
+//! `init_timeout` of the first update of a block.
+//!
+//! The updates run one after another on the main thread, so they can't be interrupted. Instead,
+//! everything an update waits for is limited to end by the deadline: HTTP requests get a shorter
+//! timeout, commands are run with `timeout(1)`, which stops them and their children, and D-Bus
+//! calls wait no longer. A block whose server or command hangs then fails in time and shows its
+//! error, instead of holding back the blocks after it.
+
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+thread_local! {
+    static DEADLINE: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+/// Runs `f` with everything it waits for limited to end by `deadline`
+pub fn with_deadline<T>(deadline: Instant, f: impl FnOnce() -> T) -> T {
+    let previous = DEADLINE.with(|d| d.replace(Some(deadline)));
+    let result = f();
+    DEADLINE.with(|d| d.set(previous));
+    result
+}
+
+/// The time left until the deadline, if there is one
+pub fn remaining() -> Option<Duration> {
+    DEADLINE.with(|d| d.get().map(|d| d.saturating_duration_since(Instant::now())))
+}
+
+/// `timeout`, or the time left if that is shorter
+pub fn limit(timeout: Duration) -> Duration {
+    remaining().map_or(timeout, |remaining| remaining.min(timeout))
+}
+
+/// `limit` for the timeouts in milliseconds of the D-Bus calls. The calls fail right away once the
+/// time is up, as a timeout of 0 would be the default of libdbus instead.
+pub fn limit_millis(timeout: i32) -> i32 {
+    let timeout = Duration::from_millis(timeout.max(0) as u64);
+    (limit(timeout).as_millis() as i32).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits() {
+        assert_eq!(limit(Duration::from_secs(5)), Duration::from_secs(5));
+        with_deadline(Instant::now() + Duration::from_secs(60), || {
+            assert_eq!(limit(Duration::from_secs(5)), Duration::from_secs(5));
+            assert!(limit(Duration::from_secs(120)) <= Duration::from_secs(60));
+        });
+        with_deadline(Instant::now(), || {
+            assert_eq!(remaining(), Some(Duration::ZERO));
+            assert_eq!(limit_millis(1000), 1);
+        });
+        assert_eq!(remaining(), None);
+    }
+}
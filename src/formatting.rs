@@ -1,7 +1,9 @@
+pub mod bidi;
 pub mod placeholder;
 pub mod prefix;
 pub mod unit;
 pub mod value;
+pub mod width;
 
 use std::borrow::Borrow;
 use std::collections::HashMap;
@@ -12,6 +14,7 @@ use serde::de::{MapAccess, Visitor};
 use serde::{de, Deserialize, Deserializer};
 
 use crate::errors::*;
+use crate::{registry, rules};
 use placeholder::unexpected_token;
 use placeholder::Placeholder;
 use value::Value;
@@ -138,34 +141,105 @@ impl FormatTemplate {
         &self,
         vars: &HashMap<impl FormatMapKey, Value>,
     ) -> Result<(String, Option<String>)> {
-        let full = match &self.full {
-            Some(tokens) => Self::render_tokens(tokens, vars)?,
-            None => String::new(), // TODO: throw an error that says that it's a bug?
-        };
-        let short = match &self.short {
-            Some(short) => Some(Self::render_tokens(short, vars)?),
-            None => None,
-        };
+        let mut full = String::new();
+        let mut short = None;
+        self.render_into(vars, &mut full, &mut short)?;
         Ok((full, short))
     }
 
-    fn render_tokens(tokens: &[Token], vars: &HashMap<impl FormatMapKey, Value>) -> Result<String> {
-        let mut rendered = String::new();
+    /// Like `render`, but into the buffers of the last rendering, e.g. those of a widget. Once the
+    /// buffers are large enough, rendering the usual placeholders doesn't allocate.
+    pub fn render_into(
+        &self,
+        vars: &HashMap<impl FormatMapKey, Value>,
+        full: &mut String,
+        short: &mut Option<String>,
+    ) -> Result<()> {
+        registry::record(vars);
+        let rule = rules::with_rule(vars, |format| {
+            let rendered = format.render_tokens_of(vars, full, short);
+            if rendered.is_ok() {
+                registry::record_shown(format.placeholders());
+            }
+            rendered
+        });
+        // Blocks can render several formats with different placeholders, the expanded format
+        // replaces those that have the placeholders it needs
+        let expanded = registry::with_expanded_format(|expanded| {
+            let rendered = expanded.render_tokens_of(vars, full, short);
+            if rendered.is_ok() {
+                registry::record_shown(expanded.placeholders());
+            }
+            rendered
+        });
+        if let Some(Ok(())) = expanded {
+            return Ok(());
+        }
+        if let Some(Ok(())) = rule {
+            return Ok(());
+        }
+        registry::record_shown(self.placeholders());
+        self.render_tokens_of(vars, full, short)
+    }
+
+    /// The names of the placeholders of the full format
+    fn placeholders(&self) -> impl Iterator<Item = &str> + Clone {
+        self.full.iter().flatten().filter_map(|token| match token {
+            Token::Var(var) => Some(var.name.as_str()),
+            Token::Text(_) => None,
+        })
+    }
+
+    fn render_tokens_of(
+        &self,
+        vars: &HashMap<impl FormatMapKey, Value>,
+        full: &mut String,
+        short: &mut Option<String>,
+    ) -> Result<()> {
+        full.clear();
+        if let Some(tokens) = &self.full {
+            Self::render_tokens(tokens, vars, full)?;
+        } // TODO: throw an error if there is no full format, that would be a bug?
+        match &self.short {
+            Some(tokens) => {
+                let short = short.get_or_insert_with(String::new);
+                short.clear();
+                Self::render_tokens(tokens, vars, short)?;
+            }
+            None => *short = None,
+        }
+        Ok(())
+    }
+
+    fn render_tokens(
+        tokens: &[Token],
+        vars: &HashMap<impl FormatMapKey, Value>,
+        rendered: &mut String,
+    ) -> Result<()> {
         for token in tokens {
             match token {
                 Token::Text(text) => rendered.push_str(text),
-                Token::Var(var) => rendered.push_str(
-                    &vars
-                        .get(&*var.name)
-                        .internal_error(
-                            "util",
-                            &format!("Unknown placeholder in format string: '{}'", var.name),
-                        )?
-                        .format(var)?,
-                ),
+                Token::Var(var) => {
+                    let metadata;
+                    let value = match vars.get(&*var.name) {
+                        Some(value) => value,
+                        None => {
+                            metadata = registry::metadata(&var.name);
+                            metadata.as_ref().internal_error(
+                                "util",
+                                &format!("Unknown placeholder in format string: '{}'", var.name),
+                            )?
+                        }
+                    };
+                    if registry::is_masked(&var.name, value) {
+                        value.masked().format_into(rendered, var)?
+                    } else {
+                        value.format_into(rendered, var)?
+                    }
+                }
             }
         }
-        Ok(rendered)
+        Ok(())
     }
 }
 
@@ -266,6 +340,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn render_into() {
+        let ft = FormatTemplate::new("{name:5}|{load} {bar:3#100}", Some("{load}")).unwrap();
+        let values = map!(
+            "name" => Value::from_string("cpu".to_string()),
+            "load" => Value::from_float(0.5),
+            "bar" => Value::from_integer(50),
+        );
+        let (mut full, mut short) = (String::with_capacity(64), None);
+        ft.render_into(&values, &mut full, &mut short).unwrap();
+        let buffers = (full.as_ptr(), short.as_ref().map(|short| short.as_ptr()));
+        ft.render_into(&values, &mut full, &mut short).unwrap();
+        // Rendered in place again, like `render` would
+        assert_eq!(
+            buffers,
+            (full.as_ptr(), short.as_ref().map(|short| short.as_ptr()))
+        );
+        assert_eq!((full, short), ft.render(&values).unwrap());
+    }
+
     #[test]
     fn contains() {
         let format = FormatTemplate::new("some text {foo} {bar:1} foobar", None);
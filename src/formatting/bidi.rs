@@ -0,0 +1,58 @@
+//! Handling of right-to-left text in placeholders.
+//!
+//! The bar lays out the whole text of a widget as one paragraph. A right-to-left value, e.g. an
+//! Arabic or Hebrew song title, would take neutral characters around it like spaces, separators
+//! and numbers into its run and reorder them. Wrapping the value in the isolate characters of the
+//! Unicode bidirectional algorithm keeps it separate from the rest of the format.
+
+use unicode_bidi::{bidi_class, BidiClass};
+
+/// First strong isolate, the direction of the isolated text follows its first strong character
+const FSI: char = '\u{2068}';
+/// Pop directional isolate
+const PDI: char = '\u{2069}';
+
+/// Letters of right-to-left scripts
+fn is_rtl(c: char) -> bool {
+    matches!(bidi_class(c), BidiClass::R | BidiClass::AL)
+}
+
+/// Isolates the end of `text` from the byte `start` on if it contains right-to-left text. Other
+/// text is left alone.
+pub fn isolate_from(text: &mut String, start: usize) {
+    if text[start..].chars().any(is_rtl) {
+        text.insert(start, FSI);
+        text.push(PDI);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn isolate(mut text: String) -> String {
+        isolate_from(&mut text, 0);
+        text
+    }
+
+    #[test]
+    fn isolation() {
+        assert_eq!(isolate("abc".to_string()), "abc");
+        // A byte order mark and the combining marks of Hebrew aren't strong characters
+        assert_eq!(
+            isolate("\u{feff}abc\u{05b0}".to_string()),
+            "\u{feff}abc\u{05b0}"
+        );
+        assert_eq!(isolate("\u{07c0}".to_string()), "\u{2068}\u{07c0}\u{2069}");
+        assert_eq!(isolate("שלום 1".to_string()), "\u{2068}שלום 1\u{2069}");
+        assert_eq!(
+            isolate("Fairuz - نسم علينا الهوى".to_string())
+                .chars()
+                .count(),
+            26
+        );
+        let mut text = "title: שלום".to_string();
+        isolate_from(&mut text, 7);
+        assert_eq!(text, "title: \u{2068}שלום\u{2069}");
+    }
+}
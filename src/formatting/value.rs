@@ -1,10 +1,14 @@
+use std::fmt::Write;
+
 use crate::errors::*;
 
+use super::bidi;
 use super::placeholder::{MinPrefixConfig, Placeholder};
 use super::prefix::Prefix;
 use super::unit::Unit;
+use super::width;
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct Value {
     unit: Unit,
     min_width: usize,
@@ -12,7 +16,26 @@ pub struct Value {
     value: InternalValue,
 }
 
-#[derive(Debug, Clone)]
+// `clone_from` reuses the strings, so recording the same values again doesn't allocate
+impl Clone for Value {
+    fn clone(&self) -> Self {
+        Self {
+            unit: self.unit,
+            min_width: self.min_width,
+            icon: self.icon.clone(),
+            value: self.value.clone(),
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        self.unit = source.unit;
+        self.min_width = source.min_width;
+        self.icon.clone_from(&source.icon);
+        self.value.clone_from(&source.value);
+    }
+}
+
+#[derive(Debug)]
 enum InternalValue {
     Text(String),
     Integer(i64),
@@ -20,13 +43,32 @@ enum InternalValue {
     Boolean(bool),
 }
 
+impl Clone for InternalValue {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Text(text) => Self::Text(text.clone()),
+            Self::Integer(value) => Self::Integer(*value),
+            Self::Float(value) => Self::Float(*value),
+            Self::Boolean(value) => Self::Boolean(*value),
+        }
+    }
+
+    fn clone_from(&mut self, source: &Self) {
+        match (self, source) {
+            (Self::Text(text), Self::Text(source)) => text.clone_from(source),
+            (value, source) => *value = source.clone(),
+        }
+    }
+}
+
 fn format_number(
+    out: &mut String,
     raw_value: f64,
     min_width: usize,
     min_prefix_config: MinPrefixConfig,
     unit: Unit,
     pad_with: char,
-) -> String {
+) {
     let min_prefix = min_prefix_config.value.unwrap_or(Prefix::Nano);
     let is_byte = unit.is_byte();
 
@@ -79,50 +121,46 @@ fn format_number(
         prefix = Prefix::One;
     }
 
-    // Apply prefix' configuration
-    let mut prefix_str = if min_prefix_config.space {
-        " ".to_string()
-    } else {
-        String::new()
-    };
-    if !min_prefix_config.hidden {
-        prefix_str.push_str(&prefix.to_string());
-    }
-
     // The length of the integer part of a number
     let digits = (value.log10().floor() + 1.0).max(1.0) as isize;
     // How many characters is left for "." and the fractional part?
-    match min_width as isize - digits {
+    let _ = match min_width as isize - digits {
         // No characters left
-        x if x <= 0 => format!("{:.0}{}", value, prefix_str),
+        x if x <= 0 => write!(out, "{:.0}", value),
         // Only one character -> pad text to the right
-        x if x == 1 => format!("{}{:.0}{}", pad_with, value, prefix_str),
+        x if x == 1 => write!(out, "{}{:.0}", pad_with, value),
         // There is space for fractional part
-        rest => format!("{:.*}{}", (rest as usize) - 1, value, prefix_str),
+        rest => write!(out, "{:.*}", (rest as usize) - 1, value),
+    };
+
+    // Apply prefix' configuration
+    if min_prefix_config.space {
+        out.push(' ');
+    }
+    if !min_prefix_config.hidden {
+        let _ = write!(out, "{}", prefix);
     }
 }
 
-fn format_bar(value: f64, length: usize) -> String {
+fn format_bar(out: &mut String, value: f64, length: usize) {
     let value = value.clamp(0., 1.);
     let chars_to_fill = value * length as f64;
-    (0..length)
-        .map(|i| {
-            let printed_chars = i as f64;
-            let val = (chars_to_fill - printed_chars).clamp(0., 1.) * 8.;
-            match val as usize {
-                //TODO make those characters configurable?
-                0 => ' ',
-                1 => '\u{258f}',
-                2 => '\u{258e}',
-                3 => '\u{258d}',
-                4 => '\u{258c}',
-                5 => '\u{258b}',
-                6 => '\u{258a}',
-                7 => '\u{2589}',
-                _ => '\u{2588}',
-            }
-        })
-        .collect()
+    out.extend((0..length).map(|i| {
+        let printed_chars = i as f64;
+        let val = (chars_to_fill - printed_chars).clamp(0., 1.) * 8.;
+        match val as usize {
+            //TODO make those characters configurable?
+            0 => ' ',
+            1 => '\u{258f}',
+            2 => '\u{258e}',
+            3 => '\u{258d}',
+            4 => '\u{258c}',
+            5 => '\u{258b}',
+            6 => '\u{258a}',
+            7 => '\u{2589}',
+            _ => '\u{2588}',
+        }
+    }));
 }
 
 impl Value {
@@ -160,6 +198,55 @@ impl Value {
         }
     }
 
+    /// The value as a number. Text is parsed, booleans are 0 or 1.
+    pub fn as_f64(&self) -> Option<f64> {
+        match &self.value {
+            InternalValue::Text(text) => text.trim().parse().ok(),
+            InternalValue::Integer(value) => Some(*value as f64),
+            InternalValue::Float(value) => Some(*value),
+            InternalValue::Boolean(value) => Some(*value as u8 as f64),
+        }
+    }
+
+    /// The value without icon, unit or any formatting, e.g. to pass it to a command
+    pub fn plain(&self) -> String {
+        match &self.value {
+            InternalValue::Text(text) => text.clone(),
+            InternalValue::Integer(value) => value.to_string(),
+            InternalValue::Float(value) => value.to_string(),
+            InternalValue::Boolean(value) => value.to_string(),
+        }
+    }
+
+    /// The value hidden behind asterisks, for the presentation mode
+    pub fn masked(&self) -> Self {
+        Self {
+            unit: Unit::None,
+            value: InternalValue::Text("*****".to_string()),
+            ..self.clone()
+        }
+    }
+
+    /// The number, if the value is a percentage
+    pub fn as_percents(&self) -> Option<f64> {
+        match self.unit {
+            Unit::Percents => self.as_f64(),
+            _ => None,
+        }
+    }
+
+    pub fn is_text(&self) -> bool {
+        matches!(self.value, InternalValue::Text(_))
+    }
+
+    /// A float with the same unit and icon, e.g. the result of a calculation with this value
+    pub fn with_number(&self, value: f64) -> Self {
+        Self {
+            value: InternalValue::Float(value),
+            ..self.clone()
+        }
+    }
+
     // Set options
     pub fn icon(mut self, icon: String) -> Self {
         self.icon = Some(icon);
@@ -201,6 +288,13 @@ impl Value {
     }
 
     pub fn format(&self, var: &Placeholder) -> Result<String> {
+        let mut out = String::new();
+        self.format_into(&mut out, var)?;
+        Ok(out)
+    }
+
+    /// Appends the formatted value to `out`, without allocating strings of its own
+    pub fn format_into(&self, out: &mut String, var: &Placeholder) -> Result<()> {
         // Get user-specified min_width and pad_with values. Use defaults instead
         let min_width = var.min_width.min_width.unwrap_or(self.min_width);
         let pad_with = var.min_width.pad_with;
@@ -212,27 +306,33 @@ impl Value {
         if let Some(bar_max_value) = var.bar_max_value {
             match self.value {
                 InternalValue::Integer(i) => {
-                    return Ok(format_bar(i as f64 / bar_max_value, min_width))
+                    format_bar(out, i as f64 / bar_max_value, min_width);
+                    return Ok(());
+                }
+                InternalValue::Float(f) => {
+                    format_bar(out, f / bar_max_value, min_width);
+                    return Ok(());
                 }
-                InternalValue::Float(f) => return Ok(format_bar(f / bar_max_value, min_width)),
                 _ => (),
             }
         }
 
-        let value = match self.value {
+        // We prepend the resulting string with the icon if it is set
+        out.push_str(self.icon.as_deref().unwrap_or(""));
+        let start = out.len();
+
+        match self.value {
             InternalValue::Text(ref text) => {
                 // Format text value. First pad it to the left with `pad_with` symbol. Then apply
                 // `max_width` option.
-                let mut text = text.clone();
-                for _ in (text.chars().count())..min_width {
-                    text.push(pad_with);
+                out.push_str(text);
+                for _ in width::str_width(text)..min_width {
+                    out.push(pad_with);
                 }
                 if let Some(max_width) = var.max_width {
-                    for _ in 0..(text.chars().count() as isize - max_width as isize) {
-                        text.pop();
-                    }
+                    out.truncate(start + width::truncate(&out[start..], max_width).len());
                 }
-                text
+                bidi::isolate_from(out, start);
             }
             InternalValue::Integer(value) => {
                 // Convert the value
@@ -240,14 +340,10 @@ impl Value {
                 let value = (value as f64 * self.unit.convert(unit)?) as i64;
 
                 // Pad the resulting string to the right
-                let text = value.to_string();
-                let mut retval = String::new();
-                let text_len = text.len();
-                for _ in text_len..min_width {
-                    retval.push(pad_with);
+                let _ = write!(out, "{}", value);
+                for _ in out.len() - start..min_width {
+                    out.insert(start, pad_with);
                 }
-                retval.push_str(&text);
-                retval
             }
             InternalValue::Float(value) => {
                 // Convert the value
@@ -255,24 +351,15 @@ impl Value {
                 let value = value * self.unit.convert(unit)?;
 
                 // Apply engineering notation (Float-only)
-                format_number(value, min_width, var.min_prefix, unit, pad_with)
+                format_number(out, value, min_width, var.min_prefix, unit, pad_with);
             }
-            InternalValue::Boolean(value) => match value {
-                true => String::from("T"),
-                false => String::from("F"),
-            },
-        };
-
-        // We prepend the resulting string with the icon if it is set
-        let icon_str = self.icon.as_deref().unwrap_or("");
+            InternalValue::Boolean(value) => out.push(if value { 'T' } else { 'F' }),
+        }
 
         // Hide the unit if a corresponding option is set
-        let unit = if var.unit.hidden {
-            String::new()
-        } else {
-            unit.to_string()
-        };
-
-        Ok(format!("{}{}{}", icon_str, value, unit))
+        if !var.unit.hidden {
+            let _ = write!(out, "{}", unit);
+        }
+        Ok(())
     }
 }
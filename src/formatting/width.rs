@@ -0,0 +1,67 @@
+//! Width of text in the columns of a terminal-like grid, as it is rendered by the bar. CJK
+//! characters and most emoji take two columns, combining characters none.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use unicode_width::UnicodeWidthChar;
+
+/// Width of characters from the private use areas, which contain the glyphs of icon fonts like
+/// Nerd Fonts. These are one column wide in the "Mono" variants of the fonts and two otherwise.
+static PRIVATE_USE_WIDTH: AtomicUsize = AtomicUsize::new(1);
+
+pub fn set_private_use_width(width: usize) {
+    PRIVATE_USE_WIDTH.store(width, Ordering::Relaxed);
+}
+
+fn is_private_use(c: char) -> bool {
+    matches!(c, '\u{e000}'..='\u{f8ff}' | '\u{f0000}'..='\u{ffffd}' | '\u{100000}'..='\u{10fffd}')
+}
+
+pub fn char_width(c: char) -> usize {
+    if is_private_use(c) {
+        PRIVATE_USE_WIDTH.load(Ordering::Relaxed)
+    } else {
+        // Control characters are not rendered
+        c.width().unwrap_or(0)
+    }
+}
+
+pub fn str_width(s: &str) -> usize {
+    s.chars().map(char_width).sum()
+}
+
+/// The longest prefix of `s` that is at most `max_width` columns wide
+pub fn truncate(s: &str, max_width: usize) -> &str {
+    let mut width = 0;
+    for (i, c) in s.char_indices() {
+        width += char_width(c);
+        if width > max_width {
+            return &s[..i];
+        }
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn widths() {
+        assert_eq!(str_width("abc"), 3);
+        assert_eq!(str_width("日本語"), 6);
+        assert_eq!(str_width("e\u{301}"), 1);
+        assert_eq!(str_width("\u{1f600}"), 2);
+        assert_eq!(str_width("\u{f240}"), 1);
+        // Bidi isolates are invisible
+        assert_eq!(str_width("\u{2068}a\u{2069}"), 1);
+    }
+
+    #[test]
+    fn truncation() {
+        assert_eq!(truncate("abcde", 3), "abc");
+        assert_eq!(truncate("日本語", 3), "日");
+        assert_eq!(truncate("日本語", 4), "日本");
+        assert_eq!(truncate("ab", 3), "ab");
+    }
+}
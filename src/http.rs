@@ -1,17 +1,277 @@
+//! The HTTP client of all blocks.
+//!
+//! Requests share a curl handle per thread, which keeps the connections to the servers alive, and
+//! go through the `[http]` options of the configuration: a proxy, a budget of requests per
+//! minute, and a backoff for servers that fail. GET requests are revalidated with `ETag` and
+//! `Last-Modified`, so unchanged responses are not transferred again.
+//!
+//! E.g. after a resume, when all blocks update at once, the requests beyond the budget and the
+//! ones to a server that is backed off are answered from the cache if possible, and fail
+//! otherwise.
+
 use curl::easy::Easy;
+use serde_derive::Deserialize;
 use serde_json::value::Value;
-use std::time::Duration;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use lazy_static::lazy_static;
 
+use crate::de::deserialize_duration;
+use crate::deadline;
 use crate::errors;
 use crate::errors::{Result, ResultExtInternal};
+use crate::mock;
+
+/// The delay after the first failure of a server, which doubles with every further one
+const FIRST_BACKOFF: Duration = Duration::from_secs(5);
 
+#[derive(Clone)]
 pub struct HttpResponse<T> {
     pub code: u32,
     pub content: T,
     pub headers: Vec<String>,
 }
 
-fn http_easy(mut easy: Easy) -> Result<HttpResponse<Vec<u8>>> {
+/// The options of `[http]`, for the requests of all blocks
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct HttpConfig {
+    /// e.g. `http://proxy:3128` or `socks5h://localhost:1080`. Without it, curl uses the proxy
+    /// of the environment, e.g. `https_proxy`.
+    pub proxy: Option<String>,
+
+    /// The most requests of all blocks together in a minute, `0` for no limit
+    pub requests_per_minute: usize,
+
+    /// The longest delay after the failures of a server
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub max_backoff: Duration,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            proxy: None,
+            requests_per_minute: 60,
+            max_backoff: Duration::from_secs(600),
+        }
+    }
+}
+
+struct Backoff {
+    failures: u32,
+    until: Instant,
+}
+
+struct Cached {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    response: HttpResponse<Vec<u8>>,
+}
+
+#[derive(Default)]
+struct State {
+    config: HttpConfig,
+    /// The times of the requests of the last minute
+    recent: VecDeque<Instant>,
+    /// By host
+    backoff: HashMap<String, Backoff>,
+    /// The responses to GET requests with an `ETag` or a `Last-Modified`, by URL
+    cache: HashMap<String, Cached>,
+}
+
+lazy_static! {
+    // Blocks make requests from their own threads
+    static ref STATE: Mutex<State> = Mutex::new(State::default());
+}
+
+thread_local! {
+    static EASY: RefCell<Easy> = RefCell::new(Easy::new());
+}
+
+/// Applies the `[http]` options of the configuration
+pub fn configure(config: HttpConfig) {
+    STATE.lock().unwrap().config = config;
+}
+
+/// Lets the requests to all servers through again, e.g. once the network is back
+pub fn forget_failures() {
+    STATE.lock().unwrap().backoff.clear();
+}
+
+/// The delay after `failures` failures of a server in a row
+fn backoff_delay(failures: u32, max: Duration) -> Duration {
+    FIRST_BACKOFF
+        .checked_mul(1 << failures.saturating_sub(1).min(16))
+        .map_or(max, |delay| delay.min(max))
+}
+
+/// The value of the first header `name` of a response
+fn header<'a>(headers: &'a [String], name: &str) -> Option<&'a str> {
+    headers.iter().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        match key.trim().eq_ignore_ascii_case(name) {
+            true => Some(value.trim()),
+            false => None,
+        }
+    })
+}
+
+fn host(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    rest.split(['/', '?']).next().unwrap_or(rest)
+}
+
+#[derive(Default)]
+struct Request<'a> {
+    timeout: Option<Duration>,
+    headers: Vec<String>,
+    basic_auth: Option<(&'a str, &'a str)>,
+    /// A form that is POSTed, GET requests without it are cached
+    form: Option<&'a str>,
+}
+
+/// The cached response to `url`, for a request that doesn't reach the server
+fn cached_or(state: &State, url: &str, reason: String) -> Result<HttpResponse<Vec<u8>>> {
+    match state.cache.get(url) {
+        Some(cached) => Ok(cached.response.clone()),
+        None => Err(errors::BlockError("curl".to_owned(), reason)),
+    }
+}
+
+fn http_request(url: &str, request: Request) -> Result<HttpResponse<Vec<u8>>> {
+    let host = host(url).to_string();
+    let Request {
+        timeout,
+        mut headers,
+        basic_auth,
+        form,
+    } = request;
+    let cacheable = form.is_none();
+    // Limited by the `init_timeout` of the block, see `deadline`
+    let timeout = match deadline::remaining() {
+        Some(left) => {
+            if left.is_zero() {
+                return Err(errors::BlockError(
+                    "curl".to_owned(),
+                    format!("no time left for a request to {}", host),
+                ));
+            }
+            Some(timeout.map_or(left, |timeout| timeout.min(left)))
+        }
+        None => timeout,
+    };
+    let proxy = {
+        let mut state = STATE.lock().unwrap();
+        let now = Instant::now();
+        if let Some(backoff) = state.backoff.get(&host) {
+            if backoff.until > now {
+                let reason = format!("{} failed, it is tried again later", host);
+                return cached_or(&state, url, reason);
+            }
+        }
+        while matches!(state.recent.front(), Some(time) if now.duration_since(*time) >= Duration::from_secs(60))
+        {
+            state.recent.pop_front();
+        }
+        let budget = state.config.requests_per_minute;
+        if budget != 0 && state.recent.len() >= budget {
+            let reason = format!("more than {} requests in the last minute", budget);
+            return cached_or(&state, url, reason);
+        }
+        state.recent.push_back(now);
+        if let Some(cached) = state.cache.get(url).filter(|_| cacheable) {
+            if let Some(etag) = &cached.etag {
+                headers.push(format!("If-None-Match: {}", etag));
+            }
+            if let Some(last_modified) = &cached.last_modified {
+                headers.push(format!("If-Modified-Since: {}", last_modified));
+            }
+        }
+        state.config.proxy.clone()
+    };
+
+    let response = EASY.with(|easy| {
+        let mut easy = easy.borrow_mut();
+        // Resetting keeps the connections
+        easy.reset();
+        easy.url(&url.replace(" ", "%20"))?;
+        easy.useragent("i3status")?;
+        if let Some(proxy) = &proxy {
+            easy.proxy(proxy)?;
+        }
+        if let Some(t) = timeout {
+            easy.timeout(t)?;
+        }
+        if let Some((username, password)) = basic_auth {
+            easy.username(username)?;
+            easy.password(password)?;
+        }
+        if let Some(form) = form {
+            easy.post(true)?;
+            easy.post_fields_copy(form.as_bytes())?;
+        }
+        let mut header_list = curl::easy::List::new();
+        for header in headers {
+            header_list.append(&header)?;
+        }
+        easy.http_headers(header_list)?;
+        http_easy(&mut easy)
+    });
+
+    let mut state = STATE.lock().unwrap();
+    let failed = match &response {
+        Ok(response) => response.code == 429 || response.code >= 500,
+        Err(_) => true,
+    };
+    if failed {
+        let max = state.config.max_backoff;
+        let backoff = state.backoff.entry(host).or_insert(Backoff {
+            failures: 0,
+            until: Instant::now(),
+        });
+        backoff.failures += 1;
+        let mut delay = backoff_delay(backoff.failures, max);
+        // Servers that are overloaded may tell how long to wait
+        if let Ok(response) = &response {
+            let retry_after = header(&response.headers, "Retry-After").and_then(|s| s.parse().ok());
+            if let Some(seconds) = retry_after {
+                delay = Duration::from_secs(seconds).min(max);
+            }
+        }
+        backoff.until = Instant::now() + delay;
+        return response;
+    }
+    state.backoff.remove(&host);
+
+    let response = response?;
+    if !cacheable {
+        return Ok(response);
+    }
+    if response.code == 304 {
+        if let Some(cached) = state.cache.get(url) {
+            return Ok(cached.response.clone());
+        }
+    }
+    let etag = header(&response.headers, "ETag").map(String::from);
+    let last_modified = header(&response.headers, "Last-Modified").map(String::from);
+    if response.code == 200 && (etag.is_some() || last_modified.is_some()) {
+        state.cache.insert(
+            url.to_string(),
+            Cached {
+                etag,
+                last_modified,
+                response: response.clone(),
+            },
+        );
+    }
+    Ok(response)
+}
+
+fn http_easy(easy: &mut Easy) -> Result<HttpResponse<Vec<u8>>> {
     let mut buf: Vec<u8> = Vec::new();
     let mut headers: Vec<String> = Vec::new();
 
@@ -40,14 +300,32 @@ fn http_easy(mut easy: Easy) -> Result<HttpResponse<Vec<u8>>> {
     })
 }
 
+/// The response of the mock source to a request of `url`, if there is one
+fn mocked_json(url: &str) -> Option<Result<HttpResponse<Value>>> {
+    let body = mock::http(url)?;
+    Some(body.and_then(|body| {
+        let content =
+            serde_json::from_str(&body).internal_error("mock", "could not parse json fixture")?;
+        Ok(HttpResponse {
+            code: 200,
+            content,
+            headers: Vec::new(),
+        })
+    }))
+}
+
 pub fn http_get_socket_json(path: std::path::PathBuf, url: &str) -> Result<HttpResponse<Value>> {
+    if let Some(response) = mocked_json(url) {
+        return response;
+    }
     let mut easy = curl::easy::Easy::new();
 
     let cleaned_url = url.replace(" ", "%20");
     easy.url(&cleaned_url)?;
     easy.unix_socket_path(Some(path))?;
 
-    let response = http_easy(easy)?;
+    // Local sockets are neither limited nor cached
+    let response = http_easy(&mut easy)?;
 
     let content = serde_json::from_slice(&response.content)
         .internal_error("curl", "could not parse json response from server")?;
@@ -64,26 +342,65 @@ pub fn http_get_json(
     timeout: Option<Duration>,
     request_headers: Vec<(&str, &str)>,
 ) -> Result<HttpResponse<Value>> {
-    let mut easy = curl::easy::Easy::new();
-
-    let cleaned_url = url.replace(" ", "%20");
-    easy.url(&cleaned_url)?;
-
-    if let Some(t) = timeout {
-        easy.timeout(t)?;
+    if let Some(response) = mocked_json(url) {
+        return response;
     }
+    let response = http_request(
+        url,
+        Request {
+            timeout,
+            headers: request_headers
+                .iter()
+                .map(|(k, v)| format!("{}: {}", k, v))
+                .collect(),
+            ..Request::default()
+        },
+    )?;
 
-    let mut header_list = curl::easy::List::new();
-
-    for (k, v) in request_headers.iter() {
-        header_list.append(&format!("{}: {}", k, v))?;
-    }
+    let content = serde_json::from_slice(&response.content)
+        .internal_error("curl", "could not parse json response from server")?;
 
-    easy.useragent("i3status")?;
+    Ok(HttpResponse {
+        code: response.code,
+        content,
+        headers: response.headers,
+    })
+}
 
-    easy.http_headers(header_list)?;
+/// Percent-encodes `value` for a query string or a form, so it can't add parameters of its own
+pub fn encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
 
-    let response = http_easy(easy)?;
+/// Sends a `application/x-www-form-urlencoded` POST request, optionally authenticated with HTTP
+/// basic auth, and parses the response as JSON.
+pub fn http_post_form_json(
+    url: &str,
+    timeout: Option<Duration>,
+    basic_auth: Option<(&str, &str)>,
+    form: &str,
+) -> Result<HttpResponse<Value>> {
+    if let Some(response) = mocked_json(url) {
+        return response;
+    }
+    let response = http_request(
+        url,
+        Request {
+            timeout,
+            basic_auth,
+            form: Some(form),
+            ..Request::default()
+        },
+    )?;
 
     let content = serde_json::from_slice(&response.content)
         .internal_error("curl", "could not parse json response from server")?;
@@ -104,3 +421,41 @@ impl From<curl::Error> for errors::Error {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff() {
+        let max = Duration::from_secs(600);
+        assert_eq!(backoff_delay(1, max), Duration::from_secs(5));
+        assert_eq!(backoff_delay(3, max), Duration::from_secs(20));
+        assert_eq!(backoff_delay(8, max), max);
+        assert_eq!(backoff_delay(100, max), max);
+    }
+
+    #[test]
+    fn headers() {
+        let headers = vec![
+            "HTTP/1.1 200 OK\r\n".to_string(),
+            "etag: \"abc\"\r\n".to_string(),
+            "Retry-After: 30\r\n".to_string(),
+        ];
+        assert_eq!(header(&headers, "ETag"), Some("\"abc\""));
+        assert_eq!(header(&headers, "retry-after"), Some("30"));
+        assert_eq!(header(&headers, "Last-Modified"), None);
+        assert_eq!(
+            host("https://api.github.com/notifications?all=1"),
+            "api.github.com"
+        );
+        assert_eq!(host("https://ipapi.co?x=/"), "ipapi.co");
+    }
+
+    #[test]
+    fn encoding() {
+        assert_eq!(encode("a-Z_0.~"), "a-Z_0.~");
+        assert_eq!(encode("New York&x=1"), "New%20York%26x%3D1");
+        assert_eq!(encode("é/+"), "%C3%A9%2F%2B");
+    }
+}
@@ -13,6 +13,7 @@ impl Default for Icons {
     fn default() -> Self {
         // "none" icon set
         Self(map_to_owned! {
+            "ambient_light" => "LUX",
             "backlight_empty" => "BRIGHT",
             "backlight_full" => "BRIGHT",
             "backlight_1" =>  "BRIGHT",
@@ -44,12 +45,18 @@ impl Default for Icons {
             "cogs" => "LOAD",
             "cpu" => "CPU",
             "disk_drive" => "DISK",
+            "dock" => "DOCK",
             "docker" => "DOCKER",
+            "equalizer" => "EQ",
             "github" => "GITHUB",
+            "gps" => "GPS",
             "gpu" => "GPU",
+            "hackernews" => "HN",
             "headphones" => "HEAD",
             "joystick" => "JOY",
             "keyboard" => "KBD",
+            "kubernetes" => "K8S",
+            "live" => "LIVE",
             "mail" => "MAIL",
             "memory_mem" => "MEM",
             "memory_swap" => "SWAP",
@@ -76,14 +83,24 @@ impl Default for Icons {
             "pomodoro_paused" => "PAUSED",
             "pomodoro_started" => "STARTED",
             "pomodoro_stopped" => "STOPPED",
+            "proton" => "PROTON",
+            "reddit" => "REDDIT",
             "resolution" => "RES",
+            "rig" => "RIG",
+            "server" => "SRV",
+            "ssh" => "SSH",
+            "steam" => "STEAM",
             "tasks" => "TSK",
             "thermometer" => "TEMP",
             "time" => "TIME",
             "toggle_off" => "OFF",
             "toggle_on" => "ON",
+            "touchpad" => "TP",
             "update" => "UPD",
             "uptime" => "UP",
+            "vault_locked" => "VAULT",
+            "vault_unlocked" => "VAULT OPEN",
+            "vm" => "VM",
             "volume_empty" => "VOL",
             "volume_full" => "VOL",
             "volume_half" => "VOL",
@@ -92,6 +109,7 @@ impl Default for Icons {
             "microphone_full" => "MIC",
             "microphone_half" => "MIC",
             "microphone_muted" => "MIC MUTED",
+            "wakeups" => "WAKE",
             "weather_clouds" => "CLOUDY",
             "weather_default" => "WEATHER",
             "weather_rain" => "RAIN",
@@ -105,6 +123,33 @@ impl Default for Icons {
     }
 }
 
+/// Pango attributes for the size and the vertical position of icons
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct IconAdjustment {
+    /// Value of the `size` attribute, e.g. `large` or `12pt`
+    pub size: Option<String>,
+    /// Value of the `rise` attribute, e.g. `-1pt`
+    pub offset: Option<String>,
+}
+
+impl IconAdjustment {
+    fn wrap(&self, icon: &str) -> String {
+        let mut attributes = String::new();
+        if let Some(ref size) = self.size {
+            attributes.push_str(&format!(" size='{}'", size));
+        }
+        if let Some(ref offset) = self.offset {
+            attributes.push_str(&format!(" rise='{}'", offset));
+        }
+        if attributes.is_empty() || icon.is_empty() {
+            icon.to_string()
+        } else {
+            format!("<span{}>{}</span>", attributes, icon)
+        }
+    }
+}
+
 impl Icons {
     pub fn from_file(file: &str) -> Option<Self> {
         if file == "none" {
@@ -114,6 +159,29 @@ impl Icons {
             Some(Icons(util::deserialize_file(&file).ok()?))
         }
     }
+
+    /// Wraps the icons in spans. The adjustments of single icons take precedence over the global
+    /// ones.
+    pub fn apply_adjustments(
+        &mut self,
+        global: &IconAdjustment,
+        icons: &HashMap<String, IconAdjustment>,
+    ) -> Result<(), String> {
+        if let Some(name) = icons.keys().find(|name| !self.0.contains_key(*name)) {
+            return Err(format!("Icon '{}' not found in the icon set", name));
+        }
+        for (name, icon) in self.0.iter_mut() {
+            let adjustment = match icons.get(name) {
+                Some(adjustment) => IconAdjustment {
+                    size: adjustment.size.clone().or_else(|| global.size.clone()),
+                    offset: adjustment.offset.clone().or_else(|| global.offset.clone()),
+                },
+                None => global.clone(),
+            };
+            *icon = adjustment.wrap(icon);
+        }
+        Ok(())
+    }
 }
 
 impl<'de> Deserialize<'de> for Icons {
@@ -127,6 +195,9 @@ impl<'de> Deserialize<'de> for Icons {
             Name,
             File,
             Overrides,
+            Size,
+            Offset,
+            Adjustments,
         }
 
         struct IconsVisitor;
@@ -156,6 +227,7 @@ impl<'de> Deserialize<'de> for Icons {
             /// ```toml
             /// [icons]
             /// name = "awesome"
+            /// size = "large"
             /// ```
             fn visit_map<V>(self, mut map: V) -> Result<Icons, V::Error>
             where
@@ -163,6 +235,8 @@ impl<'de> Deserialize<'de> for Icons {
             {
                 let mut icons: Option<&str> = None;
                 let mut overrides: Option<HashMap<String, String>> = None;
+                let mut global = IconAdjustment::default();
+                let mut adjustments: Option<HashMap<String, IconAdjustment>> = None;
                 while let Some(key) = map.next_key()? {
                     match key {
                         // TODO merge name and file into one option (let's say "icons")
@@ -184,6 +258,24 @@ impl<'de> Deserialize<'de> for Icons {
                             }
                             overrides = Some(map.next_value()?);
                         }
+                        Field::Size => {
+                            if global.size.is_some() {
+                                return Err(de::Error::duplicate_field("size"));
+                            }
+                            global.size = Some(map.next_value()?);
+                        }
+                        Field::Offset => {
+                            if global.offset.is_some() {
+                                return Err(de::Error::duplicate_field("offset"));
+                            }
+                            global.offset = Some(map.next_value()?);
+                        }
+                        Field::Adjustments => {
+                            if adjustments.is_some() {
+                                return Err(de::Error::duplicate_field("adjustments"));
+                            }
+                            adjustments = Some(map.next_value()?);
+                        }
                     }
                 }
 
@@ -199,6 +291,10 @@ impl<'de> Deserialize<'de> for Icons {
                         icons.0.insert(icon.0, icon.1);
                     }
                 }
+
+                icons
+                    .apply_adjustments(&global, &adjustments.unwrap_or_default())
+                    .map_err(de::Error::custom)?;
                 Ok(icons)
             }
         }
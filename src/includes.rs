@@ -0,0 +1,423 @@
+//! Reading of the configuration file with the files it includes, e.g. to keep the blocks in
+//! separate files and to override some options on each host:
+//!
+//! ```toml
+//! include = ["blocks/*.toml", "host-$HOSTNAME.toml"]
+//! ```
+//!
+//! The paths are relative to the file that includes them, can contain environment variables, and
+//! `*` and `?` in the file name. The files of a pattern are included in the order of their names,
+//! files that don't exist are skipped. The file itself comes first, then every include with its
+//! own includes, in the order of the list. Later files override the options of earlier ones,
+//! tables like `[theme.overrides]` are merged, and the `[[block]]`s and `[[bar]]`s are appended.
+//!
+//! Files with the extension `.json` are read as JSON, those with `.yaml` or `.yml` as YAML, all
+//! others as TOML.
+//!
+//! `${NAME}` in the strings of all files is replaced by the environment variable, or by the default
+//! of `${NAME:-default}` if the variable is not set. Other variables are left as they are, so the
+//! ones of the shell commands of blocks still work.
+//!
+//! The `[templates]` of blocks are interpolated when they are used, after their parameters.
+
+use std::borrow::Cow;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use toml::value::Table;
+use toml::Value;
+
+use crate::errors::*;
+use crate::templates;
+
+lazy_static! {
+    static ref VARIABLE: Regex = Regex::new(r"\$\{([A-Za-z_][A-Za-z0-9_]*)(:-([^}]*))?\}").unwrap();
+}
+
+/// The files a configuration is read from, the configuration file itself or the pattern of an
+/// include
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Source {
+    pub dir: PathBuf,
+    /// The file name, with `*` and `?`
+    name: String,
+}
+
+impl Source {
+    fn new(path: &Path) -> Result<Self> {
+        let (dir, name) = match (
+            path.parent(),
+            path.file_name().and_then(|name| name.to_str()),
+        ) {
+            (Some(dir), Some(name)) => (dir, name),
+            _ => {
+                return Err(ConfigurationError(
+                    "invalid include".to_string(),
+                    path.display().to_string(),
+                ))
+            }
+        };
+        if dir.to_string_lossy().contains(&['*', '?'][..]) {
+            return Err(ConfigurationError(
+                "invalid include, only the file name can contain '*' and '?'".to_string(),
+                path.display().to_string(),
+            ));
+        }
+        Ok(Source {
+            dir: dir.to_path_buf(),
+            name: name.to_string(),
+        })
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        // Like the shell, hidden files are only matched explicitly
+        (!name.starts_with('.') || self.name.starts_with('.')) && glob_match(&self.name, name)
+    }
+
+    /// The files of the pattern, in the order of their names
+    fn files(&self) -> Vec<PathBuf> {
+        if !self.name.contains(&['*', '?'][..]) {
+            let path = self.dir.join(&self.name);
+            return if path.exists() {
+                vec![path]
+            } else {
+                Vec::new()
+            };
+        }
+        let mut files: Vec<PathBuf> = fs::read_dir(&self.dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|entry| self.matches(&entry.file_name().to_string_lossy()))
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .collect();
+        files.sort();
+        files
+    }
+}
+
+pub struct Included {
+    pub value: Value,
+    /// The configuration file first, then the includes in the order they were read
+    pub sources: Vec<Source>,
+}
+
+/// Reads the configuration file at `path` and its includes into one value
+pub fn read(path: &Path) -> Result<Included> {
+    let mut included = Included {
+        value: Value::Table(Table::new()),
+        sources: vec![Source::new(path)?],
+    };
+    include(path, &mut Vec::new(), &mut included)?;
+    templates::expand(&mut included.value)?;
+    Ok(included)
+}
+
+fn include(path: &Path, parents: &mut Vec<PathBuf>, included: &mut Included) -> Result<()> {
+    if parents.iter().any(|parent| parent == path) {
+        return Err(ConfigurationError(
+            "the configuration includes itself".to_string(),
+            path.display().to_string(),
+        ));
+    }
+    let contents = fs::read_to_string(path).configuration_error(&format!(
+        "failed to read the configuration file '{}'",
+        path.display()
+    ))?;
+    let mut value = parse(path, &contents)?;
+    // The templates are interpolated when they are used, with their parameters first
+    let templates = value
+        .as_table_mut()
+        .and_then(|table| table.remove("templates"));
+    interpolate(&mut value, &Table::new());
+    if let (Some(table), Some(templates)) = (value.as_table_mut(), templates) {
+        table.insert("templates".to_string(), templates);
+    }
+
+    let patterns = match value
+        .as_table_mut()
+        .and_then(|table| table.remove("include"))
+    {
+        None => Vec::new(),
+        Some(Value::String(pattern)) => vec![pattern],
+        Some(Value::Array(patterns)) => patterns
+            .into_iter()
+            .map(|pattern| match pattern {
+                Value::String(pattern) => Ok(pattern),
+                _ => Err(ConfigurationError(
+                    "'include' must be a list of paths".to_string(),
+                    path.display().to_string(),
+                )),
+            })
+            .collect::<Result<_>>()?,
+        Some(_) => {
+            return Err(ConfigurationError(
+                "'include' must be a list of paths".to_string(),
+                path.display().to_string(),
+            ))
+        }
+    };
+    merge(&mut included.value, value);
+
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    parents.push(path.to_path_buf());
+    for pattern in patterns {
+        let source = Source::new(&dir.join(expand(&pattern)?.as_ref()))?;
+        for file in source.files() {
+            include(&file, parents, included)?;
+        }
+        included.sources.push(source);
+    }
+    parents.pop();
+    Ok(())
+}
+
+/// Parses a file as JSON or YAML by its extension, e.g. for configurations that are generated, and
+/// as TOML otherwise
+fn parse(path: &Path, contents: &str) -> Result<Value> {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("json") => serde_json::from_str(contents)
+            .configuration_error(&format!("failed to parse JSON from '{}'", path.display())),
+        Some("yaml" | "yml") => serde_yaml::from_str(contents)
+            .configuration_error(&format!("failed to parse YAML from '{}'", path.display())),
+        _ => toml::from_str(contents)
+            .configuration_error(&format!("failed to parse TOML from '{}'", path.display())),
+    }
+}
+
+/// `HOSTNAME` is set by shells, but usually not exported
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().or_else(|| match name {
+        "HOSTNAME" => {
+            let mut buffer = [0; 256];
+            nix::unistd::gethostname(&mut buffer)
+                .ok()?
+                .to_str()
+                .ok()
+                .map(String::from)
+        }
+        _ => None,
+    })
+}
+
+/// Expands the variables and `~` of the path of an include
+fn expand(pattern: &str) -> Result<Cow<'_, str>> {
+    shellexpand::full_with_context(
+        pattern,
+        || std::env::var("HOME").ok(),
+        |name| match env_var(name) {
+            Some(value) => Ok(Some(value)),
+            None => Err(format!("the environment variable '{}' is not set", name)),
+        },
+    )
+    .map_err(|e| ConfigurationError(format!("invalid include '{}'", pattern), e.to_string()))
+}
+
+/// Replaces `${NAME}` by the parameter `NAME` of a template, or by the environment variable
+pub fn interpolate(value: &mut Value, parameters: &Table) {
+    match value {
+        Value::String(string) if string.contains("${") => {
+            // A string that is only a parameter becomes its value, e.g. an integer
+            let whole = VARIABLE
+                .captures(string)
+                .filter(|captures| captures[0].len() == string.len())
+                .and_then(|captures| parameters.get(&captures[1]));
+            if let Some(parameter) = whole {
+                *value = parameter.clone();
+                return;
+            }
+            let replaced = VARIABLE.replace_all(string, |captures: &Captures| {
+                let parameter = parameters.get(&captures[1]).map(|value| match value {
+                    Value::String(string) => string.clone(),
+                    other => other.to_string(),
+                });
+                match (parameter.or_else(|| env_var(&captures[1])), captures.get(3)) {
+                    (Some(value), _) => value,
+                    (None, Some(default)) => default.as_str().to_string(),
+                    (None, None) => captures[0].to_string(),
+                }
+            });
+            *string = replaced.into_owned();
+        }
+        Value::Array(values) => values
+            .iter_mut()
+            .for_each(|value| interpolate(value, parameters)),
+        Value::Table(table) => table
+            .iter_mut()
+            .for_each(|(_, value)| interpolate(value, parameters)),
+        _ => {}
+    }
+}
+
+/// The names of the variables in the strings of `value`
+pub fn variables(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(string) => VARIABLE
+            .captures_iter(string)
+            .map(|captures| captures[1].to_string())
+            .collect(),
+        Value::Array(values) => values.iter().flat_map(variables).collect(),
+        Value::Table(table) => table.values().flat_map(variables).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn merge(base: &mut Value, value: Value) {
+    if let (Value::Table(base), Value::Table(table)) = (base, value) {
+        merge_tables(base, table, true);
+    }
+}
+
+fn merge_tables(base: &mut Table, table: Table, top_level: bool) {
+    for (key, value) in table {
+        let replaced = match (base.get_mut(&key), value) {
+            (Some(Value::Array(items)), Value::Array(more))
+                if top_level && (key == "block" || key == "bar") =>
+            {
+                items.extend(more);
+                None
+            }
+            (Some(Value::Table(base)), Value::Table(table)) => {
+                merge_tables(base, table, false);
+                None
+            }
+            (_, value) => Some(value),
+        };
+        if let Some(value) = replaced {
+            base.insert(key, value);
+        }
+    }
+}
+
+/// Matches `name` against a pattern with `*` and `?`
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // The last `*` and the position in the name it was tried at, to try it with one more character
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn globs() {
+        assert!(glob_match("*.toml", "net.toml"));
+        assert!(glob_match("*.toml", ".toml"));
+        assert!(!glob_match("*.toml", "net.toml.bak"));
+        assert!(glob_match("host-?.toml", "host-a.toml"));
+        assert!(!glob_match("host-?.toml", "host-ab.toml"));
+        assert!(glob_match("*-*.toml", "a-b-c.toml"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("a", ""));
+
+        let source = Source::new(Path::new("/blocks/*.toml")).unwrap();
+        assert!(source.matches("net.toml"));
+        assert!(!source.matches(".net.toml"));
+        assert!(Source::new(Path::new("/*/net.toml")).is_err());
+    }
+
+    #[test]
+    fn merging() {
+        let mut base: Value = toml::from_str(
+            r#"
+            theme = "plain"
+            [icons]
+            name = "awesome"
+            overrides = { cpu = "C" }
+            [[block]]
+            block = "cpu"
+            "#,
+        )
+        .unwrap();
+        let host: Value = toml::from_str(
+            r#"
+            theme = "solarized-dark"
+            [icons.overrides]
+            net = "N"
+            [[block]]
+            block = "net"
+            "#,
+        )
+        .unwrap();
+        merge(&mut base, host);
+        assert_eq!(base["theme"].as_str(), Some("solarized-dark"));
+        assert_eq!(base["icons"]["name"].as_str(), Some("awesome"));
+        assert_eq!(base["icons"]["overrides"]["cpu"].as_str(), Some("C"));
+        assert_eq!(base["icons"]["overrides"]["net"].as_str(), Some("N"));
+        let blocks: Vec<_> = base["block"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|block| block["block"].as_str().unwrap())
+            .collect();
+        assert_eq!(blocks, ["cpu", "net"]);
+    }
+
+    #[test]
+    fn formats() {
+        let json = r#"{"theme": "plain", "block": [{"block": "cpu", "interval": 1.5}]}"#;
+        let value = parse(Path::new("config.json"), json).unwrap();
+        assert_eq!(value["theme"].as_str(), Some("plain"));
+        assert_eq!(value["block"][0]["interval"].as_float(), Some(1.5));
+        assert!(parse(Path::new("config.toml"), json).is_err());
+
+        let yaml = "theme: plain\nblock:\n  - block: cpu\n    interval: 1.5\n  - block: time\n";
+        let value = parse(Path::new("config.yaml"), yaml).unwrap();
+        assert_eq!(value["theme"].as_str(), Some("plain"));
+        assert_eq!(value["block"][0]["interval"].as_float(), Some(1.5));
+        assert_eq!(value["block"][1]["block"].as_str(), Some("time"));
+        assert_eq!(parse(Path::new("config.yml"), yaml).unwrap(), value);
+        assert_eq!(
+            parse(Path::new("config.json"), json).unwrap()["block"][0],
+            value["block"][0]
+        );
+    }
+
+    #[test]
+    fn interpolation() {
+        std::env::set_var("I3RS_TEST_INTERFACE", "wlan0");
+        std::env::remove_var("I3RS_TEST_UNSET");
+        let mut value: Value = toml::from_str(
+            r#"
+            device = "${I3RS_TEST_INTERFACE}"
+            format = "{ssid} on ${I3RS_TEST_UNSET:-eth0}"
+            command = "awk '{print $1}' ${I3RS_TEST_UNSET}"
+            "#,
+        )
+        .unwrap();
+        interpolate(&mut value, &Table::new());
+        assert_eq!(value["device"].as_str(), Some("wlan0"));
+        assert_eq!(value["format"].as_str(), Some("{ssid} on eth0"));
+        assert_eq!(
+            value["command"].as_str(),
+            Some("awk '{print $1}' ${I3RS_TEST_UNSET}")
+        );
+    }
+}
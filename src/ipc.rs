@@ -0,0 +1,603 @@
+//! Control of a running bar, e.g. from the key bindings of i3 or sway.
+//!
+//! Every bar listens on a Unix socket in `$XDG_RUNTIME_DIR/i3status-rust`, and the first one is
+//! also reachable at `$XDG_RUNTIME_DIR/i3status-rs.sock`. Requests are single lines, either text
+//! like `hide net` or JSON objects like `{"command": "hide", "block": "net"}`. Text requests are
+//! answered with `ok` or `error <message>`, JSON requests with `{"ok": true}` or
+//! `{"error": "<message>"}`. The query `get-state` is answered with the state of the blocks as JSON
+//! instead of `ok`, or with `{"ok": true, "state": {..}}`.
+//!
+//! With `command_fifo`, the bar also reads text requests from a FIFO, e.g. for key bindings that
+//! just `echo` into it. These are not answered, errors are printed to stderr.
+
+use std::fs;
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, MetadataExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use crossbeam_channel::{Receiver, Sender};
+use serde_derive::Deserialize;
+use serde_json::Value;
+
+use crate::errors::*;
+use crate::protocol::i3bar_event::MouseButton;
+
+/// How long a client may take to send its request or to receive the reply
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct Request {
+    pub command: Command,
+    reply: Sender<Reply>,
+}
+
+/// The result of a request, with the answer of a query
+type Reply = StdResult<Option<Value>, String>;
+
+impl Request {
+    pub fn reply(self, result: StdResult<(), String>) {
+        // The client might have gone away in the meantime
+        let _ = self.reply.send(result.map(|()| None));
+    }
+
+    /// Answers a query like `GetState`
+    pub fn reply_with(self, answer: Value) {
+        let _ = self.reply.send(Ok(Some(answer)));
+    }
+}
+
+/// Blocks are referenced by their `export` name, their type or their position on the bar
+#[derive(Deserialize, Debug)]
+#[serde(tag = "command", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Command {
+    /// Runs the action of the block
+    Action {
+        block: String,
+        action: String,
+    },
+    /// Updates the block right away
+    Refresh {
+        block: String,
+    },
+    Hide {
+        block: String,
+    },
+    Show {
+        block: String,
+    },
+    /// Hides the block if it's shown and shows it otherwise
+    Toggle {
+        block: String,
+    },
+    /// Shows `text` instead of the block, `None` shows the block again
+    SetText {
+        block: String,
+        #[serde(default)]
+        text: Option<String>,
+    },
+    /// Switches all blocks to their icons, `None` toggles
+    Compact {
+        #[serde(default)]
+        enabled: Option<bool>,
+    },
+    /// Masks the sensitive placeholders, `None` toggles
+    Presentation {
+        #[serde(default)]
+        enabled: Option<bool>,
+    },
+    /// Switches to the light or the dark variant of the theme, `None` toggles
+    Theme {
+        #[serde(default)]
+        light: Option<bool>,
+    },
+    /// Shows only the blocks of the profile, `None` switches to the next profile
+    Profile {
+        #[serde(default)]
+        name: Option<String>,
+    },
+    /// Reloads the configuration file
+    Reload,
+    /// Adds a reminder to the `reminders` block, e.g. `standup in 20m`
+    Remind {
+        text: String,
+    },
+    /// Answers with the state of all blocks, see `state`
+    GetState,
+    /// The release of the held button, `None` for any button, see `hold_interval`
+    Release {
+        #[serde(default)]
+        button: Option<MouseButton>,
+    },
+}
+
+/// `on`, `off` or `toggle` (`None`)
+pub fn parse_mode(mode: Option<&str>) -> StdResult<Option<bool>, String> {
+    match mode {
+        Some("on") => Ok(Some(true)),
+        Some("off") => Ok(Some(false)),
+        Some("toggle") | None => Ok(None),
+        Some(mode) => Err(format!("invalid mode '{}'", mode)),
+    }
+}
+
+fn mode_name(mode: Option<bool>) -> &'static str {
+    match mode {
+        Some(true) => "on",
+        Some(false) => "off",
+        None => "toggle",
+    }
+}
+
+/// `light`, `dark` or `toggle` (`None`)
+pub fn parse_variant(variant: Option<&str>) -> StdResult<Option<bool>, String> {
+    match variant {
+        Some("light") => Ok(Some(true)),
+        Some("dark") => Ok(Some(false)),
+        Some("toggle") | None => Ok(None),
+        Some(variant) => Err(format!("invalid theme variant '{}'", variant)),
+    }
+}
+
+/// `left`, `middle`, `right` or any button (`None`)
+pub fn parse_button(button: Option<&str>) -> StdResult<Option<MouseButton>, String> {
+    match button {
+        Some("left") => Ok(Some(MouseButton::Left)),
+        Some("middle") => Ok(Some(MouseButton::Middle)),
+        Some("right") => Ok(Some(MouseButton::Right)),
+        Some("any") | None => Ok(None),
+        Some(button) => Err(format!("invalid button '{}'", button)),
+    }
+}
+
+fn button_name(button: Option<MouseButton>) -> &'static str {
+    match button {
+        Some(MouseButton::Left) => "left",
+        Some(MouseButton::Middle) => "middle",
+        Some(MouseButton::Right) => "right",
+        _ => "any",
+    }
+}
+
+fn variant_name(light: Option<bool>) -> &'static str {
+    match light {
+        Some(true) => "light",
+        Some(false) => "dark",
+        None => "toggle",
+    }
+}
+
+impl Command {
+    fn parse(line: &str) -> StdResult<Self, String> {
+        if is_json(line) {
+            return serde_json::from_str(line).map_err(|e| format!("invalid request: {}", e));
+        }
+        let mut words = line.split_whitespace();
+        match (words.next(), words.next(), words.next(), words.next()) {
+            (Some("action"), Some(block), Some(action), None) => Ok(Command::Action {
+                block: block.to_string(),
+                action: action.to_string(),
+            }),
+            (Some("refresh"), Some(block), None, None) => Ok(Command::Refresh {
+                block: block.to_string(),
+            }),
+            (Some("hide"), Some(block), None, None) => Ok(Command::Hide {
+                block: block.to_string(),
+            }),
+            (Some("show"), Some(block), None, None) => Ok(Command::Show {
+                block: block.to_string(),
+            }),
+            (Some("toggle"), Some(block), None, None) => Ok(Command::Toggle {
+                block: block.to_string(),
+            }),
+            (Some("text"), Some(block), ..) => {
+                // The rest of the line, which can contain spaces. Nothing clears the text.
+                let text = line
+                    .trim()
+                    .splitn(3, char::is_whitespace)
+                    .nth(2)
+                    .map(str::trim)
+                    .filter(|text| !text.is_empty());
+                Ok(Command::SetText {
+                    block: block.to_string(),
+                    text: text.map(String::from),
+                })
+            }
+            (Some("compact"), mode, None, None) => {
+                parse_mode(mode).map(|enabled| Command::Compact { enabled })
+            }
+            (Some("presentation"), mode, None, None) => {
+                parse_mode(mode).map(|enabled| Command::Presentation { enabled })
+            }
+            (Some("theme"), variant, None, None) => {
+                parse_variant(variant).map(|light| Command::Theme { light })
+            }
+            (Some("profile"), name, None, None) => Ok(Command::Profile {
+                name: name.map(String::from),
+            }),
+            (Some("reload"), None, None, None) => Ok(Command::Reload),
+            (Some("get-state"), None, None, None) => Ok(Command::GetState),
+            (Some("release"), button, None, None) => {
+                parse_button(button).map(|button| Command::Release { button })
+            }
+            (Some("remind"), Some(_), ..) => Ok(Command::Remind {
+                text: line
+                    .trim()
+                    .split_once(char::is_whitespace)
+                    .unwrap()
+                    .1
+                    .trim()
+                    .to_string(),
+            }),
+            _ => Err(format!("invalid request '{}'", line.trim())),
+        }
+    }
+
+    fn to_line(&self) -> String {
+        match self {
+            Command::Action { block, action } => format!("action {} {}\n", block, action),
+            Command::Refresh { block } => format!("refresh {}\n", block),
+            Command::Hide { block } => format!("hide {}\n", block),
+            Command::Show { block } => format!("show {}\n", block),
+            Command::Toggle { block } => format!("toggle {}\n", block),
+            Command::SetText { block, text } => format!(
+                "text {} {}\n",
+                block,
+                text.as_deref().unwrap_or_default().replace('\n', " ")
+            ),
+            Command::Compact { enabled } => format!("compact {}\n", mode_name(*enabled)),
+            Command::Presentation { enabled } => {
+                format!("presentation {}\n", mode_name(*enabled))
+            }
+            Command::Theme { light } => format!("theme {}\n", variant_name(*light)),
+            Command::Profile { name } => match name {
+                Some(name) => format!("profile {}\n", name),
+                None => "profile\n".to_string(),
+            },
+            Command::Reload => "reload\n".to_string(),
+            Command::GetState => "get-state\n".to_string(),
+            Command::Release { button } => format!("release {}\n", button_name(*button)),
+            Command::Remind { text } => format!("remind {}\n", text.replace('\n', " ")),
+        }
+    }
+}
+
+fn is_json(line: &str) -> bool {
+    line.trim_start().starts_with('{')
+}
+
+/// The directory of the sockets, in `$XDG_RUNTIME_DIR` or else in `/tmp`
+pub fn socket_dir() -> PathBuf {
+    let runtime_dir = std::env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| "/tmp".to_string());
+    PathBuf::from(runtime_dir).join("i3status-rust")
+}
+
+/// Creates the socket directory, which only this user can access. In `/tmp` another user could
+/// have created it first, so it's refused if it belongs to someone else.
+pub fn create_socket_dir() -> Result<PathBuf> {
+    let dir = socket_dir();
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(&dir)
+        .internal_error("ipc", "failed to create the socket directory")?;
+    check_socket_dir(&dir)?;
+    Ok(dir)
+}
+
+/// Fails if `dir` is not a directory of this user, and makes it accessible only to them
+fn check_socket_dir(dir: &Path) -> Result<()> {
+    let metadata =
+        fs::symlink_metadata(dir).internal_error("ipc", "failed to read the socket directory")?;
+    if !metadata.is_dir() || metadata.uid() != nix::unistd::getuid().as_raw() {
+        return Err(InternalError(
+            "ipc".to_string(),
+            format!("'{}' is not a directory of this user", dir.display()),
+            None,
+        ));
+    }
+    if metadata.mode() & 0o077 != 0 {
+        fs::set_permissions(dir, fs::Permissions::from_mode(0o700))
+            .internal_error("ipc", "failed to restrict the socket directory")?;
+    }
+    Ok(())
+}
+
+/// Starts a thread that accepts connections on the socket of this process and passes the
+/// requests to the main loop
+pub fn listen(sender: Sender<Request>) -> Result<()> {
+    let dir = create_socket_dir()?;
+    let path = dir.join(format!("{}.sock", std::process::id()));
+    // Left over by a process with the same pid that didn't exit cleanly, or by this process
+    // before it reloaded
+    let _ = fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).internal_error("ipc", "failed to bind socket")?;
+    link_socket(&path);
+
+    thread::Builder::new()
+        .name("ipc".into())
+        .spawn(move || {
+            for stream in listener.incoming().flatten() {
+                // A broken client must not take the listener down
+                let _ = handle_client(stream, &sender);
+            }
+        })
+        .map(|_| ())
+        .internal_error("ipc", "failed to start thread")
+}
+
+/// Points `$XDG_RUNTIME_DIR/i3status-rs.sock` at the socket at `path`, unless it already leads to
+/// another running bar. Without `XDG_RUNTIME_DIR` there is no such link, as another user could
+/// create it in `/tmp`.
+fn link_socket(path: &Path) {
+    let link = match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(runtime_dir) => PathBuf::from(runtime_dir).join("i3status-rs.sock"),
+        None => return,
+    };
+    if fs::read_link(&link).is_ok_and(|target| target == path) || UnixStream::connect(&link).is_ok()
+    {
+        return;
+    }
+    // Only a link left by a bar that is gone is replaced, never a file of something else
+    if fs::symlink_metadata(&link).is_ok_and(|metadata| !metadata.file_type().is_symlink()) {
+        tracing::warn!(
+            "not linking the control socket, '{}' exists",
+            link.display()
+        );
+        return;
+    }
+    let _ = fs::remove_file(&link);
+    if let Err(e) = std::os::unix::fs::symlink(path, &link) {
+        tracing::warn!("failed to link the control socket: {}", e);
+    }
+}
+
+fn handle_client(mut stream: UnixStream, sender: &Sender<Request>) -> std::io::Result<()> {
+    // The clients are served one at a time, so one that doesn't send its request or doesn't read
+    // the reply must not hold up the others
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    let result = match Command::parse(&line) {
+        // The bar restarts before it could reply
+        Ok(Command::Reload) => {
+            reply(&mut stream, &line, Ok(None))?;
+            let (reply, _) = crossbeam_channel::bounded(1);
+            sender
+                .send(Request {
+                    command: Command::Reload,
+                    reply,
+                })
+                .unwrap();
+            return Ok(());
+        }
+        Ok(command) => execute(sender, command),
+        Err(e) => Err(e),
+    };
+    reply(&mut stream, &line, result)
+}
+
+/// Starts a thread that reads requests from the FIFO at `path`, which is created if it doesn't
+/// exist yet
+pub fn listen_fifo(path: &Path, sender: Sender<Request>) -> Result<()> {
+    match fs::metadata(path) {
+        Ok(metadata) if metadata.file_type().is_fifo() => {}
+        Ok(_) => {
+            return Err(ConfigurationError(
+                "command_fifo".to_string(),
+                format!("'{}' exists and is not a FIFO", path.display()),
+            ))
+        }
+        Err(_) => nix::unistd::mkfifo(path, nix::sys::stat::Mode::S_IRWXU)
+            .configuration_error(&format!("failed to create the FIFO '{}'", path.display()))?,
+    }
+
+    let path = path.to_path_buf();
+    thread::Builder::new()
+        .name("command fifo".into())
+        .spawn(move || loop {
+            // Waits for a writer, and the lines end when the last writer closes the FIFO
+            let fifo = match fs::File::open(&path) {
+                Ok(fifo) => fifo,
+                Err(e) => {
+                    tracing::error!("command fifo: failed to open '{}': {}", path.display(), e);
+                    return;
+                }
+            };
+            for line in BufReader::new(fifo).lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(_) => break,
+                };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let result = Command::parse(&line).and_then(|command| execute(&sender, command));
+                if let Err(e) = result {
+                    tracing::warn!("command fifo: {}", e);
+                }
+            }
+        })
+        .map(|_| ())
+        .internal_error("ipc", "failed to start thread")
+}
+
+/// Passes `command` to the main loop and waits for the result
+pub fn execute(sender: &Sender<Request>, command: Command) -> Reply {
+    let (reply, rx_reply): (_, Receiver<_>) = crossbeam_channel::bounded(1);
+    sender.send(Request { command, reply }).unwrap();
+    rx_reply
+        .recv()
+        .unwrap_or_else(|_| Err("the bar stopped".to_string()))
+}
+
+fn reply(stream: &mut UnixStream, line: &str, result: Reply) -> std::io::Result<()> {
+    match (result, is_json(line)) {
+        (Ok(None), false) => writeln!(stream, "ok"),
+        (Ok(Some(answer)), false) => writeln!(stream, "{}", answer),
+        (Err(e), false) => writeln!(stream, "error {}", e),
+        (Ok(None), true) => writeln!(stream, "{}", serde_json::json!({ "ok": true })),
+        (Ok(Some(answer)), true) => writeln!(
+            stream,
+            "{}",
+            serde_json::json!({ "ok": true, "state": answer })
+        ),
+        (Err(e), true) => writeln!(stream, "{}", serde_json::json!({ "error": e })),
+    }
+}
+
+/// Sends `command` to all running bars and returns the replies of the bars that accepted it.
+/// Fails if no bar accepted it.
+pub fn send(command: &Command) -> Result<Vec<String>> {
+    let dir = socket_dir();
+    let entries = fs::read_dir(&dir)
+        .configuration_error("no running i3status-rs found (no socket directory)")?;
+    check_socket_dir(&dir)?;
+    let mut errors = Vec::new();
+    let mut replies = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        // The sockets of the other bars are in a directory
+        if path.extension().is_none() {
+            continue;
+        }
+        let mut stream = match UnixStream::connect(&path) {
+            Ok(stream) => stream,
+            Err(e) if e.kind() == ErrorKind::ConnectionRefused => {
+                // The process is gone
+                let _ = fs::remove_file(&path);
+                continue;
+            }
+            // E.g. the bar is too busy to accept, which doesn't mean that it's gone
+            Err(_) => continue,
+        };
+        let mut reply = String::new();
+        stream
+            .write_all(command.to_line().as_bytes())
+            .and_then(|_| BufReader::new(&stream).read_line(&mut reply))
+            .internal_error("ipc", "failed to talk to i3status-rs")?;
+        match reply.trim_end().strip_prefix("error ") {
+            Some(e) => errors.push(e.to_string()),
+            None => replies.push(reply.trim_end().to_string()),
+        }
+    }
+    match (replies.is_empty(), errors.first()) {
+        (false, _) => Ok(replies),
+        (true, Some(e)) => Err(ConfigurationError("ipc".to_string(), e.clone())),
+        (true, None) => Err(ConfigurationError(
+            "ipc".to_string(),
+            "no running i3status-rs found".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        match Command::parse("action sound toggle_mute\n") {
+            Ok(Command::Action { block, action }) => {
+                assert_eq!(block, "sound");
+                assert_eq!(action, "toggle_mute");
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(matches!(
+            Command::parse("compact off\n"),
+            Ok(Command::Compact {
+                enabled: Some(false)
+            })
+        ));
+        assert!(matches!(
+            Command::parse("compact"),
+            Ok(Command::Compact { enabled: None })
+        ));
+        assert!(Command::parse("compact maybe").is_err());
+        assert!(matches!(
+            Command::parse("presentation on"),
+            Ok(Command::Presentation {
+                enabled: Some(true)
+            })
+        ));
+        match Command::parse("text net  on a train \n") {
+            Ok(Command::SetText { block, text }) => {
+                assert_eq!(block, "net");
+                assert_eq!(text.as_deref(), Some("on a train"));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(matches!(
+            Command::parse("text net\n"),
+            Ok(Command::SetText { text: None, .. })
+        ));
+        assert!(matches!(
+            Command::parse("theme light"),
+            Ok(Command::Theme { light: Some(true) })
+        ));
+        assert!(matches!(Command::parse("reload\n"), Ok(Command::Reload)));
+        assert!(matches!(
+            Command::parse("get-state\n"),
+            Ok(Command::GetState)
+        ));
+        match Command::parse("profile work\n") {
+            Ok(Command::Profile { name }) => assert_eq!(name.as_deref(), Some("work")),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(matches!(
+            Command::parse("profile"),
+            Ok(Command::Profile { name: None })
+        ));
+        assert!(matches!(
+            Command::parse("toggle net"),
+            Ok(Command::Toggle { .. })
+        ));
+        match Command::parse("remind standup  in 20m\n") {
+            Ok(Command::Remind { text }) => assert_eq!(text, "standup  in 20m"),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(Command::parse("remind").is_err());
+        assert!(matches!(
+            Command::parse("release left\n"),
+            Ok(Command::Release {
+                button: Some(MouseButton::Left)
+            })
+        ));
+        assert!(matches!(
+            Command::parse("release"),
+            Ok(Command::Release { button: None })
+        ));
+        assert!(Command::parse("release up").is_err());
+        assert!(Command::parse("action sound").is_err());
+        assert!(Command::parse("hide").is_err());
+        assert!(Command::parse("foo bar baz").is_err());
+    }
+
+    #[test]
+    fn parse_json() {
+        match Command::parse(r#"{"command": "set_text", "block": "0", "text": "hi"}"#) {
+            Ok(Command::SetText { block, text }) => {
+                assert_eq!(block, "0");
+                assert_eq!(text.as_deref(), Some("hi"));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(matches!(
+            Command::parse(r#"{"command": "compact"}"#),
+            Ok(Command::Compact { enabled: None })
+        ));
+        assert!(matches!(
+            Command::parse(r#"{"command": "reload"}"#),
+            Ok(Command::Reload)
+        ));
+        assert!(matches!(
+            Command::parse(r#"{"command": "get_state"}"#),
+            Ok(Command::GetState)
+        ));
+        assert!(Command::parse(r#"{"command": "hide"}"#).is_err());
+        assert!(Command::parse(r#"{"command": "explode", "block": "0"}"#).is_err());
+    }
+}
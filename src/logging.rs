@@ -0,0 +1,107 @@
+//! The log of the bar for `--log-level` and `--log-file`, and the updates of `--debug-dump`.
+//!
+//! Messages of the `tracing` macros are written with the time, the level, the spans and the
+//! module, e.g. `12:00:00.123  WARN block{id=3 name=cpu}: i3status_rs::blocks::base_block: update
+//! failed`. Updates and clicks of a block run in its `block` span. Messages of the threads of the
+//! blocks and of the rest of the bar only name their module. The levels are the directives of
+//! `EnvFilter`, like `warn,i3status_rs::scheduler=trace,[block{name=battery}]=debug`.
+//!
+//! The dump records every update of every block as a line of JSON: when it happened, how long it
+//! took, its error and the texts of the widgets it shows afterwards. It is meant to be attached to
+//! bug reports, e.g. when a block stopped updating.
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::Local;
+use lazy_static::lazy_static;
+use serde_json::json;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::time::FormatTime;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::EnvFilter;
+
+use crate::errors::*;
+use crate::state;
+use crate::widgets::I3BarWidget;
+
+lazy_static! {
+    static ref DUMP: Mutex<Option<File>> = Mutex::new(None);
+}
+
+/// The local time, like the times of the dump
+struct LocalTime;
+
+impl FormatTime for LocalTime {
+    fn format_time(&self, w: &mut Writer<'_>) -> fmt::Result {
+        write!(w, "{}", Local::now().format("%Y-%m-%dT%H:%M:%S%.3f"))
+    }
+}
+
+/// Sets up the log with the levels of `--log-level` and writes it to `file` or else to stderr
+pub fn init(levels: Option<&str>, file: Option<&str>) -> Result<()> {
+    let levels = levels.unwrap_or("warn");
+    let filter = EnvFilter::try_new(levels)
+        .configuration_error(&format!("invalid log levels '{}'", levels))?;
+    let writer = match file {
+        Some(path) => BoxMakeWriter::new(Mutex::new(
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .configuration_error(&format!("failed to open the log file '{}'", path))?,
+        )),
+        None => BoxMakeWriter::new(io::stderr),
+    };
+    // Restarts keep the subscriber of the process
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer)
+        .with_timer(LocalTime)
+        .with_ansi(false)
+        .try_init();
+    Ok(())
+}
+
+/// Records the updates of all blocks to `path`, for `--debug-dump`
+pub fn start_dump(path: &str) -> Result<()> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .configuration_error(&format!("failed to open the debug dump '{}'", path))?;
+    *DUMP.lock().unwrap() = Some(file);
+    Ok(())
+}
+
+/// Records an update of a block and what it shows afterwards, if the updates are dumped
+pub fn dump_update(
+    id: usize,
+    name: &str,
+    duration: Duration,
+    error: Option<&Error>,
+    next: Option<Duration>,
+    widgets: &[&dyn I3BarWidget],
+) {
+    let mut dump = DUMP.lock().unwrap();
+    let file = match dump.as_mut() {
+        Some(file) => file,
+        None => return,
+    };
+    let line = json!({
+        "time": Local::now().format("%Y-%m-%dT%H:%M:%S%.3f").to_string(),
+        "block": name,
+        "id": id,
+        "duration_ms": duration.as_secs_f64() * 1000.,
+        "error": error.map(|error| format!("{:?}", error)),
+        "next_update_in_ms": next.map(|next| next.as_millis() as u64),
+        "widgets": state::widgets(widgets),
+    });
+    if writeln!(file, "{}", line).is_err() {
+        // No dump rather than a broken one, e.g. if the disk is full
+        *dump = None;
+    }
+}
@@ -5,38 +5,76 @@ mod util;
 #[macro_use]
 mod formatting;
 mod apcaccess;
+mod bars;
 pub mod blocks;
+mod bus;
+mod cache;
+mod calendars;
+mod check;
+mod click;
 mod config;
+mod cron;
+mod dbus_connections;
+mod deadline;
 mod errors;
 mod http;
 mod icons;
+mod includes;
+mod ipc;
+mod logging;
+mod metrics;
+mod migrate;
+mod mock;
+mod netlink;
+mod nvml;
+mod polling;
+mod profiles;
 mod protocol;
+mod registry;
+mod reload;
+mod resume;
+mod rules;
 mod scheduler;
+mod schema;
+mod services;
 mod signals;
+mod state;
 mod subprocess;
+mod templates;
+mod theme_switch;
 mod themes;
+mod topics;
 mod widgets;
 
 #[cfg(feature = "pulseaudio")]
 use libpulse_binding as pulse;
 
-use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
-use clap::{crate_authors, crate_description, App, Arg, ArgMatches};
+use clap::{crate_authors, crate_description, App, Arg, ArgMatches, SubCommand};
 use crossbeam_channel::{select, Receiver, Sender};
 
 use crate::blocks::create_block;
 use crate::blocks::Block;
+use crate::click::ClickDispatcher;
 use crate::config::Config;
 use crate::config::SharedConfig;
 use crate::errors::*;
 use crate::protocol::i3bar_event::{process_events, I3BarEvent};
+use crate::reload::{Reload, Reloader};
 use crate::scheduler::{Task, UpdateScheduler};
 use crate::signals::process_signals;
 use crate::util::deserialize_file;
 use crate::widgets::text::TextWidget;
 use crate::widgets::{I3BarWidget, State};
 
+/// The time in which updates of the blocks are collected before the bar is drawn
+const FRAME: Duration = Duration::from_millis(30);
+
+/// How often the blocks are checked for being stuck
+const WATCHDOG_CHECK: Duration = Duration::from_secs(10);
+
 fn main() {
     let ver = if env!("GIT_COMMIT_HASH").is_empty() || env!("GIT_COMMIT_DATE").is_empty() {
         env!("CARGO_PKG_VERSION").to_string()
@@ -73,20 +111,431 @@ fn main() {
                 .long("never-pause")
                 .takes_value(false),
         )
+        .arg(
+            Arg::with_name("debug-clicks")
+                .help("Log click events and print the actions of clicks instead of executing them")
+                .long("debug-clicks")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("log-level")
+                .help("Sets the levels of the log, e.g. \"info\" or \"warn,[block{name=battery}]=debug,i3status_rs::scheduler=trace\"")
+                .long("log-level")
+                .value_name("LEVELS"),
+        )
+        .arg(
+            Arg::with_name("log-file")
+                .help("Writes the log to a file instead of stderr")
+                .long("log-file")
+                .value_name("LOG_FILE"),
+        )
+        .arg(
+            Arg::with_name("debug-dump")
+                .help("Records every update of the blocks and what they show to a file, e.g. to report a bug")
+                .long("debug-dump")
+                .value_name("DUMP_FILE"),
+        )
+        .arg(
+            Arg::with_name("metrics-addr")
+                .help("Serves the values of the blocks for Prometheus on this address, instead of the one of 'metrics'")
+                .long("metrics-addr")
+                .value_name("ADDRESS"),
+        )
+        .arg(
+            Arg::with_name("headless")
+                .help("Runs the blocks without printing a bar, their state is served by get-state, D-Bus and the metrics")
+                .long("headless")
+                .takes_value(false)
+                .conflicts_with_all(&["bar", "replay"]),
+        )
+        .arg(
+            Arg::with_name("mock")
+                .help("Runs only the blocks of this type, with the data of the fixtures file")
+                .long("mock")
+                .value_name("BLOCK")
+                .requires("fixtures"),
+        )
+        .arg(
+            Arg::with_name("fixtures")
+                .help("Sets a toml file with the recorded data for --mock")
+                .long("fixtures")
+                .value_name("FIXTURES_FILE"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .help(
+                    "Prints the bar for i3bar, as a line with (term) or without (plain) colors, or for Waybar",
+                )
+                .long("output")
+                .value_name("OUTPUT")
+                .possible_values(&["i3bar", "term", "plain", "waybar"])
+                .default_value("i3bar"),
+        )
+        .arg(
+            Arg::with_name("block")
+                .help("Runs only the block with this export name, type or position, e.g. for a module of Waybar")
+                .long("block")
+                .value_name("BLOCK")
+                .conflicts_with("mock"),
+        )
+        .arg(
+            Arg::with_name("bar")
+                .help("Shows a bar of the [[bar]] sections of the i3status-rs that is running")
+                .long("bar")
+                .value_name("NAME")
+                .conflicts_with_all(&["block", "mock", "replay"]),
+        )
+        .arg(
+            Arg::with_name("record")
+                .help("Records the output with its timing to a file, e.g. to report a bug")
+                .long("record")
+                .value_name("RECORDING_FILE")
+                .conflicts_with("replay"),
+        )
+        .arg(
+            Arg::with_name("replay")
+                .help("Sends the output of a recording instead of running the blocks")
+                .long("replay")
+                .value_name("RECORDING_FILE"),
+        )
+        .arg(
+            Arg::with_name("replay-speed")
+                .help("Replays the recording this many times faster, 0 replays without pauses")
+                .long("replay-speed")
+                .value_name("FACTOR")
+                .default_value("1")
+                .validator(|speed| match speed.parse::<f64>() {
+                    Ok(speed) if speed >= 0. && speed.is_finite() => Ok(()),
+                    _ => Err("must be a number of at least 0".to_string()),
+                }),
+        )
         .arg(
             Arg::with_name("no-init")
                 .help("Do not send an init sequence")
                 .long("no-init")
                 .takes_value(false)
                 .hidden(true),
+        )
+        .subcommand(
+            SubCommand::with_name("action")
+                .about("Runs an action of a block in the running bars")
+                .arg(
+                    Arg::with_name("block")
+                        .help("The export name, the type or the position of the block")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("action")
+                        .help("The action, e.g. toggle_mute or up")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("refresh")
+                .about("Updates a block in the running bars")
+                .arg(
+                    Arg::with_name("block")
+                        .help("The export name, the type or the position of the block")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("hide")
+                .about("Hides a block in the running bars")
+                .arg(
+                    Arg::with_name("block")
+                        .help("The export name, the type or the position of the block")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("show")
+                .about("Shows a block again that was hidden")
+                .arg(
+                    Arg::with_name("block")
+                        .help("The export name, the type or the position of the block")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("toggle")
+                .about("Hides a block in the running bars if it's shown and shows it otherwise")
+                .arg(
+                    Arg::with_name("block")
+                        .help("The export name, the type or the position of the block")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("text")
+                .about("Shows a text instead of a block in the running bars")
+                .arg(
+                    Arg::with_name("block")
+                        .help("The export name, the type or the position of the block")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("text")
+                        .help("The text, or nothing to show the block again")
+                        .multiple(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("theme")
+                .about("Switches the running bars to the light or the dark theme")
+                .arg(
+                    Arg::with_name("variant")
+                        .possible_values(&["light", "dark", "toggle"])
+                        .default_value("toggle"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("remind")
+                .about("Adds a reminder to the reminders block of the running bars")
+                .arg(
+                    Arg::with_name("reminder")
+                        .help("The text and the time, e.g. \"standup in 20m\" or \"lunch at 12:30\"")
+                        .required(true)
+                        .multiple(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("profile")
+                .about("Switches the running bars to a profile, which shows only its blocks")
+                .arg(
+                    Arg::with_name("name")
+                        .help("The name of the profile, or nothing for the next profile"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("reload")
+                .about("Reloads the configuration files of the running bars"),
+        )
+        .subcommand(
+            SubCommand::with_name("release")
+                .about("Tells the running bars that a button was released, for hold bindings")
+                .arg(
+                    Arg::with_name("button")
+                        .possible_values(&["left", "middle", "right", "any"])
+                        .default_value("any"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("get-state")
+                .about("Prints the state of all blocks of the running bars as JSON"),
+        )
+        .subcommand(
+            SubCommand::with_name("check")
+                .about("Checks the configuration file and the blocks in it, without starting a bar")
+                .arg(
+                    Arg::with_name("config")
+                        .value_name("CONFIG_FILE")
+                        .help("The configuration file, if it's not the one given before 'check'"),
+                )
+                .arg(
+                    Arg::with_name("update")
+                        .help("Also updates every block once, e.g. to find missing programs")
+                        .long("update")
+                        .takes_value(false),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("schema")
+                .about("Prints a JSON Schema of the configuration, for editors"),
+        )
+        .subcommand(
+            SubCommand::with_name("migrate")
+                .about("Converts the configuration of i3status or polybar and prints it as TOML")
+                .arg(
+                    Arg::with_name("from")
+                        .help("The program of the configuration")
+                        .long("from")
+                        .possible_values(&["i3status", "polybar"])
+                        .default_value("i3status"),
+                )
+                .arg(
+                    Arg::with_name("file")
+                        .help("The configuration file, e.g. ~/.config/i3status/config")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("install-taskwarrior-hook")
+                .about("Installs a hook that updates the taskwarrior block when tasks change")
+                .arg(
+                    Arg::with_name("hooks")
+                        .help("The hooks directory of taskwarrior, its 'hooks.location'")
+                        .long("hooks")
+                        .value_name("DIR")
+                        .default_value("~/.task/hooks"),
+                )
+                .arg(
+                    Arg::with_name("block")
+                        .help("The export name, the type or the position of the block")
+                        .long("block")
+                        .default_value("taskwarrior"),
+                )
+                .arg(
+                    Arg::with_name("signal")
+                        .help("Send the 'signal' of the block instead of using the socket")
+                        .long("signal")
+                        .value_name("SIGNAL")
+                        .conflicts_with("block")
+                        .validator(|signal| match signal.parse::<i32>() {
+                            Ok(_) => Ok(()),
+                            Err(_) => Err(format!("invalid signal '{}'", signal)),
+                        }),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("compact")
+                .about("Shows only the icons of the blocks in the running bars")
+                .arg(
+                    Arg::with_name("mode")
+                        .possible_values(&["on", "off", "toggle"])
+                        .default_value("toggle"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("presentation")
+                .about("Masks the sensitive placeholders in the running bars")
+                .arg(
+                    Arg::with_name("mode")
+                        .possible_values(&["on", "off", "toggle"])
+                        .default_value("toggle"),
+                ),
         );
 
     let matches = builder.get_matches();
     let exit_on_error = matches.is_present("exit-on-error");
 
+    if let ("check", Some(check)) = matches.subcommand() {
+        let path = check
+            .value_of("config")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| config_path(&matches));
+        let errors = check::check(&path, check.is_present("update"));
+        ::std::process::exit(if errors == 0 { 0 } else { 1 });
+    }
+    if let ("schema", Some(_)) = matches.subcommand() {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&schema::schema()).unwrap()
+        );
+        return;
+    }
+    if let ("migrate", Some(migrate)) = matches.subcommand() {
+        let source = match migrate.value_of("from") {
+            Some("polybar") => migrate::Source::Polybar,
+            _ => migrate::Source::I3status,
+        };
+        let path = Path::new(migrate.value_of("file").unwrap());
+        let result = std::fs::read_to_string(path)
+            .configuration_error(&format!("failed to read '{}'", path.display()))
+            .and_then(|contents| migrate::migrate(source, &contents));
+        match result {
+            Ok(toml) => print!("{}", toml),
+            Err(error) => {
+                eprintln!("{:?}", error);
+                ::std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if let ("install-taskwarrior-hook", Some(install)) = matches.subcommand() {
+        // The values were checked by clap
+        let trigger = match install.value_of("signal") {
+            Some(signal) => blocks::taskwarrior::HookTrigger::Signal(signal.parse().unwrap()),
+            None => {
+                blocks::taskwarrior::HookTrigger::Refresh(install.value_of("block").unwrap().into())
+            }
+        };
+        let hooks = install.value_of("hooks").unwrap();
+        let result = shellexpand::full(hooks)
+            .configuration_error("failed to expand the hooks directory")
+            .and_then(|hooks| blocks::taskwarrior::install_hook(Path::new(&*hooks), &trigger));
+        match result {
+            Ok(path) => println!("installed {}", path.display()),
+            Err(error) => {
+                eprintln!("{:?}", error);
+                ::std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let command = match matches.subcommand() {
+        ("action", Some(action)) => Some(ipc::Command::Action {
+            block: action.value_of("block").unwrap().to_string(),
+            action: action.value_of("action").unwrap().to_string(),
+        }),
+        ("refresh", Some(refresh)) => Some(ipc::Command::Refresh {
+            block: refresh.value_of("block").unwrap().to_string(),
+        }),
+        ("hide", Some(hide)) => Some(ipc::Command::Hide {
+            block: hide.value_of("block").unwrap().to_string(),
+        }),
+        ("show", Some(show)) => Some(ipc::Command::Show {
+            block: show.value_of("block").unwrap().to_string(),
+        }),
+        ("toggle", Some(toggle)) => Some(ipc::Command::Toggle {
+            block: toggle.value_of("block").unwrap().to_string(),
+        }),
+        ("text", Some(text)) => Some(ipc::Command::SetText {
+            block: text.value_of("block").unwrap().to_string(),
+            text: text
+                .values_of("text")
+                .map(|words| words.collect::<Vec<_>>().join(" ")),
+        }),
+        ("profile", Some(profile)) => Some(ipc::Command::Profile {
+            name: profile.value_of("name").map(String::from),
+        }),
+        ("reload", Some(_)) => Some(ipc::Command::Reload),
+        ("get-state", Some(_)) => Some(ipc::Command::GetState),
+        ("remind", Some(remind)) => Some(ipc::Command::Remind {
+            text: remind
+                .values_of("reminder")
+                .unwrap()
+                .collect::<Vec<_>>()
+                .join(" "),
+        }),
+        // The values were checked by clap
+        ("theme", Some(theme)) => Some(ipc::Command::Theme {
+            light: ipc::parse_variant(theme.value_of("variant")).unwrap(),
+        }),
+        ("release", Some(release)) => Some(ipc::Command::Release {
+            button: ipc::parse_button(release.value_of("button")).unwrap(),
+        }),
+        ("compact", Some(compact)) => Some(ipc::Command::Compact {
+            enabled: ipc::parse_mode(compact.value_of("mode")).unwrap(),
+        }),
+        ("presentation", Some(presentation)) => Some(ipc::Command::Presentation {
+            enabled: ipc::parse_mode(presentation.value_of("mode")).unwrap(),
+        }),
+        _ => None,
+    };
+    if let Some(command) = command {
+        match ipc::send(&command) {
+            // A line of JSON for every running bar
+            Ok(replies) if matches!(command, ipc::Command::GetState) => {
+                for reply in replies {
+                    println!("{}", reply);
+                }
+            }
+            Ok(_) => (),
+            Err(error) => {
+                eprintln!("{:?}", error);
+                ::std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     // Run and match for potential error
     if let Err(error) = run(&matches) {
-        if exit_on_error {
+        // Without a bar, nobody would see the error
+        if exit_on_error || matches.is_present("headless") {
             eprintln!("{:?}", error);
             ::std::process::exit(1);
         }
@@ -97,7 +546,7 @@ fn main() {
             .with_text(&format!("{:?}", error));
 
         // Print errors
-        println!("[{}],", error_widget.get_data().render());
+        protocol::write_blocks(&[error_widget.get_data()]);
         eprintln!("\n\n{:?}", error);
 
         // Wait for USR2 signal to restart
@@ -111,17 +560,83 @@ fn main() {
 }
 
 fn run(matches: &ArgMatches) -> Result<()> {
+    logging::init(matches.value_of("log-level"), matches.value_of("log-file"))?;
+    if let Some(path) = matches.value_of("debug-dump") {
+        logging::start_dump(path)?;
+    }
+    if let Some(path) = matches.value_of("replay") {
+        // The values were checked by clap
+        let speed = matches.value_of("replay-speed").unwrap().parse().unwrap();
+        return protocol::recording::replay(Path::new(path), speed);
+    }
+    if let Some(bar) = matches.value_of("bar") {
+        if !matches.is_present("no-init") {
+            protocol::init(matches.is_present("never-pause"));
+        }
+        return bars::relay(bar);
+    }
+    if let Some(path) = matches.value_of("record") {
+        protocol::recording::start(Path::new(path))?;
+    }
+    let headless = matches.is_present("headless");
+    let output = match matches.value_of("output") {
+        _ if headless => protocol::Output::Headless,
+        Some("term") => protocol::Output::Term,
+        Some("plain") => protocol::Output::Plain,
+        Some("waybar") => protocol::Output::Waybar,
+        _ => protocol::Output::I3bar,
+    };
+    if output == protocol::Output::Waybar {
+        // The tooltips show the values of the blocks
+        registry::record_all_values();
+    }
+    protocol::set_output(output);
     if !matches.is_present("no-init") {
         // Now we can start to run the i3bar protocol
         protocol::init(matches.is_present("never-pause"));
     }
 
     // Read & parse the config file
-    let config_path = match matches.value_of("config") {
-        Some(config_path) => std::path::PathBuf::from(config_path),
-        None => util::xdg_config_home().join("i3status-rust/config.toml"),
+    let config_path = config_path(matches);
+    let mock = matches.value_of("mock");
+    let mut config: Config = match mock {
+        // A block can be tried without a configuration file
+        Some(_) if !config_path.exists() => Config::default(),
+        _ => includes::read(&config_path)?
+            .value
+            .try_into()
+            .configuration_error("failed to parse the configuration")?,
     };
-    let config: Config = deserialize_file(&config_path)?;
+    config
+        .apply_conditions()
+        .configuration_error("invalid condition of a block")?;
+    if let Some(block) = mock {
+        let fixtures: mock::Fixtures =
+            deserialize_file(Path::new(matches.value_of("fixtures").unwrap()))?;
+        mock::set_source(Box::new(fixtures));
+        // Fake system services, e.g. from python-dbusmock, run on the session bus
+        if let Ok(address) = std::env::var("DBUS_SESSION_BUS_ADDRESS") {
+            std::env::set_var("DBUS_SYSTEM_BUS_ADDRESS", address);
+        }
+        config.blocks.retain(|(name, _)| name == block);
+        if config.blocks.is_empty() {
+            config
+                .blocks
+                .push((block.to_string(), toml::Value::Table(Default::default())));
+        }
+    }
+    let only = matches.value_of("block");
+    if let Some(block) = only {
+        config.retain_block(block);
+        if config.blocks.is_empty() {
+            return Err(ConfigurationError(
+                format!("there is no block '{}' in the configuration", block),
+                config_path.display().to_string(),
+            ));
+        }
+    }
+    formatting::width::set_private_use_width(config.private_use_width);
+    http::configure(config.http.clone());
 
     // Update request channel
     let (tx_update_requests, rx_update_requests): (Sender<Task>, Receiver<Task>) =
@@ -141,54 +656,197 @@ fn run(matches: &ArgMatches) -> Result<()> {
         )?);
     }
 
+    services::watch(dependents(&blocks), tx_update_requests.clone())?;
+    resume::watch(resumed(&blocks), tx_update_requests.clone())?;
+
+    // The blocks of `--mock` are not the ones of the configuration file
+    let (tx_reload, rx_reload): (Sender<()>, Receiver<()>) = crossbeam_channel::unbounded();
+    let mut reloader = match mock {
+        Some(_) => None,
+        None => {
+            let reloader = Reloader::new(&config_path, only, blocks.len())?;
+            reloader.watch(tx_reload)?;
+            Some(reloader)
+        }
+    };
+
+    tracing::info!(
+        "started {} blocks of {}",
+        blocks.len(),
+        config_path.display()
+    );
     let mut scheduler = UpdateScheduler::new(&blocks);
 
     // We wait for click events in a separate thread, to avoid blocking to wait for stdin
     let (tx_clicks, rx_clicks): (Sender<I3BarEvent>, Receiver<I3BarEvent>) =
         crossbeam_channel::unbounded();
-    process_events(tx_clicks);
+    let debug_clicks = matches.is_present("debug-clicks");
+    click::set_debug(debug_clicks);
+    // Terminals don't send click events. The sender is kept, so the channel stays open.
+    if matches.value_of("output") == Some("i3bar") && !headless {
+        process_events(tx_clicks.clone(), debug_clicks);
+    }
+
+    // The other bars are served by the process that runs the whole configuration
+    if mock.is_none() && only.is_none() {
+        bars::listen(&config.bars, &tx_clicks, debug_clicks)?;
+        bars::set_blocks(
+            bar_blocks(&blocks, &config)
+                .map_err(|e| ConfigurationError("invalid [[bar]]".to_string(), e))?,
+        );
+        profiles::set_names(
+            config
+                .profiles
+                .iter()
+                .map(|profile| profile.name.clone())
+                .collect(),
+        );
+        if let Some(name) = &config.default_profile {
+            switch_profile(&mut blocks, &config, &mut scheduler, name)
+                .map_err(|e| ConfigurationError("invalid default_profile".to_string(), e))?;
+        }
+    }
 
     // We wait for signals in a separate thread
     let (tx_signals, rx_signals): (Sender<i32>, Receiver<i32>) = crossbeam_channel::unbounded();
     process_signals(tx_signals);
 
+    // Redraw requests, sent when the theme is switched between dark and light or dimmed
+    let (tx_redraw, rx_redraw): (Sender<()>, Receiver<()>) = crossbeam_channel::unbounded();
+    if let Some(switch) = config.theme.switch.clone() {
+        switch.start(theme_switch::Target::Light, tx_redraw.clone())?;
+    }
+    if let Some(night_dimming) = config.theme.night_dimming.clone() {
+        night_dimming
+            .switch
+            .start(theme_switch::Target::Night, tx_redraw.clone())?;
+    }
+
+    // Requests from `i3status-rs action` and the D-Bus interface
+    let (tx_ipc, rx_ipc): (Sender<ipc::Request>, Receiver<ipc::Request>) =
+        crossbeam_channel::unbounded();
+    if config.dbus_interface {
+        registry::record_all_values();
+        bus::serve(tx_ipc.clone())?;
+    }
+    // Like the other bars, the FIFO belongs to the process that runs the whole configuration
+    if let (Some(path), None, None) = (&config.command_fifo, &mock, &only) {
+        let path = shellexpand::full(path)
+            .configuration_error("failed to expand the path of command_fifo")?;
+        ipc::listen_fifo(Path::new(path.as_ref()), tx_ipc.clone())?;
+    }
+    if let Some(address) = matches
+        .value_of("metrics-addr")
+        .or(config.metrics.as_deref())
+    {
+        registry::record_all_values();
+        metrics::serve(address, config.metrics_token.clone(), tx_ipc.clone())?;
+    }
+    // E.g. `XDG_RUNTIME_DIR` is read-only, or another user owns `/tmp/i3status-rust`
+    if let Err(e) = ipc::listen(tx_ipc) {
+        tracing::warn!("running without the control socket: {}", e);
+    }
+
+    // The `loading_text` of the blocks, before their first updates
+    protocol::print_blocks(&blocks, &shared_config)?;
+
     // Time to next update channel.
     // Fires immediately for first updates
     let mut ttnu = crossbeam_channel::after(Duration::from_millis(0));
 
+    // Redraw timer for flashing widgets
+    let mut flash = crossbeam_channel::never();
+
+    // Timer for the deadlines of the blocks, e.g. to collapse them after they were expanded
+    let mut deadline = crossbeam_channel::never();
+
+    // Double click and hold detection
+    let mut clicks = ClickDispatcher::new(config.double_click_interval, config.hold_interval);
+    let mut double_click = crossbeam_channel::never();
+
+    // Updates of the blocks within a frame are drawn together
+    let mut frame_due: Option<Instant> = None;
+    let mut last_frame: Option<Instant> = None;
+    let mut frame = crossbeam_channel::never();
+
+    // Checks of the watchdogs of the blocks
+    let watchdog = crossbeam_channel::tick(WATCHDOG_CHECK);
+    let mut last_watchdog = Instant::now();
+
     loop {
         // We use the message passing concept of channel selection
         // to avoid busy wait
         select! {
             // Receive click events
             recv(rx_clicks) -> res => if let Ok(event) = res {
-                if let Some(id) = event.id {
-                        blocks.get_mut(id)
-                    .internal_error("click handler", "could not get required block")?
-                            .click(&event)?;
+                let (wants_double_click, wants_hold) = event
+                    .id
+                    .and_then(|id| blocks::position(&blocks, id))
+                    .map(|position| {
+                        let block = &blocks[position];
+                        (block.wants_double_click(event.button), block.wants_hold(event.button))
+                    })
+                    .unwrap_or((false, false));
+                let events = clicks.push(event, wants_double_click, wants_hold);
+                if events.is_empty() && click::debug() {
+                    eprintln!("click: held back, waiting for a double click or the release");
+                }
+                if !events.is_empty() {
+                    for event in events {
+                        dispatch_click(&mut blocks, &config, &event)?;
+                    }
+                    switch_requested_profile(&mut blocks, &config, &mut scheduler);
+                    protocol::print_blocks(&blocks, &shared_config)?;
+                }
+            },
+            // Deliver held back clicks that didn't become double clicks, and presses that became
+            // holds
+            recv(double_click) -> _ => {
+                let events = clicks.expire();
+                if !events.is_empty() {
+                    for event in events {
+                        dispatch_click(&mut blocks, &config, &event)?;
+                    }
+                    switch_requested_profile(&mut blocks, &config, &mut scheduler);
                     protocol::print_blocks(&blocks, &shared_config)?;
                 }
             },
             // Receive async update requests
             recv(rx_update_requests) -> request => if let Ok(req) = request {
-                if scheduler.schedule.iter().any(|x| x.id == req.id) {
-                // If block is already scheduled then process immediately and forget
-                blocks.get_mut(req.id)
-                    .internal_error("scheduler", "could not get required block")?
-                    .update()?;
+                // Bursts of requests of blocks with `min_update_interval` are coalesced
+                // Requests of blocks that were removed by a reload are dropped
+                let position = blocks::position(&blocks, req.id);
+                let throttled = position.is_some_and(|position| blocks[position].throttle());
+                if throttled || position.is_none() {
+                    // The deadline of the block requests the update again
+                } else if polling::sped_up(req.id) {
+                    scheduler.update_now(req.id);
+                    scheduler.do_scheduled_updates(&mut blocks)?;
+                } else if scheduler.schedule.iter().any(|x| x.id == req.id) {
+                    // If block is already scheduled then process immediately and forget
+                    if let Some(position) = position {
+                        blocks[position].update()?;
+                    }
                 } else {
-                // Otherwise add to scheduler tasks and trigger update
-                // In case this needs to schedule further updates e.g. marquee
-                scheduler.schedule.push(req);
-                scheduler.do_scheduled_updates(&mut blocks)?;
+                    // Otherwise add to scheduler tasks and trigger update
+                    // In case this needs to schedule further updates e.g. marquee
+                    scheduler.schedule.push(req);
+                    scheduler.do_scheduled_updates(&mut blocks)?;
+                }
+                if !throttled {
+                    request_frame(&mut frame_due);
                 }
-                protocol::print_blocks(&blocks, &shared_config)?;
             },
             // Receive update timer events
             recv(ttnu) -> _ => {
+                let starting = scheduler.starting();
                 scheduler.do_scheduled_updates(&mut blocks)?;
-                // redraw the blocks, state changed
-                protocol::print_blocks(&blocks, &shared_config)?;
+                // redraw the blocks, state changed. While the bar starts, the blocks are drawn as
+                // soon as they are done, so the ones that take longer don't hold back the others.
+                match starting {
+                    true => protocol::print_blocks(&blocks, &shared_config)?,
+                    false => request_frame(&mut frame_due),
+                }
             },
             // Receive signal events
             recv(rx_signals) -> res => if let Ok(sig) = res {
@@ -204,6 +862,9 @@ fn run(matches: &ArgMatches) -> Result<()> {
                         blocks.drain(..);
                         restart();
                     },
+                    signal_hook::consts::SIGHUP => {
+                        reload(&mut reloader, &mut config, &mut blocks, &shared_config, &tx_update_requests, &mut scheduler)?;
+                    },
                     _ => {
                         //Real time signal that updates only the blocks listening
                         //for that signal
@@ -213,14 +874,509 @@ fn run(matches: &ArgMatches) -> Result<()> {
                     },
                 };
                 protocol::print_blocks(&blocks, &shared_config)?;
+            },
+            // Receive requests from `i3status-rs action`
+            recv(rx_ipc) -> res => if let Ok(request) = res {
+                let mut answer = None;
+                let result = match &request.command {
+                    ipc::Command::Action { block, action } => {
+                        match bar_block(&mut blocks, &config, block) {
+                            Ok(block) => match block.action(action)? {
+                                true => Ok(()),
+                                false => Err(format!("the block has no action '{}'", action)),
+                            },
+                            Err(e) => Err(e),
+                        }
+                    }
+                    ipc::Command::Refresh { block } => match bar_block(&mut blocks, &config, block) {
+                        Ok(block) => {
+                            block.update()?;
+                            Ok(())
+                        }
+                        Err(e) => Err(e),
+                    },
+                    ipc::Command::Hide { block } | ipc::Command::Show { block } => {
+                        let hidden = matches!(request.command, ipc::Command::Hide { .. });
+                        bar_block(&mut blocks, &config, block).map(|block| block.set_hidden(hidden))
+                    }
+                    ipc::Command::Toggle { block } => bar_block(&mut blocks, &config, block)
+                        .map(|block| block.set_hidden(!block.is_hidden())),
+                    ipc::Command::SetText { block, text } => bar_block(&mut blocks, &config, block)
+                        .map(|block| block.set_text_override(text.clone())),
+                    ipc::Command::Compact { enabled } => {
+                        widgets::set_compact(enabled.unwrap_or(!widgets::is_compact()));
+                        Ok(())
+                    }
+                    ipc::Command::Theme { light } => match config.theme.has_light() {
+                        true => {
+                            themes::set_light(light.unwrap_or(!themes::is_light()));
+                            Ok(())
+                        }
+                        false => Err("the theme has no 'light' variant".to_string()),
+                    },
+                    ipc::Command::Profile { name } => match name.clone().or_else(|| profiles::next(false)) {
+                        Some(name) => switch_profile(&mut blocks, &config, &mut scheduler, &name),
+                        None => Err("there are no profiles".to_string()),
+                    },
+                    ipc::Command::Reload => {
+                        // Replied to already, since the bar might restart
+                        reload(&mut reloader, &mut config, &mut blocks, &shared_config, &tx_update_requests, &mut scheduler)?;
+                        Ok(())
+                    }
+                    ipc::Command::Remind { text } => {
+                        let reminder = blocks::reminders::Reminder::parse(text, chrono::Local::now());
+                        match (reminder, bar_block(&mut blocks, &config, "reminders")) {
+                            (Ok(reminder), Ok(block)) => match block.remind(&reminder)? {
+                                true => {
+                                    // The reminder can be due before the next update of the block
+                                    scheduler.update_now(block.id());
+                                    Ok(())
+                                }
+                                false => Err("the block has no reminders".to_string()),
+                            },
+                            (Err(e), _) | (_, Err(e)) => Err(e),
+                        }
+                    }
+                    ipc::Command::Presentation { enabled } => {
+                        registry::set_presenting(enabled.unwrap_or(!registry::is_presenting()));
+                        // The placeholders are masked when the blocks render their formats
+                        for block in blocks.iter_mut() {
+                            if registry::is_sensitive(block.id()) {
+                                block.update()?;
+                            }
+                        }
+                        Ok(())
+                    }
+                    ipc::Command::GetState => {
+                        // The values are only recorded once they are asked for
+                        if registry::record_all_values() {
+                            for block in blocks.iter_mut() {
+                                block.update()?;
+                            }
+                        }
+                        answer = Some(state::state(&blocks, &config));
+                        Ok(())
+                    }
+                    ipc::Command::Release { button } => {
+                        for event in clicks.release(*button) {
+                            dispatch_click(&mut blocks, &config, &event)?;
+                        }
+                        Ok(())
+                    }
+                };
+                match answer {
+                    Some(answer) => request.reply_with(answer),
+                    None => request.reply(result),
+                }
+                // E.g. the action `next` of a `profile` block
+                switch_requested_profile(&mut blocks, &config, &mut scheduler);
+                protocol::print_blocks(&blocks, &shared_config)?;
+            },
+            // Blocks that stopped updating
+            recv(watchdog) -> _ => {
+                // The bar was stopped in the meantime, e.g. by i3bar while it was hidden, so the
+                // blocks couldn't update
+                let stopped = last_watchdog.elapsed() > 2 * WATCHDOG_CHECK;
+                last_watchdog = Instant::now();
+                if !stopped && restart_stuck_blocks(&mut reloader, &config, &mut blocks, &shared_config, &tx_update_requests, &mut scheduler)? {
+                    request_frame(&mut frame_due);
+                }
+            },
+            // The configuration file was written
+            recv(rx_reload) -> _ => {
+                reload(&mut reloader, &mut config, &mut blocks, &shared_config, &tx_update_requests, &mut scheduler)?;
+                protocol::print_blocks(&blocks, &shared_config)?;
+            },
+            // Receive theme switches
+            recv(rx_redraw) -> _ => {
+                protocol::print_blocks(&blocks, &shared_config)?;
+            },
+            // Redraw flashing widgets
+            recv(flash) -> _ => {
+                protocol::print_blocks(&blocks, &shared_config)?;
+            },
+            // Blocks whose deadline has passed
+            recv(deadline) -> _ => {
+                let now = Instant::now();
+                for block in blocks.iter_mut() {
+                    if matches!(block.deadline(), Some(until) if until <= now) {
+                        block.timeout()?;
+                    }
+                }
+                request_frame(&mut frame_due);
+            },
+            // Draw the updates of the last frame
+            recv(frame) -> _ => {
+                frame_due = None;
+                last_frame = Some(Instant::now());
+                protocol::print_blocks(&blocks, &shared_config)?;
             }
         }
 
+        // Set the frame timer, if updates are waiting to be drawn, no sooner than
+        // `max_frame_rate` allows
+        frame = match (frame_due, last_frame, config.min_frame_interval()) {
+            (Some(due), Some(last), Some(interval)) => {
+                crossbeam_channel::at(due.max(last + interval))
+            }
+            (Some(due), _, _) => crossbeam_channel::at(due),
+            (None, _, _) => crossbeam_channel::never(),
+        };
+
         // Set the time-to-next-update timer
         if let Some(time) = scheduler.time_to_next_update() {
             ttnu = crossbeam_channel::after(time)
         }
+
+        // Set the double click timer, if a click or a press is held back
+        double_click = match clicks.timeout() {
+            Some(time) => crossbeam_channel::after(time),
+            None => crossbeam_channel::never(),
+        };
+
+        // Set the flash timer, if any widget is flashing
+        flash = match protocol::next_flash(&blocks) {
+            Some(time) => crossbeam_channel::after(time),
+            None => crossbeam_channel::never(),
+        };
+
+        // Set the deadline timer, if any block has a deadline
+        deadline = match blocks.iter().filter_map(|block| block.deadline()).min() {
+            Some(until) => crossbeam_channel::at(until),
+            None => crossbeam_channel::never(),
+        };
+    }
+}
+
+/// Draws the bar at the end of the current frame. Blocks that update at the same time, e.g.
+/// after a resume from suspend, are drawn once instead of once for each block.
+fn request_frame(frame_due: &mut Option<Instant>) {
+    frame_due.get_or_insert_with(|| Instant::now() + FRAME);
+}
+
+/// The ids and `depends_on` of the blocks that depend on services
+fn dependents(blocks: &[Box<dyn Block>]) -> Vec<(usize, Vec<services::Service>)> {
+    blocks
+        .iter()
+        .filter(|block| !block.depends_on().is_empty())
+        .map(|block| (block.id(), block.depends_on().to_vec()))
+        .collect()
+}
+
+/// The ids of the blocks that are updated after a resume from suspend
+fn resumed(blocks: &[Box<dyn Block>]) -> Vec<usize> {
+    blocks
+        .iter()
+        .filter(|block| block.updates_on_resume())
+        .map(|block| block.id())
+        .collect()
+}
+
+/// Reloads the configuration file and schedules the new blocks. Errors in the configuration are
+/// printed, the previous blocks keep running.
+fn reload(
+    reloader: &mut Option<Reloader>,
+    config: &mut Config,
+    blocks: &mut Vec<Box<dyn Block>>,
+    shared_config: &SharedConfig,
+    tx_update_requests: &Sender<Task>,
+    scheduler: &mut UpdateScheduler,
+) -> Result<()> {
+    let reloader = match reloader {
+        Some(reloader) => reloader,
+        None => return Ok(()),
+    };
+    match reloader.reload(config, blocks, shared_config, tx_update_requests) {
+        Ok(Reload::Blocks { created, restyled }) => {
+            tracing::info!(
+                "reloaded the configuration, {} blocks changed",
+                created.len()
+            );
+            if restyled {
+                // The widgets look up their icons again when they are updated
+                for block in blocks.iter() {
+                    if !created.contains(&block.id()) {
+                        scheduler.update_now(block.id());
+                    }
+                }
+            }
+            blocks_created(created, config, blocks, tx_update_requests, scheduler)?;
+        }
+        Ok(Reload::Restart) => {
+            tracing::info!("restarting to reload the configuration");
+            blocks.drain(..);
+            restart();
+        }
+        Err(error) => tracing::error!(
+            "reloading failed, the previous blocks keep running: {:?}",
+            error
+        ),
+    }
+    Ok(())
+}
+
+/// Starts the blocks `ids`, which were created instead of other blocks
+fn blocks_created(
+    ids: Vec<usize>,
+    config: &Config,
+    blocks: &mut Vec<Box<dyn Block>>,
+    tx_update_requests: &Sender<Task>,
+    scheduler: &mut UpdateScheduler,
+) -> Result<()> {
+    services::watch(dependents(blocks), tx_update_requests.clone())?;
+    resume::watch(resumed(blocks), tx_update_requests.clone())?;
+    // The blocks were created with new ids
+    match bar_blocks(blocks, config) {
+        Ok(ids) => bars::set_blocks(ids),
+        Err(e) => tracing::error!("invalid [[bar]]: {}", e),
+    }
+    // The new blocks are hidden like the ones they replace
+    profiles::set_names(
+        config
+            .profiles
+            .iter()
+            .map(|profile| profile.name.clone())
+            .collect(),
+    );
+    if let Some(name) = profiles::active() {
+        if let Err(e) = switch_profile(blocks, config, scheduler, &name) {
+            tracing::error!("{}", e);
+        }
+    }
+    if !ids.is_empty() {
+        let now = Instant::now();
+        for id in ids {
+            scheduler.schedule.push(Task {
+                id,
+                update_time: now,
+            });
+        }
+        // So the new blocks are not shown empty
+        scheduler.do_scheduled_updates(blocks)?;
+    }
+    Ok(())
+}
+
+/// Creates the blocks again that didn't update for longer than their watchdog allows. Returns
+/// whether a block was restarted.
+fn restart_stuck_blocks(
+    reloader: &mut Option<Reloader>,
+    config: &Config,
+    blocks: &mut Vec<Box<dyn Block>>,
+    shared_config: &SharedConfig,
+    tx_update_requests: &Sender<Task>,
+    scheduler: &mut UpdateScheduler,
+) -> Result<bool> {
+    let now = Instant::now();
+    let stuck: Vec<usize> = (0..blocks.len())
+        .filter(|&position| blocks[position].stuck(now))
+        .collect();
+    let mut ids = Vec::new();
+    for position in stuck {
+        let (name, _) = &config.blocks[position];
+        let old = blocks[position].id();
+        // The blocks of `--mock` are not the ones of the configuration file
+        let reloader = match reloader {
+            Some(reloader) => reloader,
+            None => {
+                tracing::warn!("the block {} ({}) is stuck", old, name);
+                continue;
+            }
+        };
+        match reloader.restart_block(position, config, blocks, shared_config, tx_update_requests) {
+            Ok(id) => {
+                tracing::warn!(
+                    "the block {} ({}) is stuck, restarted it as block {}",
+                    old,
+                    name,
+                    id
+                );
+                ids.push(id);
+            }
+            Err(e) => tracing::error!(
+                "the block {} ({}) is stuck, but restarting it failed: {:?}",
+                old,
+                name,
+                e
+            ),
+        }
+    }
+    if ids.is_empty() {
+        return Ok(false);
+    }
+    blocks_created(ids, config, blocks, tx_update_requests, scheduler)?;
+    Ok(true)
+}
+
+/// The configuration file given on the command line, or the default one
+fn config_path(matches: &ArgMatches) -> PathBuf {
+    match matches.value_of("config") {
+        Some(config_path) => PathBuf::from(config_path),
+        None => {
+            let dir = util::xdg_config_home().join("i3status-rust");
+            // Generated configurations are often JSON or YAML
+            ["config.toml", "config.json", "config.yaml", "config.yml"]
+                .iter()
+                .map(|name| dir.join(name))
+                .find(|path| path.exists())
+                .unwrap_or_else(|| dir.join("config.toml"))
+        }
+    }
+}
+
+/// The ids of the blocks of each `[[bar]]`
+fn bar_blocks(blocks: &[Box<dyn Block>], config: &Config) -> StdResult<Vec<Vec<usize>>, String> {
+    config
+        .bars
+        .iter()
+        .map(|bar| {
+            bar.blocks
+                .iter()
+                .map(|block| match find_block(blocks, config, block)? {
+                    Some(position) => Ok(blocks[position].id()),
+                    None => Err(format!("the block '{}' is inside of another block", block)),
+                })
+                .collect::<StdResult<Vec<usize>, String>>()
+                .map_err(|e| format!("bar '{}': {}", bar.name, e))
+        })
+        .collect()
+}
+
+/// The positions of the blocks of the profile `name`
+fn profile_blocks(
+    blocks: &[Box<dyn Block>],
+    config: &Config,
+    name: &str,
+) -> StdResult<Vec<usize>, String> {
+    let profile = config
+        .profiles
+        .iter()
+        .find(|profile| profile.name == name)
+        .ok_or_else(|| format!("there is no profile '{}'", name))?;
+    let mut positions = Vec::new();
+    for block in &profile.blocks {
+        // Exported blocks inside of other blocks are shown with them
+        if let Some(position) =
+            find_block(blocks, config, block).map_err(|e| format!("profile '{}': {}", name, e))?
+        {
+            positions.push(position);
+        }
+    }
+    Ok(positions)
+}
+
+/// Shows only the blocks of the profile `name` and hides the others, except for the `profile`
+/// blocks, which are always shown
+fn switch_profile(
+    blocks: &mut [Box<dyn Block>],
+    config: &Config,
+    scheduler: &mut UpdateScheduler,
+    name: &str,
+) -> StdResult<(), String> {
+    let shown = profile_blocks(blocks, config, name)?;
+    tracing::info!("switched to the profile '{}'", name);
+    profiles::set_active(Some(name.to_string()));
+    for (position, block) in blocks.iter_mut().enumerate() {
+        match config.blocks.get(position) {
+            Some((block_name, _)) if block_name == "profile" => scheduler.update_now(block.id()),
+            _ => block.set_hidden(!shown.contains(&position)),
+        }
+    }
+    Ok(())
+}
+
+/// Switches to the profile a block asked for, e.g. after a click on a `profile` block
+fn switch_requested_profile(
+    blocks: &mut [Box<dyn Block>],
+    config: &Config,
+    scheduler: &mut UpdateScheduler,
+) {
+    if let Some(name) = profiles::take_request() {
+        if let Err(e) = switch_profile(blocks, config, scheduler, &name) {
+            tracing::error!("{}", e);
+        }
+    }
+}
+
+/// The block with the `export` name, type or position `block`
+fn bar_block<'a>(
+    blocks: &'a mut [Box<dyn Block>],
+    config: &Config,
+    block: &str,
+) -> StdResult<&'a mut Box<dyn Block>, String> {
+    // Exported blocks inside of other blocks are not on the bar
+    let position = find_block(blocks, config, block)?
+        .ok_or_else(|| format!("the block '{}' is not on the bar", block))?;
+    Ok(&mut blocks[position])
+}
+
+/// The position of the block with the `export` name, type or position `block`, or `None` if the
+/// block is inside of another block
+fn find_block(
+    blocks: &[Box<dyn Block>],
+    config: &Config,
+    block: &str,
+) -> StdResult<Option<usize>, String> {
+    if let Some(id) = registry::id_by_name(block) {
+        return Ok(blocks::position(blocks, id));
+    }
+    if let Ok(position) = block.parse::<usize>() {
+        return match position < config.blocks.len() {
+            true => Ok(Some(position)),
+            false => Err(format!("there is no block at position {}", position)),
+        };
+    }
+    let mut positions = config
+        .blocks
+        .iter()
+        .enumerate()
+        .filter(|(_, (name, _))| name == block)
+        .map(|(position, _)| position);
+    match (positions.next(), positions.next()) {
+        (Some(position), None) => Ok(Some(position)),
+        (Some(_), Some(_)) => Err(format!(
+            "there are several '{}' blocks, give one of them an 'export' name",
+            block
+        )),
+        (None, _) => Err(format!("there is no block '{}'", block)),
+    }
+}
+
+fn dispatch_click(
+    blocks: &mut [Box<dyn Block>],
+    config: &Config,
+    event: &I3BarEvent,
+) -> Result<()> {
+    click::take_unhandled();
+    // Blocks are found by their positions from here on
+    let position = event.id.and_then(|id| blocks::position(blocks, id));
+    let unhandled = if let Some(position) = position {
+        blocks[position].click(event)?;
+        click::take_unhandled()
+    } else if event.id.is_some() {
+        // The block was removed by a reload
+        return Ok(());
+    } else {
+        if click::debug() {
+            eprintln!("click: {:?} not on any block", event.button);
+        }
+        // E.g. a click on a separator
+        true
+    };
+
+    if let (true, Some(cmd)) = (unhandled, &config.on_click_fallthrough) {
+        let block = position
+            .and_then(|position| config.blocks.get(position))
+            .map(|(name, _)| name.as_str());
+        let payload = click::fallthrough_payload(event, block);
+        if click::debug() {
+            eprintln!("click: would pipe {} to `{}`", payload, cmd);
+        } else {
+            subprocess::spawn_child_async_with_input("sh", &["-c", cmd], payload)
+                .internal_error("click handler", "could not spawn on_click_fallthrough")?;
+        }
     }
+    Ok(())
 }
 
 /// Restart `i3status-rs` in-place
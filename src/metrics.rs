@@ -0,0 +1,323 @@
+//! Export of the numeric placeholders of all blocks for Prometheus, enabled with the top-level
+//! option `metrics`, e.g. `metrics = "127.0.0.1:9123"`.
+//!
+//! Every placeholder is a gauge named after the type of the block and the placeholder, e.g.
+//! `i3status_rs_cpu_utilization{block_id="0"} 12.5`, served on `/metrics`. The values are the ones
+//! of the last update of the block. The updates and the failed updates of every block are counted
+//! as well, in `i3status_rs_block_updates_total` and `i3status_rs_block_update_errors_total`.
+//!
+//! The state of all blocks of `i3status-rs get-state` is served as JSON on `/state`, e.g. for a
+//! bar that runs with `--headless` on another machine, which the `remote` block shows. With
+//! `metrics_token`, both are only served to clients that send the token. The server only speaks
+//! plain HTTP, so the token can be read by anyone who can see the traffic, and is no protection on
+//! a network that isn't trusted.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use lazy_static::lazy_static;
+use serde_json::json;
+
+use crate::errors::*;
+use crate::ipc::{self, Command, Request};
+use crate::registry;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// How long a client may take to send its request or to receive the response
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The most bytes of a request that are read, requests are small as they have no body
+const MAX_REQUEST: u64 = 8 * 1024;
+
+#[derive(Debug, Default)]
+struct BlockMetrics {
+    /// The name of the type of the block, e.g. `DiskSpace`
+    block: String,
+    /// The numeric values of the last successful update
+    values: Vec<(String, f64)>,
+    updates: u64,
+    errors: u64,
+}
+
+type Blocks = BTreeMap<usize, BlockMetrics>;
+
+type Counter = fn(&BlockMetrics) -> u64;
+
+lazy_static! {
+    static ref BLOCKS: Mutex<Blocks> = Mutex::new(BTreeMap::new());
+}
+
+/// Takes the values that the block `id` of type `block` recorded in its last update
+pub fn block_updated(id: usize, block: &str) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let values = registry::values(id)
+        .into_iter()
+        .filter(|(name, value)| {
+            !value.is_text() && !registry::with_block(id, || registry::is_masked(name, value))
+        })
+        .filter_map(|(name, value)| Some((name, value.as_f64()?)))
+        .collect();
+    let mut blocks = BLOCKS.lock().unwrap();
+    let metrics = blocks.entry(id).or_default();
+    metrics.block = block.to_string();
+    metrics.values = values;
+    metrics.updates += 1;
+}
+
+/// Counts a failed update of the block `id` of type `block`, which keeps its last values
+pub fn block_failed(id: usize, block: &str) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut blocks = BLOCKS.lock().unwrap();
+    let metrics = blocks.entry(id).or_default();
+    metrics.block = block.to_string();
+    metrics.updates += 1;
+    metrics.errors += 1;
+}
+
+/// Removes the block `id`, which was removed by a reload of the configuration
+pub fn forget(id: usize) {
+    BLOCKS.lock().unwrap().remove(&id);
+}
+
+/// Starts a thread that serves the metrics on `address`, and the state of the blocks from the
+/// main loop through `requests`, to the clients that send `token` if there is one
+pub fn serve(address: &str, token: Option<String>, requests: Sender<Request>) -> Result<()> {
+    let address = bind_address(address);
+    let listener = TcpListener::bind(&address)
+        .configuration_error(&format!("failed to listen for metrics on '{}'", address))?;
+    ENABLED.store(true, Ordering::Relaxed);
+
+    thread::Builder::new()
+        .name("metrics".into())
+        .spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let token = token.clone();
+                let requests = requests.clone();
+                // Every client has its own thread, so a slow one doesn't hold up the others
+                let _ = thread::Builder::new()
+                    .name("metrics client".into())
+                    .spawn(move || handle_client(stream, token.as_deref(), &requests));
+            }
+        })
+        .map(|_| ())
+        .internal_error("metrics", "failed to start thread")
+}
+
+/// Addresses without a host, like `9123` or `:9123`, are on `127.0.0.1`, so the metrics are only
+/// served to other machines if their address is given
+fn bind_address(address: &str) -> String {
+    let port = address.strip_prefix(':').unwrap_or(address);
+    match port.parse::<u16>() {
+        Ok(port) => format!("127.0.0.1:{}", port),
+        Err(_) => address.to_string(),
+    }
+}
+
+/// Whether the headers of a request have `Authorization: Bearer <token>`
+fn authorized(headers: &[String], token: &str) -> bool {
+    headers.iter().any(|line| match line.split_once(':') {
+        Some((name, value)) => {
+            name.trim().eq_ignore_ascii_case("authorization")
+                && value.trim().strip_prefix("Bearer ").map(str::trim) == Some(token)
+        }
+        None => false,
+    })
+}
+
+fn handle_client(
+    mut stream: TcpStream,
+    token: Option<&str>,
+    requests: &Sender<Request>,
+) -> std::io::Result<()> {
+    const TEXT: &str = "text/plain; version=0.0.4";
+    const JSON: &str = "application/json";
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut reader = BufReader::new((&stream).take(MAX_REQUEST));
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        headers.push(line);
+    }
+    let too_large = reader.get_ref().limit() == 0;
+    let path = match token {
+        Some(token) if !authorized(&headers, token) => None,
+        _ => request.split_whitespace().nth(1),
+    };
+    let (status, content_type, body) = match path {
+        _ if too_large => (
+            "431 Request Header Fields Too Large",
+            TEXT,
+            "Request Header Fields Too Large\n".to_string(),
+        ),
+        // In JSON like the errors of `/state`, for the `remote` block
+        None => (
+            "401 Unauthorized",
+            JSON,
+            json!({ "error": "unauthorized" }).to_string(),
+        ),
+        Some("/metrics") => ("200 OK", TEXT, render(&BLOCKS.lock().unwrap())),
+        Some("/state") => match ipc::execute(requests, Command::GetState) {
+            Ok(state) => ("200 OK", JSON, state.unwrap_or_default().to_string()),
+            Err(e) => (
+                "503 Service Unavailable",
+                JSON,
+                json!({ "error": e }).to_string(),
+            ),
+        },
+        _ => ("404 Not Found", TEXT, "Not Found\n".to_string()),
+    };
+    write!(
+        stream,
+        "HTTP/1.0 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+/// The metrics in the text format of Prometheus, grouped by name
+fn render(blocks: &Blocks) -> String {
+    let mut metrics: BTreeMap<String, Vec<(usize, f64)>> = BTreeMap::new();
+    for (id, block) in blocks {
+        for (name, value) in &block.values {
+            metrics
+                .entry(metric_name(&block.block, name))
+                .or_default()
+                .push((*id, *value));
+        }
+    }
+    let mut out = String::new();
+    for (name, samples) in metrics {
+        let _ = writeln!(out, "# TYPE {} gauge", name);
+        for (id, value) in samples {
+            let _ = writeln!(
+                out,
+                "{}{{block_id=\"{}\"}} {}",
+                name,
+                id,
+                format_value(value)
+            );
+        }
+    }
+    let counters: [(&str, Counter); 2] = [
+        ("i3status_rs_block_updates_total", |block| block.updates),
+        ("i3status_rs_block_update_errors_total", |block| {
+            block.errors
+        }),
+    ];
+    for (name, count) in counters {
+        let _ = writeln!(out, "# TYPE {} counter", name);
+        for (id, block) in blocks {
+            let _ = writeln!(
+                out,
+                "{}{{block_id=\"{}\",block=\"{}\"}} {}",
+                name,
+                id,
+                snake_case(&block.block),
+                count(block)
+            );
+        }
+    }
+    out
+}
+
+/// Names can only contain ASCII letters, digits and underscores. `block` is the name of the type
+/// of the block, e.g. `DiskSpace` for `disk_space`.
+fn metric_name(block: &str, placeholder: &str) -> String {
+    let mut name = format!("i3status_rs_{}_", snake_case(block));
+    name.extend(
+        placeholder
+            .chars()
+            .map(|c| match c.is_ascii_alphanumeric() {
+                true => c,
+                false => '_',
+            }),
+    );
+    name
+}
+
+/// The name of a block in the configuration, e.g. `disk_space` for `DiskSpace`
+fn snake_case(block: &str) -> String {
+    let mut name = String::new();
+    for (i, c) in block.chars().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 {
+            name.push('_');
+        }
+        name.push(c.to_ascii_lowercase());
+    }
+    name
+}
+
+fn format_value(value: f64) -> String {
+    match value {
+        _ if value.is_nan() => "NaN".to_string(),
+        _ if value == f64::INFINITY => "+Inf".to_string(),
+        _ if value == f64::NEG_INFINITY => "-Inf".to_string(),
+        _ => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_format() {
+        let block = |block: &str, name: &str, value, updates, errors| BlockMetrics {
+            block: block.to_string(),
+            values: vec![(name.to_string(), value)],
+            updates,
+            errors,
+        };
+        let mut blocks = BTreeMap::new();
+        blocks.insert(0, block("Cpu", "utilization", 12.5, 4, 0));
+        blocks.insert(3, block("DiskSpace", "used_percents", f64::NAN, 2, 1));
+        blocks.insert(5, block("Cpu", "utilization", 3., 1, 0));
+        assert_eq!(
+            render(&blocks),
+            "# TYPE i3status_rs_cpu_utilization gauge\n\
+             i3status_rs_cpu_utilization{block_id=\"0\"} 12.5\n\
+             i3status_rs_cpu_utilization{block_id=\"5\"} 3\n\
+             # TYPE i3status_rs_disk_space_used_percents gauge\n\
+             i3status_rs_disk_space_used_percents{block_id=\"3\"} NaN\n\
+             # TYPE i3status_rs_block_updates_total counter\n\
+             i3status_rs_block_updates_total{block_id=\"0\",block=\"cpu\"} 4\n\
+             i3status_rs_block_updates_total{block_id=\"3\",block=\"disk_space\"} 2\n\
+             i3status_rs_block_updates_total{block_id=\"5\",block=\"cpu\"} 1\n\
+             # TYPE i3status_rs_block_update_errors_total counter\n\
+             i3status_rs_block_update_errors_total{block_id=\"0\",block=\"cpu\"} 0\n\
+             i3status_rs_block_update_errors_total{block_id=\"3\",block=\"disk_space\"} 1\n\
+             i3status_rs_block_update_errors_total{block_id=\"5\",block=\"cpu\"} 0\n"
+        );
+        assert_eq!(
+            metric_name("DiskSpace", "speed-down"),
+            "i3status_rs_disk_space_speed_down"
+        );
+    }
+    #[test]
+    fn bind_addresses() {
+        assert_eq!(bind_address("9123"), "127.0.0.1:9123");
+        assert_eq!(bind_address(":9123"), "127.0.0.1:9123");
+        assert_eq!(bind_address("0.0.0.0:9123"), "0.0.0.0:9123");
+        assert_eq!(bind_address("[::1]:9123"), "[::1]:9123");
+    }
+}
@@ -0,0 +1,767 @@
+//! `i3status-rs migrate`, which converts the configuration of i3status or polybar into blocks of
+//! i3status-rust and prints it as TOML.
+//!
+//! The common modules are converted into the equivalent blocks, with the `%name` placeholders of
+//! i3status replaced by the placeholders of the blocks. What can't be converted is left as
+//! comments in the TOML, next to the blocks, so the result is a start to edit rather than a
+//! finished configuration.
+
+use std::fmt::Write;
+
+use toml::Value;
+
+use crate::errors::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Source {
+    I3status,
+    Polybar,
+}
+
+/// A block of the converted configuration
+struct Block {
+    /// The module it was converted from, e.g. `disk "/"`
+    module: String,
+    /// `None` if there is no equivalent block
+    name: Option<&'static str>,
+    options: Vec<(&'static str, Value)>,
+    /// What was not converted
+    notes: Vec<String>,
+}
+
+impl Block {
+    fn new(module: String, name: &'static str) -> Self {
+        Block {
+            module,
+            name: Some(name),
+            options: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    fn unsupported(module: String, note: &str) -> Self {
+        Block {
+            module,
+            name: None,
+            options: Vec::new(),
+            notes: vec![note.to_string()],
+        }
+    }
+
+    fn set(&mut self, option: &'static str, value: impl Into<Value>) {
+        self.options.push((option, value.into()));
+    }
+}
+
+/// Converts the configuration file `contents` and returns the TOML
+pub fn migrate(source: Source, contents: &str) -> Result<String> {
+    let blocks = match source {
+        Source::I3status => i3status::convert(contents)?,
+        Source::Polybar => polybar::convert(contents)?,
+    };
+    let mut toml = format!(
+        "# Converted from the {} configuration by `i3status-rs migrate`. Check the comments, and see\n\
+         # https://github.com/greshake/i3status-rust/blob/master/doc/blocks.md for the options of the blocks.\n",
+        match source {
+            Source::I3status => "i3status",
+            Source::Polybar => "polybar",
+        }
+    );
+    for block in blocks {
+        toml.push('\n');
+        let _ = writeln!(toml, "# {}", block.module);
+        for note in &block.notes {
+            let _ = writeln!(toml, "# {}", note);
+        }
+        if let Some(name) = block.name {
+            let _ = writeln!(toml, "[[block]]\nblock = \"{}\"", name);
+            for (option, value) in &block.options {
+                let _ = writeln!(toml, "{} = {}", option, value);
+            }
+        }
+    }
+    Ok(toml)
+}
+
+/// Replaces the `%name` placeholders of i3status by the placeholders of i3status-rust, and drops
+/// the ones without an equivalent
+fn convert_format(format: &str, placeholders: &[(&str, &str)], notes: &mut Vec<String>) -> String {
+    let mut converted = String::with_capacity(format.len());
+    let mut rest = format;
+    while let Some(start) = rest.find('%') {
+        converted.push_str(&rest[..start]);
+        let name_length = rest[start + 1..]
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .unwrap_or(rest.len() - start - 1);
+        let name = &rest[start + 1..start + 1 + name_length];
+        match placeholders.iter().find(|(from, _)| *from == name) {
+            _ if name.is_empty() => converted.push('%'),
+            Some((_, to)) => {
+                converted.push('{');
+                converted.push_str(to);
+                converted.push('}');
+            }
+            None => notes.push(format!("%{} has no equivalent and was removed", name)),
+        }
+        rest = &rest[start + 1 + name_length..];
+    }
+    converted.push_str(rest);
+    converted.trim().to_string()
+}
+
+mod i3status {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    enum Token {
+        /// A word or a quoted string
+        Text(String),
+        Open,
+        Close,
+        Assign,
+        Append,
+    }
+
+    fn tokenize(contents: &str) -> Result<Vec<(usize, Token)>> {
+        let mut tokens = Vec::new();
+        for (number, line) in contents.lines().enumerate() {
+            let mut chars = line.chars().peekable();
+            while let Some(c) = chars.next() {
+                let token = match c {
+                    '#' => break,
+                    c if c.is_whitespace() => continue,
+                    '{' => Token::Open,
+                    '}' => Token::Close,
+                    '=' => Token::Assign,
+                    '+' if chars.peek() == Some(&'=') => {
+                        chars.next();
+                        Token::Append
+                    }
+                    '"' => {
+                        let mut text = String::new();
+                        loop {
+                            match chars.next() {
+                                Some('"') => break,
+                                Some('\\') => text.extend(chars.next()),
+                                Some(c) => text.push(c),
+                                None => {
+                                    return Err(ConfigurationError(
+                                        format!("line {}: unterminated string", number + 1),
+                                        line.to_string(),
+                                    ))
+                                }
+                            }
+                        }
+                        Token::Text(text)
+                    }
+                    c => {
+                        let mut word = c.to_string();
+                        while let Some(&c) = chars.peek() {
+                            if c.is_whitespace() || "{}=\"#".contains(c) {
+                                break;
+                            }
+                            word.push(c);
+                            chars.next();
+                        }
+                        Token::Text(word)
+                    }
+                };
+                tokens.push((number + 1, token));
+            }
+        }
+        Ok(tokens)
+    }
+
+    #[derive(Clone)]
+    struct Module {
+        name: String,
+        instance: Option<String>,
+        options: Vec<(String, String)>,
+    }
+
+    impl Module {
+        fn get(&self, option: &str) -> Option<&str> {
+            self.options
+                .iter()
+                .rev()
+                .find(|(name, _)| name == option)
+                .map(|(_, value)| value.as_str())
+        }
+
+        fn title(&self) -> String {
+            match &self.instance {
+                Some(instance) if instance.contains(' ') || instance.contains('/') => {
+                    format!("{} \"{}\"", self.name, instance)
+                }
+                Some(instance) => format!("{} {}", self.name, instance),
+                None => self.name.clone(),
+            }
+        }
+    }
+
+    /// The `order` and the modules of the configuration
+    fn parse(contents: &str) -> Result<(Vec<String>, Vec<Module>)> {
+        let mut tokens = tokenize(contents)?.into_iter();
+        let mut order = Vec::new();
+        let mut modules = Vec::new();
+        let error = |line: usize, message: &str| {
+            ConfigurationError(format!("line {}: {}", line, message), String::new())
+        };
+        while let Some((line, token)) = tokens.next() {
+            let name = match token {
+                Token::Text(name) => name,
+                _ => return Err(error(line, "expected the name of a module")),
+            };
+            match tokens.next() {
+                Some((_, Token::Append)) if name == "order" => match tokens.next() {
+                    Some((_, Token::Text(module))) => order.push(module),
+                    _ => return Err(error(line, "expected a module after 'order +='")),
+                },
+                Some((_, Token::Open)) => modules.push(parse_module(name, None, &mut tokens)?),
+                Some((_, Token::Text(instance))) => match tokens.next() {
+                    Some((_, Token::Open)) => {
+                        modules.push(parse_module(name, Some(instance), &mut tokens)?)
+                    }
+                    _ => return Err(error(line, "expected '{'")),
+                },
+                _ => return Err(error(line, "expected '{' or 'order +='")),
+            }
+        }
+        Ok((order, modules))
+    }
+
+    fn parse_module(
+        name: String,
+        instance: Option<String>,
+        tokens: &mut impl Iterator<Item = (usize, Token)>,
+    ) -> Result<Module> {
+        let mut options = Vec::new();
+        loop {
+            match tokens.next() {
+                Some((_, Token::Close)) => break,
+                Some((line, Token::Text(option))) => match (tokens.next(), tokens.next()) {
+                    (Some((_, Token::Assign)), Some((_, Token::Text(value)))) => {
+                        options.push((option, value))
+                    }
+                    _ => {
+                        return Err(ConfigurationError(
+                            format!("line {}: expected '{} = <value>'", line, option),
+                            String::new(),
+                        ))
+                    }
+                },
+                _ => {
+                    return Err(ConfigurationError(
+                        format!("module '{}' has no end", name),
+                        "expected '}'".to_string(),
+                    ))
+                }
+            }
+        }
+        Ok(Module {
+            name,
+            instance,
+            options,
+        })
+    }
+
+    pub(super) fn convert(contents: &str) -> Result<Vec<Block>> {
+        let (order, modules) = parse(contents)?;
+        let interval = modules
+            .iter()
+            .find(|module| module.name == "general")
+            .and_then(|general| general.get("interval"))
+            .and_then(|interval| interval.parse::<i64>().ok());
+        // Modules without a section of their own, e.g. `order += "ipv6"`, have the defaults
+        let find = |entry: &str| {
+            let (name, instance) = match entry.split_once(char::is_whitespace) {
+                Some((name, instance)) => (name, Some(instance.trim().to_string())),
+                None => (entry, None),
+            };
+            modules
+                .iter()
+                .find(|module| module.name == name && module.instance == instance)
+                .cloned()
+                .unwrap_or_else(|| Module {
+                    name: name.to_string(),
+                    instance,
+                    options: Vec::new(),
+                })
+        };
+        // Without an `order`, the modules are shown in the order of the file
+        let ordered: Vec<Module> = match order.is_empty() {
+            true => modules
+                .iter()
+                .filter(|module| module.name != "general")
+                .cloned()
+                .collect(),
+            false => order.iter().map(|entry| find(entry)).collect(),
+        };
+        Ok(ordered
+            .into_iter()
+            .map(|module| {
+                let mut block = convert_module(&module);
+                if let (Some(interval), Some(name)) = (interval, block.name) {
+                    if name != "sound"
+                        && !block
+                            .options
+                            .iter()
+                            .any(|(option, _)| *option == "interval")
+                    {
+                        block.set("interval", interval);
+                    }
+                }
+                block
+            })
+            .collect())
+    }
+
+    fn convert_module(module: &Module) -> Block {
+        let instance = module.instance.as_deref();
+        let mut block = match module.name.as_str() {
+            "wireless" | "ethernet" => Block::new(module.title(), "net"),
+            "battery" => Block::new(module.title(), "battery"),
+            "disk" => Block::new(module.title(), "disk_space"),
+            "load" => Block::new(module.title(), "load"),
+            "memory" => Block::new(module.title(), "memory"),
+            "cpu_usage" => Block::new(module.title(), "cpu"),
+            "cpu_temperature" => Block::new(module.title(), "temperature"),
+            "time" | "tztime" => Block::new(module.title(), "time"),
+            "volume" => Block::new(module.title(), "sound"),
+            "read_file" => Block::new(module.title(), "custom"),
+            _ => {
+                return Block::unsupported(
+                    module.title(),
+                    "There is no equivalent block, a `custom` block can show the output of a command instead.",
+                )
+            }
+        };
+        let mut notes = Vec::new();
+        let mut format = |option: &str, placeholders: &[(&str, &str)]| {
+            module
+                .get(option)
+                .map(|format| convert_format(format, placeholders, &mut notes))
+        };
+        match module.name.as_str() {
+            "wireless" | "ethernet" => {
+                if let Some(device) = instance.filter(|device| !device.starts_with('_')) {
+                    block.set("device", device);
+                }
+                let format = format(
+                    "format_up",
+                    &[
+                        ("essid", "ssid"),
+                        ("quality", "signal_strength"),
+                        ("frequency", "frequency"),
+                        ("bitrate", "bitrate"),
+                        ("speed", "bitrate"),
+                        ("ip", "ip"),
+                    ],
+                );
+                if let Some(format) = format {
+                    block.set("format", format);
+                }
+                if module.get("format_down").is_some() {
+                    block.notes.push(
+                        "format_down: the block shows that the interface is down, or hides it with hide_inactive = true".to_string(),
+                    );
+                }
+            }
+            "battery" => {
+                if let Some(number) = instance.filter(|instance| *instance != "all") {
+                    block.set("device", format!("BAT{}", number));
+                }
+                let format = format(
+                    "format",
+                    &[
+                        ("percentage", "percentage"),
+                        ("remaining", "time"),
+                        ("consumption", "power"),
+                    ],
+                );
+                if let Some(format) = format {
+                    block.set("format", format);
+                }
+            }
+            "disk" => {
+                block.set("path", instance.unwrap_or("/"));
+                let format = module.get("format").map(|format| {
+                    // `{percentage}` is either the used or the available space
+                    if format.contains("%percentage_used") {
+                        block.set("info_type", "used");
+                    }
+                    convert_format(
+                        format,
+                        &[
+                            ("avail", "available"),
+                            ("free", "free"),
+                            ("used", "used"),
+                            ("total", "total"),
+                            ("percentage_used", "percentage"),
+                            ("percentage_avail", "percentage"),
+                            ("percentage_free", "percentage"),
+                        ],
+                        &mut notes,
+                    )
+                });
+                if let Some(format) = format {
+                    block.set("format", format);
+                }
+            }
+            "load" => {
+                let format = format(
+                    "format",
+                    &[("1min", "1m"), ("5min", "5m"), ("15min", "15m")],
+                );
+                if let Some(format) = format {
+                    block.set("format", format);
+                }
+            }
+            "memory" => {
+                let format = format(
+                    "format",
+                    &[
+                        ("total", "mem_total"),
+                        ("used", "mem_used"),
+                        ("free", "mem_free"),
+                        ("available", "mem_avail"),
+                        ("percentage_used", "mem_used_percents"),
+                        ("percentage_free", "mem_free_percents"),
+                        ("percentage_available", "mem_avail_percents"),
+                    ],
+                );
+                if let Some(format) = format {
+                    block.set("format_mem", format);
+                }
+                block.set("clickable", false);
+            }
+            "cpu_usage" => {
+                if let Some(format) = format("format", &[("usage", "utilization")]) {
+                    block.set("format", format);
+                }
+            }
+            "cpu_temperature" => {
+                if let Some(format) = format("format", &[("degrees", "max")]) {
+                    block.set("format", format);
+                    block.set("collapsed", false);
+                }
+            }
+            "time" | "tztime" => {
+                // Both use the format of strftime
+                if let Some(format) = module.get("format") {
+                    block.set("format", format);
+                }
+                if let Some(timezone) = module.get("timezone") {
+                    block.set("timezone", timezone);
+                }
+            }
+            "volume" => {
+                if let Some(format) = format("format", &[("volume", "volume")]) {
+                    block.set("format", format);
+                }
+            }
+            "read_file" => {
+                let path = module.get("path").unwrap_or_default();
+                block.set("command", format!("cat '{}'", path.replace('\'', r"'\''")));
+            }
+            _ => {}
+        }
+        block.notes.extend(notes);
+        block
+    }
+}
+
+mod polybar {
+    use super::*;
+
+    /// The sections of the INI file, with their keys and values
+    type Sections = Vec<(String, Vec<(String, String)>)>;
+
+    fn parse(contents: &str) -> Sections {
+        let mut sections: Sections = Vec::new();
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') || line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|x| x.strip_suffix(']')) {
+                sections.push((name.trim().to_string(), Vec::new()));
+                continue;
+            }
+            if let (Some((_, keys)), Some((key, value))) =
+                (sections.last_mut(), line.split_once('='))
+            {
+                let value = value.trim();
+                let value = match value.strip_prefix('"').and_then(|x| x.strip_suffix('"')) {
+                    Some(quoted) => quoted,
+                    None => value,
+                };
+                keys.push((key.trim().to_string(), value.to_string()));
+            }
+        }
+        sections
+    }
+
+    fn get<'a>(keys: &'a [(String, String)], key: &str) -> Option<&'a str> {
+        keys.iter()
+            .rev()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub(super) fn convert(contents: &str) -> Result<Vec<Block>> {
+        let sections = parse(contents);
+        let modules: Vec<&str> = match sections.iter().find(|(name, _)| name.starts_with("bar/")) {
+            Some((_, bar)) => ["modules-left", "modules-center", "modules-right"]
+                .iter()
+                .filter_map(|key| get(bar, key))
+                .flat_map(str::split_whitespace)
+                .collect(),
+            None => sections
+                .iter()
+                .filter_map(|(name, _)| name.strip_prefix("module/"))
+                .collect(),
+        };
+        if modules.is_empty() {
+            return Err(ConfigurationError(
+                "no modules found".to_string(),
+                "expected a [bar/...] with modules-left, modules-center or modules-right"
+                    .to_string(),
+            ));
+        }
+        let mut blocks = Vec::new();
+        for module in modules {
+            let title = format!("[module/{}]", module);
+            let keys = match sections
+                .iter()
+                .find(|(name, _)| name.strip_prefix("module/") == Some(module))
+            {
+                Some((_, keys)) => keys.as_slice(),
+                None => {
+                    blocks.push(Block::unsupported(title, "The module is not in the file."));
+                    continue;
+                }
+            };
+            // One block for every mount point
+            if get(keys, "type") == Some("internal/fs") {
+                for i in 0.. {
+                    match get(keys, &format!("mount-{}", i)) {
+                        Some(path) => {
+                            let mut block =
+                                Block::new(format!("{} mount-{}", title, i), "disk_space");
+                            block.set("path", path);
+                            set_interval(&mut block, keys);
+                            blocks.push(block);
+                        }
+                        None => break,
+                    }
+                }
+                continue;
+            }
+            blocks.push(convert_module(title, keys));
+        }
+        Ok(blocks)
+    }
+
+    fn set_interval(block: &mut Block, keys: &[(String, String)]) {
+        if let Some(interval) = get(keys, "interval").and_then(|x| x.parse::<f64>().ok()) {
+            match interval.fract() == 0.0 {
+                true => block.set("interval", interval as i64),
+                false => block.set("interval", interval),
+            }
+        }
+    }
+
+    fn convert_module(title: String, keys: &[(String, String)]) -> Block {
+        let mut block = match get(keys, "type").unwrap_or_default() {
+            "internal/cpu" => Block::new(title, "cpu"),
+            "internal/memory" => Block::new(title, "memory"),
+            "internal/date" => Block::new(title, "time"),
+            "internal/battery" => Block::new(title, "battery"),
+            "internal/network" => Block::new(title, "net"),
+            "internal/pulseaudio" | "internal/alsa" => Block::new(title, "sound"),
+            "internal/backlight" | "internal/xbacklight" => Block::new(title, "backlight"),
+            "internal/temperature" => Block::new(title, "temperature"),
+            "custom/script" => Block::new(title, "custom"),
+            module_type => {
+                return Block::unsupported(
+                    title,
+                    &format!("There is no equivalent block for {}.", module_type),
+                )
+            }
+        };
+        match block.name {
+            Some("time") => {
+                // `label` combines `date` and `time`, which use the format of strftime
+                let label = get(keys, "label").unwrap_or("%date%");
+                let format = label
+                    .replace("%date%", get(keys, "date").unwrap_or_default())
+                    .replace("%time%", get(keys, "time").unwrap_or_default());
+                block.set("format", format.trim());
+            }
+            Some("battery") => {
+                if let Some(device) = get(keys, "battery") {
+                    block.set("device", device);
+                }
+                if let Some(full_at) = get(keys, "full-at").and_then(|x| x.parse::<i64>().ok()) {
+                    block.set("full_threshold", full_at);
+                }
+            }
+            Some("net") => {
+                if let Some(device) = get(keys, "interface") {
+                    block.set("device", device);
+                }
+            }
+            Some("sound") if get(keys, "type") == Some("internal/alsa") => {
+                block.set("driver", "alsa");
+            }
+            Some("backlight") => {
+                if let Some(device) = get(keys, "card") {
+                    block.set("device", device);
+                }
+            }
+            Some("custom") => {
+                if let Some(command) = get(keys, "exec") {
+                    block.set("command", command);
+                }
+                if get(keys, "tail") == Some("true") {
+                    block.notes.push(
+                        "tail = true: the block runs the command at every interval instead of reading its output continuously".to_string(),
+                    );
+                }
+                if let Some(click) = get(keys, "click-left") {
+                    block.set("on_click", click);
+                }
+            }
+            _ => {}
+        }
+        // The dates and the scripts are the only labels that are converted
+        if block.name != Some("time") && keys.iter().any(|(key, _)| key.starts_with("label")) {
+            block
+                .notes
+                .push("The labels were not converted, see the format of the block.".to_string());
+        }
+        set_interval(&mut block, keys);
+        block
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats() {
+        let mut notes = Vec::new();
+        assert_eq!(
+            convert_format(
+                "W: (%quality at %essid) %ip %bitrate",
+                &[
+                    ("quality", "signal_strength"),
+                    ("essid", "ssid"),
+                    ("ip", "ip")
+                ],
+                &mut notes,
+            ),
+            "W: ({signal_strength} at {ssid}) {ip}"
+        );
+        assert_eq!(notes, ["%bitrate has no equivalent and was removed"]);
+    }
+
+    #[test]
+    fn i3status() {
+        let config = r#"
+# i3status configuration file.
+general {
+        colors = true
+        interval = 5
+}
+
+order += "wireless _first_"
+order += "disk /"
+order += "ipv6"
+order += "tztime local"
+
+wireless _first_ {
+        format_up = "W: (%quality at %essid) %ip"
+        format_down = "W: down"
+}
+
+disk "/" {
+        format = "%percentage_used used"
+}
+
+tztime local {
+        format = "%Y-%m-%d %H:%M:%S"
+        timezone = "Europe/Berlin"
+}
+"#;
+        let toml = migrate(Source::I3status, config).unwrap();
+        assert!(toml.contains("# ipv6\n# There is no equivalent block"));
+        let config: crate::config::Config = toml::from_str(&toml).unwrap();
+        let blocks: Vec<&str> = config
+            .blocks
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(blocks, ["net", "disk_space", "time"]);
+        let net = &config.blocks[0].1;
+        assert_eq!(
+            net["format"].as_str(),
+            Some("W: ({signal_strength} at {ssid}) {ip}")
+        );
+        assert!(net.get("device").is_none());
+        assert_eq!(net["interval"].as_integer(), Some(5));
+        let disk = &config.blocks[1].1;
+        assert_eq!(disk["path"].as_str(), Some("/"));
+        assert_eq!(disk["info_type"].as_str(), Some("used"));
+        assert_eq!(disk["format"].as_str(), Some("{percentage} used"));
+        assert_eq!(
+            config.blocks[2].1["timezone"].as_str(),
+            Some("Europe/Berlin")
+        );
+
+        assert!(migrate(Source::I3status, "disk \"/\" {\n format = \"a\"\n").is_err());
+    }
+
+    #[test]
+    fn polybar() {
+        let config = r#"
+[bar/example]
+modules-left = i3
+modules-right = filesystem cpu date
+
+; a comment
+[module/filesystem]
+type = internal/fs
+mount-0 = /
+mount-1 = /home
+
+[module/cpu]
+type = internal/cpu
+interval = 2
+label = %percentage:2%%
+
+[module/date]
+type = internal/date
+date = "%Y-%m-%d"
+time = %H:%M
+label = %date% %time%
+"#;
+        let toml = migrate(Source::Polybar, config).unwrap();
+        let config: crate::config::Config = toml::from_str(&toml).unwrap();
+        let blocks: Vec<&str> = config
+            .blocks
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        assert_eq!(blocks, ["disk_space", "disk_space", "cpu", "time"]);
+        assert_eq!(config.blocks[1].1["path"].as_str(), Some("/home"));
+        assert_eq!(config.blocks[2].1["interval"].as_integer(), Some(2));
+        assert_eq!(
+            config.blocks[3].1["format"].as_str(),
+            Some("%Y-%m-%d %H:%M")
+        );
+        assert!(toml.contains("# [module/i3]\n# The module is not in the file."));
+    }
+}
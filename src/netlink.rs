@@ -0,0 +1,206 @@
+//! Events of the kernel that update blocks right away instead of at their next interval.
+//!
+//! Changes of network interfaces, addresses and routes come from rtnetlink, power supplies and
+//! block devices that are plugged or unplugged from the uevents of the kernel, and mounts from
+//! `/proc/self/mountinfo`. Blocks still update at their interval for the values that change
+//! without an event, like byte counters or the charge of a battery. Without access to the events,
+//! e.g. in some containers, the blocks only update at their interval.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use lazy_static::lazy_static;
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags};
+use nix::sys::socket::{
+    bind, recv, socket, AddressFamily, MsgFlags, SockAddr, SockFlag, SockProtocol, SockType,
+};
+
+use crate::scheduler::Task;
+
+/// Events that arrive this soon after another one are handled together
+const BURST: Duration = Duration::from_millis(100);
+
+const RTMGRP_LINK: u32 = 0x1;
+const RTMGRP_IPV4_IFADDR: u32 = 0x10;
+const RTMGRP_IPV4_ROUTE: u32 = 0x40;
+const RTMGRP_IPV6_IFADDR: u32 = 0x100;
+const RTMGRP_IPV6_ROUTE: u32 = 0x400;
+/// The uevents sent by the kernel, rather than the ones sent again by udev
+const UEVENT_KERNEL: u32 = 0x1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Source {
+    /// Interfaces, addresses and routes
+    Network,
+    PowerSupply,
+    /// Block devices and mounts
+    Storage,
+}
+
+const SOURCES: [Source; 3] = [Source::Network, Source::PowerSupply, Source::Storage];
+
+static GENERATIONS: [AtomicU64; 3] = [AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0)];
+static LISTENING: [AtomicBool; 3] = [
+    AtomicBool::new(false),
+    AtomicBool::new(false),
+    AtomicBool::new(false),
+];
+
+lazy_static! {
+    static ref SUBSCRIBERS: Mutex<HashMap<Source, HashMap<usize, Sender<Task>>>> =
+        Mutex::new(HashMap::new());
+}
+
+fn index(source: Source) -> usize {
+    SOURCES.iter().position(|&s| s == source).unwrap()
+}
+
+/// Updates the block `id` on the events of `source`. Returns whether the events arrive, otherwise
+/// the block has to find changes at its interval.
+pub fn subscribe(source: Source, id: usize, tx_update_request: Sender<Task>) -> bool {
+    SUBSCRIBERS
+        .lock()
+        .unwrap()
+        .entry(source)
+        .or_default()
+        .insert(id, tx_update_request);
+    start();
+    LISTENING[index(source)].load(Ordering::Relaxed)
+}
+
+/// A number that changes with every event of `source`, so a block can tell whether something
+/// changed since its last update
+pub fn generation(source: Source) -> u64 {
+    GENERATIONS[index(source)].load(Ordering::Relaxed)
+}
+
+fn notify(sources: &[Source]) {
+    let subscribers = SUBSCRIBERS.lock().unwrap();
+    for &source in sources {
+        GENERATIONS[index(source)].fetch_add(1, Ordering::Relaxed);
+        for (&id, tx_update_request) in subscribers.get(&source).into_iter().flatten() {
+            let _ = tx_update_request.send(Task {
+                id,
+                update_time: Instant::now(),
+            });
+        }
+    }
+}
+
+/// The source of a uevent, from its `SUBSYSTEM`
+fn uevent_source(message: &[u8]) -> Option<Source> {
+    message
+        .split(|&byte| byte == 0)
+        .find_map(|field| field.strip_prefix(b"SUBSYSTEM="))
+        .and_then(|subsystem| match subsystem {
+            b"power_supply" => Some(Source::PowerSupply),
+            b"block" => Some(Source::Storage),
+            _ => None,
+        })
+}
+
+fn open(protocol: SockProtocol, groups: u32) -> nix::Result<RawFd> {
+    let fd = socket(
+        AddressFamily::Netlink,
+        SockType::Raw,
+        SockFlag::SOCK_CLOEXEC,
+        protocol,
+    )?;
+    bind(fd, &SockAddr::new_netlink(0, groups))?;
+    Ok(fd)
+}
+
+/// Passes the sources of the messages on `fd` to `notify`, a burst of messages at once. When the
+/// kernel dropped messages, all of `all` may have changed.
+fn listen(fd: RawFd, classify: fn(&[u8]) -> Option<Source>, all: &[Source]) {
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let mut sources = Vec::new();
+        let mut flags = MsgFlags::empty();
+        loop {
+            match recv(fd, &mut buffer, flags) {
+                Ok(length) => sources.extend(classify(&buffer[..length])),
+                Err(Errno::ENOBUFS) => sources.extend_from_slice(all),
+                Err(Errno::EINTR) => continue,
+                Err(Errno::EAGAIN) => break,
+                Err(_) => return,
+            }
+            if flags.is_empty() {
+                thread::sleep(BURST);
+                flags = MsgFlags::MSG_DONTWAIT;
+            }
+        }
+        sources.sort_by_key(|&source| index(source));
+        sources.dedup();
+        notify(&sources);
+    }
+}
+
+/// Waits for changes of the mount table, which the kernel reports as an exceptional condition
+fn watch_mounts(mountinfo: File) {
+    loop {
+        let mut fds = [PollFd::new(mountinfo.as_raw_fd(), PollFlags::POLLPRI)];
+        match poll(&mut fds, -1) {
+            Ok(_) => {
+                thread::sleep(BURST);
+                notify(&[Source::Storage]);
+            }
+            Err(Errno::EINTR) => continue,
+            Err(_) => return,
+        }
+    }
+}
+
+fn spawn<F: FnOnce() + Send + 'static>(f: F) {
+    let _ = thread::Builder::new().name("netlink".into()).spawn(f);
+}
+
+/// Starts the threads that listen for the events, once
+fn start() {
+    static STARTED: AtomicBool = AtomicBool::new(false);
+    if STARTED.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    let groups = RTMGRP_LINK
+        | RTMGRP_IPV4_IFADDR
+        | RTMGRP_IPV4_ROUTE
+        | RTMGRP_IPV6_IFADDR
+        | RTMGRP_IPV6_ROUTE;
+    if let Ok(fd) = open(SockProtocol::NetlinkRoute, groups) {
+        LISTENING[index(Source::Network)].store(true, Ordering::Relaxed);
+        spawn(move || listen(fd, |_| Some(Source::Network), &[Source::Network]));
+    }
+    if let Ok(fd) = open(SockProtocol::NetlinkKObjectUEvent, UEVENT_KERNEL) {
+        LISTENING[index(Source::PowerSupply)].store(true, Ordering::Relaxed);
+        spawn(move || listen(fd, uevent_source, &[Source::PowerSupply, Source::Storage]));
+    }
+    if let Ok(mountinfo) = File::open("/proc/self/mountinfo") {
+        // Devices are mounted a moment after the uevent, so the mounts count for storage
+        LISTENING[index(Source::Storage)].store(true, Ordering::Relaxed);
+        spawn(move || watch_mounts(mountinfo));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uevents() {
+        let message = b"change@/devices/LNXSYSTM:00/ACPI0003:00/power_supply/AC\0ACTION=change\0\
+            DEVPATH=/devices/LNXSYSTM:00/ACPI0003:00/power_supply/AC\0SUBSYSTEM=power_supply\0\
+            POWER_SUPPLY_NAME=AC\0POWER_SUPPLY_ONLINE=0\0SEQNUM=4242\0";
+        assert_eq!(uevent_source(message), Some(Source::PowerSupply));
+        let message = b"add@/devices/virtual/block/loop0\0ACTION=add\0SUBSYSTEM=block\0";
+        assert_eq!(uevent_source(message), Some(Source::Storage));
+        let message = b"add@/module/usb_storage\0ACTION=add\0SUBSYSTEM=module\0";
+        assert_eq!(uevent_source(message), None);
+    }
+}
@@ -0,0 +1,280 @@
+//! The values of NVIDIA GPUs from NVML, the library of the driver that `nvidia-smi` is built on,
+//! for the `nvidia_gpu` block.
+//!
+//! The library is loaded at runtime with `dlopen`, so the bar builds and runs without it. Reading
+//! the values is a few calls into the library instead of a process that prints them, and values
+//! that a GPU doesn't support, like the fan of a laptop GPU, are `None`.
+
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_uint, c_void};
+
+use nix::libc;
+
+use crate::errors::StdResult;
+
+const LIBRARY: &[u8] = b"libnvidia-ml.so.1\0";
+
+type Return = c_uint;
+type Device = *mut c_void;
+
+const SUCCESS: Return = 0;
+const ERROR_NOT_SUPPORTED: Return = 3;
+const ERROR_DRIVER_NOT_LOADED: Return = 9;
+const TEMPERATURE_GPU: c_uint = 0;
+const CLOCK_GRAPHICS: c_uint = 0;
+/// `NVML_DEVICE_NAME_V2_BUFFER_SIZE`
+const NAME_SIZE: usize = 96;
+
+#[repr(C)]
+#[derive(Default)]
+struct Utilization {
+    gpu: c_uint,
+    memory: c_uint,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Memory {
+    total: u64,
+    free: u64,
+    used: u64,
+}
+
+/// The functions of the library that are used
+struct Functions {
+    init: unsafe extern "C" fn() -> Return,
+    shutdown: unsafe extern "C" fn() -> Return,
+    error_string: unsafe extern "C" fn(Return) -> *const c_char,
+    handle_by_index: unsafe extern "C" fn(c_uint, *mut Device) -> Return,
+    name: unsafe extern "C" fn(Device, *mut c_char, c_uint) -> Return,
+    utilization: unsafe extern "C" fn(Device, *mut Utilization) -> Return,
+    memory: unsafe extern "C" fn(Device, *mut Memory) -> Return,
+    temperature: unsafe extern "C" fn(Device, c_uint, *mut c_uint) -> Return,
+    fan_speed: unsafe extern "C" fn(Device, *mut c_uint) -> Return,
+    clock: unsafe extern "C" fn(Device, c_uint, *mut c_uint) -> Return,
+    power_usage: unsafe extern "C" fn(Device, *mut c_uint) -> Return,
+}
+
+/// An error of the library
+#[derive(Debug, Clone)]
+pub struct Error {
+    code: Return,
+    pub message: String,
+}
+
+impl Error {
+    /// Whether the NVIDIA driver isn't loaded, e.g. while a hybrid laptop uses the other GPU
+    pub fn is_driver_not_loaded(&self) -> bool {
+        self.code == ERROR_DRIVER_NOT_LOADED
+    }
+}
+
+/// The values of a GPU, like the ones of `nvidia-smi --query-gpu`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Reading {
+    pub name: String,
+    /// In MiB
+    pub memory_total: Option<u64>,
+    pub memory_used: Option<u64>,
+    /// In percent
+    pub utilization: Option<u64>,
+    /// In degrees Celsius
+    pub temperature: Option<u64>,
+    /// In percent
+    pub fan_speed: Option<u64>,
+    /// In MHz
+    pub clocks: Option<u64>,
+    /// In watts
+    pub power_draw: Option<f64>,
+}
+
+/// The function `name` of `library`, which has to be an `F`. `name` ends with a 0.
+unsafe fn symbol<F: Copy>(library: *mut c_void, name: &[u8]) -> StdResult<F, String> {
+    let function = libc::dlsym(library, name.as_ptr() as *const c_char);
+    match function.is_null() {
+        true => Err(format!(
+            "libnvidia-ml.so.1 has no {}",
+            String::from_utf8_lossy(&name[..name.len() - 1])
+        )),
+        false => Ok(std::mem::transmute_copy::<*mut c_void, F>(&function)),
+    }
+}
+
+pub struct Nvml {
+    library: *mut c_void,
+    functions: Functions,
+    initialized: bool,
+}
+
+impl Nvml {
+    /// Loads the library, which doesn't need the driver yet
+    pub fn load() -> StdResult<Nvml, String> {
+        // Safe as long as the library has the functions of NVML with their signatures, which it
+        // has had since version 5 of NVML
+        unsafe {
+            let library = libc::dlopen(LIBRARY.as_ptr() as *const c_char, libc::RTLD_NOW);
+            if library.is_null() {
+                return Err("failed to load libnvidia-ml.so.1".to_string());
+            }
+            let functions = (|| {
+                Ok(Functions {
+                    init: symbol(library, b"nvmlInit_v2\0")?,
+                    shutdown: symbol(library, b"nvmlShutdown\0")?,
+                    error_string: symbol(library, b"nvmlErrorString\0")?,
+                    handle_by_index: symbol(library, b"nvmlDeviceGetHandleByIndex_v2\0")?,
+                    name: symbol(library, b"nvmlDeviceGetName\0")?,
+                    utilization: symbol(library, b"nvmlDeviceGetUtilizationRates\0")?,
+                    memory: symbol(library, b"nvmlDeviceGetMemoryInfo\0")?,
+                    temperature: symbol(library, b"nvmlDeviceGetTemperature\0")?,
+                    fan_speed: symbol(library, b"nvmlDeviceGetFanSpeed\0")?,
+                    clock: symbol(library, b"nvmlDeviceGetClockInfo\0")?,
+                    power_usage: symbol(library, b"nvmlDeviceGetPowerUsage\0")?,
+                })
+            })();
+            match functions {
+                Ok(functions) => Ok(Nvml {
+                    library,
+                    functions,
+                    initialized: false,
+                }),
+                Err(e) => {
+                    libc::dlclose(library);
+                    Err(e)
+                }
+            }
+        }
+    }
+
+    fn check(&self, code: Return) -> StdResult<(), Error> {
+        if code == SUCCESS {
+            return Ok(());
+        }
+        let message = unsafe { (self.functions.error_string)(code) };
+        Err(Error {
+            code,
+            message: match message.is_null() {
+                true => format!("error {}", code),
+                false => unsafe { CStr::from_ptr(message) }
+                    .to_string_lossy()
+                    .into_owned(),
+            },
+        })
+    }
+
+    /// A value that the GPU may not support
+    fn optional(&self, code: Return) -> StdResult<bool, Error> {
+        match code {
+            ERROR_NOT_SUPPORTED => Ok(false),
+            code => self.check(code).map(|()| true),
+        }
+    }
+
+    /// Reads the values of the GPU `index`. Initializes the library first if it isn't yet.
+    pub fn read(&mut self, index: u32) -> StdResult<Reading, Error> {
+        if !self.initialized {
+            self.check(unsafe { (self.functions.init)() })?;
+            self.initialized = true;
+        }
+        let result = self.read_device(index);
+        if result.is_err() {
+            // The GPU may be gone, or the driver was unloaded
+            self.shutdown();
+        }
+        result
+    }
+
+    fn read_device(&self, index: u32) -> StdResult<Reading, Error> {
+        let f = &self.functions;
+        let mut device: Device = std::ptr::null_mut();
+        let mut name = [0 as c_char; NAME_SIZE];
+        let mut utilization = Utilization::default();
+        let mut memory = Memory::default();
+        let (mut temperature, mut fan_speed, mut clock, mut power) = (0, 0, 0, 0);
+        // Safe because the buffers are as big as the functions expect
+        unsafe {
+            self.check((f.handle_by_index)(index, &mut device))?;
+            self.check((f.name)(device, name.as_mut_ptr(), NAME_SIZE as c_uint))?;
+            let has_utilization = self.optional((f.utilization)(device, &mut utilization))?;
+            let has_memory = self.optional((f.memory)(device, &mut memory))?;
+            let has_temperature =
+                self.optional((f.temperature)(device, TEMPERATURE_GPU, &mut temperature))?;
+            let has_fan_speed = self.optional((f.fan_speed)(device, &mut fan_speed))?;
+            let has_clock = self.optional((f.clock)(device, CLOCK_GRAPHICS, &mut clock))?;
+            let has_power = self.optional((f.power_usage)(device, &mut power))?;
+            let mib = |bytes: u64| bytes / 1024 / 1024;
+            Ok(Reading {
+                name: CStr::from_ptr(name.as_ptr()).to_string_lossy().into_owned(),
+                memory_total: Some(mib(memory.total)).filter(|_| has_memory),
+                memory_used: Some(mib(memory.used)).filter(|_| has_memory),
+                utilization: Some(utilization.gpu as u64).filter(|_| has_utilization),
+                temperature: Some(temperature as u64).filter(|_| has_temperature),
+                fan_speed: Some(fan_speed as u64).filter(|_| has_fan_speed),
+                clocks: Some(clock as u64).filter(|_| has_clock),
+                // In milliwatts
+                power_draw: Some(power as f64 / 1000.).filter(|_| has_power),
+            })
+        }
+    }
+
+    fn shutdown(&mut self) {
+        if self.initialized {
+            unsafe { (self.functions.shutdown)() };
+            self.initialized = false;
+        }
+    }
+}
+
+impl Drop for Nvml {
+    fn drop(&mut self) {
+        self.shutdown();
+        unsafe { libc::dlclose(self.library) };
+    }
+}
+
+/// Parses a line of `nvidia-smi --query-gpu=<SMI_FIELDS> --format=csv,noheader,nounits`. Values
+/// that the GPU doesn't support are `[N/A]` or `[Not Supported]`.
+pub fn parse_smi(line: &str) -> Option<Reading> {
+    let fields: Vec<&str> = line.trim().split(", ").collect();
+    if fields.len() < 8 {
+        return None;
+    }
+    let number = |field: &str| field.trim().parse::<f64>().ok();
+    let integer = |field: &str| number(field).map(|value| value.round() as u64);
+    Some(Reading {
+        name: fields[0].to_string(),
+        memory_total: integer(fields[1]),
+        utilization: integer(fields[2]),
+        memory_used: integer(fields[3]),
+        temperature: integer(fields[4]),
+        fan_speed: integer(fields[5]),
+        clocks: integer(fields[6]),
+        power_draw: number(fields[7]),
+    })
+}
+
+/// The fields of `parse_smi`
+pub const SMI_FIELDS: &str = "name,memory.total,utilization.gpu,memory.used,temperature.gpu,\
+                              fan.speed,clocks.current.graphics,power.draw";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smi_output() {
+        assert_eq!(
+            parse_smi("NVIDIA GeForce GTX 1650, 3911, 7, 356, 42, [N/A], 300, 3.57\n"),
+            Some(Reading {
+                name: "NVIDIA GeForce GTX 1650".to_string(),
+                memory_total: Some(3911),
+                memory_used: Some(356),
+                utilization: Some(7),
+                temperature: Some(42),
+                fan_speed: None,
+                clocks: Some(300),
+                power_draw: Some(3.57),
+            })
+        );
+        assert_eq!(parse_smi(""), None);
+    }
+}
@@ -0,0 +1,267 @@
+//! Whether the bar is hidden and whether the machine runs on battery, for the blocks with
+//! `interval_hidden` or `interval_on_battery`, which then update less often.
+//!
+//! The bar is hidden while all bars that run i3status-rs (or all bars, if none of their
+//! `status_command`s mentions it) are in the `hide` or `invisible` mode of i3 or sway and not
+//! shown by the modifier. Blocks that were slowed down are updated right away once the bar is
+//! shown again or the machine is plugged in.
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use lazy_static::lazy_static;
+
+use crate::errors::*;
+use crate::scheduler::Task;
+
+/// How often the power supplies are checked
+const BATTERY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long to wait before connecting again after the window manager went away
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(10);
+
+const GET_BAR_CONFIG: u32 = 6;
+const SUBSCRIBE: u32 = 2;
+const BARCONFIG_UPDATE: u32 = 0x8000_0004;
+/// Sway only
+const BAR_STATE_UPDATE: u32 = 0x8000_0014;
+
+static BAR_HIDDEN: AtomicBool = AtomicBool::new(false);
+static ON_BATTERY: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    /// The blocks that update less often than they would, with the channel of their updates
+    static ref SLOWED: Mutex<HashMap<usize, Sender<Task>>> = Mutex::new(HashMap::new());
+    /// The blocks whose update was requested because they aren't slowed down anymore
+    static ref SPED_UP: Mutex<HashSet<usize>> = Mutex::new(HashSet::new());
+}
+
+pub fn bar_hidden() -> bool {
+    BAR_HIDDEN.load(Ordering::Relaxed)
+}
+
+pub fn on_battery() -> bool {
+    ON_BATTERY.load(Ordering::Relaxed)
+}
+
+/// Remembers that the block `id` updates less often, so it's updated once that's over
+pub fn slowed(id: usize, tx_update_request: &Sender<Task>) {
+    SLOWED.lock().unwrap().insert(id, tx_update_request.clone());
+}
+
+/// Whether the update of the block `id` was requested because it isn't slowed down anymore. Its
+/// scheduled update is then replaced, so the shorter interval applies.
+pub fn sped_up(id: usize) -> bool {
+    SPED_UP.lock().unwrap().remove(&id)
+}
+
+fn speed_up() {
+    for (id, tx_update_request) in SLOWED.lock().unwrap().drain() {
+        SPED_UP.lock().unwrap().insert(id);
+        let _ = tx_update_request.send(Task {
+            id,
+            update_time: Instant::now(),
+        });
+    }
+}
+
+/// Sets `state` and updates the slowed blocks if it was cleared
+fn set(state: &AtomicBool, value: bool) {
+    if state.swap(value, Ordering::Relaxed) && !value {
+        speed_up();
+    }
+}
+
+/// A bar of the window manager, from its `bar_config` and `bar_state_update`
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Bar {
+    mode: String,
+    status_command: String,
+    visible_by_modifier: bool,
+}
+
+/// Whether the bars of i3status-rs are hidden
+fn hidden(bars: &HashMap<String, Bar>) -> bool {
+    let ours: Vec<&Bar> = bars
+        .values()
+        .filter(|bar| bar.status_command.contains("i3status-rs"))
+        .collect();
+    let ours = match ours.is_empty() {
+        true => bars.values().collect(),
+        false => ours,
+    };
+    !ours.is_empty()
+        && ours.iter().all(|bar| {
+            matches!(bar.mode.as_str(), "hide" | "invisible") && !bar.visible_by_modifier
+        })
+}
+
+fn send(stream: &mut UnixStream, kind: u32, payload: &str) -> io::Result<()> {
+    let mut message = b"i3-ipc".to_vec();
+    message.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+    message.extend_from_slice(&kind.to_ne_bytes());
+    message.extend_from_slice(payload.as_bytes());
+    stream.write_all(&message)
+}
+
+fn receive(stream: &mut UnixStream) -> io::Result<(u32, serde_json::Value)> {
+    let mut header = [0; 14];
+    stream.read_exact(&mut header)?;
+    let length = u32::from_ne_bytes([header[6], header[7], header[8], header[9]]);
+    let kind = u32::from_ne_bytes([header[10], header[11], header[12], header[13]]);
+    let mut payload = vec![0; length as usize];
+    stream.read_exact(&mut payload)?;
+    let payload = serde_json::from_slice(&payload)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok((kind, payload))
+}
+
+fn apply_config(bars: &mut HashMap<String, Bar>, config: &serde_json::Value) {
+    if let Some(id) = config["id"].as_str() {
+        let bar = bars.entry(id.to_string()).or_default();
+        bar.mode = config["mode"].as_str().unwrap_or("dock").to_string();
+        bar.status_command = config["status_command"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+    }
+}
+
+/// Follows the bars over the IPC of i3 or sway, until the connection fails
+fn follow_bars(path: &str) -> io::Result<()> {
+    let mut bars: HashMap<String, Bar> = HashMap::new();
+    let mut stream = UnixStream::connect(path)?;
+    send(&mut stream, GET_BAR_CONFIG, "")?;
+    let (_, ids) = receive(&mut stream)?;
+    for id in ids
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|id| id.as_str())
+    {
+        send(&mut stream, GET_BAR_CONFIG, id)?;
+        let (_, config) = receive(&mut stream)?;
+        apply_config(&mut bars, &config);
+    }
+    set(&BAR_HIDDEN, hidden(&bars));
+
+    send(
+        &mut stream,
+        SUBSCRIBE,
+        r#"["barconfig_update", "bar_state_update"]"#,
+    )?;
+    loop {
+        let (kind, payload) = receive(&mut stream)?;
+        match kind {
+            BARCONFIG_UPDATE => apply_config(&mut bars, &payload),
+            BAR_STATE_UPDATE => {
+                if let Some(id) = payload["id"].as_str() {
+                    bars.entry(id.to_string()).or_default().visible_by_modifier =
+                        payload["visible_by_modifier"].as_bool().unwrap_or(false);
+                }
+            }
+            _ => continue,
+        }
+        set(&BAR_HIDDEN, hidden(&bars));
+    }
+}
+
+/// Starts the thread that follows whether the bar is hidden, once
+pub fn watch_bar() -> Result<()> {
+    static STARTED: AtomicBool = AtomicBool::new(false);
+    if STARTED.swap(true, Ordering::Relaxed) {
+        return Ok(());
+    }
+    // Without a window manager, e.g. in a terminal, the bar is never hidden
+    let path = match env::var("SWAYSOCK").or_else(|_| env::var("I3SOCK")) {
+        Ok(path) => path,
+        Err(_) => return Ok(()),
+    };
+    thread::Builder::new()
+        .name("polling".into())
+        .spawn(move || loop {
+            let _ = follow_bars(&path);
+            set(&BAR_HIDDEN, false);
+            thread::sleep(RECONNECT_INTERVAL);
+        })
+        .map(|_| ())
+        .internal_error("polling", "failed to start thread")
+}
+
+/// Whether there is a mains power supply and none is online
+fn check_battery() -> bool {
+    let mut mains = false;
+    let supplies = fs::read_dir("/sys/class/power_supply")
+        .into_iter()
+        .flatten();
+    for supply in supplies.flatten() {
+        let path = supply.path();
+        let kind = fs::read_to_string(path.join("type")).unwrap_or_default();
+        if kind.trim() != "Mains" {
+            continue;
+        }
+        mains = true;
+        if fs::read_to_string(path.join("online"))
+            .unwrap_or_default()
+            .trim()
+            == "1"
+        {
+            return false;
+        }
+    }
+    mains
+}
+
+/// Starts the thread that checks whether the machine runs on battery, once
+pub fn watch_battery() -> Result<()> {
+    static STARTED: AtomicBool = AtomicBool::new(false);
+    if STARTED.swap(true, Ordering::Relaxed) {
+        return Ok(());
+    }
+    ON_BATTERY.store(check_battery(), Ordering::Relaxed);
+    thread::Builder::new()
+        .name("polling".into())
+        .spawn(move || loop {
+            thread::sleep(BATTERY_INTERVAL);
+            set(&ON_BATTERY, check_battery());
+        })
+        .map(|_| ())
+        .internal_error("polling", "failed to start thread")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bars() {
+        let mut bars = HashMap::new();
+        assert!(!hidden(&bars));
+        let bar = |mode: &str, status_command: &str| Bar {
+            mode: mode.to_string(),
+            status_command: status_command.to_string(),
+            visible_by_modifier: false,
+        };
+        bars.insert(
+            "bar-0".to_string(),
+            bar("hide", "i3status-rs ~/.config/bar.toml"),
+        );
+        bars.insert("bar-1".to_string(), bar("dock", "i3status"));
+        assert!(hidden(&bars));
+        bars.get_mut("bar-0").unwrap().visible_by_modifier = true;
+        assert!(!hidden(&bars));
+        // Without a bar of i3status-rs, all bars count
+        bars.insert("bar-0".to_string(), bar("invisible", "i3blocks"));
+        assert!(!hidden(&bars));
+        bars.insert("bar-1".to_string(), bar("hide", "i3blocks"));
+        assert!(hidden(&bars));
+    }
+}
@@ -0,0 +1,75 @@
+//! Profiles, named sets of the blocks that are shown, e.g. `work`, `gaming` or `presentation`.
+//!
+//! The main loop switches between them, for `i3status-rs profile` or when a `profile` block asks
+//! for it after a click. All blocks live on the main thread, so thread locals are sufficient.
+
+use std::cell::RefCell;
+
+thread_local! {
+    static NAMES: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    // `None` while all blocks are shown
+    static ACTIVE: RefCell<Option<String>> = const { RefCell::new(None) };
+    static REQUESTED: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// The names of the profiles of the configuration, in its order
+pub fn set_names(names: Vec<String>) {
+    NAMES.with(|all| *all.borrow_mut() = names);
+}
+
+pub fn active() -> Option<String> {
+    ACTIVE.with(|active| active.borrow().clone())
+}
+
+pub fn set_active(name: Option<String>) {
+    ACTIVE.with(|active| *active.borrow_mut() = name);
+}
+
+/// The profile after the active one, or before it with `backwards`. Without an active profile,
+/// it's the first or the last one.
+pub fn next(backwards: bool) -> Option<String> {
+    let active = active();
+    NAMES.with(|names| {
+        let names = names.borrow();
+        let count = names.len();
+        if count == 0 {
+            return None;
+        }
+        let position = active.and_then(|active| names.iter().position(|name| *name == active));
+        let next = match (position, backwards) {
+            (None, false) => 0,
+            (None, true) => count - 1,
+            (Some(position), false) => (position + 1) % count,
+            (Some(position), true) => (position + count - 1) % count,
+        };
+        Some(names[next].clone())
+    })
+}
+
+/// Asks the main loop to switch to the profile `name`, e.g. after a click on a block
+pub fn request(name: String) {
+    REQUESTED.with(|requested| *requested.borrow_mut() = Some(name));
+}
+
+/// The profile that was requested since the last call
+pub fn take_request() -> Option<String> {
+    REQUESTED.with(|requested| requested.borrow_mut().take())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cycle() {
+        assert_eq!(next(false), None);
+        set_names(vec!["work".to_string(), "gaming".to_string()]);
+        assert_eq!(next(false).as_deref(), Some("work"));
+        assert_eq!(next(true).as_deref(), Some("gaming"));
+        set_active(Some("gaming".to_string()));
+        assert_eq!(next(false).as_deref(), Some("work"));
+        assert_eq!(next(true).as_deref(), Some("work"));
+        set_active(Some("removed".to_string()));
+        assert_eq!(next(false).as_deref(), Some("work"));
+    }
+}
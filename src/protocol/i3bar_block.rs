@@ -1,3 +1,5 @@
+use std::fmt::{self, Write};
+
 use crate::themes::Color;
 
 /// Represent block as described in https://i3wm.org/docs/i3bar-protocol.html
@@ -21,68 +23,121 @@ pub struct I3BarBlock {
     pub separator: Option<bool>,
     pub separator_block_width: Option<usize>,
     pub markup: Option<String>,
+    /// Not part of the protocol: the color the background fades to from `background`
+    pub background_gradient: Color,
 }
 
-macro_rules! json_add_str {
-    ($retval:ident, $obj:expr, $name:expr) => {
-        if let Some(ref val) = $obj {
-            $retval.push_str(&format!(
-                "\"{}\":\"{}\",",
-                stringify!($name),
-                val.chars()
-                    .map(|c| match c {
-                        '\\' => "\\\\".to_string(),
-                        '\n' => "\\n".to_string(),
-                        '"' => "\\\"".to_string(),
-                        x => x.to_string(),
-                    })
-                    .collect::<String>(),
-            ));
+// The blocks are serialized on every redraw, many times a second with some blocks, so they are
+// written into a reused buffer instead of building strings for every field
+
+fn add_str(out: &mut String, name: &str, value: &str) {
+    out.push('"');
+    out.push_str(name);
+    out.push_str("\":\"");
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '"' => out.push_str("\\\""),
+            c => out.push(c),
         }
-    };
+    }
+    out.push_str("\",");
 }
-macro_rules! json_add_val {
-    ($retval:ident, $obj:expr, $name:expr) => {
-        if let Some(val) = $obj {
-            $retval.push_str(&format!("\"{}\":{},", stringify!($name), val));
-        }
-    };
+
+fn add_val(out: &mut String, name: &str, value: impl fmt::Display) {
+    // Writing to a `String` can't fail
+    let _ = write!(out, "\"{}\":{},", name, value);
+}
+
+fn add_color(out: &mut String, name: &str, color: Color) {
+    if let Color::Rgba(r, g, b, a) = color {
+        let _ = write!(
+            out,
+            "\"{}\":\"#{:02X}{:02X}{:02X}{:02X}\",",
+            name, r, g, b, a
+        );
+    }
 }
 
 impl I3BarBlock {
-    pub fn render(&self) -> String {
-        let mut retval = String::from("{");
+    /// Makes the block a copy of `source`, reusing its strings instead of allocating new ones
+    pub fn copy_from(&mut self, source: &I3BarBlock) {
+        self.full_text.clone_from(&source.full_text);
+        self.short_text.clone_from(&source.short_text);
+        self.color = source.color;
+        self.background = source.background;
+        self.border.clone_from(&source.border);
+        self.border_top = source.border_top;
+        self.border_right = source.border_right;
+        self.border_bottom = source.border_bottom;
+        self.border_left = source.border_left;
+        self.min_width.clone_from(&source.min_width);
+        self.align = source.align;
+        self.name.clone_from(&source.name);
+        self.instance.clone_from(&source.instance);
+        self.urgent = source.urgent;
+        self.separator = source.separator;
+        self.separator_block_width = source.separator_block_width;
+        self.markup.clone_from(&source.markup);
+        self.background_gradient = source.background_gradient;
+    }
 
-        json_add_str!(retval, Some(&self.full_text), full_text);
-        json_add_str!(retval, self.short_text, short_text);
-        json_add_str!(retval, self.color.to_string(), color);
-        json_add_str!(retval, self.background.to_string(), background);
-        json_add_str!(retval, self.border, border);
-        json_add_val!(retval, self.border_top, border_top);
-        json_add_val!(retval, self.border_right, border_right);
-        json_add_val!(retval, self.border_bottom, border_bottom);
-        json_add_val!(retval, self.border_left, border_left);
+    /// Appends the block as a JSON object to `out`
+    pub fn write_json(&self, out: &mut String) {
+        out.push('{');
+        add_str(out, "full_text", &self.full_text);
+        if let Some(ref short_text) = self.short_text {
+            add_str(out, "short_text", short_text);
+        }
+        add_color(out, "color", self.color);
+        add_color(out, "background", self.background);
+        if let Some(ref border) = self.border {
+            add_str(out, "border", border);
+        }
+        let borders = [
+            ("border_top", self.border_top),
+            ("border_right", self.border_right),
+            ("border_bottom", self.border_bottom),
+            ("border_left", self.border_left),
+        ];
+        for (name, width) in borders {
+            if let Some(width) = width {
+                add_val(out, name, width);
+            }
+        }
         match self.min_width {
-            Some(I3BarBlockMinWidth::Pixels(x)) => json_add_val!(retval, Some(x), min_width),
-            Some(I3BarBlockMinWidth::Text(ref x)) => json_add_str!(retval, Some(x), min_width),
+            Some(I3BarBlockMinWidth::Pixels(x)) => add_val(out, "min_width", x),
+            Some(I3BarBlockMinWidth::Text(ref x)) => add_str(out, "min_width", x),
             None => {}
         }
         match self.align {
-            Some(I3BarBlockAlign::Center) => retval.push_str("\"align\":\"center\","),
-            Some(I3BarBlockAlign::Right) => retval.push_str("\"align\":\"right\","),
-            Some(I3BarBlockAlign::Left) => retval.push_str("\"align\":\"left\","),
+            Some(I3BarBlockAlign::Center) => out.push_str("\"align\":\"center\","),
+            Some(I3BarBlockAlign::Right) => out.push_str("\"align\":\"right\","),
+            Some(I3BarBlockAlign::Left) => out.push_str("\"align\":\"left\","),
             None => {}
         }
-        json_add_str!(retval, self.name, name);
-        json_add_str!(retval, self.instance, instance);
-        json_add_val!(retval, self.urgent, urgent);
-        json_add_val!(retval, self.separator, separator);
-        json_add_val!(retval, self.separator_block_width, separator_block_width);
-        json_add_str!(retval, self.markup, markup);
-
-        retval.pop();
-        retval.push('}');
-        retval
+        if let Some(ref name) = self.name {
+            add_str(out, "name", name);
+        }
+        if let Some(ref instance) = self.instance {
+            add_str(out, "instance", instance);
+        }
+        if let Some(urgent) = self.urgent {
+            add_val(out, "urgent", urgent);
+        }
+        if let Some(separator) = self.separator {
+            add_val(out, "separator", separator);
+        }
+        if let Some(width) = self.separator_block_width {
+            add_val(out, "separator_block_width", width);
+        }
+        if let Some(ref markup) = self.markup {
+            add_str(out, "markup", markup);
+        }
+        // The trailing comma, there is always `full_text`
+        out.pop();
+        out.push('}');
     }
 }
 
@@ -110,6 +165,7 @@ impl Default for I3BarBlock {
             separator: Some(false),
             separator_block_width: Some(0),
             markup: Some("pango".to_string()),
+            background_gradient: Color::None,
         }
     }
 }
@@ -126,3 +182,28 @@ pub enum I3BarBlockMinWidth {
     Pixels(usize),
     Text(String),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json() {
+        let block = I3BarBlock {
+            full_text: "a \"b\" \\ c\n".to_string(),
+            color: Color::Rgba(0x12, 0x34, 0x56, 0xFF),
+            border: None,
+            min_width: Some(I3BarBlockMinWidth::Pixels(10)),
+            name: Some("3".to_string()),
+            ..Default::default()
+        };
+        let mut out = String::new();
+        block.write_json(&mut out);
+        assert_eq!(
+            out,
+            r##"{"full_text":"a \"b\" \\ c\n","color":"#123456FF","min_width":10,"name":"3","separator":false,"separator_block_width":0,"markup":"pango"}"##
+        );
+        let parsed: serde_json::Value = serde_json::from_str(&out).unwrap();
+        assert_eq!(parsed["full_text"], "a \"b\" \\ c\n");
+    }
+}
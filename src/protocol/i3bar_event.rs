@@ -1,5 +1,5 @@
 use std::fmt;
-use std::io;
+use std::io::{self, BufRead};
 use std::option::Option;
 use std::string::*;
 use std::thread;
@@ -8,16 +8,49 @@ use crossbeam_channel::Sender;
 use serde::{de, Deserializer};
 use serde_derive::Deserialize;
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum MouseButton {
     Left,
     Middle,
     Right,
+    #[serde(alias = "up")]
     WheelUp,
+    #[serde(alias = "down")]
     WheelDown,
     Forward, // On my mouse, these map to forward and back
     Back,
     Unknown,
+    // Double clicks are detected by the click dispatcher, i3bar doesn't send them
+    DoubleLeft,
+    DoubleMiddle,
+    DoubleRight,
+    // Holds too, from the releases of `i3status-rs release`
+    HoldLeft,
+    HoldMiddle,
+    HoldRight,
+}
+
+impl MouseButton {
+    /// The double click variant of the button, if there is one
+    pub fn double(self) -> Self {
+        match self {
+            MouseButton::Left => MouseButton::DoubleLeft,
+            MouseButton::Middle => MouseButton::DoubleMiddle,
+            MouseButton::Right => MouseButton::DoubleRight,
+            other => other,
+        }
+    }
+
+    /// The hold variant of the button, if there is one
+    pub fn hold(self) -> Self {
+        match self {
+            MouseButton::Left => MouseButton::HoldLeft,
+            MouseButton::Middle => MouseButton::HoldMiddle,
+            MouseButton::Right => MouseButton::HoldRight,
+            other => other,
+        }
+    }
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -28,6 +61,9 @@ struct I3BarEventInternal {
     pub x: u64,
     #[allow(dead_code)]
     pub y: u64,
+    /// Only sent by newer versions of i3bar and swaybar
+    pub relative_x: Option<f64>,
+    pub width: Option<f64>,
 
     #[serde(deserialize_with = "deserialize_mousebutton")]
     pub button: MouseButton,
@@ -38,9 +74,27 @@ pub struct I3BarEvent {
     pub id: Option<usize>,
     pub instance: Option<usize>,
     pub button: MouseButton,
+    /// Horizontal position of the click within the widget, from 0 (left edge) to 1 (right edge)
+    pub position: Option<f64>,
+    /// Scroll actions are multiplied by this, see `ScrollAcceleration`
+    pub scroll_factor: f64,
+    /// The event as it was sent by the bar, including e.g. the modifiers
+    pub raw: Option<String>,
 }
 
 impl I3BarEvent {
+    /// `step` of a scroll action, multiplied by `scroll_factor` but at least 1
+    pub fn scale_step(&self, step: f64) -> f64 {
+        (step * self.scroll_factor).round().max(1.0)
+    }
+
+    /// The value at the position of the click, for widgets used as a slider from `min` at the left
+    /// edge to `max` at the right edge
+    pub fn slider_value(&self, min: f64, max: f64) -> Option<f64> {
+        self.position
+            .map(|position| (min + position.clamp(0.0, 1.0) * (max - min)).round())
+    }
+
     pub fn matches_id(&self, other: usize) -> bool {
         match self.id {
             Some(id) => id == other,
@@ -49,31 +103,58 @@ impl I3BarEvent {
     }
 }
 
-pub fn process_events(sender: Sender<I3BarEvent>) {
+/// With `debug`, the events are logged as they were received
+pub fn process_events(sender: Sender<I3BarEvent>, debug: bool) {
     thread::Builder::new()
         .name("input".into())
-        .spawn(move || loop {
-            let mut input = String::new();
-            io::stdin().read_line(&mut input).unwrap();
-
-            // Take only the valid JSON object betweem curly braces (cut off leading bracket, commas and whitespace)
-            let slice = input.trim_start_matches(|c| c != '{');
-            let slice = slice.trim_end_matches(|c| c != '}');
-
-            if !slice.is_empty() {
-                let e: I3BarEventInternal = serde_json::from_str(slice).unwrap();
-                sender
-                    .send(I3BarEvent {
-                        id: e.name.map(|x| x.parse::<usize>().unwrap()),
-                        instance: e.instance.map(|x| x.parse::<usize>().unwrap()),
-                        button: e.button,
-                    })
-                    .unwrap();
-            }
-        })
+        .spawn(move || read_events(io::stdin().lock(), &sender, debug))
         .unwrap();
 }
 
+/// Passes the events of `input` to `sender` until `input` is closed, e.g. the events of another
+/// bar
+pub fn read_events(mut input: impl BufRead, sender: &Sender<I3BarEvent>, debug: bool) {
+    let mut line = String::new();
+    loop {
+        line.clear();
+        match input.read_line(&mut line) {
+            Ok(0) | Err(_) => return,
+            Ok(_) => {}
+        }
+
+        // Take only the valid JSON object betweem curly braces (cut off leading bracket, commas and whitespace)
+        let slice = line.trim_start_matches(|c| c != '{');
+        let slice = slice.trim_end_matches(|c| c != '}');
+
+        if !slice.is_empty() {
+            if debug {
+                eprintln!("click: received {}", slice);
+            }
+            let e: I3BarEventInternal = match serde_json::from_str(slice) {
+                Ok(e) => e,
+                Err(e) => {
+                    tracing::warn!("invalid click event {}: {}", slice, e);
+                    continue;
+                }
+            };
+            let event = I3BarEvent {
+                id: e.name.and_then(|x| x.parse::<usize>().ok()),
+                instance: e.instance.and_then(|x| x.parse::<usize>().ok()),
+                button: e.button,
+                position: match (e.relative_x, e.width) {
+                    (Some(x), Some(width)) if width > 0.0 => Some(x / width),
+                    _ => None,
+                },
+                scroll_factor: 1.0,
+                raw: Some(slice.to_string()),
+            };
+            if sender.send(event).is_err() {
+                return;
+            }
+        }
+    }
+}
+
 fn deserialize_mousebutton<'de, D>(deserializer: D) -> Result<MouseButton, D::Error>
 where
     D: Deserializer<'de>,
@@ -91,8 +172,6 @@ where
         where
             E: de::Error,
         {
-            // TODO: put this behind `--debug` flag
-            //eprintln!("{}", value);
             Ok(match value {
                 1 => MouseButton::Left,
                 2 => MouseButton::Middle,
@@ -1,110 +1,382 @@
 pub mod i3bar_block;
 pub mod i3bar_event;
+pub mod recording;
+mod term;
+mod waybar;
 
-use crate::blocks::Block;
+use std::cell::{Cell, RefCell};
+use std::io::{self, Write};
+use std::time::Duration;
+
+use crate::bars;
+use crate::blocks::{self, Block};
 use crate::config::SharedConfig;
 use crate::errors::*;
 use crate::themes::Color;
 
 use i3bar_block::I3BarBlock;
 
+/// What the bar is printed for, set with `--output`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Output {
+    I3bar,
+    /// A single line with ANSI colors
+    Term,
+    /// A single line without colors
+    Plain,
+    /// JSON for a custom module of Waybar
+    Waybar,
+    /// Nothing, with `--headless`
+    Headless,
+}
+
+thread_local! {
+    // The bar is printed on the main thread
+    static OUTPUT: Cell<Output> = const { Cell::new(Output::I3bar) };
+    /// The buffers of `print_blocks`, reused for every redraw
+    static RENDERED: RefCell<Vec<I3BarBlock>> = const { RefCell::new(Vec::new()) };
+    static LINE: RefCell<String> = const { RefCell::new(String::new()) };
+    /// The last line that was sent, so redraws that don't change anything aren't sent
+    static LAST_LINE: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+pub fn set_output(output: Output) {
+    OUTPUT.with(|current| current.set(output));
+}
+
+fn output() -> Output {
+    OUTPUT.with(Cell::get)
+}
+
 pub fn init(never_pause: bool) {
+    if output() != Output::I3bar {
+        return;
+    }
     if never_pause {
-        println!("{{\"version\": 1, \"click_events\": true, \"stop_signal\": 0}}\n[");
+        write_line("{\"version\": 1, \"click_events\": true, \"stop_signal\": 0}");
     } else {
-        println!("{{\"version\": 1, \"click_events\": true}}\n[");
+        write_line("{\"version\": 1, \"click_events\": true}");
     }
+    write_line("[");
+}
+
+/// Whether `line` differs from the last line of the bar, which it replaces
+fn changed(line: &str) -> bool {
+    LAST_LINE.with(|last| {
+        let mut last = last.borrow_mut();
+        if *last == line {
+            return false;
+        }
+        last.clear();
+        last.push_str(line);
+        true
+    })
+}
+
+/// Sends a line to i3bar, and records it if `--record` is given
+pub fn write_line(line: &str) {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    // Like `println!`, which panics if i3bar is gone
+    stdout
+        .write_all(line.as_bytes())
+        .and_then(|_| stdout.write_all(b"\n"))
+        .and_then(|_| stdout.flush())
+        .expect("failed printing to stdout");
+    recording::record(line);
 }
 
 pub fn print_blocks(blocks: &[Box<dyn Block>], config: &SharedConfig) -> Result<()> {
+    let mut rendered_blocks = RENDERED.with(|rendered| rendered.take());
+    if output() == Output::Waybar {
+        let line = waybar::render(blocks);
+        if changed(&line) {
+            write_line(&line);
+        }
+    } else if output() != Output::Headless {
+        let all = blocks.iter().map(|block| block.as_ref());
+        render(all, config, output() == Output::I3bar, &mut rendered_blocks);
+        write_blocks(&rendered_blocks);
+    }
+
+    // The other bars are always drawn by i3bar or swaybar
+    bars::send(|ids| {
+        let shown = ids
+            .iter()
+            .filter_map(|&id| blocks::position(blocks, id))
+            .map(|position| blocks[position].as_ref());
+        render(shown, config, true, &mut rendered_blocks);
+        let mut line = String::new();
+        json_line(&rendered_blocks, &mut line);
+        line
+    });
+    RENDERED.with(|rendered| *rendered.borrow_mut() = rendered_blocks);
+
+    Ok(())
+}
+
+/// The next block of `rendered_blocks`, whose strings are reused from the last redraw
+fn next_slot<'a>(rendered_blocks: &'a mut Vec<I3BarBlock>, len: &mut usize) -> &'a mut I3BarBlock {
+    if *len == rendered_blocks.len() {
+        rendered_blocks.push(I3BarBlock::default());
+    }
+    *len += 1;
+    &mut rendered_blocks[*len - 1]
+}
+
+/// Renders the widgets of `blocks` with the separators of the theme into `rendered_blocks`, which
+/// keeps its strings from the last redraw. Gradients are only drawn for `i3bar`.
+fn render<'a>(
+    blocks: impl Iterator<Item = &'a dyn Block>,
+    config: &SharedConfig,
+    i3bar: bool,
+    rendered_blocks: &mut Vec<I3BarBlock>,
+) {
     let mut last_bg = Color::None;
+    let mut len = 0;
 
-    let mut rendered_blocks = vec![];
+    let views: Vec<_> = blocks
+        .map(|block| block.view())
+        .filter(|widgets| !widgets.is_empty())
+        .collect();
 
     /* To always start with the same alternating tint on the right side of the
      * bar it is easiest to calculate the number of visible blocks here and
      * flip the starting tint if an even number of blocks is visible. This way,
      * the last block should always be untinted.
      */
-    let visible_count = blocks
-        .iter()
-        .filter(|block| !block.view().is_empty())
-        .count();
+    let mut alternator = views.len() % 2 == 0;
+    let theme = config.theme();
+    let weather_separator = theme.weather.separator();
+    let theme_separator =
+        weather_separator
+            .as_ref()
+            .or(theme.separator.as_ref())
+            .map(|separator| I3BarBlock {
+                full_text: separator.clone(),
+                ..Default::default()
+            });
 
-    let mut alternator = visible_count % 2 == 0;
+    for widgets in views {
+        // The separator goes before the widgets, but its colors depend on them
+        let separator = theme_separator.as_ref().map(|separator| {
+            next_slot(rendered_blocks, &mut len).copy_from(separator);
+            len - 1
+        });
 
-    for block in blocks.iter() {
-        let widgets = block.view();
-        if widgets.is_empty() {
-            continue;
+        let start = len;
+        for widget in widgets {
+            let data = next_slot(rendered_blocks, &mut len);
+            widget.write_data(data);
+            if alternator {
+                // Apply tint for all widgets of every second block
+                // TODO: Allow for other non-additive tints
+                data.background = data.background + theme.alternating_tint_bg;
+                data.background_gradient = data.background_gradient + theme.alternating_tint_bg;
+                data.color = data.color + theme.alternating_tint_fg;
+            }
+            if i3bar {
+                apply_gradient(data);
+            }
         }
-
-        let mut rendered_widgets: Vec<I3BarBlock> = widgets
-            .iter()
-            .map(|widget| {
-                let mut data = widget.get_data();
-                if alternator {
-                    // Apply tint for all widgets of every second block
-                    // TODO: Allow for other non-additive tints
-                    data.background = data.background + config.theme.alternating_tint_bg;
-                    data.color = data.color + config.theme.alternating_tint_fg;
-                }
-                data
-            })
-            .collect();
+        let rendered_widgets = &mut rendered_blocks[start..len];
 
         alternator = !alternator;
 
-        if config.theme.separator.is_none() {
-            // Re-add native separator on last widget for native theme
-            rendered_widgets.last_mut().unwrap().separator = None;
-            rendered_widgets.last_mut().unwrap().separator_block_width = None;
+        // The widgets of a block share one border, so there are none between them
+        let count = rendered_widgets.len();
+        for (i, widget) in rendered_widgets.iter_mut().enumerate() {
+            if widget.border.is_some() {
+                if i > 0 {
+                    widget.border_left = Some(0);
+                }
+                if i + 1 < count {
+                    widget.border_right = Some(0);
+                }
+            }
         }
 
-        // Serialize and concatenate widgets
-        let block_str = rendered_widgets
-            .iter()
-            .map(|w| w.render())
-            .collect::<Vec<String>>()
-            .join(",");
-
-        if config.theme.separator.is_none() {
-            // Skip separator block for native theme
-            rendered_blocks.push(block_str.to_string());
-            continue;
-        }
+        let separator = match separator {
+            Some(separator) => separator,
+            None => {
+                // Re-add native separator on last widget for native theme
+                let last = rendered_widgets.last_mut().unwrap();
+                match theme.block_gap {
+                    // A gap instead of the separator line
+                    Some(gap) => last.separator_block_width = Some(gap),
+                    None => {
+                        last.separator = None;
+                        last.separator_block_width = None;
+                    }
+                }
+                continue;
+            }
+        };
 
         // The first widget's BG is used to get the FG color for the current separator
-        let sep_fg = if config.theme.separator_fg == Color::Auto {
+        let sep_fg = if theme.separator_fg == Color::Auto {
             rendered_widgets.first().unwrap().background
         } else {
-            config.theme.separator_fg
+            theme.separator_fg
         };
 
         // The separator's BG is the last block's last widget's BG
-        let sep_bg = if config.theme.separator_bg == Color::Auto {
+        let sep_bg = if theme.separator_bg == Color::Auto {
             last_bg
         } else {
-            config.theme.separator_bg
+            theme.separator_bg
         };
 
-        if let Some(ref separator) = config.theme.separator {
-            let separator = I3BarBlock {
-                full_text: separator.clone(),
-                background: sep_bg,
-                color: sep_fg,
-                ..Default::default()
-            };
-            rendered_blocks.push(format!("{},{}", separator.render(), block_str));
-        } else {
-            rendered_blocks.push(block_str);
+        // The last widget's BG is used to get the BG color for the next separator
+        let last_widget = rendered_widgets.last().unwrap();
+        last_bg = match last_widget.background_gradient {
+            Color::Rgba(..) if last_widget.markup.as_deref() == Some("pango") => {
+                last_widget.background_gradient
+            }
+            _ => last_widget.background,
+        };
+
+        rendered_blocks[separator].background = sep_bg;
+        rendered_blocks[separator].color = sep_fg;
+    }
+    rendered_blocks.truncate(len);
+}
+
+/// Sends a status line with `blocks`
+pub fn write_blocks(blocks: &[I3BarBlock]) {
+    match output() {
+        Output::I3bar => LINE.with(|line| {
+            let mut line = line.borrow_mut();
+            json_line(blocks, &mut line);
+            if changed(&line) {
+                write_line(&line);
+            }
+        }),
+        Output::Waybar => {
+            let line = waybar::render_widgets(blocks);
+            if changed(&line) {
+                write_line(&line);
+            }
         }
+        Output::Headless => (),
+        output => {
+            let line = term::render(blocks, output == Output::Term);
+            if changed(&line) {
+                term::write(&line);
+                recording::record(&line);
+            }
+        }
+    }
+}
 
-        // The last widget's BG is used to get the BG color for the next separator
-        last_bg = rendered_widgets.last().unwrap().background;
+/// Replaces `line` with the status line of i3bar for `blocks`
+fn json_line(blocks: &[I3BarBlock], line: &mut String) {
+    line.clear();
+    line.push('[');
+    for (i, block) in blocks.iter().enumerate() {
+        if i > 0 {
+            line.push(',');
+        }
+        block.write_json(line);
     }
+    line.push_str("],");
+}
 
-    println!("[{}],", rendered_blocks.join(","));
+/// The time until the next redraw is needed for flashing widgets
+pub fn next_flash(blocks: &[Box<dyn Block>]) -> Option<Duration> {
+    blocks
+        .iter()
+        .flat_map(|block| block.view())
+        .filter_map(|widget| widget.next_flash())
+        .min()
+}
 
-    Ok(())
+/// i3bar can only draw solid backgrounds, so gradients are drawn as backgrounds of the
+/// individual characters.
+fn apply_gradient(data: &mut I3BarBlock) {
+    if data.markup.as_deref() != Some("pango") {
+        return;
+    }
+    if let (Color::Rgba(..), Color::Rgba(..)) = (data.background, data.background_gradient) {
+        data.full_text =
+            gradient_markup(&data.full_text, data.background, data.background_gradient);
+        data.short_text = data
+            .short_text
+            .as_ref()
+            .map(|text| gradient_markup(text, data.background, data.background_gradient));
+    }
+}
+
+/// Wraps every character of the pango markup `text` in a span with a background between `from`
+/// and `to`. Tags and entities are kept intact.
+fn gradient_markup(text: &str, from: Color, to: Color) -> String {
+    enum Token<'a> {
+        Tag(&'a str),
+        Char(&'a str),
+    }
+
+    let mut tokens = Vec::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let end = match c {
+            '<' => rest.find('>').map(|i| i + 1),
+            '&' => rest.find(';').map(|i| i + 1),
+            _ => None,
+        }
+        .unwrap_or_else(|| c.len_utf8());
+        let (token, tail) = rest.split_at(end);
+        tokens.push(if c == '<' {
+            Token::Tag(token)
+        } else {
+            Token::Char(token)
+        });
+        rest = tail;
+    }
+
+    let chars = tokens
+        .iter()
+        .filter(|t| matches!(t, Token::Char(_)))
+        .count();
+    let mut out = String::new();
+    let mut i = 0;
+    for token in tokens {
+        match token {
+            Token::Tag(tag) => out.push_str(tag),
+            Token::Char(c) => {
+                let t = if chars > 1 {
+                    i as f64 / (chars - 1) as f64
+                } else {
+                    0.
+                };
+                if let Color::Rgba(r, g, b, _) = from.mix(to, t) {
+                    out.push_str(&format!(
+                        "<span background=\"#{:02X}{:02X}{:02X}\">{}</span>",
+                        r, g, b, c
+                    ));
+                }
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gradient() {
+        let black = Color::Rgba(0, 0, 0, 0xFF);
+        let white = Color::Rgba(0xFF, 0xFF, 0xFF, 0xFF);
+        assert_eq!(
+            gradient_markup("a<b>&amp;</b>c", black, white),
+            "<span background=\"#000000\">a</span><b><span background=\"#808080\">&amp;</span></b>\
+             <span background=\"#FFFFFF\">c</span>"
+        );
+        assert_eq!(
+            gradient_markup("x", black, white),
+            "<span background=\"#000000\">x</span>"
+        );
+    }
 }
@@ -0,0 +1,93 @@
+//! Recording of the output of the bar, e.g. to reproduce a rendering bug with
+//! `i3status-rs --replay session.txt` in the bar of someone else.
+//!
+//! Every line that is sent to i3bar is written to the recording with the milliseconds since the
+//! start of the recording and a tab before it, e.g. `1048\t[{"full_text":" 10:00 ",...}],`.
+
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::errors::*;
+
+thread_local! {
+    // The output is written on the main thread
+    static RECORDING: RefCell<Option<(File, Instant)>> = const { RefCell::new(None) };
+}
+
+/// Records all following output to the file at `path`
+pub fn start(path: &Path) -> Result<()> {
+    let file = File::create(path).configuration_error("failed to create the recording")?;
+    RECORDING.with(|recording| *recording.borrow_mut() = Some((file, Instant::now())));
+    Ok(())
+}
+
+pub fn record(line: &str) {
+    RECORDING.with(|recording| {
+        if let Some((file, start)) = &mut *recording.borrow_mut() {
+            // Not buffered, so the recording is complete when the bar crashes
+            if let Err(e) = writeln!(file, "{}\t{}", start.elapsed().as_millis(), line) {
+                tracing::error!("failed to write the recording: {}", e);
+                *recording.borrow_mut() = None;
+            }
+        }
+    });
+}
+
+/// Writes the recorded output to stdout with the recorded timing, `speed` times faster. `0`
+/// writes it as fast as possible, e.g. to benchmark i3bar. Stdout is kept open until stdin is
+/// closed, so i3bar keeps showing the last line.
+pub fn replay(path: &Path, speed: f64) -> Result<()> {
+    let file = File::open(path).configuration_error("failed to open the recording")?;
+    let start = Instant::now();
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.configuration_error("failed to read the recording")?;
+        let (time, output) = parse_line(&line).ok_or_else(|| {
+            ConfigurationError(
+                format!("invalid line {} in the recording", number + 1),
+                line.clone(),
+            )
+        })?;
+        if speed > 0. {
+            let due = start + time.div_f64(speed);
+            let now = Instant::now();
+            if due > now {
+                thread::sleep(due - now);
+            }
+        }
+        writeln!(stdout, "{}", output)
+            .and_then(|_| stdout.flush())
+            .internal_error("recording", "failed to write to stdout")?;
+    }
+    drop(stdout);
+    // Click events are ignored
+    io::copy(&mut io::stdin(), &mut io::sink())
+        .internal_error("recording", "failed to read stdin")?;
+    Ok(())
+}
+
+fn parse_line(line: &str) -> Option<(Duration, &str)> {
+    let (time, output) = line.split_once('\t')?;
+    Some((Duration::from_millis(time.parse().ok()?), output))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            parse_line("1500\t[{\"full_text\":\"a\tb\"}],"),
+            Some((Duration::from_millis(1500), "[{\"full_text\":\"a\tb\"}],"))
+        );
+        assert_eq!(parse_line("0\t["), Some((Duration::from_millis(0), "[")));
+        assert_eq!(parse_line("[{}],"), None);
+        assert_eq!(parse_line("soon\t[{}],"), None);
+    }
+}
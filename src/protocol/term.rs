@@ -0,0 +1,132 @@
+//! Rendering of the bar as a single line of text for terminals, tmux or other status lines
+//! (`--output term` and `--output plain`).
+//!
+//! Pango markup is removed. The separators of the theme are drawn like the blocks, the native
+//! separators of i3bar are replaced by `|`.
+
+use std::io::{self, Write};
+use std::os::unix::io::AsRawFd;
+
+use nix::unistd::isatty;
+
+use crate::protocol::i3bar_block::I3BarBlock;
+use crate::themes::Color;
+
+const RESET: &str = "\x1b[0m";
+
+/// The line for `blocks`, with ANSI colors if `colors` is set
+pub fn render(blocks: &[I3BarBlock], colors: bool) -> String {
+    let mut line = String::new();
+    for (i, block) in blocks.iter().enumerate() {
+        let text = match block.markup.as_deref() {
+            Some("pango") => strip_markup(&block.full_text),
+            _ => block.full_text.clone(),
+        };
+        if colors {
+            line.push_str(&ansi(block.color, 38));
+            line.push_str(&ansi(block.background, 48));
+            line.push_str(&text);
+            line.push_str(RESET);
+        } else {
+            line.push_str(&text);
+        }
+        if i + 1 == blocks.len() {
+            break;
+        }
+        if block.separator != Some(false) {
+            line.push_str(" | ");
+        } else if block.separator_block_width.unwrap_or(9) > 0 {
+            line.push(' ');
+        }
+    }
+    line
+}
+
+/// Writes `line`. Terminals show only the latest line, which replaces the previous one.
+pub fn write(line: &str) {
+    let stdout = io::stdout();
+    let tty = isatty(stdout.as_raw_fd()).unwrap_or(false);
+    let mut stdout = stdout.lock();
+    // There is no one to report the error to if stdout is gone
+    let _ = match tty {
+        true => write!(stdout, "\r{}\x1b[K", line),
+        false => writeln!(stdout, "{}", line),
+    }
+    .and_then(|_| stdout.flush());
+}
+
+/// The escape sequence that sets the 24-bit foreground (`38`) or background (`48`) color
+fn ansi(color: Color, layer: u8) -> String {
+    match color {
+        Color::Rgba(r, g, b, a) if a > 0 => format!("\x1b[{};2;{};{};{}m", layer, r, g, b),
+        _ => String::new(),
+    }
+}
+
+/// The text of the pango markup `markup`, without the tags
+fn strip_markup(markup: &str) -> String {
+    let mut text = String::new();
+    let mut rest = markup;
+    while let Some(c) = rest.chars().next() {
+        let end = match c {
+            '<' => rest.find('>').map(|i| i + 1),
+            '&' => rest.find(';').map(|i| i + 1),
+            _ => None,
+        }
+        .unwrap_or_else(|| c.len_utf8());
+        let (token, tail) = rest.split_at(end);
+        match token {
+            _ if c == '<' && end > 1 => {}
+            "&amp;" => text.push('&'),
+            "&lt;" => text.push('<'),
+            "&gt;" => text.push('>'),
+            "&quot;" => text.push('"'),
+            "&apos;" => text.push('\''),
+            _ => match token
+                .strip_prefix("&#")
+                .and_then(|code| code.strip_suffix(';'))
+                .and_then(|code| match code.strip_prefix('x') {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => code.parse().ok(),
+                })
+                .and_then(char::from_u32)
+            {
+                Some(c) => text.push(c),
+                None => text.push_str(token),
+            },
+        }
+        rest = tail;
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markup() {
+        assert_eq!(
+            strip_markup("<span foreground=\"#FF0000\">a &amp; b</span> &#39;c&#x27; 1 < 2"),
+            "a & b 'c' 1 < 2"
+        );
+    }
+
+    #[test]
+    fn line() {
+        let block = |text: &str, separator| I3BarBlock {
+            full_text: text.to_string(),
+            color: Color::Rgba(0xFF, 0, 0, 0xFF),
+            separator,
+            separator_block_width: None,
+            ..Default::default()
+        };
+        let blocks = [
+            block(" <b>a</b> ", Some(false)),
+            block(" b ", None),
+            block(" c ", None),
+        ];
+        assert_eq!(render(&blocks, false), " a   b  |  c ");
+        assert_eq!(render(&blocks[..1], true), "\x1b[38;2;255;0;0m a \x1b[0m");
+    }
+}
@@ -0,0 +1,109 @@
+//! Output for a custom module of Waybar (`--output waybar`), one JSON object per update:
+//!
+//! ```json
+//! {"text": "<span>50%</span>", "tooltip": "volume: 50", "class": "warning", "percentage": 50}
+//! ```
+//!
+//! Every module can run a single block with `--block`, or the whole bar as one module.
+
+use std::collections::BTreeMap;
+
+use serde_json::{json, Map};
+
+use crate::blocks::Block;
+use crate::protocol::i3bar_block::I3BarBlock;
+use crate::registry;
+use crate::util::escape_pango_text;
+use crate::widgets::State;
+
+/// The object for the visible `blocks`
+pub fn render(blocks: &[Box<dyn Block>]) -> String {
+    let mut texts = Vec::new();
+    let mut tooltips = Vec::new();
+    let mut state = State::Idle;
+    let mut percentage = None;
+    for block in blocks {
+        let widgets = block.view();
+        if widgets.is_empty() {
+            continue;
+        }
+        texts.push(
+            widgets
+                .iter()
+                .map(|widget| markup(&widget.get_data()))
+                .collect::<String>()
+                .trim()
+                .to_string(),
+        );
+        state = widgets
+            .iter()
+            .map(|widget| widget.state())
+            .chain(Some(state))
+            .max_by_key(|&state| severity(state))
+            .unwrap();
+
+        // Sorted, so the order doesn't change between updates
+        let values: BTreeMap<String, String> =
+            registry::plain_values(block.id()).into_iter().collect();
+        let tooltip = values
+            .iter()
+            .map(|(name, value)| format!("{}: {}", name, escape_pango_text(value)))
+            .collect::<Vec<String>>()
+            .join("\n");
+        if !tooltip.is_empty() {
+            tooltips.push(tooltip);
+        }
+        if percentage.is_none() {
+            let recorded = registry::values(block.id());
+            percentage = registry::shown(block.id())
+                .iter()
+                .find_map(|name| recorded.get(name)?.as_percents());
+        }
+    }
+
+    let mut object = Map::new();
+    object.insert("text".to_string(), json!(texts.join(" ")));
+    if !tooltips.is_empty() {
+        object.insert("tooltip".to_string(), json!(tooltips.join("\n\n")));
+    }
+    object.insert("class".to_string(), json!(class(state)));
+    if let Some(percentage) = percentage {
+        object.insert("percentage".to_string(), json!(percentage.round() as i64));
+    }
+    serde_json::Value::Object(object).to_string()
+}
+
+/// The object for widgets without a block, e.g. an error
+pub fn render_widgets(widgets: &[I3BarBlock]) -> String {
+    let text: String = widgets.iter().map(markup).collect();
+    json!({ "text": text.trim() }).to_string()
+}
+
+/// Waybar always parses the text as pango markup
+fn markup(widget: &I3BarBlock) -> String {
+    match widget.markup.as_deref() {
+        Some("pango") => widget.full_text.clone(),
+        _ => escape_pango_text(&widget.full_text),
+    }
+}
+
+fn severity(state: State) -> u8 {
+    match state {
+        State::Idle => 0,
+        State::Info => 1,
+        State::Good => 2,
+        State::Warning => 3,
+        State::Critical => 4,
+    }
+}
+
+/// The CSS class of the module
+fn class(state: State) -> &'static str {
+    match state {
+        State::Idle => "idle",
+        State::Info => "info",
+        State::Good => "good",
+        State::Warning => "warning",
+        State::Critical => "critical",
+    }
+}
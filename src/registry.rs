@@ -0,0 +1,439 @@
+//! What the bar knows about each block besides its widgets, by block id.
+//!
+//! The values of a block's placeholders are recorded when it renders its format, but only if
+//! something uses them: an `export` name, click commands, a parent block, or an output or
+//! interface that shows all values. The registry also tracks the updates of each block, which are
+//! extra placeholders in every format, the expanded formats, and the placeholders that the
+//! presentation mode hides. All blocks live on the main thread, so thread locals are sufficient,
+//! except for the heartbeats that the threads of the blocks send while they wait for events.
+
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use lazy_static::lazy_static;
+use serde_derive::Deserialize;
+
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::{FormatMapKey, FormatTemplate};
+use crate::rules;
+
+thread_local! {
+    static CURRENT_BLOCK: Cell<Option<usize>> = const { Cell::new(None) };
+    static VALUES: RefCell<HashMap<usize, HashMap<String, Value>>> = RefCell::new(HashMap::new());
+    // The blocks whose values are used, and whether the values of all blocks are
+    static RECORDED: RefCell<HashSet<usize>> = RefCell::new(HashSet::new());
+    static RECORD_ALL: Cell<bool> = const { Cell::new(false) };
+    static NAMES: RefCell<HashMap<String, usize>> = RefCell::new(HashMap::new());
+    static METADATA: RefCell<HashMap<usize, Metadata>> = RefCell::new(HashMap::new());
+    // The expanded formats of the blocks, and whether they are in use right now
+    static EXPANDED: RefCell<HashMap<usize, (FormatTemplate, bool)>> = RefCell::new(HashMap::new());
+    static SENSITIVE: RefCell<HashMap<usize, Sensitive>> = RefCell::new(HashMap::new());
+    // The placeholders of the format that was rendered the last time, in order
+    static SHOWN: RefCell<HashMap<usize, Vec<String>>> = RefCell::new(HashMap::new());
+}
+
+/// How often the threads of the blocks send heartbeats while they wait for events
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+
+lazy_static! {
+    /// When the threads of the blocks that wait for events were last seen alive, for the watchdog
+    static ref HEARTBEATS: Mutex<HashMap<usize, Instant>> = Mutex::new(HashMap::new());
+}
+
+/// Set by `i3status-rs presentation`, sensitive placeholders are masked
+static PRESENTING: AtomicBool = AtomicBool::new(false);
+
+pub fn is_presenting() -> bool {
+    PRESENTING.load(Ordering::Relaxed)
+}
+
+pub fn set_presenting(presenting: bool) {
+    PRESENTING.store(presenting, Ordering::Relaxed);
+}
+
+/// The `sensitive` option of a block: either `true` for all text placeholders, or the names of the
+/// placeholders
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Sensitive {
+    Text(bool),
+    Placeholders(Vec<String>),
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Metadata {
+    /// Start of the update that is running right now
+    pub started: Option<Instant>,
+    pub last_success: Option<Instant>,
+    pub error_count: u64,
+    pub interval: Option<Duration>,
+}
+
+/// Runs `f` with the values recorded in the meantime belonging to the block `id`
+pub fn with_block<T>(id: usize, f: impl FnOnce() -> T) -> T {
+    // Blocks can contain other blocks, so the previous block is restored afterwards
+    let previous = CURRENT_BLOCK.with(|current| current.replace(Some(id)));
+    let result = f();
+    CURRENT_BLOCK.with(|current| current.set(previous));
+    result
+}
+
+/// The block that is running right now, if any
+pub fn current_block() -> Option<usize> {
+    CURRENT_BLOCK.with(Cell::get)
+}
+
+/// Records the values of the block `id` from now on, e.g. for its click commands
+pub fn record_values(id: usize) {
+    RECORDED.with(|recorded| recorded.borrow_mut().insert(id));
+}
+
+/// Records the values of all blocks from now on, for the outputs and the interfaces that show
+/// them. Returns whether they weren't recorded before.
+pub fn record_all_values() -> bool {
+    !RECORD_ALL.with(|all| all.replace(true))
+}
+
+fn is_recorded(id: usize) -> bool {
+    RECORD_ALL.with(Cell::get) || RECORDED.with(|recorded| recorded.borrow().contains(&id))
+}
+
+/// Records the values of the current block, if they are used. Blocks that don't render a format
+/// can call this directly.
+pub fn record(vars: &HashMap<impl FormatMapKey, Value>) {
+    if let Some(id) = CURRENT_BLOCK.with(Cell::get).filter(|&id| is_recorded(id)) {
+        VALUES.with(|values| {
+            // Blocks render the same placeholders every time, so the names and the strings of the
+            // last values are reused
+            let mut values = values.borrow_mut();
+            let recorded = values.entry(id).or_default();
+            recorded.retain(|name, _| vars.contains_key(name.as_str()));
+            for (name, value) in vars {
+                match recorded.get_mut(name.borrow()) {
+                    Some(recorded) => recorded.clone_from(value),
+                    None => {
+                        recorded.insert(name.borrow().to_string(), value.clone());
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Records the placeholders the current block shows
+pub fn record_shown<'a>(names: impl Iterator<Item = &'a str> + Clone) {
+    if let Some(id) = CURRENT_BLOCK.with(Cell::get) {
+        SHOWN.with(|shown| {
+            let mut shown = shown.borrow_mut();
+            let shown = shown.entry(id).or_default();
+            if !shown.iter().map(String::as_str).eq(names.clone()) {
+                *shown = names.map(str::to_string).collect();
+            }
+        });
+    }
+}
+
+/// The placeholders the block `id` showed the last time, in the order of its format
+pub fn shown(id: usize) -> Vec<String> {
+    SHOWN.with(|shown| shown.borrow().get(&id).cloned().unwrap_or_default())
+}
+
+pub fn update_started(id: usize) {
+    METADATA.with(|metadata| {
+        metadata.borrow_mut().entry(id).or_default().started = Some(Instant::now());
+    });
+}
+
+/// `interval` is the time until the next update, if there is one
+pub fn update_succeeded(id: usize, interval: Option<Duration>) {
+    METADATA.with(|metadata| {
+        let mut metadata = metadata.borrow_mut();
+        let metadata = metadata.entry(id).or_default();
+        metadata.last_success = metadata.started.take();
+        metadata.interval = interval;
+    });
+}
+
+/// Tells the watchdog that the thread of the block `id` that waits for its events still runs. This
+/// can be called from any thread.
+pub fn heartbeat(id: usize) {
+    HEARTBEATS.lock().unwrap().insert(id, Instant::now());
+}
+
+/// The last heartbeat of the block `id`, if it has a thread that sends them
+pub fn last_heartbeat(id: usize) -> Option<Instant> {
+    HEARTBEATS.lock().unwrap().get(&id).copied()
+}
+
+pub fn update_failed(id: usize) {
+    METADATA.with(|metadata| {
+        let mut metadata = metadata.borrow_mut();
+        let metadata = metadata.entry(id).or_default();
+        metadata.started = None;
+        metadata.error_count += 1;
+    });
+}
+
+/// The placeholders `block_id`, `update_age`, `error_count` and `interval` of the current block
+pub fn metadata(name: &str) -> Option<Value> {
+    let id = CURRENT_BLOCK.with(Cell::get)?;
+    METADATA.with(|metadata| {
+        let metadata = metadata.borrow();
+        let metadata = metadata.get(&id);
+        Some(match name {
+            "block_id" => Value::from_integer(id as i64),
+            // Values rendered during an update are fresh
+            "update_age" => {
+                let age = match metadata {
+                    Some(Metadata {
+                        started: None,
+                        last_success: Some(last_success),
+                        ..
+                    }) => last_success.elapsed().as_secs(),
+                    _ => 0,
+                };
+                Value::from_integer(age as i64).seconds()
+            }
+            "error_count" => Value::from_integer(metadata.map_or(0, |m| m.error_count) as i64),
+            "interval" => Value::from_integer(
+                metadata
+                    .and_then(|m| m.interval)
+                    .map_or(0, |interval| interval.as_secs()) as i64,
+            )
+            .seconds(),
+            _ => return None,
+        })
+    })
+}
+
+/// The updates of the block `id` so far
+pub fn block_metadata(id: usize) -> Metadata {
+    METADATA.with(|metadata| metadata.borrow().get(&id).cloned().unwrap_or_default())
+}
+
+/// All values recorded by the block `id` the last time
+pub fn values(id: usize) -> HashMap<String, Value> {
+    VALUES.with(|values| values.borrow().get(&id).cloned().unwrap_or_default())
+}
+
+/// The values recorded by the block `id` the last time as plain text. Sensitive values are masked
+/// in the presentation mode, like on the bar.
+pub fn plain_values(id: usize) -> HashMap<String, String> {
+    values(id)
+        .into_iter()
+        .map(|(name, value)| {
+            let masked = with_block(id, || is_masked(&name, &value));
+            let value = if masked { value.masked() } else { value };
+            (name, value.plain())
+        })
+        .collect()
+}
+
+/// The last value of `name` recorded by the block `id`
+pub fn get(id: usize, name: &str) -> Option<Value> {
+    VALUES.with(|values| values.borrow().get(&id)?.get(name).cloned())
+}
+
+/// Makes the values of the block `id` available under `name`
+pub fn set_name(id: usize, name: &str) -> Result<()> {
+    NAMES.with(|names| {
+        let mut names = names.borrow_mut();
+        if names.contains_key(name) {
+            return Err(ConfigurationError(
+                "export".to_string(),
+                format!("the name '{}' is used by more than one block", name),
+            ));
+        }
+        names.insert(name.to_string(), id);
+        record_values(id);
+        Ok(())
+    })
+}
+
+/// Sets the format that replaces the formats of the block `id` while it is expanded. Blocks can set
+/// a default in `new`, which is replaced by the `format_expanded` option.
+pub fn set_expanded_format(id: usize, format: FormatTemplate) {
+    EXPANDED.with(|expanded| expanded.borrow_mut().insert(id, (format, false)));
+}
+
+pub fn has_expanded_format(id: usize) -> bool {
+    EXPANDED.with(|expanded| expanded.borrow().contains_key(&id))
+}
+
+pub fn set_expanded(id: usize, is_expanded: bool) {
+    EXPANDED.with(|expanded| {
+        if let Some((_, state)) = expanded.borrow_mut().get_mut(&id) {
+            *state = is_expanded;
+        }
+    });
+}
+
+pub fn is_expanded(id: usize) -> bool {
+    EXPANDED.with(|expanded| matches!(expanded.borrow().get(&id), Some((_, true))))
+}
+
+/// Runs `f` with the format to render instead of the formats of the current block, if it is
+/// expanded
+pub fn with_expanded_format<T>(f: impl FnOnce(&FormatTemplate) -> T) -> Option<T> {
+    let id = CURRENT_BLOCK.with(Cell::get)?;
+    EXPANDED.with(|expanded| match expanded.borrow().get(&id) {
+        Some((format, true)) => Some(f(format)),
+        _ => None,
+    })
+}
+
+pub fn set_sensitive(id: usize, sensitive: Sensitive) {
+    SENSITIVE.with(|map| map.borrow_mut().insert(id, sensitive));
+}
+
+pub fn is_sensitive(id: usize) -> bool {
+    SENSITIVE.with(|map| match map.borrow().get(&id) {
+        Some(Sensitive::Text(text)) => *text,
+        Some(Sensitive::Placeholders(names)) => !names.is_empty(),
+        None => false,
+    })
+}
+
+/// Whether the placeholder `name` of the current block is hidden right now
+pub fn is_masked(name: &str, value: &Value) -> bool {
+    if !is_presenting() {
+        return false;
+    }
+    let id = match CURRENT_BLOCK.with(Cell::get) {
+        Some(id) => id,
+        None => return false,
+    };
+    SENSITIVE.with(|map| match map.borrow().get(&id) {
+        Some(Sensitive::Text(text)) => *text && value.is_text(),
+        Some(Sensitive::Placeholders(names)) => names.iter().any(|n| n == name),
+        None => false,
+    })
+}
+
+/// Removes the names the block `id` was exported as, and returns them
+pub fn take_names(id: usize) -> Vec<String> {
+    NAMES.with(|names| {
+        let mut names = names.borrow_mut();
+        let taken: Vec<String> = names
+            .iter()
+            .filter(|(_, named)| **named == id)
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in &taken {
+            names.remove(name);
+        }
+        taken
+    })
+}
+
+/// Removes everything about the block `id`, which was removed by a reload of the configuration
+pub fn forget(id: usize) {
+    VALUES.with(|values| values.borrow_mut().remove(&id));
+    RECORDED.with(|recorded| recorded.borrow_mut().remove(&id));
+    NAMES.with(|names| names.borrow_mut().retain(|_, named| *named != id));
+    METADATA.with(|metadata| metadata.borrow_mut().remove(&id));
+    EXPANDED.with(|expanded| expanded.borrow_mut().remove(&id));
+    SENSITIVE.with(|map| map.borrow_mut().remove(&id));
+    SHOWN.with(|shown| shown.borrow_mut().remove(&id));
+    HEARTBEATS.lock().unwrap().remove(&id);
+    rules::forget(id);
+}
+
+/// The id of the block that was exported as `name`
+pub fn id_by_name(name: &str) -> Option<usize> {
+    NAMES.with(|names| names.borrow().get(name).copied())
+}
+
+/// The `export` name of the block `id`, if it has one
+pub fn name_of(id: usize) -> Option<String> {
+    NAMES.with(|names| {
+        names
+            .borrow()
+            .iter()
+            .find(|(_, named)| **named == id)
+            .map(|(name, _)| name.clone())
+    })
+}
+
+/// The last value of `name` recorded by the block that was exported as `block`
+pub fn get_by_name(block: &str, name: &str) -> Option<Value> {
+    get(id_by_name(block)?, name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_blocks() {
+        record_values(1);
+        record_values(2);
+        with_block(1, || {
+            record(&map!("a" => Value::from_integer(1)));
+            with_block(2, || record(&map!("a" => Value::from_integer(2))));
+            record(&map!("b" => Value::from_integer(3)));
+        });
+        record(&map!("c" => Value::from_integer(4)));
+
+        assert!(get(1, "a").is_none());
+        assert_eq!(get(1, "b").and_then(|v| v.as_f64()), Some(3.0));
+        assert_eq!(get(2, "a").and_then(|v| v.as_f64()), Some(2.0));
+
+        set_name(2, "inner").unwrap();
+        assert!(set_name(1, "inner").is_err());
+        assert_eq!(
+            get_by_name("inner", "a").and_then(|v| v.as_f64()),
+            Some(2.0)
+        );
+        assert!(get_by_name("outer", "a").is_none());
+    }
+
+    #[test]
+    fn unused_values() {
+        with_block(6, || record(&map!("a" => Value::from_integer(1))));
+        assert!(get(6, "a").is_none());
+        set_name(6, "used").unwrap();
+        with_block(6, || record(&map!("a" => Value::from_integer(1))));
+        assert!(get_by_name("used", "a").is_some());
+    }
+
+    #[test]
+    fn metadata_placeholders() {
+        with_block(3, || {
+            update_started(3);
+            update_failed(3);
+            update_started(3);
+            assert_eq!(metadata("update_age").and_then(|v| v.as_f64()), Some(0.0));
+            update_succeeded(3, Some(Duration::from_secs(5)));
+            assert_eq!(metadata("block_id").and_then(|v| v.as_f64()), Some(3.0));
+            assert_eq!(metadata("error_count").and_then(|v| v.as_f64()), Some(1.0));
+            assert_eq!(metadata("interval").and_then(|v| v.as_f64()), Some(5.0));
+            assert!(metadata("foo").is_none());
+        });
+        assert!(metadata("block_id").is_none());
+    }
+
+    #[test]
+    fn masking() {
+        let ssid = Value::from_string("home".to_string());
+        let strength = Value::from_integer(80);
+        set_sensitive(4, Sensitive::Text(true));
+        set_sensitive(5, Sensitive::Placeholders(vec!["strength".to_string()]));
+        with_block(4, || {
+            assert!(!is_masked("ssid", &ssid));
+            set_presenting(true);
+            assert!(is_masked("ssid", &ssid));
+            assert!(!is_masked("strength", &strength));
+        });
+        with_block(5, || {
+            assert!(!is_masked("ssid", &ssid));
+            assert!(is_masked("strength", &strength));
+        });
+        assert!(!is_masked("ssid", &ssid));
+        set_presenting(false);
+    }
+}
@@ -0,0 +1,298 @@
+//! Reloading of the configuration while the bar is running, on `SIGHUP`, when the configuration
+//! file changes, or with `i3status-rs reload`.
+//!
+//! Blocks whose configuration didn't change are kept, so they don't lose their state, e.g. a
+//! running pomodoro. Removed blocks are dropped, and new or changed blocks are created with new
+//! ids. The theme and the icons are replaced in place for all blocks, unless the switches of the
+//! theme changed. Changes of the other top-level options restart the bar, since all blocks depend
+//! on them, and so do changes of the includes, since the files to watch change.
+
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use inotify::{Inotify, WatchMask};
+
+use crate::blocks::{create_block, Block};
+use crate::config::{Config, SharedConfig};
+use crate::errors::*;
+use crate::includes::{self, Source};
+use crate::metrics;
+use crate::registry;
+use crate::scheduler::Task;
+use crate::topics;
+
+pub enum Reload {
+    Blocks {
+        /// The ids of the blocks that were created
+        created: Vec<usize>,
+        /// Whether the theme or the icons were replaced
+        restyled: bool,
+    },
+    Restart,
+}
+
+/// The top-level options that are replaced in place
+const STYLE: &[&str] = &["theme", "icons", "icons_format"];
+
+pub struct Reloader {
+    path: PathBuf,
+    /// The block of `--block`
+    only: Option<String>,
+    /// The configuration without the blocks
+    top_level: toml::Value,
+    sources: Vec<Source>,
+    next_id: usize,
+}
+
+impl Reloader {
+    /// `blocks` is the number of blocks created from the file at `path`
+    pub fn new(path: &Path, only: Option<&str>, blocks: usize) -> Result<Self> {
+        let included = includes::read(path)?;
+        Ok(Reloader {
+            path: path.to_path_buf(),
+            only: only.map(String::from),
+            top_level: top_level(&included.value),
+            sources: included.sources,
+            next_id: blocks,
+        })
+    }
+
+    /// Replaces `config` and the blocks with the ones from the file. If this fails, the previous
+    /// blocks keep running.
+    pub fn reload(
+        &mut self,
+        config: &mut Config,
+        blocks: &mut Vec<Box<dyn Block>>,
+        shared_config: &SharedConfig,
+        tx_update_request: &Sender<Task>,
+    ) -> Result<Reload> {
+        let included = includes::read(&self.path)?;
+        let new_top_level = top_level(&included.value);
+        if without_style(&new_top_level) != without_style(&self.top_level)
+            || theme_switches(&new_top_level) != theme_switches(&self.top_level)
+            || included.sources != self.sources
+        {
+            return Ok(Reload::Restart);
+        }
+        let restyled = new_top_level != self.top_level;
+        let mut new_config: Config = included
+            .value
+            .try_into()
+            .configuration_error("failed to parse the configuration")?;
+        new_config
+            .apply_conditions()
+            .configuration_error("invalid condition of a block")?;
+        if let Some(block) = &self.only {
+            new_config.retain_block(block);
+        }
+        // Before the blocks are created, so the new ones get the new style as well
+        if restyled {
+            shared_config.replace_style(&new_config);
+        }
+
+        // The blocks that are kept, by their position in the new configuration
+        let mut unused: Vec<bool> = vec![true; blocks.len()];
+        let kept: Vec<Option<usize>> = new_config
+            .blocks
+            .iter()
+            .map(|block| {
+                let old = (0..config.blocks.len())
+                    .find(|&old| unused[old] && config.blocks[old] == *block)?;
+                unused[old] = false;
+                Some(old)
+            })
+            .collect();
+        let removed: Vec<usize> = (0..blocks.len())
+            .filter(|&old| unused[old])
+            .map(|old| blocks[old].id())
+            .collect();
+
+        // Changed blocks can be exported under the same names as before
+        let names: Vec<(usize, Vec<String>)> = removed
+            .iter()
+            .map(|&id| (id, registry::take_names(id)))
+            .collect();
+        let mut created = Vec::new();
+        for (position, (name, block_config)) in new_config.blocks.iter().enumerate() {
+            if kept[position].is_some() {
+                continue;
+            }
+            let id = self.next_id + created.len();
+            let block = create_block(
+                id,
+                name,
+                block_config.clone(),
+                shared_config.clone(),
+                tx_update_request.clone(),
+            );
+            match block {
+                Ok(block) => created.push((position, block)),
+                Err(e) => {
+                    if restyled {
+                        shared_config.replace_style(config);
+                    }
+                    for (_, block) in created {
+                        registry::forget(block.id());
+                    }
+                    registry::forget(id);
+                    for (id, names) in names {
+                        for name in names {
+                            registry::set_name(id, &name)?;
+                        }
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        self.next_id += created.len();
+
+        for &id in &removed {
+            registry::forget(id);
+            metrics::forget(id);
+            topics::forget(id);
+        }
+        let mut old: Vec<Option<Box<dyn Block>>> = blocks.drain(..).map(Some).collect();
+        let mut created = created.into_iter();
+        let mut ids = Vec::new();
+        for (position, kept) in kept.into_iter().enumerate() {
+            match kept {
+                Some(old_position) => blocks.push(old[old_position].take().unwrap()),
+                None => {
+                    let (created_position, block) = created.next().unwrap();
+                    debug_assert_eq!(created_position, position);
+                    ids.push(block.id());
+                    blocks.push(block);
+                }
+            }
+        }
+        *config = new_config;
+        self.top_level = new_top_level;
+        Ok(Reload::Blocks {
+            created: ids,
+            restyled,
+        })
+    }
+
+    /// Replaces the block at `position` with a new one from its configuration, e.g. because it's
+    /// stuck, and returns the id of the new block. If this fails, the block keeps running.
+    pub fn restart_block(
+        &mut self,
+        position: usize,
+        config: &Config,
+        blocks: &mut [Box<dyn Block>],
+        shared_config: &SharedConfig,
+        tx_update_request: &Sender<Task>,
+    ) -> Result<usize> {
+        let old = blocks[position].id();
+        let (name, block_config) = &config.blocks[position];
+        // The new block is exported under the same names
+        let names = registry::take_names(old);
+        let id = self.next_id;
+        match create_block(
+            id,
+            name,
+            block_config.clone(),
+            shared_config.clone(),
+            tx_update_request.clone(),
+        ) {
+            Ok(block) => {
+                self.next_id += 1;
+                registry::forget(old);
+                metrics::forget(old);
+                topics::forget(old);
+                blocks[position] = block;
+                Ok(id)
+            }
+            Err(e) => {
+                registry::forget(id);
+                for name in names {
+                    registry::set_name(old, &name)?;
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Starts a thread that notifies `tx_reload` every time the configuration file or one of its
+    /// includes is written. The directories are watched, since many editors replace the files.
+    pub fn watch(&self, tx_reload: Sender<()>) -> Result<()> {
+        let mut notify = Inotify::init()
+            .internal_error("reload", "failed to start watching the configuration")?;
+        let mut watches = Vec::new();
+        for (i, source) in self.sources.iter().enumerate() {
+            let dir = match &source.dir {
+                dir if dir.as_os_str().is_empty() => Path::new("."),
+                dir => dir,
+            };
+            match notify.add_watch(dir, WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO) {
+                Ok(watch) => watches.push((watch, source.clone())),
+                Err(e) if i == 0 => {
+                    return Err(ConfigurationError(
+                        "failed to watch the directory of the configuration file".to_string(),
+                        e.to_string(),
+                    ))
+                }
+                // The directory of an include that doesn't exist
+                Err(_) => {}
+            }
+        }
+
+        thread::Builder::new()
+            .name("reload".into())
+            .spawn(move || {
+                let mut buffer = [0; 1024];
+                loop {
+                    let events = match notify.read_events_blocking(&mut buffer) {
+                        Ok(events) => events,
+                        Err(_) => return,
+                    };
+                    if events.into_iter().any(|event| {
+                        let name = match event.name {
+                            Some(name) => name.to_string_lossy(),
+                            None => return false,
+                        };
+                        watches
+                            .iter()
+                            .any(|(watch, source)| *watch == event.wd && source.matches(&name))
+                    }) {
+                        // Editors write several times when saving
+                        thread::sleep(Duration::from_millis(100));
+                        tx_reload.send(()).unwrap();
+                    }
+                }
+            })
+            .map(|_| ())
+            .internal_error("reload", "failed to start thread")
+    }
+}
+
+fn top_level(config: &toml::Value) -> toml::Value {
+    let mut config = config.clone();
+    if let Some(table) = config.as_table_mut() {
+        table.remove("block");
+    }
+    config
+}
+
+fn without_style(top_level: &toml::Value) -> toml::Value {
+    let mut top_level = top_level.clone();
+    if let Some(table) = top_level.as_table_mut() {
+        for key in STYLE {
+            table.remove(*key);
+        }
+    }
+    top_level
+}
+
+/// The switches of the theme run in threads that were started with the bar
+fn theme_switches(top_level: &toml::Value) -> (Option<&toml::Value>, Option<&toml::Value>) {
+    let theme = top_level.get("theme");
+    (
+        theme.and_then(|theme| theme.get("switch")),
+        theme
+            .and_then(|theme| theme.get("night"))
+            .and_then(|night| night.get("switch")),
+    )
+}
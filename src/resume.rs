@@ -0,0 +1,168 @@
+//! Updates of the blocks that show something about the time right after a resume from suspend or
+//! a jump of the clock, e.g. the `time` block.
+//!
+//! The updates are scheduled with the monotonic clock, which stands still during a suspend, so
+//! a block that updates every 10 minutes would show data of before the suspend for up to 10 more
+//! minutes. The resume is signalled by logind with `PrepareForSleep`. When the time is set, and on
+//! a resume without logind, the kernel cancels a timer on the real time clock that was armed with
+//! `TFD_TIMER_CANCEL_ON_SET`, so nothing has to be polled.
+
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use dbus::ffidisp::BusType;
+use lazy_static::lazy_static;
+use nix::libc;
+
+use crate::dbus_connections::{self, Event};
+use crate::errors::*;
+use crate::scheduler::Task;
+
+/// Both logind and the timer report a resume, which only needs one update
+const DEBOUNCE: Duration = Duration::from_secs(10);
+
+/// Not in the `libc` crate yet, see timerfd_create(2)
+const TFD_TIMER_CANCEL_ON_SET: libc::c_int = 1 << 1;
+
+lazy_static! {
+    /// The ids of the blocks that are updated on a resume
+    static ref BLOCKS: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+    /// When the blocks were updated the last time
+    static ref UPDATED: Mutex<Option<Instant>> = Mutex::new(None);
+}
+
+fn update_all(tx_update_request: &Sender<Task>, reason: &str) {
+    {
+        let mut updated = UPDATED.lock().unwrap();
+        if updated.is_some_and(|updated| updated.elapsed() < DEBOUNCE) {
+            return;
+        }
+        *updated = Some(Instant::now());
+    }
+    let blocks = BLOCKS.lock().unwrap().clone();
+    tracing::info!("{}, updating {} blocks", reason, blocks.len());
+    for id in blocks {
+        let _ = tx_update_request.send(Task {
+            id,
+            update_time: Instant::now(),
+        });
+    }
+}
+
+/// A timerfd on the real time clock that never expires, but is cancelled whenever the clock is
+/// set
+struct ClockChanges(libc::c_int);
+
+impl ClockChanges {
+    fn new() -> io::Result<Self> {
+        let fd = unsafe { libc::timerfd_create(libc::CLOCK_REALTIME, libc::TFD_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self(fd))
+    }
+
+    /// Blocks until the real time clock is set, or jumps because of a resume
+    fn wait(&self) -> io::Result<()> {
+        let never = libc::itimerspec {
+            it_interval: libc::timespec {
+                tv_sec: 0,
+                tv_nsec: 0,
+            },
+            // The timer has to be armed, but with a time that never comes
+            it_value: libc::timespec {
+                tv_sec: libc::time_t::MAX / 2,
+                tv_nsec: 0,
+            },
+        };
+        let flags = libc::TFD_TIMER_ABSTIME | TFD_TIMER_CANCEL_ON_SET;
+        if unsafe { libc::timerfd_settime(self.0, flags, &never, std::ptr::null_mut()) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut expirations = 0u64;
+        loop {
+            let read = unsafe {
+                libc::read(
+                    self.0,
+                    &mut expirations as *mut u64 as *mut libc::c_void,
+                    std::mem::size_of::<u64>(),
+                )
+            };
+            let error = io::Error::last_os_error();
+            match error.raw_os_error() {
+                _ if read >= 0 => return Ok(()),
+                Some(libc::ECANCELED) => return Ok(()),
+                Some(libc::EINTR) => continue,
+                _ => return Err(error),
+            }
+        }
+    }
+}
+
+impl Drop for ClockChanges {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.0) };
+    }
+}
+
+/// Starts watching for resumes and jumps of the clock, which request updates of the blocks `ids`.
+/// After the configuration was reloaded, calling it again replaces the blocks.
+pub fn watch(ids: Vec<usize>, tx_update_request: Sender<Task>) -> Result<()> {
+    static STARTED: AtomicBool = AtomicBool::new(false);
+    let empty = ids.is_empty();
+    *BLOCKS.lock().unwrap() = ids;
+    if empty || STARTED.swap(true, Ordering::Relaxed) {
+        return Ok(());
+    }
+
+    let tx = tx_update_request.clone();
+    dbus_connections::watch(
+        "resume",
+        BusType::System,
+        vec!["type='signal',\
+              interface='org.freedesktop.login1.Manager',\
+              member='PrepareForSleep'"
+            .to_string()],
+        move |event| {
+            // `false` once the machine woke up again
+            if let Event::Signal(message) = event {
+                if message.get1::<bool>() == Some(false) {
+                    update_all(&tx, "resumed from suspend");
+                }
+            }
+        },
+    )?;
+
+    let changes = ClockChanges::new().internal_error("resume", "failed to create timerfd")?;
+    thread::Builder::new()
+        .name("clock jumps".into())
+        .spawn(move || loop {
+            match changes.wait() {
+                Ok(()) => update_all(&tx_update_request, "the clock jumped"),
+                Err(e) => {
+                    tracing::warn!("jumps of the clock aren't noticed anymore: {}", e);
+                    return;
+                }
+            }
+        })
+        .map(|_| ())
+        .internal_error("resume", "failed to start thread")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clock_changes() {
+        // Arming the timer works, and it isn't cancelled for nothing
+        let changes = ClockChanges::new().unwrap();
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        thread::spawn(move || tx.send(changes.wait().is_ok()));
+        assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+    }
+}
@@ -0,0 +1,363 @@
+//! The `rules` of blocks, which change the state, the icon, the format or the visibility of a
+//! block depending on its placeholders, like
+//!
+//! ```toml
+//! [[block.rules]]
+//! when = "utilization > 90 and temperature > 80"
+//! state = "Critical"
+//! ```
+//!
+//! Conditions compare placeholders with `==`, `!=`, `<`, `<=`, `>` and `>=`, or match them with
+//! a regex with `=~`. A placeholder on its own holds if it isn't empty or zero. Comparisons are
+//! joined with `and` and `or`, where `and` binds tighter.
+//!
+//! A placeholder and a value are compared as numbers if both are numbers. Otherwise every
+//! operator compares the plain text of the placeholder with the value lexicographically, so
+//! `name < "m"` holds for names that sort before `m`. A comparison with a placeholder that the
+//! block doesn't have doesn't hold.
+//!
+//! The first rule of a block whose condition holds is applied to the widget whose text was
+//! rendered.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use regex::Regex;
+use serde::de::{self, Deserialize, Deserializer};
+use serde_derive::Deserialize;
+
+use crate::errors::*;
+use crate::formatting::value::Value;
+use crate::formatting::{FormatMapKey, FormatTemplate};
+use crate::registry;
+use crate::widgets::State;
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub when: Condition,
+    /// Replaces the state the block sets
+    pub state: Option<State>,
+    /// The name of an icon that replaces the icon of the block
+    pub icon: Option<String>,
+    /// Rendered instead of the format of the block
+    pub format: Option<FormatTemplate>,
+    /// Hides the widget
+    #[serde(default)]
+    pub hide: bool,
+}
+
+/// What the rule that matched changes about a widget
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Effect {
+    pub state: Option<State>,
+    /// The icon itself, not its name
+    pub icon: Option<String>,
+    pub hide: bool,
+}
+
+/// Comparisons joined with `or`, of comparisons joined with `and`
+#[derive(Debug, Clone)]
+pub struct Condition {
+    any: Vec<Vec<Comparison>>,
+}
+
+#[derive(Debug, Clone)]
+struct Comparison {
+    name: String,
+    test: Test,
+}
+
+#[derive(Debug, Clone)]
+enum Test {
+    IsSet,
+    Compare(Operator, Operand),
+    Matches(Regex),
+}
+
+/// Compares numbers by their value and other text lexicographically, see the module
+/// documentation
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+#[derive(Debug, Clone)]
+struct Operand {
+    text: String,
+    number: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Operator(String),
+}
+
+fn is_operator(c: char) -> bool {
+    matches!(c, '=' | '!' | '<' | '>' | '~')
+}
+
+fn tokenize(condition: &str) -> StdResult<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = condition.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' || c == '\'' {
+            chars.next();
+            let mut text = String::new();
+            loop {
+                match chars.next() {
+                    Some(next) if next == c => break,
+                    Some(next) => text.push(next),
+                    None => return Err(format!("missing {} in '{}'", c, condition)),
+                }
+            }
+            tokens.push(Token::Quoted(text));
+        } else {
+            let operator = is_operator(c);
+            let mut word = String::new();
+            while let Some(&next) = chars.peek() {
+                if next.is_whitespace()
+                    || next == '"'
+                    || next == '\''
+                    || is_operator(next) != operator
+                {
+                    break;
+                }
+                word.push(next);
+                chars.next();
+            }
+            tokens.push(match operator {
+                true => Token::Operator(word),
+                false => Token::Word(word),
+            });
+        }
+    }
+    Ok(tokens)
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(condition: &str) -> StdResult<Self, String> {
+        let mut tokens = tokenize(condition)?.into_iter().peekable();
+        let mut any = vec![Vec::new()];
+        loop {
+            let name = match tokens.next() {
+                // Like in formats, `{name}` is the placeholder `name`
+                Some(Token::Word(word)) => word
+                    .trim_start_matches('{')
+                    .trim_end_matches('}')
+                    .to_string(),
+                _ => return Err(format!("expected a placeholder in '{}'", condition)),
+            };
+            let test = match tokens.next_if(|token| matches!(token, Token::Operator(_))) {
+                Some(Token::Operator(operator)) => match tokens.next() {
+                    Some(Token::Word(operand)) | Some(Token::Quoted(operand)) => {
+                        parse_test(&operator, operand)?
+                    }
+                    _ => return Err(format!("expected a value after '{}'", operator)),
+                },
+                _ => Test::IsSet,
+            };
+            any.last_mut().unwrap().push(Comparison { name, test });
+            if !next_comparison(&mut tokens, &mut any, condition)? {
+                break;
+            }
+        }
+        Ok(Condition { any })
+    }
+}
+
+fn parse_test(operator: &str, operand: String) -> StdResult<Test, String> {
+    let operator = match operator {
+        "=~" => {
+            return Regex::new(&operand)
+                .map(Test::Matches)
+                .map_err(|e| format!("invalid regex '{}': {}", operand, e))
+        }
+        "==" => Operator::Equal,
+        "!=" => Operator::NotEqual,
+        "<" => Operator::Less,
+        "<=" => Operator::LessOrEqual,
+        ">" => Operator::Greater,
+        ">=" => Operator::GreaterOrEqual,
+        other => return Err(format!("unknown operator '{}'", other)),
+    };
+    Ok(Test::Compare(
+        operator,
+        Operand {
+            number: operand.parse().ok(),
+            text: operand,
+        },
+    ))
+}
+
+/// Consumes the `and` or `or` after a comparison, false at the end of the condition
+fn next_comparison(
+    tokens: &mut impl Iterator<Item = Token>,
+    any: &mut Vec<Vec<Comparison>>,
+    condition: &str,
+) -> StdResult<bool, String> {
+    match tokens.next() {
+        None => Ok(false),
+        Some(Token::Word(word)) if word == "and" => Ok(true),
+        Some(Token::Word(word)) if word == "or" => {
+            any.push(Vec::new());
+            Ok(true)
+        }
+        Some(_) => Err(format!("expected 'and' or 'or' in '{}'", condition)),
+    }
+}
+
+impl<'de> Deserialize<'de> for Condition {
+    fn deserialize<D>(deserializer: D) -> StdResult<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+impl Operator {
+    fn holds(self, ordering: Option<std::cmp::Ordering>) -> bool {
+        use std::cmp::Ordering::*;
+        match (self, ordering) {
+            (Operator::NotEqual, ordering) => ordering != Some(Equal),
+            (_, None) => false,
+            (Operator::Equal, Some(ordering)) => ordering == Equal,
+            (Operator::Less, Some(ordering)) => ordering == Less,
+            (Operator::LessOrEqual, Some(ordering)) => ordering != Greater,
+            (Operator::Greater, Some(ordering)) => ordering == Greater,
+            (Operator::GreaterOrEqual, Some(ordering)) => ordering != Less,
+        }
+    }
+}
+
+impl Comparison {
+    fn holds(&self, value: &Value) -> bool {
+        match &self.test {
+            Test::IsSet => match value.as_f64() {
+                Some(number) => number != 0.,
+                None => !value.plain().is_empty(),
+            },
+            Test::Matches(regex) => regex.is_match(&value.plain()),
+            Test::Compare(operator, operand) => match (value.as_f64(), operand.number) {
+                (Some(number), Some(operand)) => operator.holds(number.partial_cmp(&operand)),
+                _ => operator.holds(Some(value.plain().as_str().cmp(operand.text.as_str()))),
+            },
+        }
+    }
+}
+
+impl Condition {
+    /// Whether the condition holds for the placeholders `vars`, and those every block has
+    pub fn holds(&self, vars: &HashMap<impl FormatMapKey, Value>) -> bool {
+        self.any.iter().any(|all| {
+            all.iter().all(|comparison| {
+                match vars
+                    .get(comparison.name.as_str())
+                    .cloned()
+                    .or_else(|| registry::metadata(&comparison.name))
+                {
+                    Some(value) => comparison.holds(&value),
+                    None => false,
+                }
+            })
+        })
+    }
+}
+
+thread_local! {
+    // The rules of the blocks, each with what it changes about a widget
+    static RULES: RefCell<HashMap<usize, Vec<(Rule, Effect)>>> = RefCell::new(HashMap::new());
+    // What the rule that matched the last rendering changes, until a widget of the block takes it
+    static MATCHED: RefCell<Option<(usize, Effect)>> = const { RefCell::new(None) };
+}
+
+/// Sets the rules of the block `id`, each with what it changes about a widget
+pub fn set(id: usize, rules: Vec<(Rule, Effect)>) {
+    RULES.with(|map| map.borrow_mut().insert(id, rules));
+}
+
+/// Removes the rules of the block `id`
+pub fn forget(id: usize) {
+    RULES.with(|map| map.borrow_mut().remove(&id));
+}
+
+/// Applies the first rule of the current block whose condition holds for `vars`. Runs `f` with the
+/// format of the rule if it has one.
+pub fn with_rule<T>(
+    vars: &HashMap<impl FormatMapKey, Value>,
+    f: impl FnOnce(&FormatTemplate) -> T,
+) -> Option<T> {
+    let id = registry::current_block()?;
+    RULES.with(|map| {
+        let map = map.borrow();
+        let rule = map.get(&id)?.iter().find(|(rule, _)| rule.when.holds(vars));
+        MATCHED.with(|matched| {
+            *matched.borrow_mut() = rule.map(|(_, effect)| (id, effect.clone()));
+        });
+        rule?.0.format.as_ref().map(f)
+    })
+}
+
+/// Takes what the rule that matched the last rendering of the current block changes, if one did
+pub fn take_effect() -> Option<Effect> {
+    let id = registry::current_block()?;
+    MATCHED.with(|matched| match matched.borrow_mut().take() {
+        Some((matched_id, effect)) if matched_id == id => Some(effect),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conditions() {
+        let vars = map!(
+            "utilization" => Value::from_float(95.).percents(),
+            "temperature" => Value::from_integer(70).degrees(),
+            "name" => Value::from_string("nginx".to_string()),
+            "empty" => Value::from_string(String::new()),
+            "zero" => Value::from_integer(0),
+        );
+        let holds = |condition: &str| condition.parse::<Condition>().unwrap().holds(&vars);
+        assert!(holds("utilization > 90"));
+        assert!(!holds("utilization > 90 and temperature > 80"));
+        assert!(holds("temperature > 80 or utilization>=95"));
+        assert!(holds("zero or {utilization} > 90 and name == nginx"));
+        assert!(holds("name == 'nginx'"));
+        assert!(holds("name != \"apache\""));
+        assert!(holds("name =~ ^ng"));
+        assert!(!holds("name < 5"));
+        assert!(holds("name > apache"));
+        assert!(holds("name <= nginx"));
+        assert!(!holds("name >= 'nginx-1'"));
+        assert!(holds("name"));
+        assert!(!holds("empty"));
+        assert!(!holds("zero"));
+        assert!(!holds("missing"));
+        assert!(!holds("missing != 1"));
+
+        assert!("".parse::<Condition>().is_err());
+        assert!("utilization >".parse::<Condition>().is_err());
+        assert!("utilization => 5".parse::<Condition>().is_err());
+        assert!("a b".parse::<Condition>().is_err());
+        assert!("name == 'nginx".parse::<Condition>().is_err());
+        assert!("name =~ (".parse::<Condition>().is_err());
+    }
+}
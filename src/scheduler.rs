@@ -1,11 +1,11 @@
 use crate::blocks::Update;
 use std::cmp;
-use std::collections::BinaryHeap;
+use std::collections::{BinaryHeap, HashSet};
 use std::fmt;
 use std::thread;
 use std::time::{Duration, Instant};
 
-use crate::blocks::Block;
+use crate::blocks::{self, Block};
 use crate::errors::*;
 
 #[derive(Debug, Clone)]
@@ -42,21 +42,47 @@ impl cmp::Ord for Task {
 
 pub struct UpdateScheduler {
     pub schedule: BinaryHeap<Task>,
+    /// The blocks that weren't updated since the bar started
+    first_updates: HashSet<usize>,
 }
 
 impl UpdateScheduler {
     pub fn new(blocks: &[Box<dyn Block>]) -> UpdateScheduler {
         let mut schedule = BinaryHeap::new();
 
+        // The first updates are one at a time, so the bar is drawn after each block and the
+        // blocks that are done don't wait for the slow ones, which show their `loading_text`. The
+        // blocks that fetch something from the network can take a while, so they are last.
         let now = Instant::now();
-        for block in blocks.iter() {
+        let (local, remote): (Vec<_>, Vec<_>) = blocks
+            .iter()
+            .partition(|block| block.depends_on().is_empty());
+        for (i, block) in local.iter().chain(remote.iter()).enumerate() {
             schedule.push(Task {
                 id: block.id(),
-                update_time: now,
+                update_time: now + Duration::from_micros(i as u64),
             });
         }
 
-        UpdateScheduler { schedule }
+        UpdateScheduler {
+            schedule,
+            first_updates: blocks.iter().map(|block| block.id()).collect(),
+        }
+    }
+
+    /// Whether some blocks weren't updated yet since the bar started
+    pub fn starting(&self) -> bool {
+        !self.first_updates.is_empty()
+    }
+
+    /// Updates the block `id` right away instead of at the time it asked for, e.g. because it
+    /// has to show something sooner
+    pub fn update_now(&mut self, id: usize) {
+        self.schedule.retain(|task| task.id != id);
+        self.schedule.push(Task {
+            id,
+            update_time: Instant::now(),
+        });
     }
 
     pub fn time_to_next_update(&self) -> Option<Duration> {
@@ -104,16 +130,22 @@ impl UpdateScheduler {
         let now = Instant::now();
 
         for task in tasks_next {
-            if let Some(dur) = blocks
-                .get_mut(task.id as usize)
-                .internal_error("scheduler", "could not get required block")?
-                .update()?
-            {
+            self.first_updates.remove(&task.id);
+            // The block was removed by a reload of the configuration
+            let position = match blocks::position(blocks, task.id) {
+                Some(position) => position,
+                None => continue,
+            };
+            tracing::trace!("running the update of block {}", task.id);
+            if let Some(dur) = blocks[position].update()? {
                 match dur {
-                    Update::Every(d) => self.schedule.push(Task {
-                        id: task.id,
-                        update_time: now + d,
-                    }),
+                    Update::Every(d) => {
+                        tracing::trace!("scheduled block {} again in {:?}", task.id, d);
+                        self.schedule.push(Task {
+                            id: task.id,
+                            update_time: now + d,
+                        })
+                    }
                     Update::Once => {} // do not schedule this task again
                 }
             }
@@ -0,0 +1,663 @@
+//! `i3status-rs schema`, a JSON Schema of the configuration for editors, e.g. for Taplo:
+//!
+//! ```toml
+//! #:schema ./i3status-rs.schema.json
+//! ```
+//!
+//! The schema is generated from the serde types of the configuration by deserializing them from a
+//! tracer. Each run of the tracer follows a path of fields and elements to one type and records
+//! what that type asks the tracer for: the fields of a struct, the variants of an enum, a sequence
+//! and so on. Types with their own `Deserialize`, like durations and formats, ask for "any" value,
+//! so they are offered a value of every kind in turn, and the kinds they don't reject with
+//! `invalid_type` are the types of the schema.
+
+use std::cell::RefCell;
+use std::fmt;
+
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor};
+use serde_json::{json, Map, Value as Json};
+
+use crate::config::{Conditions, Config};
+
+/// Types nested deeper than this are left open, the configuration has no recursive types
+const MAX_DEPTH: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Boolean,
+    Integer,
+    Number,
+    String,
+    Array,
+    Object,
+}
+
+const KINDS: [Kind; 6] = [
+    Kind::Boolean,
+    Kind::Integer,
+    Kind::Number,
+    Kind::String,
+    Kind::Array,
+    Kind::Object,
+];
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Boolean => "boolean",
+            Kind::Integer => "integer",
+            Kind::Number => "number",
+            Kind::String => "string",
+            Kind::Array => "array",
+            Kind::Object => "object",
+        }
+    }
+}
+
+/// The steps from a type to one of the types in it
+#[derive(Debug, Clone, Copy)]
+enum Step {
+    Field(&'static str),
+    /// The element of a sequence or an option, the value of a map or the content of a variant
+    Element,
+    Variant(usize),
+}
+
+/// What the type at the end of the path asked for
+#[derive(Debug)]
+enum Node {
+    Kind(Kind),
+    /// Whether the type accepted the kind it was offered
+    Any(bool),
+    Option,
+    Seq,
+    Map,
+    Struct {
+        fields: &'static [&'static str],
+        deny_unknown_fields: bool,
+    },
+    Enum(&'static [&'static str]),
+    UnitVariant,
+    NewtypeVariant,
+}
+
+#[derive(Debug)]
+enum Error {
+    /// The run is over, because the type at the end of the path was recorded or the path doesn't
+    /// exist
+    Stop,
+    InvalidType,
+    UnknownField,
+    Custom(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Custom(message) => f.write_str(message),
+            other => write!(f, "{:?}", other),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        Error::Custom(message.to_string())
+    }
+
+    fn invalid_type(_: de::Unexpected, _: &dyn de::Expected) -> Self {
+        Error::InvalidType
+    }
+
+    fn unknown_field(_: &str, _: &'static [&'static str]) -> Self {
+        Error::UnknownField
+    }
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Clone, Copy)]
+struct Tracer<'a> {
+    path: &'a [Step],
+    /// The kind that is offered to types that ask for any value
+    offer: Kind,
+    node: &'a RefCell<Option<Node>>,
+}
+
+impl<'a> Tracer<'a> {
+    fn at_end(self) -> bool {
+        self.path.is_empty()
+    }
+
+    fn record(self, node: Node) -> Error {
+        *self.node.borrow_mut() = Some(node);
+        Error::Stop
+    }
+
+    fn rest(self) -> Tracer<'a> {
+        Tracer {
+            path: &self.path[1..],
+            ..self
+        }
+    }
+
+    fn primitive(self, kind: Kind) -> Error {
+        match self.at_end() {
+            true => self.record(Node::Kind(kind)),
+            false => Error::Stop,
+        }
+    }
+
+    fn element(self) -> Result<Tracer<'a>> {
+        match self.path.first() {
+            Some(Step::Element) => Ok(self.rest()),
+            _ => Err(Error::Stop),
+        }
+    }
+
+    fn field(self) -> Result<(&'static str, Tracer<'a>)> {
+        match self.path.first() {
+            Some(&Step::Field(field)) => Ok((field, self.rest())),
+            _ => Err(Error::Stop),
+        }
+    }
+
+    fn variant(self) -> Result<(usize, Tracer<'a>)> {
+        match self.path.first() {
+            Some(&Step::Variant(index)) => Ok((index, self.rest())),
+            _ => Err(Error::Stop),
+        }
+    }
+}
+
+macro_rules! primitives {
+    ($($method:ident => $kind:ident,)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+                Err(self.primitive(Kind::$kind))
+            }
+        )*
+    };
+}
+
+impl<'de, 'a> de::Deserializer<'de> for Tracer<'a> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if !self.at_end() {
+            return Err(Error::Stop);
+        }
+        let result = match self.offer {
+            Kind::Boolean => visitor.visit_bool(false),
+            Kind::Integer => visitor.visit_i64(0),
+            Kind::Number => visitor.visit_f64(0.0),
+            Kind::String => visitor.visit_str(""),
+            Kind::Array => visitor.visit_seq(Elements(None)),
+            Kind::Object => visitor.visit_map(Entries {
+                key: None,
+                value: None,
+            }),
+        };
+        // Other errors are about the value, e.g. an empty format, not about its kind
+        let accepted = !matches!(result, Err(Error::InvalidType));
+        Err(self.record(Node::Any(accepted)))
+    }
+
+    primitives! {
+        deserialize_bool => Boolean,
+        deserialize_i8 => Integer,
+        deserialize_i16 => Integer,
+        deserialize_i32 => Integer,
+        deserialize_i64 => Integer,
+        deserialize_u8 => Integer,
+        deserialize_u16 => Integer,
+        deserialize_u32 => Integer,
+        deserialize_u64 => Integer,
+        deserialize_f32 => Number,
+        deserialize_f64 => Number,
+        deserialize_char => String,
+        deserialize_str => String,
+        deserialize_string => String,
+        deserialize_identifier => String,
+        deserialize_bytes => Array,
+        deserialize_byte_buf => Array,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if self.at_end() {
+            return Err(self.record(Node::Option));
+        }
+        visitor.visit_some(self.element()?)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if self.at_end() {
+            return Err(self.record(Node::Seq));
+        }
+        visitor.visit_seq(Elements(Some(self.element()?)))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if self.at_end() {
+            return Err(self.record(Node::Map));
+        }
+        visitor.visit_map(Entries {
+            key: Some(""),
+            value: Some(self.element()?),
+        })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        if self.at_end() {
+            // Structs with `deny_unknown_fields` reject the key before they ask for its value
+            let result = visitor.visit_map(Entries {
+                key: Some("\0"),
+                value: None,
+            });
+            return Err(self.record(Node::Struct {
+                fields,
+                deny_unknown_fields: matches!(result, Err(Error::UnknownField)),
+            }));
+        }
+        let (field, tracer) = self.field()?;
+        visitor.visit_map(Entries {
+            key: Some(field),
+            value: Some(tracer),
+        })
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        if self.at_end() {
+            return Err(self.record(Node::Enum(variants)));
+        }
+        let (index, tracer) = self.variant()?;
+        let name = variants.get(index).ok_or(Error::Stop)?;
+        visitor.visit_enum(Variant { name, tracer })
+    }
+
+    serde::forward_to_deserialize_any! {
+        i128 u128 unit unit_struct ignored_any
+    }
+}
+
+/// The key of a map or a field
+struct Key<'a>(&'a str);
+
+impl<'de, 'a> de::Deserializer<'de> for Key<'a> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_str(self.0)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        // Maps with enum keys, like the buttons of clicks
+        let name = match variants.first() {
+            Some(first) if !variants.contains(&self.0) => first,
+            _ => self.0,
+        };
+        visitor.visit_enum(name.into_deserializer())
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+/// A sequence with the element on the path, or an empty one
+struct Elements<'a>(Option<Tracer<'a>>);
+
+impl<'de, 'a> de::SeqAccess<'de> for Elements<'a> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        match self.0.take() {
+            Some(tracer) => seed.deserialize(tracer).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// A map with one entry, or an empty one
+struct Entries<'a> {
+    key: Option<&'a str>,
+    /// The run ends if the value of a key without one is asked for
+    value: Option<Tracer<'a>>,
+}
+
+impl<'de, 'a> de::MapAccess<'de> for Entries<'a> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        match self.key.take() {
+            Some(key) => seed.deserialize(Key(key)).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        match self.value.take() {
+            Some(tracer) => seed.deserialize(tracer),
+            None => Err(Error::Stop),
+        }
+    }
+}
+
+struct Variant<'a> {
+    name: &'static str,
+    tracer: Tracer<'a>,
+}
+
+impl<'de, 'a> de::EnumAccess<'de> for Variant<'a> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self)> {
+        let value = seed.deserialize(Key(self.name))?;
+        Ok((value, self))
+    }
+}
+
+impl<'de, 'a> de::VariantAccess<'de> for Variant<'a> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        match self.tracer.at_end() {
+            true => Err(self.tracer.record(Node::UnitVariant)),
+            false => Err(Error::Stop),
+        }
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        if self.tracer.at_end() {
+            return Err(self.tracer.record(Node::NewtypeVariant));
+        }
+        seed.deserialize(self.tracer.element()?)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_seq(self.tracer, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        de::Deserializer::deserialize_struct(self.tracer, "", fields, visitor)
+    }
+}
+
+/// Deserializes `T` from a tracer that follows `path`, and returns what the type at its end asked
+/// for. The runs always end with an error, the ones that don't reach the end of the path return
+/// `None`.
+fn run<T: DeserializeOwned>(path: &[Step], offer: Kind) -> Option<Node> {
+    let node = RefCell::new(None);
+    let _ = T::deserialize(Tracer {
+        path,
+        offer,
+        node: &node,
+    });
+    node.into_inner()
+}
+
+fn types(kinds: &[Kind]) -> Json {
+    // Like untagged enums, which take any value and try their variants on it
+    if kinds.len() == KINDS.len() {
+        return json!({});
+    }
+    // Integers are numbers too
+    let names: Vec<&str> = kinds
+        .iter()
+        .filter(|&&kind| kind != Kind::Integer || !kinds.contains(&Kind::Number))
+        .map(|kind| kind.name())
+        .collect();
+    match names.as_slice() {
+        [] => json!({}),
+        [name] => json!({ "type": name }),
+        _ => json!({ "type": names }),
+    }
+}
+
+fn child<T: DeserializeOwned>(path: &mut Vec<Step>, step: Step) -> Json {
+    path.push(step);
+    let schema = schema_at::<T>(path);
+    path.pop();
+    schema
+}
+
+/// The schema of the type at the end of `path` in `T`
+fn schema_at<T: DeserializeOwned>(path: &mut Vec<Step>) -> Json {
+    if path.len() > MAX_DEPTH {
+        return json!({});
+    }
+    match run::<T>(path, Kind::Boolean) {
+        None | Some(Node::UnitVariant) => json!({}),
+        Some(Node::Kind(kind)) => types(&[kind]),
+        Some(Node::Any(_)) => {
+            let kinds: Vec<Kind> = KINDS
+                .iter()
+                .copied()
+                .filter(|&kind| matches!(run::<T>(path, kind), Some(Node::Any(true))))
+                .collect();
+            types(&kinds)
+        }
+        Some(Node::Option) | Some(Node::NewtypeVariant) => child::<T>(path, Step::Element),
+        Some(Node::Seq) => json!({
+            "type": "array",
+            "items": child::<T>(path, Step::Element),
+        }),
+        Some(Node::Map) => json!({
+            "type": "object",
+            "additionalProperties": child::<T>(path, Step::Element),
+        }),
+        Some(Node::Struct {
+            fields,
+            deny_unknown_fields,
+        }) => {
+            let properties: Map<String, Json> = fields
+                .iter()
+                .map(|&field| (field.to_string(), child::<T>(path, Step::Field(field))))
+                .collect();
+            let mut schema = json!({ "type": "object", "properties": properties });
+            if deny_unknown_fields {
+                schema["additionalProperties"] = json!(false);
+            }
+            schema
+        }
+        Some(Node::Enum(variants)) => {
+            // Unit variants are strings, the others tables with the variant as the only key
+            let mut names = Vec::new();
+            let mut schemas = Vec::new();
+            for (index, &name) in variants.iter().enumerate() {
+                path.push(Step::Variant(index));
+                match run::<T>(path, Kind::Boolean) {
+                    Some(Node::UnitVariant) => names.push(name),
+                    _ => schemas.push(json!({
+                        "type": "object",
+                        "properties": { name: schema_at::<T>(path) },
+                        "required": [name],
+                        "additionalProperties": false,
+                    })),
+                }
+                path.pop();
+            }
+            match (names.is_empty(), schemas.len()) {
+                (false, 0) => json!({ "enum": names }),
+                (true, 1) => schemas.remove(0),
+                (true, _) => json!({ "oneOf": schemas }),
+                (false, _) => {
+                    schemas.insert(0, json!({ "enum": names }));
+                    json!({ "oneOf": schemas })
+                }
+            }
+        }
+    }
+}
+
+/// The schema of the type `T`
+pub fn trace<T: DeserializeOwned>() -> Json {
+    schema_at::<T>(&mut Vec::new())
+}
+
+/// The schema of the whole configuration, with the options of every block
+pub fn schema() -> Json {
+    let mut schema = trace::<Config>();
+    schema["$schema"] = json!("http://json-schema.org/draft-07/schema#");
+    schema["title"] = json!("i3status-rust configuration");
+    // The includes are read before the configuration is deserialized
+    schema["properties"]["include"] = json!({
+        "type": ["string", "array"],
+        "items": { "type": "string" },
+    });
+    schema["properties"]["templates"] = json!({
+        "type": "object",
+        "additionalProperties": { "type": "object" },
+    });
+
+    // The options of all blocks, and the conditions under which they are created
+    let mut common = Map::new();
+    for options in [trace::<Conditions>(), crate::blocks::common_schema()] {
+        if let Some(Json::Object(properties)) = options.get("properties") {
+            common.extend(properties.clone());
+        }
+    }
+    let blocks = crate::blocks::block_schemas();
+    let names: Vec<&str> = blocks.iter().map(|(name, _)| *name).collect();
+    let options: Vec<Json> = blocks
+        .into_iter()
+        .map(|(name, block)| {
+            let mut properties = common.clone();
+            properties.insert("block".to_string(), json!({ "const": name }));
+            if let Some(Json::Object(own)) = block.get("properties") {
+                properties.extend(own.clone());
+            }
+            let mut then = json!({ "properties": properties });
+            if let Some(additional) = block.get("additionalProperties") {
+                then["additionalProperties"] = additional.clone();
+            }
+            // The options of blocks with a template are only known once it is expanded
+            json!({
+                "if": {
+                    "properties": { "block": { "const": name } },
+                    "required": ["block"],
+                    "not": { "required": ["template"] },
+                },
+                "then": then,
+            })
+        })
+        .collect();
+    schema["properties"]["block"] = json!({
+        "type": "array",
+        "items": {
+            "type": "object",
+            "properties": {
+                "block": { "enum": names },
+                "template": { "type": "string" },
+            },
+            "anyOf": [{ "required": ["block"] }, { "required": ["template"] }],
+            "allOf": options,
+        },
+    });
+    schema
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_derive::Deserialize;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    #[allow(dead_code)]
+    #[derive(Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Mode {
+        On,
+        Off,
+        Command(String),
+    }
+
+    #[allow(dead_code)]
+    #[derive(Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct Options {
+        name: String,
+        count: Option<u32>,
+        modes: Vec<Mode>,
+        #[serde(deserialize_with = "crate::de::deserialize_duration")]
+        interval: Duration,
+        env: HashMap<String, toml::Value>,
+    }
+
+    #[test]
+    fn tracing() {
+        assert_eq!(
+            trace::<Options>(),
+            json!({
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string" },
+                    "count": { "type": "integer" },
+                    "modes": {
+                        "type": "array",
+                        "items": {
+                            "oneOf": [
+                                { "enum": ["on", "off"] },
+                                {
+                                    "type": "object",
+                                    "properties": { "command": { "type": "string" } },
+                                    "required": ["command"],
+                                    "additionalProperties": false,
+                                },
+                            ],
+                        },
+                    },
+                    "interval": { "type": ["number", "object"] },
+                    "env": { "type": "object", "additionalProperties": {} },
+                },
+                "additionalProperties": false,
+            })
+        );
+    }
+}
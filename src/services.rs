@@ -0,0 +1,216 @@
+//! Services that blocks can depend on with `depends_on`, e.g. so blocks which fetch something from
+//! the internet don't fail while the network is not connected.
+//!
+//! A single thread watches the services for all blocks. The blocks are updated as soon as a service
+//! they depend on becomes ready or goes away, instead of each block running into timeouts on its
+//! own. The thread sleeps until the kernel reports a change of the routes over netlink, and only
+//! polls if it can't listen to those.
+
+use std::fs;
+use std::os::unix::io::RawFd;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use lazy_static::lazy_static;
+use nix::sys::socket::{
+    bind, recv, socket, AddressFamily, MsgFlags, NetlinkAddr, SockAddr, SockFlag, SockProtocol,
+    SockType,
+};
+use serde_derive::Deserialize;
+
+use crate::errors::*;
+use crate::scheduler::Task;
+
+/// How often the services are checked if the changes of the routes can't be watched
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The netlink groups of the changes of the routes, see rtnetlink(7)
+const RTMGRP_IPV4_ROUTE: u32 = 0x40;
+const RTMGRP_IPV6_ROUTE: u32 = 0x400;
+
+static NETWORK: AtomicBool = AtomicBool::new(false);
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Service {
+    /// There is a default route. This works with any network manager, e.g. NetworkManager,
+    /// connman or systemd-networkd.
+    Network,
+}
+
+const SERVICES: &[Service] = &[Service::Network];
+
+impl Service {
+    /// Whether the service was ready when it was checked the last time
+    pub fn is_ready(self) -> bool {
+        self.state().load(Ordering::Relaxed)
+    }
+
+    fn state(self) -> &'static AtomicBool {
+        match self {
+            Service::Network => &NETWORK,
+        }
+    }
+
+    fn check(self) -> bool {
+        match self {
+            Service::Network => {
+                let ipv4 = fs::read_to_string("/proc/net/route").unwrap_or_default();
+                let ipv6 = fs::read_to_string("/proc/net/ipv6_route").unwrap_or_default();
+                has_default_route(&ipv4) || has_default_ipv6_route(&ipv6)
+            }
+        }
+    }
+}
+
+lazy_static! {
+    /// The ids of the blocks with their `depends_on`
+    static ref DEPENDENTS: Mutex<Vec<(usize, Vec<Service>)>> = Mutex::new(Vec::new());
+}
+
+/// Checks the services, and starts a thread that requests updates of the blocks that depend on a
+/// service every time it becomes ready or goes away. `dependents` are the ids of the blocks with
+/// their `depends_on`. After the configuration was reloaded, calling it again replaces the
+/// dependents.
+pub fn watch(
+    dependents: Vec<(usize, Vec<Service>)>,
+    tx_update_request: Sender<Task>,
+) -> Result<()> {
+    static STARTED: AtomicBool = AtomicBool::new(false);
+    if dependents.is_empty() {
+        DEPENDENTS.lock().unwrap().clear();
+        return Ok(());
+    }
+    for service in SERVICES {
+        service.state().store(service.check(), Ordering::Relaxed);
+    }
+    *DEPENDENTS.lock().unwrap() = dependents;
+    if STARTED.swap(true, Ordering::Relaxed) {
+        return Ok(());
+    }
+
+    let routes = route_changes()
+        .map_err(|e| tracing::warn!("polling the routes, as netlink failed: {}", e))
+        .ok();
+    thread::Builder::new()
+        .name("services".into())
+        .spawn(move || loop {
+            match routes {
+                Some(routes) => wait_for_messages(routes),
+                None => thread::sleep(WATCH_INTERVAL),
+            }
+            let changed: Vec<Service> = SERVICES
+                .iter()
+                .copied()
+                .filter(|service| {
+                    let ready = service.check();
+                    service.state().swap(ready, Ordering::Relaxed) != ready
+                })
+                .collect();
+            // Servers that failed while the network was down are likely reachable again
+            if changed.contains(&Service::Network) && Service::Network.is_ready() {
+                crate::http::forget_failures();
+            }
+            for (id, services) in DEPENDENTS.lock().unwrap().iter() {
+                if services.iter().any(|service| changed.contains(service)) {
+                    tx_update_request
+                        .send(Task {
+                            id: *id,
+                            update_time: Instant::now(),
+                        })
+                        .unwrap();
+                }
+            }
+        })
+        .map(|_| ())
+        .internal_error("services", "failed to start thread")
+}
+
+/// A netlink socket that receives a message for every route that is added or removed
+fn route_changes() -> nix::Result<RawFd> {
+    let fd = socket(
+        AddressFamily::Netlink,
+        SockType::Raw,
+        SockFlag::SOCK_CLOEXEC,
+        SockProtocol::NetlinkRoute,
+    )?;
+    let groups = RTMGRP_IPV4_ROUTE | RTMGRP_IPV6_ROUTE;
+    bind(fd, &SockAddr::Netlink(NetlinkAddr::new(0, groups)))?;
+    Ok(fd)
+}
+
+/// Blocks until there are messages on `fd`, and reads them all. Connecting to a network changes
+/// several routes at once, which are checked together after a short while.
+fn wait_for_messages(fd: RawFd) {
+    let mut buffer = [0; 8192];
+    // An error is an overflow of the socket, which is a change as well
+    let _ = recv(fd, &mut buffer, MsgFlags::empty());
+    thread::sleep(Duration::from_millis(100));
+    while recv(fd, &mut buffer, MsgFlags::MSG_DONTWAIT).is_ok() {}
+}
+
+/// Whether the table in the format of `/proc/net/route` contains a default route
+fn has_default_route(table: &str) -> bool {
+    table.lines().skip(1).any(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        // Iface, Destination, Gateway, Flags, RefCnt, Use, Metric, Mask
+        matches!(fields[..], [iface, "00000000", _, _, _, _, _, "00000000", ..] if iface != "lo")
+    })
+}
+
+/// Whether the table in the format of `/proc/net/ipv6_route` contains a default route. The kernel
+/// adds unreachable default routes to `lo`, which are not counted.
+fn has_default_ipv6_route(table: &str) -> bool {
+    table.lines().any(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        // Destination, prefix length, ..., device
+        match fields[..] {
+            [destination, "00", .., iface] => {
+                iface != "lo" && destination.chars().all(|c| c == '0')
+            }
+            _ => false,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn netlink() {
+        let fd = route_changes().unwrap();
+        nix::unistd::close(fd).unwrap();
+    }
+
+    #[test]
+    fn default_routes() {
+        let header =
+            "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT";
+        let local = "eth0\t000200C0\t00000000\t0001\t0\t0\t0\t00FFFFFF\t0\t0\t0";
+        let default = "wlan0\t00000000\t010200C0\t0003\t0\t0\t600\t00000000\t0\t0\t0";
+        assert!(!has_default_route(&format!("{}\n{}\n", header, local)));
+        assert!(has_default_route(&format!(
+            "{}\n{}\n{}\n",
+            header, local, default
+        )));
+
+        let zeros = "00000000000000000000000000000000";
+        let unreachable = format!(
+            "{} 00 {} 00 {} ffffffff 00000001 00000000 00200200       lo",
+            zeros, zeros, zeros
+        );
+        let default = format!(
+            "{} 00 {} 00 fe800000000000000000000000000001 00000400 00000001 00000000 00000003     eth0",
+            zeros, zeros
+        );
+        assert!(!has_default_ipv6_route(&unreachable));
+        assert!(has_default_ipv6_route(&format!(
+            "{}\n{}\n",
+            unreachable, default
+        )));
+    }
+}
@@ -0,0 +1,69 @@
+//! The state of all blocks of a running bar as JSON, for `i3status-rs get-state`.
+//!
+//! External widgets like eww or conky can show what the blocks collected without collecting it
+//! again: the placeholders of every block as text, the widgets it shows and its updates. Sensitive
+//! placeholders are masked in the presentation mode, like on the bar.
+
+use std::time::SystemTime;
+
+use chrono::{DateTime, Local, SecondsFormat};
+use serde_json::{json, Value};
+
+use crate::blocks::Block;
+use crate::config::Config;
+use crate::widgets::I3BarWidget;
+use crate::{profiles, registry, themes, widgets};
+
+/// The texts and the states of `widgets`
+pub fn widgets(widgets: &[&dyn I3BarWidget]) -> Vec<Value> {
+    widgets
+        .iter()
+        .map(|widget| {
+            let data = widget.get_data();
+            json!({
+                "full_text": data.full_text,
+                "short_text": data.short_text,
+                "state": format!("{:?}", widget.state()),
+            })
+        })
+        .collect()
+}
+
+fn time(time: SystemTime) -> String {
+    DateTime::<Local>::from(time).to_rfc3339_opts(SecondsFormat::Millis, false)
+}
+
+/// The state of the bar and of its blocks, in the order of the configuration
+pub fn state(blocks: &[Box<dyn Block>], config: &Config) -> Value {
+    let now = SystemTime::now();
+    let blocks: Vec<_> = blocks
+        .iter()
+        .enumerate()
+        .map(|(position, block)| {
+            let id = block.id();
+            let metadata = registry::block_metadata(id);
+            json!({
+                "position": position,
+                "id": id,
+                "block": config.blocks.get(position).map(|(name, _)| name),
+                "name": registry::name_of(id),
+                "hidden": block.is_hidden(),
+                "placeholders": registry::plain_values(id),
+                "widgets": widgets(&block.view()),
+                "last_update": metadata
+                    .last_success
+                    .map(|last_success| time(now - last_success.elapsed())),
+                "interval_secs": metadata.interval.map(|interval| interval.as_secs_f64()),
+                "error_count": metadata.error_count,
+            })
+        })
+        .collect();
+    json!({
+        "time": time(now),
+        "profile": profiles::active(),
+        "compact": widgets::is_compact(),
+        "presentation": registry::is_presenting(),
+        "light": themes::is_light(),
+        "blocks": blocks,
+    })
+}
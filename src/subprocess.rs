@@ -1,11 +1,98 @@
-use std::io;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{self, Write};
 use std::process::{Command, Stdio};
+use std::rc::Rc;
 use std::thread;
+use std::time::Duration;
+
+use serde_derive::Deserialize;
+
+use crate::deadline;
+use crate::errors::*;
+
+thread_local! {
+    /// Environment variables of the block that is currently running
+    static BLOCK_ENV: RefCell<Rc<Vec<(String, String)>>> = RefCell::new(Rc::new(Vec::new()));
+}
+
+/// A value of a block's `env` option. Secrets can be read from a command or a file instead of
+/// being written into the configuration.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum EnvValue {
+    Plain(String),
+    Command { command: String },
+    File { file: String },
+}
+
+/// Resolves the `env` option of a block. This runs the commands, so it should only be done once.
+pub fn resolve_env(env: &HashMap<String, EnvValue>) -> Result<Vec<(String, String)>> {
+    env.iter()
+        .map(|(key, value)| {
+            let value = match value {
+                EnvValue::Plain(value) => value.clone(),
+                EnvValue::Command { command } => {
+                    let output = Command::new("sh")
+                        .args(["-c", command])
+                        .stdin(Stdio::null())
+                        .output()
+                        .configuration_error(&format!("failed to run the command for ${}", key))?;
+                    if !output.status.success() {
+                        return Err(ConfigurationError(
+                            format!("the command for ${} failed", key),
+                            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+                        ));
+                    }
+                    String::from_utf8_lossy(&output.stdout).trim().to_string()
+                }
+                EnvValue::File { file } => {
+                    let path = shellexpand::full(file)
+                        .configuration_error(&format!("failed to expand file path {}", file))?;
+                    std::fs::read_to_string(&*path)
+                        .configuration_error(&format!("failed to read the file for ${}", key))?
+                        .trim()
+                        .to_string()
+                }
+            };
+            Ok((key.clone(), value))
+        })
+        .collect()
+}
+
+/// Runs `f` with `env` being added to the commands created with `command()` in the meantime
+pub fn with_env<T>(env: &Rc<Vec<(String, String)>>, f: impl FnOnce() -> T) -> T {
+    // Blocks can contain other blocks, so the previous environment is restored afterwards
+    let previous = BLOCK_ENV.with(|current| current.replace(env.clone()));
+    let result = f();
+    BLOCK_ENV.with(|current| current.replace(previous));
+    result
+}
+
+/// Like `Command::new`, but with the environment variables of the current block. During an
+/// update with a deadline, the command is run with `timeout(1)`, see `deadline`.
+pub fn command(program: &str) -> Command {
+    let mut command = match deadline::remaining() {
+        Some(remaining) => {
+            // A duration of 0 would turn the timeout off
+            let remaining = remaining.max(Duration::from_millis(1));
+            let mut command = Command::new("timeout");
+            command
+                .arg("--kill-after=1")
+                .arg(format!("{:.3}s", remaining.as_secs_f64()))
+                .arg(program);
+            command
+        }
+        None => Command::new(program),
+    };
+    BLOCK_ENV.with(|env| command.envs(env.borrow().iter().cloned()));
+    command
+}
 
 /// Spawns a new child process. This closes stdin and stdout, and returns to the caller after the
 /// child has been started, while a background thread waits for the child to exit.
 pub fn spawn_child_async(name: &str, args: &[&str]) -> io::Result<()> {
-    let mut child = Command::new(name)
+    let mut child = command(name)
         .args(args)
         .stdin(Stdio::null())
         .stdout(Stdio::null())
@@ -16,3 +103,43 @@ pub fn spawn_child_async(name: &str, args: &[&str]) -> io::Result<()> {
         .unwrap();
     Ok(())
 }
+
+/// Like `spawn_child_async`, but `input` is written to the stdin of the child
+pub fn spawn_child_async_with_input(name: &str, args: &[&str], input: String) -> io::Result<()> {
+    let mut child = command(name)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()?;
+    let mut stdin = child.stdin.take();
+    thread::Builder::new()
+        .name("subprocess".into())
+        .spawn(move || {
+            if let Some(ref mut stdin) = stdin {
+                // The child doesn't have to read its input
+                let _ = stdin.write_all(input.as_bytes());
+            }
+            // Closes stdin, so the child sees the end of the input
+            drop(stdin);
+            child.wait()
+        })
+        .unwrap();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn deadline() {
+        let started = Instant::now();
+        let status = deadline::with_deadline(started + Duration::from_millis(100), || {
+            command("sh").args(["-c", "sleep 5 | cat"]).status()
+        })
+        .unwrap();
+        assert!(!status.success());
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+}
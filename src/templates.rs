@@ -0,0 +1,139 @@
+//! Templates of blocks, for blocks that are configured several times with a few differences:
+//!
+//! ```toml
+//! [templates.disk]
+//! block = "disk_space"
+//! path = "${path}"
+//! format = "${name:-disk} {available}"
+//!
+//! [[block]]
+//! template = "disk"
+//! path = "/home"
+//! name = "home"
+//! ```
+//!
+//! The options of a `[[block]]` with a `template` that appear as `${NAME}` in the template are its
+//! parameters. They are replaced in the template, where a string that is only a parameter gets
+//! the value of the parameter, e.g. an integer, and `${NAME:-default}` the default if the block
+//! doesn't have the parameter. The other options are added to the template or override its
+//! options. Variables of the template without a parameter are environment variables, as in the
+//! rest of the configuration.
+
+use toml::value::Table;
+use toml::Value;
+
+use crate::errors::*;
+use crate::includes::{interpolate, variables};
+
+/// Replaces the blocks with a `template` by their templates, and removes the templates
+pub fn expand(value: &mut Value) -> Result<()> {
+    let table = match value.as_table_mut() {
+        Some(table) => table,
+        None => return Ok(()),
+    };
+    let templates = match table.remove("templates") {
+        None => Table::new(),
+        Some(Value::Table(templates)) => templates,
+        Some(_) => {
+            return Err(ConfigurationError(
+                "'templates' must be a table of blocks".to_string(),
+                "e.g. [templates.disk]".to_string(),
+            ))
+        }
+    };
+    let blocks = match table.get_mut("block") {
+        Some(Value::Array(blocks)) => blocks,
+        _ => return Ok(()),
+    };
+    for block in blocks {
+        let arguments = match block {
+            Value::Table(arguments) if arguments.contains_key("template") => arguments,
+            _ => continue,
+        };
+        let name = match arguments.remove("template") {
+            Some(Value::String(name)) => name,
+            _ => {
+                return Err(ConfigurationError(
+                    "invalid block".to_string(),
+                    "'template' must be the name of a template".to_string(),
+                ))
+            }
+        };
+        let template = match templates.get(&name) {
+            Some(Value::Table(template)) => template,
+            _ => {
+                return Err(ConfigurationError(
+                    "invalid block".to_string(),
+                    format!("there is no template '{}'", name),
+                ))
+            }
+        };
+        *block = instantiate(template, std::mem::take(arguments));
+    }
+    Ok(())
+}
+
+fn instantiate(template: &Table, arguments: Table) -> Value {
+    let mut instance = Value::Table(template.clone());
+    let names = variables(&instance);
+    let (parameters, options): (Table, Table) = arguments
+        .into_iter()
+        .partition(|(name, _)| names.contains(name));
+    interpolate(&mut instance, &parameters);
+    if let Value::Table(instance) = &mut instance {
+        instance.extend(options);
+    }
+    instance
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn instances() {
+        std::env::remove_var("I3RS_TEST_UNSET");
+        let mut value: Value = toml::from_str(
+            r#"
+            [templates.disk]
+            block = "disk_space"
+            path = "${path}"
+            format = "${name:-disk} {available}"
+            unit = "${I3RS_TEST_UNSET:-GB}"
+            interval = 60
+
+            [[block]]
+            template = "disk"
+            path = "/home"
+            name = "home"
+            interval = 10
+
+            [[block]]
+            template = "disk"
+            path = "/"
+            warning = 5.0
+
+            [[block]]
+            block = "time"
+            "#,
+        )
+        .unwrap();
+        expand(&mut value).unwrap();
+        assert!(value.get("templates").is_none());
+        let home = &value["block"][0];
+        assert_eq!(home["block"].as_str(), Some("disk_space"));
+        assert_eq!(home["path"].as_str(), Some("/home"));
+        assert_eq!(home["format"].as_str(), Some("home {available}"));
+        assert_eq!(home["interval"].as_integer(), Some(10));
+        assert_eq!(home["unit"].as_str(), Some("GB"));
+        assert!(home.get("name").is_none());
+        let root = &value["block"][1];
+        assert_eq!(root["format"].as_str(), Some("disk {available}"));
+        assert_eq!(root["interval"].as_integer(), Some(60));
+        assert_eq!(root["warning"].as_float(), Some(5.0));
+        assert_eq!(value["block"][2]["block"].as_str(), Some("time"));
+
+        let mut unknown: Value = toml::from_str("[[block]]\ntemplate = \"net\"").unwrap();
+        assert!(expand(&mut unknown).is_err());
+    }
+}
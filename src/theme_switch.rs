@@ -0,0 +1,393 @@
+//! Switching between the dark and the light variant of the theme at runtime, and dimming the
+//! theme at night.
+
+use std::f64::consts::PI;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use chrono::{DateTime, Local, NaiveTime, TimeZone, Utc};
+use crossbeam_channel::Sender;
+use dbus::arg::{RefArg, Variant};
+use dbus::ffidisp::{BusType, Connection};
+use dbus::Message;
+use inotify::{Inotify, WatchMask};
+use serde_derive::Deserialize;
+
+use crate::dbus_connections::{self, Event};
+use crate::errors::*;
+use crate::subprocess::command;
+use crate::themes;
+
+const NAME: &str = "theme.switch";
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "source", rename_all = "lowercase", deny_unknown_fields)]
+pub enum ThemeSwitch {
+    /// Light during the day. The day is either given by fixed times or computed from the
+    /// location.
+    Time {
+        sunrise: Option<String>,
+        sunset: Option<String>,
+        latitude: Option<f64>,
+        longitude: Option<f64>,
+    },
+    /// A file containing either `dark` or `light`
+    File { path: String },
+    /// The `color-scheme` setting of the XDG desktop portal
+    Portal,
+    /// The period of gammastep, which is light during the daytime
+    Gammastep,
+}
+
+/// What a switch changes
+#[derive(Debug, Clone, Copy)]
+pub enum Target {
+    /// The light variant of the theme during the day
+    Light,
+    /// The dimmed theme during the night
+    Night,
+}
+
+impl Target {
+    fn is_day(self) -> bool {
+        match self {
+            Target::Light => themes::is_light(),
+            Target::Night => !themes::is_night(),
+        }
+    }
+
+    fn set_day(self, day: bool) {
+        match self {
+            Target::Light => themes::set_light(day),
+            Target::Night => themes::set_night(!day),
+        }
+    }
+}
+
+impl ThemeSwitch {
+    /// Applies the current preference to `target` and starts a thread that follows its changes.
+    /// `tx_redraw` is notified every time the theme is switched.
+    pub fn start(self, target: Target, tx_redraw: Sender<()>) -> Result<()> {
+        match self {
+            ThemeSwitch::Time {
+                sunrise,
+                sunset,
+                latitude,
+                longitude,
+            } => {
+                let day = match (sunrise, sunset, latitude, longitude) {
+                    (Some(sunrise), Some(sunset), None, None) => {
+                        Day::Fixed(parse_time(&sunrise)?, parse_time(&sunset)?)
+                    }
+                    (None, None, Some(latitude), Some(longitude)) => {
+                        Day::Location(latitude, longitude)
+                    }
+                    _ => return Err(ConfigurationError(
+                        NAME.to_string(),
+                        "either 'sunrise' and 'sunset' or 'latitude' and 'longitude' must be set"
+                            .to_string(),
+                    )),
+                };
+                target.set_day(day.is_light_at(Local::now()));
+                spawn(move || loop {
+                    thread::sleep(Duration::from_secs(60));
+                    switch_to(target, day.is_light_at(Local::now()), &tx_redraw);
+                })
+            }
+            ThemeSwitch::File { path } => {
+                let path = PathBuf::from(
+                    shellexpand::full(&path)
+                        .map_err(|e| {
+                            ConfigurationError(
+                                NAME.to_string(),
+                                format!("Failed to expand file path {}: {}", &path, e),
+                            )
+                        })?
+                        .to_string(),
+                );
+                // Editors and scripts often replace the file instead of writing to it, so the
+                // directory is watched.
+                let dir = path
+                    .parent()
+                    .filter(|dir| !dir.as_os_str().is_empty())
+                    .unwrap_or_else(|| Path::new("."));
+                let mut notify = Inotify::init().configuration_error("failed to start inotify")?;
+                notify
+                    .add_watch(
+                        dir,
+                        WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO | WatchMask::CREATE,
+                    )
+                    .configuration_error(&format!("failed to watch {}", dir.display()))?;
+                target.set_day(read_preference(&path));
+                spawn(move || {
+                    let mut buffer = [0; 1024];
+                    loop {
+                        let events = notify
+                            .read_events_blocking(&mut buffer)
+                            .expect("Error while reading inotify events");
+                        if events.count() > 0 {
+                            switch_to(target, read_preference(&path), &tx_redraw);
+                        }
+                        // Avoid update spam.
+                        thread::sleep(Duration::from_millis(250))
+                    }
+                })
+            }
+            ThemeSwitch::Portal => {
+                let c = dbus_connections::session()?;
+                // The portal might not be running, stay dark in that case
+                target.set_day(read_portal(&c).unwrap_or(false));
+                dbus_connections::watch(
+                    "theme_switch",
+                    BusType::Session,
+                    vec![
+                        "type='signal',interface='org.freedesktop.portal.Settings',member='SettingChanged'"
+                            .to_string(),
+                    ],
+                    move |event| match event {
+                        // The setting may have changed while the bus was away
+                        Event::Connected => {
+                            if let Some(day) =
+                                dbus_connections::session().ok().and_then(|c| read_portal(&c))
+                            {
+                                switch_to(target, day, &tx_redraw);
+                            }
+                        }
+                        Event::Signal(msg) => {
+                            if let Ok((namespace, key, value)) =
+                                msg.read3::<&str, &str, Variant<Box<dyn RefArg>>>()
+                            {
+                                if namespace == "org.freedesktop.appearance"
+                                    && key == "color-scheme"
+                                {
+                                    switch_to(target, value.as_u64() == Some(2), &tx_redraw);
+                                }
+                            }
+                        }
+                    },
+                )
+            }
+            ThemeSwitch::Gammastep => {
+                // gammastep has no interface to follow its period, it is computed again
+                // instead
+                target.set_day(read_gammastep()?);
+                spawn(move || loop {
+                    thread::sleep(Duration::from_secs(60));
+                    if let Ok(day) = read_gammastep() {
+                        switch_to(target, day, &tx_redraw);
+                    }
+                })
+            }
+        }
+    }
+}
+
+fn spawn<F>(f: F) -> Result<()>
+where
+    F: FnOnce() + Send + 'static,
+{
+    thread::Builder::new()
+        .name("theme_switch".into())
+        .spawn(f)
+        .map(|_| ())
+        .internal_error(NAME, "failed to start thread")
+}
+
+fn switch_to(target: Target, day: bool, tx_redraw: &Sender<()>) {
+    if target.is_day() != day {
+        target.set_day(day);
+        tx_redraw.send(()).unwrap();
+    }
+}
+
+fn parse_time(time: &str) -> Result<NaiveTime> {
+    NaiveTime::parse_from_str(time, "%H:%M").configuration_error(&format!(
+        "invalid time '{}', expected the format HH:MM",
+        time
+    ))
+}
+
+/// Missing or unreadable files mean dark
+fn read_preference(path: &Path) -> bool {
+    std::fs::read_to_string(path)
+        .map(|content| content.trim().eq_ignore_ascii_case("light"))
+        .unwrap_or(false)
+}
+
+/// Refer to https://flatpak.github.io/xdg-desktop-portal/#gdbus-org.freedesktop.portal.Settings
+fn read_portal(c: &Connection) -> Option<bool> {
+    let msg = Message::new_method_call(
+        "org.freedesktop.portal.Desktop",
+        "/org/freedesktop/portal/desktop",
+        "org.freedesktop.portal.Settings",
+        "Read",
+    )
+    .ok()?
+    .append2("org.freedesktop.appearance", "color-scheme");
+    let reply = c.send_with_reply_and_block(msg, 1000).ok()?;
+    // The value is wrapped in two variants by older versions of the portal. 0 means no
+    // preference, 1 dark and 2 light.
+    let value: Variant<Box<dyn RefArg>> = reply.read1().ok()?;
+    Some(value.as_u64() == Some(2))
+}
+
+/// Whether gammastep is in its daytime period or more than halfway through the transition to it,
+/// from the output of `gammastep -p`, which prints the period for its configured location
+fn parse_gammastep_period(output: &str) -> Option<bool> {
+    let period = output
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("Period:"))?
+        .trim();
+    match period {
+        "Daytime" => Some(true),
+        "Night" => Some(false),
+        transition => {
+            // e.g. `Transition (63.25% day)`
+            let (_, day) = transition.strip_prefix("Transition")?.split_once('(')?;
+            let (day, _) = day.split_once('%')?;
+            Some(day.trim().parse::<f64>().ok()? >= 50.)
+        }
+    }
+}
+
+fn read_gammastep() -> Result<bool> {
+    let output = command("gammastep")
+        .arg("-p")
+        .output()
+        .configuration_error("failed to run gammastep")?;
+    // The period is printed to stdout, the notices to stderr
+    parse_gammastep_period(&String::from_utf8_lossy(&output.stdout)).ok_or_else(|| {
+        ConfigurationError(
+            NAME.to_string(),
+            format!(
+                "failed to read the period of gammastep: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        )
+    })
+}
+
+enum Day {
+    Fixed(NaiveTime, NaiveTime),
+    /// Latitude and longitude in degrees
+    Location(f64, f64),
+}
+
+impl Day {
+    fn is_light_at<Tz: TimeZone>(&self, now: DateTime<Tz>) -> bool {
+        match *self {
+            Day::Fixed(sunrise, sunset) => {
+                let time = now.time();
+                if sunrise <= sunset {
+                    sunrise <= time && time < sunset
+                } else {
+                    time < sunset || sunrise <= time
+                }
+            }
+            Day::Location(latitude, longitude) => {
+                let now = now.with_timezone(&Utc).timestamp();
+                match sun_times(now, latitude, longitude) {
+                    SunTimes::Regular(sunrise, sunset) => sunrise <= now && now < sunset,
+                    SunTimes::PolarDay => true,
+                    SunTimes::PolarNight => false,
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum SunTimes {
+    /// Unix timestamps
+    Regular(i64, i64),
+    PolarDay,
+    PolarNight,
+}
+
+/// Sunrise and sunset around the solar noon closest to `timestamp`, following
+/// https://en.wikipedia.org/wiki/Sunrise_equation
+fn sun_times(timestamp: i64, latitude: f64, longitude: f64) -> SunTimes {
+    let sin = |deg: f64| (deg * PI / 180.0).sin();
+    let cos = |deg: f64| (deg * PI / 180.0).cos();
+
+    // Days since 2000-01-01 12:00 UTC
+    let julian_day = timestamp as f64 / 86400.0 + 2440587.5;
+    let n = (julian_day - 2451545.0 + 0.0008 + longitude / 360.0).round();
+    let mean_solar_time = n - longitude / 360.0;
+    let anomaly = (357.5291 + 0.98560028 * mean_solar_time) % 360.0;
+    let center = 1.9148 * sin(anomaly) + 0.02 * sin(2.0 * anomaly) + 0.0003 * sin(3.0 * anomaly);
+    let ecliptic_longitude = (anomaly + center + 180.0 + 102.9372) % 360.0;
+    let transit = 2451545.0 + mean_solar_time + 0.0053 * sin(anomaly)
+        - 0.0069 * sin(2.0 * ecliptic_longitude);
+    let declination = (sin(ecliptic_longitude) * sin(23.4397)).asin() * 180.0 / PI;
+    let cos_hour_angle =
+        (sin(-0.833) - sin(latitude) * sin(declination)) / (cos(latitude) * cos(declination));
+
+    if cos_hour_angle < -1.0 {
+        return SunTimes::PolarDay;
+    }
+    if cos_hour_angle > 1.0 {
+        return SunTimes::PolarNight;
+    }
+    let hour_angle = cos_hour_angle.acos() * 180.0 / PI;
+    let to_timestamp = |julian_day: f64| ((julian_day - 2440587.5) * 86400.0) as i64;
+    SunTimes::Regular(
+        to_timestamp(transit - hour_angle / 360.0),
+        to_timestamp(transit + hour_angle / 360.0),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_day() {
+        let day = Day::Fixed(parse_time("07:00").unwrap(), parse_time("19:30").unwrap());
+        assert!(day.is_light_at(Utc.ymd(2021, 6, 1).and_hms(12, 0, 0)));
+        assert!(!day.is_light_at(Utc.ymd(2021, 6, 1).and_hms(20, 0, 0)));
+        assert!(!day.is_light_at(Utc.ymd(2021, 6, 1).and_hms(6, 59, 0)));
+
+        let night_shift = Day::Fixed(parse_time("22:00").unwrap(), parse_time("06:00").unwrap());
+        assert!(night_shift.is_light_at(Utc.ymd(2021, 6, 1).and_hms(23, 0, 0)));
+        assert!(!night_shift.is_light_at(Utc.ymd(2021, 6, 1).and_hms(12, 0, 0)));
+    }
+
+    #[test]
+    fn gammastep_period() {
+        let output = "Location: 52.52 N, 13.40 E\nPeriod: Night\nColor temperature: 4500K\n";
+        assert_eq!(parse_gammastep_period(output), Some(false));
+        assert_eq!(parse_gammastep_period("Period: Daytime\n"), Some(true));
+        assert_eq!(
+            parse_gammastep_period("Period: Transition (63.25% day)\n"),
+            Some(true)
+        );
+        assert_eq!(
+            parse_gammastep_period("Period: Transition (12.00% day)\n"),
+            Some(false)
+        );
+        assert_eq!(parse_gammastep_period("Using provider `manual'.\n"), None);
+    }
+
+    #[test]
+    fn sunrise_equation() {
+        // Berlin on 2021-06-21, sunrise 02:43 and sunset 19:33 UTC
+        let noon = Utc.ymd(2021, 6, 21).and_hms(11, 0, 0).timestamp();
+        match sun_times(noon, 52.52, 13.405) {
+            SunTimes::Regular(sunrise, sunset) => {
+                let sunrise_expected = Utc.ymd(2021, 6, 21).and_hms(2, 43, 0).timestamp();
+                let sunset_expected = Utc.ymd(2021, 6, 21).and_hms(19, 33, 0).timestamp();
+                assert!((sunrise - sunrise_expected).abs() < 300);
+                assert!((sunset - sunset_expected).abs() < 300);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        // North of the arctic circle
+        let solstice = Utc.ymd(2021, 12, 21).and_hms(12, 0, 0).timestamp();
+        assert_eq!(sun_times(solstice, 78.22, 15.65), SunTimes::PolarNight);
+        let solstice = Utc.ymd(2021, 6, 21).and_hms(12, 0, 0).timestamp();
+        assert_eq!(sun_times(solstice, 78.22, 15.65), SunTimes::PolarDay);
+    }
+}
@@ -3,11 +3,14 @@ use std::default::Default;
 use std::fmt;
 use std::ops::Add;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use serde::de::{self, Deserialize, Deserializer, MapAccess, Visitor};
 use serde_derive::Deserialize;
 
 use crate::errors::ToSerdeError;
+use crate::theme_switch::ThemeSwitch;
+use crate::topics;
 use crate::util;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -38,12 +41,24 @@ impl Add for Color {
 impl FromStr for Color {
     type Err = crate::errors::Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use crate::errors::{OptionExt, ResultExtInternal};
         if s == "none" || s.is_empty() {
             Ok(Color::None)
         } else if s == "auto" {
             Ok(Color::Auto)
+        } else if let Some(args) = color_function_args(s, &["hsl", "hsla"]) {
+            let err_msg =
+                "invalid HSL color, expected hsl(<hue>, <saturation>%, <lightness>%[, <alpha>])";
+            let (h, s, l, a) = parse_color_args(&args).internal_error("color parser", err_msg)?;
+            let (r, g, b) = hsl_to_rgb(h, s, l);
+            Ok(Color::from_float_rgba(r, g, b, a))
+        } else if let Some(args) = color_function_args(s, &["oklch"]) {
+            let err_msg =
+                "invalid OKLCH color, expected oklch(<lightness>[%] <chroma> <hue>[ / <alpha>])";
+            let (l, c, h, a) = parse_color_args(&args).internal_error("color parser", err_msg)?;
+            let (r, g, b) = oklch_to_rgb(l, c, h);
+            Ok(Color::from_float_rgba(r, g, b, a))
         } else {
-            use crate::errors::{OptionExt, ResultExtInternal};
             let err_msg = "invaild RGBA color";
             let r = s.get(1..3).internal_error("color parser", err_msg)?;
             let g = s.get(3..5).internal_error("color parser", err_msg)?;
@@ -59,6 +74,89 @@ impl FromStr for Color {
     }
 }
 
+/// Returns the arguments of a CSS-like color function (e.g. `hsl(120, 50%, 50%)`) if `s` is a
+/// call to one of the given functions.
+fn color_function_args(s: &str, names: &[&str]) -> Option<Vec<String>> {
+    let (name, rest) = s.trim().split_once('(')?;
+    if !names.contains(&name.trim().to_lowercase().as_str()) {
+        return None;
+    }
+    let args = rest.strip_suffix(')')?;
+    Some(
+        args.split(|c: char| c == ',' || c == '/' || c.is_whitespace())
+            .filter(|x| !x.is_empty())
+            .map(|x| x.to_string())
+            .collect(),
+    )
+}
+
+/// Parses three mandatory and one optional (alpha) arguments of a color function. Percentages
+/// are converted to fractions, hues may have a `deg` suffix.
+fn parse_color_args(args: &[String]) -> Result<(f64, f64, f64, f64), String> {
+    fn parse_arg(arg: &str) -> Result<f64, String> {
+        let arg = arg.trim_end_matches("deg");
+        match arg.strip_suffix('%') {
+            Some(percents) => percents.parse::<f64>().map(|x| x / 100.),
+            None => arg.parse::<f64>(),
+        }
+        .map_err(|e| format!("'{}': {}", arg, e))
+    }
+
+    if args.len() != 3 && args.len() != 4 {
+        return Err(format!("expected 3 or 4 arguments, got {}", args.len()));
+    }
+    Ok((
+        parse_arg(&args[0])?,
+        parse_arg(&args[1])?,
+        parse_arg(&args[2])?,
+        args.get(3).map(|a| parse_arg(a)).transpose()?.unwrap_or(1.),
+    ))
+}
+
+/// `h` is in degrees, `s` and `l` are in `0..=1`. Returns sRGB components in `0..=1`.
+fn hsl_to_rgb(h: f64, s: f64, l: f64) -> (f64, f64, f64) {
+    let s = s.clamp(0., 1.);
+    let l = l.clamp(0., 1.);
+    let h = h.rem_euclid(360.) / 60.;
+    let c = (1. - (2. * l - 1.).abs()) * s;
+    let x = c * (1. - (h.rem_euclid(2.) - 1.).abs());
+    let m = l - c / 2.;
+    let (r, g, b) = match h as u8 {
+        0 => (c, x, 0.),
+        1 => (x, c, 0.),
+        2 => (0., c, x),
+        3 => (0., x, c),
+        4 => (x, 0., c),
+        _ => (c, 0., x),
+    };
+    (r + m, g + m, b + m)
+}
+
+/// `l` is in `0..=1` (or percents), `c` is the chroma (usually `0..=0.4`) and `h` is in degrees.
+/// Returns sRGB components in `0..=1`, colors outside of the sRGB gamut are clipped.
+///
+/// See https://bottosson.github.io/posts/oklab/
+fn oklch_to_rgb(l: f64, c: f64, h: f64) -> (f64, f64, f64) {
+    let (a, b) = (c * h.to_radians().cos(), c * h.to_radians().sin());
+
+    let l_ = (l + 0.396_337_777_4 * a + 0.215_803_757_3 * b).powi(3);
+    let m_ = (l - 0.105_561_345_8 * a - 0.063_854_172_8 * b).powi(3);
+    let s_ = (l - 0.089_484_177_5 * a - 1.291_485_548_0 * b).powi(3);
+
+    let r = 4.076_741_662_1 * l_ - 3.307_711_591_3 * m_ + 0.230_969_929_2 * s_;
+    let g = -1.268_438_004_6 * l_ + 2.609_757_401_1 * m_ - 0.341_319_396_5 * s_;
+    let b = -0.004_196_086_3 * l_ - 0.703_418_614_7 * m_ + 1.707_614_701_0 * s_;
+
+    fn gamma(x: f64) -> f64 {
+        if x <= 0.003_130_8 {
+            12.92 * x
+        } else {
+            1.055 * x.powf(1. / 2.4) - 0.055
+        }
+    }
+    (gamma(r), gamma(g), gamma(b))
+}
+
 impl<'de> Deserialize<'de> for Color {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -92,6 +190,65 @@ impl Color {
             _ => None,
         }
     }
+
+    fn from_float_rgba(r: f64, g: f64, b: f64, a: f64) -> Self {
+        let to_byte = |x: f64| (x.clamp(0., 1.) * 255.).round() as u8;
+        Color::Rgba(to_byte(r), to_byte(g), to_byte(b), to_byte(a))
+    }
+
+    /// Relative luminance as defined by WCAG 2.0. `None` for non-RGBA colors.
+    pub fn luminance(self) -> Option<f64> {
+        match self {
+            Color::Rgba(r, g, b, _) => {
+                let linear = |x: u8| {
+                    let x = x as f64 / 255.;
+                    if x <= 0.039_28 {
+                        x / 12.92
+                    } else {
+                        ((x + 0.055) / 1.055).powf(2.4)
+                    }
+                };
+                Some(0.2126 * linear(r) + 0.7152 * linear(g) + 0.0722 * linear(b))
+            }
+            _ => None,
+        }
+    }
+
+    /// Black or white, whichever has the better contrast ratio against `self`.
+    /// Non-RGBA colors are returned as is.
+    pub fn contrasting(self) -> Color {
+        match self.luminance() {
+            // The contrast ratios against black and white are equal at L = sqrt(1.05 * 0.05) - 0.05
+            Some(l) if l > 0.179 => Color::Rgba(0, 0, 0, 0xFF),
+            Some(_) => Color::Rgba(0xFF, 0xFF, 0xFF, 0xFF),
+            None => self,
+        }
+    }
+
+    /// Linear interpolation between `self` (t = 0) and `other` (t = 1). If one of the colors
+    /// is not RGBA, `self` is returned.
+    pub fn mix(self, other: Color, t: f64) -> Color {
+        match (self, other) {
+            (Color::Rgba(r1, g1, b1, a1), Color::Rgba(r2, g2, b2, a2)) => {
+                let mix = |x: u8, y: u8| (x as f64 + (y as f64 - x as f64) * t) / 255.;
+                Color::from_float_rgba(mix(r1, r2), mix(g1, g2), mix(b1, b2), mix(a1, a2))
+            }
+            _ => self,
+        }
+    }
+
+    /// Scales the brightness and the saturation of the color by factors between 0 and 1. The
+    /// saturation is reduced by moving the channels towards the luma of the color.
+    pub fn dimmed(self, brightness: f64, saturation: f64) -> Color {
+        match self {
+            Color::Rgba(r, g, b, a) => {
+                let luma = 0.299 * r as f64 + 0.587 * g as f64 + 0.114 * b as f64;
+                let dim = |x: u8| (luma + (x as f64 - luma) * saturation) * brightness / 255.;
+                Color::from_float_rgba(dim(r), dim(g), dim(b), a as f64 / 255.)
+            }
+            _ => self,
+        }
+    }
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -112,6 +269,41 @@ pub struct InternalTheme {
     pub separator_fg: Color,
     pub alternating_tint_bg: Color,
     pub alternating_tint_fg: Color,
+    /// The backgrounds fade from `<state>_bg` to these colors
+    pub idle_bg_gradient: Color,
+    pub info_bg_gradient: Color,
+    pub good_bg_gradient: Color,
+    pub warning_bg_gradient: Color,
+    pub critical_bg_gradient: Color,
+    /// Seconds between swapping the colors of critical widgets
+    pub critical_flash_interval: Option<f64>,
+    /// Seconds after becoming critical until the flashing stops, or 0 to never stop
+    pub critical_flash_duration: f64,
+    /// Pango underline styles (e.g. `single` or `error`), so states differ in more than color
+    pub idle_underline: Option<String>,
+    pub info_underline: Option<String>,
+    pub good_underline: Option<String>,
+    pub warning_underline: Option<String>,
+    pub critical_underline: Option<String>,
+    /// Text appended to the widgets, e.g. a warning sign
+    pub idle_suffix: Option<String>,
+    pub info_suffix: Option<String>,
+    pub good_suffix: Option<String>,
+    pub warning_suffix: Option<String>,
+    pub critical_suffix: Option<String>,
+    /// Colors of the borders of the widgets, `auto` uses the foreground
+    pub idle_border: Color,
+    pub info_border: Color,
+    pub good_border: Color,
+    pub warning_border: Color,
+    pub critical_border: Color,
+    /// Widths of the borders in pixels, i3bar draws 1 pixel wide borders by default
+    pub border_top: Option<usize>,
+    pub border_right: Option<usize>,
+    pub border_bottom: Option<usize>,
+    pub border_left: Option<usize>,
+    /// Pixels between the blocks, if there is no `separator`
+    pub block_gap: Option<usize>,
 }
 
 impl Default for InternalTheme {
@@ -132,45 +324,60 @@ impl Default for InternalTheme {
             separator_fg: Color::None,
             alternating_tint_bg: Color::None,
             alternating_tint_fg: Color::None,
+            idle_bg_gradient: Color::None,
+            info_bg_gradient: Color::None,
+            good_bg_gradient: Color::None,
+            warning_bg_gradient: Color::None,
+            critical_bg_gradient: Color::None,
+            critical_flash_interval: None,
+            critical_flash_duration: 10.,
+            idle_underline: None,
+            info_underline: None,
+            good_underline: None,
+            warning_underline: None,
+            critical_underline: None,
+            idle_suffix: None,
+            info_suffix: None,
+            good_suffix: None,
+            warning_suffix: None,
+            critical_suffix: None,
+            idle_border: Color::None,
+            info_border: Color::None,
+            good_border: Color::None,
+            warning_border: Color::None,
+            critical_border: Color::None,
+            border_top: None,
+            border_right: None,
+            border_bottom: None,
+            border_left: None,
+            block_gap: None,
         }
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct Theme(pub InternalTheme);
-
-impl Default for Theme {
-    fn default() -> Self {
-        Self::from_file("plain").unwrap_or_else(|| Self(InternalTheme::default()))
-    }
-}
-
-impl std::ops::Deref for Theme {
-    type Target = InternalTheme;
-    fn deref(&self) -> &Self::Target {
-        &self.0
-    }
-}
-
-impl std::ops::DerefMut for Theme {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
-    }
-}
-
-impl Theme {
-    pub fn from_file(file: &str) -> Option<Theme> {
-        let file = util::find_file(file, Some("themes"), Some("toml"))?;
-        Some(Theme(util::deserialize_file(&file).ok()?))
-    }
-
-    pub fn apply_overrides(
+impl InternalTheme {
+    fn apply_overrides(
         &mut self,
         overrides: &HashMap<String, String>,
     ) -> Result<(), crate::errors::Error> {
-        if let Some(separator) = overrides.get("separator") {
-            self.separator = Some(separator.clone());
+        macro_rules! apply_string {
+            ($prop:tt) => {
+                if let Some(val) = overrides.get(stringify!($prop)) {
+                    self.$prop = Some(val.clone());
+                }
+            };
         }
+        apply_string!(separator);
+        apply_string!(idle_underline);
+        apply_string!(info_underline);
+        apply_string!(good_underline);
+        apply_string!(warning_underline);
+        apply_string!(critical_underline);
+        apply_string!(idle_suffix);
+        apply_string!(info_suffix);
+        apply_string!(good_suffix);
+        apply_string!(warning_suffix);
+        apply_string!(critical_suffix);
         macro_rules! apply {
             ($prop:tt) => {
                 if let Some(val) = overrides.get(stringify!($prop)) {
@@ -192,8 +399,326 @@ impl Theme {
         apply!(separator_fg);
         apply!(alternating_tint_bg);
         apply!(alternating_tint_fg);
+        apply!(idle_bg_gradient);
+        apply!(info_bg_gradient);
+        apply!(good_bg_gradient);
+        apply!(warning_bg_gradient);
+        apply!(critical_bg_gradient);
+        apply!(idle_border);
+        apply!(info_border);
+        apply!(good_border);
+        apply!(warning_border);
+        apply!(critical_border);
+        let parse_seconds = |key: &str, val: &str| {
+            val.parse::<f64>().map_err(|_| {
+                crate::errors::ConfigurationError(
+                    format!("'{}' must be a number of seconds", key),
+                    val.to_string(),
+                )
+            })
+        };
+        if let Some(val) = overrides.get("critical_flash_interval") {
+            self.critical_flash_interval = Some(parse_seconds("critical_flash_interval", val)?);
+        }
+        if let Some(val) = overrides.get("critical_flash_duration") {
+            self.critical_flash_duration = parse_seconds("critical_flash_duration", val)?;
+        }
+        macro_rules! apply_pixels {
+            ($prop:tt) => {
+                if let Some(val) = overrides.get(stringify!($prop)) {
+                    self.$prop = Some(val.parse().map_err(|_| {
+                        crate::errors::ConfigurationError(
+                            format!("'{}' must be a number of pixels", stringify!($prop)),
+                            val.to_string(),
+                        )
+                    })?);
+                }
+            };
+        }
+        apply_pixels!(border_top);
+        apply_pixels!(border_right);
+        apply_pixels!(border_bottom);
+        apply_pixels!(border_left);
+        apply_pixels!(block_gap);
+        Ok(())
+    }
+}
+
+impl InternalTheme {
+    fn dim(&mut self, dimming: &NightDimming) {
+        let (brightness, saturation) = (
+            dimming.brightness.clamp(0., 1.),
+            dimming.saturation.clamp(0., 1.),
+        );
+        for color in [
+            &mut self.idle_bg,
+            &mut self.idle_fg,
+            &mut self.info_bg,
+            &mut self.info_fg,
+            &mut self.good_bg,
+            &mut self.good_fg,
+            &mut self.warning_bg,
+            &mut self.warning_fg,
+            &mut self.critical_bg,
+            &mut self.critical_fg,
+            &mut self.separator_bg,
+            &mut self.separator_fg,
+            &mut self.alternating_tint_bg,
+            &mut self.alternating_tint_fg,
+            &mut self.idle_bg_gradient,
+            &mut self.info_bg_gradient,
+            &mut self.good_bg_gradient,
+            &mut self.warning_bg_gradient,
+            &mut self.critical_bg_gradient,
+            &mut self.idle_border,
+            &mut self.info_border,
+            &mut self.good_border,
+            &mut self.warning_border,
+            &mut self.critical_border,
+        ] {
+            *color = color.dimmed(brightness, saturation);
+        }
+    }
+}
+
+/// Dims the whole theme at night, so the bar doesn't glare
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct NightDimming {
+    /// Factor of the brightness of all colors
+    #[serde(default = "NightDimming::default_brightness")]
+    pub brightness: f64,
+    /// Factor of the saturation of all colors
+    #[serde(default = "NightDimming::default_saturation")]
+    pub saturation: f64,
+    /// When it is night, i.e. not light
+    pub switch: ThemeSwitch,
+}
+
+impl NightDimming {
+    fn default_brightness() -> f64 {
+        0.7
+    }
+
+    fn default_saturation() -> f64 {
+        0.6
+    }
+}
+
+/// The colors and separators of the conditions that the `weather` block publishes
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields, default)]
+pub struct WeatherTheme {
+    /// How much of the color of the condition is mixed into the blocks with `weather_tint`
+    pub tint: f64,
+    /// Colors of the conditions, in addition to the default ones
+    pub colors: HashMap<String, Color>,
+    /// Separators of the conditions, which replace the separator of the theme
+    pub separators: HashMap<String, String>,
+}
+
+impl Default for WeatherTheme {
+    fn default() -> Self {
+        Self {
+            tint: 0.2,
+            colors: HashMap::new(),
+            separators: HashMap::new(),
+        }
+    }
+}
+
+impl WeatherTheme {
+    pub fn color(&self, condition: &str) -> Option<Color> {
+        if let Some(color) = self.colors.get(condition) {
+            return Some(*color);
+        }
+        let rgb = |r, g, b| Some(Color::Rgba(r, g, b, 0xFF));
+        match condition {
+            "sun" => rgb(0xF0, 0xC0, 0x40),
+            "clouds" => rgb(0x80, 0x90, 0xA0),
+            "rain" => rgb(0x30, 0x70, 0xC0),
+            "thunder" => rgb(0x70, 0x40, 0xA0),
+            "snow" => rgb(0xC0, 0xE0, 0xF0),
+            _ => None,
+        }
+    }
+
+    /// The separator of the current condition, if it has one
+    pub fn separator(&self) -> Option<String> {
+        if self.separators.is_empty() {
+            return None;
+        }
+        topics::with(topics::WEATHER, |condition| {
+            self.separators.get(condition).cloned()
+        })
+        .flatten()
+    }
+}
+
+/// State colors that can be told apart without distinguishing red and green, based on the palette
+/// by Okabe and Ito
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Palette {
+    Deuteranopia,
+    /// Red looks dark with protanopia, so critical is orange instead
+    Protanopia,
+}
+
+impl Palette {
+    /// Colors of info, good, warning and critical
+    fn colors(self) -> [Color; 4] {
+        let rgb = |r, g, b| Color::Rgba(r, g, b, 0xFF);
+        match self {
+            Palette::Deuteranopia => [
+                rgb(0x56, 0xB4, 0xE9),
+                rgb(0x00, 0x72, 0xB2),
+                rgb(0xE6, 0x9F, 0x00),
+                rgb(0xD5, 0x5E, 0x00),
+            ],
+            Palette::Protanopia => [
+                rgb(0x56, 0xB4, 0xE9),
+                rgb(0x00, 0x72, 0xB2),
+                rgb(0xF0, 0xE4, 0x42),
+                rgb(0xE6, 0x9F, 0x00),
+            ],
+        }
+    }
+}
+
+impl InternalTheme {
+    /// Replaces the colors of the states. Themes that color the background of the states get new
+    /// backgrounds, the others new foregrounds.
+    fn apply_palette(&mut self, palette: Palette) {
+        let [info, good, warning, critical] = palette.colors();
+        let idle_bg = self.idle_bg;
+        for (bg, fg, color) in [
+            (&mut self.info_bg, &mut self.info_fg, info),
+            (&mut self.good_bg, &mut self.good_fg, good),
+            (&mut self.warning_bg, &mut self.warning_fg, warning),
+            (&mut self.critical_bg, &mut self.critical_fg, critical),
+        ] {
+            if *bg != idle_bg {
+                *bg = color;
+                *fg = Color::Auto;
+            } else {
+                *fg = color;
+            }
+        }
+    }
+}
+
+/// Whether the light variant of the theme is currently in use
+static LIGHT: AtomicBool = AtomicBool::new(false);
+
+pub fn is_light() -> bool {
+    LIGHT.load(Ordering::Relaxed)
+}
+
+pub fn set_light(light: bool) {
+    LIGHT.store(light, Ordering::Relaxed);
+}
+
+/// Whether the theme is currently dimmed for the night
+static NIGHT: AtomicBool = AtomicBool::new(false);
+
+pub fn is_night() -> bool {
+    NIGHT.load(Ordering::Relaxed)
+}
+
+pub fn set_night(night: bool) {
+    NIGHT.store(night, Ordering::Relaxed);
+}
+
+#[derive(Debug, Clone)]
+pub struct Theme {
+    dark: InternalTheme,
+    light: Option<InternalTheme>,
+    pub switch: Option<ThemeSwitch>,
+    pub night_dimming: Option<NightDimming>,
+    pub weather: WeatherTheme,
+    /// The dimmed copies of `dark` and `light`, kept up to date with `night_dimming`
+    night: Option<(InternalTheme, Option<InternalTheme>)>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::from_file("plain").unwrap_or_else(|| Self::from(InternalTheme::default()))
+    }
+}
+
+impl From<InternalTheme> for Theme {
+    fn from(theme: InternalTheme) -> Self {
+        Self {
+            dark: theme,
+            light: None,
+            switch: None,
+            night_dimming: None,
+            weather: WeatherTheme::default(),
+            night: None,
+        }
+    }
+}
+
+impl std::ops::Deref for Theme {
+    type Target = InternalTheme;
+    fn deref(&self) -> &Self::Target {
+        let (dark, light) = match self.night {
+            Some((ref dark, ref light)) if is_night() => (dark, light),
+            _ => (&self.dark, &self.light),
+        };
+        match light {
+            Some(light) if is_light() => light,
+            _ => dark,
+        }
+    }
+}
+
+impl Theme {
+    pub fn has_light(&self) -> bool {
+        self.light.is_some()
+    }
+
+    pub fn from_file(file: &str) -> Option<Theme> {
+        let file = util::find_file(file, Some("themes"), Some("toml"))?;
+        Some(Theme::from(
+            util::deserialize_file::<InternalTheme>(&file).ok()?,
+        ))
+    }
+
+    pub fn apply_palette(&mut self, palette: Palette) {
+        self.dark.apply_palette(palette);
+        if let Some(ref mut light) = self.light {
+            light.apply_palette(palette);
+        }
+        self.update_night();
+    }
+
+    /// Overrides apply to both the dark and the light variant
+    pub fn apply_overrides(
+        &mut self,
+        overrides: &HashMap<String, String>,
+    ) -> Result<(), crate::errors::Error> {
+        self.dark.apply_overrides(overrides)?;
+        if let Some(ref mut light) = self.light {
+            light.apply_overrides(overrides)?;
+        }
+        self.update_night();
         Ok(())
     }
+
+    /// Dims the variants again, after they changed
+    fn update_night(&mut self) {
+        self.night = self.night_dimming.as_ref().map(|dimming| {
+            let mut dark = self.dark.clone();
+            dark.dim(dimming);
+            let light = self.light.clone().map(|mut light| {
+                light.dim(dimming);
+                light
+            });
+            (dark, light)
+        });
+    }
 }
 
 impl<'de> Deserialize<'de> for Theme {
@@ -207,6 +732,12 @@ impl<'de> Deserialize<'de> for Theme {
             Name,
             File,
             Overrides,
+            Dark,
+            Light,
+            Switch,
+            Palette,
+            Night,
+            Weather,
         }
 
         struct ThemeVisitor;
@@ -237,11 +768,27 @@ impl<'de> Deserialize<'de> for Theme {
             /// [theme]
             /// name = "modern"
             /// ```
+            ///
+            /// and
+            ///
+            /// ```toml
+            /// [theme]
+            /// dark = "slick"
+            /// light = "solarized-light"
+            /// switch = { source = "portal" }
+            /// night = { switch = { source = "gammastep" } }
+            /// weather = { separators = { rain = "\ue0b2" } }
+            /// ```
             fn visit_map<V>(self, mut map: V) -> Result<Theme, V::Error>
             where
                 V: MapAccess<'de>,
             {
                 let mut theme: Option<String> = None;
+                let mut light: Option<String> = None;
+                let mut switch: Option<ThemeSwitch> = None;
+                let mut palette: Option<Palette> = None;
+                let mut night_dimming: Option<NightDimming> = None;
+                let mut weather: Option<WeatherTheme> = None;
                 let mut overrides: Option<HashMap<String, String>> = None;
                 while let Some(key) = map.next_key()? {
                     match key {
@@ -258,6 +805,42 @@ impl<'de> Deserialize<'de> for Theme {
                             }
                             theme = Some(map.next_value()?);
                         }
+                        Field::Dark => {
+                            if theme.is_some() {
+                                return Err(de::Error::duplicate_field("name, file or dark"));
+                            }
+                            theme = Some(map.next_value()?);
+                        }
+                        Field::Light => {
+                            if light.is_some() {
+                                return Err(de::Error::duplicate_field("light"));
+                            }
+                            light = Some(map.next_value()?);
+                        }
+                        Field::Switch => {
+                            if switch.is_some() {
+                                return Err(de::Error::duplicate_field("switch"));
+                            }
+                            switch = Some(map.next_value()?);
+                        }
+                        Field::Palette => {
+                            if palette.is_some() {
+                                return Err(de::Error::duplicate_field("palette"));
+                            }
+                            palette = Some(map.next_value()?);
+                        }
+                        Field::Night => {
+                            if night_dimming.is_some() {
+                                return Err(de::Error::duplicate_field("night"));
+                            }
+                            night_dimming = Some(map.next_value()?);
+                        }
+                        Field::Weather => {
+                            if weather.is_some() {
+                                return Err(de::Error::duplicate_field("weather"));
+                            }
+                            weather = Some(map.next_value()?);
+                        }
                         Field::Overrides => {
                             if overrides.is_some() {
                                 return Err(de::Error::duplicate_field("overrides"));
@@ -271,6 +854,26 @@ impl<'de> Deserialize<'de> for Theme {
                 let mut theme = Theme::from_file(&theme)
                     .ok_or_else(|| de::Error::custom(format!("Theme '{}' not found.", theme)))?;
 
+                if let Some(light) = light {
+                    theme.light = Some(
+                        Theme::from_file(&light)
+                            .ok_or_else(|| {
+                                de::Error::custom(format!("Theme '{}' not found.", light))
+                            })?
+                            .dark,
+                    );
+                    // Follow the system preference by default
+                    theme.switch = Some(switch.unwrap_or(ThemeSwitch::Portal));
+                } else if switch.is_some() {
+                    return Err(de::Error::custom("'switch' requires a 'light' theme"));
+                }
+
+                theme.night_dimming = night_dimming;
+                theme.weather = weather.unwrap_or_default();
+                theme.update_night();
+                if let Some(palette) = palette {
+                    theme.apply_palette(palette);
+                }
                 if let Some(ref overrides) = overrides {
                     theme.apply_overrides(overrides).serde_error()?;
                 }
@@ -281,3 +884,99 @@ impl<'de> Deserialize<'de> for Theme {
         deserializer.deserialize_any(ThemeVisitor)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_hex() {
+        assert_eq!(
+            "#102030".parse::<Color>().unwrap(),
+            Color::Rgba(16, 32, 48, 255)
+        );
+        assert_eq!(
+            "#10203040".parse::<Color>().unwrap(),
+            Color::Rgba(16, 32, 48, 64)
+        );
+        assert!("#1020".parse::<Color>().is_err());
+    }
+
+    #[test]
+    fn parse_hsl() {
+        assert_eq!(
+            "hsl(0, 100%, 50%)".parse::<Color>().unwrap(),
+            Color::Rgba(255, 0, 0, 255)
+        );
+        assert_eq!(
+            "hsl(120deg 100% 25%)".parse::<Color>().unwrap(),
+            Color::Rgba(0, 128, 0, 255)
+        );
+        assert_eq!(
+            "hsla(240, 100%, 50%, 0.5)".parse::<Color>().unwrap(),
+            Color::Rgba(0, 0, 255, 128)
+        );
+        assert!("hsl(0, 100%)".parse::<Color>().is_err());
+    }
+
+    #[test]
+    fn parse_oklch() {
+        assert_eq!(
+            "oklch(100% 0 0)".parse::<Color>().unwrap(),
+            Color::Rgba(255, 255, 255, 255)
+        );
+        assert_eq!(
+            "oklch(0 0 0)".parse::<Color>().unwrap(),
+            Color::Rgba(0, 0, 0, 255)
+        );
+        // sRGB red is approximately oklch(62.8% 0.2577 29.23)
+        assert_eq!(
+            "oklch(62.8% 0.2577 29.23 / 50%)".parse::<Color>().unwrap(),
+            Color::Rgba(255, 0, 0, 128)
+        );
+    }
+
+    #[test]
+    fn palette() {
+        let mut plain = Theme::from(InternalTheme {
+            idle_bg: Color::Rgba(0, 0, 0, 0xFF),
+            good_bg: Color::Rgba(0, 0, 0, 0xFF),
+            good_fg: Color::Rgba(0, 0xFF, 0, 0xFF),
+            critical_bg: Color::Rgba(0xFF, 0, 0, 0xFF),
+            ..InternalTheme::default()
+        });
+        plain.apply_palette(Palette::Deuteranopia);
+        // Only the foreground of good is colored, but the background of critical
+        assert_eq!(plain.good_bg, Color::Rgba(0, 0, 0, 0xFF));
+        assert_eq!(plain.good_fg, Color::Rgba(0x00, 0x72, 0xB2, 0xFF));
+        assert_eq!(plain.critical_bg, Color::Rgba(0xD5, 0x5E, 0x00, 0xFF));
+        assert_eq!(plain.critical_fg, Color::Auto);
+    }
+
+    #[test]
+    fn contrasting() {
+        assert_eq!(
+            Color::Rgba(255, 255, 0, 255).contrasting(),
+            Color::Rgba(0, 0, 0, 255)
+        );
+        assert_eq!(
+            Color::Rgba(0, 0, 128, 255).contrasting(),
+            Color::Rgba(255, 255, 255, 255)
+        );
+        assert_eq!(Color::None.contrasting(), Color::None);
+    }
+
+    #[test]
+    fn dimmed() {
+        assert_eq!(
+            Color::Rgba(200, 100, 0, 128).dimmed(0.5, 1.),
+            Color::Rgba(100, 50, 0, 128)
+        );
+        // Without saturation only the luma is left
+        assert_eq!(
+            Color::Rgba(255, 0, 0, 255).dimmed(1., 0.),
+            Color::Rgba(76, 76, 76, 255)
+        );
+        assert_eq!(Color::Auto.dimmed(0.5, 0.5), Color::Auto);
+    }
+}
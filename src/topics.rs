@@ -0,0 +1,95 @@
+//! Conditions that blocks publish for the other blocks and the theme, like the weather outside.
+//!
+//! Unlike the values of the registry, which are read whenever the other blocks happen to update,
+//! a change of a topic updates the blocks that subscribed to it right away. The bar is drawn after
+//! the update of the publishing block anyway, so the theme reads the topics while drawing. Like
+//! the blocks, the topics live on the main thread.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::time::Instant;
+
+use crossbeam_channel::Sender;
+
+use crate::scheduler::Task;
+
+/// The condition of the `weather` block: `sun`, `clouds`, `rain`, `thunder`, `snow` or `other`
+pub const WEATHER: &str = "weather";
+
+#[derive(Default)]
+struct Topic {
+    value: Option<String>,
+    subscribers: Vec<(usize, Sender<Task>)>,
+}
+
+thread_local! {
+    static TOPICS: RefCell<HashMap<&'static str, Topic>> = RefCell::new(HashMap::new());
+}
+
+/// Sets the value of `topic` and updates the subscribers if it changed
+pub fn publish(topic: &'static str, value: &str) {
+    TOPICS.with(|topics| {
+        let mut topics = topics.borrow_mut();
+        let topic = topics.entry(topic).or_default();
+        if topic.value.as_deref() == Some(value) {
+            return;
+        }
+        topic.value = Some(value.to_string());
+        for (id, tx_update_request) in &topic.subscribers {
+            let _ = tx_update_request.send(Task {
+                id: *id,
+                update_time: Instant::now(),
+            });
+        }
+    });
+}
+
+/// Updates the block `id` whenever the value of `topic` changes
+pub fn subscribe(topic: &'static str, id: usize, tx_update_request: Sender<Task>) {
+    TOPICS.with(|topics| {
+        topics
+            .borrow_mut()
+            .entry(topic)
+            .or_default()
+            .subscribers
+            .push((id, tx_update_request));
+    });
+}
+
+/// Runs `f` with the value of `topic`, if a block published one
+pub fn with<T>(topic: &str, f: impl FnOnce(&str) -> T) -> Option<T> {
+    TOPICS.with(|topics| {
+        let topics = topics.borrow();
+        topics.get(topic)?.value.as_deref().map(f)
+    })
+}
+
+/// Removes the subscriptions of a block that was removed by a reload
+pub fn forget(id: usize) {
+    TOPICS.with(|topics| {
+        for topic in topics.borrow_mut().values_mut() {
+            topic
+                .subscribers
+                .retain(|(subscriber, _)| *subscriber != id);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn publish_and_subscribe() {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        subscribe(WEATHER, 3, tx);
+        assert_eq!(with(WEATHER, str::to_string), None);
+        publish(WEATHER, "rain");
+        publish(WEATHER, "rain");
+        assert_eq!(with(WEATHER, str::to_string), Some("rain".to_string()));
+        assert_eq!(rx.try_iter().map(|task| task.id).collect::<Vec<_>>(), [3]);
+        forget(3);
+        publish(WEATHER, "snow");
+        assert!(rx.try_recv().is_err());
+    }
+}
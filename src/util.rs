@@ -1,14 +1,20 @@
 use std::fs::{File, OpenOptions};
 use std::io::prelude::*;
 use std::io::BufReader;
+use std::os::unix::io::AsRawFd;
 use std::path::{Path, PathBuf};
 use std::prelude::v1::String;
-use std::process::Command;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
+use inotify::{Events, Inotify};
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags};
 use serde::de::DeserializeOwned;
 
 use crate::errors::*;
+use crate::mock;
+use crate::registry;
+use crate::subprocess;
 
 pub const USR_SHARE_PATH: &str = "/usr/share/i3status-rust";
 
@@ -71,6 +77,24 @@ pub fn find_file(file: &str, subdir: Option<&str>, extension: Option<&str>) -> O
     None
 }
 
+/// Like `Inotify::read_events_blocking`, but sends heartbeats of the block `id` while it waits, so
+/// the watchdog knows that the thread still runs
+pub fn read_inotify_events<'a>(
+    id: usize,
+    notify: &mut Inotify,
+    buffer: &'a mut [u8],
+) -> std::io::Result<Events<'a>> {
+    loop {
+        registry::heartbeat(id);
+        let mut fds = [PollFd::new(notify.as_raw_fd(), PollFlags::POLLIN)];
+        match poll(&mut fds, registry::HEARTBEAT_INTERVAL.as_millis() as i32) {
+            Ok(0) | Err(Errno::EINTR) => continue,
+            Ok(_) => return notify.read_events(buffer),
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
 pub fn escape_pango_text(text: &str) -> String {
     text.chars()
         .map(|x| match x {
@@ -83,6 +107,11 @@ pub fn escape_pango_text(text: &str) -> String {
         .collect()
 }
 
+/// Quotes `word` as a single argument for `sh`, so it is never expanded or split
+pub fn shell_quote(word: &str) -> String {
+    format!("'{}'", word.replace('\'', r"'\''"))
+}
+
 pub fn battery_level_to_icon(charge_level: Result<u64>, fallback_icons: bool) -> &'static str {
     // TODO remove fallback in next release
     if fallback_icons {
@@ -133,6 +162,10 @@ where
 }
 
 pub fn read_file(blockname: &str, path: &Path) -> Result<String> {
+    if let Some(content) = mock::file(path) {
+        let content = content?;
+        return Ok(content.strip_suffix('\n').unwrap_or(&content).to_string());
+    }
     let mut f = OpenOptions::new().read(true).open(path).block_error(
         blockname,
         &format!("failed to open file {}", path.to_string_lossy()),
@@ -148,7 +181,7 @@ pub fn read_file(blockname: &str, path: &Path) -> Result<String> {
 }
 
 pub fn has_command(block_name: &str, command: &str) -> Result<bool> {
-    let exit_status = Command::new("sh")
+    let exit_status = subprocess::command("sh")
         .args(&[
             "-c",
             format!("command -v {} >/dev/null 2>&1", command).as_ref(),
@@ -230,7 +263,8 @@ pub fn country_flag_from_iso_code(country_code: &str) -> String {
 
 #[cfg(test)]
 mod tests {
-    use crate::util::{country_flag_from_iso_code, has_command};
+    use crate::registry;
+    use crate::util::{country_flag_from_iso_code, has_command, read_inotify_events, shell_quote};
 
     #[test]
     // we assume sh is always available
@@ -256,4 +290,36 @@ mod tests {
         assert!(country_flag_from_iso_code("US") == "🇺🇸");
         assert!(country_flag_from_iso_code("USA") == "USA");
     }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("/it's"), r"'/it'\''s'");
+        assert_eq!(shell_quote("$(rm -rf ~)"), "'$(rm -rf ~)'");
+    }
+
+    #[test]
+    fn test_inotify_heartbeats() {
+        let path = std::env::temp_dir().join(format!("i3rs-heartbeat-{}", std::process::id()));
+        std::fs::write(&path, "").unwrap();
+        let mut notify = inotify::Inotify::init().unwrap();
+        notify.add_watch(&path, inotify::WatchMask::MODIFY).unwrap();
+        let id = usize::MAX - 7;
+        let writer = {
+            let path = path.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(registry::HEARTBEAT_INTERVAL * 3 / 2);
+                std::fs::write(path, "changed").unwrap();
+            })
+        };
+        let started = std::time::Instant::now();
+        let mut buffer = [0; 1024];
+        let mut events = read_inotify_events(id, &mut notify, &mut buffer).unwrap();
+        assert!(events.any(|event| event.mask.contains(inotify::EventMask::MODIFY)));
+        // The thread sent heartbeats while it waited for the change
+        assert!(registry::last_heartbeat(id).unwrap() > started);
+        writer.join().unwrap();
+        registry::forget(id);
+        assert!(registry::last_heartbeat(id).is_none());
+        std::fs::remove_file(path).unwrap();
+    }
 }
@@ -2,14 +2,70 @@ pub mod rotatingtext;
 pub mod text;
 
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
 use serde::de::value::{Error, StrDeserializer};
 use serde::de::{Deserialize, IntoDeserializer};
 use serde_derive::Deserialize;
 
+use crate::config::SharedConfig;
+use crate::errors;
 use crate::protocol::i3bar_block::I3BarBlock;
 use crate::themes::{Color, Theme};
 
+/// Set by `i3status-rs compact`, widgets with an icon show only the icon
+static COMPACT: AtomicBool = AtomicBool::new(false);
+
+pub fn is_compact() -> bool {
+    COMPACT.load(Ordering::Relaxed)
+}
+
+pub fn set_compact(compact: bool) {
+    COMPACT.store(compact, Ordering::Relaxed);
+}
+
+/// Replaces the text of a widget with its icon in compact mode. Widgets without an icon keep their
+/// text, so they don't disappear.
+fn apply_compact(icon: Option<&str>, block: &mut I3BarBlock) {
+    if let Some(icon) = icon.filter(|icon| !icon.trim().is_empty() && is_compact()) {
+        block.full_text.clear();
+        block.full_text.push_str(icon);
+        block.short_text = None;
+        block.min_width = None;
+    }
+}
+
+/// The icon of a widget, which is looked up again after a reload replaced the icons
+#[derive(Debug, Clone)]
+struct Icon {
+    name: String,
+    text: String,
+    generation: usize,
+}
+
+impl Icon {
+    fn new(name: &str, shared_config: &SharedConfig) -> errors::Result<Self> {
+        Ok(Icon {
+            name: name.to_string(),
+            text: shared_config.get_icon(name)?,
+            generation: shared_config.style_generation(),
+        })
+    }
+
+    fn refresh(&mut self, shared_config: &SharedConfig) {
+        let generation = shared_config.style_generation();
+        if generation != self.generation {
+            // Icons can't be removed by the configuration, so this keeps the old one only for
+            // names that never existed
+            if let Ok(text) = shared_config.get_icon(&self.name) {
+                self.text = text;
+            }
+            self.generation = generation;
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, Deserialize)]
 pub enum Spacing {
     /// Add a leading and trailing space around the widget contents
@@ -29,22 +85,22 @@ impl Spacing {
         }
     }
 
-    pub fn to_string_leading(self) -> String {
+    pub fn leading(self) -> &'static str {
         match self {
-            Self::Normal => String::from(" "),
-            _ => String::from(""),
+            Self::Normal => " ",
+            _ => "",
         }
     }
 
-    pub fn to_string_trailing(self) -> String {
+    pub fn trailing(self) -> &'static str {
         match self {
-            Self::Hidden => String::from(""),
-            _ => String::from(" "),
+            Self::Hidden => "",
+            _ => " ",
         }
     }
 }
 
-#[derive(Debug, Copy, Clone, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
 pub enum State {
     Idle,
     Info,
@@ -56,14 +112,116 @@ pub enum State {
 impl State {
     pub fn theme_keys(self, theme: &Theme) -> (Color, Color) {
         use self::State::*;
-        match self {
+        let (bg, fg) = match self {
             Idle => (theme.idle_bg, theme.idle_fg),
             Info => (theme.info_bg, theme.info_fg),
             Good => (theme.good_bg, theme.good_fg),
             Warning => (theme.warning_bg, theme.warning_fg),
             Critical => (theme.critical_bg, theme.critical_fg),
+        };
+        // "auto" foreground picks black or white, whichever is more readable on the background
+        let fg = if fg == Color::Auto {
+            bg.contrasting()
+        } else {
+            fg
+        };
+        (bg, fg)
+    }
+
+    /// Like `theme_keys`, but the colors of critical widgets are swapped while they flash.
+    /// `since` is the time the widget entered this state.
+    pub fn theme_keys_since(self, theme: &Theme, since: Instant) -> (Color, Color) {
+        let (bg, fg) = self.theme_keys(theme);
+        match self.flash(theme, since) {
+            Some((true, _)) => (fg, bg),
+            _ => (bg, fg),
+        }
+    }
+
+    /// Applies the underline, the suffix and the border of the state to a widget
+    pub fn apply_style(self, theme: &Theme, block: &mut I3BarBlock) {
+        use self::State::*;
+        let (underline, suffix, border) = match self {
+            Idle => (&theme.idle_underline, &theme.idle_suffix, theme.idle_border),
+            Info => (&theme.info_underline, &theme.info_suffix, theme.info_border),
+            Good => (&theme.good_underline, &theme.good_suffix, theme.good_border),
+            Warning => (
+                &theme.warning_underline,
+                &theme.warning_suffix,
+                theme.warning_border,
+            ),
+            Critical => (
+                &theme.critical_underline,
+                &theme.critical_suffix,
+                theme.critical_border,
+            ),
+        };
+        let border = if border == Color::Auto {
+            block.color
+        } else {
+            border
+        };
+        if let Some(border) = border.to_string() {
+            block.border = Some(border);
+            block.border_top = theme.border_top;
+            block.border_right = theme.border_right;
+            block.border_bottom = theme.border_bottom;
+            block.border_left = theme.border_left;
+        }
+        let pango = block.markup.as_deref() == Some("pango");
+        let style = |text: &str| {
+            // The suffix goes before the trailing spacing of the widget
+            let content = text.trim_end();
+            if content.trim_start().is_empty() {
+                // Don't show empty widgets
+                return text.to_string();
+            }
+            let mut styled = format!("{}{}", content, suffix.as_deref().unwrap_or_default());
+            if let (Some(underline), true) = (underline, pango) {
+                styled = format!("<span underline='{}'>{}</span>", underline, styled);
+            }
+            styled + &text[content.len()..]
+        };
+        if underline.is_some() || suffix.is_some() {
+            block.full_text = style(&block.full_text);
+            block.short_text = block.short_text.as_deref().map(style);
+        }
+    }
+
+    pub fn theme_gradient(self, theme: &Theme) -> Color {
+        use self::State::*;
+        match self {
+            Idle => theme.idle_bg_gradient,
+            Info => theme.info_bg_gradient,
+            Good => theme.good_bg_gradient,
+            Warning => theme.warning_bg_gradient,
+            Critical => theme.critical_bg_gradient,
         }
     }
+
+    /// Time until the colors of a flashing widget change next
+    pub fn next_flash(self, theme: &Theme, since: Instant) -> Option<Duration> {
+        self.flash(theme, since).map(|(_, next)| next)
+    }
+
+    /// Whether the colors are swapped right now and the time until that changes
+    fn flash(self, theme: &Theme, since: Instant) -> Option<(bool, Duration)> {
+        if self != State::Critical {
+            return None;
+        }
+        let interval = theme.critical_flash_interval.filter(|i| *i > 0.)?;
+        let elapsed = since.elapsed().as_secs_f64();
+        let mut next = interval - elapsed % interval;
+        if theme.critical_flash_duration > 0. {
+            let remaining = theme.critical_flash_duration - elapsed;
+            if remaining <= 0. {
+                return None;
+            }
+            next = next.min(remaining);
+        }
+        let swapped = (elapsed / interval) as u64 % 2 == 1;
+        Some((swapped, Duration::from_secs_f64(next)))
+    }
 }
 
 impl FromStr for State {
@@ -76,5 +234,27 @@ impl FromStr for State {
 }
 
 pub trait I3BarWidget {
-    fn get_data(&self) -> I3BarBlock;
+    /// Writes the widget into `data`. The bar is redrawn often, so this reuses the strings of
+    /// `data` from the last redraw.
+    fn write_data(&self, data: &mut I3BarBlock);
+
+    fn get_data(&self) -> I3BarBlock {
+        let mut data = I3BarBlock::default();
+        self.write_data(&mut data);
+        data
+    }
+
+    fn state(&self) -> State {
+        State::Idle
+    }
+
+    /// Time until the widget has to be redrawn because it is flashing
+    fn next_flash(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Whether a rule of the block hides the widget
+    fn is_hidden(&self) -> bool {
+        false
+    }
 }
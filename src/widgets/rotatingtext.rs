@@ -1,8 +1,9 @@
 use std::time::{Duration, Instant};
 
-use super::{I3BarWidget, Spacing, State};
+use super::{apply_compact, I3BarWidget, Icon, Spacing, State};
 use crate::config::SharedConfig;
 use crate::errors::*;
+use crate::formatting::width;
 use crate::protocol::i3bar_block::{I3BarBlock, I3BarBlockMinWidth};
 use crate::util::escape_pango_text;
 
@@ -17,8 +18,10 @@ pub struct RotatingTextWidget {
     rotation_speed: Duration,
     next_rotation: Option<Instant>,
     content: String,
-    icon: Option<String>,
+    icon: Option<Icon>,
     state: State,
+    /// When the widget entered `state`
+    state_since: Instant,
     spacing: Spacing,
     shared_config: SharedConfig,
     inner: I3BarBlock,
@@ -52,6 +55,7 @@ impl RotatingTextWidget {
             content: String::new(),
             icon: None,
             state: State::Idle,
+            state_since: Instant::now(),
             spacing: Spacing::Normal,
             //cached_output: None,
             shared_config,
@@ -62,14 +66,13 @@ impl RotatingTextWidget {
     }
 
     pub fn with_icon(mut self, name: &str) -> Result<Self> {
-        self.icon = Some(self.shared_config.get_icon(name)?);
+        self.icon = Some(Icon::new(name, &self.shared_config)?);
         self.update();
         Ok(self)
     }
 
     pub fn with_state(mut self, state: State) -> Self {
-        self.state = state;
-        self.update();
+        self.set_state(state);
         self
     }
 
@@ -82,7 +85,7 @@ impl RotatingTextWidget {
     pub fn with_text(mut self, content: &str) -> Self {
         self.content = String::from(content);
         self.rotation_pos = 0;
-        if self.content.chars().count() > self.max_width {
+        if width::str_width(&self.content) > self.max_width {
             self.next_rotation = Some(Instant::now() + self.rotation_interval);
         } else {
             self.next_rotation = None;
@@ -92,12 +95,15 @@ impl RotatingTextWidget {
     }
 
     pub fn set_state(&mut self, state: State) {
+        if state != self.state {
+            self.state_since = Instant::now();
+        }
         self.state = state;
         self.update();
     }
 
     pub fn set_icon(&mut self, name: &str) -> Result<()> {
-        self.icon = Some(self.shared_config.get_icon(name)?);
+        self.icon = Some(Icon::new(name, &self.shared_config)?);
         self.update();
         Ok(())
     }
@@ -106,7 +112,7 @@ impl RotatingTextWidget {
         if self.content != content {
             self.content = content;
             self.rotation_pos = 0;
-            if self.content.chars().count() > self.max_width {
+            if width::str_width(&self.content) > self.max_width {
                 self.next_rotation = Some(Instant::now() + self.rotation_interval);
             } else {
                 self.next_rotation = None;
@@ -120,38 +126,48 @@ impl RotatingTextWidget {
     }
 
     fn get_rotated_content(&self) -> String {
-        if self.content.chars().count() > self.max_width {
-            let missing =
-                (self.rotation_pos + self.max_width).saturating_sub(self.content.chars().count());
-            if missing == 0 {
-                self.content
-                    .chars()
-                    .skip(self.rotation_pos)
-                    .take(self.max_width)
-                    .collect()
-            } else {
-                let mut avail: String = self
-                    .content
-                    .chars()
-                    .skip(self.rotation_pos)
-                    .take(self.max_width)
-                    .collect();
-                avail.push('|');
-                avail.push_str(&self.content.chars().take(missing - 1).collect::<String>());
-                avail
+        if width::str_width(&self.content) > self.max_width {
+            // The content continues after a separator, until `max_width` columns are filled
+            let mut avail = String::new();
+            let mut avail_width = 0;
+            let chars = self
+                .content
+                .chars()
+                .skip(self.rotation_pos)
+                .chain(std::iter::once('|'))
+                .chain(self.content.chars());
+            for c in chars {
+                let char_width = width::char_width(c);
+                if avail_width + char_width > self.max_width {
+                    break;
+                }
+                avail.push(c);
+                avail_width += char_width;
+            }
+            // A wide character that doesn't fit anymore would make the widget jump
+            for _ in avail_width..self.max_width {
+                avail.push(' ');
             }
+            avail
         } else {
             self.content.clone()
         }
     }
 
     fn update(&mut self) {
-        let (key_bg, key_fg) = self.state.theme_keys(&self.shared_config.theme);
+        let (key_bg, key_fg) = self.state.theme_keys(&self.shared_config.theme());
+        if let Some(icon) = &mut self.icon {
+            icon.refresh(&self.shared_config);
+        }
 
-        let mut icon = self.icon.clone().unwrap_or_else(|| match self.spacing {
-            Spacing::Normal => String::from(" "),
-            _ => String::from(""),
-        });
+        let mut icon = self
+            .icon
+            .as_ref()
+            .map(|icon| icon.text.clone())
+            .unwrap_or_else(|| match self.spacing {
+                Spacing::Normal => String::from(" "),
+                _ => String::from(""),
+            });
 
         self.inner.full_text = format!(
             "{}{}{}",
@@ -203,7 +219,25 @@ impl RotatingTextWidget {
 }
 
 impl I3BarWidget for RotatingTextWidget {
-    fn get_data(&self) -> I3BarBlock {
-        self.inner.clone()
+    fn write_data(&self, data: &mut I3BarBlock) {
+        // The colors are looked up again because the theme may have been switched or the
+        // widget may be flashing
+        let theme = &*self.shared_config.theme();
+        let (key_bg, key_fg) = self.state.theme_keys_since(theme, self.state_since);
+        data.copy_from(&self.inner);
+        data.background = key_bg;
+        data.background_gradient = self.state.theme_gradient(theme);
+        data.color = key_fg;
+        apply_compact(self.icon.as_ref().map(|icon| icon.text.as_str()), data);
+        self.state.apply_style(theme, data);
+    }
+
+    fn state(&self) -> State {
+        self.state
+    }
+
+    fn next_flash(&self) -> Option<Duration> {
+        self.state
+            .next_flash(&self.shared_config.theme(), self.state_since)
     }
 }
@@ -1,24 +1,37 @@
-use super::{I3BarWidget, Spacing, State};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use super::{apply_compact, I3BarWidget, Icon, Spacing, State};
 use crate::config::SharedConfig;
 use crate::errors::*;
-use crate::protocol::i3bar_block::I3BarBlock;
+use crate::formatting::value::Value;
+use crate::formatting::{FormatMapKey, FormatTemplate};
+use crate::protocol::i3bar_block::{I3BarBlock, I3BarBlockMinWidth};
+use crate::rules::{self, Effect};
+use crate::themes::Color;
 
 #[derive(Clone, Debug)]
 pub struct TextWidget {
     pub instance: usize,
-    content: Option<String>,
+    content: String,
     content_short: Option<String>,
-    icon: Option<String>,
+    icon: Option<Icon>,
     state: State,
+    /// When the widget entered `state`
+    state_since: Instant,
     spacing: Spacing,
     spacing_short: Spacing,
+    /// A color and how much of it is mixed into the colors of the state
+    tint: Option<(Color, f64)>,
+    /// What the rule that matched the rendering of the content changes
+    rule: Option<Effect>,
     shared_config: SharedConfig,
     inner: I3BarBlock,
 }
 
 impl TextWidget {
     pub fn new(id: usize, instance: usize, shared_config: SharedConfig) -> Self {
-        let (key_bg, key_fg) = State::Idle.theme_keys(&shared_config.theme); // Initial colors
+        let (key_bg, key_fg) = State::Idle.theme_keys(&shared_config.theme()); // Initial colors
         let inner = I3BarBlock {
             name: Some(id.to_string()),
             instance: Some(instance.to_string()),
@@ -29,32 +42,34 @@ impl TextWidget {
 
         TextWidget {
             instance,
-            content: None,
+            content: String::new(),
             content_short: None,
             icon: None,
             state: State::Idle,
+            state_since: Instant::now(),
             spacing: Spacing::Normal,
             spacing_short: Spacing::Normal,
+            tint: None,
+            rule: None,
             shared_config,
             inner,
         }
     }
 
     pub fn with_icon(mut self, name: &str) -> Result<Self> {
-        self.icon = Some(self.shared_config.get_icon(name)?);
+        self.icon = Some(Icon::new(name, &self.shared_config)?);
         self.update();
         Ok(self)
     }
 
     pub fn with_text(mut self, content: &str) -> Self {
-        self.content = Some(String::from(content));
+        self.content = String::from(content);
         self.update();
         self
     }
 
     pub fn with_state(mut self, state: State) -> Self {
-        self.state = state;
-        self.update();
+        self.set_state(state);
         self
     }
 
@@ -65,12 +80,25 @@ impl TextWidget {
         self
     }
 
+    /// Tells the bar to keep the widget at least this wide, e.g. so the positions of clicks don't
+    /// depend on the text
+    pub fn with_min_width(mut self, min_width: Option<I3BarBlockMinWidth>) -> Self {
+        self.inner.min_width = min_width;
+        self
+    }
+
     pub fn set_icon(&mut self, name: &str) -> Result<()> {
-        self.icon = Some(self.shared_config.get_icon(name)?);
+        self.icon = Some(Icon::new(name, &self.shared_config)?);
         self.update();
         Ok(())
     }
 
+    /// Mixes `amount` of `color` into the background, or into the text if the theme has no
+    /// backgrounds
+    pub fn set_tint(&mut self, tint: Option<(Color, f64)>) {
+        self.tint = tint;
+    }
+
     pub fn unset_icon(&mut self) {
         self.icon = None;
         self.update();
@@ -81,18 +109,39 @@ impl TextWidget {
     }
 
     pub fn set_texts(&mut self, contents: (String, Option<String>)) {
-        self.spacing = Spacing::from_content(&contents.0);
-        self.spacing_short = if let Some(ref short) = contents.1 {
+        self.content = contents.0;
+        self.content_short = contents.1;
+        self.rule = rules::take_effect();
+        self.update_spacing();
+    }
+
+    /// Renders `format` into the texts of the widget, reusing their buffers. This is what blocks
+    /// that update often should use instead of `set_texts(format.render(..))`.
+    pub fn set_format(
+        &mut self,
+        format: &FormatTemplate,
+        vars: &HashMap<impl FormatMapKey, Value>,
+    ) -> Result<()> {
+        format.render_into(vars, &mut self.content, &mut self.content_short)?;
+        self.rule = rules::take_effect();
+        self.update_spacing();
+        Ok(())
+    }
+
+    fn update_spacing(&mut self) {
+        self.spacing = Spacing::from_content(&self.content);
+        self.spacing_short = if let Some(ref short) = self.content_short {
             Spacing::from_content(short)
         } else {
             self.spacing
         };
-        self.content = Some(contents.0);
-        self.content_short = contents.1;
         self.update();
     }
 
     pub fn set_state(&mut self, state: State) {
+        if state != self.state {
+            self.state_since = Instant::now();
+        }
         self.state = state;
         self.update();
     }
@@ -102,33 +151,93 @@ impl TextWidget {
         self.update();
     }
 
-    fn format_text(&self, content: String, spacing: Spacing) -> String {
-        format!(
-            "{}{}{}",
-            self.icon
-                .clone()
-                .unwrap_or_else(|| spacing.to_string_leading()),
-            content,
-            spacing.to_string_trailing()
-        )
+    /// Writes the icon, the content and the spacing to `text`, which keeps its buffer
+    fn format_text(text: &mut String, icon: Option<&str>, content: &str, spacing: Spacing) {
+        text.clear();
+        text.push_str(icon.unwrap_or_else(|| spacing.leading()));
+        text.push_str(content);
+        text.push_str(spacing.trailing());
+    }
+
+    /// The state of the block, unless a rule replaces it
+    fn effective_state(&self) -> State {
+        match &self.rule {
+            Some(Effect {
+                state: Some(state), ..
+            }) => *state,
+            _ => self.state,
+        }
+    }
+
+    fn effective_icon(&self) -> Option<&str> {
+        match &self.rule {
+            Some(Effect {
+                icon: Some(icon), ..
+            }) => Some(icon),
+            _ => self.icon.as_ref().map(|icon| icon.text.as_str()),
+        }
     }
 
     fn update(&mut self) {
-        let (key_bg, key_fg) = self.state.theme_keys(&self.shared_config.theme);
+        let (key_bg, key_fg) = self
+            .effective_state()
+            .theme_keys(&self.shared_config.theme());
+        if let Some(icon) = &mut self.icon {
+            icon.refresh(&self.shared_config);
+        }
 
-        self.inner.full_text =
-            self.format_text(self.content.clone().unwrap_or_default(), self.spacing);
-        self.inner.short_text = match &self.content_short {
-            Some(text) => Some(self.format_text(text.clone(), self.spacing_short)),
-            _ => None,
+        let icon = match &self.rule {
+            Some(Effect {
+                icon: Some(icon), ..
+            }) => Some(icon.as_str()),
+            _ => self.icon.as_ref().map(|icon| icon.text.as_str()),
         };
+        Self::format_text(&mut self.inner.full_text, icon, &self.content, self.spacing);
+        match &self.content_short {
+            Some(content) => Self::format_text(
+                self.inner.short_text.get_or_insert_with(String::new),
+                icon,
+                content,
+                self.spacing_short,
+            ),
+            None => self.inner.short_text = None,
+        }
         self.inner.background = key_bg;
         self.inner.color = key_fg;
     }
 }
 
 impl I3BarWidget for TextWidget {
-    fn get_data(&self) -> I3BarBlock {
-        self.inner.clone()
+    fn write_data(&self, data: &mut I3BarBlock) {
+        // The colors are looked up again because the theme may have been switched or the
+        // widget may be flashing
+        let theme = &*self.shared_config.theme();
+        let state = self.effective_state();
+        let (key_bg, key_fg) = state.theme_keys_since(theme, self.state_since);
+        data.copy_from(&self.inner);
+        data.background = key_bg;
+        data.background_gradient = state.theme_gradient(theme);
+        data.color = key_fg;
+        if let Some((color, amount)) = self.tint {
+            match data.background {
+                Color::Rgba(..) => data.background = data.background.mix(color, amount),
+                _ => data.color = data.color.mix(color, amount),
+            }
+        }
+        apply_compact(self.effective_icon(), data);
+        state.apply_style(theme, data);
+    }
+
+    fn state(&self) -> State {
+        self.effective_state()
+    }
+
+    fn next_flash(&self) -> Option<Duration> {
+        self.effective_state()
+            .next_flash(&self.shared_config.theme(), self.state_since)
+    }
+
+    fn is_hidden(&self) -> bool {
+        matches!(self.rule, Some(Effect { hide: true, .. }))
     }
 }